@@ -0,0 +1,80 @@
+//! Batch serialization and parsing of Ethernet frames against one call
+//! per frame, for 100-frame batches of mixed sizes.
+
+use std::hint::black_box;
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use ethercrafter::ethernet::{ETHERTYPE_IPV4, Ethernet, MacAddr};
+
+const BATCH: usize = 100;
+
+fn frames() -> Vec<Ethernet> {
+    (0..BATCH)
+        .map(|i| {
+            Ethernet::new(
+                MacAddr([0x02, 0, 0, 0, 0, 1]),
+                MacAddr([0x02, 0, 0, 0, 0, 2]),
+                ETHERTYPE_IPV4,
+                vec![i as u8; 46 + i * 14],
+            )
+        })
+        .collect()
+}
+
+fn serialize(c: &mut Criterion) {
+    let frames = frames();
+    let mut group = c.benchmark_group("serialize");
+    group.throughput(Throughput::Elements(BATCH as u64));
+    group.bench_function("per_frame", |b| {
+        b.iter(|| {
+            let mut output = Vec::new();
+            for frame in black_box(&frames) {
+                output.extend_from_slice(&frame.to_bytes());
+            }
+            output
+        })
+    });
+    group.bench_function("batch", |b| {
+        b.iter(|| {
+            let mut output = Vec::new();
+            Ethernet::batch_serialize(black_box(&frames), &mut output);
+            output
+        })
+    });
+    group.finish();
+}
+
+fn parse(c: &mut Criterion) {
+    let frames = frames();
+    let lengths: Vec<usize> = frames.iter().map(Ethernet::len).collect();
+    let mut buf = Vec::new();
+    Ethernet::batch_serialize(&frames, &mut buf);
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Elements(BATCH as u64));
+    group.bench_function("per_frame", |b| {
+        b.iter(|| {
+            let mut parsed = Vec::new();
+            let mut offset = 0;
+            for len in black_box(&lengths) {
+                parsed.push(Ethernet::from_bytes(&buf[offset..offset + len]).unwrap());
+                offset += len;
+            }
+            parsed
+        })
+    });
+    group.bench_function("batch", |b| {
+        b.iter(|| Ethernet::batch_parse(black_box(&buf), black_box(&lengths)).unwrap())
+    });
+    // Batch after batch into the same vector, as a receive loop would.
+    group.bench_function("batch_into", |b| {
+        let mut parsed = Vec::new();
+        b.iter(|| {
+            Ethernet::batch_parse_into(black_box(&buf), black_box(&lengths), &mut parsed).unwrap();
+            parsed.len()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, serialize, parse);
+criterion_main!(benches);
@@ -0,0 +1,109 @@
+//! Receiving a flood of small frames on an AF_PACKET socket: one `recv`
+//! and one `Vec` per frame against `recv_into`, which fills a ring of
+//! caller buffers with `recvmmsg`, 64 frames per system call.
+//!
+//! Needs CAP_NET_RAW. Floods `ETHERCRAFTER_IFACE`, `lo` by default;
+//! skipped when the sockets cannot be created. Each round sends `FLOOD`
+//! frames, untimed, then times receiving them; on `lo` every frame is
+//! received twice, going out and coming back in.
+
+use std::env;
+use std::io;
+use std::time::{Duration, Instant};
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use ethercrafter::filter::Filter;
+use ethercrafter::transport::{RawReceiver, RawSender, RawSocket, RecvMeta};
+
+/// Frames sent per round: few enough for the receive buffer to keep them.
+const FLOOD: usize = 100;
+const BATCH: usize = 64;
+const SLOT: usize = 2048;
+
+fn flood(sender: &RawSocket, frame: &[u8]) {
+    let frames = vec![frame; FLOOD];
+    let mut sent = 0;
+    while sent < FLOOD {
+        sent += sender.send_batch(&frames[sent..]).unwrap();
+    }
+}
+
+/// Receives until the socket is drained, returning the number of frames.
+fn drain_allocating(receiver: &RawSocket) -> usize {
+    let mut buf = vec![0; SLOT];
+    let mut frames = Vec::new();
+    loop {
+        match receiver.recv(&mut buf) {
+            Ok(len) => frames.push(buf[..len].to_vec()),
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => return frames.len(),
+            Err(err) => panic!("recv failed: {err}"),
+        }
+    }
+}
+
+fn drain_batched(receiver: &RawSocket, bufs: &mut [&mut [u8]], meta: &mut [RecvMeta]) -> usize {
+    let mut received = 0;
+    loop {
+        match receiver.recv_into(bufs, meta) {
+            Ok(count) => received += count,
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => return received,
+            Err(err) => panic!("recvmmsg failed: {err}"),
+        }
+    }
+}
+
+fn receive(c: &mut Criterion) {
+    let iface = env::var("ETHERCRAFTER_IFACE").unwrap_or_else(|_| "lo".to_string());
+    let (sender, receiver) = match (RawSocket::new(&iface), RawSocket::new(&iface)) {
+        (Ok(sender), Ok(receiver)) => (sender, receiver),
+        (Err(err), _) | (_, Err(err)) => {
+            eprintln!("cannot open an AF_PACKET socket on {iface}: {err}, skipping");
+            return;
+        }
+    };
+    receiver
+        .set_filter(&Filter::parse("ether proto 0x88b5").unwrap())
+        .unwrap();
+    receiver.set_nonblocking(true).unwrap();
+    let mut frame = vec![0xff; 6];
+    frame.extend([0x02, 0, 0, 0, 0, 0x01, 0x88, 0xb5]);
+    frame.resize(64, 0);
+
+    drain_allocating(&receiver);
+    flood(&sender, &frame);
+    let per_round = drain_allocating(&receiver);
+    let mut storage = vec![0; BATCH * SLOT];
+    let mut bufs: Vec<&mut [u8]> = storage.chunks_mut(SLOT).collect();
+    let mut meta = [RecvMeta::default(); BATCH];
+
+    let mut group = c.benchmark_group("recv");
+    group.throughput(Throughput::Elements(per_round as u64));
+    group.bench_function("recv_to_vec", |b| {
+        b.iter_custom(|rounds| {
+            let mut elapsed = Duration::ZERO;
+            for _ in 0..rounds {
+                flood(&sender, &frame);
+                let start = Instant::now();
+                assert_eq!(drain_allocating(&receiver), per_round);
+                elapsed += start.elapsed();
+            }
+            elapsed
+        })
+    });
+    group.bench_function("recv_into", |b| {
+        b.iter_custom(|rounds| {
+            let mut elapsed = Duration::ZERO;
+            for _ in 0..rounds {
+                flood(&sender, &frame);
+                let start = Instant::now();
+                assert_eq!(drain_batched(&receiver, &mut bufs, &mut meta), per_round);
+                elapsed += start.elapsed();
+            }
+            elapsed
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, receive);
+criterion_main!(benches);
@@ -0,0 +1,48 @@
+//! `bulk_inject` over an AF_PACKET socket: all 4094 VLANs back to back,
+//! and paced to 100 000 frames per second, where the measured rate should
+//! stay at the target.
+//!
+//! Needs CAP_NET_RAW. Sends on `ETHERCRAFTER_IFACE`, `lo` by default;
+//! skipped when the socket cannot be created.
+
+use std::env;
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use ethercrafter::ethernet::{Ethernet, MacAddr};
+use ethercrafter::transport::RawSocket;
+use ethercrafter::vlan::{MAX_VID, MIN_VID, VlanRange, bulk_inject};
+
+const TARGET_PPS: u64 = 100_000;
+
+fn inject(c: &mut Criterion) {
+    let iface = env::var("ETHERCRAFTER_IFACE").unwrap_or_else(|_| "lo".to_string());
+    let socket = match RawSocket::new(&iface) {
+        Ok(socket) => socket,
+        Err(err) => {
+            eprintln!("cannot open an AF_PACKET socket on {iface}: {err}, skipping");
+            return;
+        }
+    };
+    let inner = Ethernet::new(
+        MacAddr([0xff; 6]),
+        MacAddr([0x02, 0, 0, 0, 0, 1]),
+        0x88b5,
+        vec![0; 46],
+    )
+    .to_bytes();
+    let range = VlanRange::new(MIN_VID, MAX_VID).unwrap();
+
+    let mut group = c.benchmark_group("bulk_inject");
+    group.throughput(Throughput::Elements(range.len() as u64));
+    group.sample_size(20);
+    group.bench_function("unpaced", |b| {
+        b.iter(|| bulk_inject(&socket, &inner, &range, 0).unwrap())
+    });
+    group.bench_function(format!("paced_{TARGET_PPS}pps"), |b| {
+        b.iter(|| bulk_inject(&socket, &inner, &range, TARGET_PPS).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, inject);
+criterion_main!(benches);
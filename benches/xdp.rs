@@ -0,0 +1,63 @@
+//! AF_XDP transmit rate for 64-byte frames, in bursts of 64.
+//!
+//! Needs CAP_NET_RAW and an interface to send on, given by
+//! `ETHERCRAFTER_XDP_IFACE` (queue `ETHERCRAFTER_XDP_QUEUE`, default 0).
+//! Skipped when the variable is unset or the socket cannot be created.
+
+use std::env;
+use std::hint::black_box;
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use ethercrafter::ethernet::{ETHERTYPE_IPV4, Ethernet, MacAddr};
+use ethercrafter::xdp::{XdpError, XdpSocket};
+
+const BURST: usize = 64;
+const UMEM_SIZE: usize = 16 << 20;
+
+fn send(c: &mut Criterion) {
+    let Ok(iface) = env::var("ETHERCRAFTER_XDP_IFACE") else {
+        eprintln!("ETHERCRAFTER_XDP_IFACE is not set, skipping the AF_XDP benchmark");
+        return;
+    };
+    let queue_id = env::var("ETHERCRAFTER_XDP_QUEUE")
+        .ok()
+        .and_then(|queue| queue.parse().ok())
+        .unwrap_or(0);
+    let mut socket = match XdpSocket::new(&iface, queue_id, UMEM_SIZE) {
+        Ok(socket) => socket,
+        Err(err) => {
+            eprintln!("cannot bind AF_XDP socket to {iface}:{queue_id}: {err}, skipping");
+            return;
+        }
+    };
+    let frame = Ethernet::new(
+        MacAddr([0xff; 6]),
+        MacAddr([0x02, 0, 0, 0, 0, 1]),
+        ETHERTYPE_IPV4,
+        vec![0; 50],
+    )
+    .to_bytes();
+    assert_eq!(frame.len(), 64);
+
+    let mut group = c.benchmark_group("xdp");
+    group.throughput(Throughput::Elements(BURST as u64));
+    group.bench_function(
+        format!("send_64b_zero_copy={}", socket.is_zero_copy()),
+        |b| {
+            b.iter(|| {
+                let mut sent = 0;
+                while sent < BURST {
+                    match socket.send(black_box(&frame)) {
+                        Ok(()) => sent += 1,
+                        Err(XdpError::TxFull) => std::hint::spin_loop(),
+                        Err(err) => panic!("send failed: {err}"),
+                    }
+                }
+            })
+        },
+    );
+    group.finish();
+}
+
+criterion_group!(benches, send);
+criterion_main!(benches);
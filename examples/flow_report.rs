@@ -0,0 +1,67 @@
+//! Summarizes a pcap or pcapng capture: protocol stacks, the largest
+//! conversations with their client and server, TCP anomalies and decoding
+//! warnings, as text or as the JSON of `Manifest::to_json`.
+//!
+//!     cargo run --example flow_report --features examples -- capture.pcap --json
+
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufReader, Read};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::Parser;
+use ethercrafter::manifest::{self, Manifest};
+use ethercrafter::pcap::Reader;
+use ethercrafter::pcapng;
+
+/// First bytes of a pcapng file: the Section Header Block type.
+const PCAPNG_MAGIC: [u8; 4] = [0x0a, 0x0d, 0x0d, 0x0a];
+
+/// Report the conversations of a capture.
+#[derive(Parser)]
+struct Args {
+    /// pcap or pcapng capture to read.
+    capture: PathBuf,
+    /// Print JSON instead of text.
+    #[arg(long)]
+    json: bool,
+    /// Write the report to this file instead of standard output.
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+}
+
+fn describe(args: &Args) -> Result<Manifest, Box<dyn Error>> {
+    let mut magic = [0; 4];
+    File::open(&args.capture)?.read_exact(&mut magic)?;
+    let file = BufReader::new(File::open(&args.capture)?);
+    let manifest = match magic {
+        PCAPNG_MAGIC => manifest::describe_pcapng(&mut pcapng::Reader::new(file)?)?,
+        _ => manifest::describe(&mut Reader::new(file)?)?,
+    };
+    Ok(manifest)
+}
+
+fn run(args: &Args) -> Result<(), Box<dyn Error>> {
+    let manifest =
+        describe(args).map_err(|err| format!("cannot read {}: {err}", args.capture.display()))?;
+    let report = match args.json {
+        true => manifest.to_json(),
+        false => format!("{manifest}\n"),
+    };
+    match &args.output {
+        Some(path) => fs::write(path, report)?,
+        None => print!("{report}"),
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match run(&Args::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("flow_report: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
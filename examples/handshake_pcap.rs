@@ -0,0 +1,142 @@
+//! Writes a capture of a complete TCP connection: three-way handshake, a
+//! request and its response split to the MSS with the ACKs the other side
+//! sends, then the four-way close.
+//!
+//!     cargo run --example handshake_pcap --features examples -- http.pcap
+//!
+//! Open the result with Wireshark or read it back with the `flow_report`
+//! example.
+
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+use std::net::SocketAddrV4;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::{Duration, SystemTime};
+
+use clap::Parser;
+use ethercrafter::ethernet::{ETHERTYPE_IPV4, Ethernet, MacAddr};
+use ethercrafter::pcap::{CapturedPacket, GlobalHeader, LINKTYPE_ETHERNET, Resolution, Writer};
+use ethercrafter::tcp::{TCP, TcpSegmentStream, flags};
+
+const CLIENT_MAC: MacAddr = MacAddr([0x02, 0, 0, 0, 0, 0x01]);
+const SERVER_MAC: MacAddr = MacAddr([0x02, 0, 0, 0, 0, 0x02]);
+
+/// Write a pcap of one TCP connection.
+#[derive(Parser)]
+struct Args {
+    /// Capture file to write.
+    output: PathBuf,
+    #[arg(long, default_value = "192.0.2.1:49152")]
+    client: SocketAddrV4,
+    #[arg(long, default_value = "192.0.2.2:80")]
+    server: SocketAddrV4,
+    /// Data the client sends.
+    #[arg(long, default_value = "GET / HTTP/1.0\r\nHost: example.com\r\n\r\n")]
+    request: String,
+    /// Bytes of the response the server sends back.
+    #[arg(long, default_value_t = 4000)]
+    response_len: usize,
+    /// Largest segment payload, both ways.
+    #[arg(long, default_value_t = 1460)]
+    mss: usize,
+    /// Round-trip time, in milliseconds.
+    #[arg(long, default_value_t = 20)]
+    rtt_ms: u64,
+}
+
+/// Packets of the connection, in the order they are sent.
+struct Connection {
+    args: Args,
+    packets: Vec<CapturedPacket>,
+    now: Duration,
+}
+
+impl Connection {
+    /// Adds `segment`, sent by the client if `from_client` is set, half a
+    /// round trip after the previous one.
+    fn push(&mut self, segment: TCP, from_client: bool) {
+        let (source, destination, mac) = match from_client {
+            true => (self.args.client, self.args.server, (SERVER_MAC, CLIENT_MAC)),
+            false => (self.args.server, self.args.client, (CLIENT_MAC, SERVER_MAC)),
+        };
+        let ip = segment.to_ipv4(*source.ip(), *destination.ip());
+        let frame = Ethernet::new(mac.0, mac.1, ETHERTYPE_IPV4, ip.to_bytes());
+        self.packets
+            .push(CapturedPacket::new(self.now, frame.to_bytes()));
+        self.now += Duration::from_millis(self.args.rtt_ms) / 2;
+    }
+
+    /// Sends `data` one way, each segment acknowledged by the other side.
+    /// Returns the sender's next sequence number.
+    fn transfer(&mut self, data: Vec<u8>, seq: u32, ack: u32, from_client: bool) -> u32 {
+        let (source, destination) = match from_client {
+            true => (self.args.client.port(), self.args.server.port()),
+            false => (self.args.server.port(), self.args.client.port()),
+        };
+        let stream = TcpSegmentStream::new(source, destination, seq, data, self.args.mss)
+            .set_acknowledgment(ack);
+        let next = stream.ack_next_seq();
+        for segment in stream {
+            let acked = segment.sequence.wrapping_add(segment.data.len() as u32);
+            self.push(segment, from_client);
+            let ack = TCP::segment(destination, source, ack, acked, flags::ACK);
+            self.push(ack, !from_client);
+        }
+        next
+    }
+}
+
+fn run(args: Args) -> Result<usize, Box<dyn Error>> {
+    let start = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?;
+    let (client_port, server_port) = (args.client.port(), args.server.port());
+    let response = (0..args.response_len)
+        .map(|i| b'a' + (i % 26) as u8)
+        .collect();
+    let request = args.request.clone().into_bytes();
+    let output = args.output.clone();
+    let mut connection = Connection {
+        args,
+        packets: Vec::new(),
+        now: start,
+    };
+
+    let (client_isn, server_isn) = (1000, 5000);
+    let [syn, syn_ack, ack] =
+        TCP::connection_setup_with_server_isn(client_port, server_port, client_isn, server_isn);
+    connection.push(syn, true);
+    connection.push(syn_ack, false);
+    connection.push(ack, true);
+
+    let (client_seq, server_seq) = (client_isn + 1, server_isn + 1);
+    let client_seq = connection.transfer(request, client_seq, server_seq, true);
+    let server_seq = connection.transfer(response, server_seq, client_seq, false);
+
+    let close = TCP::connection_teardown(client_port, server_port, client_seq, server_seq);
+    for (segment, from_client) in close.into_iter().zip([true, false, false, true]) {
+        connection.push(segment, from_client);
+    }
+
+    let header = GlobalHeader::new(LINKTYPE_ETHERNET, 65535, Resolution::Micros);
+    let mut writer = Writer::new(BufWriter::new(File::create(&output)?), header)?;
+    for packet in &connection.packets {
+        writer.write_packet(packet)?;
+    }
+    Ok(connection.packets.len())
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let output = args.output.display().to_string();
+    match run(args) {
+        Ok(packets) => {
+            println!("wrote {packets} packets to {output}");
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("handshake_pcap: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,151 @@
+//! Finds the hosts of an IPv4 network that answer ICMP echo requests.
+//! Requests go out at a fixed rate while a second thread receives the
+//! replies on the same AF_PACKET socket, filtered in the kernel, and
+//! matches them to the requests by identifier and sequence number; each
+//! request carries its send time, so the round trip is measured without
+//! bookkeeping.
+//!
+//! Needs CAP_NET_RAW (Linux only):
+//!
+//!     sudo cargo run --example pingsweep --features examples -- \
+//!         192.168.1.0/24 --iface eth0 --source 192.168.1.10 --next-hop 52:54:00:12:34:56
+//!
+//! `--next-hop` is the MAC address frames are sent to, that of the
+//! router for a remote network; the broadcast default reaches the hosts
+//! of the local link.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::io;
+use std::net::Ipv4Addr;
+use std::process::ExitCode;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use clap::Parser;
+use ethercrafter::ethernet::{ETHERTYPE_IPV4, Ethernet, MacAddr};
+use ethercrafter::filter::Filter;
+use ethercrafter::icmpv4::{Icmpv4, Icmpv4Message};
+use ethercrafter::pcap::{DecodedStack, LINKTYPE_ETHERNET};
+use ethercrafter::transport::{RawReceiver, RawSender, RawSocket};
+use ethercrafter::util::Ipv4Cidr;
+
+/// Sequence numbers are 16 bits: one per host.
+const MAX_HOSTS: usize = 1 << 16;
+
+/// Ping every host of a network.
+#[derive(Parser)]
+struct Args {
+    /// Network to sweep, e.g. 192.168.1.0/24.
+    network: Ipv4Cidr,
+    /// Interface to send on.
+    #[arg(long, short)]
+    iface: String,
+    /// IPv4 address of the interface, the replies' destination.
+    #[arg(long, short)]
+    source: Ipv4Addr,
+    /// MAC address the requests are sent to.
+    #[arg(long, default_value = "ff:ff:ff:ff:ff:ff")]
+    next_hop: MacAddr,
+    /// Requests per second.
+    #[arg(long, default_value_t = 100)]
+    rate: u32,
+    /// How long to wait for replies after the last request, in
+    /// milliseconds.
+    #[arg(long, default_value_t = 1000)]
+    timeout_ms: u64,
+}
+
+/// Receives echo replies to `identifier` until `deadline`, returning the
+/// round trip of each host that answered, by sequence number.
+fn receive_replies(
+    socket: &RawSocket,
+    hosts: &[Ipv4Addr],
+    identifier: u16,
+    start: Instant,
+    deadline: Instant,
+) -> io::Result<BTreeMap<u16, Duration>> {
+    let mut replies = BTreeMap::new();
+    let mut buf = vec![0; 2048];
+    while Instant::now() < deadline {
+        let len = match socket.recv(&mut buf) {
+            Ok(len) => len,
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => continue,
+            Err(err) => return Err(err),
+        };
+        let received = start.elapsed();
+        let stack = DecodedStack::decode(LINKTYPE_ETHERNET, &buf[..len]);
+        let Some(ip) = stack.network.as_ipv4() else {
+            continue;
+        };
+        let Ok(Icmpv4Message::EchoReply {
+            identifier: id,
+            sequence,
+            data,
+        }) = Icmpv4::from_bytes(&ip.payload).and_then(|icmp| icmp.message())
+        else {
+            continue;
+        };
+        let Ok(sent) = <[u8; 8]>::try_from(data.as_slice()) else {
+            continue;
+        };
+        if id == identifier && hosts.get(sequence as usize) == Some(&ip.source) {
+            let sent = Duration::from_nanos(u64::from_be_bytes(sent));
+            replies
+                .entry(sequence)
+                .or_insert(received.saturating_sub(sent));
+        }
+    }
+    Ok(replies)
+}
+
+fn run(args: &Args) -> Result<(), Box<dyn Error>> {
+    let hosts: Vec<Ipv4Addr> = args.network.hosts().take(MAX_HOSTS + 1).collect();
+    if hosts.len() > MAX_HOSTS {
+        return Err(format!("{} has more than {MAX_HOSTS} hosts", args.network).into());
+    }
+    let socket = RawSocket::new(&args.iface)
+        .map_err(|err| format!("cannot open a raw socket on {}: {err}", args.iface))?;
+    let source_mac = socket.mac_address()?;
+    socket.set_filter(&Filter::parse(&format!(
+        "icmp and dst host {}",
+        args.source
+    ))?)?;
+    socket.set_read_timeout(Some(Duration::from_millis(100)))?;
+
+    let identifier = std::process::id() as u16;
+    let interval = Duration::from_secs(1) / args.rate.max(1);
+    let start = Instant::now();
+    let deadline = start + interval * hosts.len() as u32 + Duration::from_millis(args.timeout_ms);
+    let replies = thread::scope(|scope| {
+        let receiver =
+            scope.spawn(|| receive_replies(&socket, &hosts, identifier, start, deadline));
+        for (sequence, host) in hosts.iter().enumerate() {
+            let due = start + interval * sequence as u32;
+            thread::sleep(due.saturating_duration_since(Instant::now()));
+            let sent = (start.elapsed().as_nanos() as u64).to_be_bytes();
+            let echo = Icmpv4::echo_request(identifier, sequence as u16, sent.to_vec());
+            let ip = echo.to_ipv4(args.source, *host);
+            let frame = Ethernet::new(args.next_hop, source_mac, ETHERTYPE_IPV4, ip.to_bytes());
+            socket.send(&frame.to_bytes())?;
+        }
+        receiver.join().expect("receiver thread panicked")
+    })?;
+
+    for (sequence, rtt) in &replies {
+        let host = hosts[*sequence as usize];
+        println!("{host:<15} up  {:.3} ms", rtt.as_secs_f64() * 1e3);
+    }
+    println!("{} of {} hosts up", replies.len(), hosts.len());
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match run(&Args::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("pingsweep: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
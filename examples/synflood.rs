@@ -0,0 +1,130 @@
+//! Sends SYNs to a TCP port at a fixed rate, each from a random source
+//! port with a random initial sequence number, to load-test a server or
+//! a firewall's connection tracking. Only point it at hosts you are
+//! authorized to test.
+//!
+//! One SYN is built with the library; every frame sent is a copy of it
+//! with the port and sequence number rewritten in place, checksums
+//! updated incrementally, so the cost per frame stays a few stores.
+//!
+//! Needs CAP_NET_RAW (Linux only):
+//!
+//!     sudo cargo run --release --example synflood --features examples -- \
+//!         --iface eth0 --source 192.168.1.10 --target 192.168.1.20:80 \
+//!         --next-hop 52:54:00:12:34:56 --count 100000 --rate 20000
+//!
+//! `--next-hop` is the MAC address of the target, or of the router if it
+//! is not on the local link.
+
+use std::collections::hash_map::RandomState;
+use std::error::Error;
+use std::hash::BuildHasher;
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::process::ExitCode;
+use std::time::Instant;
+
+use clap::Parser;
+use ethercrafter::ethernet::{ETHERTYPE_IPV4, Ethernet, MacAddr};
+use ethercrafter::matcher::FieldId;
+use ethercrafter::pcap::{DecodedStack, LINKTYPE_ETHERNET};
+use ethercrafter::rewrite::{FieldValue, set_field};
+use ethercrafter::tcp::TCP;
+use ethercrafter::transport::{RawSocket, send_paced};
+
+/// Send TCP SYNs at a fixed rate.
+#[derive(Parser)]
+struct Args {
+    /// Interface to send on.
+    #[arg(long, short)]
+    iface: String,
+    /// Source IPv4 address of the SYNs.
+    #[arg(long, short)]
+    source: Ipv4Addr,
+    /// Address and port the SYNs are sent to.
+    #[arg(long, short)]
+    target: SocketAddrV4,
+    /// MAC address the frames are sent to.
+    #[arg(long, default_value = "ff:ff:ff:ff:ff:ff")]
+    next_hop: MacAddr,
+    /// Number of SYNs to send.
+    #[arg(long, short, default_value_t = 1000)]
+    count: u64,
+    /// SYNs per second; 0 sends them as fast as the socket takes them.
+    #[arg(long, default_value_t = 1000)]
+    rate: u64,
+    /// Frames handed to the kernel per system call.
+    #[arg(long, default_value_t = 64)]
+    batch: usize,
+}
+
+/// xorshift64: fast, and random enough for ports and sequence numbers.
+struct XorShift(u64);
+
+impl XorShift {
+    fn new() -> Self {
+        XorShift(RandomState::new().hash_one(Instant::now()) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+/// Frame of a SYN with options, as a client opening a connection sends.
+fn syn_template(args: &Args, source_mac: MacAddr) -> Vec<u8> {
+    let syn = TCP::syn_with_options_and_tsval(49152, args.target.port(), 0, 1460, 7, None);
+    let ip = syn.to_ipv4(args.source, *args.target.ip());
+    Ethernet::new(args.next_hop, source_mac, ETHERTYPE_IPV4, ip.to_bytes()).to_bytes()
+}
+
+fn run(args: &Args) -> Result<(), Box<dyn Error>> {
+    let socket = RawSocket::new(&args.iface)
+        .map_err(|err| format!("cannot open a raw socket on {}: {err}", args.iface))?;
+    let template = syn_template(args, socket.mac_address()?);
+
+    let mut rng = XorShift::new();
+    let mut randomize = move |frame: &mut Vec<u8>| {
+        let bits = rng.next();
+        let port = FieldValue::Number(1024 + (bits as u32 % (65536 - 1024)));
+        let sequence = FieldValue::Number((bits >> 32) as u32);
+        set_field(LINKTYPE_ETHERNET, frame, FieldId::SourcePort, &port)?;
+        set_field(LINKTYPE_ETHERNET, frame, FieldId::TcpSequence, &sequence)
+    };
+
+    // Checked once: the fields and checksums of every other frame are
+    // written the same way.
+    let mut first = template.clone();
+    randomize(&mut first)?;
+    let warnings = DecodedStack::parse(LINKTYPE_ETHERNET, &first).warnings;
+    if let Some(warning) = warnings.first() {
+        return Err(format!("rewritten SYN does not decode cleanly: {warning}").into());
+    }
+
+    let frames = (0..args.count).map(|_| {
+        let mut frame = template.clone();
+        randomize(&mut frame).expect("fields checked on the first frame");
+        frame
+    });
+    let start = Instant::now();
+    let sent = send_paced(&socket, frames, args.rate, args.batch)?;
+    let elapsed = start.elapsed().as_secs_f64();
+    println!(
+        "sent {sent} SYNs to {} in {elapsed:.3} s ({:.0} per second)",
+        args.target,
+        sent as f64 / elapsed
+    );
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match run(&Args::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("synflood: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
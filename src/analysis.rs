@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use crate::flow::FiveTuple;
+use crate::tcp::{TCP, flags};
+
+mod align;
+mod dedup;
+mod entropy;
+mod protocol;
+
+pub use align::{ClockModel, align};
+pub use dedup::{DedupOptions, DedupReport, DuplicateCopy, DuplicateGroup, KeepPolicy, dedup};
+pub use entropy::{
+    DEFAULT_WINDOW, EntropyAlert, EntropyAlertKind, EntropyProfile, EntropyProfiler,
+    EntropyThresholds, FlowEntropy, entropy, entropy_alerts, flow_entropy,
+};
+pub use protocol::{AppProtocol, ProtocolDetection, ProtocolEvidence, detect_protocol};
+
+/// A TCP segment of a flow, together with the endpoints it travelled between.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlowSegment {
+    pub source: SocketAddr,
+    pub destination: SocketAddr,
+    pub tcp: TCP,
+}
+
+impl FlowSegment {
+    /// Constructor to create a new flow segment.
+    pub fn new(source: SocketAddr, destination: SocketAddr, tcp: TCP) -> Self {
+        FlowSegment {
+            source,
+            destination,
+            tcp,
+        }
+    }
+}
+
+// --- ROLE INFERENCE ---
+
+/// How much the inferred client/server assignment can be trusted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Confidence {
+    Low,
+    Medium,
+    High,
+}
+
+/// The heuristic that decided the client/server assignment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoleEvidence {
+    /// A SYN or SYN-ACK was seen.
+    Handshake,
+    /// Only one endpoint uses a port from the well-known port registry.
+    WellKnownPort(u16),
+    /// The first payload looks like a server greeting or a client request.
+    Greeting,
+    /// The server sent more payload bytes than the client.
+    DataVolume,
+    /// The server uses the lower port number.
+    PortMagnitude,
+    /// The server was given by a `RoleOverrides`.
+    Override,
+}
+
+/// Client/server assignment of a TCP flow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RoleInference {
+    pub client: SocketAddr,
+    pub server: SocketAddr,
+    pub confidence: Confidence,
+    pub evidence: RoleEvidence,
+}
+
+/// Ports of common TCP services, used to recognise the server side of a flow.
+pub const WELL_KNOWN_PORTS: &[u16] = &[
+    20, 21, 22, 23, 25, 53, 80, 110, 111, 119, 135, 139, 143, 179, 389, 443, 445, 465, 514, 587,
+    636, 853, 873, 993, 995, 1433, 1521, 1883, 2049, 3306, 3389, 5060, 5432, 5672, 5900, 6379,
+    6443, 8080, 8443, 9092, 9200, 11211, 27017,
+];
+
+/// Bytes of the first payload kept by `RoleObserver`, enough for every
+/// greeting and request prefix.
+const GREETING_PREFIX_LEN: usize = 16;
+
+/// Payload prefixes a server sends before the client says anything.
+const SERVER_GREETINGS: &[&[u8]] = &[b"220 ", b"220-", b"+OK", b"* OK", b"* PREAUTH", b"RFB "];
+
+/// Payload prefixes a client sends to open a conversation.
+const CLIENT_REQUESTS: &[&[u8]] = &[
+    b"GET ",
+    b"POST ",
+    b"PUT ",
+    b"HEAD ",
+    b"DELETE ",
+    b"OPTIONS ",
+    b"CONNECT ",
+    b"PRI * HTTP/2",
+    b"\x16\x03",
+];
+
+/// Infers which endpoint of a flow is the client and which is the server.
+///
+/// Heuristics are tried in order: handshake flags, the well-known port
+/// registry, greeting-style payloads, relative data volume and finally port
+/// magnitude. Returns `None` when `segments` is empty.
+pub fn infer_roles(segments: &[FlowSegment]) -> Option<RoleInference> {
+    let mut observer = RoleObserver::new();
+    for segment in segments {
+        observer.observe(
+            segment.source,
+            segment.destination,
+            segment.tcp.flags,
+            &segment.tcp.data,
+        );
+    }
+    observer.infer()
+}
+
+/// What `infer_roles` looks at in a flow, gathered one segment at a time
+/// so that tools going through a whole capture need not keep the segments.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RoleObserver {
+    /// Endpoints of the first segment.
+    endpoints: Option<(SocketAddr, SocketAddr)>,
+    /// Server named by the first SYN or SYN-ACK.
+    handshake_server: Option<SocketAddr>,
+    /// Source, destination and start of the first payload.
+    first_payload: Option<(SocketAddr, SocketAddr, Vec<u8>)>,
+    /// Payload bytes sent by each endpoint of `endpoints`.
+    sent: (u64, u64),
+}
+
+impl RoleObserver {
+    /// Constructor to create an observer that has seen nothing.
+    pub fn new() -> Self {
+        RoleObserver::default()
+    }
+
+    /// Records a segment from `source` to `destination` with TCP `flags`.
+    pub fn observe(
+        &mut self,
+        source: SocketAddr,
+        destination: SocketAddr,
+        flags: u16,
+        payload: &[u8],
+    ) {
+        let (a, _) = *self.endpoints.get_or_insert((source, destination));
+        if self.handshake_server.is_none() {
+            let syn_ack = flags & (flags::SYN | flags::ACK);
+            if syn_ack == flags::SYN {
+                self.handshake_server = Some(destination);
+            } else if syn_ack == flags::SYN | flags::ACK {
+                self.handshake_server = Some(source);
+            }
+        }
+        if payload.is_empty() {
+            return;
+        }
+        if self.first_payload.is_none() {
+            let prefix = payload[..payload.len().min(GREETING_PREFIX_LEN)].to_vec();
+            self.first_payload = Some((source, destination, prefix));
+        }
+        if source == a {
+            self.sent.0 += payload.len() as u64;
+        } else {
+            self.sent.1 += payload.len() as u64;
+        }
+    }
+
+    /// Client/server assignment of the segments seen so far, as
+    /// `infer_roles` makes it; `None` before the first segment.
+    pub fn infer(&self) -> Option<RoleInference> {
+        let (a, b) = self.endpoints?;
+        let assign = |server: SocketAddr, confidence, evidence| {
+            let client = if server == a { b } else { a };
+            Some(RoleInference {
+                client,
+                server,
+                confidence,
+                evidence,
+            })
+        };
+
+        if let Some(server) = self.handshake_server {
+            return assign(server, Confidence::High, RoleEvidence::Handshake);
+        }
+
+        let a_known = WELL_KNOWN_PORTS.contains(&a.port());
+        let b_known = WELL_KNOWN_PORTS.contains(&b.port());
+        if a_known != b_known {
+            let server = if a_known { a } else { b };
+            return assign(
+                server,
+                Confidence::Medium,
+                RoleEvidence::WellKnownPort(server.port()),
+            );
+        }
+
+        if let Some((source, destination, payload)) = &self.first_payload {
+            if SERVER_GREETINGS.iter().any(|p| payload.starts_with(p)) {
+                return assign(*source, Confidence::Medium, RoleEvidence::Greeting);
+            }
+            if CLIENT_REQUESTS.iter().any(|p| payload.starts_with(p)) {
+                return assign(*destination, Confidence::Medium, RoleEvidence::Greeting);
+            }
+        }
+
+        let (a_bytes, b_bytes) = self.sent;
+        if a_bytes != b_bytes {
+            let server = if a_bytes > b_bytes { a } else { b };
+            return assign(server, Confidence::Low, RoleEvidence::DataVolume);
+        }
+
+        let server = if a.port() <= b.port() { a } else { b };
+        assign(server, Confidence::Low, RoleEvidence::PortMagnitude)
+    }
+}
+
+/// Servers of flows whose roles are known beforehand, taking precedence
+/// over the inference in the reassembler and the capture manifest.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RoleOverrides {
+    /// Server endpoint, by normalized flow key.
+    servers: HashMap<FiveTuple, SocketAddr>,
+}
+
+impl RoleOverrides {
+    /// Constructor to create an empty set of overrides: every flow gets
+    /// the inferred roles.
+    pub fn new() -> Self {
+        RoleOverrides::default()
+    }
+
+    /// Makes `server` the server of `flow`, in either direction. It should
+    /// be one of the endpoints of `flow`; otherwise the override is
+    /// ignored.
+    pub fn set_server(mut self, flow: &FiveTuple, server: SocketAddr) -> Self {
+        self.servers.insert(flow.normalized(), server);
+        self
+    }
+
+    /// Roles of `flow`: the override if there is one, the inference of
+    /// `observer` otherwise.
+    pub fn resolve(&self, flow: &FiveTuple, observer: &RoleObserver) -> Option<RoleInference> {
+        let a = SocketAddr::new(flow.source, flow.source_port);
+        let b = SocketAddr::new(flow.destination, flow.destination_port);
+        match self.servers.get(&flow.normalized()) {
+            Some(&server) if server == a || server == b => Some(RoleInference {
+                client: if server == a { b } else { a },
+                server,
+                confidence: Confidence::High,
+                evidence: RoleEvidence::Override,
+            }),
+            _ => observer.infer(),
+        }
+    }
+}
@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Read, Write};
+use std::time::Duration;
+
+use super::dedup::{DedupOptions, signature};
+use crate::pcap::{CapturedPacket, Reader, Writer, invalid_data};
+
+/// Maximum number of matched packets used for the slope estimate, which
+/// is quadratic in the number of samples.
+const MAX_FIT_SAMPLES: usize = 256;
+
+/// Linear mapping from the clock of one capture to that of another:
+/// `a = reference + offset + rate * (b - reference)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClockModel {
+    /// Point of capture B's clock the model is expanded around.
+    pub reference: Duration,
+    /// Offset of clock A from clock B at `reference`, in nanoseconds.
+    pub offset_ns: f64,
+    /// Rate of clock A relative to clock B (1.0 when there is no skew).
+    pub rate: f64,
+    /// Number of packets seen in both captures.
+    pub matches: usize,
+}
+
+impl ClockModel {
+    /// Model that leaves timestamps unchanged.
+    pub fn identity() -> Self {
+        ClockModel {
+            reference: Duration::ZERO,
+            offset_ns: 0.0,
+            rate: 1.0,
+            matches: 0,
+        }
+    }
+
+    /// Skew of clock A relative to clock B in parts per million.
+    pub fn skew_ppm(&self) -> f64 {
+        (self.rate - 1.0) * 1e6
+    }
+
+    /// Converts a timestamp of capture B to the clock of capture A.
+    pub fn correct(&self, timestamp: Duration) -> Duration {
+        let delta = nanos_between(self.reference, timestamp);
+        let shift = (self.offset_ns + (self.rate - 1.0) * delta).round() as i128;
+        let corrected = timestamp.as_nanos() as i128 + shift;
+        Duration::from_nanos(corrected.max(0) as u64)
+    }
+
+    /// Returns the packets of `reader` with their timestamps corrected.
+    pub fn apply<R: Read>(
+        &self,
+        reader: Reader<R>,
+    ) -> impl Iterator<Item = io::Result<CapturedPacket>> {
+        let model = *self;
+        reader.map(move |packet| {
+            packet.map(|mut packet| {
+                packet.timestamp = model.correct(packet.timestamp);
+                packet
+            })
+        })
+    }
+
+    /// Copies `reader` to `writer` with corrected timestamps. Returns the
+    /// number of packets written.
+    pub fn rewrite<R: Read, W: Write>(
+        &self,
+        reader: Reader<R>,
+        writer: &mut Writer<W>,
+    ) -> io::Result<usize> {
+        let mut written = 0;
+        for packet in self.apply(reader) {
+            writer.write_packet(&packet?)?;
+            written += 1;
+        }
+        writer.flush()?;
+        Ok(written)
+    }
+}
+
+/// Hashes of the packet signatures of a capture, with the timestamp of
+/// each signature seen exactly once.
+fn unique_signatures<R: Read>(
+    reader: &mut Reader<R>,
+) -> io::Result<HashMap<u64, Option<Duration>>> {
+    let link_type = reader.link_type();
+    let opts = DedupOptions::default();
+    let mut seen = HashMap::new();
+    while let Some(packet) = reader.next_packet()? {
+        let mut hasher = DefaultHasher::new();
+        signature(link_type, &packet.data, &opts).hash(&mut hasher);
+        seen.entry(hasher.finish())
+            .and_modify(|timestamp| *timestamp = None)
+            .or_insert(Some(packet.timestamp));
+    }
+    Ok(seen)
+}
+
+/// `to - from` in nanoseconds, computed exactly before the conversion to f64.
+fn nanos_between(from: Duration, to: Duration) -> f64 {
+    (to.as_nanos() as i128 - from.as_nanos() as i128) as f64
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(f64::total_cmp);
+    let middle = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[middle - 1] + values[middle]) / 2.0
+    } else {
+        values[middle]
+    }
+}
+
+/// Estimates the clock model mapping the timestamps of `capture_b` to the
+/// clock of `capture_a`.
+///
+/// Packets are matched by their content, ignoring link-layer headers and
+/// the TTL, and only when they occur once in each capture. The line is
+/// fitted with the Theil-Sen estimator (median of pairwise slopes, then
+/// median offset), so a minority of mismatched pairs does not skew it.
+pub fn align<RA: Read, RB: Read>(
+    capture_a: &mut Reader<RA>,
+    capture_b: &mut Reader<RB>,
+) -> io::Result<ClockModel> {
+    let a = unique_signatures(capture_a)?;
+    let b = unique_signatures(capture_b)?;
+    let mut pairs: Vec<(Duration, Duration)> = b
+        .iter()
+        .filter_map(|(hash, timestamp_b)| Some(((*timestamp_b)?, a.get(hash).copied()??)))
+        .collect();
+    if pairs.len() < 2 {
+        return Err(invalid_data("not enough packets common to both captures"));
+    }
+    pairs.sort();
+
+    let reference = pairs[0].0;
+    let ns = |timestamp: Duration| nanos_between(reference, timestamp);
+    let step = pairs.len().div_ceil(MAX_FIT_SAMPLES);
+    let samples: Vec<(f64, f64)> = pairs
+        .iter()
+        .step_by(step)
+        .map(|(b, a)| (ns(*b), ns(*a)))
+        .collect();
+
+    let mut slopes = Vec::new();
+    for (i, (b1, a1)) in samples.iter().enumerate() {
+        for (b2, a2) in &samples[i + 1..] {
+            if b2 != b1 {
+                slopes.push((a2 - a1) / (b2 - b1));
+            }
+        }
+    }
+    let rate = if slopes.is_empty() {
+        1.0
+    } else {
+        median(&mut slopes)
+    };
+    let mut offsets: Vec<f64> = pairs.iter().map(|(b, a)| ns(*a) - rate * ns(*b)).collect();
+    Ok(ClockModel {
+        reference,
+        offset_ns: median(&mut offsets),
+        rate,
+        matches: pairs.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::net::Ipv4Addr;
+
+    use super::*;
+    use crate::ipv4::IPv4;
+    use crate::pcap::{GlobalHeader, LINKTYPE_IPV4, Resolution};
+    use crate::udp::{self, UDP};
+
+    const OFFSET_NS: f64 = 2_500_000.0;
+    const SKEW_PPM: f64 = 50.0;
+
+    fn packet(id: u16, ttl: u8) -> Vec<u8> {
+        let udp = UDP::new(id, 53, id.to_be_bytes().to_vec());
+        IPv4::with_payload(
+            Ipv4Addr::new(10, 0, 0, 1),
+            Ipv4Addr::new(10, 0, 0, 2),
+            udp::IP_PROTOCOL,
+            udp.to_bytes(),
+        )
+        .set_ttl(ttl)
+        .with_checksum()
+        .to_bytes()
+    }
+
+    type MemoryReader = Reader<Cursor<Vec<u8>>>;
+
+    fn capture(packets: &[(Duration, Vec<u8>)]) -> MemoryReader {
+        let header = GlobalHeader::new(LINKTYPE_IPV4, 65535, Resolution::Nanos);
+        let mut writer = Writer::new(Vec::new(), header).unwrap();
+        for (timestamp, data) in packets {
+            writer
+                .write_packet(&CapturedPacket::new(*timestamp, data.clone()))
+                .unwrap();
+        }
+        Reader::new(Cursor::new(writer.into_inner())).unwrap()
+    }
+
+    /// Deterministic jitter in `-amplitude..=amplitude` nanoseconds.
+    fn jitter(i: u64, amplitude: u64) -> i64 {
+        (i.wrapping_mul(2_654_435_761) % (2 * amplitude + 1)) as i64 - amplitude as i64
+    }
+
+    fn shifted(timestamp: Duration, ns: f64) -> Duration {
+        Duration::from_nanos((timestamp.as_nanos() as f64 + ns).round() as u64)
+    }
+
+    /// Capture B sees 400 packets over 4 seconds, plus 20 of its own.
+    /// Capture A sees them one hop later, with clock A ahead by
+    /// `OFFSET_NS` at the first one and running `SKEW_PPM` fast, 1 µs of
+    /// jitter, 20 packets of its own and 10 matched packets delayed by 40 ms.
+    fn captures() -> (MemoryReader, MemoryReader, Duration) {
+        let start = Duration::from_secs(1_700_000_000);
+        let mut a = Vec::new();
+        let mut b = Vec::new();
+        for i in 0..400u64 {
+            let timestamp_b = start + Duration::from_millis(10 * i);
+            let elapsed = (timestamp_b - start).as_nanos() as f64;
+            let mut ns = OFFSET_NS + SKEW_PPM * 1e-6 * elapsed + jitter(i, 1_000) as f64;
+            if i % 40 == 7 {
+                ns += 40e6;
+            }
+            b.push((timestamp_b, packet(i as u16, 64)));
+            a.push((shifted(timestamp_b, ns), packet(i as u16, 63)));
+        }
+        for i in 0..20u64 {
+            let timestamp = start + Duration::from_millis(200 * i + 5);
+            b.push((timestamp, packet(1000 + i as u16, 64)));
+            a.push((timestamp, packet(2000 + i as u16, 64)));
+        }
+        a.sort();
+        b.sort();
+        (capture(&a), capture(&b), start)
+    }
+
+    #[test]
+    fn recovers_injected_offset_and_drift() {
+        let (mut a, mut b, start) = captures();
+        let model = align(&mut a, &mut b).unwrap();
+        assert_eq!(model.matches, 400);
+        assert_eq!(model.reference, start);
+        assert!(
+            (model.offset_ns - OFFSET_NS).abs() < 1_000.0,
+            "offset {} ns",
+            model.offset_ns
+        );
+        assert!(
+            (model.skew_ppm() - SKEW_PPM).abs() < 0.1,
+            "skew {} ppm",
+            model.skew_ppm()
+        );
+    }
+
+    #[test]
+    fn corrected_timestamps_land_on_clock_a() {
+        let (mut a, mut b, start) = captures();
+        let model = align(&mut a, &mut b).unwrap();
+        let last_b = start + Duration::from_millis(3990);
+        let elapsed = (last_b - start).as_nanos() as f64;
+        let expected = shifted(last_b, OFFSET_NS + SKEW_PPM * 1e-6 * elapsed);
+        let error = nanos_between(expected, model.correct(last_b)).abs();
+        assert!(error < 2_000.0, "off by {error} ns");
+    }
+
+    #[test]
+    fn disjoint_captures_do_not_align() {
+        let start = Duration::from_secs(1);
+        let mut a = capture(&[(start, packet(1, 64)), (start * 2, packet(2, 64))]);
+        let mut b = capture(&[(start, packet(3, 64)), (start * 2, packet(4, 64))]);
+        let err = align(&mut a, &mut b).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,358 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{self, Read, Write};
+use std::time::Duration;
+
+use crate::matcher::{FieldId, FieldMask};
+use crate::pcap::{Reader, Writer};
+
+/// Which copy of a duplicate group is kept when writing a cleaned capture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeepPolicy {
+    #[default]
+    First,
+    Last,
+}
+
+/// Settings of `dedup`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DedupOptions {
+    /// Maximum time between two copies of a packet.
+    pub window: Duration,
+    /// Also group packets that differ only in the ignored fields below.
+    pub near_duplicates: bool,
+    /// Fields left out of near-duplicate signatures, compared as
+    /// `PacketMatcher` does.
+    pub ignored: FieldMask,
+    pub keep: KeepPolicy,
+}
+
+impl Default for DedupOptions {
+    fn default() -> Self {
+        DedupOptions {
+            window: Duration::from_millis(10),
+            near_duplicates: true,
+            ignored: FieldMask::new(),
+            keep: KeepPolicy::First,
+        }
+        .set_ignore_link_layer(true)
+        .set_ignore_ttl(true)
+    }
+}
+
+/// Ethernet header fields and VLAN tags.
+const LINK_LAYER_FIELDS: [FieldId; 4] = [
+    FieldId::EthDestination,
+    FieldId::EthSource,
+    FieldId::EthType,
+    FieldId::VlanIds,
+];
+
+/// The TTL or hop limit, and the IPv4 header checksum, which changes with
+/// it.
+const TTL_FIELDS: [FieldId; 2] = [FieldId::IpTtl, FieldId::IpChecksum];
+
+impl DedupOptions {
+    /// Sets the time window.
+    pub fn set_window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Enables or disables near-duplicate detection.
+    pub fn set_near_duplicates(mut self, near_duplicates: bool) -> Self {
+        self.near_duplicates = near_duplicates;
+        self
+    }
+
+    /// Sets the fields left out of near-duplicate signatures.
+    pub fn set_ignored(mut self, ignored: FieldMask) -> Self {
+        self.ignored = ignored;
+        self
+    }
+
+    /// Sets whether MAC addresses, the EtherType and VLAN tags take part
+    /// in near-duplicate signatures.
+    pub fn set_ignore_link_layer(self, ignore_link_layer: bool) -> Self {
+        self.set_fields(&LINK_LAYER_FIELDS, ignore_link_layer)
+    }
+
+    /// Sets whether the TTL takes part in near-duplicate signatures.
+    pub fn set_ignore_ttl(self, ignore_ttl: bool) -> Self {
+        self.set_fields(&TTL_FIELDS, ignore_ttl)
+    }
+
+    fn set_fields(mut self, fields: &[FieldId], ignore: bool) -> Self {
+        for &field in fields {
+            self.ignored = if ignore {
+                self.ignored.ignore(field)
+            } else {
+                self.ignored.compare(field)
+            };
+        }
+        self
+    }
+
+    /// Sets the keep policy.
+    pub fn set_keep(mut self, keep: KeepPolicy) -> Self {
+        self.keep = keep;
+        self
+    }
+}
+
+/// One copy of a duplicated packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DuplicateCopy {
+    /// Packet number in the capture (zero-based).
+    pub index: usize,
+    /// Byte offset of the record in the capture file.
+    pub offset: u64,
+    pub timestamp: Duration,
+    /// True if the bytes are identical to those of the first copy.
+    pub exact: bool,
+}
+
+/// Copies of the same packet, in capture order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateGroup {
+    pub copies: Vec<DuplicateCopy>,
+}
+
+impl DuplicateGroup {
+    /// Returns true if every copy is byte-for-byte identical.
+    pub fn is_exact(&self) -> bool {
+        self.copies.iter().all(|copy| copy.exact)
+    }
+
+    /// Delay of each later copy after the first one. For near-duplicates
+    /// seen at two capture points, this is the latency between them.
+    pub fn delays(&self) -> Vec<Duration> {
+        let first = self.copies[0].timestamp;
+        self.copies[1..]
+            .iter()
+            .map(|copy| copy.timestamp.saturating_sub(first))
+            .collect()
+    }
+}
+
+/// Result of `dedup`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DedupReport {
+    /// Number of packets read.
+    pub packets: usize,
+    pub groups: Vec<DuplicateGroup>,
+    pub keep: KeepPolicy,
+}
+
+impl DedupReport {
+    /// Numbers of the packets a cleaned capture leaves out.
+    pub fn dropped(&self) -> HashSet<usize> {
+        let mut dropped = HashSet::new();
+        for group in &self.groups {
+            let kept = match self.keep {
+                KeepPolicy::First => 0,
+                KeepPolicy::Last => group.copies.len() - 1,
+            };
+            for (position, copy) in group.copies.iter().enumerate() {
+                if position != kept {
+                    dropped.insert(copy.index);
+                }
+            }
+        }
+        dropped
+    }
+
+    /// Copies `reader` (the capture the report was made from, read again
+    /// from the start) to `writer`, leaving out the dropped duplicates.
+    /// Returns the number of packets written.
+    pub fn write_cleaned<R: Read, W: Write>(
+        &self,
+        reader: &mut Reader<R>,
+        writer: &mut Writer<W>,
+    ) -> io::Result<usize> {
+        let dropped = self.dropped();
+        let mut written = 0;
+        let mut index = 0;
+        while let Some(packet) = reader.next_packet()? {
+            if !dropped.contains(&index) {
+                writer.write_packet(&packet)?;
+                written += 1;
+            }
+            index += 1;
+        }
+        writer.flush()?;
+        Ok(written)
+    }
+}
+
+/// What copies of a packet have in common.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Signature {
+    Bytes(Vec<u8>),
+    Fields(Vec<(FieldId, String)>),
+}
+
+/// Latest copy of a signature still inside the window.
+struct Seen {
+    timestamp: Duration,
+    first_data: Vec<u8>,
+    first: DuplicateCopy,
+    group: Option<usize>,
+}
+
+/// Finds exact and near-duplicate packets in a capture. Two packets are
+/// copies when their signatures match and they are at most `window` apart;
+/// with `near_duplicates` disabled the signature is the whole frame.
+pub fn dedup<R: Read>(reader: &mut Reader<R>, opts: &DedupOptions) -> io::Result<DedupReport> {
+    let link_type = reader.link_type();
+    let mut seen: HashMap<Signature, Seen> = HashMap::new();
+    let mut recent: VecDeque<(Duration, Signature)> = VecDeque::new();
+    let mut groups: Vec<DuplicateGroup> = Vec::new();
+    let mut packets = 0;
+    loop {
+        let offset = Reader::position(reader);
+        let Some(packet) = reader.next_packet()? else {
+            break;
+        };
+        let timestamp = packet.timestamp;
+        while let Some((oldest, _)) = recent.front() {
+            if timestamp.saturating_sub(*oldest) <= opts.window {
+                break;
+            }
+            let (oldest, key) = recent.pop_front().unwrap();
+            if seen
+                .get(&key)
+                .is_some_and(|entry| entry.timestamp == oldest)
+            {
+                seen.remove(&key);
+            }
+        }
+
+        let key = if opts.near_duplicates {
+            Signature::Fields(signature(link_type, &packet.data, opts))
+        } else {
+            Signature::Bytes(packet.data.clone())
+        };
+        let mut copy = DuplicateCopy {
+            index: packets,
+            offset,
+            timestamp,
+            exact: true,
+        };
+        match seen.get_mut(&key) {
+            Some(entry) => {
+                copy.exact = packet.data == entry.first_data;
+                let group = *entry.group.get_or_insert_with(|| {
+                    groups.push(DuplicateGroup {
+                        copies: vec![entry.first],
+                    });
+                    groups.len() - 1
+                });
+                groups[group].copies.push(copy);
+                entry.timestamp = timestamp;
+            }
+            None => {
+                seen.insert(
+                    key.clone(),
+                    Seen {
+                        timestamp,
+                        first_data: packet.data,
+                        first: copy,
+                        group: None,
+                    },
+                );
+            }
+        }
+        recent.push_back((timestamp, key));
+        packets += 1;
+    }
+    Ok(DedupReport {
+        packets,
+        groups,
+        keep: opts.keep,
+    })
+}
+
+/// Fields of the frame that `opts` does not ignore. The layer names are
+/// left out: the fields present already tell the layers apart, and an
+/// ignored VLAN tag must not show up through them.
+pub(crate) fn signature(
+    link_type: u32,
+    frame: &[u8],
+    opts: &DedupOptions,
+) -> Vec<(FieldId, String)> {
+    opts.ignored
+        .ignore(FieldId::Layers)
+        .frame_fields(link_type, frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::net::Ipv4Addr;
+
+    use super::*;
+    use crate::ipv4::IPv4;
+    use crate::pcap::{CapturedPacket, GlobalHeader, LINKTYPE_IPV4, Resolution};
+    use crate::udp::{self, UDP};
+
+    fn packet(identification: u16, ttl: u8) -> Vec<u8> {
+        let udp = UDP::new(5000, 53, b"query".to_vec());
+        IPv4::with_payload(
+            Ipv4Addr::new(10, 0, 0, 1),
+            Ipv4Addr::new(10, 0, 0, 2),
+            udp::IP_PROTOCOL,
+            udp.to_bytes(),
+        )
+        .set_identification(identification)
+        .set_ttl(ttl)
+        .with_checksum()
+        .to_bytes()
+    }
+
+    fn capture(packets: &[(u64, Vec<u8>)]) -> Reader<Cursor<Vec<u8>>> {
+        let header = GlobalHeader::new(LINKTYPE_IPV4, 65535, Resolution::Micros);
+        let mut writer = Writer::new(Vec::new(), header).unwrap();
+        for (micros, data) in packets {
+            let timestamp = Duration::from_micros(*micros);
+            writer
+                .write_packet(&CapturedPacket::new(timestamp, data.clone()))
+                .unwrap();
+        }
+        Reader::new(Cursor::new(writer.into_inner())).unwrap()
+    }
+
+    #[test]
+    fn groups_copies_seen_at_two_capture_points() {
+        let packets = [
+            (0, packet(1, 64)),
+            (250, packet(1, 63)),
+            (300, packet(2, 64)),
+            (400, packet(2, 64)),
+        ];
+        let report = dedup(&mut capture(&packets), &DedupOptions::default()).unwrap();
+        assert_eq!(report.groups.len(), 2);
+        assert!(!report.groups[0].is_exact());
+        assert_eq!(report.groups[0].delays(), [Duration::from_micros(250)]);
+        assert!(report.groups[1].is_exact());
+        assert_eq!(report.dropped(), HashSet::from([1, 3]));
+
+        // With the TTL compared, only the exact copies remain.
+        let opts = DedupOptions::default().set_ignore_ttl(false);
+        let report = dedup(&mut capture(&packets), &opts).unwrap();
+        assert_eq!(report.groups.len(), 1);
+        assert_eq!(report.groups[0].copies[0].index, 2);
+    }
+
+    #[test]
+    fn signature_fields_come_from_the_mask() {
+        let packets = [(0, packet(1, 64)), (10, packet(2, 64))];
+        let report = dedup(&mut capture(&packets), &DedupOptions::default()).unwrap();
+        assert!(report.groups.is_empty());
+
+        // The volatile fields include the IP identification.
+        let opts = DedupOptions::default().set_ignored(FieldMask::volatile());
+        let report = dedup(&mut capture(&packets), &opts).unwrap();
+        assert_eq!(report.groups.len(), 1);
+        assert_eq!(report.keep, KeepPolicy::First);
+    }
+}
@@ -0,0 +1,299 @@
+use std::net::SocketAddr;
+
+use super::protocol::AppProtocol;
+use super::{FlowSegment, RoleInference, infer_roles};
+
+/// Default number of bytes per entropy window.
+pub const DEFAULT_WINDOW: usize = 256;
+
+/// Bytes compressed at a time by the incompressibility estimate.
+#[cfg(feature = "compression")]
+const COMPRESSION_BLOCK: usize = 64 * 1024;
+
+/// Limits above which a payload is considered encrypted or compressed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EntropyThresholds {
+    /// Shannon entropy, in bits per byte, of a high-entropy window. Random
+    /// data reaches about 7.3 over 256 bytes and 7.8 over 1024.
+    pub high_entropy: f64,
+    /// Compressed/original size ratio of an incompressible payload.
+    pub incompressible: f64,
+}
+
+impl Default for EntropyThresholds {
+    fn default() -> Self {
+        EntropyThresholds {
+            high_entropy: 7.0,
+            incompressible: 0.9,
+        }
+    }
+}
+
+/// Entropy of a payload over consecutive windows and as a whole.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct EntropyProfile {
+    /// Entropy of each full window, in bits per byte. A payload shorter
+    /// than one window yields a single entry for the whole payload.
+    pub windows: Vec<f64>,
+    /// Entropy of the whole payload, in bits per byte.
+    pub overall: f64,
+    /// Number of payload bytes.
+    pub bytes: u64,
+    /// Compressed/original size ratio after an LZ4 pass, when the
+    /// `compression` feature is enabled.
+    pub compression_ratio: Option<f64>,
+}
+
+impl EntropyProfile {
+    /// Returns true if the payload is high-entropy from its first byte on,
+    /// which suggests encryption with no plaintext handshake. The
+    /// compression ratio, when known, must also exceed its threshold.
+    pub fn high_entropy_from_start(&self, thresholds: &EntropyThresholds) -> bool {
+        let Some(first) = self.windows.first() else {
+            return false;
+        };
+        *first >= thresholds.high_entropy
+            && self.overall >= thresholds.high_entropy
+            && self
+                .compression_ratio
+                .is_none_or(|ratio| ratio >= thresholds.incompressible)
+    }
+
+    /// Index of the first high-entropy window of a payload whose first
+    /// window is below the threshold, if there is one.
+    pub fn entropy_rise(&self, thresholds: &EntropyThresholds) -> Option<usize> {
+        let (first, rest) = self.windows.split_first()?;
+        if *first >= thresholds.high_entropy {
+            return None;
+        }
+        rest.iter()
+            .position(|entropy| *entropy >= thresholds.high_entropy)
+            .map(|index| index + 1)
+    }
+}
+
+/// Streaming entropy calculator: payload is fed in pieces and only the
+/// current window (and compression block) is buffered.
+#[derive(Debug, Clone)]
+pub struct EntropyProfiler {
+    window: usize,
+    window_counts: [u64; 256],
+    window_len: usize,
+    total_counts: [u64; 256],
+    windows: Vec<f64>,
+    bytes: u64,
+    #[cfg(feature = "compression")]
+    block: Vec<u8>,
+    #[cfg(feature = "compression")]
+    compressed: u64,
+}
+
+/// Shannon entropy in bits per byte of the given byte counts.
+fn shannon(counts: &[u64; 256], total: u64) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    let total = total as f64;
+    counts
+        .iter()
+        .filter(|count| **count > 0)
+        .map(|count| {
+            let p = *count as f64 / total;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+impl EntropyProfiler {
+    /// Constructor to create a profiler with `window` bytes per window
+    /// (at least 1).
+    pub fn new(window: usize) -> Self {
+        EntropyProfiler {
+            window: window.max(1),
+            window_counts: [0; 256],
+            window_len: 0,
+            total_counts: [0; 256],
+            windows: Vec::new(),
+            bytes: 0,
+            #[cfg(feature = "compression")]
+            block: Vec::new(),
+            #[cfg(feature = "compression")]
+            compressed: 0,
+        }
+    }
+
+    /// Feeds the next piece of payload.
+    pub fn update(&mut self, data: &[u8]) {
+        for byte in data {
+            self.window_counts[*byte as usize] += 1;
+            self.total_counts[*byte as usize] += 1;
+            self.window_len += 1;
+            if self.window_len == self.window {
+                self.windows
+                    .push(shannon(&self.window_counts, self.window_len as u64));
+                self.window_counts = [0; 256];
+                self.window_len = 0;
+            }
+        }
+        self.bytes += data.len() as u64;
+
+        #[cfg(feature = "compression")]
+        {
+            let mut data = data;
+            while !data.is_empty() {
+                let take = (COMPRESSION_BLOCK - self.block.len()).min(data.len());
+                self.block.extend_from_slice(&data[..take]);
+                data = &data[take..];
+                if self.block.len() == COMPRESSION_BLOCK {
+                    self.flush_block();
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "compression")]
+    fn flush_block(&mut self) {
+        self.compressed += lz4_flex::block::compress(&self.block).len() as u64;
+        self.block.clear();
+    }
+
+    #[cfg(feature = "compression")]
+    fn compression_ratio(&mut self) -> Option<f64> {
+        if !self.block.is_empty() {
+            self.flush_block();
+        }
+        (self.bytes > 0).then(|| self.compressed as f64 / self.bytes as f64)
+    }
+
+    #[cfg(not(feature = "compression"))]
+    fn compression_ratio(&mut self) -> Option<f64> {
+        None
+    }
+
+    /// Finishes the profile.
+    pub fn finish(mut self) -> EntropyProfile {
+        if self.windows.is_empty() && self.window_len > 0 {
+            self.windows
+                .push(shannon(&self.window_counts, self.window_len as u64));
+        }
+        let compression_ratio = self.compression_ratio();
+        EntropyProfile {
+            overall: shannon(&self.total_counts, self.bytes),
+            windows: self.windows,
+            bytes: self.bytes,
+            compression_ratio,
+        }
+    }
+}
+
+/// Computes the entropy profile of a flow payload over windows of
+/// `window` bytes.
+pub fn entropy(flow_payload: &[u8], window: usize) -> EntropyProfile {
+    let mut profiler = EntropyProfiler::new(window);
+    profiler.update(flow_payload);
+    profiler.finish()
+}
+
+/// Entropy profiles of each direction of a TCP flow.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlowEntropy {
+    pub roles: RoleInference,
+    pub client_to_server: EntropyProfile,
+    pub server_to_client: EntropyProfile,
+}
+
+impl FlowEntropy {
+    /// Returns true if either direction is high-entropy from its first byte,
+    /// supporting evidence that the flow is encrypted or of unknown protocol.
+    pub fn looks_encrypted(&self, thresholds: &EntropyThresholds) -> bool {
+        self.client_to_server.high_entropy_from_start(thresholds)
+            || self.server_to_client.high_entropy_from_start(thresholds)
+    }
+
+    /// Alerts raised by the profiles of both directions, client to server
+    /// first.
+    pub fn alerts(&self, thresholds: &EntropyThresholds) -> Vec<EntropyAlert> {
+        let (client, server) = (self.roles.client, self.roles.server);
+        let mut alerts = Vec::new();
+        for (source, destination, profile) in [
+            (client, server, &self.client_to_server),
+            (server, client, &self.server_to_client),
+        ] {
+            let port = server.port();
+            let plaintext = AppProtocol::from_port(port).is_some_and(|p| p.is_plaintext());
+            let kind = if plaintext && profile.high_entropy_from_start(thresholds) {
+                Some(EntropyAlertKind::EncryptedOnPlaintextPort(port))
+            } else {
+                profile
+                    .entropy_rise(thresholds)
+                    .map(|window| EntropyAlertKind::EntropyRise { window })
+            };
+            if let Some(kind) = kind {
+                alerts.push(EntropyAlert {
+                    source,
+                    destination,
+                    kind,
+                });
+            }
+        }
+        alerts
+    }
+}
+
+// --- ALERTS ---
+
+/// Why an `EntropyAlert` was raised.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntropyAlertKind {
+    /// The direction is high-entropy from its first byte although the
+    /// server port belongs to a plaintext protocol: tunnelled or
+    /// disguised traffic.
+    EncryptedOnPlaintextPort(u16),
+    /// The direction starts below the high-entropy threshold and first
+    /// reaches it at the given window: STARTTLS, or an encrypted or
+    /// compressed blob after a plaintext preamble.
+    EntropyRise { window: usize },
+}
+
+/// Entropy anomaly in one direction of a flow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntropyAlert {
+    pub source: SocketAddr,
+    pub destination: SocketAddr,
+    pub kind: EntropyAlertKind,
+}
+
+/// Alerts of every flow, in flow order. Flows with no segments raise
+/// nothing.
+pub fn entropy_alerts<'a>(
+    flows: impl IntoIterator<Item = &'a [FlowSegment]>,
+    window: usize,
+    thresholds: &EntropyThresholds,
+) -> Vec<EntropyAlert> {
+    flows
+        .into_iter()
+        .filter_map(|segments| flow_entropy(segments, window))
+        .flat_map(|flow| flow.alerts(thresholds))
+        .collect()
+}
+
+/// Profiles the payload of each direction of a flow, in segment order
+/// (retransmissions are counted again). Returns `None` when `segments`
+/// is empty.
+pub fn flow_entropy(segments: &[FlowSegment], window: usize) -> Option<FlowEntropy> {
+    let roles = infer_roles(segments)?;
+    let mut client = EntropyProfiler::new(window);
+    let mut server = EntropyProfiler::new(window);
+    for segment in segments {
+        if segment.source == roles.client {
+            client.update(&segment.tcp.data);
+        } else {
+            server.update(&segment.tcp.data);
+        }
+    }
+    Some(FlowEntropy {
+        roles,
+        client_to_server: client.finish(),
+        server_to_client: server.finish(),
+    })
+}
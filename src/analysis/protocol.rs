@@ -0,0 +1,310 @@
+use std::net::SocketAddr;
+
+use super::entropy::{DEFAULT_WINDOW, EntropyThresholds, FlowEntropy, flow_entropy};
+use super::{Confidence, FlowSegment, infer_roles};
+
+/// Application protocol of a TCP flow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AppProtocol {
+    Http,
+    Http2,
+    Tls,
+    Ssh,
+    Smtp,
+    Ftp,
+    Pop3,
+    Imap,
+    Vnc,
+    /// No plaintext signature, but the payload is high-entropy from its
+    /// first byte: an encrypted or compressed protocol this detector does
+    /// not know.
+    EncryptedUnknown,
+    Unknown,
+}
+
+/// Observation that contributed to a `ProtocolDetection`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtocolEvidence {
+    /// The first payload of the client or server starts with a signature
+    /// of the protocol.
+    Signature,
+    /// The server uses the usual port of the protocol.
+    Port(u16),
+    /// A direction is high-entropy from its first byte.
+    HighEntropyFromStart,
+}
+
+/// Result of `detect_protocol`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProtocolDetection {
+    pub protocol: AppProtocol,
+    pub confidence: Confidence,
+    /// What the decision rests on, strongest first.
+    pub evidence: Vec<ProtocolEvidence>,
+    /// Entropy profiles of both directions.
+    pub entropy: FlowEntropy,
+}
+
+/// Prefixes of the first client payload.
+const CLIENT_SIGNATURES: &[(&[u8], AppProtocol)] = &[
+    (b"PRI * HTTP/2", AppProtocol::Http2),
+    (b"GET ", AppProtocol::Http),
+    (b"POST ", AppProtocol::Http),
+    (b"PUT ", AppProtocol::Http),
+    (b"HEAD ", AppProtocol::Http),
+    (b"DELETE ", AppProtocol::Http),
+    (b"OPTIONS ", AppProtocol::Http),
+    (b"CONNECT ", AppProtocol::Http),
+    (b"\x16\x03", AppProtocol::Tls),
+    (b"SSH-", AppProtocol::Ssh),
+];
+
+/// Prefixes of the first server payload.
+const SERVER_SIGNATURES: &[(&[u8], AppProtocol)] = &[
+    (b"HTTP/1.", AppProtocol::Http),
+    (b"\x16\x03", AppProtocol::Tls),
+    (b"SSH-", AppProtocol::Ssh),
+    (b"+OK", AppProtocol::Pop3),
+    (b"* OK", AppProtocol::Imap),
+    (b"* PREAUTH", AppProtocol::Imap),
+    (b"RFB ", AppProtocol::Vnc),
+];
+
+/// Usual server ports of the protocols.
+const PROTOCOL_PORTS: &[(u16, AppProtocol)] = &[
+    (21, AppProtocol::Ftp),
+    (22, AppProtocol::Ssh),
+    (25, AppProtocol::Smtp),
+    (80, AppProtocol::Http),
+    (110, AppProtocol::Pop3),
+    (143, AppProtocol::Imap),
+    (443, AppProtocol::Tls),
+    (465, AppProtocol::Tls),
+    (587, AppProtocol::Smtp),
+    (993, AppProtocol::Tls),
+    (995, AppProtocol::Tls),
+    (5900, AppProtocol::Vnc),
+    (8080, AppProtocol::Http),
+    (8443, AppProtocol::Tls),
+];
+
+impl AppProtocol {
+    /// Returns true if the protocol carries readable text or headers, so
+    /// that a payload high-entropy from its first byte is unexpected.
+    pub fn is_plaintext(&self) -> bool {
+        matches!(
+            self,
+            AppProtocol::Http
+                | AppProtocol::Smtp
+                | AppProtocol::Ftp
+                | AppProtocol::Pop3
+                | AppProtocol::Imap
+        )
+    }
+
+    /// Protocol usually served on `port`, if any.
+    pub fn from_port(port: u16) -> Option<AppProtocol> {
+        PROTOCOL_PORTS
+            .iter()
+            .find(|(known, _)| *known == port)
+            .map(|(_, protocol)| *protocol)
+    }
+}
+
+/// First payload sent from `source`, in segment order.
+fn first_payload(segments: &[FlowSegment], source: SocketAddr) -> Option<&[u8]> {
+    segments
+        .iter()
+        .find(|segment| segment.source == source && !segment.tcp.data.is_empty())
+        .map(|segment| segment.tcp.data.as_slice())
+}
+
+fn signature(payload: Option<&[u8]>, signatures: &[(&[u8], AppProtocol)]) -> Option<AppProtocol> {
+    let payload = payload?;
+    signatures
+        .iter()
+        .find(|(prefix, _)| payload.starts_with(prefix))
+        .map(|(_, protocol)| *protocol)
+}
+
+/// Detects the application protocol of a TCP flow.
+///
+/// A payload signature of either side decides with high confidence; a
+/// `220` greeting is SMTP or FTP depending on the port. Without one, a
+/// flow high-entropy from its first byte is `EncryptedUnknown`, unless
+/// the server port names an encrypted protocol, which the entropy then
+/// supports. Otherwise the port alone decides with low confidence.
+/// Returns `None` when `segments` is empty.
+pub fn detect_protocol(
+    segments: &[FlowSegment],
+    thresholds: &EntropyThresholds,
+) -> Option<ProtocolDetection> {
+    let roles = infer_roles(segments)?;
+    let entropy = flow_entropy(segments, DEFAULT_WINDOW)?;
+    let client_payload = first_payload(segments, roles.client);
+    let server_payload = first_payload(segments, roles.server);
+    let port = roles.server.port();
+    let by_port = AppProtocol::from_port(port);
+
+    let greeting = server_payload
+        .filter(|payload| payload.starts_with(b"220"))
+        .map(|_| match by_port {
+            Some(AppProtocol::Ftp) => AppProtocol::Ftp,
+            _ => AppProtocol::Smtp,
+        });
+    let signature = signature(client_payload, CLIENT_SIGNATURES)
+        .or_else(|| signature(server_payload, SERVER_SIGNATURES))
+        .or(greeting);
+    let high_entropy = entropy.looks_encrypted(thresholds);
+
+    let (protocol, confidence, mut evidence) = match (signature, by_port) {
+        (Some(protocol), _) => (
+            protocol,
+            Confidence::High,
+            vec![ProtocolEvidence::Signature],
+        ),
+        (None, Some(protocol)) if high_entropy && !protocol.is_plaintext() => (
+            protocol,
+            Confidence::Medium,
+            vec![
+                ProtocolEvidence::Port(port),
+                ProtocolEvidence::HighEntropyFromStart,
+            ],
+        ),
+        (None, _) if high_entropy => (
+            AppProtocol::EncryptedUnknown,
+            Confidence::Medium,
+            vec![ProtocolEvidence::HighEntropyFromStart],
+        ),
+        (None, Some(protocol)) => (
+            protocol,
+            Confidence::Low,
+            vec![ProtocolEvidence::Port(port)],
+        ),
+        (None, None) => (AppProtocol::Unknown, Confidence::Low, Vec::new()),
+    };
+    if signature.is_some() && by_port == signature {
+        evidence.push(ProtocolEvidence::Port(port));
+    }
+    Some(ProtocolDetection {
+        protocol,
+        confidence,
+        evidence,
+        entropy,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::EntropyAlertKind;
+    use crate::tcp::{TCP, flags};
+
+    /// Deterministic bytes with close to 8 bits of entropy per byte.
+    fn noise(len: usize) -> Vec<u8> {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state >> 24) as u8
+            })
+            .collect()
+    }
+
+    fn flow(server_port: u16, client_data: Vec<u8>, server_data: Vec<u8>) -> Vec<FlowSegment> {
+        let client: SocketAddr = "10.0.0.1:40000".parse().unwrap();
+        let server = SocketAddr::new("10.0.0.2".parse().unwrap(), server_port);
+        let syn = TCP::segment(40000, server_port, 0, 0, flags::SYN);
+        let syn_ack = TCP::segment(server_port, 40000, 0, 1, flags::SYN | flags::ACK);
+        let request = TCP::segment(40000, server_port, 1, 1, flags::ACK).set_data(client_data);
+        let response = TCP::segment(server_port, 40000, 1, 1, flags::ACK).set_data(server_data);
+        vec![
+            FlowSegment::new(client, server, syn),
+            FlowSegment::new(server, client, syn_ack),
+            FlowSegment::new(client, server, request),
+            FlowSegment::new(server, client, response),
+        ]
+    }
+
+    #[test]
+    fn signatures_decide_over_ports_and_entropy() {
+        let thresholds = EntropyThresholds::default();
+        let http = flow(8000, b"GET / HTTP/1.1\r\n\r\n".to_vec(), noise(4096));
+        let detection = detect_protocol(&http, &thresholds).unwrap();
+        assert_eq!(detection.protocol, AppProtocol::Http);
+        assert_eq!(detection.confidence, Confidence::High);
+        assert_eq!(detection.evidence, [ProtocolEvidence::Signature]);
+
+        let ftp = flow(
+            21,
+            b"USER anonymous\r\n".to_vec(),
+            b"220 ready\r\n".to_vec(),
+        );
+        let detection = detect_protocol(&ftp, &thresholds).unwrap();
+        assert_eq!(detection.protocol, AppProtocol::Ftp);
+        assert_eq!(
+            detection.evidence,
+            [ProtocolEvidence::Signature, ProtocolEvidence::Port(21)]
+        );
+    }
+
+    #[test]
+    fn high_entropy_from_the_first_byte_means_encrypted() {
+        let thresholds = EntropyThresholds::default();
+        let unknown = flow(40001, noise(2048), noise(2048));
+        let detection = detect_protocol(&unknown, &thresholds).unwrap();
+        assert_eq!(detection.protocol, AppProtocol::EncryptedUnknown);
+        assert_eq!(detection.evidence, [ProtocolEvidence::HighEntropyFromStart]);
+
+        // The entropy supports the port of an encrypted protocol.
+        let tls = flow(443, noise(2048), noise(2048));
+        let detection = detect_protocol(&tls, &thresholds).unwrap();
+        assert_eq!(detection.protocol, AppProtocol::Tls);
+        assert_eq!(detection.confidence, Confidence::Medium);
+
+        // Text on an unknown port stays unknown.
+        let text = flow(
+            40001,
+            b"hello there".repeat(100),
+            b"general kenobi".repeat(100),
+        );
+        let detection = detect_protocol(&text, &thresholds).unwrap();
+        assert_eq!(detection.protocol, AppProtocol::Unknown);
+
+        // Thresholds above what random data reaches disable the signal.
+        let strict = EntropyThresholds {
+            high_entropy: 7.99,
+            ..thresholds
+        };
+        let detection = detect_protocol(&unknown, &strict).unwrap();
+        assert_eq!(detection.protocol, AppProtocol::Unknown);
+    }
+
+    #[test]
+    fn alerts_on_encryption_where_plaintext_is_expected() {
+        let thresholds = EntropyThresholds::default();
+        let tunnel = flow(80, noise(2048), b"HTTP/1.1 200 OK\r\n\r\n".to_vec());
+        let alerts = flow_entropy(&tunnel, DEFAULT_WINDOW)
+            .unwrap()
+            .alerts(&thresholds);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].source, tunnel[0].source);
+        assert_eq!(
+            alerts[0].kind,
+            EntropyAlertKind::EncryptedOnPlaintextPort(80)
+        );
+
+        // Plaintext for three windows, then an encrypted blob.
+        let mut upload = b"STARTTLS please ".repeat(48);
+        upload.extend(noise(1024));
+        let starttls = flow(25, upload, b"220 ok\r\n".to_vec());
+        let alerts = crate::analysis::entropy_alerts([starttls.as_slice()], 256, &thresholds);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].kind, EntropyAlertKind::EntropyRise { window: 3 });
+
+        let quiet = flow(443, noise(2048), noise(2048));
+        assert!(crate::analysis::entropy_alerts([quiet.as_slice()], 256, &thresholds).is_empty());
+    }
+}
@@ -0,0 +1,172 @@
+use std::net::Ipv4Addr;
+
+use crate::ethernet::{ETHERTYPE_ARP, ETHERTYPE_IPV4, Ethernet, MacAddr, read_mac};
+use crate::util::{Ipv4Cidr, ParseError, ensure_len, read_ipv4};
+
+// ARP over Ethernet for IPv4 (RFC 826)
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |         Hardware Type         |         Protocol Type         |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |  HW Addr Len  | Proto Addr Len|           Operation           |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                 Sender Hardware Address (6)                   |
+// +                               +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                               |  Sender Protocol Address (4)  |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                               |                               |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+                               +
+// |                 Target Hardware Address (6)                   |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                  Target Protocol Address (4)                  |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+/// Length of an Ethernet/IPv4 ARP packet in bytes.
+pub const PACKET_LEN: usize = 28;
+
+/// Hardware type of Ethernet.
+pub const HARDWARE_ETHERNET: u16 = 1;
+
+// Operations.
+pub const OPERATION_REQUEST: u16 = 1;
+pub const OPERATION_REPLY: u16 = 2;
+
+/// ARP packet mapping IPv4 addresses to MAC addresses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Arp {
+    pub hardware_type: u16,
+    pub protocol_type: u16,
+    pub operation: u16,
+    pub sender_hw: MacAddr,
+    pub sender_ip: Ipv4Addr,
+    pub target_hw: MacAddr,
+    pub target_ip: Ipv4Addr,
+}
+
+impl Arp {
+    /// Constructor to create a request from `sender_hw`/`sender_ip` asking
+    /// for the MAC address of `target_ip`, with the broadcast address as
+    /// target hardware address.
+    pub fn request(sender_hw: MacAddr, sender_ip: Ipv4Addr, target_ip: Ipv4Addr) -> Self {
+        Arp {
+            hardware_type: HARDWARE_ETHERNET,
+            protocol_type: ETHERTYPE_IPV4,
+            operation: OPERATION_REQUEST,
+            sender_hw,
+            sender_ip,
+            target_hw: MacAddr::BROADCAST,
+            target_ip,
+        }
+    }
+
+    /// Reply to `request` announcing that its target IP address is at
+    /// `sender_hw`.
+    pub fn reply(request: &Arp, sender_hw: MacAddr) -> Self {
+        Arp {
+            hardware_type: request.hardware_type,
+            protocol_type: request.protocol_type,
+            operation: OPERATION_REPLY,
+            sender_hw,
+            sender_ip: request.target_ip,
+            target_hw: request.sender_hw,
+            target_ip: request.sender_ip,
+        }
+    }
+
+    /// Probe checking that `target_ip` is unused before claiming it (RFC
+    /// 5227 section 2.1.1): a request with an all-zero sender IP address
+    /// and target hardware address.
+    pub fn probe(sender_hw: MacAddr, target_ip: Ipv4Addr) -> Self {
+        Arp {
+            target_hw: MacAddr([0; 6]),
+            ..Arp::request(sender_hw, Ipv4Addr::UNSPECIFIED, target_ip)
+        }
+    }
+
+    /// Gratuitous ARP announcing that `ip` is at `sender_hw` (RFC 5227
+    /// section 2.3): a request with `ip` as both sender and target, which
+    /// updates the caches of the hosts that know `ip`.
+    pub fn gratuitous(sender_hw: MacAddr, ip: Ipv4Addr) -> Self {
+        Arp {
+            target_hw: MacAddr([0; 6]),
+            ..Arp::request(sender_hw, ip, ip)
+        }
+    }
+
+    /// Wraps the packet in a frame from its sender hardware address,
+    /// broadcast for requests and sent to the target hardware address
+    /// otherwise.
+    pub fn to_ethernet(&self) -> Ethernet {
+        let destination = if self.operation == OPERATION_REQUEST {
+            MacAddr::BROADCAST
+        } else {
+            self.target_hw
+        };
+        Ethernet::new(destination, self.sender_hw, ETHERTYPE_ARP, self.to_bytes())
+    }
+
+    /// Requests for every host address of `network` (see
+    /// `Ipv4Cidr::hosts`), as sent by a discovery scan.
+    pub fn scan(
+        network: Ipv4Cidr,
+        src_mac: MacAddr,
+        src_ip: Ipv4Addr,
+    ) -> impl Iterator<Item = Arp> {
+        network
+            .hosts()
+            .map(move |target_ip| Arp::request(src_mac, src_ip, target_ip))
+    }
+
+    // --- SERIALIZATION ---
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(PACKET_LEN);
+        bytes.extend_from_slice(&self.hardware_type.to_be_bytes());
+        bytes.extend_from_slice(&self.protocol_type.to_be_bytes());
+        bytes.extend_from_slice(&[6, 4]);
+        bytes.extend_from_slice(&self.operation.to_be_bytes());
+        bytes.extend_from_slice(&self.sender_hw.octets());
+        bytes.extend_from_slice(&self.sender_ip.octets());
+        bytes.extend_from_slice(&self.target_hw.octets());
+        bytes.extend_from_slice(&self.target_ip.octets());
+        bytes
+    }
+
+    /// Parses an ARP packet with 6-byte hardware and 4-byte protocol
+    /// addresses; trailing bytes (e.g. Ethernet padding) are ignored.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, PACKET_LEN)?;
+        if buf[4] != 6 || buf[5] != 4 {
+            return Err(ParseError::InvalidField("address length"));
+        }
+        Ok(Arp {
+            hardware_type: u16::from_be_bytes([buf[0], buf[1]]),
+            protocol_type: u16::from_be_bytes([buf[2], buf[3]]),
+            operation: u16::from_be_bytes([buf[6], buf[7]]),
+            sender_hw: read_mac(buf, 8),
+            sender_ip: read_ipv4(buf, 14),
+            target_hw: read_mac(buf, 18),
+            target_ip: read_ipv4(buf, 24),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scan_of_a_slash_24() {
+        let src_mac = MacAddr([0x02, 0, 0, 0, 0, 1]);
+        let src_ip = Ipv4Addr::new(192, 168, 1, 10);
+        let requests: Vec<Arp> =
+            Arp::scan("192.168.1.0/24".parse().unwrap(), src_mac, src_ip).collect();
+        assert_eq!(requests.len(), 254);
+        for (i, request) in requests.iter().enumerate() {
+            assert_eq!(request.target_ip, Ipv4Addr::new(192, 168, 1, i as u8 + 1));
+            assert_eq!(request.target_hw, MacAddr::BROADCAST);
+            assert_eq!(request.operation, OPERATION_REQUEST);
+            assert_eq!((request.sender_hw, request.sender_ip), (src_mac, src_ip));
+        }
+    }
+}
@@ -0,0 +1,360 @@
+use std::time::{Duration, Instant};
+
+use crate::util::{ParseError, ensure_len};
+
+// BFD Control packet (RFC 5880 section 4.1), carried in UDP
+//
+//  0                   1                   2                   3
+//  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |Vers |  Diag   |Sta|P|F|C|A|D|M|  Detect Mult  |    Length     |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                       My Discriminator                        |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                      Your Discriminator                       |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                    Desired Min TX Interval                    |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                   Required Min RX Interval                    |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                 Required Min Echo RX Interval                 |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// :           Optional Authentication Section                     :
+//
+// Intervals are in microseconds.
+
+/// UDP destination port of single-hop control packets (RFC 5881).
+pub const UDP_PORT: u16 = 3784;
+/// UDP destination port of multihop control packets (RFC 5883).
+pub const UDP_PORT_MULTIHOP: u16 = 4784;
+
+/// BFD version carried in every packet.
+pub const VERSION: u8 = 1;
+
+/// Length of a control packet without authentication, in bytes.
+pub const HEADER_LEN: usize = 24;
+
+// Flags.
+pub const FLAG_POLL: u8 = 0x20;
+pub const FLAG_FINAL: u8 = 0x10;
+pub const FLAG_CONTROL_PLANE_INDEPENDENT: u8 = 0x08;
+pub const FLAG_AUTH_PRESENT: u8 = 0x04;
+pub const FLAG_DEMAND: u8 = 0x02;
+pub const FLAG_MULTIPOINT: u8 = 0x01;
+
+/// Slowest transmit interval used while the session is not up, in
+/// microseconds (RFC 5880 section 6.8.3).
+pub const SLOW_TX_INTERVAL: u32 = 1_000_000;
+
+/// Session state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BfdState {
+    AdminDown = 0,
+    Down = 1,
+    Init = 2,
+    Up = 3,
+}
+
+impl From<u8> for BfdState {
+    fn from(value: u8) -> Self {
+        match value & 0x3 {
+            0 => BfdState::AdminDown,
+            1 => BfdState::Down,
+            2 => BfdState::Init,
+            _ => BfdState::Up,
+        }
+    }
+}
+
+/// Diagnostic code: why the session last left the Up state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BfdDiag {
+    None,
+    ControlDetectionTimeExpired,
+    EchoFunctionFailed,
+    NeighborSignaledSessionDown,
+    ForwardingPlaneReset,
+    PathDown,
+    ConcatenatedPathDown,
+    AdministrativelyDown,
+    ReverseConcatenatedPathDown,
+    Reserved(u8),
+}
+
+impl From<u8> for BfdDiag {
+    fn from(value: u8) -> Self {
+        match value & 0x1f {
+            0 => BfdDiag::None,
+            1 => BfdDiag::ControlDetectionTimeExpired,
+            2 => BfdDiag::EchoFunctionFailed,
+            3 => BfdDiag::NeighborSignaledSessionDown,
+            4 => BfdDiag::ForwardingPlaneReset,
+            5 => BfdDiag::PathDown,
+            6 => BfdDiag::ConcatenatedPathDown,
+            7 => BfdDiag::AdministrativelyDown,
+            8 => BfdDiag::ReverseConcatenatedPathDown,
+            other => BfdDiag::Reserved(other),
+        }
+    }
+}
+
+impl From<BfdDiag> for u8 {
+    fn from(value: BfdDiag) -> Self {
+        match value {
+            BfdDiag::None => 0,
+            BfdDiag::ControlDetectionTimeExpired => 1,
+            BfdDiag::EchoFunctionFailed => 2,
+            BfdDiag::NeighborSignaledSessionDown => 3,
+            BfdDiag::ForwardingPlaneReset => 4,
+            BfdDiag::PathDown => 5,
+            BfdDiag::ConcatenatedPathDown => 6,
+            BfdDiag::AdministrativelyDown => 7,
+            BfdDiag::ReverseConcatenatedPathDown => 8,
+            BfdDiag::Reserved(other) => other & 0x1f,
+        }
+    }
+}
+
+/// BFD Control packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bfd {
+    pub version: u8,
+    pub diag: BfdDiag,
+    pub state: BfdState,
+    /// P, F, C, A, D and M bits (`FLAG_*`).
+    pub flags: u8,
+    pub detect_mult: u8,
+    pub my_discriminator: u32,
+    pub your_discriminator: u32,
+    pub desired_min_tx: u32,
+    pub required_min_rx: u32,
+    pub required_min_echo_rx: u32,
+    /// Authentication section, kept as it is.
+    pub auth: Vec<u8>,
+}
+
+impl Bfd {
+    /// Constructor to create a control packet without authentication.
+    pub fn new(state: BfdState, my_discriminator: u32, your_discriminator: u32) -> Self {
+        Bfd {
+            version: VERSION,
+            diag: BfdDiag::None,
+            state,
+            flags: 0,
+            detect_mult: 3,
+            my_discriminator,
+            your_discriminator,
+            desired_min_tx: SLOW_TX_INTERVAL,
+            required_min_rx: SLOW_TX_INTERVAL,
+            required_min_echo_rx: 0,
+            auth: Vec::new(),
+        }
+    }
+
+    /// Returns true if all of `flags` are set.
+    pub fn has_flags(&self, flags: u8) -> bool {
+        self.flags & flags == flags
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.auth.len());
+        let flags = if self.auth.is_empty() {
+            self.flags & !FLAG_AUTH_PRESENT
+        } else {
+            self.flags | FLAG_AUTH_PRESENT
+        };
+        bytes.push((self.version & 0x7) << 5 | u8::from(self.diag));
+        bytes.push((self.state as u8) << 6 | flags & 0x3f);
+        bytes.push(self.detect_mult);
+        bytes.push((HEADER_LEN + self.auth.len()) as u8);
+        for field in [
+            self.my_discriminator,
+            self.your_discriminator,
+            self.desired_min_tx,
+            self.required_min_rx,
+            self.required_min_echo_rx,
+        ] {
+            bytes.extend_from_slice(&field.to_be_bytes());
+        }
+        bytes.extend_from_slice(&self.auth);
+        bytes
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, HEADER_LEN)?;
+        let length = buf[3] as usize;
+        if length < HEADER_LEN {
+            return Err(ParseError::InvalidField("length"));
+        }
+        ensure_len(buf, length)?;
+        let word = |offset: usize| {
+            u32::from_be_bytes([
+                buf[offset],
+                buf[offset + 1],
+                buf[offset + 2],
+                buf[offset + 3],
+            ])
+        };
+        Ok(Bfd {
+            version: buf[0] >> 5,
+            diag: BfdDiag::from(buf[0]),
+            state: BfdState::from(buf[1] >> 6),
+            flags: buf[1] & 0x3f,
+            detect_mult: buf[2],
+            my_discriminator: word(4),
+            your_discriminator: word(8),
+            desired_min_tx: word(12),
+            required_min_rx: word(16),
+            required_min_echo_rx: word(20),
+            auth: buf[HEADER_LEN..length].to_vec(),
+        })
+    }
+}
+
+/// State of one BFD session in asynchronous mode (RFC 5880 section 6.8).
+/// Demand mode, echo and authentication are not handled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BfdSession {
+    pub local_disc: u32,
+    pub remote_disc: u32,
+    pub session_state: BfdState,
+    pub remote_state: BfdState,
+    pub local_diag: BfdDiag,
+    /// Intervals in microseconds.
+    pub desired_min_tx: u32,
+    pub required_min_rx: u32,
+    pub detect_mult: u8,
+    /// Last values advertised by the remote system.
+    pub remote_desired_min_tx: u32,
+    pub remote_min_rx: u32,
+    pub remote_detect_mult: u8,
+    pub last_rx: Option<Instant>,
+}
+
+impl BfdSession {
+    /// Constructor to create a session in the Down state.
+    pub fn new(
+        local_disc: u32,
+        desired_min_tx: u32,
+        required_min_rx: u32,
+        detect_mult: u8,
+    ) -> Self {
+        BfdSession {
+            local_disc,
+            remote_disc: 0,
+            session_state: BfdState::Down,
+            remote_state: BfdState::Down,
+            local_diag: BfdDiag::None,
+            desired_min_tx,
+            required_min_rx,
+            detect_mult,
+            remote_desired_min_tx: 0,
+            remote_min_rx: 1,
+            remote_detect_mult: 0,
+            last_rx: None,
+        }
+    }
+
+    /// Control packet describing the session. While the session is not
+    /// up, the transmit interval is at least one second.
+    pub fn control_packet(&self) -> Bfd {
+        let desired_min_tx = if self.session_state == BfdState::Up {
+            self.desired_min_tx
+        } else {
+            self.desired_min_tx.max(SLOW_TX_INTERVAL)
+        };
+        Bfd {
+            diag: self.local_diag,
+            detect_mult: self.detect_mult,
+            desired_min_tx,
+            required_min_rx: self.required_min_rx,
+            ..Bfd::new(self.session_state, self.local_disc, self.remote_disc)
+        }
+    }
+
+    /// Processes a received control packet (RFC 5880 section 6.8.6).
+    /// Returns the packet to send right away: the answer to a poll, or an
+    /// update after a state change. Invalid packets are discarded.
+    pub fn process_packet(&mut self, pkt: &Bfd) -> Option<Bfd> {
+        if pkt.version != VERSION
+            || pkt.detect_mult == 0
+            || pkt.has_flags(FLAG_MULTIPOINT)
+            || pkt.my_discriminator == 0
+        {
+            return None;
+        }
+        if pkt.your_discriminator == 0 {
+            if !matches!(pkt.state, BfdState::Down | BfdState::AdminDown) {
+                return None;
+            }
+        } else if pkt.your_discriminator != self.local_disc {
+            return None;
+        }
+
+        self.remote_disc = pkt.my_discriminator;
+        self.remote_state = pkt.state;
+        self.remote_desired_min_tx = pkt.desired_min_tx;
+        self.remote_min_rx = pkt.required_min_rx;
+        self.remote_detect_mult = pkt.detect_mult;
+        if self.session_state == BfdState::AdminDown {
+            return None;
+        }
+        self.last_rx = Some(Instant::now());
+
+        let previous = self.session_state;
+        if pkt.state == BfdState::AdminDown {
+            if self.session_state != BfdState::Down {
+                self.local_diag = BfdDiag::NeighborSignaledSessionDown;
+                self.session_state = BfdState::Down;
+            }
+        } else {
+            match (self.session_state, pkt.state) {
+                (BfdState::Down, BfdState::Down) => self.session_state = BfdState::Init,
+                (BfdState::Down, BfdState::Init) => self.session_state = BfdState::Up,
+                (BfdState::Init, BfdState::Init | BfdState::Up) => {
+                    self.session_state = BfdState::Up
+                }
+                (BfdState::Up, BfdState::Down) => {
+                    self.local_diag = BfdDiag::NeighborSignaledSessionDown;
+                    self.session_state = BfdState::Down;
+                }
+                _ => {}
+            }
+        }
+
+        if pkt.has_flags(FLAG_POLL) {
+            let mut response = self.control_packet();
+            response.flags |= FLAG_FINAL;
+            return Some(response);
+        }
+        (self.session_state != previous).then(|| self.control_packet())
+    }
+
+    /// Detection time of the session: the remote detect multiplier times
+    /// the agreed remote transmit interval.
+    pub fn detection_time(&self) -> Duration {
+        let interval = self.required_min_rx.max(self.remote_desired_min_tx);
+        Duration::from_micros(self.remote_detect_mult as u64 * interval as u64)
+    }
+
+    /// Returns true if the session is in Init or Up and no valid packet was
+    /// received within the detection time.
+    pub fn is_timed_out(&self) -> bool {
+        matches!(self.session_state, BfdState::Init | BfdState::Up)
+            && self
+                .last_rx
+                .is_some_and(|last_rx| last_rx.elapsed() > self.detection_time())
+    }
+
+    /// Takes the session down if it timed out, recording the diagnostic.
+    /// Returns true if it did.
+    pub fn expire(&mut self) -> bool {
+        if !self.is_timed_out() {
+            return false;
+        }
+        self.session_state = BfdState::Down;
+        self.local_diag = BfdDiag::ControlDetectionTimeExpired;
+        self.remote_disc = 0;
+        true
+    }
+}
@@ -0,0 +1,54 @@
+// BGP-4 (RFC 4271). Messages start with a 19-byte header:
+//
+// +-----------------------------------------------+
+// |                 Marker (16 bytes)             |
+// +-----------------------+-----------+-----------+
+// |   Length (2 bytes)    |   Type    |
+// +-----------------------+-----------+
+//
+// Capabilities (RFC 5492) are advertised in the optional parameters of
+// OPEN messages.
+
+pub mod as_path;
+pub mod bmp;
+pub mod capability;
+
+pub use as_path::{AsPath, AsPathSegment};
+
+use crate::util::{ParseError, ensure_len};
+
+/// TCP port of BGP.
+pub const PORT: u16 = 179;
+
+/// Length of the message header in bytes.
+pub const HEADER_LEN: usize = 19;
+
+// Message types.
+pub const MSG_OPEN: u8 = 1;
+pub const MSG_UPDATE: u8 = 2;
+pub const MSG_NOTIFICATION: u8 = 3;
+pub const MSG_KEEPALIVE: u8 = 4;
+
+/// Optional parameter type carrying capabilities in an OPEN message.
+pub const OPT_PARAM_CAPABILITIES: u8 = 2;
+
+// Address family identifiers and subsequent address family identifiers.
+pub const AFI_IPV4: u16 = 1;
+pub const AFI_IPV6: u16 = 2;
+pub const SAFI_UNICAST: u8 = 1;
+pub const SAFI_MULTICAST: u8 = 2;
+
+/// Length of the BGP message at the start of `buf`, from its header. Fails
+/// if the marker is not all ones or the message is incomplete.
+pub fn message_len(buf: &[u8]) -> Result<usize, ParseError> {
+    ensure_len(buf, HEADER_LEN)?;
+    if buf[..16].iter().any(|byte| *byte != 0xff) {
+        return Err(ParseError::InvalidField("marker"));
+    }
+    let length = u16::from_be_bytes([buf[16], buf[17]]) as usize;
+    if length < HEADER_LEN {
+        return Err(ParseError::InvalidField("length"));
+    }
+    ensure_len(buf, length)?;
+    Ok(length)
+}
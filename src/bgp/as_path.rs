@@ -0,0 +1,225 @@
+use crate::util::{ParseError, ensure_len};
+
+// AS_PATH attribute value (RFC 4271 section 4.3, with the 4-octet AS
+// numbers of RFC 6793), a list of segments:
+//
+// +------------------------------+
+// | Segment Type (1 octet)       |
+// +------------------------------+
+// | Segment Length (1 octet)     |  number of ASes
+// +------------------------------+
+// | ASes (4 octets each)         |
+// +------------------------------+
+
+/// Path attribute type code of AS_PATH.
+pub const ATTR_AS_PATH: u8 = 2;
+
+// Segment types.
+pub const SEGMENT_AS_SET: u8 = 1;
+pub const SEGMENT_AS_SEQUENCE: u8 = 2;
+
+/// Most ASes a segment holds.
+pub const MAX_SEGMENT_LEN: usize = 255;
+
+/// A segment of an AS path.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AsPathSegment {
+    /// Unordered ASes a route traversed, as left by aggregation.
+    Set(Vec<u32>),
+    /// ASes in the order the route traversed them, the most recent first.
+    Sequence(Vec<u32>),
+}
+
+impl AsPathSegment {
+    pub fn get_segment_type(&self) -> u8 {
+        match self {
+            AsPathSegment::Set(_) => SEGMENT_AS_SET,
+            AsPathSegment::Sequence(_) => SEGMENT_AS_SEQUENCE,
+        }
+    }
+
+    pub fn get_asns(&self) -> &[u32] {
+        match self {
+            AsPathSegment::Set(asns) | AsPathSegment::Sequence(asns) => asns,
+        }
+    }
+
+    /// Length of the segment for best-path selection: each AS of a
+    /// sequence counts, a whole set counts as one (RFC 4271 section
+    /// 9.1.2.2).
+    pub fn path_length(&self) -> usize {
+        match self {
+            AsPathSegment::Set(asns) => asns.len().min(1),
+            AsPathSegment::Sequence(asns) => asns.len(),
+        }
+    }
+
+    // --- SERIALIZATION ---
+
+    /// Serializes the segment; ASes beyond `MAX_SEGMENT_LEN` are left out.
+    pub fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        let asns = self.get_asns();
+        let asns = &asns[..asns.len().min(MAX_SEGMENT_LEN)];
+        bytes.push(self.get_segment_type());
+        bytes.push(asns.len() as u8);
+        for asn in asns {
+            bytes.extend_from_slice(&asn.to_be_bytes());
+        }
+    }
+
+    /// Parses a segment, returning it and the number of bytes consumed.
+    pub fn from_bytes(buf: &[u8]) -> Result<(Self, usize), ParseError> {
+        ensure_len(buf, 2)?;
+        let len = 2 + buf[1] as usize * 4;
+        ensure_len(buf, len)?;
+        let asns = buf[2..len]
+            .chunks_exact(4)
+            .map(|asn| u32::from_be_bytes([asn[0], asn[1], asn[2], asn[3]]))
+            .collect();
+        let segment = match buf[0] {
+            SEGMENT_AS_SET => AsPathSegment::Set(asns),
+            SEGMENT_AS_SEQUENCE => AsPathSegment::Sequence(asns),
+            _ => return Err(ParseError::InvalidField("segment type")),
+        };
+        Ok((segment, len))
+    }
+}
+
+/// The AS_PATH of a route.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct AsPath {
+    pub segments: Vec<AsPathSegment>,
+}
+
+impl AsPath {
+    /// Constructor to create a path of `segments`.
+    pub fn new(segments: Vec<AsPathSegment>) -> Self {
+        AsPath { segments }
+    }
+
+    /// Prepends `asn` `times` times, as a speaker advertising the route
+    /// does once and route policies do more to make the path look longer.
+    /// The ASes go into the first segment if it is a sequence with room,
+    /// otherwise into new sequences in front of it.
+    pub fn prepend(mut self, asn: u32, times: u8) -> AsPath {
+        for _ in 0..times {
+            match self.segments.first_mut() {
+                Some(AsPathSegment::Sequence(asns)) if asns.len() < MAX_SEGMENT_LEN => {
+                    asns.insert(0, asn)
+                }
+                _ => self.segments.insert(0, AsPathSegment::Sequence(vec![asn])),
+            }
+        }
+        self
+    }
+
+    /// Length of the path compared in best-path selection: the number of
+    /// ASes in sequences, plus one per set.
+    pub fn length(&self) -> usize {
+        self.segments.iter().map(AsPathSegment::path_length).sum()
+    }
+
+    /// Returns true if `asn` appears in any segment.
+    pub fn contains_as(&self, asn: u32) -> bool {
+        self.segments
+            .iter()
+            .any(|segment| segment.get_asns().contains(&asn))
+    }
+
+    /// Returns true if a route with this path must be rejected by the
+    /// speaker of `local_asn` because it already went through it.
+    pub fn is_loop(&self, local_asn: u32) -> bool {
+        self.contains_as(local_asn)
+    }
+
+    // --- SERIALIZATION ---
+
+    /// Serializes the attribute value.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for segment in &self.segments {
+            segment.serialize_into(&mut bytes);
+        }
+        bytes
+    }
+
+    /// Parses an attribute value of 4-octet ASes.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        let mut segments = Vec::new();
+        let mut offset = 0;
+        while offset < buf.len() {
+            let (segment, len) = AsPathSegment::from_bytes(&buf[offset..])?;
+            segments.push(segment);
+            offset += len;
+        }
+        Ok(AsPath { segments })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_counts_as_one() {
+        let path = AsPath::new(vec![
+            AsPathSegment::Sequence(vec![65001, 65002]),
+            AsPathSegment::Set(vec![65010, 65011, 65012]),
+            AsPathSegment::Set(vec![]),
+        ]);
+        assert_eq!(path.length(), 3);
+    }
+
+    #[test]
+    fn prepend_in_front_of_a_set_starts_a_sequence() {
+        let set = AsPathSegment::Set(vec![65010, 65011]);
+        let path = AsPath::new(vec![set.clone()]).prepend(65001, 2);
+        assert_eq!(
+            path.segments,
+            [AsPathSegment::Sequence(vec![65001, 65001]), set]
+        );
+        assert_eq!(path.length(), 3);
+        assert_eq!(AsPath::from_bytes(&path.to_bytes()).unwrap(), path);
+    }
+
+    #[test]
+    fn prepend_overflows_into_a_new_segment() {
+        let full: Vec<u32> = (1..=MAX_SEGMENT_LEN as u32).collect();
+        let path = AsPath::new(vec![AsPathSegment::Sequence(full.clone())]).prepend(65001, 3);
+        assert_eq!(
+            path.segments,
+            [
+                AsPathSegment::Sequence(vec![65001; 3]),
+                AsPathSegment::Sequence(full)
+            ]
+        );
+        assert_eq!(path.length(), MAX_SEGMENT_LEN + 3);
+
+        let bytes = path.to_bytes();
+        assert_eq!(bytes[..2], [SEGMENT_AS_SEQUENCE, 3]);
+        assert_eq!(bytes[14..16], [SEGMENT_AS_SEQUENCE, 255]);
+        assert_eq!(AsPath::from_bytes(&bytes).unwrap(), path);
+    }
+
+    #[test]
+    fn prepend_fills_the_first_sequence_up_to_the_limit() {
+        let almost_full = vec![1; MAX_SEGMENT_LEN - 1];
+        let path = AsPath::new(vec![AsPathSegment::Sequence(almost_full)]).prepend(65001, 2);
+        assert_eq!(path.segments.len(), 2);
+        assert_eq!(path.segments[0], AsPathSegment::Sequence(vec![65001]));
+        assert_eq!(path.segments[1].get_asns().len(), MAX_SEGMENT_LEN);
+        assert_eq!(path.segments[1].get_asns()[0], 65001);
+    }
+
+    #[test]
+    fn loop_detected_in_sequences_and_sets() {
+        let path = AsPath::new(vec![
+            AsPathSegment::Sequence(vec![65001, 65002]),
+            AsPathSegment::Set(vec![65010]),
+        ]);
+        assert!(path.is_loop(65002));
+        assert!(path.is_loop(65010));
+        assert!(!path.is_loop(65003));
+        assert!(!AsPath::default().is_loop(65001));
+    }
+}
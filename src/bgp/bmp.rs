@@ -0,0 +1,578 @@
+use std::net::{IpAddr, Ipv4Addr};
+
+use crate::bgp::message_len;
+use crate::limits::{Limit, Limits};
+use crate::util::{ParseError, ensure_len, read_ipv4, read_ipv6};
+
+// BGP Monitoring Protocol (RFC 7854). Common header:
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |    Version    |              Message Length                   |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |  (continued)  |   Msg. Type   |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// Per-peer header, before the body of every message but Initiation and
+// Termination:
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |   Peer Type   |  Peer Flags   |                               |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+                               +
+// |             Peer Distinguisher (8 bytes)                      |
+// +                               +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                               |                               |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+                               +
+// |                 Peer Address (16 bytes)                       |
+// +                               +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                               |           Peer AS             |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |  (continued)  |         Peer BGP ID                           |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |  (continued)  |      Timestamp (seconds)                      |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |  (continued)  |      Timestamp (microseconds)                 |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |  (continued)  |
+// +-+-+-+-+-+-+-+-+
+//
+// IPv4 addresses take the last 4 bytes of 16-byte address fields.
+
+/// Version of BMP described by RFC 7854.
+pub const VERSION: u8 = 3;
+
+/// TCP port commonly used by BMP collectors.
+pub const PORT: u16 = 11019;
+
+/// Length of the common header in bytes.
+pub const COMMON_HEADER_LEN: usize = 6;
+
+/// Length of the per-peer header in bytes.
+pub const PEER_HEADER_LEN: usize = 42;
+
+// Message types.
+pub const TYPE_ROUTE_MONITORING: u8 = 0;
+pub const TYPE_STATISTICS_REPORT: u8 = 1;
+pub const TYPE_PEER_DOWN: u8 = 2;
+pub const TYPE_PEER_UP: u8 = 3;
+pub const TYPE_INITIATION: u8 = 4;
+pub const TYPE_TERMINATION: u8 = 5;
+pub const TYPE_ROUTE_MIRRORING: u8 = 6;
+
+// Peer flags.
+pub const PEER_FLAG_IPV6: u8 = 0x80;
+pub const PEER_FLAG_POST_POLICY: u8 = 0x40;
+pub const PEER_FLAG_LEGACY_AS_PATH: u8 = 0x20;
+
+// Statistics types.
+pub const STAT_PREFIXES_REJECTED: u16 = 0;
+pub const STAT_DUPLICATE_PREFIX_ADVERTISEMENTS: u16 = 1;
+pub const STAT_DUPLICATE_WITHDRAWALS: u16 = 2;
+pub const STAT_CLUSTER_LIST_LOOP: u16 = 3;
+pub const STAT_AS_PATH_LOOP: u16 = 4;
+pub const STAT_ORIGINATOR_ID_LOOP: u16 = 5;
+pub const STAT_AS_CONFED_LOOP: u16 = 6;
+pub const STAT_ADJ_RIB_IN_ROUTES: u16 = 7;
+pub const STAT_LOC_RIB_ROUTES: u16 = 8;
+pub const STAT_UPDATES_TREATED_AS_WITHDRAW: u16 = 11;
+
+/// Header identifying the monitored peer of a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PeerHeader {
+    pub peer_type: u8,
+    pub flags: u8,
+    pub distinguisher: u64,
+    /// IPv6 when `PEER_FLAG_IPV6` is set.
+    pub address: IpAddr,
+    pub asn: u32,
+    pub bgp_id: Ipv4Addr,
+    pub timestamp_seconds: u32,
+    pub timestamp_micros: u32,
+}
+
+impl PeerHeader {
+    /// Constructor to create the header of a global instance peer
+    /// (type 0), with the IPv6 flag set for IPv6 addresses.
+    pub fn new(address: IpAddr, asn: u32, bgp_id: Ipv4Addr) -> Self {
+        PeerHeader {
+            peer_type: 0,
+            flags: if address.is_ipv6() { PEER_FLAG_IPV6 } else { 0 },
+            distinguisher: 0,
+            address,
+            asn,
+            bgp_id,
+            timestamp_seconds: 0,
+            timestamp_micros: 0,
+        }
+    }
+
+    // --- SETTER METHODS ---
+
+    pub fn set_timestamp(mut self, seconds: u32, micros: u32) -> Self {
+        self.timestamp_seconds = seconds;
+        self.timestamp_micros = micros;
+        self
+    }
+
+    pub fn set_flags(mut self, flags: u8) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    // --- SERIALIZATION ---
+
+    pub fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        bytes.push(self.peer_type);
+        bytes.push(self.flags);
+        bytes.extend_from_slice(&self.distinguisher.to_be_bytes());
+        serialize_address_into(self.address, bytes);
+        bytes.extend_from_slice(&self.asn.to_be_bytes());
+        bytes.extend_from_slice(&self.bgp_id.octets());
+        bytes.extend_from_slice(&self.timestamp_seconds.to_be_bytes());
+        bytes.extend_from_slice(&self.timestamp_micros.to_be_bytes());
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> Result<(Self, usize), ParseError> {
+        ensure_len(buf, PEER_HEADER_LEN)?;
+        let flags = buf[1];
+        let header = PeerHeader {
+            peer_type: buf[0],
+            flags,
+            distinguisher: u64::from_be_bytes(buf[2..10].try_into().unwrap()),
+            address: read_address(buf, 10, flags & PEER_FLAG_IPV6 != 0),
+            asn: u32::from_be_bytes([buf[26], buf[27], buf[28], buf[29]]),
+            bgp_id: read_ipv4(buf, 30),
+            timestamp_seconds: u32::from_be_bytes([buf[34], buf[35], buf[36], buf[37]]),
+            timestamp_micros: u32::from_be_bytes([buf[38], buf[39], buf[40], buf[41]]),
+        };
+        Ok((header, PEER_HEADER_LEN))
+    }
+}
+
+fn serialize_address_into(address: IpAddr, bytes: &mut Vec<u8>) {
+    match address {
+        IpAddr::V4(address) => {
+            bytes.extend_from_slice(&[0; 12]);
+            bytes.extend_from_slice(&address.octets());
+        }
+        IpAddr::V6(address) => bytes.extend_from_slice(&address.octets()),
+    }
+}
+
+/// Reads a 16-byte address field; the caller checked the length.
+fn read_address(buf: &[u8], offset: usize, ipv6: bool) -> IpAddr {
+    if ipv6 {
+        IpAddr::V6(read_ipv6(buf, offset))
+    } else {
+        IpAddr::V4(read_ipv4(buf, offset + 12))
+    }
+}
+
+/// Counter of a Statistics Report.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum BmpStat {
+    /// Prefixes rejected by inbound policy.
+    PrefixesRejected(u32),
+    DuplicatePrefixAdvertisements(u32),
+    DuplicateWithdrawals(u32),
+    ClusterListLoop(u32),
+    AsPathLoop(u32),
+    OriginatorIdLoop(u32),
+    AsConfedLoop(u32),
+    /// Routes in the Adj-RIBs-In.
+    AdjRibInRoutes(u64),
+    /// Routes in the Loc-RIB.
+    LocRibRoutes(u64),
+    /// Invalid UPDATE messages handled as withdrawals (RFC 7606).
+    UpdatesTreatedAsWithdraw(u32),
+    /// Counter without a dedicated variant.
+    Other {
+        stat_type: u16,
+        data: Vec<u8>,
+    },
+}
+
+impl BmpStat {
+    /// Statistics type.
+    pub fn stat_type(&self) -> u16 {
+        match self {
+            BmpStat::PrefixesRejected(_) => STAT_PREFIXES_REJECTED,
+            BmpStat::DuplicatePrefixAdvertisements(_) => STAT_DUPLICATE_PREFIX_ADVERTISEMENTS,
+            BmpStat::DuplicateWithdrawals(_) => STAT_DUPLICATE_WITHDRAWALS,
+            BmpStat::ClusterListLoop(_) => STAT_CLUSTER_LIST_LOOP,
+            BmpStat::AsPathLoop(_) => STAT_AS_PATH_LOOP,
+            BmpStat::OriginatorIdLoop(_) => STAT_ORIGINATOR_ID_LOOP,
+            BmpStat::AsConfedLoop(_) => STAT_AS_CONFED_LOOP,
+            BmpStat::AdjRibInRoutes(_) => STAT_ADJ_RIB_IN_ROUTES,
+            BmpStat::LocRibRoutes(_) => STAT_LOC_RIB_ROUTES,
+            BmpStat::UpdatesTreatedAsWithdraw(_) => STAT_UPDATES_TREATED_AS_WITHDRAW,
+            BmpStat::Other { stat_type, .. } => *stat_type,
+        }
+    }
+
+    // --- SERIALIZATION ---
+
+    pub fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        let data = match self {
+            BmpStat::PrefixesRejected(count)
+            | BmpStat::DuplicatePrefixAdvertisements(count)
+            | BmpStat::DuplicateWithdrawals(count)
+            | BmpStat::ClusterListLoop(count)
+            | BmpStat::AsPathLoop(count)
+            | BmpStat::OriginatorIdLoop(count)
+            | BmpStat::AsConfedLoop(count)
+            | BmpStat::UpdatesTreatedAsWithdraw(count) => count.to_be_bytes().to_vec(),
+            BmpStat::AdjRibInRoutes(gauge) | BmpStat::LocRibRoutes(gauge) => {
+                gauge.to_be_bytes().to_vec()
+            }
+            BmpStat::Other { data, .. } => data.clone(),
+        };
+        bytes.extend_from_slice(&self.stat_type().to_be_bytes());
+        bytes.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(&data);
+    }
+
+    /// Parses a statistics TLV, returning it and the number of bytes
+    /// consumed. Counters of an unexpected size are kept as `Other`.
+    pub fn from_bytes(buf: &[u8]) -> Result<(Self, usize), ParseError> {
+        ensure_len(buf, 4)?;
+        let stat_type = u16::from_be_bytes([buf[0], buf[1]]);
+        let len = u16::from_be_bytes([buf[2], buf[3]]) as usize;
+        ensure_len(buf, 4 + len)?;
+        let data = &buf[4..4 + len];
+        let stat = match (stat_type, data.len()) {
+            (STAT_ADJ_RIB_IN_ROUTES, 8) => {
+                BmpStat::AdjRibInRoutes(u64::from_be_bytes(data.try_into().unwrap()))
+            }
+            (STAT_LOC_RIB_ROUTES, 8) => {
+                BmpStat::LocRibRoutes(u64::from_be_bytes(data.try_into().unwrap()))
+            }
+            (_, 4) => {
+                let count = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+                match stat_type {
+                    STAT_PREFIXES_REJECTED => BmpStat::PrefixesRejected(count),
+                    STAT_DUPLICATE_PREFIX_ADVERTISEMENTS => {
+                        BmpStat::DuplicatePrefixAdvertisements(count)
+                    }
+                    STAT_DUPLICATE_WITHDRAWALS => BmpStat::DuplicateWithdrawals(count),
+                    STAT_CLUSTER_LIST_LOOP => BmpStat::ClusterListLoop(count),
+                    STAT_AS_PATH_LOOP => BmpStat::AsPathLoop(count),
+                    STAT_ORIGINATOR_ID_LOOP => BmpStat::OriginatorIdLoop(count),
+                    STAT_AS_CONFED_LOOP => BmpStat::AsConfedLoop(count),
+                    STAT_UPDATES_TREATED_AS_WITHDRAW => BmpStat::UpdatesTreatedAsWithdraw(count),
+                    _ => BmpStat::Other {
+                        stat_type,
+                        data: data.to_vec(),
+                    },
+                }
+            }
+            _ => BmpStat::Other {
+                stat_type,
+                data: data.to_vec(),
+            },
+        };
+        Ok((stat, 4 + len))
+    }
+}
+
+/// Information TLV of Initiation, Termination, Peer Up and Route
+/// Mirroring messages.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct InformationTlv {
+    pub info_type: u16,
+    pub value: Vec<u8>,
+}
+
+impl InformationTlv {
+    /// Constructor to create a new TLV.
+    pub fn new(info_type: u16, value: Vec<u8>) -> Self {
+        InformationTlv { info_type, value }
+    }
+
+    pub fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.info_type.to_be_bytes());
+        bytes.extend_from_slice(&(self.value.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(&self.value);
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> Result<(Self, usize), ParseError> {
+        ensure_len(buf, 4)?;
+        let len = u16::from_be_bytes([buf[2], buf[3]]) as usize;
+        ensure_len(buf, 4 + len)?;
+        let tlv = InformationTlv::new(
+            u16::from_be_bytes([buf[0], buf[1]]),
+            buf[4..4 + len].to_vec(),
+        );
+        Ok((tlv, 4 + len))
+    }
+}
+
+fn parse_tlvs(buf: &[u8], limits: &Limits) -> Result<Vec<InformationTlv>, ParseError> {
+    let mut tlvs = Vec::new();
+    let mut offset = 0;
+    while offset < buf.len() {
+        limits.check(Limit::OptionsPerLayer, tlvs.len() + 1)?;
+        let (tlv, len) = InformationTlv::from_bytes(&buf[offset..])?;
+        limits.check(Limit::TlvBytes, tlv.value.len())?;
+        tlvs.push(tlv);
+        offset += len;
+    }
+    Ok(tlvs)
+}
+
+/// Body of a BMP message. BGP messages are kept whole, header included.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum BmpType {
+    RouteMonitoring {
+        peer: PeerHeader,
+        update: Vec<u8>,
+    },
+    StatisticsReport {
+        peer: PeerHeader,
+        stats_count: u32,
+        stats: Vec<BmpStat>,
+    },
+    PeerDown {
+        peer: PeerHeader,
+        reason: u8,
+        /// NOTIFICATION message or FSM event code, depending on `reason`.
+        data: Vec<u8>,
+    },
+    PeerUp {
+        peer: PeerHeader,
+        local_address: IpAddr,
+        local_port: u16,
+        remote_port: u16,
+        /// OPEN message sent by the monitored router.
+        sent_open: Vec<u8>,
+        /// OPEN message received from the peer.
+        received_open: Vec<u8>,
+        information: Vec<InformationTlv>,
+    },
+    Initiation(Vec<InformationTlv>),
+    Termination(Vec<InformationTlv>),
+    RouteMirroring {
+        peer: PeerHeader,
+        tlvs: Vec<InformationTlv>,
+    },
+}
+
+impl BmpType {
+    /// Message type of the common header.
+    pub fn type_code(&self) -> u8 {
+        match self {
+            BmpType::RouteMonitoring { .. } => TYPE_ROUTE_MONITORING,
+            BmpType::StatisticsReport { .. } => TYPE_STATISTICS_REPORT,
+            BmpType::PeerDown { .. } => TYPE_PEER_DOWN,
+            BmpType::PeerUp { .. } => TYPE_PEER_UP,
+            BmpType::Initiation(_) => TYPE_INITIATION,
+            BmpType::Termination(_) => TYPE_TERMINATION,
+            BmpType::RouteMirroring { .. } => TYPE_ROUTE_MIRRORING,
+        }
+    }
+
+    /// Monitored peer, for messages that have one.
+    pub fn peer(&self) -> Option<&PeerHeader> {
+        match self {
+            BmpType::RouteMonitoring { peer, .. }
+            | BmpType::StatisticsReport { peer, .. }
+            | BmpType::PeerDown { peer, .. }
+            | BmpType::PeerUp { peer, .. }
+            | BmpType::RouteMirroring { peer, .. } => Some(peer),
+            BmpType::Initiation(_) | BmpType::Termination(_) => None,
+        }
+    }
+
+    pub fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        if let Some(peer) = self.peer() {
+            peer.serialize_into(bytes);
+        }
+        match self {
+            BmpType::RouteMonitoring { update, .. } => bytes.extend_from_slice(update),
+            BmpType::StatisticsReport {
+                stats_count, stats, ..
+            } => {
+                bytes.extend_from_slice(&stats_count.to_be_bytes());
+                for stat in stats {
+                    stat.serialize_into(bytes);
+                }
+            }
+            BmpType::PeerDown { reason, data, .. } => {
+                bytes.push(*reason);
+                bytes.extend_from_slice(data);
+            }
+            BmpType::PeerUp {
+                local_address,
+                local_port,
+                remote_port,
+                sent_open,
+                received_open,
+                information,
+                ..
+            } => {
+                serialize_address_into(*local_address, bytes);
+                bytes.extend_from_slice(&local_port.to_be_bytes());
+                bytes.extend_from_slice(&remote_port.to_be_bytes());
+                bytes.extend_from_slice(sent_open);
+                bytes.extend_from_slice(received_open);
+                for tlv in information {
+                    tlv.serialize_into(bytes);
+                }
+            }
+            BmpType::Initiation(tlvs)
+            | BmpType::Termination(tlvs)
+            | BmpType::RouteMirroring { tlvs, .. } => {
+                for tlv in tlvs {
+                    tlv.serialize_into(bytes);
+                }
+            }
+        }
+    }
+
+    /// Parses the body of a message of type `type_code`.
+    pub fn from_bytes(type_code: u8, body: &[u8]) -> Result<Self, ParseError> {
+        BmpType::from_bytes_with_limits(type_code, body, &Limits::default())
+    }
+
+    /// Same as `from_bytes`, bounding the number of information TLVs by
+    /// `limits.max_options_per_layer` and their values by
+    /// `limits.max_tlv_bytes`.
+    pub fn from_bytes_with_limits(
+        type_code: u8,
+        body: &[u8],
+        limits: &Limits,
+    ) -> Result<Self, ParseError> {
+        if matches!(type_code, TYPE_INITIATION | TYPE_TERMINATION) {
+            let tlvs = parse_tlvs(body, limits)?;
+            return Ok(if type_code == TYPE_INITIATION {
+                BmpType::Initiation(tlvs)
+            } else {
+                BmpType::Termination(tlvs)
+            });
+        }
+        let (peer, offset) = PeerHeader::from_bytes(body)?;
+        let rest = &body[offset..];
+        let message = match type_code {
+            TYPE_ROUTE_MONITORING => {
+                let len = message_len(rest)?;
+                BmpType::RouteMonitoring {
+                    peer,
+                    update: rest[..len].to_vec(),
+                }
+            }
+            TYPE_STATISTICS_REPORT => {
+                ensure_len(rest, 4)?;
+                let stats_count = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]);
+                let mut stats = Vec::new();
+                let mut offset = 4;
+                // The count comes from the message: only trust the bytes.
+                while offset < rest.len() && stats.len() < stats_count as usize {
+                    let (stat, len) = BmpStat::from_bytes(&rest[offset..])?;
+                    stats.push(stat);
+                    offset += len;
+                }
+                BmpType::StatisticsReport {
+                    peer,
+                    stats_count,
+                    stats,
+                }
+            }
+            TYPE_PEER_DOWN => {
+                ensure_len(rest, 1)?;
+                BmpType::PeerDown {
+                    peer,
+                    reason: rest[0],
+                    data: rest[1..].to_vec(),
+                }
+            }
+            TYPE_PEER_UP => {
+                ensure_len(rest, 20)?;
+                let ipv6 = peer.flags & PEER_FLAG_IPV6 != 0;
+                let sent_len = message_len(&rest[20..])?;
+                let received = &rest[20 + sent_len..];
+                let received_len = message_len(received)?;
+                BmpType::PeerUp {
+                    peer,
+                    local_address: read_address(rest, 0, ipv6),
+                    local_port: u16::from_be_bytes([rest[16], rest[17]]),
+                    remote_port: u16::from_be_bytes([rest[18], rest[19]]),
+                    sent_open: rest[20..20 + sent_len].to_vec(),
+                    received_open: received[..received_len].to_vec(),
+                    information: parse_tlvs(&received[received_len..], limits)?,
+                }
+            }
+            TYPE_ROUTE_MIRRORING => BmpType::RouteMirroring {
+                peer,
+                tlvs: parse_tlvs(rest, limits)?,
+            },
+            _ => return Err(ParseError::InvalidField("message type")),
+        };
+        Ok(message)
+    }
+}
+
+/// BMP message.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Bmp {
+    pub version: u8,
+    /// Length of the whole message, common header included.
+    pub length: u32,
+    pub type_: BmpType,
+}
+
+impl Bmp {
+    /// Constructor to create a version 3 message with its length filled in.
+    pub fn new(type_: BmpType) -> Self {
+        let mut body = Vec::new();
+        type_.serialize_into(&mut body);
+        Bmp {
+            version: VERSION,
+            length: (COMMON_HEADER_LEN + body.len()) as u32,
+            type_,
+        }
+    }
+
+    // --- SERIALIZATION ---
+
+    /// Serializes the message, using the length field as is.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.length as usize);
+        bytes.push(self.version);
+        bytes.extend_from_slice(&self.length.to_be_bytes());
+        bytes.push(self.type_.type_code());
+        self.type_.serialize_into(&mut bytes);
+        bytes
+    }
+
+    /// Parses the message at the start of `buf`, returning it and its
+    /// length; a stream from a router holds messages back to back.
+    pub fn from_bytes(buf: &[u8]) -> Result<(Self, usize), ParseError> {
+        Bmp::from_bytes_with_limits(buf, &Limits::default())
+    }
+
+    /// Same as `from_bytes`, applying `limits` to the information TLVs.
+    pub fn from_bytes_with_limits(
+        buf: &[u8],
+        limits: &Limits,
+    ) -> Result<(Self, usize), ParseError> {
+        ensure_len(buf, COMMON_HEADER_LEN)?;
+        if buf[0] != VERSION {
+            return Err(ParseError::InvalidField("version"));
+        }
+        let length = u32::from_be_bytes([buf[1], buf[2], buf[3], buf[4]]);
+        if (length as usize) < COMMON_HEADER_LEN {
+            return Err(ParseError::InvalidField("length"));
+        }
+        ensure_len(buf, length as usize)?;
+        let type_ = BmpType::from_bytes_with_limits(
+            buf[5],
+            &buf[COMMON_HEADER_LEN..length as usize],
+            limits,
+        )?;
+        let message = Bmp {
+            version: buf[0],
+            length,
+            type_,
+        };
+        Ok((message, length as usize))
+    }
+}
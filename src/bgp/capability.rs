@@ -0,0 +1,209 @@
+use crate::bgp::OPT_PARAM_CAPABILITIES;
+use crate::util::{ParseError, ensure_len};
+
+// Capability (RFC 5492 section 4):
+//
+// +------------------------------+
+// | Capability Code (1 octet)    |
+// +------------------------------+
+// | Capability Length (1 octet)  |
+// +------------------------------+
+// | Capability Value (variable)  |
+// +------------------------------+
+//
+// Graceful Restart value (RFC 4724 section 3):
+//
+// +--------------------------------------------------+
+// | Restart Flags (4 bits) | Restart Time (12 bits)  |
+// +--------------------------------------------------+
+// | AFI (16 bits) | SAFI (8 bits) | Flags (8 bits)   | repeated
+// +--------------------------------------------------+
+//
+// Long-Lived Graceful Restart value (RFC 9494 section 3), repeated:
+//
+// +--------------------------------------------------+
+// | AFI (16 bits) | SAFI (8 bits) | Flags (8 bits)   |
+// +--------------------------------------------------+
+// | Long-lived Stale Time (24 bits)                  |
+// +--------------------------------------------------+
+
+// Capability codes.
+pub const CODE_MULTIPROTOCOL: u8 = 1;
+pub const CODE_ROUTE_REFRESH: u8 = 2;
+pub const CODE_GRACEFUL_RESTART: u8 = 64;
+pub const CODE_FOUR_OCTET_AS: u8 = 65;
+pub const CODE_LONG_LIVED_GRACEFUL_RESTART: u8 = 71;
+
+// Restart flags.
+pub const RESTART_STATE: u16 = 0x8000;
+pub const RESTART_NOTIFICATION: u16 = 0x4000;
+
+/// Address family flag: forwarding state was preserved.
+pub const FORWARDING_STATE: u8 = 0x80;
+
+/// Largest restart time in seconds (12 bits).
+pub const MAX_RESTART_TIME: u16 = 0x0fff;
+
+/// Largest long-lived stale time in seconds (24 bits).
+pub const MAX_STALE_TIME: u32 = 0x00ff_ffff;
+
+/// A capability as carried in an OPEN message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capability {
+    pub code: u8,
+    pub value: Vec<u8>,
+}
+
+impl Capability {
+    /// Constructor to create a new capability.
+    pub fn new(code: u8, value: Vec<u8>) -> Self {
+        Capability { code, value }
+    }
+
+    pub fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        bytes.push(self.code);
+        bytes.push(self.value.len() as u8);
+        bytes.extend_from_slice(&self.value);
+    }
+
+    /// Parses a capability, returning it and the number of bytes consumed.
+    pub fn from_bytes(buf: &[u8]) -> Result<(Self, usize), ParseError> {
+        ensure_len(buf, 2)?;
+        let length = buf[1] as usize;
+        ensure_len(buf, 2 + length)?;
+        Ok((
+            Capability::new(buf[0], buf[2..2 + length].to_vec()),
+            2 + length,
+        ))
+    }
+}
+
+/// Encodes `capabilities` as an OPEN optional parameter.
+pub fn optional_parameter(capabilities: &[Capability]) -> Vec<u8> {
+    let mut value = Vec::new();
+    for capability in capabilities {
+        capability.serialize_into(&mut value);
+    }
+    let mut bytes = vec![OPT_PARAM_CAPABILITIES, value.len() as u8];
+    bytes.extend_from_slice(&value);
+    bytes
+}
+
+/// Graceful Restart capability (code 64).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GracefulRestart {
+    /// The speaker has restarted (R bit).
+    pub restart_state: bool,
+    /// Seconds the peer should wait for the session to come back; 12 bits.
+    pub restart_time: u16,
+    /// (AFI, SAFI, forwarding state preserved) for each address family.
+    pub address_families: Vec<(u16, u8, bool)>,
+}
+
+impl GracefulRestart {
+    /// Constructor to create a capability for the given address families.
+    pub fn new(restart_time: u16, address_families: Vec<(u16, u8, bool)>) -> Self {
+        GracefulRestart {
+            restart_state: false,
+            restart_time: restart_time.min(MAX_RESTART_TIME),
+            address_families,
+        }
+    }
+
+    pub fn set_restart_state(mut self, restart_state: bool) -> Self {
+        self.restart_state = restart_state;
+        self
+    }
+
+    /// Encodes the capability value.
+    pub fn to_capability_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(2 + 4 * self.address_families.len());
+        let header = if self.restart_state { RESTART_STATE } else { 0 }
+            | self.restart_time & MAX_RESTART_TIME;
+        bytes.extend_from_slice(&header.to_be_bytes());
+        for (afi, safi, forwarding) in &self.address_families {
+            bytes.extend_from_slice(&afi.to_be_bytes());
+            bytes.push(*safi);
+            bytes.push(if *forwarding { FORWARDING_STATE } else { 0 });
+        }
+        bytes
+    }
+
+    pub fn to_capability(&self) -> Capability {
+        Capability::new(CODE_GRACEFUL_RESTART, self.to_capability_bytes())
+    }
+
+    /// Parses a capability value.
+    pub fn from_capability_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, 2)?;
+        if !(buf.len() - 2).is_multiple_of(4) {
+            return Err(ParseError::InvalidField("capability length"));
+        }
+        let header = u16::from_be_bytes([buf[0], buf[1]]);
+        Ok(GracefulRestart {
+            restart_state: header & RESTART_STATE != 0,
+            restart_time: header & MAX_RESTART_TIME,
+            address_families: buf[2..]
+                .chunks_exact(4)
+                .map(|family| {
+                    (
+                        u16::from_be_bytes([family[0], family[1]]),
+                        family[2],
+                        family[3] & FORWARDING_STATE != 0,
+                    )
+                })
+                .collect(),
+        })
+    }
+}
+
+/// Long-Lived Graceful Restart capability (code 71).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LongLivedGracefulRestart {
+    /// (AFI, SAFI, forwarding state preserved, long-lived stale time in
+    /// seconds) for each address family; the stale time is 24 bits.
+    pub address_families: Vec<(u16, u8, bool, u32)>,
+}
+
+impl LongLivedGracefulRestart {
+    /// Constructor to create a capability for the given address families.
+    pub fn new(address_families: Vec<(u16, u8, bool, u32)>) -> Self {
+        LongLivedGracefulRestart { address_families }
+    }
+
+    /// Encodes the capability value.
+    pub fn to_capability_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(7 * self.address_families.len());
+        for (afi, safi, forwarding, stale_time) in &self.address_families {
+            bytes.extend_from_slice(&afi.to_be_bytes());
+            bytes.push(*safi);
+            bytes.push(if *forwarding { FORWARDING_STATE } else { 0 });
+            bytes.extend_from_slice(&stale_time.min(&MAX_STALE_TIME).to_be_bytes()[1..]);
+        }
+        bytes
+    }
+
+    pub fn to_capability(&self) -> Capability {
+        Capability::new(CODE_LONG_LIVED_GRACEFUL_RESTART, self.to_capability_bytes())
+    }
+
+    /// Parses a capability value.
+    pub fn from_capability_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        if !buf.len().is_multiple_of(7) {
+            return Err(ParseError::InvalidField("capability length"));
+        }
+        Ok(LongLivedGracefulRestart {
+            address_families: buf
+                .chunks_exact(7)
+                .map(|family| {
+                    (
+                        u16::from_be_bytes([family[0], family[1]]),
+                        family[2],
+                        family[3] & FORWARDING_STATE != 0,
+                        u32::from_be_bytes([0, family[4], family[5], family[6]]),
+                    )
+                })
+                .collect(),
+        })
+    }
+}
@@ -0,0 +1,366 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::io;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, SyncSender, TrySendError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+// Bump-in-the-wire between two ports, A and B. Each direction runs two
+// threads:
+//
+//   source --> receiver --(bounded queue)--> releaser --> sink
+//                 |                             |
+//              callback                   release queue
+//          (drop/delay/modify)        (ordered by release time)
+//
+// The receiver never blocks on a full queue: frames that do not fit are
+// dropped and counted, as a congested wire would.
+
+/// How long receivers wait on the channel ports before checking for a
+/// stop request.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Receiving half of a port.
+pub trait FrameSource: Send + 'static {
+    /// Returns the next frame, or `None` once the port is closed. Errors
+    /// of kind `WouldBlock` or `TimedOut` mean no frame arrived yet; a
+    /// source should return one periodically so the bridge can stop.
+    fn recv(&mut self) -> io::Result<Option<Vec<u8>>>;
+}
+
+/// Sending half of a port.
+pub trait FrameSink: Send + 'static {
+    fn send(&mut self, frame: &[u8]) -> io::Result<()>;
+}
+
+impl FrameSource for Receiver<Vec<u8>> {
+    fn recv(&mut self) -> io::Result<Option<Vec<u8>>> {
+        match self.recv_timeout(POLL_INTERVAL) {
+            Ok(frame) => Ok(Some(frame)),
+            Err(RecvTimeoutError::Timeout) => Err(io::ErrorKind::TimedOut.into()),
+            Err(RecvTimeoutError::Disconnected) => Ok(None),
+        }
+    }
+}
+
+impl FrameSink for Sender<Vec<u8>> {
+    fn send(&mut self, frame: &[u8]) -> io::Result<()> {
+        Sender::send(self, frame.to_vec()).map_err(|_| io::ErrorKind::BrokenPipe.into())
+    }
+}
+
+/// Direction of a frame through the bridge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    AToB,
+    BToA,
+}
+
+/// What happens to a frame, after the callback had the chance to modify
+/// it in place.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Forward,
+    Drop,
+    /// Forward once the delay has elapsed since the frame was received.
+    Delay(Duration),
+}
+
+/// Settings of a bridge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BridgeConfig {
+    /// Frames held per direction, queued or waiting for their release.
+    pub queue_capacity: usize,
+    /// Release delayed frames strictly by release time, so that a frame
+    /// delayed less than the previous one overtakes it. Otherwise frames
+    /// leave in arrival order and a delay also holds back later frames.
+    pub allow_reordering: bool,
+}
+
+impl Default for BridgeConfig {
+    fn default() -> Self {
+        BridgeConfig::new()
+    }
+}
+
+impl BridgeConfig {
+    /// Constructor to create a configuration holding 1024 frames per
+    /// direction, without reordering.
+    pub fn new() -> Self {
+        BridgeConfig {
+            queue_capacity: 1024,
+            allow_reordering: false,
+        }
+    }
+
+    // --- SETTER METHODS ---
+
+    pub fn set_queue_capacity(mut self, queue_capacity: usize) -> Self {
+        self.queue_capacity = queue_capacity.max(1);
+        self
+    }
+
+    pub fn set_allow_reordering(mut self, allow_reordering: bool) -> Self {
+        self.allow_reordering = allow_reordering;
+        self
+    }
+}
+
+/// Counters of one direction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub received: u64,
+    pub forwarded: u64,
+    /// Frames dropped by the callback.
+    pub dropped: u64,
+    /// Frames the callback delayed.
+    pub delayed: u64,
+    /// Frames dropped because the queue was full.
+    pub queue_drops: u64,
+    pub send_errors: u64,
+}
+
+#[derive(Default)]
+struct Counters {
+    received: AtomicU64,
+    forwarded: AtomicU64,
+    dropped: AtomicU64,
+    delayed: AtomicU64,
+    queue_drops: AtomicU64,
+    send_errors: AtomicU64,
+}
+
+impl Counters {
+    fn snapshot(&self) -> Stats {
+        Stats {
+            received: self.received.load(Ordering::Relaxed),
+            forwarded: self.forwarded.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            delayed: self.delayed.load(Ordering::Relaxed),
+            queue_drops: self.queue_drops.load(Ordering::Relaxed),
+            send_errors: self.send_errors.load(Ordering::Relaxed),
+        }
+    }
+}
+
+fn increment(counter: &AtomicU64) {
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Frame waiting in the release queue; `sequence` keeps frames with the
+/// same release time in arrival order.
+type Pending = Reverse<(Instant, u64, Vec<u8>)>;
+
+/// Direction with the source it reads and the sink it writes.
+type Path = (Direction, Box<dyn FrameSource>, Box<dyn FrameSink>);
+
+/// A running bridge between ports A and B.
+pub struct Bridge {
+    stop: Arc<AtomicBool>,
+    counters: [Arc<Counters>; 2],
+    workers: Vec<JoinHandle<io::Result<()>>>,
+}
+
+impl Bridge {
+    /// Starts forwarding frames from `a_source` to `b_sink` and from
+    /// `b_source` to `a_sink`. `callback` sees every received frame with
+    /// its direction, may modify it and decides its `Action`.
+    pub fn start<F>(
+        a_source: impl FrameSource,
+        a_sink: impl FrameSink,
+        b_source: impl FrameSource,
+        b_sink: impl FrameSink,
+        config: BridgeConfig,
+        callback: F,
+    ) -> Self
+    where
+        F: Fn(Direction, &mut Vec<u8>) -> Action + Send + Sync + 'static,
+    {
+        let stop = Arc::new(AtomicBool::new(false));
+        let callback = Arc::new(callback);
+        let counters = [Arc::new(Counters::default()), Arc::new(Counters::default())];
+        let mut workers = Vec::with_capacity(4);
+        let directions: [Path; 2] = [
+            (Direction::AToB, Box::new(a_source), Box::new(b_sink)),
+            (Direction::BToA, Box::new(b_source), Box::new(a_sink)),
+        ];
+        for ((direction, source, sink), counters) in directions.into_iter().zip(&counters) {
+            let (queue, releases) = mpsc::sync_channel(config.queue_capacity);
+            let held = Arc::new(AtomicU64::new(0));
+            let receiver = ReceiveWorker {
+                direction,
+                source,
+                queue,
+                held: held.clone(),
+                capacity: config.queue_capacity as u64,
+                counters: counters.clone(),
+                stop: stop.clone(),
+            };
+            let callback = callback.clone();
+            workers.push(thread::spawn(move || receiver.run(&*callback)));
+            let releaser = ReleaseWorker {
+                sink,
+                releases,
+                held,
+                allow_reordering: config.allow_reordering,
+                counters: counters.clone(),
+            };
+            workers.push(thread::spawn(move || releaser.run()));
+        }
+        Bridge {
+            stop,
+            counters,
+            workers,
+        }
+    }
+
+    /// Counters of `direction` so far.
+    pub fn stats(&self, direction: Direction) -> Stats {
+        self.counters[direction as usize].snapshot()
+    }
+
+    /// Asks the receivers to stop; frames already queued are still
+    /// released.
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    /// Waits until both sources are closed or the bridge is stopped, and
+    /// every queued frame was released. Returns the first error of a
+    /// source, or the counters of both directions.
+    pub fn join(self) -> io::Result<[Stats; 2]> {
+        let mut result = Ok(());
+        for worker in self.workers {
+            let outcome = worker
+                .join()
+                .unwrap_or_else(|_| Err(io::Error::other("bridge worker panicked")));
+            if result.is_ok() {
+                result = outcome;
+            }
+        }
+        result.map(|()| [self.counters[0].snapshot(), self.counters[1].snapshot()])
+    }
+}
+
+/// Receiving worker of one direction.
+struct ReceiveWorker {
+    direction: Direction,
+    source: Box<dyn FrameSource>,
+    queue: SyncSender<(Instant, Vec<u8>)>,
+    /// Frames queued or in the release queue.
+    held: Arc<AtomicU64>,
+    capacity: u64,
+    counters: Arc<Counters>,
+    stop: Arc<AtomicBool>,
+}
+
+impl ReceiveWorker {
+    fn run(mut self, callback: &dyn Fn(Direction, &mut Vec<u8>) -> Action) -> io::Result<()> {
+        while !self.stop.load(Ordering::Relaxed) {
+            let mut frame = match self.source.recv() {
+                Ok(Some(frame)) => frame,
+                Ok(None) => break,
+                Err(err)
+                    if matches!(
+                        err.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    continue;
+                }
+                Err(err) => return Err(err),
+            };
+            let received = Instant::now();
+            increment(&self.counters.received);
+            let release = match callback(self.direction, &mut frame) {
+                Action::Forward => received,
+                Action::Drop => {
+                    increment(&self.counters.dropped);
+                    continue;
+                }
+                Action::Delay(delay) => {
+                    increment(&self.counters.delayed);
+                    received + delay
+                }
+            };
+            if self.held.load(Ordering::Relaxed) >= self.capacity {
+                increment(&self.counters.queue_drops);
+                continue;
+            }
+            self.held.fetch_add(1, Ordering::Relaxed);
+            match self.queue.try_send((release, frame)) {
+                Ok(()) => {}
+                Err(TrySendError::Full(_)) => {
+                    self.held.fetch_sub(1, Ordering::Relaxed);
+                    increment(&self.counters.queue_drops);
+                }
+                Err(TrySendError::Disconnected(_)) => break,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Sending worker of one direction, releasing frames in time order.
+struct ReleaseWorker {
+    sink: Box<dyn FrameSink>,
+    releases: mpsc::Receiver<(Instant, Vec<u8>)>,
+    held: Arc<AtomicU64>,
+    allow_reordering: bool,
+    counters: Arc<Counters>,
+}
+
+impl ReleaseWorker {
+    fn run(mut self) -> io::Result<()> {
+        let mut pending: BinaryHeap<Pending> = BinaryHeap::new();
+        let mut sequence = 0u64;
+        let mut last_release: Option<Instant> = None;
+        let mut open = true;
+        while open || !pending.is_empty() {
+            let next = pending.peek().map(|Reverse((release, _, _))| *release);
+            let message = match (open, next) {
+                (true, None) => self
+                    .releases
+                    .recv()
+                    .map_err(|_| RecvTimeoutError::Disconnected),
+                (true, Some(release)) => self
+                    .releases
+                    .recv_timeout(release.saturating_duration_since(Instant::now())),
+                (false, Some(release)) => {
+                    thread::sleep(release.saturating_duration_since(Instant::now()));
+                    Err(RecvTimeoutError::Timeout)
+                }
+                (false, None) => break,
+            };
+            match message {
+                Ok((mut release, frame)) => {
+                    if !self.allow_reordering {
+                        release = last_release.map_or(release, |last| release.max(last));
+                        last_release = Some(release);
+                    }
+                    pending.push(Reverse((release, sequence, frame)));
+                    sequence += 1;
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => open = false,
+            }
+            let now = Instant::now();
+            while pending
+                .peek()
+                .is_some_and(|Reverse((release, _, _))| *release <= now)
+            {
+                let Some(Reverse((_, _, frame))) = pending.pop() else {
+                    break;
+                };
+                self.held.fetch_sub(1, Ordering::Relaxed);
+                match self.sink.send(&frame) {
+                    Ok(()) => increment(&self.counters.forwarded),
+                    Err(_) => increment(&self.counters.send_errors),
+                }
+            }
+        }
+        Ok(())
+    }
+}
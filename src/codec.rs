@@ -0,0 +1,357 @@
+use crate::util::{ParseError, ensure_len};
+
+// Building blocks shared by the parsers: variable-length integers, bit
+// fields packed in network order and length-prefixed vectors. Decoders
+// take the input from its start and return the value with the number of
+// bytes consumed.
+
+/// Largest value a QUIC variable-length integer can hold (RFC 9000
+/// section 16).
+pub const QUIC_VARINT_MAX: u64 = (1 << 62) - 1;
+
+/// Number of bytes of the QUIC encoding of `value`, or `None` if it does
+/// not fit.
+pub fn quic_varint_len(value: u64) -> Option<usize> {
+    match value {
+        0..=0x3f => Some(1),
+        0x40..=0x3fff => Some(2),
+        0x4000..=0x3fff_ffff => Some(4),
+        0x4000_0000..=QUIC_VARINT_MAX => Some(8),
+        _ => None,
+    }
+}
+
+/// Appends `value` as a QUIC variable-length integer, in its shortest
+/// encoding. Fails for values above `QUIC_VARINT_MAX`.
+pub fn write_quic_varint(bytes: &mut Vec<u8>, value: u64) -> Result<(), ParseError> {
+    let len = quic_varint_len(value).ok_or(ParseError::InvalidField("varint"))?;
+    let encoded = value | (len.trailing_zeros() as u64) << (len * 8 - 2);
+    bytes.extend_from_slice(&encoded.to_be_bytes()[8 - len..]);
+    Ok(())
+}
+
+/// Decodes a QUIC variable-length integer. Non-shortest encodings are
+/// accepted, as RFC 9000 allows.
+pub fn read_quic_varint(buf: &[u8]) -> Result<(u64, usize), ParseError> {
+    ensure_len(buf, 1)?;
+    let len = 1 << (buf[0] >> 6);
+    ensure_len(buf, len)?;
+    let value = buf[1..len]
+        .iter()
+        .fold((buf[0] & 0x3f) as u64, |value, byte| {
+            value << 8 | *byte as u64
+        });
+    Ok((value, len))
+}
+
+/// Appends `value` as a protobuf-style (LEB128) varint: seven bits per
+/// byte, least significant group first.
+pub fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+/// Decodes a protobuf-style varint. Encodings longer than ten bytes or
+/// overflowing 64 bits are rejected.
+pub fn read_varint(buf: &[u8]) -> Result<(u64, usize), ParseError> {
+    let mut value = 0u64;
+    for (index, byte) in buf.iter().take(10).enumerate() {
+        let group = (byte & 0x7f) as u64;
+        if index == 9 && group > 1 {
+            return Err(ParseError::InvalidField("varint"));
+        }
+        value |= group << (index * 7);
+        if byte & 0x80 == 0 {
+            return Ok((value, index + 1));
+        }
+    }
+    if buf.len() >= 10 {
+        return Err(ParseError::InvalidField("varint"));
+    }
+    Err(ParseError::Truncated {
+        needed: buf.len() + 1,
+        available: buf.len(),
+    })
+}
+
+/// Maps signed integers to unsigned ones so that small magnitudes encode
+/// as short varints (protobuf `sint64`).
+pub fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+/// Reverses `zigzag`.
+pub fn unzigzag(value: u64) -> i64 {
+    (value >> 1) as i64 ^ -((value & 1) as i64)
+}
+
+/// Reads the `width` bits (at most 64) starting `offset` bits into
+/// `bytes`, most significant bit first, as in protocol diagrams.
+///
+/// Panics if the field extends past `bytes`.
+pub fn get_bits(bytes: &[u8], offset: usize, width: usize) -> u64 {
+    (offset..offset + width).fold(0, |value, bit| {
+        value << 1 | ((bytes[bit / 8] >> (7 - bit % 8)) & 1) as u64
+    })
+}
+
+/// Writes the low `width` bits of `value` at `offset` bits into `bytes`,
+/// most significant bit first; higher bits of `value` are ignored.
+///
+/// Panics if the field extends past `bytes`.
+pub fn set_bits(bytes: &mut [u8], offset: usize, width: usize, value: u64) {
+    for (index, bit) in (offset..offset + width).enumerate() {
+        let mask = 0x80 >> (bit % 8);
+        if value >> (width - 1 - index) & 1 != 0 {
+            bytes[bit / 8] |= mask;
+        } else {
+            bytes[bit / 8] &= !mask;
+        }
+    }
+}
+
+/// Declares a byte array newtype with getters and setters for named bit
+/// fields, each given by its offset and width in bits, most significant
+/// bit first. Setters mask the value to the field width.
+///
+/// ```text
+/// bitfields! {
+///     pub struct OffsetAndFlags([u8; 2]) {
+///         get_data_offset, set_data_offset: u8 = 0, 4;
+///         get_flags, set_flags: u16 = 7, 9;
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! bitfields {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident([u8; $len:expr]) {
+            $(
+                $(#[$field_meta:meta])*
+                $getter:ident, $setter:ident: $ty:ty = $offset:expr, $width:expr;
+            )*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+        $vis struct $name(pub [u8; $len]);
+
+        impl $name {
+            $(
+                $(#[$field_meta])*
+                pub fn $getter(&self) -> $ty {
+                    $crate::codec::get_bits(&self.0, $offset, $width) as $ty
+                }
+
+                pub fn $setter(mut self, value: $ty) -> Self {
+                    $crate::codec::set_bits(&mut self.0, $offset, $width, value as u64);
+                    self
+                }
+            )*
+        }
+    };
+}
+
+/// Width of the length in front of a vector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LengthPrefix {
+    U8,
+    U16,
+    U24,
+    U32,
+}
+
+impl LengthPrefix {
+    /// Size of the prefix in bytes.
+    pub fn size(&self) -> usize {
+        match self {
+            LengthPrefix::U8 => 1,
+            LengthPrefix::U16 => 2,
+            LengthPrefix::U24 => 3,
+            LengthPrefix::U32 => 4,
+        }
+    }
+
+    /// Largest length the prefix can express.
+    pub fn max_len(&self) -> usize {
+        (u32::MAX >> (32 - 8 * self.size())) as usize
+    }
+}
+
+/// Appends `data` preceded by its length. Fails if the length exceeds
+/// `max_len` or what the prefix can express.
+pub fn write_length_prefixed(
+    bytes: &mut Vec<u8>,
+    prefix: LengthPrefix,
+    data: &[u8],
+    max_len: usize,
+) -> Result<(), ParseError> {
+    if data.len() > max_len.min(prefix.max_len()) {
+        return Err(ParseError::InvalidField("length"));
+    }
+    let size = prefix.size();
+    bytes.extend_from_slice(&(data.len() as u32).to_be_bytes()[4 - size..]);
+    bytes.extend_from_slice(data);
+    Ok(())
+}
+
+/// Reads a vector preceded by its length, returning its contents and the
+/// number of bytes consumed, prefix included. Fails if the length exceeds
+/// `max_len` or the input.
+pub fn read_length_prefixed(
+    buf: &[u8],
+    prefix: LengthPrefix,
+    max_len: usize,
+) -> Result<(&[u8], usize), ParseError> {
+    let size = prefix.size();
+    ensure_len(buf, size)?;
+    let len = buf[..size]
+        .iter()
+        .fold(0usize, |len, byte| len << 8 | *byte as usize);
+    if len > max_len {
+        return Err(ParseError::InvalidField("length"));
+    }
+    ensure_len(buf, size + len)?;
+    Ok((&buf[size..size + len], size + len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic xorshift generator, so failures are reproducible.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn bytes(&mut self, max_len: usize) -> Vec<u8> {
+            let len = self.next() as usize % (max_len + 1);
+            (0..len).map(|_| self.next() as u8).collect()
+        }
+    }
+
+    /// Feeds every one and two byte input and many random ones to `check`.
+    fn fuzz(mut check: impl FnMut(&[u8])) {
+        check(&[]);
+        for first in 0..=255u8 {
+            check(&[first]);
+            for second in 0..=255u8 {
+                check(&[first, second]);
+            }
+        }
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..100_000 {
+            check(&rng.bytes(16));
+        }
+    }
+
+    #[test]
+    fn fuzz_quic_varint() {
+        fuzz(|buf| {
+            if let Ok((value, len)) = read_quic_varint(buf) {
+                assert!(len <= buf.len());
+                assert!(value <= QUIC_VARINT_MAX);
+                let mut encoded = Vec::new();
+                write_quic_varint(&mut encoded, value).unwrap();
+                assert!(encoded.len() <= len);
+                assert_eq!(read_quic_varint(&encoded).unwrap(), (value, encoded.len()));
+            }
+        });
+    }
+
+    #[test]
+    fn fuzz_varint() {
+        fuzz(|buf| {
+            if let Ok((value, len)) = read_varint(buf) {
+                assert!(len <= buf.len().min(10));
+                let mut encoded = Vec::new();
+                write_varint(&mut encoded, value);
+                assert!(encoded.len() <= len);
+                assert_eq!(read_varint(&encoded).unwrap(), (value, encoded.len()));
+            }
+        });
+    }
+
+    #[test]
+    fn fuzz_length_prefixed() {
+        let prefixes = [
+            LengthPrefix::U8,
+            LengthPrefix::U16,
+            LengthPrefix::U24,
+            LengthPrefix::U32,
+        ];
+        fuzz(|buf| {
+            for prefix in prefixes {
+                for max_len in [0, 3, usize::MAX] {
+                    if let Ok((data, len)) = read_length_prefixed(buf, prefix, max_len) {
+                        assert!(data.len() <= max_len);
+                        assert_eq!(len, prefix.size() + data.len());
+                        let mut encoded = Vec::new();
+                        write_length_prefixed(&mut encoded, prefix, data, max_len).unwrap();
+                        assert_eq!(encoded, buf[..len]);
+                    }
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn varint_boundaries() {
+        for value in [
+            0,
+            0x3f,
+            0x40,
+            0x3fff,
+            0x4000,
+            0x3fff_ffff,
+            0x4000_0000,
+            QUIC_VARINT_MAX,
+        ] {
+            let mut encoded = Vec::new();
+            write_quic_varint(&mut encoded, value).unwrap();
+            assert_eq!(encoded.len(), quic_varint_len(value).unwrap());
+            assert_eq!(read_quic_varint(&encoded).unwrap(), (value, encoded.len()));
+        }
+        assert!(write_quic_varint(&mut Vec::new(), QUIC_VARINT_MAX + 1).is_err());
+
+        let mut encoded = Vec::new();
+        write_varint(&mut encoded, u64::MAX);
+        assert_eq!(encoded.len(), 10);
+        assert_eq!(read_varint(&encoded).unwrap(), (u64::MAX, 10));
+        encoded[9] = 0x02;
+        assert!(read_varint(&encoded).is_err());
+        assert!(read_varint(&[0x80; 11]).is_err());
+
+        for value in [0, 1, -1, i64::MAX, i64::MIN] {
+            assert_eq!(unzigzag(zigzag(value)), value);
+        }
+    }
+
+    #[test]
+    fn bits_round_trip() {
+        let mut rng = Rng(1);
+        for _ in 0..10_000 {
+            let mut bytes = [0u8; 10];
+            bytes.iter_mut().for_each(|byte| *byte = rng.next() as u8);
+            let original = bytes;
+            let width = 1 + rng.next() as usize % 16;
+            let offset = rng.next() as usize % (80 - width + 1);
+            let value = rng.next();
+            set_bits(&mut bytes, offset, width, value);
+            assert_eq!(get_bits(&bytes, offset, width), value & ((1 << width) - 1));
+            for bit in (0..80).filter(|bit| *bit < offset || *bit >= offset + width) {
+                assert_eq!(get_bits(&bytes, bit, 1), get_bits(&original, bit, 1));
+            }
+        }
+    }
+}
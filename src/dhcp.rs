@@ -0,0 +1,658 @@
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use std::time::{Duration, Instant};
+
+use crate::ethernet::MacAddr;
+use crate::ipv4::IPv4;
+use crate::limits::{Limit, Limits};
+use crate::udp::{self, UDP};
+use crate::util::{ParseError, PseudoHeader, ensure_len, read_ipv4};
+
+/// Lease time handed out by `LeaseDatabase::new`.
+pub const DEFAULT_LEASE_DURATION: Duration = Duration::from_secs(86400);
+
+/// An address leased to a client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DhcpLease {
+    pub ip: Ipv4Addr,
+    pub lease_start: Instant,
+    pub lease_duration: Duration,
+    pub hostname: Option<String>,
+}
+
+impl DhcpLease {
+    /// Returns true once the lease time has elapsed.
+    pub fn is_expired(&self) -> bool {
+        self.lease_start.elapsed() >= self.lease_duration
+    }
+}
+
+/// Address pool of a simulated DHCP server.
+#[derive(Debug, Clone)]
+pub struct LeaseDatabase {
+    pub address_pool: Vec<Ipv4Addr>,
+    pub leases: HashMap<MacAddr, DhcpLease>,
+    pub lease_duration: Duration,
+}
+
+impl LeaseDatabase {
+    /// Constructor to create a database over `address_pool` with the default
+    /// lease time.
+    pub fn new(address_pool: Vec<Ipv4Addr>) -> Self {
+        LeaseDatabase {
+            address_pool,
+            leases: HashMap::new(),
+            lease_duration: DEFAULT_LEASE_DURATION,
+        }
+    }
+
+    /// Builds a pool of every address from `first` to `last` inclusive.
+    pub fn with_range(first: Ipv4Addr, last: Ipv4Addr) -> Self {
+        let pool = (u32::from(first)..=u32::from(last))
+            .map(Ipv4Addr::from)
+            .collect();
+        LeaseDatabase::new(pool)
+    }
+
+    /// Sets the lease time of new leases.
+    pub fn set_lease_duration(mut self, lease_duration: Duration) -> Self {
+        self.lease_duration = lease_duration;
+        self
+    }
+
+    /// Returns the lease of `mac`, if any.
+    pub fn lease(&self, mac: &MacAddr) -> Option<&DhcpLease> {
+        self.leases.get(mac)
+    }
+
+    /// Returns true if `ip` is in the pool and not held by an active lease.
+    pub fn is_free(&self, ip: Ipv4Addr) -> bool {
+        self.address_pool.contains(&ip)
+            && !self
+                .leases
+                .values()
+                .any(|lease| lease.ip == ip && !lease.is_expired())
+    }
+
+    /// Leases an address to `mac` and returns it. A client that already
+    /// holds a lease gets it renewed. Otherwise `requested_ip` is used if it
+    /// is free, else the first free address of the pool. Addresses of
+    /// expired leases are reused. Returns `None` if the pool is exhausted.
+    pub fn allocate(&mut self, mac: MacAddr, requested_ip: Option<Ipv4Addr>) -> Option<Ipv4Addr> {
+        if let Some(lease) = self.leases.get_mut(&mac) {
+            lease.lease_start = Instant::now();
+            return Some(lease.ip);
+        }
+        let ip = requested_ip.filter(|ip| self.is_free(*ip)).or_else(|| {
+            self.address_pool
+                .iter()
+                .copied()
+                .find(|ip| self.is_free(*ip))
+        })?;
+        self.leases.retain(|_, lease| lease.ip != ip);
+        self.leases.insert(
+            mac,
+            DhcpLease {
+                ip,
+                lease_start: Instant::now(),
+                lease_duration: self.lease_duration,
+                hostname: None,
+            },
+        );
+        Some(ip)
+    }
+
+    /// Records the hostname a client sent.
+    pub fn set_hostname(&mut self, mac: &MacAddr, hostname: impl Into<String>) {
+        if let Some(lease) = self.leases.get_mut(mac) {
+            lease.hostname = Some(hostname.into());
+        }
+    }
+
+    /// Frees the lease of `mac`.
+    pub fn release(&mut self, mac: MacAddr) {
+        self.leases.remove(&mac);
+    }
+
+    /// Clients whose lease has expired.
+    pub fn expired_leases(&self) -> Vec<MacAddr> {
+        self.leases
+            .iter()
+            .filter(|(_, lease)| lease.is_expired())
+            .map(|(mac, _)| *mac)
+            .collect()
+    }
+}
+
+// --- MESSAGES ---
+
+// DHCP message (RFC 2131 section 2), the BOOTP format with options,
+// carried over UDP between ports 68 (client) and 67 (server):
+//
+//  0                   1                   2                   3
+//  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |     op (1)    |   htype (1)   |   hlen (1)    |   hops (1)    |
+// +---------------+---------------+---------------+---------------+
+// |                            xid (4)                            |
+// +-------------------------------+-------------------------------+
+// |           secs (2)            |           flags (2)           |
+// +-------------------------------+-------------------------------+
+// |                          ciaddr  (4)                          |
+// |                          yiaddr  (4)                          |
+// |                          siaddr  (4)                          |
+// |                          giaddr  (4)                          |
+// +---------------------------------------------------------------+
+// |                          chaddr  (16)                         |
+// |                          sname   (64)                         |
+// |                          file    (128)                        |
+// +---------------------------------------------------------------+
+// |                  magic cookie 99.130.83.99                    |
+// |                          options ...                          |
+// +---------------------------------------------------------------+
+//
+// Options are (code, length, data) with 8-bit code and length, except Pad
+// (0) and End (255) which are a single byte.
+
+pub const SERVER_PORT: u16 = 67;
+pub const CLIENT_PORT: u16 = 68;
+
+// Operations.
+pub const OP_BOOTREQUEST: u8 = 1;
+pub const OP_BOOTREPLY: u8 = 2;
+
+/// Hardware type of Ethernet.
+pub const HTYPE_ETHERNET: u8 = 1;
+
+/// Flag asking the server to broadcast its replies.
+pub const FLAG_BROADCAST: u16 = 0x8000;
+
+/// Length of the fixed part of a message, magic cookie excluded.
+pub const HEADER_LEN: usize = 236;
+
+/// Magic cookie preceding the options (RFC 2131 section 3).
+pub const MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99];
+
+// Option codes (RFC 2132).
+pub const OPTION_PAD: u8 = 0;
+pub const OPTION_SUBNET_MASK: u8 = 1;
+pub const OPTION_ROUTER: u8 = 3;
+pub const OPTION_DNS_SERVERS: u8 = 6;
+pub const OPTION_HOST_NAME: u8 = 12;
+pub const OPTION_REQUESTED_IP: u8 = 50;
+pub const OPTION_LEASE_TIME: u8 = 51;
+pub const OPTION_MESSAGE_TYPE: u8 = 53;
+pub const OPTION_SERVER_ID: u8 = 54;
+pub const OPTION_PARAMETER_REQUEST_LIST: u8 = 55;
+pub const OPTION_RENEWAL_TIME: u8 = 58;
+pub const OPTION_REBINDING_TIME: u8 = 59;
+pub const OPTION_CLIENT_ID: u8 = 61;
+pub const OPTION_END: u8 = 255;
+
+/// DHCP message types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DhcpMessageType {
+    Discover = 1,
+    Offer = 2,
+    Request = 3,
+    Decline = 4,
+    Ack = 5,
+    Nak = 6,
+    Release = 7,
+    Inform = 8,
+}
+
+impl TryFrom<u8> for DhcpMessageType {
+    type Error = ParseError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(DhcpMessageType::Discover),
+            2 => Ok(DhcpMessageType::Offer),
+            3 => Ok(DhcpMessageType::Request),
+            4 => Ok(DhcpMessageType::Decline),
+            5 => Ok(DhcpMessageType::Ack),
+            6 => Ok(DhcpMessageType::Nak),
+            7 => Ok(DhcpMessageType::Release),
+            8 => Ok(DhcpMessageType::Inform),
+            _ => Err(ParseError::InvalidField("DHCP message type")),
+        }
+    }
+}
+
+/// DHCP option. Pad and End are implied by the encoding.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DhcpOption {
+    SubnetMask(Ipv4Addr),
+    Router(Vec<Ipv4Addr>),
+    DnsServers(Vec<Ipv4Addr>),
+    HostName(String),
+    /// Address the client asks for in a Discover or Request.
+    RequestedIp(Ipv4Addr),
+    /// Lease time in seconds.
+    LeaseTime(u32),
+    MessageType(DhcpMessageType),
+    /// Address of the server, identifying it to clients.
+    ServerId(Ipv4Addr),
+    /// Option codes the client asks for.
+    ParameterRequestList(Vec<u8>),
+    /// T1 in seconds.
+    RenewalTime(u32),
+    /// T2 in seconds.
+    RebindingTime(u32),
+    /// Hardware type followed by the address, or any opaque identifier.
+    ClientId(Vec<u8>),
+    /// Option without a dedicated variant.
+    Raw {
+        code: u8,
+        data: Vec<u8>,
+    },
+}
+
+impl DhcpOption {
+    /// Option code.
+    pub fn code(&self) -> u8 {
+        match self {
+            DhcpOption::SubnetMask(_) => OPTION_SUBNET_MASK,
+            DhcpOption::Router(_) => OPTION_ROUTER,
+            DhcpOption::DnsServers(_) => OPTION_DNS_SERVERS,
+            DhcpOption::HostName(_) => OPTION_HOST_NAME,
+            DhcpOption::RequestedIp(_) => OPTION_REQUESTED_IP,
+            DhcpOption::LeaseTime(_) => OPTION_LEASE_TIME,
+            DhcpOption::MessageType(_) => OPTION_MESSAGE_TYPE,
+            DhcpOption::ServerId(_) => OPTION_SERVER_ID,
+            DhcpOption::ParameterRequestList(_) => OPTION_PARAMETER_REQUEST_LIST,
+            DhcpOption::RenewalTime(_) => OPTION_RENEWAL_TIME,
+            DhcpOption::RebindingTime(_) => OPTION_REBINDING_TIME,
+            DhcpOption::ClientId(_) => OPTION_CLIENT_ID,
+            DhcpOption::Raw { code, .. } => *code,
+        }
+    }
+
+    // --- SERIALIZATION ---
+
+    /// Serializes the option; data beyond 255 bytes is cut off.
+    pub fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        let mut data = Vec::new();
+        match self {
+            DhcpOption::SubnetMask(addr)
+            | DhcpOption::RequestedIp(addr)
+            | DhcpOption::ServerId(addr) => data.extend_from_slice(&addr.octets()),
+            DhcpOption::Router(addrs) | DhcpOption::DnsServers(addrs) => {
+                for addr in addrs {
+                    data.extend_from_slice(&addr.octets());
+                }
+            }
+            DhcpOption::HostName(name) => data.extend_from_slice(name.as_bytes()),
+            DhcpOption::LeaseTime(seconds)
+            | DhcpOption::RenewalTime(seconds)
+            | DhcpOption::RebindingTime(seconds) => data.extend_from_slice(&seconds.to_be_bytes()),
+            DhcpOption::MessageType(msg_type) => data.push(*msg_type as u8),
+            DhcpOption::ParameterRequestList(codes) => data.extend_from_slice(codes),
+            DhcpOption::ClientId(data_) | DhcpOption::Raw { data: data_, .. } => {
+                data.extend_from_slice(data_)
+            }
+        }
+        data.truncate(u8::MAX as usize);
+        bytes.push(self.code());
+        bytes.push(data.len() as u8);
+        bytes.extend_from_slice(&data);
+    }
+
+    /// Parses the option at the start of `buf`, which must not be Pad or
+    /// End. Returns it with the number of bytes it occupies. Malformed
+    /// values of known options are returned as `DhcpOption::Raw`.
+    pub fn from_bytes(buf: &[u8]) -> Result<(Self, usize), ParseError> {
+        ensure_len(buf, 2)?;
+        let code = buf[0];
+        let len = buf[1] as usize;
+        ensure_len(buf, 2 + len)?;
+        let data = &buf[2..2 + len];
+        let addr = || (len == 4).then(|| read_ipv4(data, 0));
+        let addrs = || {
+            (len > 0 && len.is_multiple_of(4)).then(|| {
+                data.chunks_exact(4)
+                    .map(|addr| read_ipv4(addr, 0))
+                    .collect()
+            })
+        };
+        let seconds = || <[u8; 4]>::try_from(data).ok().map(u32::from_be_bytes);
+        let option = match code {
+            OPTION_SUBNET_MASK => addr().map(DhcpOption::SubnetMask),
+            OPTION_ROUTER => addrs().map(DhcpOption::Router),
+            OPTION_DNS_SERVERS => addrs().map(DhcpOption::DnsServers),
+            OPTION_HOST_NAME => String::from_utf8(data.to_vec())
+                .ok()
+                .map(DhcpOption::HostName),
+            OPTION_REQUESTED_IP => addr().map(DhcpOption::RequestedIp),
+            OPTION_LEASE_TIME => seconds().map(DhcpOption::LeaseTime),
+            OPTION_MESSAGE_TYPE if len == 1 => DhcpMessageType::try_from(data[0])
+                .ok()
+                .map(DhcpOption::MessageType),
+            OPTION_SERVER_ID => addr().map(DhcpOption::ServerId),
+            OPTION_PARAMETER_REQUEST_LIST => Some(DhcpOption::ParameterRequestList(data.to_vec())),
+            OPTION_RENEWAL_TIME => seconds().map(DhcpOption::RenewalTime),
+            OPTION_REBINDING_TIME => seconds().map(DhcpOption::RebindingTime),
+            OPTION_CLIENT_ID => Some(DhcpOption::ClientId(data.to_vec())),
+            _ => None,
+        };
+        let option = option.unwrap_or_else(|| DhcpOption::Raw {
+            code,
+            data: data.to_vec(),
+        });
+        Ok((option, 2 + len))
+    }
+}
+
+/// DHCP message.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Dhcp {
+    pub op: u8,
+    pub htype: u8,
+    pub hlen: u8,
+    /// Relay agents the message went through.
+    pub hops: u8,
+    /// Transaction ID, chosen by the client.
+    pub xid: u32,
+    /// Seconds since the client began acquiring or renewing an address.
+    pub secs: u16,
+    pub flags: u16,
+    /// Address of a client that already has one.
+    pub ciaddr: Ipv4Addr,
+    /// Address offered or assigned to the client.
+    pub yiaddr: Ipv4Addr,
+    /// Next server of the bootstrap process.
+    pub siaddr: Ipv4Addr,
+    /// Relay agent address.
+    pub giaddr: Ipv4Addr,
+    /// Client hardware address, padded with zeros.
+    pub chaddr: [u8; 16],
+    /// Server host name, NUL terminated.
+    pub sname: [u8; 64],
+    /// Boot file name, NUL terminated.
+    pub file: [u8; 128],
+    pub options: Vec<DhcpOption>,
+}
+
+impl Dhcp {
+    /// Constructor to create a message of the client with Ethernet address
+    /// `client_mac`, with zero addresses and no options.
+    pub fn new(op: u8, xid: u32, client_mac: MacAddr) -> Self {
+        let mut chaddr = [0; 16];
+        chaddr[..6].copy_from_slice(&client_mac.octets());
+        Dhcp {
+            op,
+            htype: HTYPE_ETHERNET,
+            hlen: 6,
+            hops: 0,
+            xid,
+            secs: 0,
+            flags: 0,
+            ciaddr: Ipv4Addr::UNSPECIFIED,
+            yiaddr: Ipv4Addr::UNSPECIFIED,
+            siaddr: Ipv4Addr::UNSPECIFIED,
+            giaddr: Ipv4Addr::UNSPECIFIED,
+            chaddr,
+            sname: [0; 64],
+            file: [0; 128],
+            options: Vec::new(),
+        }
+    }
+
+    /// Discover broadcast by `client_mac`, asking for the subnet mask,
+    /// router, DNS servers and lease time.
+    pub fn discover(xid: u32, client_mac: MacAddr) -> Self {
+        Dhcp::new(OP_BOOTREQUEST, xid, client_mac)
+            .set_flags(FLAG_BROADCAST)
+            .add_option(DhcpOption::MessageType(DhcpMessageType::Discover))
+            .add_option(DhcpOption::ParameterRequestList(vec![
+                OPTION_SUBNET_MASK,
+                OPTION_ROUTER,
+                OPTION_DNS_SERVERS,
+                OPTION_LEASE_TIME,
+            ]))
+    }
+
+    /// Offer of `your_ip` for `lease_time` seconds answering `discover`,
+    /// from the server at `server_ip`.
+    pub fn offer(
+        discover: &Dhcp,
+        your_ip: Ipv4Addr,
+        server_ip: Ipv4Addr,
+        lease_time: u32,
+        subnet_mask: Ipv4Addr,
+    ) -> Self {
+        Dhcp::reply(discover, DhcpMessageType::Offer, server_ip)
+            .set_yiaddr(your_ip)
+            .add_option(DhcpOption::LeaseTime(lease_time))
+            .add_option(DhcpOption::SubnetMask(subnet_mask))
+    }
+
+    /// Request of the address offered by `offer`, selecting its server.
+    /// Fails if the offer has no Server Identifier.
+    pub fn request(offer: &Dhcp) -> Result<Self, ParseError> {
+        let server_id = offer
+            .server_id()
+            .ok_or(ParseError::InvalidField("server identifier"))?;
+        let request = Dhcp {
+            htype: offer.htype,
+            hlen: offer.hlen,
+            chaddr: offer.chaddr,
+            ..Dhcp::new(OP_BOOTREQUEST, offer.xid, MacAddr::default())
+        };
+        Ok(request
+            .set_flags(offer.flags)
+            .set_giaddr(offer.giaddr)
+            .add_option(DhcpOption::MessageType(DhcpMessageType::Request))
+            .add_option(DhcpOption::RequestedIp(offer.yiaddr))
+            .add_option(DhcpOption::ServerId(server_id)))
+    }
+
+    /// Ack assigning `your_ip` for `lease_time` seconds, answering
+    /// `request`, from the server at `server_ip`.
+    pub fn ack(
+        request: &Dhcp,
+        your_ip: Ipv4Addr,
+        server_ip: Ipv4Addr,
+        lease_time: u32,
+        subnet_mask: Ipv4Addr,
+    ) -> Self {
+        Dhcp::reply(request, DhcpMessageType::Ack, server_ip)
+            .set_yiaddr(your_ip)
+            .add_option(DhcpOption::LeaseTime(lease_time))
+            .add_option(DhcpOption::SubnetMask(subnet_mask))
+    }
+
+    /// Nak refusing `request`, from the server at `server_ip`.
+    pub fn nak(request: &Dhcp, server_ip: Ipv4Addr) -> Self {
+        Dhcp::reply(request, DhcpMessageType::Nak, server_ip)
+    }
+
+    /// Reply of type `msg_type` to `request`, keeping its transaction,
+    /// flags, relay agent and client address.
+    fn reply(request: &Dhcp, msg_type: DhcpMessageType, server_ip: Ipv4Addr) -> Self {
+        let reply = Dhcp {
+            htype: request.htype,
+            hlen: request.hlen,
+            chaddr: request.chaddr,
+            ..Dhcp::new(OP_BOOTREPLY, request.xid, MacAddr::default())
+        };
+        reply
+            .set_flags(request.flags)
+            .set_giaddr(request.giaddr)
+            .add_option(DhcpOption::MessageType(msg_type))
+            .add_option(DhcpOption::ServerId(server_ip))
+    }
+
+    /// Ethernet address of the client, if the hardware type is Ethernet.
+    pub fn client_mac(&self) -> Option<MacAddr> {
+        (self.htype == HTYPE_ETHERNET && self.hlen == 6).then(|| {
+            let mut mac = [0; 6];
+            mac.copy_from_slice(&self.chaddr[..6]);
+            MacAddr(mac)
+        })
+    }
+
+    /// Returns the first option of code `code`.
+    pub fn find_option(&self, code: u8) -> Option<&DhcpOption> {
+        self.options.iter().find(|option| option.code() == code)
+    }
+
+    /// Message type, from the DHCP Message Type option. `None` for BOOTP
+    /// messages.
+    pub fn message_type(&self) -> Option<DhcpMessageType> {
+        match self.find_option(OPTION_MESSAGE_TYPE) {
+            Some(DhcpOption::MessageType(msg_type)) => Some(*msg_type),
+            _ => None,
+        }
+    }
+
+    pub fn requested_ip(&self) -> Option<Ipv4Addr> {
+        match self.find_option(OPTION_REQUESTED_IP) {
+            Some(DhcpOption::RequestedIp(addr)) => Some(*addr),
+            _ => None,
+        }
+    }
+
+    pub fn server_id(&self) -> Option<Ipv4Addr> {
+        match self.find_option(OPTION_SERVER_ID) {
+            Some(DhcpOption::ServerId(addr)) => Some(*addr),
+            _ => None,
+        }
+    }
+
+    /// Wraps the message in a UDP datagram, from the client port to the
+    /// server port for requests and the other way for replies, in an IPv4
+    /// packet with both checksums computed.
+    pub fn to_ipv4(&self, source: Ipv4Addr, destination: Ipv4Addr) -> IPv4 {
+        let (source_port, destination_port) = if self.op == OP_BOOTREPLY {
+            (SERVER_PORT, CLIENT_PORT)
+        } else {
+            (CLIENT_PORT, SERVER_PORT)
+        };
+        let udp = UDP::new(source_port, destination_port, self.to_bytes()).with_checksum(
+            &PseudoHeader::V4 {
+                source,
+                destination,
+            },
+        );
+        IPv4::with_payload(source, destination, udp::IP_PROTOCOL, udp.to_bytes())
+    }
+
+    // --- SETTER METHODS ---
+
+    pub fn set_xid(mut self, xid: u32) -> Self {
+        self.xid = xid;
+        self
+    }
+
+    pub fn set_secs(mut self, secs: u16) -> Self {
+        self.secs = secs;
+        self
+    }
+
+    pub fn set_flags(mut self, flags: u16) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    pub fn set_ciaddr(mut self, ciaddr: Ipv4Addr) -> Self {
+        self.ciaddr = ciaddr;
+        self
+    }
+
+    pub fn set_yiaddr(mut self, yiaddr: Ipv4Addr) -> Self {
+        self.yiaddr = yiaddr;
+        self
+    }
+
+    pub fn set_siaddr(mut self, siaddr: Ipv4Addr) -> Self {
+        self.siaddr = siaddr;
+        self
+    }
+
+    pub fn set_giaddr(mut self, giaddr: Ipv4Addr) -> Self {
+        self.giaddr = giaddr;
+        self
+    }
+
+    pub fn add_option(mut self, option: DhcpOption) -> Self {
+        self.options.push(option);
+        self
+    }
+
+    // --- SERIALIZATION ---
+
+    /// Serializes the message, the options followed by End.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + MAGIC_COOKIE.len() + 64);
+        bytes.extend_from_slice(&[self.op, self.htype, self.hlen, self.hops]);
+        bytes.extend_from_slice(&self.xid.to_be_bytes());
+        bytes.extend_from_slice(&self.secs.to_be_bytes());
+        bytes.extend_from_slice(&self.flags.to_be_bytes());
+        for addr in [self.ciaddr, self.yiaddr, self.siaddr, self.giaddr] {
+            bytes.extend_from_slice(&addr.octets());
+        }
+        bytes.extend_from_slice(&self.chaddr);
+        bytes.extend_from_slice(&self.sname);
+        bytes.extend_from_slice(&self.file);
+        bytes.extend_from_slice(&MAGIC_COOKIE);
+        for option in &self.options {
+            option.serialize_into(&mut bytes);
+        }
+        bytes.push(OPTION_END);
+        bytes
+    }
+
+    /// Parses a message from a UDP payload. Options are read up to End,
+    /// skipping Pad; a BOOTP message without the magic cookie has none.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        Dhcp::from_bytes_with_limits(buf, &Limits::default())
+    }
+
+    /// Same as `from_bytes`, bounding the number of options by `limits`.
+    pub fn from_bytes_with_limits(buf: &[u8], limits: &Limits) -> Result<Self, ParseError> {
+        ensure_len(buf, HEADER_LEN)?;
+        let u32_at = |offset: usize| {
+            u32::from_be_bytes([
+                buf[offset],
+                buf[offset + 1],
+                buf[offset + 2],
+                buf[offset + 3],
+            ])
+        };
+        let mut options = Vec::new();
+        if buf[HEADER_LEN..].starts_with(&MAGIC_COOKIE) {
+            let mut offset = HEADER_LEN + MAGIC_COOKIE.len();
+            while offset < buf.len() {
+                match buf[offset] {
+                    OPTION_END => break,
+                    OPTION_PAD => offset += 1,
+                    _ => {
+                        limits.check(Limit::OptionsPerLayer, options.len() + 1)?;
+                        let (option, len) = DhcpOption::from_bytes(&buf[offset..])?;
+                        options.push(option);
+                        offset += len;
+                    }
+                }
+            }
+        }
+        Ok(Dhcp {
+            op: buf[0],
+            htype: buf[1],
+            hlen: buf[2],
+            hops: buf[3],
+            xid: u32_at(4),
+            secs: u16::from_be_bytes([buf[8], buf[9]]),
+            flags: u16::from_be_bytes([buf[10], buf[11]]),
+            ciaddr: read_ipv4(buf, 12),
+            yiaddr: read_ipv4(buf, 16),
+            siaddr: read_ipv4(buf, 20),
+            giaddr: read_ipv4(buf, 24),
+            chaddr: buf[28..44].try_into().unwrap(),
+            sname: buf[44..108].try_into().unwrap(),
+            file: buf[108..HEADER_LEN].try_into().unwrap(),
+            options,
+        })
+    }
+}
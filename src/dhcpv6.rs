@@ -0,0 +1,665 @@
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::net::Ipv6Addr;
+use std::time::SystemTime;
+
+use crate::ethernet::MacAddr;
+use crate::ipv6::IPv6;
+use crate::limits::{Limit, Limits};
+use crate::udp::{self, UDP};
+use crate::util::{ParseError, PseudoHeader, ensure_len, read_ipv6};
+
+// DHCPv6 (RFC 8415), carried over UDP from port 546 to port 547.
+//
+// Client/server message:              Relay message:
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |   msg-type    | transaction-id |   |   msg-type    |   hop-count   |
+// +-+-+-+-+-+-+-+-+ (3 bytes)      |   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                                |   |      link-address (16)        |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+   |      peer-address (16)        |
+// |         options ...            |   |         options ...           |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// Options are (code, length, data) with 16-bit code and length, without
+// padding. IA_NA, IA_TA and IAADDR end with options of their own.
+
+pub const CLIENT_PORT: u16 = 546;
+pub const SERVER_PORT: u16 = 547;
+
+/// All_DHCP_Relay_Agents_and_Servers, where clients send their messages.
+pub const ALL_RELAY_AGENTS_AND_SERVERS: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 1, 2);
+
+// Option codes.
+pub const OPTION_CLIENTID: u16 = 1;
+pub const OPTION_SERVERID: u16 = 2;
+pub const OPTION_IA_NA: u16 = 3;
+pub const OPTION_IA_TA: u16 = 4;
+pub const OPTION_IAADDR: u16 = 5;
+pub const OPTION_ORO: u16 = 6;
+pub const OPTION_PREFERENCE: u16 = 7;
+pub const OPTION_ELAPSED_TIME: u16 = 8;
+pub const OPTION_RELAY_MSG: u16 = 9;
+pub const OPTION_STATUS_CODE: u16 = 13;
+pub const OPTION_DNS_SERVERS: u16 = 23;
+
+// Status codes.
+pub const STATUS_SUCCESS: u16 = 0;
+pub const STATUS_UNSPEC_FAIL: u16 = 1;
+pub const STATUS_NO_ADDRS_AVAIL: u16 = 2;
+pub const STATUS_NO_BINDING: u16 = 3;
+pub const STATUS_NOT_ON_LINK: u16 = 4;
+pub const STATUS_USE_MULTICAST: u16 = 5;
+
+/// DUID type based on a link-layer address (RFC 8415 section 11.4).
+pub const DUID_LL: u16 = 3;
+
+/// Length of a relay message header in bytes.
+const RELAY_HEADER_LEN: usize = 34;
+
+/// DHCPv6 message types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Dhcpv6MsgType {
+    Solicit = 1,
+    Advertise = 2,
+    Request = 3,
+    Confirm = 4,
+    Renew = 5,
+    Rebind = 6,
+    Reply = 7,
+    Release = 8,
+    Decline = 9,
+    Reconfigure = 10,
+    InformRequest = 11,
+    RelayForw = 12,
+    RelayRepl = 13,
+}
+
+impl Dhcpv6MsgType {
+    /// Returns true for Relay-forward and Relay-reply, which use the relay
+    /// message format.
+    pub fn is_relay(&self) -> bool {
+        matches!(self, Dhcpv6MsgType::RelayForw | Dhcpv6MsgType::RelayRepl)
+    }
+
+    /// Returns true for the messages servers send to clients.
+    pub fn is_from_server(&self) -> bool {
+        matches!(
+            self,
+            Dhcpv6MsgType::Advertise | Dhcpv6MsgType::Reply | Dhcpv6MsgType::Reconfigure
+        )
+    }
+}
+
+impl TryFrom<u8> for Dhcpv6MsgType {
+    type Error = ParseError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(Dhcpv6MsgType::Solicit),
+            2 => Ok(Dhcpv6MsgType::Advertise),
+            3 => Ok(Dhcpv6MsgType::Request),
+            4 => Ok(Dhcpv6MsgType::Confirm),
+            5 => Ok(Dhcpv6MsgType::Renew),
+            6 => Ok(Dhcpv6MsgType::Rebind),
+            7 => Ok(Dhcpv6MsgType::Reply),
+            8 => Ok(Dhcpv6MsgType::Release),
+            9 => Ok(Dhcpv6MsgType::Decline),
+            10 => Ok(Dhcpv6MsgType::Reconfigure),
+            11 => Ok(Dhcpv6MsgType::InformRequest),
+            12 => Ok(Dhcpv6MsgType::RelayForw),
+            13 => Ok(Dhcpv6MsgType::RelayRepl),
+            _ => Err(ParseError::InvalidField("msg-type")),
+        }
+    }
+}
+
+/// DHCPv6 option.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Dhcpv6Option {
+    /// DUID of the client.
+    ClientId(Vec<u8>),
+    /// DUID of the server.
+    ServerId(Vec<u8>),
+    /// Identity association for non-temporary addresses.
+    IaNa {
+        iaid: u32,
+        t1: u32,
+        t2: u32,
+        ia_options: Vec<Dhcpv6Option>,
+    },
+    /// Identity association for temporary addresses.
+    IaTa {
+        iaid: u32,
+        ia_options: Vec<Dhcpv6Option>,
+    },
+    /// Address of an IA_NA or IA_TA, with lifetimes in seconds.
+    IaAddress {
+        addr: Ipv6Addr,
+        preferred: u32,
+        valid: u32,
+        options: Vec<Dhcpv6Option>,
+    },
+    /// Option Request option: codes the client asks for.
+    RequestedOptions(Vec<u16>),
+    Preference(u8),
+    /// Time since the client began the exchange, in hundredths of a second.
+    Elapsed(u16),
+    /// Message relayed by a Relay-forward or Relay-reply.
+    RelayMessage(Vec<u8>),
+    /// Outcome of the exchange or of an IA, e.g. `STATUS_NO_ADDRS_AVAIL`.
+    StatusCode {
+        code: u16,
+        message: String,
+    },
+    /// Option without a dedicated variant.
+    Raw {
+        code: u16,
+        data: Vec<u8>,
+    },
+}
+
+impl Dhcpv6Option {
+    /// Option code.
+    pub fn code(&self) -> u16 {
+        match self {
+            Dhcpv6Option::ClientId(_) => OPTION_CLIENTID,
+            Dhcpv6Option::ServerId(_) => OPTION_SERVERID,
+            Dhcpv6Option::IaNa { .. } => OPTION_IA_NA,
+            Dhcpv6Option::IaTa { .. } => OPTION_IA_TA,
+            Dhcpv6Option::IaAddress { .. } => OPTION_IAADDR,
+            Dhcpv6Option::RequestedOptions(_) => OPTION_ORO,
+            Dhcpv6Option::Preference(_) => OPTION_PREFERENCE,
+            Dhcpv6Option::Elapsed(_) => OPTION_ELAPSED_TIME,
+            Dhcpv6Option::RelayMessage(_) => OPTION_RELAY_MSG,
+            Dhcpv6Option::StatusCode { .. } => OPTION_STATUS_CODE,
+            Dhcpv6Option::Raw { code, .. } => *code,
+        }
+    }
+
+    // --- SERIALIZATION ---
+
+    pub fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.code().to_be_bytes());
+        let len_at = bytes.len();
+        bytes.extend_from_slice(&[0, 0]);
+        match self {
+            Dhcpv6Option::ClientId(duid) | Dhcpv6Option::ServerId(duid) => {
+                bytes.extend_from_slice(duid)
+            }
+            Dhcpv6Option::IaNa {
+                iaid,
+                t1,
+                t2,
+                ia_options,
+            } => {
+                for field in [iaid, t1, t2] {
+                    bytes.extend_from_slice(&field.to_be_bytes());
+                }
+                serialize_options_into(ia_options, bytes);
+            }
+            Dhcpv6Option::IaTa { iaid, ia_options } => {
+                bytes.extend_from_slice(&iaid.to_be_bytes());
+                serialize_options_into(ia_options, bytes);
+            }
+            Dhcpv6Option::IaAddress {
+                addr,
+                preferred,
+                valid,
+                options,
+            } => {
+                bytes.extend_from_slice(&addr.octets());
+                bytes.extend_from_slice(&preferred.to_be_bytes());
+                bytes.extend_from_slice(&valid.to_be_bytes());
+                serialize_options_into(options, bytes);
+            }
+            Dhcpv6Option::RequestedOptions(codes) => {
+                for code in codes {
+                    bytes.extend_from_slice(&code.to_be_bytes());
+                }
+            }
+            Dhcpv6Option::Preference(preference) => bytes.push(*preference),
+            Dhcpv6Option::Elapsed(elapsed) => bytes.extend_from_slice(&elapsed.to_be_bytes()),
+            Dhcpv6Option::StatusCode { code, message } => {
+                bytes.extend_from_slice(&code.to_be_bytes());
+                bytes.extend_from_slice(message.as_bytes());
+            }
+            Dhcpv6Option::RelayMessage(data) | Dhcpv6Option::Raw { data, .. } => {
+                bytes.extend_from_slice(data)
+            }
+        }
+        let len = (bytes.len() - len_at - 2) as u16;
+        bytes[len_at..len_at + 2].copy_from_slice(&len.to_be_bytes());
+    }
+
+    /// Parses the option at the start of `buf`. Returns it with the number
+    /// of bytes it occupies.
+    pub fn from_bytes(buf: &[u8]) -> Result<(Self, usize), ParseError> {
+        Dhcpv6Option::from_bytes_with_limits(buf, &Limits::default(), 1)
+    }
+
+    fn from_bytes_with_limits(
+        buf: &[u8],
+        limits: &Limits,
+        depth: usize,
+    ) -> Result<(Self, usize), ParseError> {
+        ensure_len(buf, 4)?;
+        let code = u16::from_be_bytes([buf[0], buf[1]]);
+        let len = u16::from_be_bytes([buf[2], buf[3]]) as usize;
+        ensure_len(buf, 4 + len)?;
+        let data = &buf[4..4 + len];
+        let option = match code {
+            OPTION_CLIENTID => Dhcpv6Option::ClientId(data.to_vec()),
+            OPTION_SERVERID => Dhcpv6Option::ServerId(data.to_vec()),
+            OPTION_IA_NA => {
+                ensure_len(data, 12)?;
+                limits.check(Limit::DecodeDepth, depth)?;
+                Dhcpv6Option::IaNa {
+                    iaid: u32::from_be_bytes([data[0], data[1], data[2], data[3]]),
+                    t1: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+                    t2: u32::from_be_bytes([data[8], data[9], data[10], data[11]]),
+                    ia_options: parse_options(&data[12..], limits, depth + 1)?,
+                }
+            }
+            OPTION_IA_TA => {
+                ensure_len(data, 4)?;
+                limits.check(Limit::DecodeDepth, depth)?;
+                Dhcpv6Option::IaTa {
+                    iaid: u32::from_be_bytes([data[0], data[1], data[2], data[3]]),
+                    ia_options: parse_options(&data[4..], limits, depth + 1)?,
+                }
+            }
+            OPTION_IAADDR => {
+                ensure_len(data, 24)?;
+                limits.check(Limit::DecodeDepth, depth)?;
+                Dhcpv6Option::IaAddress {
+                    addr: read_ipv6(data, 0),
+                    preferred: u32::from_be_bytes([data[16], data[17], data[18], data[19]]),
+                    valid: u32::from_be_bytes([data[20], data[21], data[22], data[23]]),
+                    options: parse_options(&data[24..], limits, depth + 1)?,
+                }
+            }
+            OPTION_ORO => {
+                if !len.is_multiple_of(2) {
+                    return Err(ParseError::InvalidField("option request length"));
+                }
+                Dhcpv6Option::RequestedOptions(
+                    data.chunks_exact(2)
+                        .map(|code| u16::from_be_bytes([code[0], code[1]]))
+                        .collect(),
+                )
+            }
+            OPTION_PREFERENCE => {
+                ensure_len(data, 1)?;
+                Dhcpv6Option::Preference(data[0])
+            }
+            OPTION_ELAPSED_TIME => {
+                ensure_len(data, 2)?;
+                Dhcpv6Option::Elapsed(u16::from_be_bytes([data[0], data[1]]))
+            }
+            OPTION_RELAY_MSG => Dhcpv6Option::RelayMessage(data.to_vec()),
+            OPTION_STATUS_CODE => {
+                ensure_len(data, 2)?;
+                Dhcpv6Option::StatusCode {
+                    code: u16::from_be_bytes([data[0], data[1]]),
+                    message: String::from_utf8_lossy(&data[2..]).into_owned(),
+                }
+            }
+            _ => Dhcpv6Option::Raw {
+                code,
+                data: data.to_vec(),
+            },
+        };
+        Ok((option, 4 + len))
+    }
+}
+
+fn serialize_options_into(options: &[Dhcpv6Option], bytes: &mut Vec<u8>) {
+    for option in options {
+        option.serialize_into(bytes);
+    }
+}
+
+/// Parses the options filling `buf`, at nesting level `depth`.
+fn parse_options(
+    buf: &[u8],
+    limits: &Limits,
+    depth: usize,
+) -> Result<Vec<Dhcpv6Option>, ParseError> {
+    let mut options = Vec::new();
+    let mut offset = 0;
+    while offset < buf.len() {
+        limits.check(Limit::OptionsPerLayer, options.len() + 1)?;
+        let (option, len) = Dhcpv6Option::from_bytes_with_limits(&buf[offset..], limits, depth)?;
+        options.push(option);
+        offset += len;
+    }
+    Ok(options)
+}
+
+/// Random transaction ID.
+fn random_transaction_id() -> [u8; 3] {
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos())
+        .unwrap_or_default();
+    let [a, b, c, ..] = RandomState::new().hash_one(nanos).to_be_bytes();
+    [a, b, c]
+}
+
+/// DUID-LL of the interface with address `mac`.
+pub fn duid_ll(mac: MacAddr) -> Vec<u8> {
+    let mut duid = Vec::with_capacity(10);
+    duid.extend_from_slice(&DUID_LL.to_be_bytes());
+    duid.extend_from_slice(&1u16.to_be_bytes());
+    duid.extend_from_slice(&mac.octets());
+    duid
+}
+
+/// DHCPv6 message exchanged between a client and a server.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Dhcpv6 {
+    pub msg_type: Dhcpv6MsgType,
+    pub transaction_id: [u8; 3],
+    pub options: Vec<Dhcpv6Option>,
+}
+
+impl Dhcpv6 {
+    /// Constructor to create a message without options.
+    pub fn new(msg_type: Dhcpv6MsgType, transaction_id: [u8; 3]) -> Self {
+        Dhcpv6 {
+            msg_type,
+            transaction_id,
+            options: Vec::new(),
+        }
+    }
+
+    /// Solicit from the client with DUID `client_duid`, with a random
+    /// transaction ID, an IA_NA of IAID 1 leaving the lifetimes to the
+    /// server, and a request for the DNS servers.
+    pub fn solicit(client_duid: Vec<u8>) -> Self {
+        Dhcpv6::new(Dhcpv6MsgType::Solicit, random_transaction_id())
+            .add_option(Dhcpv6Option::ClientId(client_duid))
+            .add_option(Dhcpv6Option::Elapsed(0))
+            .add_option(Dhcpv6Option::IaNa {
+                iaid: 1,
+                t1: 0,
+                t2: 0,
+                ia_options: Vec::new(),
+            })
+            .add_option(Dhcpv6Option::RequestedOptions(vec![OPTION_DNS_SERVERS]))
+    }
+
+    /// Advertise from the server with DUID `server_duid`, offering `addr`
+    /// in the first IA_NA or IA_TA of `solicit`. Lifetimes are in seconds;
+    /// T1 and T2 of an IA_NA are 0.5 and 0.8 times the preferred lifetime.
+    pub fn advertise(
+        solicit: &Dhcpv6,
+        server_duid: Vec<u8>,
+        addr: Ipv6Addr,
+        preferred: u32,
+        valid: u32,
+    ) -> Result<Self, ParseError> {
+        Dhcpv6::answer(
+            Dhcpv6MsgType::Advertise,
+            solicit,
+            server_duid,
+            addr,
+            preferred,
+            valid,
+        )
+    }
+
+    /// Request of the addresses offered by `advertise`, with a new random
+    /// transaction ID, echoing its client and server identifiers and IAs.
+    pub fn request(advertise: &Dhcpv6) -> Result<Self, ParseError> {
+        let client_duid = advertise
+            .client_id()
+            .ok_or(ParseError::InvalidField("client identifier"))?;
+        let server_duid = advertise
+            .server_id()
+            .ok_or(ParseError::InvalidField("server identifier"))?;
+        let mut request = Dhcpv6::new(Dhcpv6MsgType::Request, random_transaction_id())
+            .add_option(Dhcpv6Option::ClientId(client_duid.to_vec()))
+            .add_option(Dhcpv6Option::ServerId(server_duid.to_vec()))
+            .add_option(Dhcpv6Option::Elapsed(0));
+        for option in &advertise.options {
+            if matches!(
+                option,
+                Dhcpv6Option::IaNa { .. } | Dhcpv6Option::IaTa { .. }
+            ) {
+                request = request.add_option(option.clone());
+            }
+        }
+        Ok(request.add_option(Dhcpv6Option::RequestedOptions(vec![OPTION_DNS_SERVERS])))
+    }
+
+    /// Reply of the server named by `request`, assigning `addr` in its
+    /// first IA_NA or IA_TA, as `advertise` does.
+    pub fn reply(
+        request: &Dhcpv6,
+        addr: Ipv6Addr,
+        preferred: u32,
+        valid: u32,
+    ) -> Result<Self, ParseError> {
+        let server_duid = request
+            .server_id()
+            .ok_or(ParseError::InvalidField("server identifier"))?;
+        Dhcpv6::answer(
+            Dhcpv6MsgType::Reply,
+            request,
+            server_duid.to_vec(),
+            addr,
+            preferred,
+            valid,
+        )
+    }
+
+    /// Answer of type `msg_type` to `message`, in the same transaction,
+    /// holding `addr` in the first IA of `message`.
+    fn answer(
+        msg_type: Dhcpv6MsgType,
+        message: &Dhcpv6,
+        server_duid: Vec<u8>,
+        addr: Ipv6Addr,
+        preferred: u32,
+        valid: u32,
+    ) -> Result<Self, ParseError> {
+        let client_duid = message
+            .client_id()
+            .ok_or(ParseError::InvalidField("client identifier"))?;
+        let address = Dhcpv6Option::IaAddress {
+            addr,
+            preferred,
+            valid,
+            options: Vec::new(),
+        };
+        let ia = message
+            .options
+            .iter()
+            .find_map(|option| match option {
+                Dhcpv6Option::IaNa { iaid, .. } => Some(Dhcpv6Option::IaNa {
+                    iaid: *iaid,
+                    t1: preferred / 2,
+                    t2: preferred - preferred / 5,
+                    ia_options: vec![address.clone()],
+                }),
+                Dhcpv6Option::IaTa { iaid, .. } => Some(Dhcpv6Option::IaTa {
+                    iaid: *iaid,
+                    ia_options: vec![address.clone()],
+                }),
+                _ => None,
+            })
+            .ok_or(ParseError::InvalidField("identity association"))?;
+        Ok(Dhcpv6::new(msg_type, message.transaction_id)
+            .add_option(Dhcpv6Option::ClientId(client_duid.to_vec()))
+            .add_option(Dhcpv6Option::ServerId(server_duid))
+            .add_option(ia))
+    }
+
+    /// Returns the first option of code `code`.
+    pub fn find_option(&self, code: u16) -> Option<&Dhcpv6Option> {
+        self.options.iter().find(|option| option.code() == code)
+    }
+
+    /// DUID of the Client Identifier option.
+    pub fn client_id(&self) -> Option<&[u8]> {
+        match self.find_option(OPTION_CLIENTID) {
+            Some(Dhcpv6Option::ClientId(duid)) => Some(duid),
+            _ => None,
+        }
+    }
+
+    /// DUID of the Server Identifier option.
+    pub fn server_id(&self) -> Option<&[u8]> {
+        match self.find_option(OPTION_SERVERID) {
+            Some(Dhcpv6Option::ServerId(duid)) => Some(duid),
+            _ => None,
+        }
+    }
+
+    /// Addresses held by the IA_NA and IA_TA options.
+    pub fn addresses(&self) -> Vec<Ipv6Addr> {
+        self.options
+            .iter()
+            .filter_map(|option| match option {
+                Dhcpv6Option::IaNa { ia_options, .. } | Dhcpv6Option::IaTa { ia_options, .. } => {
+                    Some(ia_options)
+                }
+                _ => None,
+            })
+            .flatten()
+            .filter_map(|option| match option {
+                Dhcpv6Option::IaAddress { addr, .. } => Some(*addr),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Wraps the message in a UDP datagram, from the client port to the
+    /// server port or the other way for messages of servers, in an IPv6
+    /// packet with the UDP checksum computed.
+    pub fn to_ipv6(&self, source: Ipv6Addr, destination: Ipv6Addr) -> IPv6 {
+        let (source_port, destination_port) = if self.msg_type.is_from_server() {
+            (SERVER_PORT, CLIENT_PORT)
+        } else {
+            (CLIENT_PORT, SERVER_PORT)
+        };
+        let udp = UDP::new(source_port, destination_port, self.to_bytes()).with_checksum(
+            &PseudoHeader::V6 {
+                source,
+                destination,
+            },
+        );
+        IPv6::with_payload(source, destination, udp::IP_PROTOCOL, udp.to_bytes())
+    }
+
+    // --- SETTER METHODS ---
+
+    pub fn add_option(mut self, option: Dhcpv6Option) -> Self {
+        self.options.push(option);
+        self
+    }
+
+    // --- SERIALIZATION ---
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![self.msg_type as u8];
+        bytes.extend_from_slice(&self.transaction_id);
+        serialize_options_into(&self.options, &mut bytes);
+        bytes
+    }
+
+    /// Parses a client/server message from a UDP payload.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        Dhcpv6::from_bytes_with_limits(buf, &Limits::default())
+    }
+
+    /// Same as `from_bytes`, bounding the options and their nesting by
+    /// `limits`.
+    pub fn from_bytes_with_limits(buf: &[u8], limits: &Limits) -> Result<Self, ParseError> {
+        ensure_len(buf, 4)?;
+        let msg_type = Dhcpv6MsgType::try_from(buf[0])?;
+        if msg_type.is_relay() {
+            return Err(ParseError::InvalidField("msg-type"));
+        }
+        Ok(Dhcpv6 {
+            msg_type,
+            transaction_id: [buf[1], buf[2], buf[3]],
+            options: parse_options(&buf[4..], limits, 1)?,
+        })
+    }
+}
+
+/// DHCPv6 Relay-forward or Relay-reply message.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Dhcpv6Relay {
+    pub msg_type: Dhcpv6MsgType,
+    /// Relay agents the message went through before this one.
+    pub hop_count: u8,
+    /// Address identifying the link of the client, or unspecified.
+    pub link_address: Ipv6Addr,
+    /// Address of the client or relay the message came from.
+    pub peer_address: Ipv6Addr,
+    pub options: Vec<Dhcpv6Option>,
+}
+
+impl Dhcpv6Relay {
+    /// Relay-forward of `message`, received from `peer_address` on the
+    /// link of `link_address`.
+    pub fn forward(message: &[u8], link_address: Ipv6Addr, peer_address: Ipv6Addr) -> Self {
+        Dhcpv6Relay {
+            msg_type: Dhcpv6MsgType::RelayForw,
+            hop_count: 0,
+            link_address,
+            peer_address,
+            options: vec![Dhcpv6Option::RelayMessage(message.to_vec())],
+        }
+    }
+
+    /// Relay-reply answering `relay_forward` with `message`.
+    pub fn reply(relay_forward: &Dhcpv6Relay, message: &[u8]) -> Self {
+        Dhcpv6Relay {
+            msg_type: Dhcpv6MsgType::RelayRepl,
+            hop_count: relay_forward.hop_count,
+            link_address: relay_forward.link_address,
+            peer_address: relay_forward.peer_address,
+            options: vec![Dhcpv6Option::RelayMessage(message.to_vec())],
+        }
+    }
+
+    /// Relayed message, if any.
+    pub fn relay_message(&self) -> Option<&[u8]> {
+        self.options.iter().find_map(|option| match option {
+            Dhcpv6Option::RelayMessage(message) => Some(message.as_slice()),
+            _ => None,
+        })
+    }
+
+    // --- SERIALIZATION ---
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(RELAY_HEADER_LEN);
+        bytes.push(self.msg_type as u8);
+        bytes.push(self.hop_count);
+        bytes.extend_from_slice(&self.link_address.octets());
+        bytes.extend_from_slice(&self.peer_address.octets());
+        serialize_options_into(&self.options, &mut bytes);
+        bytes
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, RELAY_HEADER_LEN)?;
+        let msg_type = Dhcpv6MsgType::try_from(buf[0])?;
+        if !msg_type.is_relay() {
+            return Err(ParseError::InvalidField("msg-type"));
+        }
+        Ok(Dhcpv6Relay {
+            msg_type,
+            hop_count: buf[1],
+            link_address: read_ipv6(buf, 2),
+            peer_address: read_ipv6(buf, 18),
+            options: parse_options(&buf[RELAY_HEADER_LEN..], &Limits::default(), 1)?,
+        })
+    }
+}
@@ -0,0 +1,1233 @@
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::time::{Duration, Instant};
+
+use crate::limits::{Limit, Limits};
+use crate::util::{ParseError, ensure_len, read_ipv4, read_ipv6};
+
+// Resource record (RFC 1035 section 4.1.3)
+//
+// +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+// |                      NAME                     |
+// +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+// |          TYPE         |         CLASS         |
+// +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+// |                      TTL                      |
+// +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+// |        RDLENGTH       |        RDATA ...      |
+// +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+//
+// Names are sequences of length-prefixed labels ending with the empty root
+// label. A length byte with the two top bits set is a compression pointer:
+// the remaining 14 bits are the offset of the rest of the name.
+
+/// Longest encoded name allowed (RFC 1035 section 2.3.4).
+const MAX_NAME_LEN: usize = 255;
+
+/// Type of a resource record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DnsType {
+    A,
+    Ns,
+    Cname,
+    Soa,
+    Ptr,
+    Mx,
+    Txt,
+    Aaaa,
+    Srv,
+    Ds,
+    Rrsig,
+    Nsec,
+    Dnskey,
+    Nsec3,
+    Unknown(u16),
+}
+
+impl From<u16> for DnsType {
+    fn from(value: u16) -> Self {
+        match value {
+            1 => DnsType::A,
+            2 => DnsType::Ns,
+            5 => DnsType::Cname,
+            6 => DnsType::Soa,
+            12 => DnsType::Ptr,
+            15 => DnsType::Mx,
+            16 => DnsType::Txt,
+            28 => DnsType::Aaaa,
+            33 => DnsType::Srv,
+            43 => DnsType::Ds,
+            46 => DnsType::Rrsig,
+            47 => DnsType::Nsec,
+            48 => DnsType::Dnskey,
+            50 => DnsType::Nsec3,
+            other => DnsType::Unknown(other),
+        }
+    }
+}
+
+impl From<DnsType> for u16 {
+    fn from(value: DnsType) -> Self {
+        match value {
+            DnsType::A => 1,
+            DnsType::Ns => 2,
+            DnsType::Cname => 5,
+            DnsType::Soa => 6,
+            DnsType::Ptr => 12,
+            DnsType::Mx => 15,
+            DnsType::Txt => 16,
+            DnsType::Aaaa => 28,
+            DnsType::Srv => 33,
+            DnsType::Ds => 43,
+            DnsType::Rrsig => 46,
+            DnsType::Nsec => 47,
+            DnsType::Dnskey => 48,
+            DnsType::Nsec3 => 50,
+            DnsType::Unknown(other) => other,
+        }
+    }
+}
+
+// --- NAMES ---
+
+/// Encodes `name` as uncompressed labels. A trailing dot is optional and
+/// both "" and "." are the root.
+pub fn serialize_name_into(name: &str, bytes: &mut Vec<u8>) {
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() {
+            continue;
+        }
+        let label = &label.as_bytes()[..label.len().min(63)];
+        bytes.push(label.len() as u8);
+        bytes.extend_from_slice(label);
+    }
+    bytes.push(0);
+}
+
+/// Reads the name at `offset` of `message`, following compression
+/// pointers. Returns the dotted name (empty for the root) and the number
+/// of bytes it occupies at `offset`.
+pub fn read_name(message: &[u8], offset: usize) -> Result<(String, usize), ParseError> {
+    read_name_with_limits(message, offset, &Limits::default())
+}
+
+/// Same as `read_name`, following at most `limits.max_decode_depth`
+/// compression pointers, which also stops pointer loops.
+pub fn read_name_with_limits(
+    message: &[u8],
+    offset: usize,
+    limits: &Limits,
+) -> Result<(String, usize), ParseError> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut position = offset;
+    let mut consumed = None;
+    let mut pointers = 0;
+    let mut name_len = 1;
+    loop {
+        ensure_len(message, position + 1)?;
+        let len = message[position] as usize;
+        match len >> 6 {
+            0b00 if len == 0 => break,
+            0b00 => {
+                ensure_len(message, position + 1 + len)?;
+                name_len += len + 1;
+                if name_len > MAX_NAME_LEN {
+                    return Err(ParseError::InvalidField("name length"));
+                }
+                let label = &message[position + 1..position + 1 + len];
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                position += 1 + len;
+            }
+            0b11 => {
+                ensure_len(message, position + 2)?;
+                pointers += 1;
+                limits.check(Limit::DecodeDepth, pointers)?;
+                consumed.get_or_insert_with(|| position + 2 - offset);
+                position = (len & 0x3f) << 8 | message[position + 1] as usize;
+            }
+            _ => return Err(ParseError::InvalidField("label type")),
+        }
+    }
+    Ok((
+        labels.join("."),
+        consumed.unwrap_or_else(|| position + 1 - offset),
+    ))
+}
+
+/// Encodes record types as an NSEC/NSEC3 type bitmap (RFC 4034
+/// section 4.1.2).
+pub fn encode_type_bitmap(types: &[DnsType]) -> Vec<u8> {
+    let mut numbers: Vec<u16> = types.iter().map(|t| u16::from(*t)).collect();
+    numbers.sort_unstable();
+    numbers.dedup();
+    let mut bytes = Vec::new();
+    let mut index = 0;
+    while index < numbers.len() {
+        let window = numbers[index] >> 8;
+        let mut bitmap = [0u8; 32];
+        let mut used = 0;
+        while index < numbers.len() && numbers[index] >> 8 == window {
+            let low = (numbers[index] & 0xff) as usize;
+            bitmap[low / 8] |= 0x80 >> (low % 8);
+            used = low / 8 + 1;
+            index += 1;
+        }
+        bytes.push(window as u8);
+        bytes.push(used as u8);
+        bytes.extend_from_slice(&bitmap[..used]);
+    }
+    bytes
+}
+
+/// Decodes an NSEC/NSEC3 type bitmap.
+pub fn decode_type_bitmap(bitmap: &[u8]) -> Result<Vec<DnsType>, ParseError> {
+    let mut types = Vec::new();
+    let mut offset = 0;
+    while offset < bitmap.len() {
+        ensure_len(bitmap, offset + 2)?;
+        let window = bitmap[offset] as u16;
+        let len = bitmap[offset + 1] as usize;
+        if len == 0 || len > 32 {
+            return Err(ParseError::InvalidField("type bitmap length"));
+        }
+        ensure_len(bitmap, offset + 2 + len)?;
+        for (i, byte) in bitmap[offset + 2..offset + 2 + len].iter().enumerate() {
+            for bit in 0..8 {
+                if byte & (0x80 >> bit) != 0 {
+                    types.push(DnsType::from(window << 8 | (i * 8 + bit) as u16));
+                }
+            }
+        }
+        offset += 2 + len;
+    }
+    Ok(types)
+}
+
+// --- DNSSEC RECORDS ---
+
+// DNSSEC algorithm numbers (RFC 8624).
+pub const ALGORITHM_RSAMD5: u8 = 1;
+pub const ALGORITHM_RSASHA256: u8 = 8;
+pub const ALGORITHM_ECDSAP256SHA256: u8 = 13;
+pub const ALGORITHM_ED25519: u8 = 15;
+
+/// RRSIG record data (RFC 4034 section 3).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rrsig {
+    pub type_covered: DnsType,
+    pub algorithm: u8,
+    pub labels: u8,
+    pub original_ttl: u32,
+    pub sig_expiration: u32,
+    pub sig_inception: u32,
+    pub key_tag: u16,
+    pub signer_name: String,
+    pub signature: Vec<u8>,
+}
+
+/// DNSKEY record data (RFC 4034 section 2).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dnskey {
+    pub flags: u16,
+    pub protocol: u8,
+    pub algorithm: u8,
+    pub public_key: Vec<u8>,
+}
+
+impl Dnskey {
+    // Flags.
+    pub const ZONE_KEY: u16 = 0x0100;
+    pub const SECURE_ENTRY_POINT: u16 = 0x0001;
+
+    /// Key tag referencing this key from RRSIG and DS records (RFC 4034
+    /// appendix B).
+    pub fn compute_key_tag(&self) -> u16 {
+        if self.algorithm == ALGORITHM_RSAMD5 {
+            // Bits 16..32 from the end of the modulus (appendix B.1).
+            let key = &self.public_key;
+            return match key.len() {
+                len if len >= 3 => u16::from_be_bytes([key[len - 3], key[len - 2]]),
+                _ => 0,
+            };
+        }
+        let mut rdata = Vec::with_capacity(4 + self.public_key.len());
+        self.serialize_into(&mut rdata);
+        let mut ac: u32 = 0;
+        for (i, byte) in rdata.iter().enumerate() {
+            ac += if i & 1 == 1 {
+                *byte as u32
+            } else {
+                (*byte as u32) << 8
+            };
+        }
+        ac += (ac >> 16) & 0xffff;
+        (ac & 0xffff) as u16
+    }
+
+    pub fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.flags.to_be_bytes());
+        bytes.push(self.protocol);
+        bytes.push(self.algorithm);
+        bytes.extend_from_slice(&self.public_key);
+    }
+}
+
+/// DS record data (RFC 4034 section 5).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ds {
+    pub key_tag: u16,
+    pub algorithm: u8,
+    pub digest_type: u8,
+    pub digest: Vec<u8>,
+}
+
+/// NSEC record data (RFC 4034 section 4).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Nsec {
+    pub next_domain: String,
+    /// Encoded type bitmap; see `decode_type_bitmap`.
+    pub type_bitmap: Vec<u8>,
+}
+
+/// NSEC3 record data (RFC 5155 section 3).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Nsec3 {
+    pub hash_algorithm: u8,
+    pub flags: u8,
+    pub iterations: u16,
+    pub salt: Vec<u8>,
+    pub next_hashed: Vec<u8>,
+    /// Encoded type bitmap; see `decode_type_bitmap`.
+    pub type_bitmap: Vec<u8>,
+}
+
+// --- RECORD DATA ---
+
+/// Data of a resource record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DnsRecord {
+    A(Ipv4Addr),
+    Aaaa(Ipv6Addr),
+    Cname(String),
+    Ns(String),
+    Ptr(String),
+    Mx {
+        preference: u16,
+        exchange: String,
+    },
+    /// Character strings, each sent with at most 255 bytes.
+    Txt(Vec<String>),
+    Srv {
+        priority: u16,
+        weight: u16,
+        port: u16,
+        target: String,
+    },
+    Rrsig(Rrsig),
+    Dnskey(Dnskey),
+    Ds(Ds),
+    Nsec(Nsec),
+    Nsec3(Nsec3),
+    /// Data of a type without a dedicated variant.
+    Raw {
+        record_type: DnsType,
+        data: Vec<u8>,
+    },
+}
+
+impl DnsRecord {
+    /// Type of the record.
+    pub fn record_type(&self) -> DnsType {
+        match self {
+            DnsRecord::A(_) => DnsType::A,
+            DnsRecord::Aaaa(_) => DnsType::Aaaa,
+            DnsRecord::Cname(_) => DnsType::Cname,
+            DnsRecord::Ns(_) => DnsType::Ns,
+            DnsRecord::Ptr(_) => DnsType::Ptr,
+            DnsRecord::Mx { .. } => DnsType::Mx,
+            DnsRecord::Txt(_) => DnsType::Txt,
+            DnsRecord::Srv { .. } => DnsType::Srv,
+            DnsRecord::Rrsig(_) => DnsType::Rrsig,
+            DnsRecord::Dnskey(_) => DnsType::Dnskey,
+            DnsRecord::Ds(_) => DnsType::Ds,
+            DnsRecord::Nsec(_) => DnsType::Nsec,
+            DnsRecord::Nsec3(_) => DnsType::Nsec3,
+            DnsRecord::Raw { record_type, .. } => *record_type,
+        }
+    }
+
+    /// Serializes the record data (RDATA), with uncompressed names.
+    pub fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        match self {
+            DnsRecord::A(address) => bytes.extend_from_slice(&address.octets()),
+            DnsRecord::Aaaa(address) => bytes.extend_from_slice(&address.octets()),
+            DnsRecord::Cname(name) | DnsRecord::Ns(name) | DnsRecord::Ptr(name) => {
+                serialize_name_into(name, bytes)
+            }
+            DnsRecord::Mx {
+                preference,
+                exchange,
+            } => {
+                bytes.extend_from_slice(&preference.to_be_bytes());
+                serialize_name_into(exchange, bytes);
+            }
+            DnsRecord::Txt(strings) => {
+                for string in strings {
+                    let string = &string.as_bytes()[..string.len().min(255)];
+                    bytes.push(string.len() as u8);
+                    bytes.extend_from_slice(string);
+                }
+            }
+            DnsRecord::Srv {
+                priority,
+                weight,
+                port,
+                target,
+            } => {
+                bytes.extend_from_slice(&priority.to_be_bytes());
+                bytes.extend_from_slice(&weight.to_be_bytes());
+                bytes.extend_from_slice(&port.to_be_bytes());
+                serialize_name_into(target, bytes);
+            }
+            DnsRecord::Rrsig(rrsig) => {
+                bytes.extend_from_slice(&u16::from(rrsig.type_covered).to_be_bytes());
+                bytes.push(rrsig.algorithm);
+                bytes.push(rrsig.labels);
+                bytes.extend_from_slice(&rrsig.original_ttl.to_be_bytes());
+                bytes.extend_from_slice(&rrsig.sig_expiration.to_be_bytes());
+                bytes.extend_from_slice(&rrsig.sig_inception.to_be_bytes());
+                bytes.extend_from_slice(&rrsig.key_tag.to_be_bytes());
+                serialize_name_into(&rrsig.signer_name, bytes);
+                bytes.extend_from_slice(&rrsig.signature);
+            }
+            DnsRecord::Dnskey(dnskey) => dnskey.serialize_into(bytes),
+            DnsRecord::Ds(ds) => {
+                bytes.extend_from_slice(&ds.key_tag.to_be_bytes());
+                bytes.push(ds.algorithm);
+                bytes.push(ds.digest_type);
+                bytes.extend_from_slice(&ds.digest);
+            }
+            DnsRecord::Nsec(nsec) => {
+                serialize_name_into(&nsec.next_domain, bytes);
+                bytes.extend_from_slice(&nsec.type_bitmap);
+            }
+            DnsRecord::Nsec3(nsec3) => {
+                bytes.push(nsec3.hash_algorithm);
+                bytes.push(nsec3.flags);
+                bytes.extend_from_slice(&nsec3.iterations.to_be_bytes());
+                bytes.push(nsec3.salt.len() as u8);
+                bytes.extend_from_slice(&nsec3.salt);
+                bytes.push(nsec3.next_hashed.len() as u8);
+                bytes.extend_from_slice(&nsec3.next_hashed);
+                bytes.extend_from_slice(&nsec3.type_bitmap);
+            }
+            DnsRecord::Raw { data, .. } => bytes.extend_from_slice(data),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.serialize_into(&mut bytes);
+        bytes
+    }
+
+    /// Parses the `rdlength` bytes of record data at `offset` of `message`.
+    /// The whole message is needed to follow compression pointers.
+    pub fn from_bytes(
+        record_type: DnsType,
+        message: &[u8],
+        offset: usize,
+        rdlength: usize,
+    ) -> Result<Self, ParseError> {
+        DnsRecord::from_bytes_with_limits(
+            record_type,
+            message,
+            offset,
+            rdlength,
+            &Limits::default(),
+        )
+    }
+
+    /// Same as `from_bytes`, following at most `limits.max_decode_depth`
+    /// compression pointers in each name. Names and the pointers they
+    /// follow must not reach past the record data.
+    pub fn from_bytes_with_limits(
+        record_type: DnsType,
+        message: &[u8],
+        offset: usize,
+        rdlength: usize,
+        limits: &Limits,
+    ) -> Result<Self, ParseError> {
+        ensure_len(message, offset + rdlength)?;
+        let message = &message[..offset + rdlength];
+        let rdata = &message[offset..];
+        let name_at = |start: usize| read_name_with_limits(message, offset + start, limits);
+        let record = match record_type {
+            DnsType::A => {
+                ensure_len(rdata, 4)?;
+                DnsRecord::A(read_ipv4(rdata, 0))
+            }
+            DnsType::Aaaa => {
+                ensure_len(rdata, 16)?;
+                DnsRecord::Aaaa(read_ipv6(rdata, 0))
+            }
+            DnsType::Cname => DnsRecord::Cname(name_at(0)?.0),
+            DnsType::Ns => DnsRecord::Ns(name_at(0)?.0),
+            DnsType::Ptr => DnsRecord::Ptr(name_at(0)?.0),
+            DnsType::Mx => {
+                ensure_len(rdata, 3)?;
+                DnsRecord::Mx {
+                    preference: u16::from_be_bytes([rdata[0], rdata[1]]),
+                    exchange: name_at(2)?.0,
+                }
+            }
+            DnsType::Txt => {
+                let mut strings = Vec::new();
+                let mut rest = rdata;
+                while let Some((&len, tail)) = rest.split_first() {
+                    ensure_len(tail, len as usize)?;
+                    strings.push(String::from_utf8_lossy(&tail[..len as usize]).into_owned());
+                    rest = &tail[len as usize..];
+                }
+                DnsRecord::Txt(strings)
+            }
+            DnsType::Srv => {
+                ensure_len(rdata, 7)?;
+                DnsRecord::Srv {
+                    priority: u16::from_be_bytes([rdata[0], rdata[1]]),
+                    weight: u16::from_be_bytes([rdata[2], rdata[3]]),
+                    port: u16::from_be_bytes([rdata[4], rdata[5]]),
+                    target: name_at(6)?.0,
+                }
+            }
+            DnsType::Rrsig => {
+                ensure_len(rdata, 18)?;
+                let (signer_name, name_len) = read_name_with_limits(rdata, 18, limits)?;
+                DnsRecord::Rrsig(Rrsig {
+                    type_covered: DnsType::from(u16::from_be_bytes([rdata[0], rdata[1]])),
+                    algorithm: rdata[2],
+                    labels: rdata[3],
+                    original_ttl: u32::from_be_bytes([rdata[4], rdata[5], rdata[6], rdata[7]]),
+                    sig_expiration: u32::from_be_bytes([rdata[8], rdata[9], rdata[10], rdata[11]]),
+                    sig_inception: u32::from_be_bytes([rdata[12], rdata[13], rdata[14], rdata[15]]),
+                    key_tag: u16::from_be_bytes([rdata[16], rdata[17]]),
+                    signer_name,
+                    signature: rdata[18 + name_len..].to_vec(),
+                })
+            }
+            DnsType::Dnskey => {
+                ensure_len(rdata, 4)?;
+                DnsRecord::Dnskey(Dnskey {
+                    flags: u16::from_be_bytes([rdata[0], rdata[1]]),
+                    protocol: rdata[2],
+                    algorithm: rdata[3],
+                    public_key: rdata[4..].to_vec(),
+                })
+            }
+            DnsType::Ds => {
+                ensure_len(rdata, 4)?;
+                DnsRecord::Ds(Ds {
+                    key_tag: u16::from_be_bytes([rdata[0], rdata[1]]),
+                    algorithm: rdata[2],
+                    digest_type: rdata[3],
+                    digest: rdata[4..].to_vec(),
+                })
+            }
+            DnsType::Nsec => {
+                let (next_domain, name_len) = read_name_with_limits(rdata, 0, limits)?;
+                DnsRecord::Nsec(Nsec {
+                    next_domain,
+                    type_bitmap: rdata[name_len..].to_vec(),
+                })
+            }
+            DnsType::Nsec3 => {
+                ensure_len(rdata, 5)?;
+                let salt_end = 5 + rdata[4] as usize;
+                ensure_len(rdata, salt_end + 1)?;
+                let hash_end = salt_end + 1 + rdata[salt_end] as usize;
+                ensure_len(rdata, hash_end)?;
+                DnsRecord::Nsec3(Nsec3 {
+                    hash_algorithm: rdata[0],
+                    flags: rdata[1],
+                    iterations: u16::from_be_bytes([rdata[2], rdata[3]]),
+                    salt: rdata[5..salt_end].to_vec(),
+                    next_hashed: rdata[salt_end + 1..hash_end].to_vec(),
+                    type_bitmap: rdata[hash_end..].to_vec(),
+                })
+            }
+            _ => DnsRecord::Raw {
+                record_type,
+                data: rdata.to_vec(),
+            },
+        };
+        Ok(record)
+    }
+}
+
+// --- MESSAGES ---
+
+// Message header (RFC 1035 section 4.1.1)
+//
+// +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+// |                      ID                       |
+// +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+// |QR|   Opcode  |AA|TC|RD|RA| Z|AD|CD|   RCODE   |
+// +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+// |                    QDCOUNT                    |
+// +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+// |                    ANCOUNT                    |
+// +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+// |                    NSCOUNT                    |
+// +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+// |                    ARCOUNT                    |
+// +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+//
+// followed by the question, answer, authority and additional sections.
+// A question is a name, a type and a class; the other sections hold
+// resource records.
+
+/// UDP and TCP port of DNS.
+pub const PORT: u16 = 53;
+
+/// Length of the message header in bytes.
+pub const HEADER_LEN: usize = 12;
+
+/// Smallest question: root name, type and class.
+pub const MIN_QUESTION_LEN: usize = 5;
+
+/// Smallest resource record: root name, fixed fields and no data.
+pub const MIN_RECORD_LEN: usize = 11;
+
+/// Internet class.
+pub const CLASS_IN: u16 = 1;
+
+// Header flags.
+pub const FLAG_QR: u16 = 0x8000;
+pub const FLAG_AA: u16 = 0x0400;
+pub const FLAG_TC: u16 = 0x0200;
+pub const FLAG_RD: u16 = 0x0100;
+pub const FLAG_RA: u16 = 0x0080;
+pub const FLAG_AD: u16 = 0x0020;
+pub const FLAG_CD: u16 = 0x0010;
+
+// Opcodes.
+pub const OPCODE_QUERY: u8 = 0;
+pub const OPCODE_STATUS: u8 = 2;
+pub const OPCODE_NOTIFY: u8 = 4;
+pub const OPCODE_UPDATE: u8 = 5;
+
+// Response codes.
+pub const RCODE_NOERROR: u8 = 0;
+pub const RCODE_FORMERR: u8 = 1;
+pub const RCODE_SERVFAIL: u8 = 2;
+pub const RCODE_NXDOMAIN: u8 = 3;
+pub const RCODE_NOTIMP: u8 = 4;
+pub const RCODE_REFUSED: u8 = 5;
+
+/// Offsets of the names already written to a message, by lowercase
+/// suffix, so that later names can point to them.
+#[derive(Debug, Default)]
+struct NameCompressor {
+    offsets: HashMap<String, u16>,
+}
+
+impl NameCompressor {
+    /// Appends `name` to `message`, replacing its longest suffix already
+    /// present by a pointer.
+    fn serialize_name_into(&mut self, name: &str, message: &mut Vec<u8>) {
+        let labels: Vec<&str> = name
+            .trim_end_matches('.')
+            .split('.')
+            .filter(|label| !label.is_empty())
+            .collect();
+        for i in 0..labels.len() {
+            let suffix = labels[i..].join(".").to_ascii_lowercase();
+            if let Some(&offset) = self.offsets.get(&suffix) {
+                message.extend_from_slice(&(0xc000 | offset).to_be_bytes());
+                return;
+            }
+            // Pointers have 14 bits.
+            if message.len() < 0x4000 {
+                self.offsets.insert(suffix, message.len() as u16);
+            }
+            let label = &labels[i].as_bytes()[..labels[i].len().min(63)];
+            message.push(label.len() as u8);
+            message.extend_from_slice(label);
+        }
+        message.push(0);
+    }
+}
+
+/// Entry of the question section.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DnsQuestion {
+    pub name: String,
+    pub qtype: DnsType,
+    pub qclass: u16,
+}
+
+impl DnsQuestion {
+    /// Constructor to create a question of class IN.
+    pub fn new(name: &str, qtype: DnsType) -> Self {
+        DnsQuestion {
+            name: name.to_string(),
+            qtype,
+            qclass: CLASS_IN,
+        }
+    }
+}
+
+/// Resource record of the answer, authority or additional section.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnsResourceRecord {
+    pub name: String,
+    pub class: u16,
+    pub ttl: u32,
+    pub data: DnsRecord,
+}
+
+impl DnsResourceRecord {
+    /// Constructor to create a record of class IN.
+    pub fn new(name: &str, ttl: u32, data: DnsRecord) -> Self {
+        DnsResourceRecord {
+            name: name.to_string(),
+            class: CLASS_IN,
+            ttl,
+            data,
+        }
+    }
+
+    /// Appends the record to `message`. The owner name and the names in
+    /// CNAME, NS, PTR and MX data are compressed; other data is not, as
+    /// RFC 3597 section 4 requires for newer types.
+    fn serialize_into(&self, message: &mut Vec<u8>, compressor: &mut NameCompressor) {
+        compressor.serialize_name_into(&self.name, message);
+        message.extend_from_slice(&u16::from(self.data.record_type()).to_be_bytes());
+        message.extend_from_slice(&self.class.to_be_bytes());
+        message.extend_from_slice(&self.ttl.to_be_bytes());
+        let rdlength_offset = message.len();
+        message.extend_from_slice(&[0, 0]);
+        match &self.data {
+            DnsRecord::Cname(name) | DnsRecord::Ns(name) | DnsRecord::Ptr(name) => {
+                compressor.serialize_name_into(name, message)
+            }
+            DnsRecord::Mx {
+                preference,
+                exchange,
+            } => {
+                message.extend_from_slice(&preference.to_be_bytes());
+                compressor.serialize_name_into(exchange, message);
+            }
+            data => data.serialize_into(message),
+        }
+        let rdlength = (message.len() - rdlength_offset - 2) as u16;
+        message[rdlength_offset..rdlength_offset + 2].copy_from_slice(&rdlength.to_be_bytes());
+    }
+
+    /// Parses the record at `offset` of `message`, returning it with the
+    /// number of bytes it occupies.
+    fn from_bytes(
+        message: &[u8],
+        offset: usize,
+        limits: &Limits,
+    ) -> Result<(Self, usize), ParseError> {
+        let (name, name_len) = read_name_with_limits(message, offset, limits)?;
+        let fixed = offset + name_len;
+        ensure_len(message, fixed + 10)?;
+        let field = &message[fixed..fixed + 10];
+        let record_type = DnsType::from(u16::from_be_bytes([field[0], field[1]]));
+        let rdlength = u16::from_be_bytes([field[8], field[9]]) as usize;
+        let record = DnsResourceRecord {
+            name,
+            class: u16::from_be_bytes([field[2], field[3]]),
+            ttl: u32::from_be_bytes([field[4], field[5], field[6], field[7]]),
+            data: DnsRecord::from_bytes_with_limits(
+                record_type,
+                message,
+                fixed + 10,
+                rdlength,
+                limits,
+            )?,
+        };
+        Ok((record, name_len + 10 + rdlength))
+    }
+}
+
+/// DNS message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dns {
+    pub id: u16,
+    /// Flags word: QR, opcode, AA, TC, RD, RA, Z, AD, CD and RCODE.
+    pub flags: u16,
+    pub questions: Vec<DnsQuestion>,
+    pub answers: Vec<DnsResourceRecord>,
+    pub authorities: Vec<DnsResourceRecord>,
+    pub additionals: Vec<DnsResourceRecord>,
+}
+
+impl Dns {
+    /// Constructor to create an empty message with all flags clear.
+    pub fn new(id: u16) -> Self {
+        Dns {
+            id,
+            flags: 0,
+            questions: Vec::new(),
+            answers: Vec::new(),
+            authorities: Vec::new(),
+            additionals: Vec::new(),
+        }
+    }
+
+    /// Standard query for `name` and `qtype` with recursion desired.
+    pub fn query(id: u16, name: &str, qtype: DnsType) -> Self {
+        let mut query = Dns::new(id).set_flag(FLAG_RD, true);
+        query.questions.push(DnsQuestion::new(name, qtype));
+        query
+    }
+
+    /// Response to `query`, with its ID, opcode, RD flag and questions,
+    /// recursion available and no records yet.
+    pub fn response_to(query: &Dns) -> Self {
+        Dns {
+            id: query.id,
+            flags: FLAG_QR | FLAG_RA | query.flags & (0x7800 | FLAG_RD),
+            questions: query.questions.clone(),
+            answers: Vec::new(),
+            authorities: Vec::new(),
+            additionals: Vec::new(),
+        }
+    }
+
+    // --- GETTER METHODS ---
+
+    /// Returns true if all of `flags` are set.
+    pub fn has_flags(&self, flags: u16) -> bool {
+        self.flags & flags == flags
+    }
+
+    pub fn is_response(&self) -> bool {
+        self.has_flags(FLAG_QR)
+    }
+
+    pub fn get_opcode(&self) -> u8 {
+        (self.flags >> 11 & 0x0f) as u8
+    }
+
+    pub fn get_rcode(&self) -> u8 {
+        (self.flags & 0x0f) as u8
+    }
+
+    // --- SETTER METHODS ---
+
+    pub fn set_id(mut self, id: u16) -> Self {
+        self.id = id;
+        self
+    }
+
+    /// Sets or clears `flag`.
+    pub fn set_flag(mut self, flag: u16, value: bool) -> Self {
+        if value {
+            self.flags |= flag;
+        } else {
+            self.flags &= !flag;
+        }
+        self
+    }
+
+    pub fn set_opcode(mut self, opcode: u8) -> Self {
+        self.flags = self.flags & !0x7800 | (opcode as u16 & 0x0f) << 11;
+        self
+    }
+
+    pub fn set_rcode(mut self, rcode: u8) -> Self {
+        self.flags = self.flags & !0x000f | rcode as u16 & 0x0f;
+        self
+    }
+
+    pub fn add_question(mut self, question: DnsQuestion) -> Self {
+        self.questions.push(question);
+        self
+    }
+
+    pub fn add_answer(mut self, record: DnsResourceRecord) -> Self {
+        self.answers.push(record);
+        self
+    }
+
+    pub fn add_authority(mut self, record: DnsResourceRecord) -> Self {
+        self.authorities.push(record);
+        self
+    }
+
+    pub fn add_additional(mut self, record: DnsResourceRecord) -> Self {
+        self.additionals.push(record);
+        self
+    }
+
+    // --- SERIALIZATION ---
+
+    /// Serializes the message with name compression, the section counts
+    /// taken from the sections.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(512);
+        bytes.extend_from_slice(&self.id.to_be_bytes());
+        bytes.extend_from_slice(&self.flags.to_be_bytes());
+        for count in [
+            self.questions.len(),
+            self.answers.len(),
+            self.authorities.len(),
+            self.additionals.len(),
+        ] {
+            bytes.extend_from_slice(&(count as u16).to_be_bytes());
+        }
+        let mut compressor = NameCompressor::default();
+        for question in &self.questions {
+            compressor.serialize_name_into(&question.name, &mut bytes);
+            bytes.extend_from_slice(&u16::from(question.qtype).to_be_bytes());
+            bytes.extend_from_slice(&question.qclass.to_be_bytes());
+        }
+        for record in self
+            .answers
+            .iter()
+            .chain(&self.authorities)
+            .chain(&self.additionals)
+        {
+            record.serialize_into(&mut bytes, &mut compressor);
+        }
+        bytes
+    }
+
+    /// Parses a message; trailing bytes are ignored.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        Dns::from_bytes_with_limits(buf, &Limits::default())
+    }
+
+    /// Same as `from_bytes`, bounding the compression pointers of every
+    /// name by `limits.max_decode_depth`. Section counts are checked
+    /// against the bytes left, each entry taking at least
+    /// `MIN_QUESTION_LEN` or `MIN_RECORD_LEN` bytes.
+    pub fn from_bytes_with_limits(buf: &[u8], limits: &Limits) -> Result<Self, ParseError> {
+        ensure_len(buf, HEADER_LEN)?;
+        let count = |index: usize| u16::from_be_bytes([buf[4 + index * 2], buf[5 + index * 2]]);
+        let mut offset = HEADER_LEN;
+        let mut questions = Vec::new();
+        ensure_len(buf, offset + count(0) as usize * MIN_QUESTION_LEN)?;
+        for _ in 0..count(0) {
+            let (name, name_len) = read_name_with_limits(buf, offset, limits)?;
+            offset += name_len;
+            ensure_len(buf, offset + 4)?;
+            questions.push(DnsQuestion {
+                name,
+                qtype: DnsType::from(u16::from_be_bytes([buf[offset], buf[offset + 1]])),
+                qclass: u16::from_be_bytes([buf[offset + 2], buf[offset + 3]]),
+            });
+            offset += 4;
+        }
+        let mut sections = [Vec::new(), Vec::new(), Vec::new()];
+        for (index, section) in sections.iter_mut().enumerate() {
+            ensure_len(buf, offset + count(index + 1) as usize * MIN_RECORD_LEN)?;
+            for _ in 0..count(index + 1) {
+                let (record, len) = DnsResourceRecord::from_bytes(buf, offset, limits)?;
+                section.push(record);
+                offset += len;
+            }
+        }
+        let [answers, authorities, additionals] = sections;
+        Ok(Dns {
+            id: u16::from_be_bytes([buf[0], buf[1]]),
+            flags: u16::from_be_bytes([buf[2], buf[3]]),
+            questions,
+            answers,
+            authorities,
+            additionals,
+        })
+    }
+}
+
+// --- CACHE ---
+
+/// Records of one name and type held by a `DnsCache`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheEntry {
+    /// Cached records; empty for a negative entry.
+    pub records: Vec<DnsRecord>,
+    pub expires_at: Instant,
+    /// TTL the entry was inserted with, in seconds.
+    pub ttl_original: u32,
+}
+
+impl CacheEntry {
+    fn new(records: Vec<DnsRecord>, ttl: u32) -> Self {
+        CacheEntry {
+            records,
+            expires_at: Instant::now() + Duration::from_secs(ttl as u64),
+            ttl_original: ttl,
+        }
+    }
+
+    /// Returns true once the TTL has elapsed.
+    pub fn is_expired(&self) -> bool {
+        Instant::now() >= self.expires_at
+    }
+
+    /// Seconds left before expiry, as a cache puts in the TTL field of the
+    /// records it answers with.
+    pub fn remaining_ttl(&self) -> u32 {
+        self.expires_at
+            .saturating_duration_since(Instant::now())
+            .as_secs() as u32
+    }
+}
+
+/// Cache of a simulated stub or caching resolver. Names are compared
+/// case-insensitively, with or without their trailing dot.
+#[derive(Debug, Clone, Default)]
+pub struct DnsCache {
+    pub entries: HashMap<(String, DnsType), CacheEntry>,
+    /// Names known not to exist (RFC 2308), whatever the type asked.
+    pub nxdomain: HashMap<String, CacheEntry>,
+}
+
+impl DnsCache {
+    /// Constructor to create an empty cache.
+    pub fn new() -> Self {
+        DnsCache::default()
+    }
+
+    /// Returns the cached records of `name` and `rtype`: empty if the name
+    /// is cached as nonexistent, `None` if nothing valid is cached.
+    pub fn lookup(&self, name: &str, rtype: DnsType) -> Option<Vec<DnsRecord>> {
+        self.lookup_entry(name, rtype)
+            .map(|entry| entry.records.clone())
+    }
+
+    /// Same as `lookup`, returning the entry to read its remaining TTL.
+    pub fn lookup_entry(&self, name: &str, rtype: DnsType) -> Option<&CacheEntry> {
+        let name = cache_key(name);
+        let live = |entry: &&CacheEntry| !entry.is_expired();
+        self.nxdomain
+            .get(&name)
+            .filter(live)
+            .or_else(|| self.entries.get(&(name, rtype)).filter(live))
+    }
+
+    /// Caches `records` of `name` for `ttl` seconds, one entry per record
+    /// type, replacing the entries of those types and any negative entry
+    /// of the name.
+    pub fn insert(&mut self, name: &str, records: Vec<DnsRecord>, ttl: u32) {
+        let name = cache_key(name);
+        self.nxdomain.remove(&name);
+        let mut by_type: HashMap<DnsType, Vec<DnsRecord>> = HashMap::new();
+        for record in records {
+            by_type
+                .entry(record.record_type())
+                .or_default()
+                .push(record);
+        }
+        for (rtype, records) in by_type {
+            self.entries
+                .insert((name.clone(), rtype), CacheEntry::new(records, ttl));
+        }
+    }
+
+    /// Caches an NXDOMAIN answer for `name`, for the MINIMUM field of the
+    /// zone's SOA record (RFC 2308 section 5).
+    pub fn negative_cache(&mut self, name: &str, soa_min_ttl: u32) {
+        self.nxdomain
+            .insert(cache_key(name), CacheEntry::new(Vec::new(), soa_min_ttl));
+    }
+
+    /// Removes every expired entry.
+    pub fn prune_expired(&mut self) {
+        self.entries.retain(|_, entry| !entry.is_expired());
+        self.nxdomain.retain(|_, entry| !entry.is_expired());
+    }
+
+    /// Number of entries, expired ones included until pruned.
+    pub fn len(&self) -> usize {
+        self.entries.len() + self.nxdomain.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty() && self.nxdomain.is_empty()
+    }
+}
+
+fn cache_key(name: &str) -> String {
+    name.trim_end_matches('.').to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::limits::LimitExceeded;
+
+    fn response(answers: usize) -> Dns {
+        let query = Dns::query(1, "www.example.com", DnsType::A);
+        (0..answers).fold(Dns::response_to(&query), |response, i| {
+            response.add_answer(DnsResourceRecord::new(
+                "www.example.com",
+                300,
+                DnsRecord::A(Ipv4Addr::new(192, 0, 2, i as u8)),
+            ))
+        })
+    }
+
+    #[test]
+    fn record_counts_are_not_bounded_by_options_limit() {
+        let message = response(65);
+        assert_eq!(Dns::from_bytes(&message.to_bytes()).unwrap(), message);
+    }
+
+    #[test]
+    fn record_count_beyond_buffer_is_truncated() {
+        let mut bytes = response(1).to_bytes();
+        bytes[6..8].copy_from_slice(&u16::MAX.to_be_bytes());
+        assert!(matches!(
+            Dns::from_bytes(&bytes),
+            Err(ParseError::Truncated { .. })
+        ));
+    }
+
+    #[test]
+    fn rdata_name_stays_within_rdlength() {
+        // CNAME whose rdlength covers only the first label of the name.
+        let mut bytes = Dns::response_to(&Dns::query(1, "a", DnsType::Cname))
+            .add_answer(DnsResourceRecord::new(
+                "a",
+                60,
+                DnsRecord::Cname("target.example".into()),
+            ))
+            .to_bytes();
+        let rdlength_at = HEADER_LEN + 3 + 4 + 2 + 8;
+        assert_eq!(bytes[rdlength_at + 1], 16);
+        bytes[rdlength_at + 1] = 7;
+        assert!(Dns::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn rdata_names_follow_caller_limits() {
+        let message = Dns::response_to(&Dns::query(1, "www.example.com", DnsType::Cname))
+            .add_answer(DnsResourceRecord::new(
+                "www.example.com",
+                60,
+                DnsRecord::Cname("web.www.example.com".into()),
+            ))
+            .add_answer(DnsResourceRecord::new(
+                "other.test",
+                60,
+                DnsRecord::Cname("x.web.www.example.com".into()),
+            ));
+        let bytes = message.to_bytes();
+        assert_eq!(Dns::from_bytes(&bytes).unwrap(), message);
+        // Owner names take at most one pointer; the last CNAME target
+        // points to the first one, which points to the question.
+        let limits = Limits::default().set_max_decode_depth(1);
+        assert_eq!(
+            Dns::from_bytes_with_limits(&bytes, &limits),
+            Err(ParseError::LimitExceeded(LimitExceeded {
+                limit: Limit::DecodeDepth,
+                max: 1
+            }))
+        );
+    }
+
+    #[test]
+    fn expired_negative_entry_does_not_shadow_records() {
+        let mut cache = DnsCache::new();
+        cache.insert(
+            "example.com",
+            vec![DnsRecord::A(Ipv4Addr::new(192, 0, 2, 1))],
+            300,
+        );
+        cache.negative_cache("example.com", 0);
+        assert_eq!(
+            cache.lookup("example.com", DnsType::A),
+            Some(vec![DnsRecord::A(Ipv4Addr::new(192, 0, 2, 1))])
+        );
+    }
+
+    fn a(last: u8) -> DnsRecord {
+        DnsRecord::A(Ipv4Addr::new(192, 0, 2, last))
+    }
+
+    #[test]
+    fn entry_expires_after_its_ttl() {
+        let mut cache = DnsCache::new();
+        cache.insert("example.com", vec![a(1)], 1);
+        assert_eq!(cache.lookup("example.com", DnsType::A), Some(vec![a(1)]));
+        std::thread::sleep(Duration::from_secs(1));
+        assert_eq!(cache.lookup("example.com", DnsType::A), None);
+        assert_eq!(cache.len(), 1);
+        cache.prune_expired();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn negative_entry_expires_and_is_replaced_by_records() {
+        let mut cache = DnsCache::new();
+        cache.negative_cache("gone.example", 300);
+        assert_eq!(
+            cache.lookup("gone.example", DnsType::Aaaa),
+            Some(Vec::new())
+        );
+        cache.insert("gone.example", vec![a(2)], 300);
+        assert_eq!(cache.lookup("gone.example", DnsType::A), Some(vec![a(2)]));
+        cache.negative_cache("other.example", 0);
+        assert_eq!(cache.lookup("other.example", DnsType::A), None);
+    }
+
+    #[test]
+    fn poisoning_stays_within_the_name_and_type() {
+        let mut cache = DnsCache::new();
+        cache.insert("bank.example", vec![a(1)], 300);
+        // Spoofed answers for other names or types leave the entry alone.
+        cache.insert("bank.example.evil", vec![a(66)], 300);
+        cache.insert(
+            "bank.example",
+            vec![DnsRecord::Cname("evil.test".into())],
+            300,
+        );
+        cache.negative_cache("www.bank.example", 300);
+        assert_eq!(cache.lookup("bank.example", DnsType::A), Some(vec![a(1)]));
+        // Case and the trailing dot do not create separate entries.
+        cache.insert("BANK.Example.", vec![a(2)], 300);
+        assert_eq!(cache.lookup("bank.example", DnsType::A), Some(vec![a(2)]));
+        assert_eq!(cache.entries.len(), 3);
+    }
+
+    #[test]
+    fn oversized_ttl_and_record_sets_are_cached() {
+        let mut cache = DnsCache::new();
+        let records: Vec<DnsRecord> = (0..10_000).map(|i| a(i as u8)).collect();
+        cache.insert("big.example", records.clone(), u32::MAX);
+        let entry = cache.lookup_entry("big.example", DnsType::A).unwrap();
+        assert_eq!(entry.records, records);
+        assert_eq!(entry.ttl_original, u32::MAX);
+        assert!(entry.remaining_ttl() >= u32::MAX - 1);
+    }
+
+    #[test]
+    fn oversized_txt_record_round_trips() {
+        let strings = vec!["x".repeat(255); 250];
+        let message = Dns::response_to(&Dns::query(1, "big.example", DnsType::Txt)).add_answer(
+            DnsResourceRecord::new("big.example", 60, DnsRecord::Txt(strings)),
+        );
+        assert_eq!(Dns::from_bytes(&message.to_bytes()).unwrap(), message);
+    }
+
+    #[test]
+    fn pointer_loop_fails_on_decode_depth() {
+        let mut bytes = Dns::query(1, "a", DnsType::A).to_bytes();
+        bytes.truncate(HEADER_LEN);
+        // Two pointers referring to each other.
+        bytes.extend_from_slice(&[0xc0, 14, 0xc0, 12, 0, 1, 0, 1]);
+        assert_eq!(
+            Dns::from_bytes(&bytes),
+            Err(ParseError::LimitExceeded(LimitExceeded {
+                limit: Limit::DecodeDepth,
+                max: Limits::default().max_decode_depth
+            }))
+        );
+    }
+}
@@ -0,0 +1,409 @@
+use crate::util::{ParseError, ensure_len};
+
+// EtherNet/IP encapsulation header (CIP Vol. 2, 2-3), little-endian, over
+// TCP or UDP port 44818:
+//
+// +---------+--------+----------------+--------+----------------+---------+
+// | Command | Length | Session Handle | Status | Sender Context | Options |
+// | 2 bytes | 2      | 4              | 4      | 8              | 4       |
+// +---------+--------+----------------+--------+----------------+---------+
+// | Command specific data (Length bytes)                                  |
+// +-----------------------------------------------------------------------+
+//
+// SendRRData and SendUnitData carry an interface handle (4), a timeout (2)
+// and a Common Packet Format list: item count (2), then items of type ID
+// (2), length (2) and data.
+
+/// TCP and UDP port of explicit messaging.
+pub const PORT: u16 = 44818;
+
+/// Length of the encapsulation header in bytes.
+pub const HEADER_LEN: usize = 24;
+
+// CPF item type IDs.
+pub const ITEM_NULL_ADDRESS: u16 = 0x0000;
+pub const ITEM_CONNECTED_ADDRESS: u16 = 0x00a1;
+pub const ITEM_CONNECTED_DATA: u16 = 0x00b1;
+pub const ITEM_UNCONNECTED_DATA: u16 = 0x00b2;
+
+// CIP service codes.
+pub const SERVICE_GET_ATTRIBUTES_ALL: u8 = 0x01;
+pub const SERVICE_GET_ATTRIBUTE_SINGLE: u8 = 0x0e;
+pub const SERVICE_SET_ATTRIBUTE_SINGLE: u8 = 0x10;
+pub const SERVICE_UNCONNECTED_SEND: u8 = 0x52;
+pub const SERVICE_FORWARD_OPEN: u8 = 0x54;
+
+/// Bit set in the service code of a response.
+pub const SERVICE_RESPONSE: u8 = 0x80;
+
+/// Encapsulation command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnipCommand {
+    Nop,
+    ListServices,
+    ListIdentity,
+    ListInterfaces,
+    RegisterSession,
+    UnRegisterSession,
+    SendRRData,
+    SendUnitData,
+    Unknown(u16),
+}
+
+impl From<u16> for EnipCommand {
+    fn from(value: u16) -> Self {
+        match value {
+            0x0000 => EnipCommand::Nop,
+            0x0004 => EnipCommand::ListServices,
+            0x0063 => EnipCommand::ListIdentity,
+            0x0064 => EnipCommand::ListInterfaces,
+            0x0065 => EnipCommand::RegisterSession,
+            0x0066 => EnipCommand::UnRegisterSession,
+            0x006f => EnipCommand::SendRRData,
+            0x0070 => EnipCommand::SendUnitData,
+            other => EnipCommand::Unknown(other),
+        }
+    }
+}
+
+impl From<EnipCommand> for u16 {
+    fn from(value: EnipCommand) -> Self {
+        match value {
+            EnipCommand::Nop => 0x0000,
+            EnipCommand::ListServices => 0x0004,
+            EnipCommand::ListIdentity => 0x0063,
+            EnipCommand::ListInterfaces => 0x0064,
+            EnipCommand::RegisterSession => 0x0065,
+            EnipCommand::UnRegisterSession => 0x0066,
+            EnipCommand::SendRRData => 0x006f,
+            EnipCommand::SendUnitData => 0x0070,
+            EnipCommand::Unknown(other) => other,
+        }
+    }
+}
+
+/// An item of a Common Packet Format list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CpfItem {
+    NullAddress,
+    /// Address of a connected message: the connection identifier.
+    ConnectedAddress(u32),
+    /// Data of a connected message, starting with its sequence count.
+    ConnectedData(Vec<u8>),
+    UnconnectedData(Vec<u8>),
+    Unknown {
+        type_id: u16,
+        data: Vec<u8>,
+    },
+}
+
+impl CpfItem {
+    pub fn type_id(&self) -> u16 {
+        match self {
+            CpfItem::NullAddress => ITEM_NULL_ADDRESS,
+            CpfItem::ConnectedAddress(_) => ITEM_CONNECTED_ADDRESS,
+            CpfItem::ConnectedData(_) => ITEM_CONNECTED_DATA,
+            CpfItem::UnconnectedData(_) => ITEM_UNCONNECTED_DATA,
+            CpfItem::Unknown { type_id, .. } => *type_id,
+        }
+    }
+
+    pub fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        let connection_id;
+        let data: &[u8] = match self {
+            CpfItem::NullAddress => &[],
+            CpfItem::ConnectedAddress(id) => {
+                connection_id = id.to_le_bytes();
+                &connection_id
+            }
+            CpfItem::ConnectedData(data)
+            | CpfItem::UnconnectedData(data)
+            | CpfItem::Unknown { data, .. } => data,
+        };
+        bytes.extend_from_slice(&self.type_id().to_le_bytes());
+        bytes.extend_from_slice(&(data.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(data);
+    }
+
+    /// Parses an item, returning it and the number of bytes consumed.
+    pub fn from_bytes(buf: &[u8]) -> Result<(Self, usize), ParseError> {
+        ensure_len(buf, 4)?;
+        let type_id = u16::from_le_bytes([buf[0], buf[1]]);
+        let length = u16::from_le_bytes([buf[2], buf[3]]) as usize;
+        ensure_len(buf, 4 + length)?;
+        let data = &buf[4..4 + length];
+        let item = match type_id {
+            ITEM_NULL_ADDRESS => CpfItem::NullAddress,
+            ITEM_CONNECTED_ADDRESS => {
+                ensure_len(data, 4)?;
+                CpfItem::ConnectedAddress(u32::from_le_bytes([data[0], data[1], data[2], data[3]]))
+            }
+            ITEM_CONNECTED_DATA => CpfItem::ConnectedData(data.to_vec()),
+            ITEM_UNCONNECTED_DATA => CpfItem::UnconnectedData(data.to_vec()),
+            _ => CpfItem::Unknown {
+                type_id,
+                data: data.to_vec(),
+            },
+        };
+        Ok((item, 4 + length))
+    }
+}
+
+/// Command specific data of SendRRData and SendUnitData.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CpfList {
+    /// Interface handle; 0 for CIP.
+    pub interface_handle: u32,
+    /// Timeout in seconds; 0 for SendUnitData.
+    pub timeout: u16,
+    pub items: Vec<CpfItem>,
+}
+
+impl CpfList {
+    /// Unconnected message: a null address and the given data.
+    pub fn unconnected(data: Vec<u8>) -> Self {
+        CpfList {
+            interface_handle: 0,
+            timeout: 0,
+            items: vec![CpfItem::NullAddress, CpfItem::UnconnectedData(data)],
+        }
+    }
+
+    /// Connected message on `connection_id`; `data` starts with the
+    /// sequence count.
+    pub fn connected(connection_id: u32, data: Vec<u8>) -> Self {
+        CpfList {
+            interface_handle: 0,
+            timeout: 0,
+            items: vec![
+                CpfItem::ConnectedAddress(connection_id),
+                CpfItem::ConnectedData(data),
+            ],
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&self.interface_handle.to_le_bytes());
+        bytes.extend_from_slice(&self.timeout.to_le_bytes());
+        bytes.extend_from_slice(&(self.items.len() as u16).to_le_bytes());
+        for item in &self.items {
+            item.serialize_into(&mut bytes);
+        }
+        bytes
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, 8)?;
+        let item_count = u16::from_le_bytes([buf[6], buf[7]]);
+        let mut items = Vec::with_capacity(item_count as usize);
+        let mut offset = 8;
+        for _ in 0..item_count {
+            let (item, consumed) = CpfItem::from_bytes(&buf[offset..])?;
+            items.push(item);
+            offset += consumed;
+        }
+        Ok(CpfList {
+            interface_handle: u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]),
+            timeout: u16::from_le_bytes([buf[4], buf[5]]),
+            items,
+        })
+    }
+}
+
+/// EtherNet/IP encapsulation header, followed by its command data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnipHeader {
+    pub command: EnipCommand,
+    pub length: u16,
+    pub session_handle: u32,
+    pub status: u32,
+    pub sender_context: [u8; 8],
+    pub options: u32,
+    pub data: Vec<u8>,
+}
+
+impl EnipHeader {
+    /// Constructor to create a message with its length filled in.
+    pub fn new(command: EnipCommand, session_handle: u32, data: Vec<u8>) -> Self {
+        EnipHeader {
+            command,
+            length: data.len() as u16,
+            session_handle,
+            status: 0,
+            sender_context: [0; 8],
+            options: 0,
+            data,
+        }
+    }
+
+    /// RegisterSession request for protocol version 1.
+    pub fn register_session() -> Self {
+        EnipHeader::new(EnipCommand::RegisterSession, 0, vec![1, 0, 0, 0])
+    }
+
+    /// SendRRData request carrying `cpf`.
+    pub fn send_rr_data(session_handle: u32, cpf: &CpfList) -> Self {
+        EnipHeader::new(EnipCommand::SendRRData, session_handle, cpf.to_bytes())
+    }
+
+    /// SendUnitData request carrying `cpf`.
+    pub fn send_unit_data(session_handle: u32, cpf: &CpfList) -> Self {
+        EnipHeader::new(EnipCommand::SendUnitData, session_handle, cpf.to_bytes())
+    }
+
+    /// Parses the data of SendRRData and SendUnitData as a CPF list.
+    pub fn cpf_list(&self) -> Result<CpfList, ParseError> {
+        match self.command {
+            EnipCommand::SendRRData | EnipCommand::SendUnitData => CpfList::from_bytes(&self.data),
+            _ => Err(ParseError::InvalidField("command")),
+        }
+    }
+
+    pub fn set_sender_context(mut self, sender_context: [u8; 8]) -> Self {
+        self.sender_context = sender_context;
+        self
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.data.len());
+        bytes.extend_from_slice(&u16::from(self.command).to_le_bytes());
+        bytes.extend_from_slice(&self.length.to_le_bytes());
+        bytes.extend_from_slice(&self.session_handle.to_le_bytes());
+        bytes.extend_from_slice(&self.status.to_le_bytes());
+        bytes.extend_from_slice(&self.sender_context);
+        bytes.extend_from_slice(&self.options.to_le_bytes());
+        bytes.extend_from_slice(&self.data);
+        bytes
+    }
+
+    /// Parses a message; the data is bounded by the length field.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, HEADER_LEN)?;
+        let length = u16::from_le_bytes([buf[2], buf[3]]);
+        ensure_len(buf, HEADER_LEN + length as usize)?;
+        let mut sender_context = [0u8; 8];
+        sender_context.copy_from_slice(&buf[12..20]);
+        Ok(EnipHeader {
+            command: EnipCommand::from(u16::from_le_bytes([buf[0], buf[1]])),
+            length,
+            session_handle: u32::from_le_bytes([buf[4], buf[5], buf[6], buf[7]]),
+            status: u32::from_le_bytes([buf[8], buf[9], buf[10], buf[11]]),
+            sender_context,
+            options: u32::from_le_bytes([buf[20], buf[21], buf[22], buf[23]]),
+            data: buf[HEADER_LEN..HEADER_LEN + length as usize].to_vec(),
+        })
+    }
+}
+
+/// Builds an EPATH of logical segments addressing a class, an instance and
+/// optionally an attribute, using 8-bit or 16-bit segments as needed.
+pub fn logical_path(class: u16, instance: u16, attribute: Option<u16>) -> Vec<u8> {
+    let mut path = Vec::new();
+    let segments = [
+        (0x20, Some(class)),
+        (0x24, Some(instance)),
+        (0x30, attribute),
+    ];
+    for (segment_type, value) in segments {
+        match value {
+            Some(value) if value <= 0xff => path.extend_from_slice(&[segment_type, value as u8]),
+            Some(value) => {
+                path.extend_from_slice(&[segment_type | 1, 0]);
+                path.extend_from_slice(&value.to_le_bytes());
+            }
+            None => {}
+        }
+    }
+    path
+}
+
+/// CIP Message Router request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageRouterRequest {
+    pub service_code: u8,
+    /// Request path (EPATH); padded to whole words when serialized.
+    pub request_path: Vec<u8>,
+    pub request_data: Vec<u8>,
+}
+
+impl MessageRouterRequest {
+    /// Constructor to create a new request.
+    pub fn new(service_code: u8, request_path: Vec<u8>, request_data: Vec<u8>) -> Self {
+        MessageRouterRequest {
+            service_code,
+            request_path,
+            request_data,
+        }
+    }
+
+    /// Get_Attribute_Single request for `attribute` of a class instance.
+    pub fn get_attribute_single(class: u16, instance: u16, attribute: u16) -> Self {
+        MessageRouterRequest::new(
+            SERVICE_GET_ATTRIBUTE_SINGLE,
+            logical_path(class, instance, Some(attribute)),
+            Vec::new(),
+        )
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let path_words = self.request_path.len().div_ceil(2);
+        let mut bytes = vec![self.service_code, path_words as u8];
+        bytes.extend_from_slice(&self.request_path);
+        bytes.resize(2 + path_words * 2, 0);
+        bytes.extend_from_slice(&self.request_data);
+        bytes
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, 2)?;
+        let path_end = 2 + buf[1] as usize * 2;
+        ensure_len(buf, path_end)?;
+        Ok(MessageRouterRequest {
+            service_code: buf[0],
+            request_path: buf[2..path_end].to_vec(),
+            request_data: buf[path_end..].to_vec(),
+        })
+    }
+}
+
+/// CIP Message Router response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageRouterResponse {
+    /// Service code of the request with `SERVICE_RESPONSE` set.
+    pub service_code: u8,
+    pub general_status: u8,
+    pub additional_status: Vec<u16>,
+    pub response_data: Vec<u8>,
+}
+
+impl MessageRouterResponse {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![
+            self.service_code,
+            0,
+            self.general_status,
+            self.additional_status.len() as u8,
+        ];
+        for status in &self.additional_status {
+            bytes.extend_from_slice(&status.to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.response_data);
+        bytes
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, 4)?;
+        let status_end = 4 + buf[3] as usize * 2;
+        ensure_len(buf, status_end)?;
+        Ok(MessageRouterResponse {
+            service_code: buf[0],
+            general_status: buf[2],
+            additional_status: buf[4..status_end]
+                .chunks_exact(2)
+                .map(|status| u16::from_le_bytes([status[0], status[1]]))
+                .collect(),
+            response_data: buf[status_end..].to_vec(),
+        })
+    }
+}
@@ -0,0 +1,382 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::mpls::{self, MplsEntry};
+use crate::util::{ParseError, ensure_len};
+use crate::vlan::{TAG_LEN, VlanTag};
+
+// Ethernet II header
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                      Destination Address                      |
+// +                               +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                               |                               |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+                               +
+// |                         Source Address                        |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |           EtherType           |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+/// Length of the Ethernet II header in bytes.
+pub const HEADER_LEN: usize = 14;
+
+// EtherTypes.
+pub const ETHERTYPE_IPV4: u16 = 0x0800;
+pub const ETHERTYPE_ARP: u16 = 0x0806;
+pub const ETHERTYPE_VLAN: u16 = 0x8100;
+pub const ETHERTYPE_IPV6: u16 = 0x86dd;
+pub const ETHERTYPE_QINQ: u16 = 0x88a8;
+
+/// A 48-bit MAC address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct MacAddr(pub [u8; 6]);
+
+impl MacAddr {
+    /// The broadcast address ff:ff:ff:ff:ff:ff.
+    pub const BROADCAST: MacAddr = MacAddr([0xff; 6]);
+
+    /// Constructor to create an address from its six octets.
+    pub const fn new(a: u8, b: u8, c: u8, d: u8, e: u8, f: u8) -> Self {
+        MacAddr([a, b, c, d, e, f])
+    }
+
+    /// Returns the six octets of the address.
+    pub fn octets(&self) -> [u8; 6] {
+        self.0
+    }
+
+    /// Returns true for group (multicast or broadcast) addresses.
+    pub fn is_multicast(&self) -> bool {
+        self.0[0] & 0x01 != 0
+    }
+
+    /// Returns true for the broadcast address.
+    pub fn is_broadcast(&self) -> bool {
+        *self == MacAddr::BROADCAST
+    }
+}
+
+impl From<[u8; 6]> for MacAddr {
+    fn from(octets: [u8; 6]) -> Self {
+        MacAddr(octets)
+    }
+}
+
+impl fmt::Display for MacAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [a, b, c, d, e, g] = self.0;
+        write!(f, "{a:02x}:{b:02x}:{c:02x}:{d:02x}:{e:02x}:{g:02x}")
+    }
+}
+
+impl FromStr for MacAddr {
+    type Err = ParseError;
+
+    /// Parses six hexadecimal octets separated by colons or dashes.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut octets = [0u8; 6];
+        let mut parts = s.split([':', '-']);
+        for octet in &mut octets {
+            let part = parts.next().filter(|part| (1..=2).contains(&part.len()));
+            *octet = part
+                .and_then(|part| u8::from_str_radix(part, 16).ok())
+                .ok_or(ParseError::InvalidField("mac address"))?;
+        }
+        match parts.next() {
+            None => Ok(MacAddr(octets)),
+            Some(_) => Err(ParseError::InvalidField("mac address")),
+        }
+    }
+}
+
+/// Reads a MAC address starting at `offset`.
+/// The caller must have checked the length beforehand.
+pub(crate) fn read_mac(buf: &[u8], offset: usize) -> MacAddr {
+    let mut octets = [0u8; 6];
+    octets.copy_from_slice(&buf[offset..offset + 6]);
+    MacAddr(octets)
+}
+
+/// Header Ethernet II, followed by its payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ethernet {
+    pub destination: MacAddr,
+    pub source: MacAddr,
+    pub ethertype: u16,
+    pub payload: Vec<u8>,
+}
+
+impl Ethernet {
+    /// Constructor to create a new Ethernet frame.
+    pub fn new(destination: MacAddr, source: MacAddr, ethertype: u16, payload: Vec<u8>) -> Self {
+        Ethernet {
+            destination,
+            source,
+            ethertype,
+            payload,
+        }
+    }
+
+    /// Length of the serialized frame (without FCS).
+    pub fn len(&self) -> usize {
+        HEADER_LEN + self.payload.len()
+    }
+
+    /// Returns true if the frame has no payload.
+    pub fn is_empty(&self) -> bool {
+        self.payload.is_empty()
+    }
+
+    // --- GETTER METHODS ---
+
+    /// Returns the destination address.
+    pub fn get_destination(&self) -> MacAddr {
+        self.destination
+    }
+
+    /// Returns the source address.
+    pub fn get_source(&self) -> MacAddr {
+        self.source
+    }
+
+    /// Returns the EtherType.
+    pub fn get_ethertype(&self) -> u16 {
+        self.ethertype
+    }
+
+    /// Returns the payload.
+    pub fn get_payload(&self) -> &Vec<u8> {
+        &self.payload
+    }
+
+    // --- SETTER METHODS ---
+
+    /// Sets the destination address.
+    pub fn set_destination(mut self, destination: MacAddr) -> Self {
+        self.destination = destination;
+        self
+    }
+
+    /// Sets the source address.
+    pub fn set_source(mut self, source: MacAddr) -> Self {
+        self.source = source;
+        self
+    }
+
+    /// Sets the EtherType.
+    pub fn set_ethertype(mut self, ethertype: u16) -> Self {
+        self.ethertype = ethertype;
+        self
+    }
+
+    /// Sets the payload.
+    pub fn set_payload(mut self, payload: Vec<u8>) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    // --- VLAN TAGS ---
+
+    /// Inserts `tag` right after the source address, as the outermost
+    /// tag: the EtherType moves behind the tag and becomes its TPID.
+    pub fn push_vlan_tag(mut self, tag: VlanTag) -> Self {
+        let mut payload = Vec::with_capacity(TAG_LEN + self.payload.len());
+        payload.extend_from_slice(&tag.get_tci().0);
+        payload.extend_from_slice(&self.ethertype.to_be_bytes());
+        payload.extend_from_slice(&self.payload);
+        self.ethertype = tag.tpid;
+        self.payload = payload;
+        self
+    }
+
+    /// Removes the outermost VLAN tag, restoring the EtherType behind it.
+    /// Returns `None`, leaving the frame unchanged, if the frame is not
+    /// tagged.
+    pub fn pop_vlan_tag(&mut self) -> Option<VlanTag> {
+        if !matches!(self.ethertype, ETHERTYPE_VLAN | ETHERTYPE_QINQ) || self.payload.len() < 4 {
+            return None;
+        }
+        let mut bytes = self.ethertype.to_be_bytes().to_vec();
+        bytes.extend_from_slice(&self.payload[..2]);
+        let (tag, _) = VlanTag::from_bytes(&bytes).ok()?;
+        self.ethertype = u16::from_be_bytes([self.payload[2], self.payload[3]]);
+        self.payload.drain(..4);
+        Some(tag)
+    }
+
+    /// Inserts `tags` after the source address, in order: the first tag
+    /// is the outermost, e.g. an S-tag followed by a C-tag for QinQ.
+    pub fn with_vlan_tags(self, tags: Vec<VlanTag>) -> Self {
+        tags.into_iter().rev().fold(self, Ethernet::push_vlan_tag)
+    }
+
+    /// Returns the VLAN tags of the frame, the outermost first, walking
+    /// nested tags up to the first EtherType that is not a TPID.
+    pub fn vlan_tags(&self) -> Vec<VlanTag> {
+        let mut tags = Vec::new();
+        let mut tpid = self.ethertype;
+        let mut offset = 0;
+        while matches!(tpid, ETHERTYPE_VLAN | ETHERTYPE_QINQ) {
+            let Some(tci) = self.payload.get(offset..offset + 4) else {
+                break;
+            };
+            let mut bytes = tpid.to_be_bytes().to_vec();
+            bytes.extend_from_slice(&tci[..2]);
+            let Ok((tag, _)) = VlanTag::from_bytes(&bytes) else {
+                break;
+            };
+            tags.push(tag);
+            tpid = u16::from_be_bytes([tci[2], tci[3]]);
+            offset += TAG_LEN;
+        }
+        tags
+    }
+
+    /// Removes all VLAN tags, restoring the EtherType behind them, and
+    /// returns them, the outermost first.
+    pub fn pop_vlan_tags(&mut self) -> Vec<VlanTag> {
+        let mut tags = Vec::new();
+        while let Some(tag) = self.pop_vlan_tag() {
+            tags.push(tag);
+        }
+        tags
+    }
+
+    /// EtherType of the payload behind the VLAN tags.
+    pub fn inner_ethertype(&self) -> u16 {
+        let offset = self.vlan_tags().len() * TAG_LEN;
+        match offset {
+            0 => self.ethertype,
+            _ => u16::from_be_bytes([self.payload[offset - 2], self.payload[offset - 1]]),
+        }
+    }
+
+    // --- MPLS LABELS ---
+
+    /// Inserts the label stack `labels`, outermost first, between the
+    /// VLAN tags and the payload, with the S bit set on the last label
+    /// only. The EtherType behind the tags becomes MPLS unicast.
+    pub fn with_mpls_labels(mut self, labels: &[MplsEntry]) -> Self {
+        let offset = self.vlan_tags().len() * TAG_LEN;
+        match offset {
+            0 => self.ethertype = mpls::ETHERTYPE,
+            _ => self.payload[offset - 2..offset].copy_from_slice(&mpls::ETHERTYPE.to_be_bytes()),
+        }
+        self.payload
+            .splice(offset..offset, mpls::serialize_stack(labels));
+        self
+    }
+
+    /// Returns the MPLS label stack behind the VLAN tags, the outermost
+    /// label first. Empty if the frame does not carry MPLS or its stack is
+    /// truncated.
+    pub fn mpls_labels(&self) -> Vec<MplsEntry> {
+        if self.inner_ethertype() != mpls::ETHERTYPE {
+            return Vec::new();
+        }
+        let offset = self.vlan_tags().len() * TAG_LEN;
+        mpls::parse_stack(&self.payload[offset..])
+            .map(|(labels, _)| labels)
+            .unwrap_or_default()
+    }
+
+    /// Removes the MPLS label stack and returns it, the outermost label
+    /// first. MPLS does not name the protocol of its payload, so the
+    /// EtherType behind the VLAN tags is set to `ethertype`. Returns an
+    /// empty list, leaving the frame unchanged, if it carries no complete
+    /// label stack.
+    pub fn pop_mpls_labels(&mut self, ethertype: u16) -> Vec<MplsEntry> {
+        let labels = self.mpls_labels();
+        if labels.is_empty() {
+            return labels;
+        }
+        let offset = self.vlan_tags().len() * TAG_LEN;
+        match offset {
+            0 => self.ethertype = ethertype,
+            _ => self.payload[offset - 2..offset].copy_from_slice(&ethertype.to_be_bytes()),
+        }
+        self.payload
+            .drain(offset..offset + labels.len() * mpls::ENTRY_LEN);
+        labels
+    }
+
+    // --- SERIALIZATION ---
+
+    /// Appends the frame to `bytes`.
+    pub fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.destination.0);
+        bytes.extend_from_slice(&self.source.0);
+        bytes.extend_from_slice(&self.ethertype.to_be_bytes());
+        bytes.extend_from_slice(&self.payload);
+    }
+
+    /// Serializes the frame.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.len());
+        self.serialize_into(&mut bytes);
+        bytes
+    }
+
+    /// Parses an Ethernet II frame; everything after the header is payload.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, HEADER_LEN)?;
+        Ok(Ethernet {
+            destination: read_mac(buf, 0),
+            source: read_mac(buf, 6),
+            ethertype: u16::from_be_bytes([buf[12], buf[13]]),
+            payload: buf[HEADER_LEN..].to_vec(),
+        })
+    }
+
+    /// Appends `frames` back to back to `output`, reserving the space for
+    /// all of them up front.
+    pub fn batch_serialize(frames: &[Ethernet], output: &mut Vec<u8>) {
+        output.reserve(frames.iter().map(Ethernet::len).sum());
+        for frame in frames {
+            frame.serialize_into(output);
+        }
+    }
+
+    /// Parses frames stored back to back in `buf`, with the length of each
+    /// given by `frame_lengths`.
+    pub fn batch_parse(buf: &[u8], frame_lengths: &[usize]) -> Result<Vec<Ethernet>, ParseError> {
+        let mut frames = Vec::with_capacity(frame_lengths.len());
+        Ethernet::batch_parse_into(buf, frame_lengths, &mut frames)?;
+        Ok(frames)
+    }
+
+    /// Same as `batch_parse`, replacing the content of `frames` and reusing
+    /// the payload buffers of the frames already there, so that parsing
+    /// batch after batch into the same vector does not allocate once the
+    /// buffers are large enough. The lengths are checked before anything
+    /// is parsed: on error `frames` is left unchanged.
+    pub fn batch_parse_into(
+        buf: &[u8],
+        frame_lengths: &[usize],
+        frames: &mut Vec<Ethernet>,
+    ) -> Result<(), ParseError> {
+        let mut rest = buf;
+        for &len in frame_lengths {
+            ensure_len(rest, len)?;
+            let (frame, next) = rest.split_at(len);
+            ensure_len(frame, HEADER_LEN)?;
+            rest = next;
+        }
+        frames.truncate(frame_lengths.len());
+        let mut offset = 0;
+        for (i, &len) in frame_lengths.iter().enumerate() {
+            let frame = &buf[offset..offset + len];
+            offset += len;
+            let Some(reused) = frames.get_mut(i) else {
+                frames.push(Ethernet::from_bytes(frame)?);
+                continue;
+            };
+            reused.destination = read_mac(frame, 0);
+            reused.source = read_mac(frame, 6);
+            reused.ethertype = u16::from_be_bytes([frame[12], frame[13]]);
+            reused.payload.clear();
+            reused.payload.extend_from_slice(&frame[HEADER_LEN..]);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,331 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Read};
+use std::time::Duration;
+
+use crate::matcher::PacketMatcher;
+use crate::pcap::{CapturedPacket, Reader};
+
+/// Test over a single packet.
+pub type Predicate = Box<dyn Fn(&CapturedPacket) -> bool>;
+
+/// Test of whether a packet answers an earlier one.
+pub type ResponsePredicate = Box<dyn Fn(&CapturedPacket, &CapturedPacket) -> bool>;
+
+enum Kind {
+    Occurs(Predicate),
+    Never(Predicate),
+    CountBetween {
+        predicate: Predicate,
+        min: usize,
+        max: usize,
+    },
+    FollowedBy {
+        trigger: Predicate,
+        response: ResponsePredicate,
+        deadline: Duration,
+    },
+    InOrder(Vec<Predicate>),
+}
+
+/// A property of a packet stream, checked by an `Evaluator`.
+///
+/// By default every packet is considered; `within` and `after` restrict an
+/// expectation to packets in a time window relative to the first packet of
+/// the stream.
+pub struct Expectation {
+    pub name: String,
+    kind: Kind,
+    within: Option<Duration>,
+    after: Option<Duration>,
+}
+
+impl fmt::Debug for Expectation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Expectation")
+            .field("name", &self.name)
+            .field("within", &self.within)
+            .field("after", &self.after)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Expectation {
+    fn new(name: impl Into<String>, kind: Kind) -> Self {
+        Expectation {
+            name: name.into(),
+            kind,
+            within: None,
+            after: None,
+        }
+    }
+
+    /// At least one packet matches `predicate`.
+    pub fn occurs(
+        name: impl Into<String>,
+        predicate: impl Fn(&CapturedPacket) -> bool + 'static,
+    ) -> Self {
+        Expectation::new(name, Kind::Occurs(Box::new(predicate)))
+    }
+
+    /// At least one packet matches the expected packet of `matcher`, e.g.
+    /// `Expectation::matching("echo reply", matcher).within(timeout)`.
+    pub fn matching(name: impl Into<String>, matcher: PacketMatcher) -> Self {
+        Expectation::occurs(name, move |packet| matcher.matches(&packet.data).passed())
+    }
+
+    /// No packet matches `predicate`.
+    pub fn never(
+        name: impl Into<String>,
+        predicate: impl Fn(&CapturedPacket) -> bool + 'static,
+    ) -> Self {
+        Expectation::new(name, Kind::Never(Box::new(predicate)))
+    }
+
+    /// Between `min` and `max` packets (inclusive) match `predicate`.
+    pub fn count_between(
+        name: impl Into<String>,
+        predicate: impl Fn(&CapturedPacket) -> bool + 'static,
+        min: usize,
+        max: usize,
+    ) -> Self {
+        Expectation::new(
+            name,
+            Kind::CountBetween {
+                predicate: Box::new(predicate),
+                min,
+                max,
+            },
+        )
+    }
+
+    /// Every packet matching `trigger` is answered, within `deadline`, by a
+    /// later packet for which `response(trigger, packet)` holds. Triggers
+    /// still unanswered at the end of the stream fail.
+    pub fn followed_by(
+        name: impl Into<String>,
+        trigger: impl Fn(&CapturedPacket) -> bool + 'static,
+        response: impl Fn(&CapturedPacket, &CapturedPacket) -> bool + 'static,
+        deadline: Duration,
+    ) -> Self {
+        Expectation::new(
+            name,
+            Kind::FollowedBy {
+                trigger: Box::new(trigger),
+                response: Box::new(response),
+                deadline,
+            },
+        )
+    }
+
+    /// Packets matching each of `steps` occur in this order, other packets
+    /// possibly in between.
+    pub fn in_order(name: impl Into<String>, steps: Vec<Predicate>) -> Self {
+        Expectation::new(name, Kind::InOrder(steps))
+    }
+
+    // --- SETTER METHODS ---
+
+    /// Only considers packets at most `window` after the start.
+    pub fn within(mut self, window: Duration) -> Self {
+        self.within = Some(window);
+        self
+    }
+
+    /// Only considers packets at least `delay` after the start.
+    pub fn after(mut self, delay: Duration) -> Self {
+        self.after = Some(delay);
+        self
+    }
+
+    fn considers(&self, elapsed: Duration) -> bool {
+        self.within.is_none_or(|window| elapsed <= window)
+            && self.after.is_none_or(|delay| elapsed >= delay)
+    }
+}
+
+/// Outcome of one expectation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpectationResult {
+    pub name: String,
+    pub passed: bool,
+    /// Indices of the packets that satisfied the expectation (the first
+    /// match, the counted packets, the answers or the ordered steps).
+    pub matching: Vec<usize>,
+    /// Indices of the packets that violated it (forbidden packets, packets
+    /// beyond the maximum count, unanswered triggers).
+    pub offending: Vec<usize>,
+}
+
+/// Outcome of a set of expectations over a stream.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExpectationReport {
+    /// Number of packets evaluated.
+    pub packets: usize,
+    pub results: Vec<ExpectationResult>,
+}
+
+impl ExpectationReport {
+    /// Returns true if every expectation passed.
+    pub fn passed(&self) -> bool {
+        self.results.iter().all(|result| result.passed)
+    }
+
+    /// Returns the expectations that failed.
+    pub fn failures(&self) -> impl Iterator<Item = &ExpectationResult> {
+        self.results.iter().filter(|result| !result.passed)
+    }
+}
+
+impl fmt::Display for ExpectationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for result in &self.results {
+            let status = if result.passed { "PASS" } else { "FAIL" };
+            write!(f, "{status} {}", result.name)?;
+            if !result.offending.is_empty() {
+                write!(f, " (offending packets {:?})", result.offending)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct State {
+    matching: Vec<usize>,
+    offending: Vec<usize>,
+    /// Unanswered triggers of `FollowedBy`, oldest first.
+    pending: VecDeque<(usize, CapturedPacket)>,
+}
+
+/// Streaming evaluation of expectations. Packets are fed one by one, so the
+/// stream may come from a capture file or be checked as it is generated.
+pub struct Evaluator {
+    expectations: Vec<(Expectation, State)>,
+    start: Option<Duration>,
+    packets: usize,
+}
+
+impl Evaluator {
+    /// Constructor to create an evaluator of `expectations`.
+    pub fn new(expectations: Vec<Expectation>) -> Self {
+        Evaluator {
+            expectations: expectations
+                .into_iter()
+                .map(|expectation| (expectation, State::default()))
+                .collect(),
+            start: None,
+            packets: 0,
+        }
+    }
+
+    /// Evaluates the next packet of the stream.
+    pub fn feed(&mut self, packet: &CapturedPacket) {
+        let index = self.packets;
+        self.packets += 1;
+        let start = *self.start.get_or_insert(packet.timestamp);
+        let elapsed = packet.timestamp.saturating_sub(start);
+        for (expectation, state) in &mut self.expectations {
+            if let Kind::FollowedBy {
+                response, deadline, ..
+            } = &expectation.kind
+            {
+                while let Some((trigger_index, trigger)) = state.pending.front() {
+                    if packet.timestamp.saturating_sub(trigger.timestamp) <= *deadline {
+                        break;
+                    }
+                    state.offending.push(*trigger_index);
+                    state.pending.pop_front();
+                }
+                let before = state.pending.len();
+                state
+                    .pending
+                    .retain(|(_, trigger)| !response(trigger, packet));
+                if state.pending.len() < before {
+                    state.matching.push(index);
+                }
+            }
+            if !expectation.considers(elapsed) {
+                continue;
+            }
+            match &expectation.kind {
+                Kind::Occurs(predicate) => {
+                    if state.matching.is_empty() && predicate(packet) {
+                        state.matching.push(index);
+                    }
+                }
+                Kind::Never(predicate) => {
+                    if predicate(packet) {
+                        state.offending.push(index);
+                    }
+                }
+                Kind::CountBetween { predicate, max, .. } => {
+                    if predicate(packet) {
+                        if state.matching.len() < *max {
+                            state.matching.push(index);
+                        } else {
+                            state.offending.push(index);
+                        }
+                    }
+                }
+                Kind::FollowedBy { trigger, .. } => {
+                    if trigger(packet) {
+                        state.pending.push_back((index, packet.clone()));
+                    }
+                }
+                Kind::InOrder(steps) => {
+                    if let Some(step) = steps.get(state.matching.len())
+                        && step(packet)
+                    {
+                        state.matching.push(index);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Ends the stream and reports the outcome of each expectation.
+    pub fn finish(self) -> ExpectationReport {
+        let results = self
+            .expectations
+            .into_iter()
+            .map(|(expectation, mut state)| {
+                state
+                    .offending
+                    .extend(state.pending.iter().map(|(index, _)| *index));
+                let passed = match &expectation.kind {
+                    Kind::Occurs(_) => !state.matching.is_empty(),
+                    Kind::Never(_) | Kind::FollowedBy { .. } => state.offending.is_empty(),
+                    Kind::CountBetween { min, .. } => {
+                        state.matching.len() >= *min && state.offending.is_empty()
+                    }
+                    Kind::InOrder(steps) => state.matching.len() == steps.len(),
+                };
+                state.offending.sort_unstable();
+                ExpectationResult {
+                    name: expectation.name,
+                    passed,
+                    matching: state.matching,
+                    offending: state.offending,
+                }
+            })
+            .collect();
+        ExpectationReport {
+            packets: self.packets,
+            results,
+        }
+    }
+}
+
+/// Evaluates `expectations` over every packet of `reader`.
+pub fn evaluate<R: Read>(
+    reader: &mut Reader<R>,
+    expectations: Vec<Expectation>,
+) -> io::Result<ExpectationReport> {
+    let mut evaluator = Evaluator::new(expectations);
+    while let Some(packet) = reader.next_packet()? {
+        evaluator.feed(&packet);
+    }
+    Ok(evaluator.finish())
+}
@@ -0,0 +1,409 @@
+// Deterministic fault injection for testing how callers handle I/O errors.
+// Only compiled with the `faultinject` feature.
+
+use std::cell::RefCell;
+use std::io::{self, Read, Write};
+
+use crate::filter::Filter;
+use crate::pcap::{CapturedPacket, Reader};
+use crate::transport::{RawReceiver, RawSender};
+
+/// Error kinds a fault can produce (a serializable subset of `io::ErrorKind`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FaultErrorKind {
+    NotFound,
+    PermissionDenied,
+    ConnectionRefused,
+    ConnectionReset,
+    BrokenPipe,
+    WouldBlock,
+    InvalidData,
+    TimedOut,
+    Interrupted,
+    UnexpectedEof,
+    Other,
+}
+
+impl From<FaultErrorKind> for io::ErrorKind {
+    fn from(kind: FaultErrorKind) -> Self {
+        match kind {
+            FaultErrorKind::NotFound => io::ErrorKind::NotFound,
+            FaultErrorKind::PermissionDenied => io::ErrorKind::PermissionDenied,
+            FaultErrorKind::ConnectionRefused => io::ErrorKind::ConnectionRefused,
+            FaultErrorKind::ConnectionReset => io::ErrorKind::ConnectionReset,
+            FaultErrorKind::BrokenPipe => io::ErrorKind::BrokenPipe,
+            FaultErrorKind::WouldBlock => io::ErrorKind::WouldBlock,
+            FaultErrorKind::InvalidData => io::ErrorKind::InvalidData,
+            FaultErrorKind::TimedOut => io::ErrorKind::TimedOut,
+            FaultErrorKind::Interrupted => io::ErrorKind::Interrupted,
+            FaultErrorKind::UnexpectedEof => io::ErrorKind::UnexpectedEof,
+            FaultErrorKind::Other => io::ErrorKind::Other,
+        }
+    }
+}
+
+/// What happens to a targeted operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Fault {
+    /// The operation fails with an error of this kind.
+    Error(FaultErrorKind),
+    /// The operation transfers only part of its data.
+    ShortRead,
+    /// One byte of the data transferred by the operation is flipped.
+    Corrupt,
+}
+
+/// A fault applied to the `operation`-th operation (counting from 1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FaultRule {
+    pub operation: u64,
+    pub fault: Fault,
+}
+
+/// A reproducible fault scenario. The seed decides how much a short read
+/// transfers and which byte gets corrupted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FaultPlan {
+    pub seed: u64,
+    pub rules: Vec<FaultRule>,
+}
+
+impl FaultPlan {
+    /// Constructor to create an empty plan.
+    pub fn new(seed: u64) -> Self {
+        FaultPlan {
+            seed,
+            rules: Vec::new(),
+        }
+    }
+
+    /// Generates `count` faults spread over the first `operations` operations.
+    pub fn random(seed: u64, operations: u64, count: usize) -> Self {
+        let mut rng = seed;
+        let mut plan = FaultPlan::new(seed);
+        for _ in 0..count {
+            let operation = 1 + next_random(&mut rng) % operations.max(1);
+            let fault = match next_random(&mut rng) % 3 {
+                0 => Fault::Error(FaultErrorKind::Other),
+                1 => Fault::ShortRead,
+                _ => Fault::Corrupt,
+            };
+            plan = plan.add(operation, fault);
+        }
+        plan
+    }
+
+    /// Adds a fault on the `operation`-th operation.
+    pub fn add(mut self, operation: u64, fault: Fault) -> Self {
+        self.rules.push(FaultRule { operation, fault });
+        self
+    }
+
+    /// Fails the `operation`-th operation with `kind`.
+    pub fn fail_nth(self, operation: u64, kind: FaultErrorKind) -> Self {
+        self.add(operation, Fault::Error(kind))
+    }
+
+    /// Makes the `operation`-th operation a short read.
+    pub fn short_read_nth(self, operation: u64) -> Self {
+        self.add(operation, Fault::ShortRead)
+    }
+
+    /// Corrupts the data of the `operation`-th operation.
+    pub fn corrupt_nth(self, operation: u64) -> Self {
+        self.add(operation, Fault::Corrupt)
+    }
+}
+
+/// SplitMix64 step.
+fn next_random(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Counts operations and decides which fault, if any, applies to each.
+#[derive(Debug, Clone)]
+struct Injector {
+    plan: FaultPlan,
+    operations: u64,
+    rng: u64,
+}
+
+impl Injector {
+    fn new(plan: FaultPlan) -> Self {
+        let rng = plan.seed;
+        Injector {
+            plan,
+            operations: 0,
+            rng,
+        }
+    }
+
+    fn next_fault(&mut self) -> Option<Fault> {
+        self.operations += 1;
+        let operation = self.operations;
+        self.plan
+            .rules
+            .iter()
+            .find(|rule| rule.operation == operation)
+            .map(|rule| rule.fault)
+    }
+
+    /// Returns a length in `1..len` (or `len` when it is below 2).
+    fn short_len(&mut self, len: usize) -> usize {
+        if len < 2 {
+            return len;
+        }
+        1 + (next_random(&mut self.rng) % (len as u64 - 1)) as usize
+    }
+
+    fn corrupt(&mut self, data: &mut [u8]) {
+        if !data.is_empty() {
+            let index = (next_random(&mut self.rng) % data.len() as u64) as usize;
+            data[index] ^= 0xff;
+        }
+    }
+}
+
+fn fault_error(kind: FaultErrorKind) -> io::Error {
+    io::Error::new(kind.into(), "injected fault")
+}
+
+/// Wraps a reader or writer; every `read` or `write` call is one operation.
+#[derive(Debug)]
+pub struct FaultyIo<T> {
+    inner: T,
+    injector: Injector,
+}
+
+impl<T> FaultyIo<T> {
+    /// Wraps `inner`, injecting the faults of `plan`.
+    pub fn new(inner: T, plan: FaultPlan) -> Self {
+        FaultyIo {
+            inner,
+            injector: Injector::new(plan),
+        }
+    }
+
+    /// Number of operations performed so far.
+    pub fn operations(&self) -> u64 {
+        self.injector.operations
+    }
+
+    /// Returns the wrapped reader or writer.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: Read> Read for FaultyIo<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.injector.next_fault() {
+            None => self.inner.read(buf),
+            Some(Fault::Error(kind)) => Err(fault_error(kind)),
+            Some(Fault::ShortRead) => {
+                let len = self.injector.short_len(buf.len());
+                self.inner.read(&mut buf[..len])
+            }
+            Some(Fault::Corrupt) => {
+                let read = self.inner.read(buf)?;
+                self.injector.corrupt(&mut buf[..read]);
+                Ok(read)
+            }
+        }
+    }
+}
+
+impl<T: Write> Write for FaultyIo<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.injector.next_fault() {
+            None => self.inner.write(buf),
+            Some(Fault::Error(kind)) => Err(fault_error(kind)),
+            Some(Fault::ShortRead) => {
+                let len = self.injector.short_len(buf.len());
+                self.inner.write(&buf[..len])
+            }
+            Some(Fault::Corrupt) => {
+                let mut data = buf.to_vec();
+                self.injector.corrupt(&mut data);
+                self.inner.write(&data)
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Wraps a pcap reader; every record read is one operation. A short read
+/// truncates the record data, as a capture cut short would.
+#[derive(Debug)]
+pub struct FaultyReader<R> {
+    reader: Reader<R>,
+    injector: Injector,
+}
+
+impl<R: Read> FaultyReader<R> {
+    /// Wraps `reader`, injecting the faults of `plan`.
+    pub fn new(reader: Reader<R>, plan: FaultPlan) -> Self {
+        FaultyReader {
+            reader,
+            injector: Injector::new(plan),
+        }
+    }
+
+    /// Returns the wrapped reader.
+    pub fn into_inner(self) -> Reader<R> {
+        self.reader
+    }
+
+    /// Reads the next record, applying the fault planned for it.
+    pub fn next_packet(&mut self) -> io::Result<Option<CapturedPacket>> {
+        let fault = self.injector.next_fault();
+        if let Some(Fault::Error(kind)) = fault {
+            return Err(fault_error(kind));
+        }
+        let Some(mut packet) = self.reader.next_packet()? else {
+            return Ok(None);
+        };
+        match fault {
+            Some(Fault::ShortRead) => {
+                let len = self.injector.short_len(packet.data.len());
+                packet.data.truncate(len);
+            }
+            Some(Fault::Corrupt) => self.injector.corrupt(&mut packet.data),
+            _ => {}
+        }
+        Ok(Some(packet))
+    }
+}
+
+impl<R: Read> Iterator for FaultyReader<R> {
+    type Item = io::Result<CapturedPacket>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_packet().transpose()
+    }
+}
+
+/// Wraps a `RawSender` or `RawReceiver`; every frame sent or received is
+/// one operation, so a batch send fails partway when a fault targets one
+/// of its frames. A short read truncates the frame.
+#[derive(Debug)]
+pub struct FaultySocket<S> {
+    inner: S,
+    injector: RefCell<Injector>,
+}
+
+impl<S> FaultySocket<S> {
+    /// Wraps `inner`, injecting the faults of `plan`.
+    pub fn new(inner: S, plan: FaultPlan) -> Self {
+        FaultySocket {
+            inner,
+            injector: RefCell::new(Injector::new(plan)),
+        }
+    }
+
+    /// Number of operations performed so far.
+    pub fn operations(&self) -> u64 {
+        self.injector.borrow().operations
+    }
+
+    /// Returns the wrapped socket.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: RawSender> RawSender for FaultySocket<S> {
+    fn send(&self, frame: &[u8]) -> io::Result<()> {
+        let mut injector = self.injector.borrow_mut();
+        match injector.next_fault() {
+            None => self.inner.send(frame),
+            Some(Fault::Error(kind)) => Err(fault_error(kind)),
+            Some(Fault::ShortRead) => {
+                let len = injector.short_len(frame.len());
+                self.inner.send(&frame[..len])
+            }
+            Some(Fault::Corrupt) => {
+                let mut data = frame.to_vec();
+                injector.corrupt(&mut data);
+                self.inner.send(&data)
+            }
+        }
+    }
+}
+
+impl<S: RawReceiver> RawReceiver for FaultySocket<S> {
+    fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut injector = self.injector.borrow_mut();
+        let fault = injector.next_fault();
+        if let Some(Fault::Error(kind)) = fault {
+            return Err(fault_error(kind));
+        }
+        let len = self.inner.recv(buf)?;
+        match fault {
+            Some(Fault::ShortRead) => Ok(injector.short_len(len)),
+            Some(Fault::Corrupt) => {
+                injector.corrupt(&mut buf[..len]);
+                Ok(len)
+            }
+            _ => Ok(len),
+        }
+    }
+
+    /// Filters are set on the inner socket, without faults.
+    fn set_filter(&self, filter: &Filter) -> io::Result<()> {
+        self.inner.set_filter(filter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::Loopback;
+
+    #[test]
+    fn socket_faults_follow_the_plan() {
+        let plan = FaultPlan::new(7)
+            .fail_nth(3, FaultErrorKind::ConnectionReset)
+            .corrupt_nth(2)
+            .short_read_nth(6);
+        let socket = FaultySocket::new(Loopback::new(), plan.clone());
+        let frames: [&[u8]; 4] = [b"first", b"second", b"third", b"fourth"];
+        // The third send fails mid-batch, after two frames went out.
+        assert_eq!(socket.send_batch(&frames).unwrap(), 2);
+        socket.send(frames[2]).unwrap();
+        assert_eq!(socket.operations(), 4);
+
+        let mut buf = [0; 16];
+        assert_eq!(socket.recv(&mut buf).unwrap(), 5);
+        assert_eq!(&buf[..5], b"first");
+        assert!(socket.recv(&mut buf).unwrap() < 6);
+        assert_eq!(socket.recv(&mut buf).unwrap(), 5);
+        assert_eq!(&buf[..5], b"third");
+        assert_eq!(socket.into_inner().len(), 0);
+
+        let failing = FaultySocket::new(
+            Loopback::new(),
+            FaultPlan::new(0).fail_nth(1, FaultErrorKind::ConnectionReset),
+        );
+        let err = failing.send(frames[0]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionReset);
+
+        // The same plan corrupts the same byte.
+        let again = FaultySocket::new(Loopback::new(), plan);
+        again.send_batch(&frames).unwrap();
+        let sent = again.into_inner().drain();
+        assert_ne!(sent[1], frames[1]);
+        assert_eq!(sent[1].len(), frames[1].len());
+    }
+}
@@ -0,0 +1,792 @@
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use crate::pcap::{DecodedStack, NetworkLayer, TransportLayer};
+use crate::util::{IpAddrPair, IpCidr};
+
+mod bpf;
+
+pub use bpf::{BpfError, MAX_INSTRUCTIONS, SockFilter, disassemble};
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const ETHERTYPE_ARP: u16 = 0x0806;
+
+const PROTOCOL_ICMP: u8 = 1;
+const PROTOCOL_TCP: u8 = 6;
+const PROTOCOL_UDP: u8 = 17;
+const PROTOCOL_ICMPV6: u8 = 58;
+const PROTOCOL_SCTP: u8 = 132;
+
+/// Names of the TCP flags in `tcp[tcpflags]` tests, as tcpdump spells them.
+const TCP_FLAG_NAMES: &[(&str, u8)] = &[
+    ("tcp-fin", 0x01),
+    ("tcp-syn", 0x02),
+    ("tcp-rst", 0x04),
+    ("tcp-push", 0x08),
+    ("tcp-ack", 0x10),
+    ("tcp-urg", 0x20),
+    ("tcp-ece", 0x40),
+    ("tcp-cwr", 0x80),
+];
+
+/// Endpoint of a packet that a primitive looks at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Source,
+    Destination,
+    /// Either endpoint, the default when neither `src` nor `dst` is given.
+    Either,
+}
+
+/// Test of a filter expression with no sub-expression.
+///
+/// Primitives look at the outermost IP header, as tcpdump does: on an
+/// IP-in-IP packet, `host` and `proto` see the tunnel endpoints, and
+/// `port` and `tcp[tcpflags]` never match.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Primitive {
+    /// EtherType after any VLAN tags (`ether proto 0x88cc`, `arp`). Frames
+    /// without an Ethernet header have the EtherType of their IP version.
+    EtherType(u16),
+    /// IPv4 packet (`ip`).
+    Ipv4,
+    /// IPv6 packet (`ip6`).
+    Ipv6,
+    /// IP protocol or IPv6 upper-layer protocol (`proto 47`, `tcp`).
+    Protocol(u8),
+    /// Address inside a prefix (`net 10.0.0.0/8`, or `host 10.0.0.1` for
+    /// a host prefix). An address of the other family never matches.
+    Net { direction: Direction, cidr: IpCidr },
+    /// TCP or UDP port within `first..=last` (`port 80`, `portrange
+    /// 6000-6010`).
+    Port {
+        direction: Direction,
+        first: u16,
+        last: u16,
+    },
+    /// TCP segment whose flags, masked with `mask`, equal `value`, or
+    /// differ from it when `negated` (`tcp[tcpflags] & tcp-syn != 0`).
+    TcpFlags { mask: u8, value: u8, negated: bool },
+    /// Frame with an outer VLAN tag, with the given VLAN ID if any
+    /// (`vlan`, `vlan 100`).
+    Vlan(Option<u16>),
+}
+
+/// A packet filter, parsed from a subset of the tcpdump expression
+/// language or built from primitives.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Filter {
+    Primitive(Primitive),
+    Not(Box<Filter>),
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+}
+
+/// Error returned for an expression that does not parse.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilterError {
+    /// The expression ended where more was expected.
+    UnexpectedEnd,
+    /// A token that has no place where it was found.
+    UnexpectedToken(String),
+    /// A character that is not part of the language.
+    UnexpectedCharacter(char),
+    /// An address, prefix or number that does not parse or is out of
+    /// range.
+    InvalidValue(String),
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FilterError::UnexpectedEnd => write!(f, "unexpected end of filter expression"),
+            FilterError::UnexpectedToken(token) => write!(f, "unexpected `{token}`"),
+            FilterError::UnexpectedCharacter(c) => write!(f, "unexpected character `{c}`"),
+            FilterError::InvalidValue(value) => write!(f, "invalid value `{value}`"),
+        }
+    }
+}
+
+impl std::error::Error for FilterError {}
+
+impl Filter {
+    /// Parses an expression. The supported primitives are `ip`, `ip6`,
+    /// `arp`, `tcp`, `udp`, `sctp`, `icmp`, `icmp6`, `ether proto N`,
+    /// `[ip|ip6] proto N`, `[src|dst] host ADDR`, `[src|dst] net
+    /// ADDR/LEN`, `[src|dst] port N`, `[src|dst] portrange N-M`, `vlan
+    /// [ID]` and `tcp[tcpflags] [& FLAGS] (==|!=) FLAGS`, combined with
+    /// `and`/`&&`, `or`/`||`, `not`/`!` and parentheses.
+    pub fn parse(expression: &str) -> Result<Self, FilterError> {
+        let tokens = tokenize(expression)?;
+        let mut parser = Parser {
+            tokens: &tokens,
+            position: 0,
+        };
+        let filter = parser.or()?;
+        match parser.peek() {
+            None => Ok(filter),
+            Some(token) => Err(FilterError::UnexpectedToken(token.to_string())),
+        }
+    }
+
+    /// Returns a filter matching packets matched by both filters.
+    pub fn and(self, other: Filter) -> Self {
+        Filter::And(Box::new(self), Box::new(other))
+    }
+
+    /// Returns a filter matching packets matched by either filter.
+    pub fn or(self, other: Filter) -> Self {
+        Filter::Or(Box::new(self), Box::new(other))
+    }
+
+    /// Returns a filter matching the packets this one does not.
+    pub fn negated(self) -> Self {
+        Filter::Not(Box::new(self))
+    }
+
+    /// Returns true if the decoded frame matches the filter.
+    pub fn matches(&self, stack: &DecodedStack) -> bool {
+        match self {
+            Filter::Primitive(primitive) => primitive.matches(stack),
+            Filter::Not(filter) => !filter.matches(stack),
+            Filter::And(a, b) => a.matches(stack) && b.matches(stack),
+            Filter::Or(a, b) => a.matches(stack) || b.matches(stack),
+        }
+    }
+
+    /// Decodes `frame`, captured with `link_type`, and returns true if it
+    /// matches the filter.
+    pub fn matches_frame(&self, link_type: u32, frame: &[u8]) -> bool {
+        self.matches(&DecodedStack::decode(link_type, frame))
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Filter::Or(..) => 0,
+            Filter::And(..) => 1,
+            Filter::Not(_) | Filter::Primitive(_) => 2,
+        }
+    }
+
+    /// Writes `operand`, in parentheses if it binds less tightly than
+    /// `self`.
+    fn fmt_operand(&self, operand: &Filter, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if operand.precedence() < self.precedence() {
+            write!(f, "({operand})")
+        } else {
+            write!(f, "{operand}")
+        }
+    }
+}
+
+impl From<Primitive> for Filter {
+    fn from(primitive: Primitive) -> Self {
+        Filter::Primitive(primitive)
+    }
+}
+
+impl FromStr for Filter {
+    type Err = FilterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Filter::parse(s)
+    }
+}
+
+/// Writes the filter back in the expression language.
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Filter::Primitive(primitive) => primitive.fmt(f),
+            Filter::Not(filter) => {
+                write!(f, "not ")?;
+                self.fmt_operand(filter, f)
+            }
+            Filter::And(a, b) => {
+                self.fmt_operand(a, f)?;
+                write!(f, " and ")?;
+                self.fmt_operand(b, f)
+            }
+            Filter::Or(a, b) => {
+                self.fmt_operand(a, f)?;
+                write!(f, " or ")?;
+                self.fmt_operand(b, f)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Direction::Source => write!(f, "src "),
+            Direction::Destination => write!(f, "dst "),
+            Direction::Either => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Primitive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Primitive::EtherType(ETHERTYPE_ARP) => write!(f, "arp"),
+            Primitive::EtherType(ethertype) => write!(f, "ether proto {ethertype:#06x}"),
+            Primitive::Ipv4 => write!(f, "ip"),
+            Primitive::Ipv6 => write!(f, "ip6"),
+            Primitive::Protocol(PROTOCOL_TCP) => write!(f, "tcp"),
+            Primitive::Protocol(PROTOCOL_UDP) => write!(f, "udp"),
+            Primitive::Protocol(PROTOCOL_SCTP) => write!(f, "sctp"),
+            Primitive::Protocol(PROTOCOL_ICMPV6) => write!(f, "icmp6"),
+            Primitive::Protocol(protocol) => write!(f, "proto {protocol}"),
+            Primitive::Net { direction, cidr } if cidr == IpCidr::host(cidr.address()) => {
+                write!(f, "{direction}host {}", cidr.address())
+            }
+            Primitive::Net { direction, cidr } => write!(f, "{direction}net {cidr}"),
+            Primitive::Port {
+                direction,
+                first,
+                last,
+            } if first == last => write!(f, "{direction}port {first}"),
+            Primitive::Port {
+                direction,
+                first,
+                last,
+            } => write!(f, "{direction}portrange {first}-{last}"),
+            Primitive::TcpFlags {
+                mask,
+                value,
+                negated,
+            } => {
+                let operator = if negated { "!=" } else { "==" };
+                write!(f, "tcp[tcpflags] & {mask:#04x} {operator} {value:#04x}")
+            }
+            Primitive::Vlan(Some(vid)) => write!(f, "vlan {vid}"),
+            Primitive::Vlan(None) => write!(f, "vlan"),
+        }
+    }
+}
+
+// --- EVALUATION ---
+
+/// Outermost IP header of a decoded frame.
+fn outer_network(stack: &DecodedStack) -> &NetworkLayer {
+    stack.tunnels.first().unwrap_or(&stack.network)
+}
+
+/// Transport header of a frame with no IP-in-IP tunnel.
+fn outer_transport(stack: &DecodedStack) -> &TransportLayer {
+    if stack.tunnels.is_empty() {
+        &stack.transport
+    } else {
+        &TransportLayer::None
+    }
+}
+
+fn protocol(stack: &DecodedStack) -> Option<u8> {
+    match (outer_network(stack), outer_transport(stack)) {
+        (_, TransportLayer::Tcp(_)) => Some(PROTOCOL_TCP),
+        (_, TransportLayer::Udp(_)) => Some(PROTOCOL_UDP),
+        (_, TransportLayer::Other { protocol, .. }) => Some(*protocol),
+        (NetworkLayer::Ipv4(ipv4), _) => Some(ipv4.protocol),
+        (NetworkLayer::Ipv6(ipv6), _) => Some(ipv6.next_header),
+        (NetworkLayer::None, TransportLayer::None) => None,
+    }
+}
+
+fn ethertype(stack: &DecodedStack) -> Option<u16> {
+    match (&stack.ethernet, &stack.network) {
+        (Some(ethernet), _) => Some(ethernet.inner_ethertype()),
+        (None, NetworkLayer::Ipv4(_)) => Some(ETHERTYPE_IPV4),
+        (None, NetworkLayer::Ipv6(_)) => Some(ETHERTYPE_IPV6),
+        (None, NetworkLayer::None) => None,
+    }
+}
+
+fn either<T>(direction: Direction, source: T, destination: T, test: impl Fn(T) -> bool) -> bool {
+    match direction {
+        Direction::Source => test(source),
+        Direction::Destination => test(destination),
+        Direction::Either => test(source) || test(destination),
+    }
+}
+
+impl Primitive {
+    /// Returns true if the decoded frame matches the primitive.
+    pub fn matches(&self, stack: &DecodedStack) -> bool {
+        match *self {
+            Primitive::EtherType(expected) => ethertype(stack) == Some(expected),
+            Primitive::Ipv4 => matches!(outer_network(stack), NetworkLayer::Ipv4(_)),
+            Primitive::Ipv6 => matches!(outer_network(stack), NetworkLayer::Ipv6(_)),
+            Primitive::Protocol(expected) => protocol(stack) == Some(expected),
+            Primitive::Net { direction, cidr } => {
+                outer_network(stack)
+                    .addresses()
+                    .is_some_and(|pair: IpAddrPair| {
+                        either(direction, pair.src, pair.dst, |address: IpAddr| {
+                            cidr.contains(address)
+                        })
+                    })
+            }
+            Primitive::Port {
+                direction,
+                first,
+                last,
+            } => outer_transport(stack)
+                .ports()
+                .is_some_and(|(source, destination)| {
+                    either(direction, source, destination, |port| {
+                        (first..=last).contains(&port)
+                    })
+                }),
+            Primitive::TcpFlags {
+                mask,
+                value,
+                negated,
+            } => outer_transport(stack)
+                .as_tcp()
+                .is_some_and(|tcp| ((tcp.flags as u8 & mask) == value) != negated),
+            Primitive::Vlan(vid) => match (stack.vlan_ids.first(), vid) {
+                (Some(outer), Some(vid)) => *outer == vid,
+                (Some(_), None) => true,
+                (None, _) => false,
+            },
+        }
+    }
+}
+
+// --- PARSING ---
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    LeftParen,
+    RightParen,
+    LeftBracket,
+    RightBracket,
+    Not,
+    And,
+    Or,
+    Ampersand,
+    Pipe,
+    Equal,
+    NotEqual,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            Token::Word(word) => word,
+            Token::LeftParen => "(",
+            Token::RightParen => ")",
+            Token::LeftBracket => "[",
+            Token::RightBracket => "]",
+            Token::Not => "not",
+            Token::And => "and",
+            Token::Or => "or",
+            Token::Ampersand => "&",
+            Token::Pipe => "|",
+            Token::Equal => "==",
+            Token::NotEqual => "!=",
+        };
+        f.write_str(text)
+    }
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>, FilterError> {
+    let mut tokens = Vec::new();
+    let mut chars = expression.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let mut next_is = |expected: char| chars.next_if(|(_, c)| *c == expected).is_some();
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::LeftParen,
+            ')' => Token::RightParen,
+            '[' => Token::LeftBracket,
+            ']' => Token::RightBracket,
+            '&' if next_is('&') => Token::And,
+            '&' => Token::Ampersand,
+            '|' if next_is('|') => Token::Or,
+            '|' => Token::Pipe,
+            '!' if next_is('=') => Token::NotEqual,
+            '!' => Token::Not,
+            '=' => {
+                next_is('=');
+                Token::Equal
+            }
+            c if is_word_char(c) => {
+                let mut end = start + c.len_utf8();
+                while let Some((index, c)) = chars.next_if(|(_, c)| is_word_char(*c)) {
+                    end = index + c.len_utf8();
+                }
+                match &expression[start..end] {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    word => Token::Word(word.to_string()),
+                }
+            }
+            c => return Err(FilterError::UnexpectedCharacter(c)),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '.' | ':' | '/' | '-' | '_')
+}
+
+fn parse_number<T: TryFrom<u64>>(word: &str) -> Result<T, FilterError> {
+    let value = match word.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => word.parse(),
+    };
+    value
+        .ok()
+        .and_then(|value| T::try_from(value).ok())
+        .ok_or_else(|| FilterError::InvalidValue(word.to_string()))
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Result<&Token, FilterError> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .ok_or(FilterError::UnexpectedEnd)?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn accept(&mut self, expected: &Token) -> bool {
+        let found = self.peek() == Some(expected);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, expected: &Token) -> Result<(), FilterError> {
+        match self.next()? {
+            token if token == expected => Ok(()),
+            token => Err(FilterError::UnexpectedToken(token.to_string())),
+        }
+    }
+
+    fn accept_word(&mut self, expected: &str) -> bool {
+        self.accept(&Token::Word(expected.to_string()))
+    }
+
+    fn word(&mut self) -> Result<String, FilterError> {
+        match self.next()? {
+            Token::Word(word) => Ok(word.clone()),
+            token => Err(FilterError::UnexpectedToken(token.to_string())),
+        }
+    }
+
+    fn or(&mut self) -> Result<Filter, FilterError> {
+        let mut filter = self.and()?;
+        while self.accept(&Token::Or) {
+            filter = filter.or(self.and()?);
+        }
+        Ok(filter)
+    }
+
+    fn and(&mut self) -> Result<Filter, FilterError> {
+        let mut filter = self.not()?;
+        while self.accept(&Token::And) {
+            filter = filter.and(self.not()?);
+        }
+        Ok(filter)
+    }
+
+    fn not(&mut self) -> Result<Filter, FilterError> {
+        if self.accept(&Token::Not) {
+            return Ok(self.not()?.negated());
+        }
+        if self.accept(&Token::LeftParen) {
+            let filter = self.or()?;
+            self.expect(&Token::RightParen)?;
+            return Ok(filter);
+        }
+        self.primitive()
+    }
+
+    fn primitive(&mut self) -> Result<Filter, FilterError> {
+        let direction = if self.accept_word("src") {
+            Direction::Source
+        } else if self.accept_word("dst") {
+            Direction::Destination
+        } else {
+            Direction::Either
+        };
+        let keyword = self.word()?;
+        let primitive = match (keyword.as_str(), direction) {
+            ("host", _) => {
+                let word = self.word()?;
+                let address: IpAddr = word
+                    .parse()
+                    .map_err(|_| FilterError::InvalidValue(word.clone()))?;
+                Primitive::Net {
+                    direction,
+                    cidr: IpCidr::host(address),
+                }
+            }
+            ("net", _) => {
+                let word = self.word()?;
+                let cidr = word
+                    .parse()
+                    .map_err(|_| FilterError::InvalidValue(word.clone()))?;
+                Primitive::Net { direction, cidr }
+            }
+            ("port", _) => {
+                let port = parse_number(&self.word()?)?;
+                Primitive::Port {
+                    direction,
+                    first: port,
+                    last: port,
+                }
+            }
+            ("portrange", _) => {
+                let word = self.word()?;
+                let invalid = || FilterError::InvalidValue(word.clone());
+                let (first, last) = word.split_once('-').ok_or_else(invalid)?;
+                let (first, last) = (parse_number(first)?, parse_number(last)?);
+                if first > last {
+                    return Err(invalid());
+                }
+                Primitive::Port {
+                    direction,
+                    first,
+                    last,
+                }
+            }
+            (_, Direction::Source | Direction::Destination) => {
+                return Err(FilterError::UnexpectedToken(keyword));
+            }
+            ("ip" | "ip6", _) if self.accept_word("proto") => {
+                let version = if keyword == "ip" {
+                    Primitive::Ipv4
+                } else {
+                    Primitive::Ipv6
+                };
+                let protocol = parse_number(&self.word()?)?;
+                return Ok(Filter::from(version).and(Primitive::Protocol(protocol).into()));
+            }
+            ("ip", _) => Primitive::Ipv4,
+            ("ip6", _) => Primitive::Ipv6,
+            ("proto", _) => Primitive::Protocol(parse_number(&self.word()?)?),
+            ("ether", _) => {
+                if !self.accept_word("proto") {
+                    return Err(self.peek().map_or(FilterError::UnexpectedEnd, |token| {
+                        FilterError::UnexpectedToken(token.to_string())
+                    }));
+                }
+                Primitive::EtherType(parse_number(&self.word()?)?)
+            }
+            ("arp", _) => Primitive::EtherType(ETHERTYPE_ARP),
+            ("tcp", _) if self.accept(&Token::LeftBracket) => return self.tcp_flags(),
+            ("tcp", _) => Primitive::Protocol(PROTOCOL_TCP),
+            ("udp", _) => Primitive::Protocol(PROTOCOL_UDP),
+            ("sctp", _) => Primitive::Protocol(PROTOCOL_SCTP),
+            ("icmp6", _) => Primitive::Protocol(PROTOCOL_ICMPV6),
+            ("icmp", _) => {
+                return Ok(
+                    Filter::from(Primitive::Ipv4).and(Primitive::Protocol(PROTOCOL_ICMP).into())
+                );
+            }
+            ("vlan", _) => match self.peek() {
+                Some(Token::Word(word)) if word.starts_with(|c: char| c.is_ascii_digit()) => {
+                    let vid: u16 = parse_number(&self.word()?)?;
+                    if vid > 0x0fff {
+                        return Err(FilterError::InvalidValue(vid.to_string()));
+                    }
+                    Primitive::Vlan(Some(vid))
+                }
+                _ => Primitive::Vlan(None),
+            },
+            _ => return Err(FilterError::UnexpectedToken(keyword)),
+        };
+        Ok(primitive.into())
+    }
+
+    /// Parses the rest of `tcp[tcpflags] [& FLAGS] (==|!=) FLAGS`, after
+    /// the opening bracket.
+    fn tcp_flags(&mut self) -> Result<Filter, FilterError> {
+        let field = self.word()?;
+        if field != "tcpflags" && field != "13" {
+            return Err(FilterError::UnexpectedToken(field));
+        }
+        self.expect(&Token::RightBracket)?;
+        let mask = if self.accept(&Token::Ampersand) {
+            self.flags()?
+        } else {
+            0xff
+        };
+        let negated = match self.next()? {
+            Token::Equal => false,
+            Token::NotEqual => true,
+            token => return Err(FilterError::UnexpectedToken(token.to_string())),
+        };
+        let value = self.flags()?;
+        Ok(Primitive::TcpFlags {
+            mask,
+            value: value & mask,
+            negated,
+        }
+        .into())
+    }
+
+    /// Parses flag names or numbers joined with `|`, possibly in
+    /// parentheses.
+    fn flags(&mut self) -> Result<u8, FilterError> {
+        if self.accept(&Token::LeftParen) {
+            let flags = self.flags()?;
+            self.expect(&Token::RightParen)?;
+            return Ok(flags);
+        }
+        let mut flags = 0;
+        loop {
+            let word = self.word()?;
+            flags |= match TCP_FLAG_NAMES.iter().find(|(name, _)| *name == word) {
+                Some((_, flag)) => *flag,
+                None => parse_number(&word)?,
+            };
+            if !self.accept(&Token::Pipe) {
+                return Ok(flags);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use super::*;
+    use crate::ethernet::{Ethernet, MacAddr};
+    use crate::ipv4::IPv4;
+    use crate::ipv6::IPv6;
+    use crate::pcap::{LINKTYPE_ETHERNET, LINKTYPE_RAW};
+    use crate::tcp::{TCP, flags};
+    use crate::udp::UDP;
+    use crate::vlan::VlanTag;
+
+    fn tcp_v4(source: [u8; 4], destination: [u8; 4], flags: u16) -> Vec<u8> {
+        let tcp = TCP::segment(40000, 443, 1, 0, flags);
+        IPv4::with_payload(
+            Ipv4Addr::from(source),
+            Ipv4Addr::from(destination),
+            PROTOCOL_TCP,
+            tcp.to_bytes(),
+        )
+        .to_bytes()
+    }
+
+    fn udp_v6(destination_port: u16) -> Vec<u8> {
+        let udp = UDP::new(5353, destination_port, b"query".to_vec());
+        IPv6::with_payload(
+            "2001:db8::1".parse::<Ipv6Addr>().unwrap(),
+            "2001:db8:1::2".parse::<Ipv6Addr>().unwrap(),
+            PROTOCOL_UDP,
+            udp.to_bytes(),
+        )
+        .to_bytes()
+    }
+
+    fn matches(expression: &str, packet: &[u8]) -> bool {
+        Filter::parse(expression)
+            .unwrap()
+            .matches_frame(LINKTYPE_RAW, packet)
+    }
+
+    #[test]
+    fn hosts_nets_and_ports_of_both_families() {
+        let v4 = tcp_v4([10, 0, 0, 1], [192, 168, 1, 5], flags::SYN);
+        let v6 = udp_v6(53);
+        assert!(matches("dst host 192.168.1.5 and tcp", &v4));
+        assert!(!matches("src host 192.168.1.5", &v4));
+        assert!(matches("net 10.0.0.0/8 and port 443", &v4));
+        assert!(matches("ip6 and dst net 2001:db8:1::/48", &v6));
+        assert!(matches("udp and portrange 50-60", &v6));
+        // Prefixes never match addresses of the other family.
+        assert!(!matches("net ::/0", &v4));
+        assert!(!matches("net 0.0.0.0/0", &v6));
+        assert!(matches("not ip and ip6 proto 17", &v6));
+        assert!(matches("(icmp or tcp) && !udp", &v4));
+    }
+
+    #[test]
+    fn tcp_flag_tests() {
+        let syn = tcp_v4([10, 0, 0, 1], [10, 0, 0, 2], flags::SYN);
+        let syn_ack = tcp_v4([10, 0, 0, 2], [10, 0, 0, 1], flags::SYN | flags::ACK);
+        let any_syn = "tcp[tcpflags] & tcp-syn != 0";
+        assert!(matches(any_syn, &syn) && matches(any_syn, &syn_ack));
+        let only_syn = "tcp[tcpflags] & (tcp-syn|tcp-ack) == tcp-syn";
+        assert!(matches(only_syn, &syn) && !matches(only_syn, &syn_ack));
+        assert!(matches("tcp[13] = 0x12", &syn_ack));
+        assert!(!matches(any_syn, &udp_v6(53)));
+    }
+
+    #[test]
+    fn ethertypes_and_vlans() {
+        let frame = Ethernet::new(
+            MacAddr::BROADCAST,
+            MacAddr::from([2, 0, 0, 0, 0, 1]),
+            ETHERTYPE_ARP,
+            vec![0; 28],
+        )
+        .push_vlan_tag(VlanTag::new(0, false, 100))
+        .to_bytes();
+        let filter = |expression: &str| Filter::parse(expression).unwrap();
+        assert!(filter("arp and vlan 100").matches_frame(LINKTYPE_ETHERNET, &frame));
+        assert!(filter("ether proto 0x0806 and vlan").matches_frame(LINKTYPE_ETHERNET, &frame));
+        assert!(!filter("vlan 101 or ip").matches_frame(LINKTYPE_ETHERNET, &frame));
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for expression in [
+            "src host 10.0.0.1 and (tcp or udp)",
+            "not (dst net 2001:db8::/32 or vlan 7)",
+            "ip and proto 47 or portrange 1-1023",
+            "tcp[tcpflags] & 0x12 == 0x12",
+        ] {
+            let filter = Filter::parse(expression).unwrap();
+            assert_eq!(filter.to_string(), expression);
+            assert_eq!(Filter::parse(&filter.to_string()).unwrap(), filter);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_expressions() {
+        assert_eq!(Filter::parse("tcp and"), Err(FilterError::UnexpectedEnd));
+        assert_eq!(
+            Filter::parse("src tcp"),
+            Err(FilterError::UnexpectedToken("tcp".into()))
+        );
+        assert_eq!(
+            Filter::parse("host 10.0.0.256"),
+            Err(FilterError::InvalidValue("10.0.0.256".into()))
+        );
+        assert_eq!(
+            Filter::parse("portrange 20-10"),
+            Err(FilterError::InvalidValue("20-10".into()))
+        );
+        assert_eq!(
+            Filter::parse("port 80 $"),
+            Err(FilterError::UnexpectedCharacter('$'))
+        );
+        assert_eq!(Filter::parse("(tcp"), Err(FilterError::UnexpectedEnd));
+    }
+}
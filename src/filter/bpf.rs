@@ -0,0 +1,892 @@
+// Compilation of filter expressions to classic BPF, for the kernel to
+// drop unwanted frames before they reach a raw socket.
+//
+// Primitives read the outermost headers at fixed offsets, as tcpdump
+// does, which is what the userspace evaluator sees except on VLAN-tagged
+// Ethernet frames: their tags are not skipped, but Linux usually strips
+// them before socket filters run. IPv6 extension headers are not skipped
+// either, so a `tcp` test misses segments after a Hop-by-Hop header.
+
+use std::fmt;
+
+use super::{
+    Direction, ETHERTYPE_IPV4, ETHERTYPE_IPV6, Filter, PROTOCOL_TCP, PROTOCOL_UDP, Primitive,
+};
+use crate::pcap::{LINKTYPE_ETHERNET, LINKTYPE_IPV4, LINKTYPE_IPV6, LINKTYPE_NULL, LINKTYPE_RAW};
+use crate::util::IpCidr;
+
+// Instruction classes, sizes, modes and operations of classic BPF.
+const BPF_LD: u16 = 0x00;
+const BPF_LDX: u16 = 0x01;
+const BPF_ALU: u16 = 0x04;
+const BPF_JMP: u16 = 0x05;
+const BPF_RET: u16 = 0x06;
+const BPF_W: u16 = 0x00;
+const BPF_H: u16 = 0x08;
+const BPF_B: u16 = 0x10;
+const BPF_ABS: u16 = 0x20;
+const BPF_IND: u16 = 0x40;
+const BPF_MSH: u16 = 0xa0;
+const BPF_AND: u16 = 0x50;
+const BPF_JA: u16 = 0x00;
+const BPF_JEQ: u16 = 0x10;
+const BPF_JGT: u16 = 0x20;
+const BPF_JGE: u16 = 0x30;
+const BPF_JSET: u16 = 0x40;
+
+/// Longest program the kernel accepts (BPF_MAXINSNS).
+pub const MAX_INSTRUCTIONS: usize = 4096;
+
+/// Bytes of the frames accepted that are passed on, as tcpdump asks.
+const ACCEPT_LEN: u32 = 262144;
+
+/// Offset of the fragment offset field in the IPv4 header, and its mask.
+const IPV4_FRAGMENT: (u32, u32) = (6, 0x1fff);
+
+/// One classic BPF instruction, laid out as the kernel's `struct
+/// sock_filter`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SockFilter {
+    pub code: u16,
+    /// Instructions skipped when a conditional jump is taken.
+    pub jt: u8,
+    /// Instructions skipped when it is not.
+    pub jf: u8,
+    pub k: u32,
+}
+
+impl SockFilter {
+    /// Constructor to create an instruction.
+    pub fn new(code: u16, jt: u8, jf: u8, k: u32) -> Self {
+        SockFilter { code, jt, jf, k }
+    }
+}
+
+/// Error returned for a filter `compile_bpf` cannot compile. The filter
+/// can still be evaluated in userspace with `Filter::matches_frame`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BpfError {
+    /// A primitive with no compiled form, e.g. `vlan`.
+    Unsupported(Primitive),
+    /// A link type other than Ethernet and raw IP.
+    LinkType(u32),
+    /// The program is longer than the kernel accepts.
+    TooLong(usize),
+}
+
+impl fmt::Display for BpfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BpfError::Unsupported(primitive) => {
+                write!(f, "`{primitive}` cannot be compiled to BPF")
+            }
+            BpfError::LinkType(link_type) => {
+                write!(f, "link type {link_type} cannot be filtered with BPF")
+            }
+            BpfError::TooLong(len) => write!(
+                f,
+                "BPF program of {len} instructions, more than {MAX_INSTRUCTIONS}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BpfError {}
+
+impl Filter {
+    /// Compiles the filter to a classic BPF program for frames of
+    /// `link_type`, Ethernet, raw IP or BSD loopback. The program returns 0 for the
+    /// frames the filter rejects.
+    pub fn compile_bpf(&self, link_type: u32) -> Result<Vec<SockFilter>, BpfError> {
+        let link = match link_type {
+            LINKTYPE_ETHERNET => Link::Ethernet,
+            LINKTYPE_RAW | LINKTYPE_IPV4 | LINKTYPE_IPV6 => Link::Ip,
+            LINKTYPE_NULL => Link::Null,
+            _ => return Err(BpfError::LinkType(link_type)),
+        };
+        let condition = lower(self, link, Known::default())?;
+        let mut program = Program::default();
+        let reject = program.push(SockFilter::new(BPF_RET, 0, 0, 0));
+        if let Condition::False = condition {
+            return Ok(program.instructions);
+        }
+        let accept = program.push(SockFilter::new(BPF_RET, 0, 0, ACCEPT_LEN));
+        if let Condition::True = condition {
+            return Ok(vec![program.instructions[accept]]);
+        }
+        program.condition(&condition, accept, reject);
+        let mut instructions = program.instructions;
+        instructions.reverse();
+        if instructions.len() > MAX_INSTRUCTIONS {
+            return Err(BpfError::TooLong(instructions.len()));
+        }
+        Ok(instructions)
+    }
+}
+
+/// Writes `program` as `tcpdump -d` does, one instruction per line.
+pub fn disassemble(program: &[SockFilter]) -> String {
+    let mut text = String::new();
+    for (index, insn) in program.iter().enumerate() {
+        let k = insn.k;
+        let (op, operand) = match insn.code {
+            0x20 => ("ld", format!("[{k}]")),
+            0x28 => ("ldh", format!("[{k}]")),
+            0x30 => ("ldb", format!("[{k}]")),
+            0x40 => ("ld", format!("[x + {k}]")),
+            0x48 => ("ldh", format!("[x + {k}]")),
+            0x50 => ("ldb", format!("[x + {k}]")),
+            0xb1 => ("ldxb", format!("4*([{k}]&0xf)")),
+            0x54 => ("and", format!("#{k:#x}")),
+            0x06 => ("ret", format!("#{k}")),
+            0x05 => ("ja", (index + 1 + k as usize).to_string()),
+            code @ (0x15 | 0x25 | 0x35 | 0x45) => {
+                let op = match code {
+                    0x15 => "jeq",
+                    0x25 => "jgt",
+                    0x35 => "jge",
+                    _ => "jset",
+                };
+                let (jt, jf) = (index + 1 + insn.jt as usize, index + 1 + insn.jf as usize);
+                text += &format!(
+                    "({index:03}) {op:<8} {:<16} jt {jt}\tjf {jf}\n",
+                    format!("#{k:#x}")
+                );
+                continue;
+            }
+            code => ("unimp", format!("{code:#x}")),
+        };
+        text += &format!("({index:03}) {op:<8} {operand}\n");
+    }
+    text
+}
+
+// --- LOWERING ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Link {
+    /// Ethernet header, IP header after it.
+    Ethernet,
+    /// IP header first.
+    Ip,
+    /// 4-byte address family, IP header after it. The family is in host
+    /// byte order; the IP version is tested instead.
+    Null,
+}
+
+impl Link {
+    fn network_offset(self) -> u32 {
+        match self {
+            Link::Ethernet => 14,
+            Link::Ip => 0,
+            Link::Null => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Family {
+    V4,
+    V6,
+}
+
+/// What earlier tests on the path to a primitive established, so that
+/// `ip and tcp and port 80` tests the EtherType and protocol once.
+#[derive(Debug, Clone, Copy, Default)]
+struct Known {
+    family: Option<Family>,
+    protocol: Option<u8>,
+}
+
+impl Known {
+    /// What matching `filter` establishes, on top of `self`.
+    fn and(self, filter: &Filter) -> Known {
+        let mut known = self;
+        match filter {
+            Filter::Primitive(primitive) => match primitive {
+                Primitive::Ipv4 | Primitive::EtherType(ETHERTYPE_IPV4) => {
+                    known.family = Some(Family::V4)
+                }
+                Primitive::Ipv6 | Primitive::EtherType(ETHERTYPE_IPV6) => {
+                    known.family = Some(Family::V6)
+                }
+                Primitive::Net {
+                    cidr: IpCidr::V4(_),
+                    ..
+                } => known.family = Some(Family::V4),
+                Primitive::Net {
+                    cidr: IpCidr::V6(_),
+                    ..
+                } => known.family = Some(Family::V6),
+                Primitive::Protocol(protocol) => known.protocol = Some(*protocol),
+                Primitive::TcpFlags { .. } => known.protocol = Some(PROTOCOL_TCP),
+                _ => {}
+            },
+            Filter::And(a, b) => return known.and(a).and(b),
+            Filter::Not(_) | Filter::Or(..) => {}
+        }
+        known
+    }
+}
+
+/// Loads into the accumulator.
+#[derive(Debug, Clone, Copy)]
+enum Load {
+    /// Byte, half-word or word at a fixed offset.
+    Absolute(u16, u32),
+    /// Same, at an offset from the end of the IPv4 header at the given
+    /// offset, loaded into X first.
+    AfterIpv4(u16, u32, u32),
+}
+
+/// Boolean program before code generation.
+#[derive(Debug, Clone)]
+enum Condition {
+    True,
+    False,
+    /// Jumps with operation `op` and operand `k` on the loaded value,
+    /// masked if `mask` is given.
+    Test {
+        load: Load,
+        mask: Option<u32>,
+        op: u16,
+        k: u32,
+    },
+    /// The loaded value is within `first..=last`.
+    Range {
+        load: Load,
+        first: u32,
+        last: u32,
+    },
+    Not(Box<Condition>),
+    And(Vec<Condition>),
+    Or(Vec<Condition>),
+}
+
+impl Condition {
+    fn test(load: Load, op: u16, k: u32) -> Condition {
+        Condition::Test {
+            load,
+            mask: None,
+            op,
+            k,
+        }
+    }
+
+    fn not(self) -> Condition {
+        match self {
+            Condition::True => Condition::False,
+            Condition::False => Condition::True,
+            Condition::Not(condition) => *condition,
+            condition => Condition::Not(Box::new(condition)),
+        }
+    }
+
+    /// Conjunction, simplified when an operand is constant.
+    fn and(conditions: impl IntoIterator<Item = Condition>) -> Condition {
+        let mut all = Vec::new();
+        for condition in conditions {
+            match condition {
+                Condition::True => {}
+                Condition::False => return Condition::False,
+                condition => all.push(condition),
+            }
+        }
+        match all.len() {
+            0 => Condition::True,
+            1 => all.pop().unwrap(),
+            _ => Condition::And(all),
+        }
+    }
+
+    /// Disjunction, simplified when an operand is constant.
+    fn or(conditions: impl IntoIterator<Item = Condition>) -> Condition {
+        let mut any = Vec::new();
+        for condition in conditions {
+            match condition {
+                Condition::True => return Condition::True,
+                Condition::False => {}
+                condition => any.push(condition),
+            }
+        }
+        match any.len() {
+            0 => Condition::False,
+            1 => any.pop().unwrap(),
+            _ => Condition::Or(any),
+        }
+    }
+}
+
+fn lower(filter: &Filter, link: Link, known: Known) -> Result<Condition, BpfError> {
+    Ok(match filter {
+        Filter::Primitive(primitive) => lower_primitive(primitive, link, known)?,
+        Filter::Not(filter) => lower(filter, link, known)?.not(),
+        Filter::And(a, b) => {
+            Condition::and([lower(a, link, known)?, lower(b, link, known.and(a))?])
+        }
+        Filter::Or(a, b) => Condition::or([lower(a, link, known)?, lower(b, link, known)?]),
+    })
+}
+
+/// The packet is of `family`.
+fn is_family(family: Family, link: Link, known: Known) -> Condition {
+    match known.family {
+        Some(known) => match known == family {
+            true => Condition::True,
+            false => Condition::False,
+        },
+        None => match link {
+            Link::Ethernet => {
+                let ethertype = match family {
+                    Family::V4 => ETHERTYPE_IPV4,
+                    Family::V6 => ETHERTYPE_IPV6,
+                };
+                Condition::test(Load::Absolute(BPF_H, 12), BPF_JEQ, ethertype.into())
+            }
+            Link::Ip | Link::Null => Condition::Test {
+                load: Load::Absolute(BPF_B, link.network_offset()),
+                mask: Some(0xf0),
+                op: BPF_JEQ,
+                k: match family {
+                    Family::V4 => 0x40,
+                    Family::V6 => 0x60,
+                },
+            },
+        },
+    }
+}
+
+/// The packet of `family` carries one of `protocols`.
+fn is_protocol(family: Family, protocols: &[u8], link: Link, known: Known) -> Condition {
+    if let Some(protocol) = known.protocol {
+        return match protocols.contains(&protocol) {
+            true => Condition::True,
+            false => Condition::False,
+        };
+    }
+    let offset = link.network_offset()
+        + match family {
+            Family::V4 => 9,
+            Family::V6 => 6,
+        };
+    Condition::or(protocols.iter().map(|protocol| {
+        Condition::test(Load::Absolute(BPF_B, offset), BPF_JEQ, (*protocol).into())
+    }))
+}
+
+/// Loads of the header after the IP header, at `offset` in it.
+fn transport_load(family: Family, size: u16, offset: u32, link: Link) -> Load {
+    let network = link.network_offset();
+    match family {
+        Family::V4 => Load::AfterIpv4(size, network, offset),
+        Family::V6 => Load::Absolute(size, network + 40 + offset),
+    }
+}
+
+/// The packet of `family` is not a later fragment, which has no
+/// transport header.
+fn is_first_fragment(family: Family, link: Link) -> Condition {
+    match family {
+        Family::V4 => {
+            let (offset, mask) = IPV4_FRAGMENT;
+            let load = Load::Absolute(BPF_H, link.network_offset() + offset);
+            Condition::test(load, BPF_JSET, mask).not()
+        }
+        Family::V6 => Condition::True,
+    }
+}
+
+fn either(direction: Direction, test: impl Fn(bool) -> Condition) -> Condition {
+    match direction {
+        Direction::Source => test(true),
+        Direction::Destination => test(false),
+        Direction::Either => Condition::or([test(true), test(false)]),
+    }
+}
+
+fn lower_primitive(primitive: &Primitive, link: Link, known: Known) -> Result<Condition, BpfError> {
+    let families = [Family::V4, Family::V6];
+    let network = link.network_offset();
+    Ok(match *primitive {
+        Primitive::Ipv4 => is_family(Family::V4, link, known),
+        Primitive::Ipv6 => is_family(Family::V6, link, known),
+        Primitive::EtherType(ETHERTYPE_IPV4) => is_family(Family::V4, link, known),
+        Primitive::EtherType(ETHERTYPE_IPV6) => is_family(Family::V6, link, known),
+        Primitive::EtherType(ethertype) => match (link, known.family) {
+            (Link::Ethernet, None) => {
+                Condition::test(Load::Absolute(BPF_H, 12), BPF_JEQ, ethertype.into())
+            }
+            _ => Condition::False,
+        },
+        Primitive::Protocol(protocol) => Condition::or(families.map(|family| {
+            Condition::and([
+                is_family(family, link, known),
+                is_protocol(family, &[protocol], link, known),
+            ])
+        })),
+        Primitive::Net { direction, cidr } => {
+            let (family, words) = match cidr {
+                IpCidr::V4(cidr) => (
+                    Family::V4,
+                    vec![(12, 16, u32::from(cidr.network()), u32::from(cidr.mask()))],
+                ),
+                IpCidr::V6(cidr) => {
+                    let network = u128::from(cidr.network());
+                    let mask = u128::from(cidr.mask());
+                    let words = (0..4)
+                        .map(|i| {
+                            let shift = 96 - 32 * i;
+                            let offset = 8 + 4 * i as u32;
+                            (
+                                offset,
+                                offset + 16,
+                                (network >> shift) as u32,
+                                (mask >> shift) as u32,
+                            )
+                        })
+                        .filter(|(.., mask)| *mask != 0)
+                        .collect();
+                    (Family::V6, words)
+                }
+            };
+            let address = |source: bool| {
+                Condition::and(words.iter().map(|&(src, dst, network_word, mask)| {
+                    let offset = network + if source { src } else { dst };
+                    Condition::Test {
+                        load: Load::Absolute(BPF_W, offset),
+                        mask: (mask != u32::MAX).then_some(mask),
+                        op: BPF_JEQ,
+                        k: network_word,
+                    }
+                }))
+            };
+            Condition::and([is_family(family, link, known), either(direction, address)])
+        }
+        Primitive::Port {
+            direction,
+            first,
+            last,
+        } => Condition::or(families.map(|family| {
+            let port = |source: bool| Condition::Range {
+                load: transport_load(family, BPF_H, if source { 0 } else { 2 }, link),
+                first: first.into(),
+                last: last.into(),
+            };
+            Condition::and([
+                is_family(family, link, known),
+                is_protocol(family, &[PROTOCOL_TCP, PROTOCOL_UDP], link, known),
+                is_first_fragment(family, link),
+                either(direction, port),
+            ])
+        })),
+        Primitive::TcpFlags {
+            mask,
+            value,
+            negated,
+        } => Condition::or(families.map(|family| {
+            let load = transport_load(family, BPF_B, 13, link);
+            let flags = match value {
+                0 => Condition::test(load, BPF_JSET, mask.into()).not(),
+                _ => Condition::Test {
+                    load,
+                    mask: (mask != 0xff).then_some(mask.into()),
+                    op: BPF_JEQ,
+                    k: value.into(),
+                },
+            };
+            Condition::and([
+                is_family(family, link, known),
+                is_protocol(family, &[PROTOCOL_TCP], link, known),
+                is_first_fragment(family, link),
+                if negated { flags.not() } else { flags },
+            ])
+        })),
+        Primitive::Vlan(_) => return Err(BpfError::Unsupported(primitive.clone())),
+    })
+}
+
+// --- CODE GENERATION ---
+
+/// Program generated backwards, from the return instructions up, so that
+/// every jump target exists when the jump is generated. A label is the
+/// index of an instruction in `instructions`, counted from the end of
+/// the final program.
+#[derive(Debug, Default)]
+struct Program {
+    instructions: Vec<SockFilter>,
+}
+
+impl Program {
+    /// Prepends `insn` and returns its label.
+    fn push(&mut self, insn: SockFilter) -> usize {
+        self.instructions.push(insn);
+        self.instructions.len() - 1
+    }
+
+    /// Distance from the instruction prepended next to `target`.
+    fn distance(&self, target: usize) -> usize {
+        self.instructions.len() - target - 1
+    }
+
+    /// Prepends a conditional jump, preceded by unconditional jumps to
+    /// the targets too far for its 8-bit offsets.
+    fn jump(&mut self, op: u16, k: u32, mut on_true: usize, mut on_false: usize) {
+        loop {
+            let target = match (self.distance(on_true) > 255, self.distance(on_false) > 255) {
+                (true, _) => &mut on_true,
+                (false, true) => &mut on_false,
+                (false, false) => break,
+            };
+            let offset = self.distance(*target) as u32;
+            *target = self.push(SockFilter::new(BPF_JMP | BPF_JA, 0, 0, offset));
+        }
+        let (jt, jf) = (self.distance(on_true), self.distance(on_false));
+        self.push(SockFilter::new(BPF_JMP | op, jt as u8, jf as u8, k));
+    }
+
+    /// Prepends `load` and returns its label.
+    fn load(&mut self, load: Load) -> usize {
+        match load {
+            Load::Absolute(size, offset) => {
+                self.push(SockFilter::new(BPF_LD | size | BPF_ABS, 0, 0, offset))
+            }
+            Load::AfterIpv4(size, network, offset) => {
+                self.push(SockFilter::new(
+                    BPF_LD | size | BPF_IND,
+                    0,
+                    0,
+                    network + offset,
+                ));
+                self.push(SockFilter::new(BPF_LDX | BPF_B | BPF_MSH, 0, 0, network))
+            }
+        }
+    }
+
+    /// Prepends `condition`, continuing at `on_true` or `on_false`, and
+    /// returns the label of its first instruction.
+    fn condition(&mut self, condition: &Condition, on_true: usize, on_false: usize) -> usize {
+        match condition {
+            Condition::True => on_true,
+            Condition::False => on_false,
+            Condition::Test { load, mask, op, k } => {
+                self.jump(*op, *k, on_true, on_false);
+                if let Some(mask) = mask {
+                    self.push(SockFilter::new(BPF_ALU | BPF_AND, 0, 0, *mask));
+                }
+                self.load(*load)
+            }
+            Condition::Range { load, first, last } if first == last => {
+                self.jump(BPF_JEQ, *first, on_true, on_false);
+                self.load(*load)
+            }
+            Condition::Range { load, first, last } => {
+                // Both jumps test the value loaded once.
+                self.jump(BPF_JGT, *last, on_false, on_true);
+                let jgt = self.instructions.len() - 1;
+                self.jump(BPF_JGE, *first, jgt, on_false);
+                self.load(*load)
+            }
+            Condition::Not(condition) => self.condition(condition, on_false, on_true),
+            Condition::And(conditions) => {
+                conditions.iter().rev().fold(on_true, |next, condition| {
+                    self.condition(condition, next, on_false)
+                })
+            }
+            Condition::Or(conditions) => {
+                conditions.iter().rev().fold(on_false, |next, condition| {
+                    self.condition(condition, on_true, next)
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use super::*;
+    use crate::ethernet::{ETHERTYPE_ARP, Ethernet, MacAddr};
+    use crate::ipv4::{FLAG_MORE_FRAGMENTS, IPv4};
+    use crate::ipv6::IPv6;
+    use crate::tcp::{TCP, flags};
+    use crate::udp::UDP;
+
+    /// Runs `program` on `packet` and returns the number of bytes it
+    /// accepts, as the kernel does: a load out of the packet rejects it.
+    fn run(program: &[SockFilter], packet: &[u8]) -> u32 {
+        let (mut a, mut x, mut pc) = (0u32, 0u32, 0);
+        loop {
+            let insn = program[pc];
+            pc += 1;
+            let load = |offset: u32| {
+                let size = match insn.code & 0x18 {
+                    BPF_W => 4,
+                    BPF_H => 2,
+                    _ => 1,
+                };
+                let bytes = packet.get(offset as usize..offset as usize + size)?;
+                Some(
+                    bytes
+                        .iter()
+                        .fold(0, |value, byte| value << 8 | u32::from(*byte)),
+                )
+            };
+            match insn.code {
+                0x20 | 0x28 | 0x30 => match load(insn.k) {
+                    Some(value) => a = value,
+                    None => return 0,
+                },
+                0x40 | 0x48 | 0x50 => match load(x + insn.k) {
+                    Some(value) => a = value,
+                    None => return 0,
+                },
+                0xb1 => match packet.get(insn.k as usize) {
+                    Some(byte) => x = 4 * u32::from(byte & 0x0f),
+                    None => return 0,
+                },
+                0x54 => a &= insn.k,
+                0x06 => return insn.k,
+                0x05 => pc += insn.k as usize,
+                code => {
+                    let taken = match code {
+                        0x15 => a == insn.k,
+                        0x25 => a > insn.k,
+                        0x35 => a >= insn.k,
+                        0x45 => a & insn.k != 0,
+                        _ => panic!("unexpected instruction {insn:?}"),
+                    };
+                    pc += usize::from(if taken { insn.jt } else { insn.jf });
+                }
+            }
+        }
+    }
+
+    fn ipv4(protocol: u8, payload: Vec<u8>) -> IPv4 {
+        IPv4::with_payload(
+            Ipv4Addr::new(10, 0, 0, 1),
+            Ipv4Addr::new(192, 0, 2, 80),
+            protocol,
+            payload,
+        )
+    }
+
+    fn ipv6(protocol: u8, payload: Vec<u8>) -> Vec<u8> {
+        IPv6::with_payload(
+            "2001:db8::1".parse::<Ipv6Addr>().unwrap(),
+            "fd00::2".parse::<Ipv6Addr>().unwrap(),
+            protocol,
+            payload,
+        )
+        .to_bytes()
+    }
+
+    /// IP packets of both families, with and without a transport header.
+    fn packets() -> Vec<Vec<u8>> {
+        let syn = TCP::segment(40000, 80, 1, 0, flags::SYN).to_bytes();
+        let syn_ack = TCP::segment(443, 50000, 1, 2, flags::SYN | flags::ACK).to_bytes();
+        let dns = UDP::new(5000, 53, b"query".to_vec()).to_bytes();
+        vec![
+            ipv4(PROTOCOL_TCP, syn.clone()).with_checksum().to_bytes(),
+            // Options move the TCP header.
+            ipv4(PROTOCOL_TCP, syn_ack.clone())
+                .with_record_route(4)
+                .with_checksum()
+                .to_bytes(),
+            ipv4(PROTOCOL_UDP, dns.clone()).with_checksum().to_bytes(),
+            // A later fragment has no ports.
+            ipv4(PROTOCOL_TCP, syn.clone())
+                .set_flags(FLAG_MORE_FRAGMENTS)
+                .set_fragment_offset(8)
+                .with_checksum()
+                .to_bytes(),
+            ipv4(1, vec![8, 0, 0, 0, 0, 1, 0, 1])
+                .with_checksum()
+                .to_bytes(),
+            ipv6(PROTOCOL_TCP, syn_ack),
+            ipv6(PROTOCOL_UDP, dns),
+            ipv6(58, vec![128, 0, 0, 0, 0, 1, 0, 1]),
+        ]
+    }
+
+    fn ethernet(ethertype: u16, payload: Vec<u8>) -> Vec<u8> {
+        let source = MacAddr([2, 0, 0, 0, 0, 1]);
+        Ethernet::new(MacAddr::BROADCAST, source, ethertype, payload).to_bytes()
+    }
+
+    const EXPRESSIONS: &[&str] = &[
+        "ip",
+        "ip6",
+        "arp",
+        "ether proto 0x88cc",
+        "tcp",
+        "udp",
+        "icmp",
+        "icmp6",
+        "ip proto 17",
+        "ip6 proto 6",
+        "host 10.0.0.1",
+        "src net 10.0.0.0/8",
+        "dst net 192.0.2.0/25",
+        "dst host fd00::2",
+        "net 2001:db8::/32",
+        "src net 2001:db8:8000::/33",
+        "port 80",
+        "src port 5000",
+        "dst port 50000",
+        "portrange 50-60",
+        "portrange 444-50000",
+        "tcp[tcpflags] & tcp-syn != 0",
+        "tcp[tcpflags] & (tcp-syn|tcp-ack) == tcp-syn",
+        "tcp[tcpflags] & tcp-rst == 0",
+        "tcp[13] != 0x12",
+        "not tcp and not udp",
+        "ip and tcp and dst port 80",
+        "(udp or tcp) and not port 53",
+        "ip6 and (src port 443 or dst port 53)",
+        "ip and ip6",
+        "not (ip and port 80) and (arp or ip6 or src host 10.0.0.1)",
+    ];
+
+    #[test]
+    fn programs_agree_with_the_evaluator() {
+        let raw = packets();
+        let mut frames: Vec<_> = raw
+            .iter()
+            .map(|packet| {
+                let ethertype = match packet[0] >> 4 {
+                    4 => ETHERTYPE_IPV4,
+                    _ => ETHERTYPE_IPV6,
+                };
+                ethernet(ethertype, packet.clone())
+            })
+            .collect();
+        frames.push(ethernet(ETHERTYPE_ARP, vec![0; 28]));
+        frames.push(ethernet(0x88cc, vec![0; 32]));
+        // BSD loopback, with the address families of macOS.
+        let null: Vec<_> = raw
+            .iter()
+            .map(|packet| {
+                let family: u32 = if packet[0] >> 4 == 4 { 2 } else { 30 };
+                [&family.to_ne_bytes()[..], packet].concat()
+            })
+            .collect();
+
+        for expression in EXPRESSIONS {
+            let filter = Filter::parse(expression).unwrap();
+            let links = [
+                (LINKTYPE_ETHERNET, &frames),
+                (LINKTYPE_RAW, &raw),
+                (LINKTYPE_NULL, &null),
+            ];
+            for (link_type, packets) in links {
+                let program = filter.compile_bpf(link_type).unwrap();
+                for (index, packet) in packets.iter().enumerate() {
+                    assert_eq!(
+                        run(&program, packet) != 0,
+                        filter.matches_frame(link_type, packet),
+                        "`{expression}` on packet {index} of link type {link_type}:\n{}",
+                        disassemble(&program)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn programs_match_tcpdump() {
+        // As printed by `tcpdump -d` with an Ethernet interface.
+        let cases = [
+            (
+                "ip",
+                "(000) ldh      [12]
+(001) jeq      #0x800           jt 2\tjf 3
+(002) ret      #262144
+(003) ret      #0
+",
+            ),
+            (
+                "arp",
+                "(000) ldh      [12]
+(001) jeq      #0x806           jt 2\tjf 3
+(002) ret      #262144
+(003) ret      #0
+",
+            ),
+            (
+                "ip proto 17",
+                "(000) ldh      [12]
+(001) jeq      #0x800           jt 2\tjf 5
+(002) ldb      [23]
+(003) jeq      #0x11            jt 4\tjf 5
+(004) ret      #262144
+(005) ret      #0
+",
+            ),
+            (
+                "ip and src net 10.0.0.0/8",
+                "(000) ldh      [12]
+(001) jeq      #0x800           jt 2\tjf 6
+(002) ld       [26]
+(003) and      #0xff000000
+(004) jeq      #0xa000000       jt 5\tjf 6
+(005) ret      #262144
+(006) ret      #0
+",
+            ),
+            (
+                "ip and tcp and dst port 80",
+                "(000) ldh      [12]
+(001) jeq      #0x800           jt 2\tjf 10
+(002) ldb      [23]
+(003) jeq      #0x6             jt 4\tjf 10
+(004) ldh      [20]
+(005) jset     #0x1fff          jt 10\tjf 6
+(006) ldxb     4*([14]&0xf)
+(007) ldh      [x + 16]
+(008) jeq      #0x50            jt 9\tjf 10
+(009) ret      #262144
+(010) ret      #0
+",
+            ),
+        ];
+        for (expression, expected) in cases {
+            let program = Filter::parse(expression)
+                .unwrap()
+                .compile_bpf(LINKTYPE_ETHERNET)
+                .unwrap();
+            assert_eq!(disassemble(&program), expected, "`{expression}`");
+        }
+    }
+
+    #[test]
+    fn far_targets_go_through_unconditional_jumps() {
+        // Each host test is two instructions, so the last ones are more
+        // than 255 instructions away from the return.
+        let expression = (1..=200)
+            .map(|host| format!("host 10.0.{}.{}", host / 250, host % 250))
+            .collect::<Vec<_>>()
+            .join(" or ");
+        let filter = Filter::parse(&format!("tcp and ({expression})")).unwrap();
+        let program = filter.compile_bpf(LINKTYPE_RAW).unwrap();
+        assert!(program.iter().any(|insn| insn.code == BPF_JMP | BPF_JA));
+        for packet in packets() {
+            assert_eq!(
+                run(&program, &packet) != 0,
+                filter.matches_frame(LINKTYPE_RAW, &packet)
+            );
+        }
+    }
+
+    #[test]
+    fn uncompilable_filters() {
+        let vlan = Filter::parse("tcp or vlan 100").unwrap();
+        assert_eq!(
+            vlan.compile_bpf(LINKTYPE_ETHERNET),
+            Err(BpfError::Unsupported(Primitive::Vlan(Some(100))))
+        );
+        let tcp = Filter::parse("tcp").unwrap();
+        assert_eq!(tcp.compile_bpf(113), Err(BpfError::LinkType(113)));
+    }
+}
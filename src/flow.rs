@@ -0,0 +1,248 @@
+use std::net::{IpAddr, Ipv6Addr};
+
+use crate::pcap;
+use crate::util::{
+    IpAddrPair, MixedAddressFamilies, ParseError, PseudoHeader, ensure_len, read_ipv4, read_ipv6,
+};
+
+// EtherTypes walked when extracting a flow key from a frame.
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const ETHERTYPE_QINQ: u16 = 0x88a8;
+
+// IP protocol numbers whose first 4 bytes are source and destination ports.
+const PROTOCOL_TCP: u8 = 6;
+const PROTOCOL_UDP: u8 = 17;
+const PROTOCOL_SCTP: u8 = 132;
+
+/// Identifies a transport flow. Ports are zero for protocols without ports
+/// and for non-initial IPv4 fragments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FiveTuple {
+    pub protocol: u8,
+    pub source: IpAddr,
+    pub source_port: u16,
+    pub destination: IpAddr,
+    pub destination_port: u16,
+}
+
+impl FiveTuple {
+    /// Constructor to create a new five-tuple. Taking the addresses as a
+    /// pair keeps keys of mixed families from being built by mistake.
+    pub fn new(
+        protocol: u8,
+        addresses: IpAddrPair,
+        source_port: u16,
+        destination_port: u16,
+    ) -> Self {
+        FiveTuple {
+            protocol,
+            source: addresses.src,
+            source_port,
+            destination: addresses.dst,
+            destination_port,
+        }
+    }
+
+    /// Returns the addresses of the key. Fails for a key built from
+    /// addresses of different families.
+    pub fn addresses(&self) -> Result<IpAddrPair, MixedAddressFamilies> {
+        IpAddrPair::new(self.source, self.destination)
+    }
+
+    /// Returns the tuple seen from the other direction.
+    pub fn reversed(&self) -> Self {
+        FiveTuple {
+            protocol: self.protocol,
+            source: self.destination,
+            source_port: self.destination_port,
+            destination: self.source,
+            destination_port: self.source_port,
+        }
+    }
+
+    /// Returns the same key for both directions of a flow, with the lower
+    /// (address, port) endpoint as source.
+    pub fn normalized(&self) -> Self {
+        if (self.source, self.source_port) <= (self.destination, self.destination_port) {
+            *self
+        } else {
+            self.reversed()
+        }
+    }
+
+    /// Extracts the flow key of a captured frame, given the capture link
+    /// type. Returns `None` for frames that do not carry IPv4 or IPv6.
+    pub fn from_frame(link_type: u32, frame: &[u8]) -> Option<Self> {
+        let ip = match link_type {
+            pcap::LINKTYPE_ETHERNET => ethernet_payload(frame)?,
+            pcap::LINKTYPE_RAW | pcap::LINKTYPE_IPV4 | pcap::LINKTYPE_IPV6 => frame,
+            pcap::LINKTYPE_NULL => frame.get(4..)?,
+            _ => return None,
+        };
+        FiveTuple::from_ip(ip)
+    }
+
+    /// Extracts the flow key of an IPv4 or IPv6 packet.
+    pub fn from_ip(packet: &[u8]) -> Option<Self> {
+        match packet.first()? >> 4 {
+            4 => from_ipv4(packet),
+            6 => from_ipv6(packet),
+            _ => None,
+        }
+    }
+
+    /// Appends a compact encoding of the key to `bytes`.
+    pub fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        bytes.push(self.protocol);
+        match self.addresses().and_then(|pair| pair.pseudo_header()) {
+            Ok(PseudoHeader::V4 {
+                source,
+                destination,
+            }) => {
+                bytes.push(4);
+                bytes.extend_from_slice(&source.octets());
+                bytes.extend_from_slice(&destination.octets());
+            }
+            Ok(PseudoHeader::V6 {
+                source,
+                destination,
+            }) => {
+                bytes.push(6);
+                bytes.extend_from_slice(&source.octets());
+                bytes.extend_from_slice(&destination.octets());
+            }
+            // Only reachable through the public fields; both addresses are
+            // written IPv4-mapped.
+            Err(MixedAddressFamilies { src, dst }) => {
+                bytes.push(6);
+                bytes.extend_from_slice(&to_ipv6(src).octets());
+                bytes.extend_from_slice(&to_ipv6(dst).octets());
+            }
+        }
+        bytes.extend_from_slice(&self.source_port.to_be_bytes());
+        bytes.extend_from_slice(&self.destination_port.to_be_bytes());
+    }
+
+    /// Decodes a key written by `serialize_into`, returning it with the
+    /// number of bytes consumed.
+    pub fn from_bytes(buf: &[u8]) -> Result<(Self, usize), ParseError> {
+        ensure_len(buf, 2)?;
+        let (addresses, offset): (IpAddrPair, usize) = match buf[1] {
+            4 => {
+                ensure_len(buf, 14)?;
+                ((read_ipv4(buf, 2), read_ipv4(buf, 6)).into(), 10)
+            }
+            6 => {
+                ensure_len(buf, 38)?;
+                ((read_ipv6(buf, 2), read_ipv6(buf, 18)).into(), 34)
+            }
+            _ => return Err(ParseError::InvalidField("address family")),
+        };
+        let key = FiveTuple::new(
+            buf[0],
+            addresses,
+            u16::from_be_bytes([buf[offset], buf[offset + 1]]),
+            u16::from_be_bytes([buf[offset + 2], buf[offset + 3]]),
+        );
+        Ok((key, offset + 4))
+    }
+}
+
+fn to_ipv6(address: IpAddr) -> Ipv6Addr {
+    match address {
+        IpAddr::V4(address) => address.to_ipv6_mapped(),
+        IpAddr::V6(address) => address,
+    }
+}
+
+/// Skips the Ethernet header and any VLAN tags of an IP-carrying frame.
+pub(crate) fn ethernet_payload(frame: &[u8]) -> Option<&[u8]> {
+    let mut offset = 12;
+    loop {
+        let ethertype = u16::from_be_bytes([*frame.get(offset)?, *frame.get(offset + 1)?]);
+        match ethertype {
+            ETHERTYPE_VLAN | ETHERTYPE_QINQ => offset += 4,
+            ETHERTYPE_IPV4 | ETHERTYPE_IPV6 => return frame.get(offset + 2..),
+            _ => return None,
+        }
+    }
+}
+
+/// Reads the source and destination ports at the start of `transport`.
+fn ports(protocol: u8, transport: &[u8]) -> (u16, u16) {
+    match (protocol, transport) {
+        (PROTOCOL_TCP | PROTOCOL_UDP | PROTOCOL_SCTP, [a, b, c, d, ..]) => {
+            (u16::from_be_bytes([*a, *b]), u16::from_be_bytes([*c, *d]))
+        }
+        _ => (0, 0),
+    }
+}
+
+fn from_ipv4(packet: &[u8]) -> Option<FiveTuple> {
+    if packet.len() < 20 {
+        return None;
+    }
+    let header_len = (packet[0] & 0x0f) as usize * 4;
+    let protocol = packet[9];
+    let fragment_offset = u16::from_be_bytes([packet[6], packet[7]]) & 0x1fff;
+    let (source_port, destination_port) = match packet.get(header_len..) {
+        Some(transport) if fragment_offset == 0 => ports(protocol, transport),
+        _ => (0, 0),
+    };
+    Some(FiveTuple::new(
+        protocol,
+        (read_ipv4(packet, 12), read_ipv4(packet, 16)).into(),
+        source_port,
+        destination_port,
+    ))
+}
+
+fn from_ipv6(packet: &[u8]) -> Option<FiveTuple> {
+    if packet.len() < 40 {
+        return None;
+    }
+    let (next_header, offset, fragmented) = ipv6_upper_layer(packet)?;
+    let (source_port, destination_port) = match packet.get(offset..) {
+        Some(transport) if !fragmented => ports(next_header, transport),
+        _ => (0, 0),
+    };
+    Some(FiveTuple::new(
+        next_header,
+        (read_ipv6(packet, 8), read_ipv6(packet, 24)).into(),
+        source_port,
+        destination_port,
+    ))
+}
+
+/// Walks the extension headers of an IPv6 packet up to the upper-layer
+/// protocol. Returns the protocol, its offset and whether the packet is a
+/// non-initial fragment, or `None` if an extension header is truncated.
+pub(crate) fn ipv6_upper_layer(packet: &[u8]) -> Option<(u8, usize, bool)> {
+    let mut next_header = *packet.get(6)?;
+    let mut offset = 40;
+    let mut fragmented = false;
+    loop {
+        match next_header {
+            0 | 43 | 60 => {
+                let length = (*packet.get(offset + 1)? as usize + 1) * 8;
+                next_header = *packet.get(offset)?;
+                offset += length;
+            }
+            44 => {
+                let fragment_offset =
+                    u16::from_be_bytes([*packet.get(offset + 2)?, *packet.get(offset + 3)?]) >> 3;
+                fragmented = fragment_offset != 0;
+                next_header = *packet.get(offset)?;
+                offset += 8;
+            }
+            51 => {
+                let length = (*packet.get(offset + 1)? as usize + 2) * 4;
+                next_header = *packet.get(offset)?;
+                offset += length;
+            }
+            _ => return Some((next_header, offset, fragmented)),
+        }
+    }
+}
@@ -0,0 +1,415 @@
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+
+use crate::ipv4::{FLAG_MORE_FRAGMENTS, IPv4};
+use crate::ipv6::IPv6;
+use crate::ipv6::ext::{ExtensionHeader, FRAGMENT_HEADER_LEN};
+use crate::limits::{Limit, Limits};
+use crate::util::ParseError;
+
+// IP fragment reassembly (RFC 791 section 3.2, RFC 8200 section 4.5).
+// Fragments of a datagram share its source, destination, protocol and
+// identification, and carry their offset in 8-byte units; the last one
+// clears More Fragments, which gives the length of the datagram. The
+// header of the first fragment becomes the header of the datagram; for
+// IPv6 that includes the extension headers before the Fragment header.
+
+/// Largest datagram a set of fragments can describe.
+const MAX_DATAGRAM_LEN: usize = u16::MAX as usize;
+
+/// Identifies the datagram a fragment belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FragmentKey {
+    pub source: IpAddr,
+    pub destination: IpAddr,
+    /// Protocol field of IPv4, next header of the Fragment header for
+    /// IPv6.
+    pub protocol: u8,
+    pub identification: u32,
+}
+
+/// Header of the first fragment, with the payload it keeps in the
+/// datagram: nothing for IPv4, the unfragmentable extension headers for
+/// IPv6.
+#[derive(Debug, Clone)]
+enum FirstHeader {
+    Ipv4(IPv4),
+    Ipv6(IPv6),
+}
+
+#[derive(Debug, Default)]
+struct PendingDatagram {
+    /// Fragment data by offset in bytes.
+    fragments: BTreeMap<usize, Vec<u8>>,
+    /// Length of the data, once the last fragment arrived.
+    data_len: Option<usize>,
+    first: Option<FirstHeader>,
+    /// Bytes held in `fragments`.
+    bytes: usize,
+}
+
+impl PendingDatagram {
+    /// Data of the datagram, if the fragments cover it without a hole.
+    /// Overlapping bytes are taken from the fragment at the higher offset.
+    fn assemble(&self) -> Option<Vec<u8>> {
+        let data_len = self.data_len?;
+        let mut covered = 0;
+        for (&offset, data) in &self.fragments {
+            if offset > covered {
+                return None;
+            }
+            covered = covered.max(offset + data.len());
+        }
+        if covered < data_len {
+            return None;
+        }
+        let mut datagram = vec![0; data_len];
+        for (&offset, data) in &self.fragments {
+            let end = (offset + data.len()).min(data_len);
+            if offset < end {
+                datagram[offset..end].copy_from_slice(&data[..end - offset]);
+            }
+        }
+        Some(datagram)
+    }
+}
+
+/// Reassembles IPv4 and IPv6 datagrams from their fragments. Every
+/// datagram is bounded to `limits.max_fragments_per_datagram` fragments
+/// and the fragment data buffered across datagrams to
+/// `limits.max_reassembly_bytes`; a fragment crossing either limit drops
+/// its datagram and fails with `ParseError::LimitExceeded`.
+#[derive(Debug, Default)]
+pub struct FragmentReassembler {
+    pub limits: Limits,
+    pending: HashMap<FragmentKey, PendingDatagram>,
+    buffered: usize,
+}
+
+impl FragmentReassembler {
+    /// Constructor to create a reassembler with the default limits.
+    pub fn new() -> Self {
+        FragmentReassembler::with_limits(Limits::default())
+    }
+
+    /// Constructor to create a reassembler enforcing `limits`.
+    pub fn with_limits(limits: Limits) -> Self {
+        FragmentReassembler {
+            limits,
+            pending: HashMap::new(),
+            buffered: 0,
+        }
+    }
+
+    /// Number of datagrams waiting for fragments.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Bytes of fragment data buffered.
+    pub fn buffered_bytes(&self) -> usize {
+        self.buffered
+    }
+
+    /// Adds an IPv4 packet. Returns it unchanged if it is not a fragment,
+    /// the reassembled datagram if it completes one, `None` otherwise.
+    pub fn push_ipv4(&mut self, packet: IPv4) -> Result<Option<IPv4>, ParseError> {
+        let more_fragments = packet.flags & FLAG_MORE_FRAGMENTS != 0;
+        if packet.fragment_offset == 0 && !more_fragments {
+            return Ok(Some(packet));
+        }
+        let key = FragmentKey {
+            source: packet.source.into(),
+            destination: packet.destination.into(),
+            protocol: packet.protocol,
+            identification: packet.identification as u32,
+        };
+        let offset = packet.fragment_offset as usize * 8;
+        let mut packet = packet;
+        let data = std::mem::take(&mut packet.payload);
+        let first = (offset == 0).then_some(FirstHeader::Ipv4(packet));
+        let Some((first, data)) = self.insert(key, offset, more_fragments, data, first)? else {
+            return Ok(None);
+        };
+        let FirstHeader::Ipv4(mut header) = first else {
+            unreachable!("IPv4 and IPv6 fragments have distinct keys");
+        };
+        let total_length = header.header_len() + data.len();
+        if total_length > MAX_DATAGRAM_LEN {
+            return Err(ParseError::InvalidField("total length"));
+        }
+        header.flags &= !FLAG_MORE_FRAGMENTS;
+        header.fragment_offset = 0;
+        header.total_length = total_length as u16;
+        header.payload = data;
+        Ok(Some(header.with_checksum()))
+    }
+
+    /// Adds an IPv6 packet. Returns it unchanged if it has no Fragment
+    /// header, the reassembled datagram, without the Fragment header, if
+    /// it completes one, `None` otherwise. Extension headers are parsed
+    /// with the limits of the reassembler.
+    pub fn push_ipv6(&mut self, packet: IPv6) -> Result<Option<IPv6>, ParseError> {
+        let mut next_header = packet.next_header;
+        let mut offset = 0;
+        // Offset of the next header field naming the current header, `None`
+        // for the field of the IPv6 header.
+        let mut naming_field = None;
+        let mut headers = 0;
+        let fragment = loop {
+            if !ExtensionHeader::is_extension(next_header) {
+                return Ok(Some(packet));
+            }
+            headers += 1;
+            self.limits.check(Limit::OptionsPerLayer, headers)?;
+            let (header, next, len) = ExtensionHeader::from_bytes_with_limits(
+                next_header,
+                &packet.payload[offset..],
+                &self.limits,
+            )?;
+            if let ExtensionHeader::Fragment(fragment) = header {
+                break fragment;
+            }
+            naming_field = Some(offset);
+            next_header = next;
+            offset += len;
+        };
+        let protocol = packet.payload[offset];
+        let key = FragmentKey {
+            source: packet.source.into(),
+            destination: packet.destination.into(),
+            protocol,
+            identification: fragment.identification,
+        };
+        let data = packet.payload[offset + FRAGMENT_HEADER_LEN..].to_vec();
+        let fragment_offset = fragment.fragment_offset as usize * 8;
+        let first = (fragment_offset == 0).then(|| {
+            // The datagram keeps the headers before the Fragment header,
+            // the last one now naming the upper-layer protocol.
+            let mut header = packet;
+            header.payload.truncate(offset);
+            match naming_field {
+                Some(field) => header.payload[field] = protocol,
+                None => header.next_header = protocol,
+            }
+            FirstHeader::Ipv6(header)
+        });
+        let more_fragments = fragment.more_fragments;
+        let Some((first, data)) = self.insert(key, fragment_offset, more_fragments, data, first)?
+        else {
+            return Ok(None);
+        };
+        let FirstHeader::Ipv6(mut header) = first else {
+            unreachable!("IPv4 and IPv6 fragments have distinct keys");
+        };
+        header.payload.extend_from_slice(&data);
+        if header.payload.len() > MAX_DATAGRAM_LEN {
+            return Err(ParseError::InvalidField("payload length"));
+        }
+        header.payload_length = header.payload.len() as u16;
+        Ok(Some(header))
+    }
+
+    /// Drops the fragments received for `key`.
+    pub fn discard(&mut self, key: &FragmentKey) {
+        if let Some(pending) = self.pending.remove(key) {
+            self.buffered -= pending.bytes;
+        }
+    }
+
+    /// Stores a fragment, returning the first header and the data of the
+    /// datagram if it is complete.
+    fn insert(
+        &mut self,
+        key: FragmentKey,
+        offset: usize,
+        more_fragments: bool,
+        data: Vec<u8>,
+        first: Option<FirstHeader>,
+    ) -> Result<Option<(FirstHeader, Vec<u8>)>, ParseError> {
+        let end = offset + data.len();
+        if end > MAX_DATAGRAM_LEN {
+            self.discard(&key);
+            return Err(ParseError::InvalidField("fragment offset"));
+        }
+        let pending = self.pending.entry(key).or_default();
+        let replaced = pending.fragments.get(&offset).map(Vec::len);
+        let fragments = pending.fragments.len() + replaced.is_none() as usize;
+        let buffered = self.buffered - replaced.unwrap_or(0) + data.len();
+        let within_limits = self
+            .limits
+            .check(Limit::FragmentsPerDatagram, fragments)
+            .and_then(|()| self.limits.check(Limit::ReassemblyBytes, buffered));
+        if let Err(err) = within_limits {
+            self.discard(&key);
+            return Err(err.into());
+        }
+        pending.bytes = pending.bytes - replaced.unwrap_or(0) + data.len();
+        pending.fragments.insert(offset, data);
+        self.buffered = buffered;
+        if !more_fragments {
+            pending.data_len = Some(end);
+        }
+        if first.is_some() {
+            pending.first = first;
+        }
+        if pending.first.is_none() {
+            return Ok(None);
+        }
+        let Some(datagram) = pending.assemble() else {
+            return Ok(None);
+        };
+        let first = pending.first.take().expect("checked above");
+        self.discard(&key);
+        Ok(Some((first, datagram)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipv6::ext::FragmentHeader;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    fn datagram() -> IPv4 {
+        IPv4::with_payload(
+            Ipv4Addr::new(192, 0, 2, 1),
+            Ipv4Addr::new(198, 51, 100, 1),
+            17,
+            (0..100).collect(),
+        )
+        .set_flags(0)
+        .set_identification(7)
+        .with_checksum()
+    }
+
+    /// Splits the payload of `packet` into fragments of `size` bytes.
+    fn fragments_v4(packet: &IPv4, size: usize) -> Vec<IPv4> {
+        let chunks: Vec<&[u8]> = packet.payload.chunks(size).collect();
+        chunks
+            .iter()
+            .enumerate()
+            .map(|(i, chunk)| {
+                let more = if i + 1 < chunks.len() {
+                    FLAG_MORE_FRAGMENTS
+                } else {
+                    0
+                };
+                IPv4 {
+                    flags: more,
+                    fragment_offset: (i * size / 8) as u16,
+                    total_length: (packet.header_len() + chunk.len()) as u16,
+                    payload: chunk.to_vec(),
+                    ..packet.clone()
+                }
+                .with_checksum()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reassembles_ipv4_out_of_order() {
+        let original = datagram();
+        let mut reassembler = FragmentReassembler::new();
+        let mut fragments = fragments_v4(&original, 32);
+        fragments.reverse();
+        let last = fragments.pop().unwrap();
+        for fragment in fragments {
+            assert_eq!(reassembler.push_ipv4(fragment).unwrap(), None);
+        }
+        assert_eq!(reassembler.pending(), 1);
+        assert_eq!(reassembler.push_ipv4(last).unwrap(), Some(original));
+        assert_eq!(
+            (reassembler.pending(), reassembler.buffered_bytes()),
+            (0, 0)
+        );
+    }
+
+    #[test]
+    fn unfragmented_packets_pass_through() {
+        let mut reassembler = FragmentReassembler::new();
+        assert_eq!(reassembler.push_ipv4(datagram()).unwrap(), Some(datagram()));
+    }
+
+    #[test]
+    fn reassembles_ipv6_behind_a_hop_by_hop_header() {
+        let source = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+        let destination = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2);
+        let hop_by_hop = ExtensionHeader::HopByHop(Vec::new());
+        let payload: Vec<u8> = (0..100).collect();
+        let original = IPv6::with_payload(source, destination, 17, payload.clone())
+            .with_extension_headers(std::slice::from_ref(&hop_by_hop));
+        let fragment = |offset: usize, more_fragments: bool| {
+            let end = (offset + 48).min(payload.len());
+            let header = ExtensionHeader::Fragment(FragmentHeader {
+                fragment_offset: (offset / 8) as u16,
+                more_fragments,
+                identification: 0xdead_beef,
+            });
+            IPv6::with_payload(source, destination, 17, payload[offset..end].to_vec())
+                .with_extension_headers(&[hop_by_hop.clone(), header])
+        };
+        let mut reassembler = FragmentReassembler::new();
+        assert_eq!(reassembler.push_ipv6(fragment(96, false)).unwrap(), None);
+        assert_eq!(reassembler.push_ipv6(fragment(48, true)).unwrap(), None);
+        assert_eq!(
+            reassembler.push_ipv6(fragment(0, true)).unwrap(),
+            Some(original)
+        );
+    }
+
+    #[test]
+    fn fragment_flood_fails_fast() {
+        // A datagram claiming 64K fragments, all sharing one key.
+        let flood = (0..65536u32).map(|i| IPv4 {
+            flags: FLAG_MORE_FRAGMENTS,
+            fragment_offset: (i & 0x1fff) as u16,
+            payload: vec![0; 8],
+            ..datagram()
+        });
+        let mut reassembler = FragmentReassembler::new();
+        let mut accepted = 0;
+        let mut err = None;
+        for fragment in flood {
+            match reassembler.push_ipv4(fragment) {
+                Ok(_) => accepted += 1,
+                Err(e) => {
+                    err = Some(e);
+                    break;
+                }
+            }
+        }
+        assert_eq!(accepted, Limits::default().max_fragments_per_datagram);
+        assert_eq!(
+            err,
+            Some(ParseError::LimitExceeded(crate::limits::LimitExceeded {
+                limit: Limit::FragmentsPerDatagram,
+                max: 64
+            }))
+        );
+        // The datagram was dropped with its fragments.
+        assert_eq!(
+            (reassembler.pending(), reassembler.buffered_bytes()),
+            (0, 0)
+        );
+    }
+
+    #[test]
+    fn buffered_bytes_are_bounded_across_datagrams() {
+        let limits = Limits::default().set_max_reassembly_bytes(100);
+        let mut reassembler = FragmentReassembler::with_limits(limits);
+        let fragment = |identification: u16| IPv4 {
+            flags: FLAG_MORE_FRAGMENTS,
+            identification,
+            payload: vec![0; 40],
+            ..datagram()
+        };
+        reassembler.push_ipv4(fragment(1)).unwrap();
+        reassembler.push_ipv4(fragment(2)).unwrap();
+        assert!(matches!(
+            reassembler.push_ipv4(fragment(3)),
+            Err(ParseError::LimitExceeded(_))
+        ));
+        assert_eq!(reassembler.buffered_bytes(), 80);
+    }
+}
@@ -0,0 +1,314 @@
+use std::fmt;
+
+use crate::int::{self, IntStack};
+use crate::util::{ParseError, ensure_len};
+
+// GENEVE header (RFC 8926 section 3.4)
+//
+//  0                   1                   2                   3
+//  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |Ver|  Opt Len  |O|C|    Rsvd.  |          Protocol Type        |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |        Virtual Network Identifier (VNI)       |    Reserved   |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                                                               |
+// ~                    Variable-Length Options                    ~
+// |                                                               |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// Option (section 3.5)
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |          Option Class         |      Type     |R|R|R| Length  |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// ~                  Variable-Length Option Data                  ~
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// Opt Len and Length count 4-byte words, without the fixed headers. The
+// high bit of an option type marks it critical; C is set when any option
+// is.
+
+/// UDP port of GENEVE.
+pub const UDP_PORT: u16 = 6081;
+
+/// Length of the fixed header in bytes.
+pub const HEADER_LEN: usize = 8;
+
+/// Largest option data, in bytes.
+pub const MAX_OPTION_DATA_LEN: usize = 124;
+
+/// Largest total length of the options, in bytes (63 words of Opt Len).
+pub const MAX_OPTIONS_LEN: usize = 252;
+
+/// Critical bit of an option type.
+pub const OPTION_TYPE_CRITICAL: u8 = 0x80;
+
+// Protocol types.
+pub const PROTOCOL_ETHERNET: u16 = 0x6558;
+pub const PROTOCOL_IPV4: u16 = 0x0800;
+pub const PROTOCOL_IPV6: u16 = 0x86dd;
+
+/// Error returned when building a header whose options do not fit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeneveError {
+    /// The serialized options would take `len` bytes, more than Opt Len
+    /// can express.
+    OptionsTooLong { len: usize, max: usize },
+}
+
+impl fmt::Display for GeneveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GeneveError::OptionsTooLong { len, max } => {
+                write!(
+                    f,
+                    "{len} bytes of GENEVE options exceed the {max}-byte maximum"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for GeneveError {}
+
+/// A GENEVE option.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GeneveOption {
+    pub option_class: u16,
+    /// Type, the high bit being the critical bit.
+    pub option_type: u8,
+    /// Data, a multiple of 4 bytes of at most `MAX_OPTION_DATA_LEN`.
+    pub data: Vec<u8>,
+}
+
+impl GeneveOption {
+    /// Constructor to create a new option.
+    pub fn new(option_class: u16, option_type: u8, data: Vec<u8>) -> Self {
+        GeneveOption {
+            option_class,
+            option_type,
+            data,
+        }
+    }
+
+    /// Returns true if a tunnel endpoint that does not know the option
+    /// must drop the packet.
+    pub fn is_critical(&self) -> bool {
+        self.option_type & OPTION_TYPE_CRITICAL != 0
+    }
+
+    /// Length of the serialized option in bytes.
+    pub fn wire_len(&self) -> usize {
+        4 + self.data.len().min(MAX_OPTION_DATA_LEN).div_ceil(4) * 4
+    }
+
+    // --- SERIALIZATION ---
+
+    /// Serializes the option, with the data padded to 4 bytes and cut off
+    /// at `MAX_OPTION_DATA_LEN`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let data = &self.data[..self.data.len().min(MAX_OPTION_DATA_LEN)];
+        let words = data.len().div_ceil(4);
+        let mut bytes = Vec::with_capacity(4 + words * 4);
+        bytes.extend_from_slice(&self.option_class.to_be_bytes());
+        bytes.push(self.option_type);
+        bytes.push(words as u8);
+        bytes.extend_from_slice(data);
+        bytes.resize(4 + words * 4, 0);
+        bytes
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> Result<(Self, usize), ParseError> {
+        ensure_len(buf, 4)?;
+        let len = 4 + (buf[3] & 0x1f) as usize * 4;
+        ensure_len(buf, len)?;
+        let option = GeneveOption {
+            option_class: u16::from_be_bytes([buf[0], buf[1]]),
+            option_type: buf[2],
+            data: buf[4..len].to_vec(),
+        };
+        Ok((option, len))
+    }
+}
+
+/// Header GENEVE, followed by the encapsulated frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Geneve {
+    pub version: u8,
+    /// O bit: control packet.
+    pub oam: bool,
+    pub protocol_type: u16,
+    /// Virtual network identifier (24 bits).
+    pub vni: u32,
+    pub options: Vec<GeneveOption>,
+    pub payload: Vec<u8>,
+}
+
+impl Geneve {
+    /// Builds a version 0 header carrying an Ethernet frame in `vni`.
+    pub fn new(vni: u32, payload: Vec<u8>) -> Self {
+        Geneve {
+            version: 0,
+            oam: false,
+            protocol_type: PROTOCOL_ETHERNET,
+            vni: vni & 0x00ff_ffff,
+            options: Vec::new(),
+            payload,
+        }
+    }
+
+    /// Appends `option`. The options must stay within `MAX_OPTIONS_LEN`
+    /// bytes for the header to serialize; `try_add_option` checks it here.
+    pub fn add_option(mut self, option: GeneveOption) -> Self {
+        self.options.push(option);
+        self
+    }
+
+    /// Appends `option`, failing if the serialized options would exceed
+    /// `MAX_OPTIONS_LEN` bytes.
+    pub fn try_add_option(self, option: GeneveOption) -> Result<Self, GeneveError> {
+        let len = self.options_len() + option.wire_len();
+        if len > MAX_OPTIONS_LEN {
+            return Err(GeneveError::OptionsTooLong {
+                len,
+                max: MAX_OPTIONS_LEN,
+            });
+        }
+        Ok(self.add_option(option))
+    }
+
+    /// Length of the serialized options in bytes.
+    pub fn options_len(&self) -> usize {
+        self.options.iter().map(GeneveOption::wire_len).sum()
+    }
+
+    /// In-band Network Telemetry stack carried by the packet, if any (see
+    /// `IntStack::to_geneve_option`).
+    pub fn telemetry_metadata(&self) -> Result<Option<IntStack>, ParseError> {
+        self.options
+            .iter()
+            .find(|option| {
+                option.option_class == int::GENEVE_OPTION_CLASS
+                    && option.option_type == int::GENEVE_OPTION_TYPE
+            })
+            .map(IntStack::from_geneve_option)
+            .transpose()
+    }
+
+    /// Returns true if any option is critical.
+    pub fn has_critical_options(&self) -> bool {
+        self.options.iter().any(GeneveOption::is_critical)
+    }
+
+    // --- SERIALIZATION ---
+
+    /// Serializes the header, options and payload, with Opt Len and the C
+    /// bit derived from the options.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the options exceed `MAX_OPTIONS_LEN` bytes; use
+    /// `try_to_bytes` for headers built from untrusted options.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.try_to_bytes().unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Serializes the header, failing if the options exceed
+    /// `MAX_OPTIONS_LEN` bytes, which Opt Len cannot express.
+    pub fn try_to_bytes(&self) -> Result<Vec<u8>, GeneveError> {
+        let len = self.options_len();
+        if len > MAX_OPTIONS_LEN {
+            return Err(GeneveError::OptionsTooLong {
+                len,
+                max: MAX_OPTIONS_LEN,
+            });
+        }
+        let options: Vec<u8> = self
+            .options
+            .iter()
+            .flat_map(GeneveOption::to_bytes)
+            .collect();
+        let mut bytes = Vec::with_capacity(HEADER_LEN + options.len() + self.payload.len());
+        bytes.push((self.version & 0x03) << 6 | (options.len() / 4) as u8);
+        bytes.push((self.oam as u8) << 7 | (self.has_critical_options() as u8) << 6);
+        bytes.extend_from_slice(&self.protocol_type.to_be_bytes());
+        bytes.extend_from_slice(&(self.vni << 8).to_be_bytes());
+        bytes.extend_from_slice(&options);
+        bytes.extend_from_slice(&self.payload);
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, HEADER_LEN)?;
+        let header_len = HEADER_LEN + (buf[0] & 0x3f) as usize * 4;
+        ensure_len(buf, header_len)?;
+        let mut options = Vec::new();
+        let mut offset = HEADER_LEN;
+        while offset < header_len {
+            let (option, len) = GeneveOption::from_bytes(&buf[offset..header_len])?;
+            options.push(option);
+            offset += len;
+        }
+        Ok(Geneve {
+            version: buf[0] >> 6,
+            oam: buf[1] & 0x80 != 0,
+            protocol_type: u16::from_be_bytes([buf[2], buf[3]]),
+            vni: u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]) >> 8,
+            options,
+            payload: buf[header_len..].to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn option(data_len: usize) -> GeneveOption {
+        GeneveOption::new(0x0102, 0x03, vec![0xee; data_len])
+    }
+
+    fn full() -> Geneve {
+        Geneve::new(42, vec![0xaa; 14])
+            .try_add_option(option(MAX_OPTION_DATA_LEN))
+            .unwrap()
+            .try_add_option(option(120))
+            .unwrap()
+    }
+
+    #[test]
+    fn options_up_to_the_limit_round_trip() {
+        let geneve = full();
+        assert_eq!(geneve.options_len(), MAX_OPTIONS_LEN);
+        let bytes = geneve.to_bytes();
+        assert_eq!(bytes[0] & 0x3f, 63);
+        assert_eq!(bytes.len(), HEADER_LEN + MAX_OPTIONS_LEN + 14);
+        assert_eq!(Geneve::from_bytes(&bytes).unwrap(), geneve);
+    }
+
+    #[test]
+    fn try_add_option_rejects_options_past_the_limit() {
+        assert_eq!(
+            full().try_add_option(option(0)),
+            Err(GeneveError::OptionsTooLong {
+                len: MAX_OPTIONS_LEN + 4,
+                max: MAX_OPTIONS_LEN
+            })
+        );
+    }
+
+    #[test]
+    fn over_limit_options_are_refused() {
+        let geneve = full().add_option(option(0));
+        assert_eq!(
+            geneve.try_to_bytes(),
+            Err(GeneveError::OptionsTooLong {
+                len: MAX_OPTIONS_LEN + 4,
+                max: MAX_OPTIONS_LEN
+            })
+        );
+        let result = std::panic::catch_unwind(|| geneve.to_bytes());
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,207 @@
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use crate::ethernet::{ETHERTYPE_IPV4, ETHERTYPE_IPV6, Ethernet};
+use crate::ipv4::IPv4;
+use crate::ipv6::IPv6;
+use crate::util::{ParseError, checksum, ensure_len};
+
+// GRE header (RFC 2784, with the key and sequence number of RFC 2890)
+//
+//  0                   1                   2                   3
+//  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |C| |K|S| Reserved0       | Ver |         Protocol Type         |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |      Checksum (optional)      |       Reserved1 (Optional)    |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                         Key (optional)                        |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                 Sequence Number (Optional)                    |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// The checksum covers the GRE header and payload.
+
+/// IP protocol number of GRE.
+pub const IP_PROTOCOL: u8 = 47;
+
+/// Length of the header without optional fields in bytes.
+pub const HEADER_LEN: usize = 4;
+
+// Flags of the first byte.
+pub const FLAG_CHECKSUM: u8 = 0x80;
+pub const FLAG_KEY: u8 = 0x20;
+pub const FLAG_SEQUENCE: u8 = 0x10;
+
+/// Protocol type of Ethernet frames (Transparent Ethernet Bridging).
+pub const PROTOCOL_ETHERNET: u16 = 0x6558;
+
+/// Header GRE, followed by the encapsulated packet. Optional fields are
+/// present when set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Gre {
+    pub checksum: Option<u16>,
+    pub key: Option<u32>,
+    pub sequence: Option<u32>,
+    /// Version (3 bits), 0 except for PPTP.
+    pub version: u8,
+    /// EtherType of the payload.
+    pub protocol_type: u16,
+    pub payload: Vec<u8>,
+}
+
+impl Gre {
+    /// Builds a version 0 header without optional fields carrying
+    /// `payload` of EtherType `protocol_type`.
+    pub fn new(protocol_type: u16, payload: Vec<u8>) -> Self {
+        Gre {
+            checksum: None,
+            key: None,
+            sequence: None,
+            version: 0,
+            protocol_type,
+            payload,
+        }
+    }
+
+    /// Encapsulates an IPv4 packet.
+    pub fn over_ipv4(packet: &IPv4) -> Self {
+        Gre::new(ETHERTYPE_IPV4, packet.to_bytes())
+    }
+
+    /// Encapsulates an IPv6 packet.
+    pub fn over_ipv6(packet: &IPv6) -> Self {
+        Gre::new(ETHERTYPE_IPV6, packet.to_bytes())
+    }
+
+    /// Encapsulates an Ethernet frame, as in NVGRE or GRETAP tunnels.
+    pub fn over_ethernet(frame: &Ethernet) -> Self {
+        Gre::new(PROTOCOL_ETHERNET, frame.to_bytes())
+    }
+
+    /// Length of the header with its optional fields in bytes.
+    pub fn header_len(&self) -> usize {
+        let checksum = if self.checksum.is_some() { 4 } else { 0 };
+        let key = if self.key.is_some() { 4 } else { 0 };
+        let sequence = if self.sequence.is_some() { 4 } else { 0 };
+        HEADER_LEN + checksum + key + sequence
+    }
+
+    // --- SETTER METHODS ---
+
+    /// Sets the key, or removes it with `None`.
+    pub fn set_key(mut self, key: Option<u32>) -> Self {
+        self.key = key;
+        self
+    }
+
+    /// Sets the sequence number, or removes it with `None`.
+    pub fn set_sequence(mut self, sequence: Option<u32>) -> Self {
+        self.sequence = sequence;
+        self
+    }
+
+    /// Sets the checksum field, or removes it with `None`.
+    pub fn set_checksum(mut self, checksum: Option<u16>) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    pub fn set_version(mut self, version: u8) -> Self {
+        self.version = version & 0x07;
+        self
+    }
+
+    pub fn set_protocol_type(mut self, protocol_type: u16) -> Self {
+        self.protocol_type = protocol_type;
+        self
+    }
+
+    pub fn set_payload(mut self, payload: Vec<u8>) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    /// Computes the checksum over the header and payload, as if the
+    /// checksum field were present.
+    pub fn compute_checksum(&self) -> u16 {
+        let mut bytes = Gre {
+            checksum: Some(0),
+            ..self.clone()
+        }
+        .to_bytes();
+        bytes[4..6].fill(0);
+        checksum(&bytes)
+    }
+
+    /// Returns the header with the checksum field present and computed.
+    pub fn with_checksum(mut self) -> Self {
+        self.checksum = Some(self.compute_checksum());
+        self
+    }
+
+    /// Wraps the header in an IPv4 packet between the tunnel endpoints.
+    pub fn to_ipv4(&self, source: Ipv4Addr, destination: Ipv4Addr) -> IPv4 {
+        IPv4::with_payload(source, destination, IP_PROTOCOL, self.to_bytes())
+    }
+
+    /// Wraps the header in an IPv6 packet between the tunnel endpoints.
+    pub fn to_ipv6(&self, source: Ipv6Addr, destination: Ipv6Addr) -> IPv6 {
+        IPv6::with_payload(source, destination, IP_PROTOCOL, self.to_bytes())
+    }
+
+    // --- SERIALIZATION ---
+
+    /// Serializes the header and payload, with the flags derived from the
+    /// optional fields and the checksum field used as is.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.header_len() + self.payload.len());
+        let flags = self.checksum.map_or(0, |_| FLAG_CHECKSUM)
+            | self.key.map_or(0, |_| FLAG_KEY)
+            | self.sequence.map_or(0, |_| FLAG_SEQUENCE);
+        bytes.push(flags);
+        bytes.push(self.version & 0x07);
+        bytes.extend_from_slice(&self.protocol_type.to_be_bytes());
+        if let Some(checksum) = self.checksum {
+            bytes.extend_from_slice(&checksum.to_be_bytes());
+            bytes.extend_from_slice(&[0, 0]);
+        }
+        if let Some(key) = self.key {
+            bytes.extend_from_slice(&key.to_be_bytes());
+        }
+        if let Some(sequence) = self.sequence {
+            bytes.extend_from_slice(&sequence.to_be_bytes());
+        }
+        bytes.extend_from_slice(&self.payload);
+        bytes
+    }
+
+    /// Parses a GRE header and the payload following it.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, HEADER_LEN)?;
+        let mut offset = HEADER_LEN;
+        let mut field = |present: bool| -> Result<Option<[u8; 4]>, ParseError> {
+            if !present {
+                return Ok(None);
+            }
+            ensure_len(buf, offset + 4)?;
+            offset += 4;
+            Ok(Some([
+                buf[offset - 4],
+                buf[offset - 3],
+                buf[offset - 2],
+                buf[offset - 1],
+            ]))
+        };
+        let checksum = field(buf[0] & FLAG_CHECKSUM != 0)?;
+        let key = field(buf[0] & FLAG_KEY != 0)?;
+        let sequence = field(buf[0] & FLAG_SEQUENCE != 0)?;
+        Ok(Gre {
+            checksum: checksum.map(|word| u16::from_be_bytes([word[0], word[1]])),
+            key: key.map(u32::from_be_bytes),
+            sequence: sequence.map(u32::from_be_bytes),
+            version: buf[1] & 0x07,
+            protocol_type: u16::from_be_bytes([buf[2], buf[3]]),
+            payload: buf[offset..].to_vec(),
+        })
+    }
+}
@@ -0,0 +1,310 @@
+use crate::util::{ParseError, ensure_len};
+
+// GTPv1-U header (3GPP TS 29.281)
+//
+//  0                   1                   2                   3
+//  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |Version|P|R|E|S|N| Message Type  |            Length             |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |              Tunnel Endpoint Identifier (TEID)                |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |        Sequence Number        | N-PDU Number  |Next Ext. Type |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// The last word is present when any of E, S or N is set. Each extension
+// header starts with its length in 4-octet units and ends with the type of
+// the next extension (0 when it is the last one).
+
+/// UDP port of GTP-U.
+pub const UDP_PORT: u16 = 2152;
+
+/// Message type of a G-PDU (encapsulated user data).
+pub const MESSAGE_TYPE_GPDU: u8 = 255;
+
+// Extension header types.
+pub const EXT_NONE: u8 = 0x00;
+pub const EXT_PDU_SESSION_CONTAINER: u8 = 0x85;
+
+/// GTP-U extension header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GtpExtHeader {
+    /// PDU Session Container (TS 38.415), carrying the QoS flow identifier.
+    PduSessionContainer {
+        /// 0 for DL PDU SESSION INFORMATION, 1 for UL.
+        pdu_type: u8,
+        qfi: u8,
+        rqi: bool,
+        /// Paging Policy Indicator; sets the PPP bit when present.
+        paging_policy: Option<u8>,
+    },
+    /// Any other extension; `data` is the content between the length and
+    /// next-type octets (when parsed, this includes any padding).
+    Unknown { type_: u8, data: Vec<u8> },
+}
+
+impl GtpExtHeader {
+    /// Returns the extension header type.
+    pub fn ext_type(&self) -> u8 {
+        match self {
+            GtpExtHeader::PduSessionContainer { .. } => EXT_PDU_SESSION_CONTAINER,
+            GtpExtHeader::Unknown { type_, .. } => *type_,
+        }
+    }
+
+    /// Content of the extension, without the length and next-type octets.
+    fn content(&self) -> Vec<u8> {
+        match self {
+            GtpExtHeader::PduSessionContainer {
+                pdu_type,
+                qfi,
+                rqi,
+                paging_policy,
+            } => {
+                let mut content = vec![pdu_type << 4, (*rqi as u8) << 6 | (qfi & 0x3f)];
+                if let Some(ppi) = paging_policy {
+                    content[1] |= 0x80;
+                    content.push((ppi & 0x07) << 5);
+                }
+                content
+            }
+            GtpExtHeader::Unknown { data, .. } => data.clone(),
+        }
+    }
+
+    /// Appends the extension to `bytes`, padding it to a multiple of 4 octets.
+    pub fn serialize_into(&self, bytes: &mut Vec<u8>, next_type: u8) {
+        let content = self.content();
+        let units = (content.len() + 2).div_ceil(4);
+        bytes.push(units as u8);
+        bytes.extend_from_slice(&content);
+        bytes.resize(bytes.len() + units * 4 - content.len() - 2, 0);
+        bytes.push(next_type);
+    }
+
+    /// Parses an extension of type `ext_type`, returning it with the number
+    /// of bytes consumed and the type of the next extension.
+    pub fn from_bytes(ext_type: u8, buf: &[u8]) -> Result<(Self, usize, u8), ParseError> {
+        ensure_len(buf, 1)?;
+        let len = buf[0] as usize * 4;
+        if len == 0 {
+            return Err(ParseError::InvalidField("extension length"));
+        }
+        ensure_len(buf, len)?;
+        let content = &buf[1..len - 1];
+        let ext = match ext_type {
+            EXT_PDU_SESSION_CONTAINER if content.len() >= 2 => {
+                let ppp = content[1] & 0x80 != 0;
+                GtpExtHeader::PduSessionContainer {
+                    pdu_type: content[0] >> 4,
+                    qfi: content[1] & 0x3f,
+                    rqi: content[1] & 0x40 != 0,
+                    paging_policy: match content.get(2) {
+                        Some(byte) if ppp => Some(byte >> 5),
+                        _ => None,
+                    },
+                }
+            }
+            _ => GtpExtHeader::Unknown {
+                type_: ext_type,
+                data: content.to_vec(),
+            },
+        };
+        Ok((ext, len, buf[len - 1]))
+    }
+}
+
+/// Header GTPv1-U, followed by its payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GtpU {
+    pub version: u8,
+    pub protocol_type: bool,
+    pub extension_flag: bool,
+    pub sequence_flag: bool,
+    pub npdu_flag: bool,
+    pub message_type: u8,
+    pub length: u16,
+    pub teid: u32,
+    pub sequence_number: u16,
+    pub npdu_number: u8,
+    pub next_extension_header_type: u8,
+    pub extensions: Vec<GtpExtHeader>,
+    pub payload: Vec<u8>,
+}
+
+impl GtpU {
+    /// Builds a GTPv1-U message without optional fields, with the length
+    /// field computed from the payload.
+    pub fn new(message_type: u8, teid: u32, payload: Vec<u8>) -> Self {
+        GtpU {
+            version: 1,
+            protocol_type: true,
+            extension_flag: false,
+            sequence_flag: false,
+            npdu_flag: false,
+            message_type,
+            length: payload.len() as u16,
+            teid,
+            sequence_number: 0,
+            npdu_number: 0,
+            next_extension_header_type: EXT_NONE,
+            extensions: Vec::new(),
+            payload,
+        }
+    }
+
+    /// Builds a G-PDU carrying `payload` (usually an IP packet).
+    pub fn gpdu(teid: u32, payload: Vec<u8>) -> Self {
+        GtpU::new(MESSAGE_TYPE_GPDU, teid, payload)
+    }
+
+    /// Returns true if the optional sequence/N-PDU/next-type word is present.
+    pub fn has_optional_fields(&self) -> bool {
+        self.extension_flag || self.sequence_flag || self.npdu_flag
+    }
+
+    /// Value of the length field matching the current contents.
+    pub fn compute_length(&self) -> u16 {
+        let optional = if self.has_optional_fields() { 4 } else { 0 };
+        let extensions: usize = self
+            .extensions
+            .iter()
+            .map(|ext| (ext.content().len() + 2).div_ceil(4) * 4)
+            .sum();
+        (optional + extensions + self.payload.len()) as u16
+    }
+
+    // --- SETTER METHODS ---
+
+    /// Sets the TEID.
+    pub fn set_teid(mut self, teid: u32) -> Self {
+        self.teid = teid;
+        self
+    }
+
+    /// Sets the message type.
+    pub fn set_message_type(mut self, message_type: u8) -> Self {
+        self.message_type = message_type;
+        self
+    }
+
+    /// Sets the length field.
+    pub fn set_length(mut self, length: u16) -> Self {
+        self.length = length;
+        self
+    }
+
+    /// Sets the sequence number and the S flag.
+    pub fn set_sequence_number(mut self, sequence_number: u16) -> Self {
+        self.sequence_flag = true;
+        self.sequence_number = sequence_number;
+        self.length = self.compute_length();
+        self
+    }
+
+    /// Sets the N-PDU number and the PN flag.
+    pub fn set_npdu_number(mut self, npdu_number: u8) -> Self {
+        self.npdu_flag = true;
+        self.npdu_number = npdu_number;
+        self.length = self.compute_length();
+        self
+    }
+
+    /// Sets the payload and updates the length field.
+    pub fn set_payload(mut self, payload: Vec<u8>) -> Self {
+        self.payload = payload;
+        self.length = self.compute_length();
+        self
+    }
+
+    /// Appends an extension header: sets the E flag, links it at the end of
+    /// the extension chain and updates the length field.
+    pub fn add_extension(mut self, ext: GtpExtHeader) -> Self {
+        if self.extensions.is_empty() {
+            self.next_extension_header_type = ext.ext_type();
+        }
+        self.extension_flag = true;
+        self.extensions.push(ext);
+        self.length = self.compute_length();
+        self
+    }
+
+    // --- SERIALIZATION ---
+
+    /// Serializes the header, extension chain and payload, using the length
+    /// field as is. Each extension's last octet holds the type of the next.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(12 + self.length as usize);
+        bytes.push(
+            (self.version & 0x07) << 5
+                | (self.protocol_type as u8) << 4
+                | (self.extension_flag as u8) << 2
+                | (self.sequence_flag as u8) << 1
+                | self.npdu_flag as u8,
+        );
+        bytes.push(self.message_type);
+        bytes.extend_from_slice(&self.length.to_be_bytes());
+        bytes.extend_from_slice(&self.teid.to_be_bytes());
+        if self.has_optional_fields() {
+            bytes.extend_from_slice(&self.sequence_number.to_be_bytes());
+            bytes.push(self.npdu_number);
+            bytes.push(self.next_extension_header_type);
+        }
+        for (index, ext) in self.extensions.iter().enumerate() {
+            let next = self
+                .extensions
+                .get(index + 1)
+                .map_or(EXT_NONE, GtpExtHeader::ext_type);
+            ext.serialize_into(&mut bytes, next);
+        }
+        bytes.extend_from_slice(&self.payload);
+        bytes
+    }
+
+    /// Parses a GTPv1-U message, following the extension header chain.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, 8)?;
+        let flags = buf[0];
+        let length = u16::from_be_bytes([buf[2], buf[3]]);
+        let end = 8 + length as usize;
+        ensure_len(buf, end)?;
+        let mut gtp = GtpU {
+            version: flags >> 5,
+            protocol_type: flags & 0x10 != 0,
+            extension_flag: flags & 0x04 != 0,
+            sequence_flag: flags & 0x02 != 0,
+            npdu_flag: flags & 0x01 != 0,
+            message_type: buf[1],
+            length,
+            teid: u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]),
+            sequence_number: 0,
+            npdu_number: 0,
+            next_extension_header_type: EXT_NONE,
+            extensions: Vec::new(),
+            payload: Vec::new(),
+        };
+        let mut offset = 8;
+        if gtp.has_optional_fields() {
+            if end < 12 {
+                return Err(ParseError::InvalidField("length"));
+            }
+            gtp.sequence_number = u16::from_be_bytes([buf[8], buf[9]]);
+            gtp.npdu_number = buf[10];
+            gtp.next_extension_header_type = buf[11];
+            offset = 12;
+        }
+        let mut next = if gtp.extension_flag {
+            gtp.next_extension_header_type
+        } else {
+            EXT_NONE
+        };
+        while next != EXT_NONE {
+            let (ext, len, following) = GtpExtHeader::from_bytes(next, &buf[offset..end])?;
+            gtp.extensions.push(ext);
+            offset += len;
+            next = following;
+        }
+        gtp.payload = buf[offset..end].to_vec();
+        Ok(gtp)
+    }
+}
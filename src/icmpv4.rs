@@ -0,0 +1,410 @@
+use std::net::Ipv4Addr;
+
+use crate::ipv4::IPv4;
+use crate::util::{ParseError, checksum, ensure_len, read_ipv4};
+
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |     Type      |     Code      |          Checksum             |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                     Rest of Header                            |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                         Data                                  |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// Rest of header, per type (RFC 792, RFC 1191):
+//
+//   Echo Request/Reply        Identifier (16) | Sequence Number (16)
+//   Destination Unreachable   Unused (16)     | Next-Hop MTU (16)
+//   Redirect                  Gateway Internet Address (32)
+//   Time Exceeded             Unused (32)
+//
+// Error messages carry the invoking IP header and the first 8 bytes of
+// its payload as data.
+
+/// IP protocol number of ICMP.
+pub const IP_PROTOCOL: u8 = 1;
+
+// Message types.
+pub const TYPE_ECHO_REPLY: u8 = 0;
+pub const TYPE_DESTINATION_UNREACHABLE: u8 = 3;
+pub const TYPE_REDIRECT: u8 = 5;
+pub const TYPE_ECHO_REQUEST: u8 = 8;
+pub const TYPE_TIME_EXCEEDED: u8 = 11;
+
+/// Bytes of the invoking packet's payload quoted by error messages.
+pub const INVOKING_PAYLOAD_LEN: usize = 8;
+
+/// Destination Unreachable codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DestUnreachCode {
+    NetUnreachable = 0,
+    HostUnreachable = 1,
+    ProtocolUnreachable = 2,
+    PortUnreachable = 3,
+    /// Fragmentation needed and DF set; the message carries the next-hop
+    /// MTU.
+    FragmentationNeeded = 4,
+    SourceRouteFailed = 5,
+    NetUnknown = 6,
+    HostUnknown = 7,
+    NetProhibited = 9,
+    HostProhibited = 10,
+    AdministrativelyProhibited = 13,
+}
+
+impl TryFrom<u8> for DestUnreachCode {
+    type Error = ParseError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(DestUnreachCode::NetUnreachable),
+            1 => Ok(DestUnreachCode::HostUnreachable),
+            2 => Ok(DestUnreachCode::ProtocolUnreachable),
+            3 => Ok(DestUnreachCode::PortUnreachable),
+            4 => Ok(DestUnreachCode::FragmentationNeeded),
+            5 => Ok(DestUnreachCode::SourceRouteFailed),
+            6 => Ok(DestUnreachCode::NetUnknown),
+            7 => Ok(DestUnreachCode::HostUnknown),
+            9 => Ok(DestUnreachCode::NetProhibited),
+            10 => Ok(DestUnreachCode::HostProhibited),
+            13 => Ok(DestUnreachCode::AdministrativelyProhibited),
+            _ => Err(ParseError::InvalidField("destination unreachable code")),
+        }
+    }
+}
+
+/// Time Exceeded codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeExceededCode {
+    TtlExceeded = 0,
+    FragmentReassemblyTimeExceeded = 1,
+}
+
+impl TryFrom<u8> for TimeExceededCode {
+    type Error = ParseError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(TimeExceededCode::TtlExceeded),
+            1 => Ok(TimeExceededCode::FragmentReassemblyTimeExceeded),
+            _ => Err(ParseError::InvalidField("time exceeded code")),
+        }
+    }
+}
+
+/// Redirect codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedirectCode {
+    Network = 0,
+    Host = 1,
+    TosNetwork = 2,
+    TosHost = 3,
+}
+
+impl TryFrom<u8> for RedirectCode {
+    type Error = ParseError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(RedirectCode::Network),
+            1 => Ok(RedirectCode::Host),
+            2 => Ok(RedirectCode::TosNetwork),
+            3 => Ok(RedirectCode::TosHost),
+            _ => Err(ParseError::InvalidField("redirect code")),
+        }
+    }
+}
+
+/// ICMP error messages, with their type-specific field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Icmpv4ErrorType {
+    /// Code and next-hop MTU (0 unless fragmentation is needed).
+    DestinationUnreachable(DestUnreachCode, u16),
+    /// Code and the gateway to use instead.
+    Redirect(RedirectCode, Ipv4Addr),
+    TimeExceeded(TimeExceededCode),
+}
+
+/// A typed ICMP message. `invoking` holds the quoted IP header and
+/// payload bytes of error messages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Icmpv4Message {
+    EchoRequest {
+        identifier: u16,
+        sequence: u16,
+        data: Vec<u8>,
+    },
+    EchoReply {
+        identifier: u16,
+        sequence: u16,
+        data: Vec<u8>,
+    },
+    DestinationUnreachable {
+        code: DestUnreachCode,
+        /// MTU of the next hop for `FragmentationNeeded`, 0 otherwise.
+        next_hop_mtu: u16,
+        invoking: Vec<u8>,
+    },
+    Redirect {
+        code: RedirectCode,
+        gateway: Ipv4Addr,
+        invoking: Vec<u8>,
+    },
+    TimeExceeded {
+        code: TimeExceededCode,
+        invoking: Vec<u8>,
+    },
+}
+
+impl Icmpv4Message {
+    /// Returns true for error messages, which must not be answered with
+    /// another error.
+    pub fn is_error(&self) -> bool {
+        !matches!(
+            self,
+            Icmpv4Message::EchoRequest { .. } | Icmpv4Message::EchoReply { .. }
+        )
+    }
+}
+
+/// Header ICMP. `body` holds everything after the checksum.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Icmpv4 {
+    pub icmp_type: u8,
+    pub code: u8,
+    pub checksum: u16,
+    pub body: Vec<u8>,
+}
+
+impl Icmpv4 {
+    /// Constructor to create a new ICMP message.
+    pub fn new(icmp_type: u8, code: u8, checksum: u16, body: Vec<u8>) -> Self {
+        Icmpv4 {
+            icmp_type,
+            code,
+            checksum,
+            body,
+        }
+    }
+
+    /// Builds the message `message`, with its checksum computed.
+    pub fn from_message(message: &Icmpv4Message) -> Self {
+        let mut body = Vec::new();
+        let (icmp_type, code) = match message {
+            Icmpv4Message::EchoRequest {
+                identifier,
+                sequence,
+                data,
+            }
+            | Icmpv4Message::EchoReply {
+                identifier,
+                sequence,
+                data,
+            } => {
+                body.extend_from_slice(&identifier.to_be_bytes());
+                body.extend_from_slice(&sequence.to_be_bytes());
+                body.extend_from_slice(data);
+                match message {
+                    Icmpv4Message::EchoRequest { .. } => (TYPE_ECHO_REQUEST, 0),
+                    _ => (TYPE_ECHO_REPLY, 0),
+                }
+            }
+            Icmpv4Message::DestinationUnreachable {
+                code,
+                next_hop_mtu,
+                invoking,
+            } => {
+                body.extend_from_slice(&[0, 0]);
+                body.extend_from_slice(&next_hop_mtu.to_be_bytes());
+                body.extend_from_slice(invoking);
+                (TYPE_DESTINATION_UNREACHABLE, *code as u8)
+            }
+            Icmpv4Message::Redirect {
+                code,
+                gateway,
+                invoking,
+            } => {
+                body.extend_from_slice(&gateway.octets());
+                body.extend_from_slice(invoking);
+                (TYPE_REDIRECT, *code as u8)
+            }
+            Icmpv4Message::TimeExceeded { code, invoking } => {
+                body.extend_from_slice(&[0; 4]);
+                body.extend_from_slice(invoking);
+                (TYPE_TIME_EXCEEDED, *code as u8)
+            }
+        };
+        Icmpv4::new(icmp_type, code, 0, body).with_checksum()
+    }
+
+    /// Builds an echo request, as sent by ping.
+    pub fn echo_request(identifier: u16, sequence: u16, data: Vec<u8>) -> Self {
+        Icmpv4::from_message(&Icmpv4Message::EchoRequest {
+            identifier,
+            sequence,
+            data,
+        })
+    }
+
+    /// Builds the reply to `request`, echoing its identifier, sequence
+    /// number and data. Returns `None` if `request` is not an echo
+    /// request.
+    pub fn echo_reply(request: &Icmpv4) -> Option<Self> {
+        match request.message().ok()? {
+            Icmpv4Message::EchoRequest {
+                identifier,
+                sequence,
+                data,
+            } => Some(Icmpv4::from_message(&Icmpv4Message::EchoReply {
+                identifier,
+                sequence,
+                data,
+            })),
+            _ => None,
+        }
+    }
+
+    /// Decodes the typed message.
+    pub fn message(&self) -> Result<Icmpv4Message, ParseError> {
+        ensure_len(&self.body, 4)?;
+        let body = &self.body;
+        let invoking = body[4..].to_vec();
+        let message = match self.icmp_type {
+            TYPE_ECHO_REQUEST | TYPE_ECHO_REPLY => {
+                let identifier = u16::from_be_bytes([body[0], body[1]]);
+                let sequence = u16::from_be_bytes([body[2], body[3]]);
+                if self.icmp_type == TYPE_ECHO_REQUEST {
+                    Icmpv4Message::EchoRequest {
+                        identifier,
+                        sequence,
+                        data: invoking,
+                    }
+                } else {
+                    Icmpv4Message::EchoReply {
+                        identifier,
+                        sequence,
+                        data: invoking,
+                    }
+                }
+            }
+            TYPE_DESTINATION_UNREACHABLE => Icmpv4Message::DestinationUnreachable {
+                code: DestUnreachCode::try_from(self.code)?,
+                next_hop_mtu: u16::from_be_bytes([body[2], body[3]]),
+                invoking,
+            },
+            TYPE_REDIRECT => Icmpv4Message::Redirect {
+                code: RedirectCode::try_from(self.code)?,
+                gateway: read_ipv4(body, 0),
+                invoking,
+            },
+            TYPE_TIME_EXCEEDED => Icmpv4Message::TimeExceeded {
+                code: TimeExceededCode::try_from(self.code)?,
+                invoking,
+            },
+            _ => return Err(ParseError::InvalidField("icmp type")),
+        };
+        Ok(message)
+    }
+
+    // --- SETTER METHODS ---
+
+    /// Sets the type.
+    pub fn set_icmp_type(mut self, icmp_type: u8) -> Self {
+        self.icmp_type = icmp_type;
+        self
+    }
+
+    /// Sets the code.
+    pub fn set_code(mut self, code: u8) -> Self {
+        self.code = code;
+        self
+    }
+
+    /// Sets the checksum.
+    pub fn set_checksum(mut self, checksum: u16) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    /// Sets the message body.
+    pub fn set_body(mut self, body: Vec<u8>) -> Self {
+        self.body = body;
+        self
+    }
+
+    // --- SERIALIZATION ---
+
+    /// Serializes the message, using the checksum field as is.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(4 + self.body.len());
+        bytes.push(self.icmp_type);
+        bytes.push(self.code);
+        bytes.extend_from_slice(&self.checksum.to_be_bytes());
+        bytes.extend_from_slice(&self.body);
+        bytes
+    }
+
+    /// Parses an ICMP message.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, 4)?;
+        Ok(Icmpv4 {
+            icmp_type: buf[0],
+            code: buf[1],
+            checksum: u16::from_be_bytes([buf[2], buf[3]]),
+            body: buf[4..].to_vec(),
+        })
+    }
+
+    /// Computes the checksum over the message; unlike ICMPv6, there is no
+    /// pseudo-header.
+    pub fn compute_checksum(&self) -> u16 {
+        let mut bytes = self.to_bytes();
+        bytes[2..4].fill(0);
+        checksum(&bytes)
+    }
+
+    /// Returns the message with its checksum computed.
+    pub fn with_checksum(mut self) -> Self {
+        self.checksum = self.compute_checksum();
+        self
+    }
+
+    /// Wraps the message in an IPv4 packet from `source` to
+    /// `destination`.
+    pub fn to_ipv4(&self, source: Ipv4Addr, destination: Ipv4Addr) -> IPv4 {
+        IPv4::with_payload(source, destination, IP_PROTOCOL, self.to_bytes())
+    }
+
+    /// Builds the error message reporting `original_ip` back to its
+    /// sender, as sent by `source` (e.g. the router where the TTL of a
+    /// traceroute probe ran out). The message quotes the header of
+    /// `original_ip` and the first `INVOKING_PAYLOAD_LEN` bytes of its
+    /// payload.
+    pub fn error_message_from(
+        source: Ipv4Addr,
+        original_ip: &IPv4,
+        error_type: Icmpv4ErrorType,
+    ) -> (IPv4, Icmpv4) {
+        let mut invoking = original_ip.header_bytes();
+        let quoted = original_ip.payload.len().min(INVOKING_PAYLOAD_LEN);
+        invoking.extend_from_slice(&original_ip.payload[..quoted]);
+        let message = match error_type {
+            Icmpv4ErrorType::DestinationUnreachable(code, next_hop_mtu) => {
+                Icmpv4Message::DestinationUnreachable {
+                    code,
+                    next_hop_mtu,
+                    invoking,
+                }
+            }
+            Icmpv4ErrorType::Redirect(code, gateway) => Icmpv4Message::Redirect {
+                code,
+                gateway,
+                invoking,
+            },
+            Icmpv4ErrorType::TimeExceeded(code) => Icmpv4Message::TimeExceeded { code, invoking },
+        };
+        let icmp = Icmpv4::from_message(&message);
+        let ip = icmp.to_ipv4(source, original_ip.source);
+        (ip, icmp)
+    }
+}
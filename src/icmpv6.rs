@@ -0,0 +1,201 @@
+use std::net::Ipv6Addr;
+
+use crate::ipv6::{self, IPv6};
+use crate::util::{ParseError, ensure_len, ipv6_pseudo_header_checksum};
+
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |     Type      |     Code      |          Checksum             |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                                                               |
+// +                         Message Body                          +
+// |                                                               |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+/// IPv6 next header value for ICMPv6.
+pub const NEXT_HEADER: u8 = 58;
+
+// ICMPv6 error message types (RFC 4443).
+pub const TYPE_DESTINATION_UNREACHABLE: u8 = 1;
+pub const TYPE_PACKET_TOO_BIG: u8 = 2;
+pub const TYPE_TIME_EXCEEDED: u8 = 3;
+pub const TYPE_PARAMETER_PROBLEM: u8 = 4;
+
+// ICMPv6 informational message types.
+pub const TYPE_ECHO_REQUEST: u8 = 128;
+pub const TYPE_ECHO_REPLY: u8 = 129;
+
+/// Largest part of the invoking packet an error message may carry, so that
+/// the whole error fits in the IPv6 minimum MTU.
+pub const MAX_INVOKING_PACKET_LEN: usize = ipv6::MIN_MTU - ipv6::HEADER_LEN - 8;
+
+/// Destination Unreachable codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DestUnreachCode {
+    NoRoute = 0,
+    AdministrativelyProhibited = 1,
+    BeyondScope = 2,
+    AddressUnreachable = 3,
+    PortUnreachable = 4,
+    SourcePolicyFailed = 5,
+    RejectRoute = 6,
+}
+
+/// Time Exceeded codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeExceededCode {
+    HopLimitExceeded = 0,
+    FragmentReassemblyTimeExceeded = 1,
+}
+
+/// ICMPv6 error messages, with their type-specific field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Icmpv6ErrorType {
+    DestinationUnreachable(DestUnreachCode),
+    /// MTU of the next-hop link.
+    PacketTooBig(u32),
+    TimeExceeded(TimeExceededCode),
+    /// Offset of the erroneous field within the invoking packet
+    /// (code 0, erroneous header field encountered).
+    ParameterProblem(u32),
+}
+
+impl Icmpv6ErrorType {
+    /// Returns the (type, code, 32-bit message field) of the error.
+    fn fields(self) -> (u8, u8, u32) {
+        match self {
+            Icmpv6ErrorType::DestinationUnreachable(code) => {
+                (TYPE_DESTINATION_UNREACHABLE, code as u8, 0)
+            }
+            Icmpv6ErrorType::PacketTooBig(mtu) => (TYPE_PACKET_TOO_BIG, 0, mtu),
+            Icmpv6ErrorType::TimeExceeded(code) => (TYPE_TIME_EXCEEDED, code as u8, 0),
+            Icmpv6ErrorType::ParameterProblem(pointer) => (TYPE_PARAMETER_PROBLEM, 0, pointer),
+        }
+    }
+}
+
+/// Header ICMPv6. `body` holds everything after the checksum.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Icmpv6 {
+    pub icmp_type: u8,
+    pub code: u8,
+    pub checksum: u16,
+    pub body: Vec<u8>,
+}
+
+impl Icmpv6 {
+    /// Constructor to create a new ICMPv6 message.
+    pub fn new(icmp_type: u8, code: u8, checksum: u16, body: Vec<u8>) -> Self {
+        Icmpv6 {
+            icmp_type,
+            code,
+            checksum,
+            body,
+        }
+    }
+
+    /// Builds an echo request; the checksum is left to `with_checksum`.
+    pub fn echo_request(identifier: u16, sequence: u16, data: &[u8]) -> Self {
+        let mut body = Vec::with_capacity(4 + data.len());
+        body.extend_from_slice(&identifier.to_be_bytes());
+        body.extend_from_slice(&sequence.to_be_bytes());
+        body.extend_from_slice(data);
+        Icmpv6::new(TYPE_ECHO_REQUEST, 0, 0, body)
+    }
+
+    /// Builds the reply to `request`, echoing its identifier, sequence
+    /// number and data; the checksum is left to `with_checksum`. Returns
+    /// `None` if `request` is not an echo request.
+    pub fn echo_reply(request: &Icmpv6) -> Option<Self> {
+        if request.icmp_type != TYPE_ECHO_REQUEST || request.body.len() < 4 {
+            return None;
+        }
+        Some(Icmpv6::new(TYPE_ECHO_REPLY, 0, 0, request.body.clone()))
+    }
+
+    /// Sets the type.
+    pub fn set_icmp_type(mut self, icmp_type: u8) -> Self {
+        self.icmp_type = icmp_type;
+        self
+    }
+
+    /// Sets the code.
+    pub fn set_code(mut self, code: u8) -> Self {
+        self.code = code;
+        self
+    }
+
+    /// Sets the checksum.
+    pub fn set_checksum(mut self, checksum: u16) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    /// Sets the message body.
+    pub fn set_body(mut self, body: Vec<u8>) -> Self {
+        self.body = body;
+        self
+    }
+
+    /// Serializes the message, using the checksum field as is.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(4 + self.body.len());
+        bytes.push(self.icmp_type);
+        bytes.push(self.code);
+        bytes.extend_from_slice(&self.checksum.to_be_bytes());
+        bytes.extend_from_slice(&self.body);
+        bytes
+    }
+
+    /// Parses an ICMPv6 message.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, 4)?;
+        Ok(Icmpv6 {
+            icmp_type: buf[0],
+            code: buf[1],
+            checksum: u16::from_be_bytes([buf[2], buf[3]]),
+            body: buf[4..].to_vec(),
+        })
+    }
+
+    /// Computes the checksum over the IPv6 pseudo-header.
+    pub fn compute_checksum(&self, source: Ipv6Addr, destination: Ipv6Addr) -> u16 {
+        let mut bytes = self.to_bytes();
+        bytes[2..4].fill(0);
+        ipv6_pseudo_header_checksum(source, destination, NEXT_HEADER, &bytes)
+    }
+
+    /// Returns the message with its checksum computed for the given addresses.
+    pub fn with_checksum(mut self, source: Ipv6Addr, destination: Ipv6Addr) -> Self {
+        self.checksum = self.compute_checksum(source, destination);
+        self
+    }
+
+    /// Builds the error message reporting `original_ip` back to its sender,
+    /// as sent by the original destination. See `error_message_from`.
+    pub fn error_message(original_ip: &IPv6, error_type: Icmpv6ErrorType) -> (IPv6, Icmpv6) {
+        Icmpv6::error_message_from(original_ip.destination, original_ip, error_type)
+    }
+
+    /// Builds the error message (RFC 4443 section 2.4) sent by `source` about
+    /// `original_ip`. The invoking packet is included as far as possible
+    /// without the error exceeding the IPv6 minimum MTU, and the checksum is
+    /// computed over the outer IPv6 pseudo-header.
+    pub fn error_message_from(
+        source: Ipv6Addr,
+        original_ip: &IPv6,
+        error_type: Icmpv6ErrorType,
+    ) -> (IPv6, Icmpv6) {
+        let (icmp_type, code, field) = error_type.fields();
+        let mut invoking = original_ip.to_bytes();
+        invoking.truncate(MAX_INVOKING_PACKET_LEN);
+
+        let mut body = Vec::with_capacity(4 + invoking.len());
+        body.extend_from_slice(&field.to_be_bytes());
+        body.extend_from_slice(&invoking);
+
+        let destination = original_ip.source;
+        let icmp = Icmpv6::new(icmp_type, code, 0, body).with_checksum(source, destination);
+        let ip = IPv6::with_payload(source, destination, NEXT_HEADER, icmp.to_bytes());
+        (ip, icmp)
+    }
+}
@@ -0,0 +1,644 @@
+use std::net::Ipv4Addr;
+
+use crate::ipv4::{IPv4, OPTION_ROUTER_ALERT};
+use crate::util::{ParseError, checksum, ensure_len, read_ipv4};
+
+// IGMPv3 Membership Query (RFC 3376 section 4.1)
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |  Type = 0x11  | Max Resp Code |           Checksum            |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                         Group Address                         |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// | Resv  |S| QRV |     QQIC      |     Number of Sources (N)     |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                       Source Address [1..N]                   |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+/// IP protocol number of IGMP.
+pub const IP_PROTOCOL: u8 = 2;
+
+/// Destination of general queries.
+pub const ALL_SYSTEMS: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 1);
+
+/// Destination of IGMPv2 Leave Group messages.
+pub const ALL_ROUTERS: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 2);
+
+/// Destination of IGMPv3 reports.
+pub const ALL_IGMPV3_ROUTERS: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 22);
+
+// Message types.
+pub const MEMBERSHIP_QUERY_TYPE: u8 = 0x11;
+pub const V1_MEMBERSHIP_REPORT_TYPE: u8 = 0x12;
+pub const V2_MEMBERSHIP_REPORT_TYPE: u8 = 0x16;
+pub const LEAVE_GROUP_TYPE: u8 = 0x17;
+pub const V3_MEMBERSHIP_REPORT_TYPE: u8 = 0x22;
+
+/// IP TTL of every IGMP message, which never leaves the link.
+pub const TTL: u8 = 1;
+
+// Group record types (RFC 3376 section 4.2.12).
+pub const MODE_IS_INCLUDE: u8 = 1;
+pub const MODE_IS_EXCLUDE: u8 = 2;
+pub const CHANGE_TO_INCLUDE_MODE: u8 = 3;
+pub const CHANGE_TO_EXCLUDE_MODE: u8 = 4;
+pub const ALLOW_NEW_SOURCES: u8 = 5;
+pub const BLOCK_OLD_SOURCES: u8 = 6;
+
+/// Largest value the Max Resp Code and QQIC fields can express.
+pub const MAX_TIME_CODE_VALUE: u16 = 0x1f << 10;
+
+/// Encodes a Max Resp Code or QQIC value (RFC 3376 sections 4.1.1 and
+/// 4.1.7). Values from 128 use the floating point form, rounding down;
+/// values above `MAX_TIME_CODE_VALUE` are capped.
+pub fn encode_time_code(value: u16) -> u8 {
+    if value < 128 {
+        return value as u8;
+    }
+    let value = value.min(MAX_TIME_CODE_VALUE);
+    let exp = (0..8).find(|exp| value >> (exp + 3) < 0x20).unwrap_or(7);
+    let mant = (value >> (exp + 3)) as u8 & 0x0f;
+    0x80 | (exp as u8) << 4 | mant
+}
+
+/// Decodes a Max Resp Code or QQIC field.
+pub fn decode_time_code(code: u8) -> u16 {
+    if code < 128 {
+        return code as u16;
+    }
+    let mant = (code & 0x0f) as u16;
+    let exp = (code >> 4) & 0x07;
+    (mant | 0x10) << (exp + 3)
+}
+
+// IGMPv2 (RFC 2236)
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |      Type     | Max Resp Time |           Checksum            |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                         Group Address                         |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+/// IGMPv2 message types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IgmpType {
+    MembershipQuery = 0x11,
+    V1MembershipReport = 0x12,
+    V2MembershipReport = 0x16,
+    LeaveGroup = 0x17,
+}
+
+impl TryFrom<u8> for IgmpType {
+    type Error = ParseError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            MEMBERSHIP_QUERY_TYPE => Ok(IgmpType::MembershipQuery),
+            V1_MEMBERSHIP_REPORT_TYPE => Ok(IgmpType::V1MembershipReport),
+            V2_MEMBERSHIP_REPORT_TYPE => Ok(IgmpType::V2MembershipReport),
+            LEAVE_GROUP_TYPE => Ok(IgmpType::LeaveGroup),
+            _ => Err(ParseError::InvalidField("type")),
+        }
+    }
+}
+
+/// IGMPv2 message (Membership Query, Report or Leave Group).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IgmpV2 {
+    pub type_: IgmpType,
+    /// Maximum response time in tenths of a second; zero except in queries.
+    pub max_resp_time: u8,
+    pub checksum: u16,
+    pub group_address: Ipv4Addr,
+}
+
+impl IgmpV2 {
+    /// Length of an IGMPv2 message in bytes.
+    pub const LEN: usize = 8;
+
+    /// Constructor to create a new IGMPv2 message.
+    pub fn new(type_: IgmpType, max_resp_time: u8, checksum: u16, group_address: Ipv4Addr) -> Self {
+        IgmpV2 {
+            type_,
+            max_resp_time,
+            checksum,
+            group_address,
+        }
+    }
+
+    /// General query with the default 10 second maximum response time,
+    /// checksum computed.
+    pub fn general_query() -> Self {
+        IgmpV2::new(IgmpType::MembershipQuery, 100, 0, Ipv4Addr::UNSPECIFIED).with_checksum()
+    }
+
+    /// Group-specific query for `group`, with the default last member
+    /// query interval of 1 second, checksum computed.
+    pub fn group_query(group: Ipv4Addr) -> Self {
+        IgmpV2::new(IgmpType::MembershipQuery, 10, 0, group).with_checksum()
+    }
+
+    /// Membership report joining `group`, checksum computed.
+    pub fn report(group: Ipv4Addr) -> Self {
+        IgmpV2::new(IgmpType::V2MembershipReport, 0, 0, group).with_checksum()
+    }
+
+    /// Leave Group message for `group`, checksum computed.
+    pub fn leave(group: Ipv4Addr) -> Self {
+        IgmpV2::new(IgmpType::LeaveGroup, 0, 0, group).with_checksum()
+    }
+
+    /// IP destination of the message: all systems for a general query,
+    /// all routers for a leave and the group otherwise.
+    pub fn destination(&self) -> Ipv4Addr {
+        match self.type_ {
+            IgmpType::MembershipQuery if self.group_address.is_unspecified() => ALL_SYSTEMS,
+            IgmpType::LeaveGroup => ALL_ROUTERS,
+            _ => self.group_address,
+        }
+    }
+
+    // --- SETTER METHODS ---
+
+    /// Sets the message type.
+    pub fn set_type(mut self, type_: IgmpType) -> Self {
+        self.type_ = type_;
+        self
+    }
+
+    /// Sets the maximum response time, in tenths of a second.
+    pub fn set_max_resp_time(mut self, max_resp_time: u8) -> Self {
+        self.max_resp_time = max_resp_time;
+        self
+    }
+
+    /// Sets the checksum.
+    pub fn set_checksum(mut self, checksum: u16) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    /// Sets the group address.
+    pub fn set_group_address(mut self, group_address: Ipv4Addr) -> Self {
+        self.group_address = group_address;
+        self
+    }
+
+    /// Serializes the message, using the checksum field as is.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::LEN);
+        bytes.push(self.type_ as u8);
+        bytes.push(self.max_resp_time);
+        bytes.extend_from_slice(&self.checksum.to_be_bytes());
+        bytes.extend_from_slice(&self.group_address.octets());
+        bytes
+    }
+
+    /// Parses an IGMPv2 message from the IP payload.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, Self::LEN)?;
+        Ok(IgmpV2 {
+            type_: IgmpType::try_from(buf[0])?,
+            max_resp_time: buf[1],
+            checksum: u16::from_be_bytes([buf[2], buf[3]]),
+            group_address: read_ipv4(buf, 4),
+        })
+    }
+
+    /// Computes the checksum of the message.
+    pub fn compute_checksum(&self) -> u16 {
+        let mut bytes = self.to_bytes();
+        bytes[2..4].fill(0);
+        checksum(&bytes)
+    }
+
+    /// Returns the message with its checksum computed.
+    pub fn with_checksum(mut self) -> Self {
+        self.checksum = self.compute_checksum();
+        self
+    }
+}
+
+/// IGMPv3 Membership Query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IgmpV3Query {
+    pub max_resp_code: u8,
+    pub checksum: u16,
+    /// Zero for a general query.
+    pub group_address: Ipv4Addr,
+    /// Suppress Router-Side Processing flag.
+    pub suppress: bool,
+    /// Querier's Robustness Variable, 3 bits.
+    pub qrv: u8,
+    /// Querier's Query Interval Code.
+    pub qqic: u8,
+    pub sources: Vec<Ipv4Addr>,
+}
+
+impl IgmpV3Query {
+    /// Constructor to create a query for `group_address` and `sources`,
+    /// with the default timers of RFC 3376 section 8: a 10 second maximum
+    /// response time, a robustness of 2 and a 125 second query interval.
+    pub fn new(group_address: Ipv4Addr, sources: Vec<Ipv4Addr>) -> Self {
+        IgmpV3Query {
+            max_resp_code: 100,
+            checksum: 0,
+            group_address,
+            suppress: false,
+            qrv: 2,
+            qqic: 125,
+            sources,
+        }
+    }
+
+    /// Maximum response time in tenths of a second.
+    pub fn get_max_response_time(&self) -> u16 {
+        decode_time_code(self.max_resp_code)
+    }
+
+    /// Query interval in seconds.
+    pub fn get_query_interval(&self) -> u16 {
+        decode_time_code(self.qqic)
+    }
+
+    // --- SETTER METHODS ---
+
+    /// Sets the maximum response time, in tenths of a second.
+    pub fn set_max_response_time(mut self, tenths: u16) -> Self {
+        self.max_resp_code = encode_time_code(tenths);
+        self
+    }
+
+    /// Sets the query interval, in seconds.
+    pub fn set_query_interval(mut self, seconds: u16) -> Self {
+        self.qqic = encode_time_code(seconds);
+        self
+    }
+
+    /// Sets the robustness variable; values above 7 are sent as 0, as
+    /// RFC 3376 section 4.1.6 requires.
+    pub fn set_qrv(mut self, qrv: u8) -> Self {
+        self.qrv = if qrv > 7 { 0 } else { qrv };
+        self
+    }
+
+    pub fn set_suppress(mut self, suppress: bool) -> Self {
+        self.suppress = suppress;
+        self
+    }
+
+    /// Serializes the query, using the checksum field as is.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(12 + 4 * self.sources.len());
+        bytes.push(MEMBERSHIP_QUERY_TYPE);
+        bytes.push(self.max_resp_code);
+        bytes.extend_from_slice(&self.checksum.to_be_bytes());
+        bytes.extend_from_slice(&self.group_address.octets());
+        bytes.push((self.suppress as u8) << 3 | self.qrv & 0x07);
+        bytes.push(self.qqic);
+        bytes.extend_from_slice(&(self.sources.len() as u16).to_be_bytes());
+        for source in &self.sources {
+            bytes.extend_from_slice(&source.octets());
+        }
+        bytes
+    }
+
+    /// Parses an IGMPv3 query from the IP payload.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, 12)?;
+        if buf[0] != MEMBERSHIP_QUERY_TYPE {
+            return Err(ParseError::InvalidField("type"));
+        }
+        let count = u16::from_be_bytes([buf[10], buf[11]]) as usize;
+        ensure_len(buf, 12 + 4 * count)?;
+        Ok(IgmpV3Query {
+            max_resp_code: buf[1],
+            checksum: u16::from_be_bytes([buf[2], buf[3]]),
+            group_address: read_ipv4(buf, 4),
+            suppress: buf[8] & 0x08 != 0,
+            qrv: buf[8] & 0x07,
+            qqic: buf[9],
+            sources: (0..count).map(|i| read_ipv4(buf, 12 + i * 4)).collect(),
+        })
+    }
+
+    /// Computes the checksum of the message.
+    pub fn compute_checksum(&self) -> u16 {
+        let mut bytes = self.to_bytes();
+        bytes[2..4].fill(0);
+        checksum(&bytes)
+    }
+
+    /// Returns the query with its checksum computed.
+    pub fn with_checksum(mut self) -> Self {
+        self.checksum = self.compute_checksum();
+        self
+    }
+}
+
+// IGMPv3 Membership Report (RFC 3376 section 4.2)
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |  Type = 0x22  |    Reserved   |           Checksum            |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |           Reserved            |  Number of Group Records (M)  |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                      Group Record [1..M]                      |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+/// IGMPv3 group record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IgmpV3Record {
+    pub record_type: u8,
+    pub multicast_address: Ipv4Addr,
+    pub sources: Vec<Ipv4Addr>,
+}
+
+impl IgmpV3Record {
+    /// Constructor to create a new group record.
+    pub fn new(record_type: u8, multicast_address: Ipv4Addr, sources: Vec<Ipv4Addr>) -> Self {
+        IgmpV3Record {
+            record_type,
+            multicast_address,
+            sources,
+        }
+    }
+
+    /// Serialized length of the record in bytes.
+    pub fn wire_len(&self) -> usize {
+        8 + 4 * self.sources.len()
+    }
+
+    /// Appends the record to `bytes`. Auxiliary data is never emitted.
+    pub fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        bytes.push(self.record_type);
+        bytes.push(0);
+        bytes.extend_from_slice(&(self.sources.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(&self.multicast_address.octets());
+        for source in &self.sources {
+            bytes.extend_from_slice(&source.octets());
+        }
+    }
+
+    /// Parses a record, returning it with the number of bytes consumed.
+    /// Auxiliary data is skipped.
+    pub fn from_bytes(buf: &[u8]) -> Result<(Self, usize), ParseError> {
+        ensure_len(buf, 8)?;
+        let aux_len = buf[1] as usize * 4;
+        let count = u16::from_be_bytes([buf[2], buf[3]]) as usize;
+        let len = 8 + count * 4 + aux_len;
+        ensure_len(buf, len)?;
+        let sources = (0..count).map(|i| read_ipv4(buf, 8 + i * 4)).collect();
+        Ok((
+            IgmpV3Record {
+                record_type: buf[0],
+                multicast_address: read_ipv4(buf, 4),
+                sources,
+            },
+            len,
+        ))
+    }
+}
+
+/// IGMPv3 Membership Report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IgmpV3Report {
+    pub checksum: u16,
+    pub number_of_group_records: u16,
+    pub records: Vec<IgmpV3Record>,
+}
+
+impl IgmpV3Report {
+    /// Constructor to create a report; the record count is taken from `records`.
+    pub fn new(records: Vec<IgmpV3Record>) -> Self {
+        IgmpV3Report {
+            checksum: 0,
+            number_of_group_records: records.len() as u16,
+            records,
+        }
+    }
+
+    /// Source-specific join of `group` for `sources` (the (S,G) pairs of
+    /// SSM): a single CHANGE_TO_INCLUDE_MODE record, checksum computed.
+    pub fn include_source_join(group: Ipv4Addr, sources: Vec<Ipv4Addr>) -> Self {
+        IgmpV3Report::new(vec![IgmpV3Record::new(
+            CHANGE_TO_INCLUDE_MODE,
+            group,
+            sources,
+        )])
+        .with_checksum()
+    }
+
+    /// Join of `group` from every source except `sources_to_exclude`: a
+    /// single CHANGE_TO_EXCLUDE_MODE record, checksum computed. An empty
+    /// list is an any-source join.
+    pub fn exclude_source_join(group: Ipv4Addr, sources_to_exclude: Vec<Ipv4Addr>) -> Self {
+        IgmpV3Report::new(vec![IgmpV3Record::new(
+            CHANGE_TO_EXCLUDE_MODE,
+            group,
+            sources_to_exclude,
+        )])
+        .with_checksum()
+    }
+
+    /// Sets the checksum.
+    pub fn set_checksum(mut self, checksum: u16) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    /// Sets the records and updates the record count.
+    pub fn set_records(mut self, records: Vec<IgmpV3Record>) -> Self {
+        self.number_of_group_records = records.len() as u16;
+        self.records = records;
+        self
+    }
+
+    /// Serializes the report, using the checksum and count fields as is.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let len = 8 + self
+            .records
+            .iter()
+            .map(IgmpV3Record::wire_len)
+            .sum::<usize>();
+        let mut bytes = Vec::with_capacity(len);
+        bytes.push(V3_MEMBERSHIP_REPORT_TYPE);
+        bytes.push(0);
+        bytes.extend_from_slice(&self.checksum.to_be_bytes());
+        bytes.extend_from_slice(&[0, 0]);
+        bytes.extend_from_slice(&self.number_of_group_records.to_be_bytes());
+        for record in &self.records {
+            record.serialize_into(&mut bytes);
+        }
+        bytes
+    }
+
+    /// Parses an IGMPv3 report from the IP payload.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, 8)?;
+        if buf[0] != V3_MEMBERSHIP_REPORT_TYPE {
+            return Err(ParseError::InvalidField("type"));
+        }
+        let count = u16::from_be_bytes([buf[6], buf[7]]);
+        let mut offset = 8;
+        // Records take at least 8 bytes: do not trust the count further.
+        let mut records = Vec::with_capacity((count as usize).min((buf.len() - 8) / 8));
+        for _ in 0..count {
+            let (record, len) = IgmpV3Record::from_bytes(&buf[offset..])?;
+            records.push(record);
+            offset += len;
+        }
+        Ok(IgmpV3Report {
+            checksum: u16::from_be_bytes([buf[2], buf[3]]),
+            number_of_group_records: count,
+            records,
+        })
+    }
+
+    /// Computes the checksum of the message.
+    pub fn compute_checksum(&self) -> u16 {
+        let mut bytes = self.to_bytes();
+        bytes[2..4].fill(0);
+        checksum(&bytes)
+    }
+
+    /// Returns the report with its checksum computed.
+    pub fn with_checksum(mut self) -> Self {
+        self.checksum = self.compute_checksum();
+        self
+    }
+}
+
+/// Any IGMP message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Igmp {
+    V2(IgmpV2),
+    V3Query(IgmpV3Query),
+    V3Report(IgmpV3Report),
+}
+
+impl Igmp {
+    /// IP destination of the message.
+    pub fn destination(&self) -> Ipv4Addr {
+        match self {
+            Igmp::V2(message) => message.destination(),
+            Igmp::V3Query(query) if query.group_address.is_unspecified() => ALL_SYSTEMS,
+            Igmp::V3Query(query) => query.group_address,
+            Igmp::V3Report(_) => ALL_IGMPV3_ROUTERS,
+        }
+    }
+
+    /// Wraps the message in an IPv4 packet sent to its destination, with
+    /// a TTL of 1 and the Router Alert option (RFC 2113) that IGMPv2 and
+    /// IGMPv3 receivers expect.
+    pub fn to_ipv4(&self, source: Ipv4Addr) -> IPv4 {
+        IPv4::with_payload(source, self.destination(), IP_PROTOCOL, self.to_bytes())
+            .set_ttl(TTL)
+            .set_options(vec![OPTION_ROUTER_ALERT, 4, 0, 0])
+            .with_checksum()
+    }
+
+    /// Serializes the message, using its checksum field as is.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Igmp::V2(message) => message.to_bytes(),
+            Igmp::V3Query(query) => query.to_bytes(),
+            Igmp::V3Report(report) => report.to_bytes(),
+        }
+    }
+
+    /// Parses an IGMP message from the IP payload. Queries of 8 bytes are
+    /// IGMPv2 and longer ones IGMPv3 (RFC 3376 section 7.1); IGMPv1
+    /// reports are returned as IGMPv2 messages.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, IgmpV2::LEN)?;
+        match buf[0] {
+            MEMBERSHIP_QUERY_TYPE if buf.len() >= 12 => {
+                IgmpV3Query::from_bytes(buf).map(Igmp::V3Query)
+            }
+            V3_MEMBERSHIP_REPORT_TYPE => IgmpV3Report::from_bytes(buf).map(Igmp::V3Report),
+            _ => IgmpV2::from_bytes(buf).map(Igmp::V2),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn two_source_ssm_join_on_the_wire() {
+        let group = Ipv4Addr::new(232, 1, 1, 1);
+        let sources = vec![Ipv4Addr::new(192, 0, 2, 10), Ipv4Addr::new(192, 0, 2, 20)];
+        let report = IgmpV3Report::include_source_join(group, sources.clone());
+        let bytes = report.to_bytes();
+
+        #[rustfmt::skip]
+        let expected_without_checksum = [
+            V3_MEMBERSHIP_REPORT_TYPE, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x01,
+            // Record: CHANGE_TO_INCLUDE_MODE, no aux data, two sources.
+            CHANGE_TO_INCLUDE_MODE, 0x00, 0x00, 0x02,
+            232, 1, 1, 1,
+            192, 0, 2, 10,
+            192, 0, 2, 20,
+        ];
+        let mut zeroed = bytes.clone();
+        zeroed[2..4].fill(0);
+        assert_eq!(zeroed, expected_without_checksum);
+        assert_eq!(
+            u16::from_be_bytes([bytes[2], bytes[3]]),
+            checksum(&expected_without_checksum)
+        );
+
+        let Igmp::V3Report(parsed) = Igmp::from_bytes(&bytes).unwrap() else {
+            panic!("not an IGMPv3 report");
+        };
+        assert_eq!(parsed, report);
+        assert_eq!(parsed.records[0].record_type, CHANGE_TO_INCLUDE_MODE);
+        assert_eq!(parsed.records[0].sources, sources);
+    }
+
+    #[test]
+    fn record_count_beyond_the_buffer_is_truncated() {
+        let mut bytes = IgmpV3Report::new(vec![]).to_bytes();
+        bytes[6..8].copy_from_slice(&u16::MAX.to_be_bytes());
+        assert!(matches!(
+            IgmpV3Report::from_bytes(&bytes),
+            Err(ParseError::Truncated { .. })
+        ));
+    }
+
+    #[test]
+    fn time_code_round_trips_up_to_127() {
+        for value in [0, 1, 100, 127] {
+            assert_eq!(encode_time_code(value), value as u8);
+            assert_eq!(decode_time_code(encode_time_code(value)), value);
+        }
+    }
+
+    #[test]
+    fn time_code_switches_to_floating_point_at_128() {
+        assert_eq!(encode_time_code(128), 0x80);
+        assert_eq!(decode_time_code(0x80), 128);
+        // 1000 = 0b11111_01000: mantissa 0xf, exponent 2, rounded down to 992.
+        assert_eq!(encode_time_code(1000), 0xaf);
+        assert_eq!(decode_time_code(0xaf), 992);
+        for value in 128..=MAX_TIME_CODE_VALUE {
+            let code = encode_time_code(value);
+            assert!(code >= 0x80);
+            let decoded = decode_time_code(code);
+            let step = 1 << (((code >> 4) & 0x07) + 3);
+            assert!(decoded <= value && value - decoded < step, "{value}");
+        }
+    }
+
+    #[test]
+    fn time_code_caps_above_the_maximum() {
+        assert_eq!(encode_time_code(MAX_TIME_CODE_VALUE), 0xff);
+        assert_eq!(decode_time_code(0xff), MAX_TIME_CODE_VALUE);
+        for value in [MAX_TIME_CODE_VALUE + 1, 40_000, u16::MAX] {
+            assert_eq!(encode_time_code(value), 0xff);
+        }
+    }
+}
@@ -0,0 +1,252 @@
+use crate::geneve::GeneveOption;
+use crate::util::{ParseError, ensure_len};
+
+// In-band Network Telemetry carried in a GENEVE option. The option data
+// starts with a 4-byte header followed by the metadata stack, one entry
+// per switch, the last switch first (each hop pushes its entry on top):
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |    Version    |    Hop ML     |   Hop Count   |   Reserved    |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// Hop metadata (Hop ML = 7 words)
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                           Switch ID                           |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |         Ingress Port          |          Egress Port          |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                        Hop Latency (ns)                       |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |   Queue ID    |            Queue Occupancy (bytes)            |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                   Ingress Timestamp (ns, 48 bits)             |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                               |                               |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+                               +
+// |                   Egress Timestamp (ns, 48 bits)              |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// Queue occupancy is 24 bits, as in the INT specification, and the
+// timestamps are those of the switch's 48-bit nanosecond clock.
+
+/// Option class of the INT option.
+pub const GENEVE_OPTION_CLASS: u16 = 0x0102;
+
+/// Option type of the INT option.
+pub const GENEVE_OPTION_TYPE: u8 = 0x01;
+
+/// Version in the option header.
+pub const VERSION: u8 = 2;
+
+/// Length of the option header in bytes.
+const HEADER_LEN: usize = 4;
+
+/// Length of the metadata of a hop in bytes.
+pub const HOP_LEN: usize = 28;
+
+/// Most hops a GENEVE option has room for.
+pub const MAX_HOPS: usize = 4;
+
+/// Largest queue occupancy that can be reported (24 bits).
+pub const MAX_QUEUE_OCCUPANCY: u32 = 0x00ff_ffff;
+
+/// Mask of the 48-bit timestamps.
+const TIMESTAMP_MASK: u64 = 0xffff_ffff_ffff;
+
+/// Telemetry reported by one switch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IntMetadata {
+    pub switch_id: u32,
+    pub ingress_port: u16,
+    pub egress_port: u16,
+    pub hop_latency_ns: u32,
+    pub queue_id: u8,
+    pub queue_occupancy_bytes: u32,
+    pub ingress_timestamp_ns: u64,
+    pub egress_timestamp_ns: u64,
+}
+
+impl IntMetadata {
+    // --- SERIALIZATION ---
+
+    /// Serializes the metadata; the queue occupancy saturates at
+    /// `MAX_QUEUE_OCCUPANCY` and the timestamps wrap at 48 bits.
+    pub fn to_bytes(&self) -> [u8; HOP_LEN] {
+        let mut bytes = [0; HOP_LEN];
+        bytes[0..4].copy_from_slice(&self.switch_id.to_be_bytes());
+        bytes[4..6].copy_from_slice(&self.ingress_port.to_be_bytes());
+        bytes[6..8].copy_from_slice(&self.egress_port.to_be_bytes());
+        bytes[8..12].copy_from_slice(&self.hop_latency_ns.to_be_bytes());
+        let queue =
+            (self.queue_id as u32) << 24 | self.queue_occupancy_bytes.min(MAX_QUEUE_OCCUPANCY);
+        bytes[12..16].copy_from_slice(&queue.to_be_bytes());
+        bytes[16..22]
+            .copy_from_slice(&(self.ingress_timestamp_ns & TIMESTAMP_MASK).to_be_bytes()[2..]);
+        bytes[22..28]
+            .copy_from_slice(&(self.egress_timestamp_ns & TIMESTAMP_MASK).to_be_bytes()[2..]);
+        bytes
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> Result<(Self, usize), ParseError> {
+        ensure_len(buf, HOP_LEN)?;
+        let read_u48 = |offset: usize| {
+            let mut word = [0; 8];
+            word[2..].copy_from_slice(&buf[offset..offset + 6]);
+            u64::from_be_bytes(word)
+        };
+        let queue = u32::from_be_bytes([buf[12], buf[13], buf[14], buf[15]]);
+        let metadata = IntMetadata {
+            switch_id: u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]),
+            ingress_port: u16::from_be_bytes([buf[4], buf[5]]),
+            egress_port: u16::from_be_bytes([buf[6], buf[7]]),
+            hop_latency_ns: u32::from_be_bytes([buf[8], buf[9], buf[10], buf[11]]),
+            queue_id: (queue >> 24) as u8,
+            queue_occupancy_bytes: queue & MAX_QUEUE_OCCUPANCY,
+            ingress_timestamp_ns: read_u48(16),
+            egress_timestamp_ns: read_u48(22),
+        };
+        Ok((metadata, HOP_LEN))
+    }
+}
+
+/// Telemetry collected along a path, the last switch first.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct IntStack {
+    pub hops: Vec<IntMetadata>,
+}
+
+impl IntStack {
+    /// Constructor to create an empty stack.
+    pub fn new() -> Self {
+        IntStack::default()
+    }
+
+    /// Pushes the metadata of the switch the packet is leaving on top of
+    /// the stack. Returns false, leaving the stack unchanged, once
+    /// `MAX_HOPS` hops are recorded.
+    pub fn push_hop(&mut self, hop: IntMetadata) -> bool {
+        if self.hops.len() >= MAX_HOPS {
+            return false;
+        }
+        self.hops.insert(0, hop);
+        true
+    }
+
+    /// Builds the GENEVE option carrying the stack. Hops beyond `MAX_HOPS`
+    /// are left out.
+    pub fn to_geneve_option(&self) -> GeneveOption {
+        let hops = &self.hops[..self.hops.len().min(MAX_HOPS)];
+        let mut data = Vec::with_capacity(HEADER_LEN + hops.len() * HOP_LEN);
+        data.extend_from_slice(&[VERSION, (HOP_LEN / 4) as u8, hops.len() as u8, 0]);
+        for hop in hops {
+            data.extend_from_slice(&hop.to_bytes());
+        }
+        GeneveOption::new(GENEVE_OPTION_CLASS, GENEVE_OPTION_TYPE, data)
+    }
+
+    /// Parses the stack carried by `opt`.
+    pub fn from_geneve_option(opt: &GeneveOption) -> Result<IntStack, ParseError> {
+        if opt.option_class != GENEVE_OPTION_CLASS {
+            return Err(ParseError::InvalidField("option class"));
+        }
+        if opt.option_type != GENEVE_OPTION_TYPE {
+            return Err(ParseError::InvalidField("option type"));
+        }
+        let buf = &opt.data;
+        ensure_len(buf, HEADER_LEN)?;
+        if buf[0] != VERSION {
+            return Err(ParseError::InvalidField("version"));
+        }
+        if buf[1] as usize * 4 != HOP_LEN {
+            return Err(ParseError::InvalidField("hop metadata length"));
+        }
+        let count = buf[2] as usize;
+        ensure_len(buf, HEADER_LEN + count * HOP_LEN)?;
+        let hops = buf[HEADER_LEN..]
+            .chunks_exact(HOP_LEN)
+            .take(count)
+            .map(|hop| IntMetadata::from_bytes(hop).map(|(metadata, _)| metadata))
+            .collect::<Result<_, _>>()?;
+        Ok(IntStack { hops })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(text: &str) -> Vec<u8> {
+        let digits: Vec<u8> = text.bytes().filter(u8::is_ascii_hexdigit).collect();
+        digits
+            .chunks(2)
+            .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).unwrap(), 16).unwrap())
+            .collect()
+    }
+
+    fn hop(switch: u8) -> IntMetadata {
+        let n = switch as u64;
+        IntMetadata {
+            switch_id: switch as u32,
+            ingress_port: switch as u16,
+            egress_port: 0x10 | switch as u16,
+            hop_latency_ns: 0x100 * switch as u32,
+            queue_id: switch,
+            queue_occupancy_bytes: 0x1000 * switch as u32,
+            ingress_timestamp_ns: 0x1122_3344_5500 + n,
+            egress_timestamp_ns: 0x1122_3344_5510 + n,
+        }
+    }
+
+    /// Option data of four hops, written out field by field from the INT
+    /// specification's hop metadata layout: header, then the last switch
+    /// first.
+    const REFERENCE: &str = "
+        02 07 04 00
+        00000004 0004 0014 00000400 04 004000 112233445504 112233445514
+        00000003 0003 0013 00000300 03 003000 112233445503 112233445513
+        00000002 0002 0012 00000200 02 002000 112233445502 112233445512
+        00000001 0001 0011 00000100 01 001000 112233445501 112233445511";
+
+    #[test]
+    fn four_hop_stack_matches_reference() {
+        let mut stack = IntStack::new();
+        for switch in 1..=4 {
+            assert!(stack.push_hop(hop(switch)));
+        }
+        assert!(!stack.push_hop(hop(5)));
+        let option = stack.to_geneve_option();
+        assert_eq!(option.option_class, 0x0102);
+        assert_eq!(option.option_type, 0x01);
+        assert_eq!(option.data, hex(REFERENCE));
+        assert_eq!(IntStack::from_geneve_option(&option).unwrap(), stack);
+    }
+
+    #[test]
+    fn metadata_bytes() {
+        let bytes = hop(1).to_bytes();
+        assert_eq!(
+            bytes.to_vec(),
+            hex("00000001 0001 0011 00000100 01 001000 112233445501 112233445511")
+        );
+        assert_eq!(IntMetadata::from_bytes(&bytes).unwrap(), (hop(1), HOP_LEN));
+    }
+
+    #[test]
+    fn oversized_fields_are_cut_to_their_width() {
+        let metadata = IntMetadata {
+            queue_occupancy_bytes: 0x0123_4567,
+            ingress_timestamp_ns: 0xabcd_1122_3344_5566,
+            egress_timestamp_ns: u64::MAX,
+            ..hop(1)
+        };
+        let bytes = metadata.to_bytes();
+        assert_eq!(bytes[12..16], [0x01, 0xff, 0xff, 0xff]);
+        assert_eq!(bytes[16..22], hex("112233445566")[..]);
+        assert_eq!(bytes[22..28], [0xff; 6]);
+        let (parsed, _) = IntMetadata::from_bytes(&bytes).unwrap();
+        assert_eq!(parsed.queue_occupancy_bytes, MAX_QUEUE_OCCUPANCY);
+        assert_eq!(parsed.ingress_timestamp_ns, 0x1122_3344_5566);
+        assert_eq!(parsed.egress_timestamp_ns, 0xffff_ffff_ffff);
+    }
+}
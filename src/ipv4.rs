@@ -0,0 +1,613 @@
+use std::fmt;
+use std::net::Ipv4Addr;
+
+use crate::util::{
+    Ecn, ParseError, checksum, dscp_of, ecn_of, ensure_len, read_ipv4, traffic_class,
+};
+
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |Version|  IHL  |Type of Service|          Total Length         |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |         Identification        |Flags|      Fragment Offset    |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |  Time to Live |    Protocol   |         Header Checksum       |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                       Source Address                          |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                    Destination Address                        |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                    Options                    |    Padding    |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+/// Length of the IPv4 header without options in bytes.
+pub const HEADER_LEN: usize = 20;
+
+/// Default time to live of the packets built by this crate.
+pub const DEFAULT_TTL: u8 = 64;
+
+// Flags.
+pub const FLAG_DONT_FRAGMENT: u8 = 0b010;
+pub const FLAG_MORE_FRAGMENTS: u8 = 0b001;
+
+// Option types.
+pub const OPTION_END: u8 = 0;
+pub const OPTION_NOP: u8 = 1;
+pub const OPTION_RECORD_ROUTE: u8 = 7;
+pub const OPTION_ROUTER_ALERT: u8 = 148;
+
+/// Largest length of the options in bytes (IHL of 15).
+pub const MAX_OPTIONS_LEN: usize = 40;
+
+/// Most addresses a Record Route option has room for.
+pub const MAX_RECORD_ROUTE_HOPS: u8 = 9;
+
+/// Error returned when recording a hop in the Record Route option.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordRouteError {
+    /// The packet has no Record Route option.
+    NoRecordRoute,
+    /// Every address slot of the option is filled.
+    RecordRouteFull,
+}
+
+impl fmt::Display for RecordRouteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordRouteError::NoRecordRoute => write!(f, "no record route option"),
+            RecordRouteError::RecordRouteFull => write!(f, "record route option full"),
+        }
+    }
+}
+
+impl std::error::Error for RecordRouteError {}
+
+/// Header IPv4, followed by its payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IPv4 {
+    pub version: u8,
+    pub ihl: u8,
+    pub tos: u8,
+    pub total_length: u16,
+    pub identification: u16,
+    pub flags: u8,
+    pub fragment_offset: u16,
+    pub ttl: u8,
+    pub protocol: u8,
+    pub checksum: u16,
+    pub source: Ipv4Addr,
+    pub destination: Ipv4Addr,
+    pub options: Vec<u8>,
+    pub payload: Vec<u8>,
+}
+
+impl IPv4 {
+    /// Builds a version 4 packet without options carrying `payload`, with
+    /// the lengths and header checksum filled in and the default TTL.
+    pub fn with_payload(
+        source: Ipv4Addr,
+        destination: Ipv4Addr,
+        protocol: u8,
+        payload: Vec<u8>,
+    ) -> Self {
+        IPv4 {
+            version: 4,
+            ihl: 5,
+            tos: 0,
+            total_length: (HEADER_LEN + payload.len()) as u16,
+            identification: 0,
+            flags: FLAG_DONT_FRAGMENT,
+            fragment_offset: 0,
+            ttl: DEFAULT_TTL,
+            protocol,
+            checksum: 0,
+            source,
+            destination,
+            options: Vec::new(),
+            payload,
+        }
+        .with_checksum()
+    }
+
+    /// Length of the header in bytes, from the IHL field.
+    pub fn header_len(&self) -> usize {
+        self.ihl as usize * 4
+    }
+
+    // --- GETTER METHODS ---
+
+    /// Returns the version.
+    pub fn get_version(&self) -> u8 {
+        self.version
+    }
+
+    /// Returns the IHL, in 32-bit words.
+    pub fn get_ihl(&self) -> u8 {
+        self.ihl
+    }
+
+    /// Returns the type of service byte (DSCP and ECN).
+    pub fn get_tos(&self) -> u8 {
+        self.tos
+    }
+
+    /// Returns the total length.
+    pub fn get_total_length(&self) -> u16 {
+        self.total_length
+    }
+
+    /// Returns the identification.
+    pub fn get_identification(&self) -> u16 {
+        self.identification
+    }
+
+    /// Returns the flags.
+    pub fn get_flags(&self) -> u8 {
+        self.flags
+    }
+
+    /// Returns the fragment offset, in 8-byte units.
+    pub fn get_fragment_offset(&self) -> u16 {
+        self.fragment_offset
+    }
+
+    /// Returns the time to live.
+    pub fn get_ttl(&self) -> u8 {
+        self.ttl
+    }
+
+    /// Returns the protocol of the payload.
+    pub fn get_protocol(&self) -> u8 {
+        self.protocol
+    }
+
+    /// Returns the header checksum.
+    pub fn get_checksum(&self) -> u16 {
+        self.checksum
+    }
+
+    /// Returns the source address.
+    pub fn get_source(&self) -> Ipv4Addr {
+        self.source
+    }
+
+    /// Returns the destination address.
+    pub fn get_destination(&self) -> Ipv4Addr {
+        self.destination
+    }
+
+    /// Returns the Differentiated Services codepoint, the upper 6 bits of
+    /// the TOS byte.
+    pub fn get_dscp(&self) -> u8 {
+        dscp_of(self.tos)
+    }
+
+    /// Returns the ECN codepoint, the lower 2 bits of the TOS byte.
+    pub fn get_ecn(&self) -> Ecn {
+        ecn_of(self.tos)
+    }
+
+    /// Returns the options, padding included.
+    pub fn get_options(&self) -> &Vec<u8> {
+        &self.options
+    }
+
+    /// Returns the payload.
+    pub fn get_payload(&self) -> &Vec<u8> {
+        &self.payload
+    }
+
+    // --- SETTER METHODS ---
+    //
+    // Setters leave the checksum alone; call `with_checksum` once the
+    // header is complete.
+
+    /// Sets the version.
+    pub fn set_version(mut self, version: u8) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Sets the IHL, in 32-bit words.
+    pub fn set_ihl(mut self, ihl: u8) -> Self {
+        self.ihl = ihl;
+        self
+    }
+
+    /// Sets the type of service byte.
+    pub fn set_tos(mut self, tos: u8) -> Self {
+        self.tos = tos;
+        self
+    }
+
+    /// Sets the total length.
+    pub fn set_total_length(mut self, total_length: u16) -> Self {
+        self.total_length = total_length;
+        self
+    }
+
+    /// Sets the identification.
+    pub fn set_identification(mut self, identification: u16) -> Self {
+        self.identification = identification;
+        self
+    }
+
+    /// Sets the flags.
+    pub fn set_flags(mut self, flags: u8) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Sets the fragment offset, in 8-byte units.
+    pub fn set_fragment_offset(mut self, fragment_offset: u16) -> Self {
+        self.fragment_offset = fragment_offset;
+        self
+    }
+
+    /// Sets the time to live.
+    pub fn set_ttl(mut self, ttl: u8) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Sets the protocol of the payload.
+    pub fn set_protocol(mut self, protocol: u8) -> Self {
+        self.protocol = protocol;
+        self
+    }
+
+    /// Sets the header checksum.
+    pub fn set_checksum(mut self, checksum: u16) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    /// Sets the source address.
+    pub fn set_source(mut self, source: Ipv4Addr) -> Self {
+        self.source = source;
+        self
+    }
+
+    /// Sets the destination address.
+    pub fn set_destination(mut self, destination: Ipv4Addr) -> Self {
+        self.destination = destination;
+        self
+    }
+
+    /// Sets the Differentiated Services codepoint, keeping the ECN bits.
+    pub fn set_dscp(mut self, dscp: u8) -> Self {
+        self.tos = traffic_class(dscp, self.get_ecn());
+        self
+    }
+
+    /// Sets the ECN codepoint, keeping the DSCP bits.
+    pub fn set_ecn(mut self, ecn: Ecn) -> Self {
+        self.tos = traffic_class(self.get_dscp(), ecn);
+        self
+    }
+
+    /// Sets the options, padded with end-of-list bytes to a 32-bit
+    /// boundary, and updates the IHL and total length. Options beyond
+    /// `MAX_OPTIONS_LEN` bytes are left out.
+    pub fn set_options(mut self, options: Vec<u8>) -> Self {
+        self.options = options;
+        self.options.truncate(MAX_OPTIONS_LEN);
+        self.options
+            .resize(self.options.len().div_ceil(4) * 4, OPTION_END);
+        self.ihl = ((HEADER_LEN + self.options.len()) / 4) as u8;
+        self.total_length = (self.header_len() + self.payload.len()) as u16;
+        self
+    }
+
+    /// Sets the payload and updates the total length.
+    pub fn set_payload(mut self, payload: Vec<u8>) -> Self {
+        self.payload = payload;
+        self.total_length = (self.header_len() + self.payload.len()) as u16;
+        self
+    }
+
+    // --- OPTIONS ---
+
+    /// Appends a Record Route option (RFC 791) with room for `max_hops`
+    /// addresses, all zeroed, and updates the IHL, total length and
+    /// checksum. `max_hops` is capped by `MAX_RECORD_ROUTE_HOPS` and by the
+    /// room left after the existing options, which are kept up to their
+    /// end-of-list option. The packet is unchanged if the options leave no
+    /// room for the option.
+    pub fn with_record_route(mut self, max_hops: u8) -> Self {
+        let end = self.options_end();
+        if end + 3 > MAX_OPTIONS_LEN {
+            return self;
+        }
+        self.options.truncate(end);
+        let room = ((MAX_OPTIONS_LEN - end - 3) / 4) as u8;
+        let hops = max_hops.min(MAX_RECORD_ROUTE_HOPS).min(room);
+        self.options
+            .extend_from_slice(&[OPTION_RECORD_ROUTE, 3 + hops * 4, 4]);
+        self.options
+            .resize(self.options.len() + hops as usize * 4, 0);
+        self.options
+            .resize(self.options.len().div_ceil(4) * 4, OPTION_END);
+        self.ihl = ((HEADER_LEN + self.options.len()) / 4) as u8;
+        self.total_length = (self.header_len() + self.payload.len()) as u16;
+        self.with_checksum()
+    }
+
+    /// Records `hop_ip` in the next free slot of the Record Route option,
+    /// as a router forwarding the packet does, and updates the checksum.
+    pub fn add_recorded_hop(mut self, hop_ip: Ipv4Addr) -> Result<Self, RecordRouteError> {
+        let offset = self
+            .record_route_offset()
+            .ok_or(RecordRouteError::NoRecordRoute)?;
+        let length = self.options[offset + 1] as usize;
+        let pointer = self.options[offset + 2] as usize;
+        if pointer < 4 || pointer + 3 > length {
+            return Err(RecordRouteError::RecordRouteFull);
+        }
+        let slot = offset + pointer - 1;
+        self.options[slot..slot + 4].copy_from_slice(&hop_ip.octets());
+        self.options[offset + 2] += 4;
+        Ok(self.with_checksum())
+    }
+
+    /// Returns the addresses recorded in the Record Route option, the
+    /// first hop first; empty without the option.
+    pub fn recorded_hops(&self) -> Vec<Ipv4Addr> {
+        let Some(offset) = self.record_route_offset() else {
+            return Vec::new();
+        };
+        let length = self.options[offset + 1] as usize;
+        let pointer = (self.options[offset + 2] as usize).clamp(4, length + 1);
+        self.options[offset + 3..offset + pointer - 1]
+            .chunks_exact(4)
+            .map(|slot| Ipv4Addr::new(slot[0], slot[1], slot[2], slot[3]))
+            .collect()
+    }
+
+    /// Offset in `options` of a well-formed Record Route option.
+    fn record_route_offset(&self) -> Option<usize> {
+        let mut offset = 0;
+        while offset < self.options.len() {
+            match self.options[offset] {
+                OPTION_END => return None,
+                OPTION_NOP => offset += 1,
+                option_type => {
+                    let length = *self.options.get(offset + 1)? as usize;
+                    if length < 2 || offset + length > self.options.len() {
+                        return None;
+                    }
+                    if option_type == OPTION_RECORD_ROUTE && length >= 3 {
+                        return Some(offset);
+                    }
+                    offset += length;
+                }
+            }
+        }
+        None
+    }
+
+    /// Length of the options up to their end-of-list option or padding.
+    fn options_end(&self) -> usize {
+        let mut offset = 0;
+        while offset < self.options.len() {
+            match self.options[offset] {
+                OPTION_END => return offset,
+                OPTION_NOP => offset += 1,
+                _ => match self.options.get(offset + 1) {
+                    Some(&length) if length >= 2 => offset += length as usize,
+                    _ => return offset,
+                },
+            }
+        }
+        self.options.len().min(offset)
+    }
+
+    // --- SERIALIZATION ---
+
+    /// Serializes the header (with options padded to the IHL) without the
+    /// payload.
+    pub fn header_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.options.len());
+        bytes.push((self.version & 0x0f) << 4 | (self.ihl & 0x0f));
+        bytes.push(self.tos);
+        bytes.extend_from_slice(&self.total_length.to_be_bytes());
+        bytes.extend_from_slice(&self.identification.to_be_bytes());
+        let flags_fragment = (self.flags as u16 & 0x7) << 13 | (self.fragment_offset & 0x1fff);
+        bytes.extend_from_slice(&flags_fragment.to_be_bytes());
+        bytes.push(self.ttl);
+        bytes.push(self.protocol);
+        bytes.extend_from_slice(&self.checksum.to_be_bytes());
+        bytes.extend_from_slice(&self.source.octets());
+        bytes.extend_from_slice(&self.destination.octets());
+        bytes.extend_from_slice(&self.options);
+        bytes.resize(self.header_len().max(HEADER_LEN), 0);
+        bytes
+    }
+
+    /// Serializes the header followed by the payload.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.header_bytes();
+        bytes.extend_from_slice(&self.payload);
+        bytes
+    }
+
+    /// Header checksum (with the checksum field zeroed).
+    pub fn compute_checksum(&self) -> u16 {
+        let mut header = self.header_bytes();
+        header[10..12].fill(0);
+        checksum(&header)
+    }
+
+    /// Returns the packet with its header checksum computed.
+    pub fn with_checksum(mut self) -> Self {
+        self.checksum = self.compute_checksum();
+        self
+    }
+
+    /// Parses an IPv4 packet. The payload is bounded by the total length
+    /// field; trailing bytes (e.g. Ethernet padding) are ignored.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, HEADER_LEN)?;
+        let ihl = buf[0] & 0x0f;
+        let header_len = ihl as usize * 4;
+        if header_len < HEADER_LEN {
+            return Err(ParseError::InvalidField("ihl"));
+        }
+        let total_length = u16::from_be_bytes([buf[2], buf[3]]);
+        if (total_length as usize) < header_len {
+            return Err(ParseError::InvalidField("total length"));
+        }
+        ensure_len(buf, total_length as usize)?;
+        let flags_fragment = u16::from_be_bytes([buf[6], buf[7]]);
+        Ok(IPv4 {
+            version: buf[0] >> 4,
+            ihl,
+            tos: buf[1],
+            total_length,
+            identification: u16::from_be_bytes([buf[4], buf[5]]),
+            flags: (flags_fragment >> 13) as u8,
+            fragment_offset: flags_fragment & 0x1fff,
+            ttl: buf[8],
+            protocol: buf[9],
+            checksum: u16::from_be_bytes([buf[10], buf[11]]),
+            source: read_ipv4(buf, 12),
+            destination: read_ipv4(buf, 16),
+            options: buf[HEADER_LEN..header_len].to_vec(),
+            payload: buf[header_len..total_length as usize].to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tcp::{self, TCP};
+
+    fn packet() -> IPv4 {
+        IPv4::with_payload(
+            Ipv4Addr::new(192, 0, 2, 1),
+            Ipv4Addr::new(198, 51, 100, 1),
+            17,
+            vec![0xab; 8],
+        )
+    }
+
+    fn checksum_is_valid(packet: &IPv4) -> bool {
+        checksum(&packet.header_bytes()) == 0
+    }
+
+    #[test]
+    fn three_hop_record_route() {
+        let hops = [
+            Ipv4Addr::new(10, 0, 0, 1),
+            Ipv4Addr::new(10, 0, 1, 1),
+            Ipv4Addr::new(10, 0, 2, 1),
+        ];
+        let mut packet = packet().with_record_route(3);
+        assert_eq!(&packet.options[..3], &[OPTION_RECORD_ROUTE, 15, 4]);
+        assert_eq!(packet.options.len(), 16);
+        assert_eq!(packet.ihl, 9);
+        assert_eq!(packet.total_length, 36 + 8);
+        assert!(checksum_is_valid(&packet));
+        assert!(packet.recorded_hops().is_empty());
+
+        for (i, hop) in hops.iter().enumerate() {
+            packet = packet.add_recorded_hop(*hop).unwrap();
+            assert_eq!(packet.recorded_hops(), &hops[..=i]);
+            assert!(checksum_is_valid(&packet));
+        }
+        assert_eq!(packet.options[2], 16);
+        assert_eq!(
+            packet.clone().add_recorded_hop(Ipv4Addr::new(10, 0, 3, 1)),
+            Err(RecordRouteError::RecordRouteFull)
+        );
+
+        let parsed = IPv4::from_bytes(&packet.to_bytes()).unwrap();
+        assert_eq!(parsed, packet);
+        assert_eq!(parsed.recorded_hops(), hops);
+    }
+
+    #[test]
+    fn record_route_needs_option() {
+        assert_eq!(
+            packet().add_recorded_hop(Ipv4Addr::LOCALHOST),
+            Err(RecordRouteError::NoRecordRoute)
+        );
+        assert!(packet().recorded_hops().is_empty());
+    }
+
+    #[test]
+    fn record_route_is_capped_by_option_space() {
+        let packet = packet().with_record_route(200);
+        assert_eq!(packet.options[1], 3 + MAX_RECORD_ROUTE_HOPS * 4);
+        assert_eq!(packet.options.len(), MAX_OPTIONS_LEN);
+
+        // A Router Alert option leaves room for eight addresses.
+        let packet = self::packet()
+            .set_options(vec![OPTION_ROUTER_ALERT, 4, 0, 0])
+            .with_record_route(MAX_RECORD_ROUTE_HOPS);
+        assert_eq!(&packet.options[..4], &[OPTION_ROUTER_ALERT, 4, 0, 0]);
+        assert_eq!(&packet.options[4..7], &[OPTION_RECORD_ROUTE, 35, 4]);
+        assert!(packet.options.len() <= MAX_OPTIONS_LEN);
+        assert!(checksum_is_valid(&packet));
+    }
+
+    #[test]
+    fn dscp_and_ecn_are_independent() {
+        let packet = packet().set_dscp(46).set_ecn(Ecn::Ect0);
+        assert_eq!(packet.tos, 0xba);
+        assert_eq!(packet.get_dscp(), 46);
+        assert_eq!(packet.get_ecn(), Ecn::Ect0);
+        let packet = packet.set_dscp(10);
+        assert_eq!(packet.get_ecn(), Ecn::Ect0);
+        let packet = packet.set_ecn(Ecn::Ce);
+        assert_eq!(packet.get_dscp(), 10);
+        assert_eq!(packet.tos, 10 << 2 | 0b11);
+    }
+
+    #[test]
+    fn set_options_pads_and_updates_lengths() {
+        let packet = packet().set_options(vec![OPTION_NOP, OPTION_ROUTER_ALERT, 4, 0, 0]);
+        assert_eq!(packet.get_options().len(), 8);
+        assert_eq!(&packet.get_options()[5..], &[OPTION_END; 3]);
+        assert_eq!(packet.get_ihl(), 7);
+        assert_eq!(packet.get_total_length(), 28 + 8);
+
+        let packet = packet.set_options(vec![OPTION_NOP; 50]);
+        assert_eq!(packet.get_options().len(), MAX_OPTIONS_LEN);
+        assert_eq!(packet.get_ihl(), 15);
+        let packet = packet.set_payload(vec![0; 100]).with_checksum();
+        assert_eq!(packet.get_total_length(), 160);
+        assert!(checksum_is_valid(&packet));
+        assert_eq!(IPv4::from_bytes(&packet.to_bytes()).unwrap(), packet);
+    }
+
+    #[test]
+    fn carries_tcp_segment() {
+        let tcp = TCP::new(
+            40000,
+            443,
+            1,
+            0,
+            5,
+            0,
+            0x002,
+            65535,
+            0,
+            0,
+            Vec::new(),
+            Vec::new(),
+            b"hello".to_vec(),
+        );
+        let packet = IPv4::with_payload(
+            Ipv4Addr::new(192, 0, 2, 1),
+            Ipv4Addr::new(198, 51, 100, 1),
+            tcp::IP_PROTOCOL,
+            tcp.to_bytes(),
+        );
+        assert_eq!(packet.get_total_length(), 20 + 20 + 5);
+        assert!(checksum_is_valid(&packet));
+        let parsed = IPv4::from_bytes(&packet.to_bytes()).unwrap();
+        assert_eq!(parsed.get_protocol(), tcp::IP_PROTOCOL);
+        assert_eq!(TCP::from_bytes(parsed.get_payload()).unwrap(), tcp);
+    }
+}
@@ -0,0 +1,497 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::net::Ipv6Addr;
+use std::sync::OnceLock;
+
+use siphasher::sip::SipHasher13;
+
+use crate::flow::FiveTuple;
+use crate::limits::{Limit, Limits};
+use crate::util::{Ecn, ParseError, dscp_of, ecn_of, ensure_len, read_ipv6, traffic_class};
+
+pub mod ext;
+
+use ext::ExtensionHeader;
+
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |Version| Traffic Class |           Flow Label                  |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |         Payload Length        |  Next Header  |   Hop Limit   |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                                                               |
+// +                                                               +
+// |                                                               |
+// +                         Source Address                        +
+// |                                                               |
+// +                                                               +
+// |                                                               |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                                                               |
+// +                                                               +
+// |                                                               |
+// +                      Destination Address                      +
+// |                                                               |
+// +                                                               +
+// |                                                               |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+/// Length of the fixed IPv6 header in bytes.
+pub const HEADER_LEN: usize = 40;
+
+/// Minimum link MTU every IPv6 link must support (RFC 8200 section 5).
+pub const MIN_MTU: usize = 1280;
+
+/// Default hop limit of the packets built by this crate.
+pub const DEFAULT_HOP_LIMIT: u8 = 64;
+
+/// Largest flow label (20 bits); 0 means the packet is unlabeled.
+pub const MAX_FLOW_LABEL: u32 = 0x000f_ffff;
+
+// Transport protocols whose ports key the flow label.
+const PROTOCOL_TCP: u8 = 6;
+const PROTOCOL_UDP: u8 = 17;
+const PROTOCOL_SCTP: u8 = 132;
+const PROTOCOL_UDPLITE: u8 = 136;
+
+/// Header IPv6, followed by its payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IPv6 {
+    pub version: u8,
+    pub traffic_class: u8,
+    pub flow_label: u32,
+    pub payload_length: u16,
+    pub next_header: u8,
+    pub hop_limit: u8,
+    pub source: Ipv6Addr,
+    pub destination: Ipv6Addr,
+    pub payload: Vec<u8>,
+}
+
+impl IPv6 {
+    /// Constructor to create a new instance of an IPv6 packet.
+    /// All fields must be provided at creation time.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        version: u8,
+        traffic_class: u8,
+        flow_label: u32,
+        payload_length: u16,
+        next_header: u8,
+        hop_limit: u8,
+        source: Ipv6Addr,
+        destination: Ipv6Addr,
+        payload: Vec<u8>,
+    ) -> Self {
+        IPv6 {
+            version,
+            traffic_class,
+            flow_label,
+            payload_length,
+            next_header,
+            hop_limit,
+            source,
+            destination,
+            payload,
+        }
+    }
+
+    /// Builds a version 6 packet carrying `payload`, with the payload length
+    /// filled in and the default hop limit.
+    pub fn with_payload(
+        source: Ipv6Addr,
+        destination: Ipv6Addr,
+        next_header: u8,
+        payload: Vec<u8>,
+    ) -> Self {
+        IPv6::new(
+            6,
+            0,
+            0,
+            payload.len() as u16,
+            next_header,
+            DEFAULT_HOP_LIMIT,
+            source,
+            destination,
+            payload,
+        )
+    }
+
+    // --- GETTER METHODS ---
+
+    /// Returns the version.
+    pub fn get_version(&self) -> u8 {
+        self.version
+    }
+
+    /// Returns the traffic class.
+    pub fn get_traffic_class(&self) -> u8 {
+        self.traffic_class
+    }
+
+    /// Returns the DSCP, the upper 6 bits of the traffic class.
+    pub fn get_dscp(&self) -> u8 {
+        dscp_of(self.traffic_class)
+    }
+
+    /// Returns the ECN codepoint, the lower 2 bits of the traffic class.
+    pub fn get_ecn(&self) -> Ecn {
+        ecn_of(self.traffic_class)
+    }
+
+    /// Returns the flow label.
+    pub fn get_flow_label(&self) -> u32 {
+        self.flow_label
+    }
+
+    /// Returns the payload length.
+    pub fn get_payload_length(&self) -> u16 {
+        self.payload_length
+    }
+
+    /// Returns the next header.
+    pub fn get_next_header(&self) -> u8 {
+        self.next_header
+    }
+
+    /// Returns the hop limit.
+    pub fn get_hop_limit(&self) -> u8 {
+        self.hop_limit
+    }
+
+    /// Returns the source address.
+    pub fn get_source(&self) -> Ipv6Addr {
+        self.source
+    }
+
+    /// Returns the destination address.
+    pub fn get_destination(&self) -> Ipv6Addr {
+        self.destination
+    }
+
+    /// Returns the payload.
+    pub fn get_payload(&self) -> &Vec<u8> {
+        &self.payload
+    }
+
+    // --- SETTER METHODS ---
+
+    /// Sets the version.
+    pub fn set_version(mut self, version: u8) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Sets the traffic class.
+    pub fn set_traffic_class(mut self, traffic_class: u8) -> Self {
+        self.traffic_class = traffic_class;
+        self
+    }
+
+    /// Sets the DSCP, keeping the ECN bits.
+    pub fn set_dscp(mut self, dscp: u8) -> Self {
+        self.traffic_class = traffic_class(dscp, self.get_ecn());
+        self
+    }
+
+    /// Sets the ECN codepoint, keeping the DSCP.
+    pub fn set_ecn(mut self, ecn: Ecn) -> Self {
+        self.traffic_class = traffic_class(self.get_dscp(), ecn);
+        self
+    }
+
+    /// Sets the flow label (only the low 20 bits are serialized).
+    pub fn set_flow_label(mut self, flow_label: u32) -> Self {
+        self.flow_label = flow_label;
+        self
+    }
+
+    /// Sets the payload length.
+    pub fn set_payload_length(mut self, payload_length: u16) -> Self {
+        self.payload_length = payload_length;
+        self
+    }
+
+    /// Sets the next header.
+    pub fn set_next_header(mut self, next_header: u8) -> Self {
+        self.next_header = next_header;
+        self
+    }
+
+    /// Sets the hop limit.
+    pub fn set_hop_limit(mut self, hop_limit: u8) -> Self {
+        self.hop_limit = hop_limit;
+        self
+    }
+
+    /// Sets the source address.
+    pub fn set_source(mut self, source: Ipv6Addr) -> Self {
+        self.source = source;
+        self
+    }
+
+    /// Sets the destination address.
+    pub fn set_destination(mut self, destination: Ipv6Addr) -> Self {
+        self.destination = destination;
+        self
+    }
+
+    /// Sets the payload.
+    pub fn set_payload(mut self, payload: Vec<u8>) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    // --- FLOW LABEL ---
+
+    /// Returns true if the flow label fits in its 20 bits.
+    pub fn is_flow_label_valid(&self) -> bool {
+        self.flow_label <= MAX_FLOW_LABEL
+    }
+
+    /// Deterministic flow label of a five-tuple, in `1..=MAX_FLOW_LABEL`:
+    /// the same flow gets the same label in every run. Use
+    /// `with_random_flow_label` or a keyed `FlowLabelGenerator` where the
+    /// label must not be predictable.
+    pub fn flow_label_for_5tuple(
+        src: Ipv6Addr,
+        dst: Ipv6Addr,
+        proto: u8,
+        src_port: u16,
+        dst_port: u16,
+    ) -> u32 {
+        FlowLabelGenerator::new([0; 16]).label(&FiveTuple::new(
+            proto,
+            (src, dst).into(),
+            src_port,
+            dst_port,
+        ))
+    }
+
+    /// Returns the packet with a flow label derived from its five-tuple
+    /// with a key chosen at random once per process, so that labels are
+    /// stable for a flow but cannot be predicted from outside. Ports are
+    /// read from TCP, UDP, UDP-Lite and SCTP payloads directly after the
+    /// header, and taken as 0 otherwise.
+    pub fn with_random_flow_label(mut self) -> Self {
+        static KEY: OnceLock<[u8; 16]> = OnceLock::new();
+        let key = KEY.get_or_init(|| {
+            let state = RandomState::new();
+            let mut key = [0u8; 16];
+            key[..8].copy_from_slice(&state.hash_one(0u8).to_ne_bytes());
+            key[8..].copy_from_slice(&state.hash_one(1u8).to_ne_bytes());
+            key
+        });
+        let (src_port, dst_port) = match (self.next_header, self.payload.get(..4)) {
+            (PROTOCOL_TCP | PROTOCOL_UDP | PROTOCOL_UDPLITE | PROTOCOL_SCTP, Some(ports)) => (
+                u16::from_be_bytes([ports[0], ports[1]]),
+                u16::from_be_bytes([ports[2], ports[3]]),
+            ),
+            _ => (0, 0),
+        };
+        let flow = FiveTuple::new(
+            self.next_header,
+            (self.source, self.destination).into(),
+            src_port,
+            dst_port,
+        );
+        self.flow_label = FlowLabelGenerator::new(*key).label(&flow);
+        self
+    }
+
+    // --- EXTENSION HEADERS ---
+
+    /// Inserts `headers` in front of the payload, in order. Each header
+    /// names the next one, the last naming the current next header (the
+    /// upper-layer protocol), and the next header and payload length
+    /// fields are updated.
+    pub fn with_extension_headers(mut self, headers: &[ExtensionHeader]) -> Self {
+        let mut chain = Vec::new();
+        let next_headers = headers
+            .iter()
+            .skip(1)
+            .map(ExtensionHeader::header_type)
+            .chain([self.next_header]);
+        for (header, next_header) in headers.iter().zip(next_headers) {
+            header.serialize_into(next_header, &mut chain);
+        }
+        if let Some(first) = headers.first() {
+            self.next_header = first.header_type();
+        }
+        chain.extend_from_slice(&self.payload);
+        self.payload = chain;
+        self.payload_length = self.payload.len() as u16;
+        self
+    }
+
+    /// Parses the extension headers at the start of the payload. Returns
+    /// them with the upper-layer protocol and the offset of its header in
+    /// the payload.
+    pub fn extension_headers(&self) -> Result<(Vec<ExtensionHeader>, u8, usize), ParseError> {
+        self.extension_headers_with_limits(&Limits::default())
+    }
+
+    /// Same as `extension_headers`, bounding the number of headers in the
+    /// chain and their options by `limits`.
+    pub fn extension_headers_with_limits(
+        &self,
+        limits: &Limits,
+    ) -> Result<(Vec<ExtensionHeader>, u8, usize), ParseError> {
+        let mut headers = Vec::new();
+        let mut next_header = self.next_header;
+        let mut offset = 0;
+        while ExtensionHeader::is_extension(next_header) {
+            limits.check(Limit::OptionsPerLayer, headers.len() + 1)?;
+            let (header, next, len) = ExtensionHeader::from_bytes_with_limits(
+                next_header,
+                &self.payload[offset..],
+                limits,
+            )?;
+            headers.push(header);
+            next_header = next;
+            offset += len;
+        }
+        Ok((headers, next_header, offset))
+    }
+
+    // --- SERIALIZATION ---
+
+    /// Serializes the header followed by the payload, using the payload
+    /// length field as is.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.payload.len());
+        let first_word = (self.version as u32) << 28
+            | (self.traffic_class as u32) << 20
+            | (self.flow_label & 0x000f_ffff);
+        bytes.extend_from_slice(&first_word.to_be_bytes());
+        bytes.extend_from_slice(&self.payload_length.to_be_bytes());
+        bytes.push(self.next_header);
+        bytes.push(self.hop_limit);
+        bytes.extend_from_slice(&self.source.octets());
+        bytes.extend_from_slice(&self.destination.octets());
+        bytes.extend_from_slice(&self.payload);
+        bytes
+    }
+
+    /// Parses an IPv6 packet. The payload is bounded by the payload length
+    /// field; trailing bytes (e.g. Ethernet padding) are ignored.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, HEADER_LEN)?;
+        let first_word = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]);
+        let payload_length = u16::from_be_bytes([buf[4], buf[5]]);
+        ensure_len(buf, HEADER_LEN + payload_length as usize)?;
+        Ok(IPv6 {
+            version: (first_word >> 28) as u8,
+            traffic_class: (first_word >> 20) as u8,
+            flow_label: first_word & 0x000f_ffff,
+            payload_length,
+            next_header: buf[6],
+            hop_limit: buf[7],
+            source: read_ipv6(buf, 8),
+            destination: read_ipv6(buf, 24),
+            payload: buf[HEADER_LEN..HEADER_LEN + payload_length as usize].to_vec(),
+        })
+    }
+}
+
+/// Derives flow labels from transport five-tuples (RFC 6437 section 3),
+/// so that every packet of a flow carries the same label while labels look
+/// uniformly distributed to ECMP hashers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlowLabelGenerator {
+    pub key: [u8; 16],
+}
+
+impl FlowLabelGenerator {
+    /// Constructor to create a generator with a secret key.
+    pub fn new(key: [u8; 16]) -> Self {
+        FlowLabelGenerator { key }
+    }
+
+    /// Returns the non-zero 20-bit label of `flow`.
+    pub fn label(&self, flow: &FiveTuple) -> u32 {
+        let mut bytes = Vec::new();
+        flow.serialize_into(&mut bytes);
+        let mut hasher = SipHasher13::new_with_key(&self.key);
+        hasher.write(&bytes);
+        let hash = hasher.finish();
+        let label = (hash ^ hash >> 20 ^ hash >> 40) as u32 & 0x000f_ffff;
+        // Zero means "no label", so it is never generated.
+        label.max(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn tuple(n: u16) -> (Ipv6Addr, Ipv6Addr, u8, u16, u16) {
+        (
+            Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1),
+            Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, n >> 8, n & 0xff),
+            PROTOCOL_TCP,
+            49152 + n,
+            443,
+        )
+    }
+
+    fn label(n: u16) -> u32 {
+        let (src, dst, proto, src_port, dst_port) = tuple(n);
+        IPv6::flow_label_for_5tuple(src, dst, proto, src_port, dst_port)
+    }
+
+    #[test]
+    fn same_tuple_gets_same_label() {
+        assert_eq!(label(1), label(1));
+        let (src, dst, proto, src_port, dst_port) = tuple(1);
+        let flow = FiveTuple::new(proto, (src, dst).into(), src_port, dst_port);
+        let generator = FlowLabelGenerator::new([7; 16]);
+        assert_eq!(generator.label(&flow), generator.label(&flow));
+        assert_ne!(
+            generator.label(&flow),
+            FlowLabelGenerator::new([8; 16]).label(&flow)
+        );
+    }
+
+    #[test]
+    fn labels_are_non_zero_20_bit_values() {
+        for n in 0..10_000 {
+            let label = label(n);
+            assert_ne!(label, 0);
+            assert!(label <= MAX_FLOW_LABEL);
+        }
+    }
+
+    #[test]
+    fn labels_spread_across_tuples() {
+        let labels: Vec<u32> = (0..4096).map(label).collect();
+        // About 8 collisions are expected among 4096 draws from 2^20 values.
+        let distinct: HashSet<u32> = labels.iter().copied().collect();
+        assert!(distinct.len() > 4050, "{} distinct labels", distinct.len());
+        // Each of the 16 buckets of the top four bits gets about 256.
+        let mut buckets = [0u32; 16];
+        for label in &labels {
+            buckets[(label >> 16) as usize] += 1;
+        }
+        for count in buckets {
+            assert!((160..=352).contains(&count), "{buckets:?}");
+        }
+    }
+
+    #[test]
+    fn extension_options_are_bounded() {
+        let (source, destination, ..) = tuple(1);
+        let options = vec![ext::Ipv6Option::new(0x1e, vec![0; 200])];
+        let packet = IPv6::with_payload(source, destination, PROTOCOL_UDP, Vec::new())
+            .with_extension_headers(&[ExtensionHeader::HopByHop(options)]);
+        assert!(packet.extension_headers().is_ok());
+        let limits = Limits::default().set_max_tlv_bytes(100);
+        assert_eq!(
+            packet.extension_headers_with_limits(&limits),
+            Err(ParseError::LimitExceeded(crate::limits::LimitExceeded {
+                limit: Limit::TlvBytes,
+                max: 100
+            }))
+        );
+    }
+}
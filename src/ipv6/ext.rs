@@ -0,0 +1,220 @@
+use crate::limits::{Limit, Limits};
+use crate::util::{ParseError, ensure_len};
+
+// Extension headers (RFC 8200 section 4) sit between the IPv6 header and
+// the upper-layer header, each naming the next one. Except for the
+// Fragment header they are a multiple of 8 bytes long, with a length
+// field counting 8-byte units beyond the first:
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |  Next Header  |  Hdr Ext Len  |                               |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+                               +
+// |                    Options or type-specific data              |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// Routing header:
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |  Next Header  |  Hdr Ext Len  |  Routing Type | Segments Left |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                       type-specific data                      |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// Fragment header:
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |  Next Header  |   Reserved    |      Fragment Offset    |Res|M|
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                         Identification                        |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+// Next header values of the extension headers.
+pub const HOP_BY_HOP: u8 = 0;
+pub const ROUTING: u8 = 43;
+pub const FRAGMENT: u8 = 44;
+pub const DESTINATION_OPTIONS: u8 = 60;
+
+/// Next header value ending the chain with nothing after it.
+pub const NO_NEXT_HEADER: u8 = 59;
+
+// Padding option types.
+pub const OPTION_PAD1: u8 = 0;
+pub const OPTION_PADN: u8 = 1;
+
+/// Router Alert option type (RFC 2711), carried by MLD messages.
+pub const OPTION_ROUTER_ALERT: u8 = 5;
+
+/// Length of the Fragment header in bytes.
+pub const FRAGMENT_HEADER_LEN: usize = 8;
+
+/// A TLV option of a Hop-by-Hop or Destination Options header.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Ipv6Option {
+    pub option_type: u8,
+    pub data: Vec<u8>,
+}
+
+impl Ipv6Option {
+    /// Constructor to create a new option.
+    pub fn new(option_type: u8, data: Vec<u8>) -> Self {
+        Ipv6Option { option_type, data }
+    }
+}
+
+/// Routing header; `data` follows the segments left field.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RoutingHeader {
+    pub routing_type: u8,
+    pub segments_left: u8,
+    pub data: Vec<u8>,
+}
+
+/// Fragment header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FragmentHeader {
+    /// Offset of the fragment in 8-byte units (13 bits).
+    pub fragment_offset: u16,
+    pub more_fragments: bool,
+    pub identification: u32,
+}
+
+/// An IPv6 extension header, without its next header field, which is
+/// filled in when the chain is serialized.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ExtensionHeader {
+    HopByHop(Vec<Ipv6Option>),
+    Routing(RoutingHeader),
+    Fragment(FragmentHeader),
+    DestinationOptions(Vec<Ipv6Option>),
+}
+
+impl ExtensionHeader {
+    /// Returns true if `next_header` is the value of an extension header
+    /// handled by this module.
+    pub fn is_extension(next_header: u8) -> bool {
+        matches!(
+            next_header,
+            HOP_BY_HOP | ROUTING | FRAGMENT | DESTINATION_OPTIONS
+        )
+    }
+
+    /// Next header value naming this header.
+    pub fn header_type(&self) -> u8 {
+        match self {
+            ExtensionHeader::HopByHop(_) => HOP_BY_HOP,
+            ExtensionHeader::Routing(_) => ROUTING,
+            ExtensionHeader::Fragment(_) => FRAGMENT,
+            ExtensionHeader::DestinationOptions(_) => DESTINATION_OPTIONS,
+        }
+    }
+
+    // --- SERIALIZATION ---
+
+    /// Appends the header to `bytes`, followed by `next_header`, padded
+    /// to a multiple of 8 bytes (with Pad1 and PadN options for options
+    /// headers).
+    pub fn serialize_into(&self, next_header: u8, bytes: &mut Vec<u8>) {
+        let start = bytes.len();
+        bytes.extend_from_slice(&[next_header, 0]);
+        match self {
+            ExtensionHeader::HopByHop(options) | ExtensionHeader::DestinationOptions(options) => {
+                for option in options {
+                    bytes.push(option.option_type);
+                    bytes.push(option.data.len() as u8);
+                    bytes.extend_from_slice(&option.data);
+                }
+                match (8 - (bytes.len() - start) % 8) % 8 {
+                    0 => {}
+                    1 => bytes.push(OPTION_PAD1),
+                    padding => {
+                        bytes.extend_from_slice(&[OPTION_PADN, padding as u8 - 2]);
+                        bytes.resize(bytes.len() + padding - 2, 0);
+                    }
+                }
+            }
+            ExtensionHeader::Routing(routing) => {
+                bytes.extend_from_slice(&[routing.routing_type, routing.segments_left]);
+                bytes.extend_from_slice(&routing.data);
+                bytes.resize(start + (bytes.len() - start).div_ceil(8) * 8, 0);
+            }
+            ExtensionHeader::Fragment(fragment) => {
+                let offset_flags =
+                    (fragment.fragment_offset & 0x1fff) << 3 | fragment.more_fragments as u16;
+                bytes.extend_from_slice(&offset_flags.to_be_bytes());
+                bytes.extend_from_slice(&fragment.identification.to_be_bytes());
+                return;
+            }
+        }
+        bytes[start + 1] = ((bytes.len() - start) / 8 - 1) as u8;
+    }
+
+    /// Parses a header of type `header_type`, returning it with its next
+    /// header value and the number of bytes consumed. Padding options are
+    /// dropped.
+    pub fn from_bytes(header_type: u8, buf: &[u8]) -> Result<(Self, u8, usize), ParseError> {
+        ExtensionHeader::from_bytes_with_limits(header_type, buf, &Limits::default())
+    }
+
+    /// Same as `from_bytes`, bounding the number of options by
+    /// `limits.max_options_per_layer` and the bytes of an option or of the
+    /// routing data by `limits.max_tlv_bytes`.
+    pub fn from_bytes_with_limits(
+        header_type: u8,
+        buf: &[u8],
+        limits: &Limits,
+    ) -> Result<(Self, u8, usize), ParseError> {
+        if header_type == FRAGMENT {
+            ensure_len(buf, FRAGMENT_HEADER_LEN)?;
+            let offset_flags = u16::from_be_bytes([buf[2], buf[3]]);
+            let fragment = FragmentHeader {
+                fragment_offset: offset_flags >> 3,
+                more_fragments: offset_flags & 1 != 0,
+                identification: u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]),
+            };
+            return Ok((
+                ExtensionHeader::Fragment(fragment),
+                buf[0],
+                FRAGMENT_HEADER_LEN,
+            ));
+        }
+        ensure_len(buf, 8)?;
+        let len = (buf[1] as usize + 1) * 8;
+        ensure_len(buf, len)?;
+        let header = match header_type {
+            HOP_BY_HOP => ExtensionHeader::HopByHop(parse_options(&buf[2..len], limits)?),
+            DESTINATION_OPTIONS => {
+                ExtensionHeader::DestinationOptions(parse_options(&buf[2..len], limits)?)
+            }
+            ROUTING => {
+                limits.check(Limit::TlvBytes, len - 4)?;
+                ExtensionHeader::Routing(RoutingHeader {
+                    routing_type: buf[2],
+                    segments_left: buf[3],
+                    data: buf[4..len].to_vec(),
+                })
+            }
+            _ => return Err(ParseError::InvalidField("extension header type")),
+        };
+        Ok((header, buf[0], len))
+    }
+}
+
+fn parse_options(mut buf: &[u8], limits: &Limits) -> Result<Vec<Ipv6Option>, ParseError> {
+    let mut options = Vec::new();
+    while let Some(&option_type) = buf.first() {
+        if option_type == OPTION_PAD1 {
+            buf = &buf[1..];
+            continue;
+        }
+        ensure_len(buf, 2)?;
+        let len = 2 + buf[1] as usize;
+        ensure_len(buf, len)?;
+        if option_type != OPTION_PADN {
+            limits.check(Limit::OptionsPerLayer, options.len() + 1)?;
+            limits.check(Limit::TlvBytes, len - 2)?;
+            options.push(Ipv6Option::new(option_type, buf[2..len].to_vec()));
+        }
+        buf = &buf[len..];
+    }
+    Ok(options)
+}
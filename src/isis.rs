@@ -0,0 +1,438 @@
+use crate::ethernet::MacAddr;
+use crate::util::{ParseError, ensure_len, fletcher_checksum};
+
+// IS-IS common header (ISO 10589 section 9.5)
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// | Intradomain R.|Length Indicat.|Version/Prot ID|   ID Length   |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |R|R|R|PDU Type |    Version    |   Reserved    |Max. Area Addr.|
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// The fixed fields of the PDU type follow (the Length Indicator counts
+// them with the common header), then the TLVs up to the PDU length:
+//
+//   LAN Hello   circuit type, source ID, holding time, PDU length,
+//               priority, LAN ID
+//   P2P Hello   circuit type, source ID, holding time, PDU length,
+//               local circuit ID
+//   LSP         PDU length, remaining lifetime, LSP ID, sequence number,
+//               checksum, flags
+//   CSNP        PDU length, source ID, start LSP ID, end LSP ID
+//   PSNP        PDU length, source ID
+//
+// System IDs are 6 bytes (ID Length 0 or 6); a source ID of an SNP or LAN
+// ID adds the pseudonode byte, an LSP ID the fragment byte. PDUs are
+// carried over LLC (DSAP and SSAP 0xfe) to the AllL1ISs or AllL2ISs group.
+
+/// Intradomain Routeing Protocol Discriminator of IS-IS.
+pub const PROTOCOL_DISCRIMINATOR: u8 = 0x83;
+
+/// Length of the common header in bytes.
+pub const COMMON_HEADER_LEN: usize = 8;
+
+/// Length of a system ID in bytes.
+pub const SYSTEM_ID_LEN: usize = 6;
+
+/// LLC header preceding IS-IS PDUs (DSAP, SSAP, unnumbered information).
+pub const LLC_HEADER: [u8; 3] = [0xfe, 0xfe, 0x03];
+
+/// Group of the level 1 intermediate systems.
+pub const ALL_L1_ISS: MacAddr = MacAddr::new(0x01, 0x80, 0xc2, 0x00, 0x00, 0x14);
+
+/// Group of the level 2 intermediate systems.
+pub const ALL_L2_ISS: MacAddr = MacAddr::new(0x01, 0x80, 0xc2, 0x00, 0x00, 0x15);
+
+// TLV types.
+pub const TLV_AREA_ADDRESSES: u8 = 1;
+pub const TLV_IS_NEIGHBORS: u8 = 6;
+pub const TLV_PADDING: u8 = 8;
+pub const TLV_LSP_ENTRIES: u8 = 9;
+pub const TLV_AUTHENTICATION: u8 = 10;
+pub const TLV_EXTENDED_IS_REACHABILITY: u8 = 22;
+pub const TLV_PROTOCOLS_SUPPORTED: u8 = 129;
+pub const TLV_IP_INTERFACE_ADDRESS: u8 = 132;
+pub const TLV_EXTENDED_IP_REACHABILITY: u8 = 135;
+pub const TLV_HOSTNAME: u8 = 137;
+
+/// Offset of the LSP ID, where the LSP checksum coverage starts.
+const LSP_ID_OFFSET: usize = 12;
+
+/// Offset of the LSP checksum.
+const LSP_CHECKSUM_OFFSET: usize = 24;
+
+/// PDU type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IsisPduType {
+    L1Hello = 15,
+    L2Hello = 16,
+    P2PHello = 17,
+    L1Lsp = 18,
+    L2Lsp = 20,
+    L1Csnp = 24,
+    L2Csnp = 25,
+    L1Psnp = 26,
+    L2Psnp = 27,
+}
+
+impl TryFrom<u8> for IsisPduType {
+    type Error = ParseError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            15 => Ok(IsisPduType::L1Hello),
+            16 => Ok(IsisPduType::L2Hello),
+            17 => Ok(IsisPduType::P2PHello),
+            18 => Ok(IsisPduType::L1Lsp),
+            20 => Ok(IsisPduType::L2Lsp),
+            24 => Ok(IsisPduType::L1Csnp),
+            25 => Ok(IsisPduType::L2Csnp),
+            26 => Ok(IsisPduType::L1Psnp),
+            27 => Ok(IsisPduType::L2Psnp),
+            _ => Err(ParseError::InvalidField("PDU type")),
+        }
+    }
+}
+
+/// Fixed fields following the common header, by PDU type. The PDU length
+/// is not stored: it is computed when serializing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IsisPdu {
+    LanHello {
+        circuit_type: u8,
+        source_id: [u8; 6],
+        holding_time: u16,
+        priority: u8,
+        lan_id: [u8; 7],
+    },
+    P2PHello {
+        circuit_type: u8,
+        source_id: [u8; 6],
+        holding_time: u16,
+        local_circuit_id: u8,
+    },
+    Lsp {
+        remaining_lifetime: u16,
+        lsp_id: [u8; 8],
+        sequence_number: u32,
+        checksum: u16,
+        /// Partition repair, attached, overload and IS type bits.
+        flags: u8,
+    },
+    Csnp {
+        source_id: [u8; 7],
+        start_lsp_id: [u8; 8],
+        end_lsp_id: [u8; 8],
+    },
+    Psnp {
+        source_id: [u8; 7],
+    },
+}
+
+impl IsisPdu {
+    /// Length of the fixed fields in bytes.
+    pub fn fixed_len(&self) -> usize {
+        match self {
+            IsisPdu::LanHello { .. } => 19,
+            IsisPdu::P2PHello { .. } => 12,
+            IsisPdu::Lsp { .. } => 19,
+            IsisPdu::Csnp { .. } => 25,
+            IsisPdu::Psnp { .. } => 9,
+        }
+    }
+
+    fn serialize_into(&self, bytes: &mut Vec<u8>, pdu_length: u16) {
+        match self {
+            IsisPdu::LanHello {
+                circuit_type,
+                source_id,
+                holding_time,
+                priority,
+                lan_id,
+            } => {
+                bytes.push(*circuit_type);
+                bytes.extend_from_slice(source_id);
+                bytes.extend_from_slice(&holding_time.to_be_bytes());
+                bytes.extend_from_slice(&pdu_length.to_be_bytes());
+                bytes.push(priority & 0x7f);
+                bytes.extend_from_slice(lan_id);
+            }
+            IsisPdu::P2PHello {
+                circuit_type,
+                source_id,
+                holding_time,
+                local_circuit_id,
+            } => {
+                bytes.push(*circuit_type);
+                bytes.extend_from_slice(source_id);
+                bytes.extend_from_slice(&holding_time.to_be_bytes());
+                bytes.extend_from_slice(&pdu_length.to_be_bytes());
+                bytes.push(*local_circuit_id);
+            }
+            IsisPdu::Lsp {
+                remaining_lifetime,
+                lsp_id,
+                sequence_number,
+                checksum,
+                flags,
+            } => {
+                bytes.extend_from_slice(&pdu_length.to_be_bytes());
+                bytes.extend_from_slice(&remaining_lifetime.to_be_bytes());
+                bytes.extend_from_slice(lsp_id);
+                bytes.extend_from_slice(&sequence_number.to_be_bytes());
+                bytes.extend_from_slice(&checksum.to_be_bytes());
+                bytes.push(*flags);
+            }
+            IsisPdu::Csnp {
+                source_id,
+                start_lsp_id,
+                end_lsp_id,
+            } => {
+                bytes.extend_from_slice(&pdu_length.to_be_bytes());
+                bytes.extend_from_slice(source_id);
+                bytes.extend_from_slice(start_lsp_id);
+                bytes.extend_from_slice(end_lsp_id);
+            }
+            IsisPdu::Psnp { source_id } => {
+                bytes.extend_from_slice(&pdu_length.to_be_bytes());
+                bytes.extend_from_slice(source_id);
+            }
+        }
+    }
+
+    /// Parses the fixed fields of a `pdu_type` PDU, returning them with the
+    /// PDU length.
+    fn parse(pdu_type: IsisPduType, buf: &[u8]) -> Result<(Self, u16), ParseError> {
+        let u16_at = |offset: usize| u16::from_be_bytes([buf[offset], buf[offset + 1]]);
+        let id = |offset: usize| buf[offset..offset + 8].try_into().unwrap();
+        let pdu = match pdu_type {
+            IsisPduType::L1Hello | IsisPduType::L2Hello => {
+                ensure_len(buf, 19)?;
+                let pdu = IsisPdu::LanHello {
+                    circuit_type: buf[0],
+                    source_id: buf[1..7].try_into().unwrap(),
+                    holding_time: u16_at(7),
+                    priority: buf[11] & 0x7f,
+                    lan_id: buf[12..19].try_into().unwrap(),
+                };
+                return Ok((pdu, u16_at(9)));
+            }
+            IsisPduType::P2PHello => {
+                ensure_len(buf, 12)?;
+                let pdu = IsisPdu::P2PHello {
+                    circuit_type: buf[0],
+                    source_id: buf[1..7].try_into().unwrap(),
+                    holding_time: u16_at(7),
+                    local_circuit_id: buf[11],
+                };
+                return Ok((pdu, u16_at(9)));
+            }
+            IsisPduType::L1Lsp | IsisPduType::L2Lsp => {
+                ensure_len(buf, 19)?;
+                IsisPdu::Lsp {
+                    remaining_lifetime: u16_at(2),
+                    lsp_id: id(4),
+                    sequence_number: u32::from_be_bytes([buf[12], buf[13], buf[14], buf[15]]),
+                    checksum: u16_at(16),
+                    flags: buf[18],
+                }
+            }
+            IsisPduType::L1Csnp | IsisPduType::L2Csnp => {
+                ensure_len(buf, 25)?;
+                IsisPdu::Csnp {
+                    source_id: buf[2..9].try_into().unwrap(),
+                    start_lsp_id: id(9),
+                    end_lsp_id: id(17),
+                }
+            }
+            IsisPduType::L1Psnp | IsisPduType::L2Psnp => {
+                ensure_len(buf, 9)?;
+                IsisPdu::Psnp {
+                    source_id: buf[2..9].try_into().unwrap(),
+                }
+            }
+        };
+        Ok((pdu, u16_at(0)))
+    }
+}
+
+/// A TLV of an IS-IS PDU.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct IsisTlv {
+    pub type_: u8,
+    /// Value, at most 255 bytes.
+    pub data: Vec<u8>,
+}
+
+impl IsisTlv {
+    /// Constructor to create a new TLV.
+    pub fn new(type_: u8, data: Vec<u8>) -> Self {
+        IsisTlv { type_, data }
+    }
+
+    /// Area Addresses TLV listing `areas` (e.g. `[0x49, 0x00, 0x01]`).
+    pub fn area_addresses(areas: &[&[u8]]) -> Self {
+        let mut data = Vec::new();
+        for area in areas {
+            data.push(area.len() as u8);
+            data.extend_from_slice(area);
+        }
+        IsisTlv::new(TLV_AREA_ADDRESSES, data)
+    }
+
+    /// Dynamic Hostname TLV (RFC 5301).
+    pub fn hostname(hostname: &str) -> Self {
+        IsisTlv::new(TLV_HOSTNAME, hostname.as_bytes().to_vec())
+    }
+
+    // --- SERIALIZATION ---
+
+    /// Serializes the TLV; data beyond 255 bytes is cut off.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let data = &self.data[..self.data.len().min(u8::MAX as usize)];
+        let mut bytes = Vec::with_capacity(2 + data.len());
+        bytes.push(self.type_);
+        bytes.push(data.len() as u8);
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> Result<(Self, usize), ParseError> {
+        ensure_len(buf, 2)?;
+        let len = 2 + buf[1] as usize;
+        ensure_len(buf, len)?;
+        Ok((IsisTlv::new(buf[0], buf[2..len].to_vec()), len))
+    }
+}
+
+/// IS-IS PDU: common header, fixed fields of its type and TLVs.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Isis {
+    pub intradomain_routeing_protocol_discriminator: u8,
+    /// Length of the common header and fixed fields.
+    pub length_indicator: u8,
+    pub version_protocol_id_extension: u8,
+    /// Length of the system IDs; 0 means 6.
+    pub id_length: u8,
+    pub pdu_type: IsisPduType,
+    pub version: u8,
+    pub reserved: u8,
+    /// 0 means 3.
+    pub maximum_area_addresses: u8,
+    pub pdu: IsisPdu,
+    pub tlvs: Vec<IsisTlv>,
+}
+
+impl Isis {
+    /// Builds a version 1 PDU with 6-byte system IDs; the LSP checksum, if
+    /// any, is computed.
+    pub fn new(pdu_type: IsisPduType, pdu: IsisPdu, tlvs: Vec<IsisTlv>) -> Self {
+        Isis {
+            intradomain_routeing_protocol_discriminator: PROTOCOL_DISCRIMINATOR,
+            length_indicator: (COMMON_HEADER_LEN + pdu.fixed_len()) as u8,
+            version_protocol_id_extension: 1,
+            id_length: 0,
+            pdu_type,
+            version: 1,
+            reserved: 0,
+            maximum_area_addresses: 0,
+            pdu,
+            tlvs,
+        }
+        .with_checksum()
+    }
+
+    /// Appends `tlv`, updating the LSP checksum.
+    pub fn add_tlv(mut self, tlv: IsisTlv) -> Self {
+        self.tlvs.push(tlv);
+        self.with_checksum()
+    }
+
+    /// Fletcher checksum of an LSP, from the LSP ID to the end (ISO 10589
+    /// section 7.3.11); `None` for the other PDU types.
+    pub fn compute_checksum(&self) -> Option<u16> {
+        let IsisPdu::Lsp { .. } = self.pdu else {
+            return None;
+        };
+        let mut bytes = self.to_bytes();
+        bytes[LSP_CHECKSUM_OFFSET..LSP_CHECKSUM_OFFSET + 2].fill(0);
+        Some(fletcher_checksum(
+            &bytes[LSP_ID_OFFSET..],
+            LSP_CHECKSUM_OFFSET - LSP_ID_OFFSET,
+        ))
+    }
+
+    /// Returns the PDU with its LSP checksum computed.
+    pub fn with_checksum(mut self) -> Self {
+        if let (Some(value), IsisPdu::Lsp { checksum, .. }) =
+            (self.compute_checksum(), &mut self.pdu)
+        {
+            *checksum = value;
+        }
+        self
+    }
+
+    // --- SERIALIZATION ---
+
+    /// Serializes the PDU, with the PDU length filled in.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let tlvs: Vec<u8> = self.tlvs.iter().flat_map(IsisTlv::to_bytes).collect();
+        let pdu_length = COMMON_HEADER_LEN + self.pdu.fixed_len() + tlvs.len();
+        let mut bytes = Vec::with_capacity(pdu_length);
+        bytes.extend_from_slice(&[
+            self.intradomain_routeing_protocol_discriminator,
+            self.length_indicator,
+            self.version_protocol_id_extension,
+            self.id_length,
+            self.pdu_type as u8,
+            self.version,
+            self.reserved,
+            self.maximum_area_addresses,
+        ]);
+        self.pdu.serialize_into(&mut bytes, pdu_length as u16);
+        bytes.extend_from_slice(&tlvs);
+        bytes
+    }
+
+    /// Parses a PDU up to its PDU length; trailing bytes (e.g. Ethernet
+    /// padding) are ignored.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, COMMON_HEADER_LEN)?;
+        if buf[0] != PROTOCOL_DISCRIMINATOR {
+            return Err(ParseError::InvalidField("protocol discriminator"));
+        }
+        if buf[3] != 0 && buf[3] as usize != SYSTEM_ID_LEN {
+            return Err(ParseError::InvalidField("ID length"));
+        }
+        let pdu_type = IsisPduType::try_from(buf[4] & 0x1f)?;
+        let (pdu, pdu_length) = IsisPdu::parse(pdu_type, &buf[COMMON_HEADER_LEN..])?;
+        let header_len = COMMON_HEADER_LEN + pdu.fixed_len();
+        if buf[1] as usize != header_len {
+            return Err(ParseError::InvalidField("length indicator"));
+        }
+        let pdu_length = pdu_length as usize;
+        if pdu_length < header_len {
+            return Err(ParseError::InvalidField("PDU length"));
+        }
+        ensure_len(buf, pdu_length)?;
+        let mut tlvs = Vec::new();
+        let mut offset = header_len;
+        while offset < pdu_length {
+            let (tlv, len) = IsisTlv::from_bytes(&buf[offset..pdu_length])?;
+            tlvs.push(tlv);
+            offset += len;
+        }
+        Ok(Isis {
+            intradomain_routeing_protocol_discriminator: buf[0],
+            length_indicator: buf[1],
+            version_protocol_id_extension: buf[2],
+            id_length: buf[3],
+            pdu_type,
+            version: buf[5],
+            reserved: buf[6],
+            maximum_area_addresses: buf[7],
+            pdu,
+            tlvs,
+        })
+    }
+}
@@ -0,0 +1,362 @@
+use crate::ethernet::{Ethernet, MacAddr};
+use crate::util::{ParseError, ensure_len};
+
+// LACPDU (IEEE 802.1AX section 6.4.2), carried in a Slow Protocols frame:
+//
+// +---------+---------+------------------------------------------+
+// | Subtype | Version | Actor TLV (type 1, length 20)            |
+// | 1 byte  | 1 byte  | Partner TLV (type 2, length 20)          |
+// |         |         | Collector TLV (type 3, length 16)        |
+// |         |         | Terminator TLV (type 0, length 0)        |
+// |         |         | Reserved (50 bytes)                      |
+// +---------+---------+------------------------------------------+
+//
+// Actor and partner TLVs: system priority (2), system (6), key (2), port
+// priority (2), port (2), state (1), reserved (3).
+
+/// EtherType of the Slow Protocols.
+pub const ETHERTYPE_SLOW_PROTOCOLS: u16 = 0x8809;
+
+/// Destination address of Slow Protocols frames.
+pub const SLOW_PROTOCOLS_MULTICAST: MacAddr = MacAddr::new(0x01, 0x80, 0xc2, 0x00, 0x00, 0x02);
+
+/// Slow Protocols subtype of LACP.
+pub const SUBTYPE_LACP: u8 = 1;
+
+/// Length of a LACPDU in bytes, subtype included.
+pub const LACPDU_LEN: usize = 110;
+
+// TLV types.
+const TLV_TERMINATOR: u8 = 0;
+const TLV_ACTOR: u8 = 1;
+const TLV_PARTNER: u8 = 2;
+const TLV_COLLECTOR: u8 = 3;
+
+/// Default collector max delay, in tens of microseconds.
+pub const DEFAULT_COLLECTOR_MAX_DELAY: u16 = 0;
+
+// Port state flags.
+pub const STATE_ACTIVITY: u8 = 0x01;
+pub const STATE_TIMEOUT: u8 = 0x02;
+pub const STATE_AGGREGATION: u8 = 0x04;
+pub const STATE_SYNCHRONIZATION: u8 = 0x08;
+pub const STATE_COLLECTING: u8 = 0x10;
+pub const STATE_DISTRIBUTING: u8 = 0x20;
+pub const STATE_DEFAULTED: u8 = 0x40;
+pub const STATE_EXPIRED: u8 = 0x80;
+
+/// Actor or partner information of a LACPDU.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LacpPort {
+    pub system_priority: u16,
+    pub system: MacAddr,
+    pub key: u16,
+    pub port_priority: u16,
+    pub port: u16,
+    pub state: u8,
+}
+
+impl LacpPort {
+    /// Constructor to create an active, aggregatable port.
+    pub fn new(
+        system_priority: u16,
+        system: MacAddr,
+        key: u16,
+        port_priority: u16,
+        port: u16,
+    ) -> Self {
+        LacpPort {
+            system_priority,
+            system,
+            key,
+            port_priority,
+            port,
+            state: STATE_ACTIVITY | STATE_AGGREGATION,
+        }
+    }
+
+    /// Returns true if all of `flags` are set in the state.
+    pub fn has_state(&self, flags: u8) -> bool {
+        self.state & flags == flags
+    }
+
+    /// Returns true if both ports carry the same identity and key; the
+    /// state is not compared.
+    pub fn same_identity(&self, other: &LacpPort) -> bool {
+        (
+            self.system_priority,
+            self.system,
+            self.key,
+            self.port_priority,
+            self.port,
+        ) == (
+            other.system_priority,
+            other.system,
+            other.key,
+            other.port_priority,
+            other.port,
+        )
+    }
+
+    fn serialize_into(&self, tlv_type: u8, bytes: &mut Vec<u8>) {
+        bytes.push(tlv_type);
+        bytes.push(20);
+        bytes.extend_from_slice(&self.system_priority.to_be_bytes());
+        bytes.extend_from_slice(&self.system.octets());
+        bytes.extend_from_slice(&self.key.to_be_bytes());
+        bytes.extend_from_slice(&self.port_priority.to_be_bytes());
+        bytes.extend_from_slice(&self.port.to_be_bytes());
+        bytes.push(self.state);
+        bytes.extend_from_slice(&[0; 3]);
+    }
+
+    fn from_bytes(buf: &[u8], tlv_type: u8) -> Result<Self, ParseError> {
+        ensure_len(buf, 20)?;
+        if buf[0] != tlv_type || buf[1] != 20 {
+            return Err(ParseError::InvalidField("TLV type"));
+        }
+        let mut system = [0u8; 6];
+        system.copy_from_slice(&buf[4..10]);
+        Ok(LacpPort {
+            system_priority: u16::from_be_bytes([buf[2], buf[3]]),
+            system: MacAddr(system),
+            key: u16::from_be_bytes([buf[10], buf[11]]),
+            port_priority: u16::from_be_bytes([buf[12], buf[13]]),
+            port: u16::from_be_bytes([buf[14], buf[15]]),
+            state: buf[16],
+        })
+    }
+}
+
+/// LACPDU.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lacp {
+    pub version: u8,
+    pub actor: LacpPort,
+    pub partner: LacpPort,
+    pub collector_max_delay: u16,
+}
+
+impl Lacp {
+    /// Constructor to create a version 1 LACPDU.
+    pub fn new(actor: LacpPort, partner: LacpPort) -> Self {
+        Lacp {
+            version: 1,
+            actor,
+            partner,
+            collector_max_delay: DEFAULT_COLLECTOR_MAX_DELAY,
+        }
+    }
+
+    // --- SETTER METHODS ---
+
+    pub fn set_version(mut self, version: u8) -> Self {
+        self.version = version;
+        self
+    }
+
+    pub fn set_actor(mut self, actor: LacpPort) -> Self {
+        self.actor = actor;
+        self
+    }
+
+    pub fn set_partner(mut self, partner: LacpPort) -> Self {
+        self.partner = partner;
+        self
+    }
+
+    /// Sets the collector max delay, in tens of microseconds.
+    pub fn set_collector_max_delay(mut self, collector_max_delay: u16) -> Self {
+        self.collector_max_delay = collector_max_delay;
+        self
+    }
+
+    // --- SERIALIZATION ---
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(LACPDU_LEN);
+        bytes.push(SUBTYPE_LACP);
+        bytes.push(self.version);
+        self.actor.serialize_into(TLV_ACTOR, &mut bytes);
+        self.partner.serialize_into(TLV_PARTNER, &mut bytes);
+        bytes.extend_from_slice(&[TLV_COLLECTOR, 16]);
+        bytes.extend_from_slice(&self.collector_max_delay.to_be_bytes());
+        bytes.extend_from_slice(&[0; 12]);
+        bytes.extend_from_slice(&[TLV_TERMINATOR, 0]);
+        bytes.resize(LACPDU_LEN, 0);
+        bytes
+    }
+
+    /// Parses a LACPDU from the payload of a Slow Protocols frame.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, 62)?;
+        if buf[0] != SUBTYPE_LACP {
+            return Err(ParseError::InvalidField("subtype"));
+        }
+        if buf[42] != TLV_COLLECTOR || buf[43] != 16 {
+            return Err(ParseError::InvalidField("TLV type"));
+        }
+        Ok(Lacp {
+            version: buf[1],
+            actor: LacpPort::from_bytes(&buf[2..], TLV_ACTOR)?,
+            partner: LacpPort::from_bytes(&buf[22..], TLV_PARTNER)?,
+            collector_max_delay: u16::from_be_bytes([buf[44], buf[45]]),
+        })
+    }
+
+    /// Wraps the LACPDU in a Slow Protocols frame sent from `source`.
+    pub fn to_ethernet(&self, source: MacAddr) -> Ethernet {
+        Ethernet::new(
+            SLOW_PROTOCOLS_MULTICAST,
+            source,
+            ETHERTYPE_SLOW_PROTOCOLS,
+            self.to_bytes(),
+        )
+    }
+
+    /// Parses the LACPDU of a Slow Protocols frame.
+    pub fn from_ethernet(frame: &Ethernet) -> Result<Self, ParseError> {
+        if frame.ethertype != ETHERTYPE_SLOW_PROTOCOLS {
+            return Err(ParseError::InvalidField("ethertype"));
+        }
+        Lacp::from_bytes(&frame.payload)
+    }
+}
+
+/// Simplified LACP state machine of one port, exchanging LACPDUs with a
+/// single peer until both sides are collecting and distributing.
+///
+/// As in IEEE 802.1AX, the actor selects the link and goes in sync as soon
+/// as it hears from an aggregatable peer, and starts collecting and
+/// distributing once the peer reports being in sync with a correct view of
+/// this port. Two ports reach COLLECTING and DISTRIBUTING after three
+/// LACPDUs; the last one to change then sends a fourth so the peer's view
+/// is current. Timers and churn detection are not modelled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LacpNegotiator {
+    pub actor_info: LacpPort,
+    pub partner_info: Option<LacpPort>,
+}
+
+impl LacpNegotiator {
+    /// Constructor to create a port that has not heard from a partner yet.
+    pub fn new(mut actor_info: LacpPort) -> Self {
+        actor_info.state |= STATE_DEFAULTED;
+        actor_info.state &= !(STATE_SYNCHRONIZATION | STATE_COLLECTING | STATE_DISTRIBUTING);
+        LacpNegotiator {
+            actor_info,
+            partner_info: None,
+        }
+    }
+
+    /// LACPDU describing the current actor and partner information.
+    pub fn lacpdu(&self) -> Lacp {
+        Lacp::new(self.actor_info, self.partner_info.unwrap_or_default())
+    }
+
+    /// Returns true once both sides are collecting and distributing.
+    pub fn is_complete(&self) -> bool {
+        let up = STATE_SYNCHRONIZATION | STATE_COLLECTING | STATE_DISTRIBUTING;
+        self.actor_info.has_state(up)
+            && self
+                .partner_info
+                .is_some_and(|partner| partner.has_state(up))
+    }
+
+    /// Processes a LACPDU from the peer and returns the next LACPDU to
+    /// send, or `None` if the peer already has an up-to-date view of this
+    /// port and nothing changed.
+    pub fn process_lacpdu(&mut self, incoming: &Lacp) -> Option<Lacp> {
+        let previous = self.actor_info;
+        self.partner_info = Some(incoming.actor);
+        let actor = &mut self.actor_info;
+        actor.state &= !(STATE_DEFAULTED | STATE_EXPIRED);
+
+        let selected =
+            incoming.actor.has_state(STATE_AGGREGATION) && actor.has_state(STATE_AGGREGATION);
+        if selected {
+            actor.state |= STATE_SYNCHRONIZATION;
+        } else {
+            actor.state &= !(STATE_SYNCHRONIZATION | STATE_COLLECTING | STATE_DISTRIBUTING);
+        }
+        let partner_in_sync = incoming.actor.has_state(STATE_SYNCHRONIZATION)
+            && incoming.partner.same_identity(actor);
+        if selected && partner_in_sync {
+            actor.state |= STATE_COLLECTING | STATE_DISTRIBUTING;
+        } else {
+            actor.state &= !(STATE_COLLECTING | STATE_DISTRIBUTING);
+        }
+
+        let peer_up_to_date = incoming.partner == self.actor_info;
+        if self.actor_info == previous && peer_up_to_date {
+            return None;
+        }
+        Some(self.lacpdu())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UP: u8 = STATE_SYNCHRONIZATION | STATE_COLLECTING | STATE_DISTRIBUTING;
+
+    fn negotiator(last_octet: u8, port: u16) -> LacpNegotiator {
+        let system = MacAddr::new(0x02, 0, 0, 0, 0, last_octet);
+        LacpNegotiator::new(LacpPort::new(32768, system, 10, 32768, port))
+    }
+
+    /// Sends `lacpdu` over the wire to `peer`.
+    fn deliver(peer: &mut LacpNegotiator, lacpdu: &Lacp) -> Option<Lacp> {
+        peer.process_lacpdu(&Lacp::from_bytes(&lacpdu.to_bytes()).unwrap())
+    }
+
+    #[test]
+    fn three_lacpdus_bring_both_ports_up() {
+        let mut a = negotiator(1, 1);
+        let mut b = negotiator(2, 7);
+
+        let first = a.lacpdu();
+        assert!(!first.actor.has_state(STATE_SYNCHRONIZATION));
+        let second = deliver(&mut b, &first).unwrap();
+        assert!(second.actor.has_state(STATE_SYNCHRONIZATION));
+        assert!(!second.actor.has_state(STATE_COLLECTING));
+        assert!(second.partner.same_identity(&a.actor_info));
+
+        let third = deliver(&mut a, &second).unwrap();
+        assert!(a.actor_info.has_state(UP));
+        assert!(third.actor.has_state(UP));
+
+        let update = deliver(&mut b, &third).unwrap();
+        assert!(a.actor_info.has_state(UP));
+        assert!(b.actor_info.has_state(UP));
+        assert!(b.is_complete());
+
+        assert_eq!(deliver(&mut a, &update), None);
+        assert!(a.is_complete());
+        assert!(!a.actor_info.has_state(STATE_DEFAULTED));
+    }
+
+    #[test]
+    fn individual_port_never_syncs() {
+        let mut a = negotiator(1, 1);
+        let mut b = negotiator(2, 7);
+        b.actor_info.state &= !STATE_AGGREGATION;
+        let reply = deliver(&mut b, &a.lacpdu()).unwrap();
+        assert!(!reply.actor.has_state(STATE_SYNCHRONIZATION));
+        let reply = deliver(&mut a, &reply).unwrap();
+        assert!(!reply.actor.has_state(STATE_SYNCHRONIZATION));
+        assert!(!a.is_complete());
+    }
+
+    #[test]
+    fn stale_partner_view_keeps_collecting_off() {
+        let mut a = negotiator(1, 1);
+        let mut peer = negotiator(2, 7).lacpdu();
+        peer.actor.state |= STATE_SYNCHRONIZATION;
+        peer.partner = LacpPort::new(32768, MacAddr::new(0x02, 0, 0, 0, 0, 9), 10, 32768, 1);
+        let reply = deliver(&mut a, &peer).unwrap();
+        assert!(reply.actor.has_state(STATE_SYNCHRONIZATION));
+        assert!(!reply.actor.has_state(STATE_COLLECTING));
+    }
+}
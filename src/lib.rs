@@ -1,2 +1,71 @@
 pub mod util;
+pub mod validate;
+pub mod codec;
 pub mod tcp;
+pub mod mld;
+pub mod render;
+pub mod analysis;
+pub mod sctp;
+pub mod stp;
+pub mod ipv6;
+pub mod icmpv6;
+pub mod icmpv4;
+pub mod ndp;
+pub mod flow;
+pub mod filter;
+pub mod nat;
+pub mod rewrite;
+#[cfg(feature = "test-vectors")]
+pub mod testvectors;
+pub mod fragment;
+pub mod pcap;
+pub mod gre;
+pub mod gtp;
+pub mod multicast;
+pub mod overhead;
+pub mod nsh;
+pub mod ethernet;
+pub mod dhcp;
+pub mod proxy;
+pub mod ospf;
+pub mod pim;
+pub mod pppoe;
+pub mod rtcp;
+pub mod rsvp;
+pub mod dns;
+pub mod ipv4;
+pub mod udp;
+pub mod rdma;
+pub mod zigbee;
+pub mod profinet;
+pub mod enip;
+pub mod bgp;
+pub mod rtp;
+pub mod expect;
+pub mod lacp;
+pub mod truncate;
+pub mod bfd;
+pub mod manifest;
+pub mod matcher;
+pub mod vlan;
+pub mod igmp;
+pub mod bridge;
+pub mod limits;
+pub mod mpls;
+pub mod arp;
+pub mod pcapng;
+pub mod dhcpv6;
+pub mod geneve;
+pub mod int;
+pub mod isis;
+pub mod llc;
+pub mod lldp;
+pub mod vxlan;
+pub mod transport;
+pub mod replay;
+#[cfg(feature = "faultinject")]
+pub mod faultinject;
+#[cfg(feature = "srtp")]
+pub mod srtp;
+#[cfg(all(target_os = "linux", feature = "xdp"))]
+pub mod xdp;
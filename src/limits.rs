@@ -0,0 +1,138 @@
+use std::fmt;
+
+// Bounds on the work and memory spent on untrusted input. Parsers and
+// stateful components take a `Limits` and fail with `LimitExceeded` as soon
+// as one is crossed, instead of each enforcing its own constant.
+
+/// Resource limits applied while decoding input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Limits {
+    /// Nested structures followed while decoding one item: encapsulated
+    /// headers, or DNS compression pointers within a name.
+    pub max_decode_depth: usize,
+    /// Options or extension headers decoded from a single layer.
+    pub max_options_per_layer: usize,
+    /// Bytes of a single TLV value.
+    pub max_tlv_bytes: usize,
+    /// Payload bytes a stream reassembler buffers in total.
+    pub max_reassembly_bytes: usize,
+    /// Fragments accepted for a single datagram.
+    pub max_fragments_per_datagram: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits::new()
+    }
+}
+
+impl Limits {
+    /// Constructor to create the default limits, generous enough for any
+    /// well-formed traffic.
+    pub fn new() -> Self {
+        Limits {
+            max_decode_depth: 64,
+            max_options_per_layer: 64,
+            max_tlv_bytes: 64 * 1024,
+            max_reassembly_bytes: 256 * 1024 * 1024,
+            max_fragments_per_datagram: 64,
+        }
+    }
+
+    /// Limits that never trip, for trusted input.
+    pub fn unlimited() -> Self {
+        Limits {
+            max_decode_depth: usize::MAX,
+            max_options_per_layer: usize::MAX,
+            max_tlv_bytes: usize::MAX,
+            max_reassembly_bytes: usize::MAX,
+            max_fragments_per_datagram: usize::MAX,
+        }
+    }
+
+    /// Value of `limit`.
+    pub fn get(&self, limit: Limit) -> usize {
+        match limit {
+            Limit::DecodeDepth => self.max_decode_depth,
+            Limit::OptionsPerLayer => self.max_options_per_layer,
+            Limit::TlvBytes => self.max_tlv_bytes,
+            Limit::ReassemblyBytes => self.max_reassembly_bytes,
+            Limit::FragmentsPerDatagram => self.max_fragments_per_datagram,
+        }
+    }
+
+    /// Fails if `value` is above `limit`.
+    pub fn check(&self, limit: Limit, value: usize) -> Result<(), LimitExceeded> {
+        let max = self.get(limit);
+        if value > max {
+            return Err(LimitExceeded { limit, max });
+        }
+        Ok(())
+    }
+
+    // --- SETTER METHODS ---
+
+    pub fn set_max_decode_depth(mut self, max_decode_depth: usize) -> Self {
+        self.max_decode_depth = max_decode_depth;
+        self
+    }
+
+    pub fn set_max_options_per_layer(mut self, max_options_per_layer: usize) -> Self {
+        self.max_options_per_layer = max_options_per_layer;
+        self
+    }
+
+    pub fn set_max_tlv_bytes(mut self, max_tlv_bytes: usize) -> Self {
+        self.max_tlv_bytes = max_tlv_bytes;
+        self
+    }
+
+    pub fn set_max_reassembly_bytes(mut self, max_reassembly_bytes: usize) -> Self {
+        self.max_reassembly_bytes = max_reassembly_bytes;
+        self
+    }
+
+    pub fn set_max_fragments_per_datagram(mut self, max_fragments_per_datagram: usize) -> Self {
+        self.max_fragments_per_datagram = max_fragments_per_datagram;
+        self
+    }
+}
+
+/// One of the fields of `Limits`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Limit {
+    DecodeDepth,
+    OptionsPerLayer,
+    TlvBytes,
+    ReassemblyBytes,
+    FragmentsPerDatagram,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Limit::DecodeDepth => "max_decode_depth",
+            Limit::OptionsPerLayer => "max_options_per_layer",
+            Limit::TlvBytes => "max_tlv_bytes",
+            Limit::ReassemblyBytes => "max_reassembly_bytes",
+            Limit::FragmentsPerDatagram => "max_fragments_per_datagram",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Error returned when input crosses one of the `Limits`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LimitExceeded {
+    pub limit: Limit,
+    /// Value of the limit that tripped.
+    pub max: usize,
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} of {} exceeded", self.limit, self.max)
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
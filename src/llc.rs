@@ -0,0 +1,210 @@
+use crate::ethernet::{Ethernet, MacAddr};
+use crate::util::{ParseError, ensure_len};
+
+// IEEE 802.3 frames put the length of their payload where Ethernet II
+// has the EtherType: values up to 1500 are lengths, values from 0x0600
+// EtherTypes. The payload starts with an 802.2 LLC header:
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |     DSAP      |     SSAP      |    Control    |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// The control field is 1 byte for unnumbered (U) frames and 2 bytes for
+// information (I) and supervisory (S) frames. With both SAPs 0xaa, a SNAP
+// header names the protocol with an OUI and an EtherType-like ID:
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                      OUI                      |          Protocol ID          |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+/// Largest payload length of an 802.3 frame; larger values of the field
+/// are EtherTypes.
+pub const MAX_LENGTH: u16 = 1500;
+
+// SAPs.
+pub const SAP_STP: u8 = 0x42;
+pub const SAP_SNAP: u8 = 0xaa;
+pub const SAP_ISIS: u8 = 0xfe;
+
+/// Control field of unnumbered information frames.
+pub const CONTROL_UI: u8 = 0x03;
+
+/// Length of the SNAP header in bytes.
+pub const SNAP_LEN: usize = 5;
+
+/// OUI of SNAP headers carrying an EtherType (RFC 1042).
+pub const OUI_ETHERTYPE: [u8; 3] = [0, 0, 0];
+
+/// Group address of the Spanning Tree Protocol BPDUs.
+pub const STP_MULTICAST: MacAddr = MacAddr([0x01, 0x80, 0xc2, 0x00, 0x00, 0x00]);
+
+/// Returns true if the type field of an Ethernet header holds a length,
+/// i.e. the frame is 802.3 with an LLC header.
+pub fn is_length(type_or_length: u16) -> bool {
+    type_or_length <= MAX_LENGTH
+}
+
+/// 802.2 LLC header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Llc {
+    pub dsap: u8,
+    pub ssap: u8,
+    /// Control field; only the low byte is sent for U frames.
+    pub control: u16,
+}
+
+impl Llc {
+    /// Constructor to create a new LLC header.
+    pub fn new(dsap: u8, ssap: u8, control: u16) -> Self {
+        Llc {
+            dsap,
+            ssap,
+            control,
+        }
+    }
+
+    /// Unnumbered information header between `sap` and itself.
+    pub fn ui(sap: u8) -> Self {
+        Llc::new(sap, sap, CONTROL_UI as u16)
+    }
+
+    /// Returns true if the control field is 1 byte long (U frame).
+    pub fn is_unnumbered(&self) -> bool {
+        self.control & 0x03 == 0x03
+    }
+
+    /// Length of the header in bytes.
+    pub fn header_len(&self) -> usize {
+        if self.is_unnumbered() { 3 } else { 4 }
+    }
+
+    // --- SERIALIZATION ---
+
+    pub fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&[self.dsap, self.ssap]);
+        if self.is_unnumbered() {
+            bytes.push(self.control as u8);
+        } else {
+            bytes.extend_from_slice(&self.control.to_le_bytes());
+        }
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> Result<(Self, usize), ParseError> {
+        ensure_len(buf, 3)?;
+        let mut llc = Llc::new(buf[0], buf[1], buf[2] as u16);
+        if !llc.is_unnumbered() {
+            ensure_len(buf, 4)?;
+            llc.control = u16::from_le_bytes([buf[2], buf[3]]);
+        }
+        Ok((llc, llc.header_len()))
+    }
+}
+
+/// SNAP header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Snap {
+    pub oui: [u8; 3],
+    pub protocol_id: u16,
+}
+
+impl Snap {
+    /// Constructor to create a new SNAP header.
+    pub fn new(oui: [u8; 3], protocol_id: u16) -> Self {
+        Snap { oui, protocol_id }
+    }
+
+    /// SNAP header carrying `ethertype` (RFC 1042).
+    pub fn ethertype(ethertype: u16) -> Self {
+        Snap::new(OUI_ETHERTYPE, ethertype)
+    }
+
+    // --- SERIALIZATION ---
+
+    pub fn to_bytes(&self) -> [u8; SNAP_LEN] {
+        let [a, b, c] = self.oui;
+        let [d, e] = self.protocol_id.to_be_bytes();
+        [a, b, c, d, e]
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> Result<(Self, usize), ParseError> {
+        ensure_len(buf, SNAP_LEN)?;
+        let snap = Snap::new(
+            [buf[0], buf[1], buf[2]],
+            u16::from_be_bytes([buf[3], buf[4]]),
+        );
+        Ok((snap, SNAP_LEN))
+    }
+}
+
+/// Payload of an 802.3 frame: LLC header, SNAP header when both SAPs are
+/// SNAP, and the data following them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LlcFrame {
+    pub llc: Llc,
+    pub snap: Option<Snap>,
+    pub payload: Vec<u8>,
+}
+
+impl LlcFrame {
+    /// Constructor to create a new LLC frame.
+    pub fn new(llc: Llc, snap: Option<Snap>, payload: Vec<u8>) -> Self {
+        LlcFrame { llc, snap, payload }
+    }
+
+    /// UI frame between `sap` and itself, e.g. `SAP_STP` for BPDUs.
+    pub fn ui(sap: u8, payload: Vec<u8>) -> Self {
+        LlcFrame::new(Llc::ui(sap), None, payload)
+    }
+
+    /// SNAP frame carrying `payload` of protocol `snap`.
+    pub fn snap(snap: Snap, payload: Vec<u8>) -> Self {
+        LlcFrame::new(Llc::ui(SAP_SNAP), Some(snap), payload)
+    }
+
+    /// Wraps the frame in an 802.3 frame, with the type field holding the
+    /// length of the LLC frame.
+    pub fn to_ethernet(&self, destination: MacAddr, source: MacAddr) -> Ethernet {
+        let bytes = self.to_bytes();
+        Ethernet::new(destination, source, bytes.len() as u16, bytes)
+    }
+
+    /// Parses the LLC frame of an 802.3 frame, bounded by its length field
+    /// so that padding is dropped. Fails on Ethernet II frames.
+    pub fn from_ethernet(frame: &Ethernet) -> Result<Self, ParseError> {
+        if !is_length(frame.ethertype) {
+            return Err(ParseError::InvalidField("length"));
+        }
+        let len = frame.ethertype as usize;
+        ensure_len(&frame.payload, len)?;
+        LlcFrame::from_bytes(&frame.payload[..len])
+    }
+
+    // --- SERIALIZATION ---
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let snap_len = if self.snap.is_some() { SNAP_LEN } else { 0 };
+        let mut bytes = Vec::with_capacity(self.llc.header_len() + snap_len + self.payload.len());
+        self.llc.serialize_into(&mut bytes);
+        if let Some(snap) = self.snap {
+            bytes.extend_from_slice(&snap.to_bytes());
+        }
+        bytes.extend_from_slice(&self.payload);
+        bytes
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        let (llc, mut offset) = Llc::from_bytes(buf)?;
+        let snap = if llc.dsap == SAP_SNAP && llc.ssap == SAP_SNAP {
+            let (snap, len) = Snap::from_bytes(&buf[offset..])?;
+            offset += len;
+            Some(snap)
+        } else {
+            None
+        };
+        Ok(LlcFrame {
+            llc,
+            snap,
+            payload: buf[offset..].to_vec(),
+        })
+    }
+}
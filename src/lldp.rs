@@ -0,0 +1,518 @@
+use crate::ethernet::{Ethernet, MacAddr};
+use crate::limits::{Limit, Limits};
+use crate::util::{ParseError, ensure_len};
+
+// LLDP TLV (IEEE 802.1AB section 8.4)
+//
+//  0                   1
+//  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |  TLV Type   |   TLV Length    |   Value (0 to 511 bytes)    ~
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// An LLDPDU is Chassis ID, Port ID and TTL, optional TLVs, then End of
+// LLDPDU. Organizationally specific TLVs (type 127) start their value
+// with an OUI and a subtype:
+//
+//   00-80-C2  IEEE 802.1   1 Port VLAN ID, 3 VLAN Name, 4 Protocol Identity
+//   00-12-0F  IEEE 802.3   1 MAC/PHY Configuration/Status, 4 Maximum Frame
+//                          Size
+//   00-00-5E  IANA         1 MUD URL (RFC 8520)
+
+/// EtherType of LLDP.
+pub const ETHERTYPE: u16 = 0x88cc;
+
+/// Nearest bridge group, the usual destination of LLDPDUs.
+pub const NEAREST_BRIDGE: MacAddr = MacAddr::new(0x01, 0x80, 0xc2, 0x00, 0x00, 0x0e);
+
+/// Largest TLV value, in bytes (9-bit length).
+pub const MAX_VALUE_LEN: usize = 511;
+
+// TLV types.
+pub const TLV_END: u8 = 0;
+pub const TLV_CHASSIS_ID: u8 = 1;
+pub const TLV_PORT_ID: u8 = 2;
+pub const TLV_TTL: u8 = 3;
+pub const TLV_PORT_DESCRIPTION: u8 = 4;
+pub const TLV_SYSTEM_NAME: u8 = 5;
+pub const TLV_SYSTEM_DESCRIPTION: u8 = 6;
+pub const TLV_SYSTEM_CAPABILITIES: u8 = 7;
+pub const TLV_MANAGEMENT_ADDRESS: u8 = 8;
+pub const TLV_ORGANIZATION_SPECIFIC: u8 = 127;
+
+// Chassis ID subtypes.
+pub const CHASSIS_ID_CHASSIS_COMPONENT: u8 = 1;
+pub const CHASSIS_ID_INTERFACE_ALIAS: u8 = 2;
+pub const CHASSIS_ID_PORT_COMPONENT: u8 = 3;
+pub const CHASSIS_ID_MAC_ADDRESS: u8 = 4;
+pub const CHASSIS_ID_NETWORK_ADDRESS: u8 = 5;
+pub const CHASSIS_ID_INTERFACE_NAME: u8 = 6;
+pub const CHASSIS_ID_LOCAL: u8 = 7;
+
+// Port ID subtypes.
+pub const PORT_ID_INTERFACE_ALIAS: u8 = 1;
+pub const PORT_ID_PORT_COMPONENT: u8 = 2;
+pub const PORT_ID_MAC_ADDRESS: u8 = 3;
+pub const PORT_ID_NETWORK_ADDRESS: u8 = 4;
+pub const PORT_ID_INTERFACE_NAME: u8 = 5;
+pub const PORT_ID_AGENT_CIRCUIT_ID: u8 = 6;
+pub const PORT_ID_LOCAL: u8 = 7;
+
+// Organizationally unique identifiers.
+pub const OUI_IEEE_802_1: [u8; 3] = [0x00, 0x80, 0xc2];
+pub const OUI_IEEE_802_3: [u8; 3] = [0x00, 0x12, 0x0f];
+pub const OUI_IANA: [u8; 3] = [0x00, 0x00, 0x5e];
+
+// IEEE 802.1 subtypes.
+const DOT1_PORT_VLAN_ID: u8 = 1;
+const DOT1_VLAN_NAME: u8 = 3;
+const DOT1_PROTOCOL_IDENTITY: u8 = 4;
+
+// IEEE 802.3 subtypes.
+const DOT3_MAC_PHY_CONFIG_STATUS: u8 = 1;
+const DOT3_MAX_FRAME_SIZE: u8 = 4;
+
+// IANA subtypes.
+const IANA_MUD_URL: u8 = 1;
+
+/// An LLDP TLV.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LldpTlv {
+    /// Type (7 bits).
+    pub tlv_type: u8,
+    pub value: Vec<u8>,
+}
+
+impl LldpTlv {
+    /// Constructor to create a new TLV.
+    pub fn new(tlv_type: u8, value: Vec<u8>) -> Self {
+        LldpTlv { tlv_type, value }
+    }
+
+    /// Chassis ID TLV holding a MAC address.
+    pub fn chassis_id_mac(mac: MacAddr) -> Self {
+        let mut value = vec![CHASSIS_ID_MAC_ADDRESS];
+        value.extend_from_slice(&mac.octets());
+        LldpTlv::new(TLV_CHASSIS_ID, value)
+    }
+
+    /// Port ID TLV holding an interface name.
+    pub fn port_id_name(name: &str) -> Self {
+        let mut value = vec![PORT_ID_INTERFACE_NAME];
+        value.extend_from_slice(name.as_bytes());
+        LldpTlv::new(TLV_PORT_ID, value)
+    }
+
+    /// Time To Live TLV, in seconds.
+    pub fn ttl(seconds: u16) -> Self {
+        LldpTlv::new(TLV_TTL, seconds.to_be_bytes().to_vec())
+    }
+
+    /// System Name TLV.
+    pub fn system_name(name: &str) -> Self {
+        LldpTlv::new(TLV_SYSTEM_NAME, name.as_bytes().to_vec())
+    }
+
+    /// End of LLDPDU TLV.
+    pub fn end() -> Self {
+        LldpTlv::new(TLV_END, Vec::new())
+    }
+
+    /// Organizationally specific TLV.
+    pub fn org_specific(oui: [u8; 3], subtype: u8, info: &[u8]) -> Self {
+        let mut value = Vec::with_capacity(4 + info.len());
+        value.extend_from_slice(&oui);
+        value.push(subtype);
+        value.extend_from_slice(info);
+        LldpTlv::new(TLV_ORGANIZATION_SPECIFIC, value)
+    }
+
+    /// Decodes an organizationally specific TLV according to its OUI and
+    /// subtype. Returns `None` for other TLV types and for malformed values
+    /// of the known subtypes.
+    pub fn decode_org_specific(&self) -> Option<OrgTlv> {
+        if self.tlv_type != TLV_ORGANIZATION_SPECIFIC || self.value.len() < 4 {
+            return None;
+        }
+        let oui = [self.value[0], self.value[1], self.value[2]];
+        let subtype = self.value[3];
+        let info = &self.value[4..];
+        let decoded = match (oui, subtype) {
+            (OUI_IEEE_802_1, DOT1_PORT_VLAN_ID | DOT1_VLAN_NAME | DOT1_PROTOCOL_IDENTITY) => {
+                OrgTlv::Dot1(Dot1Tlv::decode(subtype, info)?)
+            }
+            (OUI_IEEE_802_3, DOT3_MAC_PHY_CONFIG_STATUS | DOT3_MAX_FRAME_SIZE) => {
+                OrgTlv::Dot3(Dot3Tlv::decode(subtype, info)?)
+            }
+            (OUI_IANA, IANA_MUD_URL) => {
+                OrgTlv::Iana(IanaTlv::MudUrl(String::from_utf8(info.to_vec()).ok()?))
+            }
+            _ => OrgTlv::Unknown {
+                oui,
+                subtype,
+                info: info.to_vec(),
+            },
+        };
+        Some(decoded)
+    }
+
+    /// Decodes the TLV according to its type. TLVs of other types and
+    /// malformed values are returned as `TypedTlv::Raw`.
+    pub fn decode(&self) -> TypedTlv {
+        let text = || String::from_utf8(self.value.clone()).ok();
+        let decoded = match self.tlv_type {
+            TLV_CHASSIS_ID | TLV_PORT_ID => self.value.split_first().map(|(&subtype, id)| {
+                if self.tlv_type == TLV_CHASSIS_ID {
+                    TypedTlv::ChassisId {
+                        subtype,
+                        id: id.to_vec(),
+                    }
+                } else {
+                    TypedTlv::PortId {
+                        subtype,
+                        id: id.to_vec(),
+                    }
+                }
+            }),
+            TLV_TTL => <[u8; 2]>::try_from(&self.value[..])
+                .ok()
+                .map(|ttl| TypedTlv::Ttl(u16::from_be_bytes(ttl))),
+            TLV_PORT_DESCRIPTION => text().map(TypedTlv::PortDescription),
+            TLV_SYSTEM_NAME => text().map(TypedTlv::SystemName),
+            TLV_SYSTEM_DESCRIPTION => text().map(TypedTlv::SystemDescription),
+            TLV_ORGANIZATION_SPECIFIC => self.decode_org_specific().map(TypedTlv::OrgSpecific),
+            _ => None,
+        };
+        decoded.unwrap_or_else(|| TypedTlv::Raw(self.clone()))
+    }
+
+    // --- SERIALIZATION ---
+
+    /// Serializes the TLV; a value beyond `MAX_VALUE_LEN` is cut off.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let value = &self.value[..self.value.len().min(MAX_VALUE_LEN)];
+        let header = (self.tlv_type as u16 & 0x7f) << 9 | value.len() as u16;
+        let mut bytes = Vec::with_capacity(2 + value.len());
+        bytes.extend_from_slice(&header.to_be_bytes());
+        bytes.extend_from_slice(value);
+        bytes
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> Result<(Self, usize), ParseError> {
+        ensure_len(buf, 2)?;
+        let header = u16::from_be_bytes([buf[0], buf[1]]);
+        let len = 2 + (header & 0x01ff) as usize;
+        ensure_len(buf, len)?;
+        Ok((LldpTlv::new((header >> 9) as u8, buf[2..len].to_vec()), len))
+    }
+}
+
+/// Decoded LLDP TLV.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TypedTlv {
+    /// Chassis ID of the given subtype, e.g. `CHASSIS_ID_MAC_ADDRESS`.
+    ChassisId {
+        subtype: u8,
+        id: Vec<u8>,
+    },
+    /// Port ID of the given subtype, e.g. `PORT_ID_INTERFACE_NAME`.
+    PortId {
+        subtype: u8,
+        id: Vec<u8>,
+    },
+    /// Time To Live in seconds; 0 withdraws the information.
+    Ttl(u16),
+    PortDescription(String),
+    SystemName(String),
+    SystemDescription(String),
+    OrgSpecific(OrgTlv),
+    /// Any TLV, sent as is.
+    Raw(LldpTlv),
+}
+
+impl TypedTlv {
+    /// Encodes the TLV.
+    pub fn to_tlv(&self) -> LldpTlv {
+        let id_tlv = |tlv_type: u8, subtype: u8, id: &[u8]| {
+            let mut value = vec![subtype];
+            value.extend_from_slice(id);
+            LldpTlv::new(tlv_type, value)
+        };
+        match self {
+            TypedTlv::ChassisId { subtype, id } => id_tlv(TLV_CHASSIS_ID, *subtype, id),
+            TypedTlv::PortId { subtype, id } => id_tlv(TLV_PORT_ID, *subtype, id),
+            TypedTlv::Ttl(seconds) => LldpTlv::ttl(*seconds),
+            TypedTlv::PortDescription(text) => {
+                LldpTlv::new(TLV_PORT_DESCRIPTION, text.as_bytes().to_vec())
+            }
+            TypedTlv::SystemName(name) => LldpTlv::system_name(name),
+            TypedTlv::SystemDescription(text) => {
+                LldpTlv::new(TLV_SYSTEM_DESCRIPTION, text.as_bytes().to_vec())
+            }
+            TypedTlv::OrgSpecific(tlv) => tlv.to_tlv(),
+            TypedTlv::Raw(tlv) => tlv.clone(),
+        }
+    }
+}
+
+/// Decoded organizationally specific TLV.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum OrgTlv {
+    Dot1(Dot1Tlv),
+    Dot3(Dot3Tlv),
+    Iana(IanaTlv),
+    /// OUI or subtype without a decoder.
+    Unknown {
+        oui: [u8; 3],
+        subtype: u8,
+        info: Vec<u8>,
+    },
+}
+
+impl OrgTlv {
+    /// Encodes the TLV.
+    pub fn to_tlv(&self) -> LldpTlv {
+        match self {
+            OrgTlv::Dot1(tlv) => tlv.to_tlv(),
+            OrgTlv::Dot3(tlv) => tlv.to_tlv(),
+            OrgTlv::Iana(IanaTlv::MudUrl(url)) => {
+                LldpTlv::org_specific(OUI_IANA, IANA_MUD_URL, url.as_bytes())
+            }
+            OrgTlv::Unknown { oui, subtype, info } => LldpTlv::org_specific(*oui, *subtype, info),
+        }
+    }
+}
+
+/// IEEE 802.1 organizationally specific TLV (IEEE 802.1Q annex D).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Dot1Tlv {
+    /// PVID of the port; 0 if the port does not support it.
+    PortVlanId(u16),
+    /// VLAN IDs and names; a TLV usually carries one.
+    VlanNameList(Vec<(u16, String)>),
+    /// Leading bytes of the frames of a protocol (e.g. an LLC header).
+    ProtocolIdentity(Vec<u8>),
+}
+
+impl Dot1Tlv {
+    fn decode(subtype: u8, info: &[u8]) -> Option<Self> {
+        match subtype {
+            DOT1_PORT_VLAN_ID => Some(Dot1Tlv::PortVlanId(u16::from_be_bytes(
+                info.get(..2)?.try_into().ok()?,
+            ))),
+            DOT1_VLAN_NAME => {
+                let mut names = Vec::new();
+                let mut rest = info;
+                while !rest.is_empty() {
+                    let vlan_id = u16::from_be_bytes(rest.get(..2)?.try_into().ok()?);
+                    let len = *rest.get(2)? as usize;
+                    let name = String::from_utf8(rest.get(3..3 + len)?.to_vec()).ok()?;
+                    names.push((vlan_id, name));
+                    rest = &rest[3 + len..];
+                }
+                Some(Dot1Tlv::VlanNameList(names))
+            }
+            DOT1_PROTOCOL_IDENTITY => {
+                let len = *info.first()? as usize;
+                Some(Dot1Tlv::ProtocolIdentity(info.get(1..1 + len)?.to_vec()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Encodes the TLV; VLAN names are cut off at 32 bytes.
+    pub fn to_tlv(&self) -> LldpTlv {
+        match self {
+            Dot1Tlv::PortVlanId(vlan_id) => {
+                LldpTlv::org_specific(OUI_IEEE_802_1, DOT1_PORT_VLAN_ID, &vlan_id.to_be_bytes())
+            }
+            Dot1Tlv::VlanNameList(names) => {
+                let mut info = Vec::new();
+                for (vlan_id, name) in names {
+                    let name = &name.as_bytes()[..name.len().min(32)];
+                    info.extend_from_slice(&vlan_id.to_be_bytes());
+                    info.push(name.len() as u8);
+                    info.extend_from_slice(name);
+                }
+                LldpTlv::org_specific(OUI_IEEE_802_1, DOT1_VLAN_NAME, &info)
+            }
+            Dot1Tlv::ProtocolIdentity(identity) => {
+                let mut info = vec![identity.len() as u8];
+                info.extend_from_slice(identity);
+                LldpTlv::org_specific(OUI_IEEE_802_1, DOT1_PROTOCOL_IDENTITY, &info)
+            }
+        }
+    }
+}
+
+/// IEEE 802.3 organizationally specific TLV (IEEE 802.3 clause 79).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Dot3Tlv {
+    MacPhyConfigStatus {
+        autoneg_support: bool,
+        autoneg_enabled: bool,
+        /// PMD auto-negotiation advertised capability bits.
+        advertised_cap: u16,
+        /// Operational MAU type (RFC 4836).
+        operational_mau: u16,
+    },
+    MaxFrameSize(u16),
+}
+
+impl Dot3Tlv {
+    fn decode(subtype: u8, info: &[u8]) -> Option<Self> {
+        match subtype {
+            DOT3_MAC_PHY_CONFIG_STATUS => {
+                let info = info.get(..5)?;
+                Some(Dot3Tlv::MacPhyConfigStatus {
+                    autoneg_support: info[0] & 0x01 != 0,
+                    autoneg_enabled: info[0] & 0x02 != 0,
+                    advertised_cap: u16::from_be_bytes([info[1], info[2]]),
+                    operational_mau: u16::from_be_bytes([info[3], info[4]]),
+                })
+            }
+            DOT3_MAX_FRAME_SIZE => Some(Dot3Tlv::MaxFrameSize(u16::from_be_bytes(
+                info.get(..2)?.try_into().ok()?,
+            ))),
+            _ => None,
+        }
+    }
+
+    /// Encodes the TLV.
+    pub fn to_tlv(&self) -> LldpTlv {
+        match self {
+            Dot3Tlv::MacPhyConfigStatus {
+                autoneg_support,
+                autoneg_enabled,
+                advertised_cap,
+                operational_mau,
+            } => {
+                let mut info = vec![(*autoneg_enabled as u8) << 1 | *autoneg_support as u8];
+                info.extend_from_slice(&advertised_cap.to_be_bytes());
+                info.extend_from_slice(&operational_mau.to_be_bytes());
+                LldpTlv::org_specific(OUI_IEEE_802_3, DOT3_MAC_PHY_CONFIG_STATUS, &info)
+            }
+            Dot3Tlv::MaxFrameSize(size) => {
+                LldpTlv::org_specific(OUI_IEEE_802_3, DOT3_MAX_FRAME_SIZE, &size.to_be_bytes())
+            }
+        }
+    }
+}
+
+/// IANA organizationally specific TLV.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum IanaTlv {
+    /// Manufacturer Usage Description URL.
+    MudUrl(String),
+}
+
+/// LLDPDU: a list of TLVs, the End TLV excluded.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Lldp {
+    pub tlvs: Vec<LldpTlv>,
+}
+
+impl Lldp {
+    /// Constructor to create an LLDPDU with the mandatory TLVs.
+    pub fn new(chassis: MacAddr, port: &str, ttl: u16) -> Self {
+        Lldp {
+            tlvs: vec![
+                LldpTlv::chassis_id_mac(chassis),
+                LldpTlv::port_id_name(port),
+                LldpTlv::ttl(ttl),
+            ],
+        }
+    }
+
+    /// Constructor to create an LLDPDU from decoded TLVs, in order; the
+    /// mandatory TLVs are not checked.
+    pub fn from_typed(tlvs: &[TypedTlv]) -> Self {
+        Lldp {
+            tlvs: tlvs.iter().map(TypedTlv::to_tlv).collect(),
+        }
+    }
+
+    /// Appends `tlv`.
+    pub fn add_tlv(mut self, tlv: LldpTlv) -> Self {
+        self.tlvs.push(tlv);
+        self
+    }
+
+    /// Appends the encoding of `tlv`.
+    pub fn add_typed_tlv(self, tlv: TypedTlv) -> Self {
+        self.add_tlv(tlv.to_tlv())
+    }
+
+    /// Decodes every TLV.
+    pub fn typed_tlvs(&self) -> Vec<TypedTlv> {
+        self.tlvs.iter().map(LldpTlv::decode).collect()
+    }
+
+    /// Time To Live in seconds, from the first TTL TLV.
+    pub fn ttl(&self) -> Option<u16> {
+        self.typed_tlvs().into_iter().find_map(|tlv| match tlv {
+            TypedTlv::Ttl(seconds) => Some(seconds),
+            _ => None,
+        })
+    }
+
+    /// System name, from the first System Name TLV.
+    pub fn system_name(&self) -> Option<String> {
+        self.typed_tlvs().into_iter().find_map(|tlv| match tlv {
+            TypedTlv::SystemName(name) => Some(name),
+            _ => None,
+        })
+    }
+
+    /// Decodes the organizationally specific TLVs, skipping malformed ones.
+    pub fn org_specific_tlvs(&self) -> impl Iterator<Item = OrgTlv> + '_ {
+        self.tlvs.iter().filter_map(LldpTlv::decode_org_specific)
+    }
+
+    /// Wraps the LLDPDU in a frame to the nearest bridge group.
+    pub fn to_ethernet(&self, source: MacAddr) -> Ethernet {
+        Ethernet::new(NEAREST_BRIDGE, source, ETHERTYPE, self.to_bytes())
+    }
+
+    /// Parses the LLDPDU of an LLDP frame.
+    pub fn from_ethernet(frame: &Ethernet) -> Result<Self, ParseError> {
+        if frame.ethertype != ETHERTYPE {
+            return Err(ParseError::InvalidField("ethertype"));
+        }
+        Lldp::from_bytes(&frame.payload)
+    }
+
+    // --- SERIALIZATION ---
+
+    /// Serializes the TLVs followed by End of LLDPDU.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = self.tlvs.iter().flat_map(LldpTlv::to_bytes).collect();
+        bytes.extend_from_slice(&LldpTlv::end().to_bytes());
+        bytes
+    }
+
+    /// Parses TLVs up to End of LLDPDU, or to the end of `buf` if it is
+    /// missing; trailing bytes are ignored.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        Lldp::from_bytes_with_limits(buf, &Limits::default())
+    }
+
+    /// Same as `from_bytes`, bounding the number of TLVs by
+    /// `limits.max_options_per_layer` and their values by
+    /// `limits.max_tlv_bytes`.
+    pub fn from_bytes_with_limits(buf: &[u8], limits: &Limits) -> Result<Self, ParseError> {
+        let mut tlvs = Vec::new();
+        let mut offset = 0;
+        while offset < buf.len() {
+            let (tlv, len) = LldpTlv::from_bytes(&buf[offset..])?;
+            offset += len;
+            if tlv.tlv_type == TLV_END {
+                break;
+            }
+            limits.check(Limit::OptionsPerLayer, tlvs.len() + 1)?;
+            limits.check(Limit::TlvBytes, tlv.value.len())?;
+            tlvs.push(tlv);
+        }
+        Ok(Lldp { tlvs })
+    }
+}
@@ -0,0 +1,616 @@
+use std::collections::HashMap;
+use std::fmt::{self, Write as _};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use crate::analysis::{RoleObserver, RoleOverrides};
+use crate::flow::FiveTuple;
+use crate::pcap::{self, CapturedPacket, DecodedStack, Reader, Severity, WarningCode};
+use crate::pcapng::{self, Provenance};
+use crate::tcp::{self, OffsetAndFlags};
+use crate::truncate::layout;
+
+/// Number of conversations listed in a manifest.
+pub const TOP_CONVERSATIONS: usize = 10;
+
+const PROTOCOL_TCP: u8 = 6;
+const PROTOCOL_UDP: u8 = 17;
+
+/// Packets and bytes seen for one protocol stack, e.g. `eth:ipv4:tcp`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProtocolCount {
+    pub stack: String,
+    pub packets: u64,
+    pub bytes: u64,
+}
+
+/// Traffic of one conversation, both directions combined.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Conversation {
+    pub protocol: u8,
+    /// Endpoints as `address:port`, in normalized order.
+    pub endpoint_a: String,
+    pub endpoint_b: String,
+    /// Client and server endpoints of a TCP conversation, as overridden or
+    /// inferred.
+    pub client: Option<String>,
+    pub server: Option<String>,
+    pub packets: u64,
+    pub bytes: u64,
+}
+
+/// Counts of unusual TCP segments.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TcpAnomalies {
+    /// Segments with payload that carry no new data for their direction.
+    pub retransmissions: u64,
+    pub resets: u64,
+    /// Segments advertising a zero window, RST segments excepted.
+    pub zero_windows: u64,
+}
+
+/// Packets the decoder found something unusual in, for one kind of
+/// warning.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WarningCount {
+    pub code: WarningCode,
+    pub severity: Severity,
+    pub packets: u64,
+}
+
+/// Summary of a capture, meant to be archived next to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Manifest {
+    /// Version of the crate that described the capture.
+    pub crate_version: String,
+    /// Version and generation parameters of the crate that wrote the
+    /// capture, when it recorded them (pcapng only).
+    pub provenance: Option<Provenance>,
+    pub link_type: u32,
+    pub packets: u64,
+    /// Captured bytes, record headers excluded.
+    pub bytes: u64,
+    /// Timestamps of the first and last packets, in nanoseconds since the
+    /// Unix epoch.
+    pub first_timestamp_ns: Option<u64>,
+    pub last_timestamp_ns: Option<u64>,
+    pub duration_ns: u64,
+    /// Protocol stacks, sorted by name.
+    pub protocols: Vec<ProtocolCount>,
+    /// Largest conversations by bytes, at most `TOP_CONVERSATIONS`.
+    pub top_conversations: Vec<Conversation>,
+    pub tcp_anomalies: TcpAnomalies,
+    /// Decoding warnings of the packets (see `DecodedStack::parse`), by
+    /// code.
+    pub warnings: Vec<WarningCount>,
+}
+
+impl Manifest {
+    /// Encodes the manifest as pretty-printed JSON.
+    pub fn to_json(&self) -> String {
+        let optional = |value: Option<u64>| value.map_or("null".to_string(), |v| v.to_string());
+        let optional_str =
+            |value: &Option<String>| value.as_deref().map_or("null".to_string(), quote);
+        let mut json = String::from("{\n");
+        let _ = writeln!(json, "  \"crate_version\": {},", quote(&self.crate_version));
+        let provenance = self
+            .provenance
+            .as_ref()
+            .map_or("null".to_string(), |provenance| {
+                let parameters: Vec<String> = provenance
+                    .parameters
+                    .iter()
+                    .map(|(key, value)| format!("[{}, {}]", quote(key), quote(value)))
+                    .collect();
+                format!(
+                    "{{\"crate_version\": {}, \"parameters\": [{}]}}",
+                    quote(&provenance.crate_version),
+                    parameters.join(", ")
+                )
+            });
+        let _ = writeln!(json, "  \"provenance\": {provenance},");
+        let _ = writeln!(json, "  \"link_type\": {},", self.link_type);
+        let _ = writeln!(json, "  \"packets\": {},", self.packets);
+        let _ = writeln!(json, "  \"bytes\": {},", self.bytes);
+        let _ = writeln!(
+            json,
+            "  \"first_timestamp_ns\": {},",
+            optional(self.first_timestamp_ns)
+        );
+        let _ = writeln!(
+            json,
+            "  \"last_timestamp_ns\": {},",
+            optional(self.last_timestamp_ns)
+        );
+        let _ = writeln!(json, "  \"duration_ns\": {},", self.duration_ns);
+        let protocols: Vec<String> = self
+            .protocols
+            .iter()
+            .map(|protocol| {
+                format!(
+                    "    {{\"stack\": {}, \"packets\": {}, \"bytes\": {}}}",
+                    quote(&protocol.stack),
+                    protocol.packets,
+                    protocol.bytes
+                )
+            })
+            .collect();
+        let _ = writeln!(json, "  \"protocols\": [\n{}\n  ],", protocols.join(",\n"));
+        let conversations: Vec<String> = self
+            .top_conversations
+            .iter()
+            .map(|conversation| {
+                format!(
+                    "    {{\"protocol\": {}, \"endpoint_a\": {}, \"endpoint_b\": {}, \"client\": {}, \"server\": {}, \"packets\": {}, \"bytes\": {}}}",
+                    conversation.protocol,
+                    quote(&conversation.endpoint_a),
+                    quote(&conversation.endpoint_b),
+                    optional_str(&conversation.client),
+                    optional_str(&conversation.server),
+                    conversation.packets,
+                    conversation.bytes
+                )
+            })
+            .collect();
+        let _ = writeln!(
+            json,
+            "  \"top_conversations\": [\n{}\n  ],",
+            conversations.join(",\n")
+        );
+        let anomalies = &self.tcp_anomalies;
+        let _ = writeln!(
+            json,
+            "  \"tcp_anomalies\": {{\"retransmissions\": {}, \"resets\": {}, \"zero_windows\": {}}},",
+            anomalies.retransmissions, anomalies.resets, anomalies.zero_windows
+        );
+        let warnings: Vec<String> = self
+            .warnings
+            .iter()
+            .map(|warning| {
+                format!(
+                    "    {{\"code\": {}, \"severity\": {}, \"packets\": {}}}",
+                    quote(warning.code.name()),
+                    quote(&warning.severity.to_string()),
+                    warning.packets
+                )
+            })
+            .collect();
+        let _ = writeln!(json, "  \"warnings\": [\n{}\n  ]", warnings.join(",\n"));
+        json.push_str("}\n");
+        json
+    }
+}
+
+/// Writes the manifest for a terminal: totals, then one line per
+/// protocol stack, conversation and kind of warning.
+impl fmt::Display for Manifest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} packets, {} bytes in {:.3} s, link type {}",
+            self.packets,
+            self.bytes,
+            self.duration_ns as f64 / 1e9,
+            self.link_type
+        )?;
+        writeln!(f, "protocols:")?;
+        for protocol in &self.protocols {
+            writeln!(
+                f,
+                "  {:<24} {:>8} packets {:>12} bytes",
+                protocol.stack, protocol.packets, protocol.bytes
+            )?;
+        }
+        writeln!(f, "top conversations:")?;
+        for conversation in &self.top_conversations {
+            let endpoints = match (&conversation.client, &conversation.server) {
+                (Some(client), Some(server)) => format!("{client} -> {server}"),
+                _ => format!(
+                    "{} <-> {}",
+                    conversation.endpoint_a, conversation.endpoint_b
+                ),
+            };
+            let protocol = match conversation.protocol {
+                PROTOCOL_TCP => "tcp".to_string(),
+                PROTOCOL_UDP => "udp".to_string(),
+                other => format!("ip-proto-{other}"),
+            };
+            writeln!(
+                f,
+                "  {protocol:<4} {endpoints:<44} {:>8} packets {:>12} bytes",
+                conversation.packets, conversation.bytes
+            )?;
+        }
+        let anomalies = &self.tcp_anomalies;
+        write!(
+            f,
+            "tcp: {} retransmissions, {} resets, {} zero windows",
+            anomalies.retransmissions, anomalies.resets, anomalies.zero_windows
+        )?;
+        for warning in &self.warnings {
+            write!(
+                f,
+                "\n{}[{}] in {} packets",
+                warning.severity, warning.code, warning.packets
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Quotes `value` as a JSON string.
+fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Names the layers of a frame, e.g. `eth:ipv6:udp`.
+fn protocol_stack(link_type: u32, frame: &[u8]) -> String {
+    let link = match link_type {
+        pcap::LINKTYPE_ETHERNET => "eth",
+        pcap::LINKTYPE_RAW | pcap::LINKTYPE_IPV4 | pcap::LINKTYPE_IPV6 => "raw",
+        pcap::LINKTYPE_NULL => "null",
+        _ => return format!("linktype-{link_type}"),
+    };
+    let Some(layout) = layout(link_type, frame) else {
+        return link.to_string();
+    };
+    let network = if layout.ipv6 { "ipv6" } else { "ipv4" };
+    let transport = match layout.protocol {
+        1 => "icmp".to_string(),
+        6 => "tcp".to_string(),
+        17 => "udp".to_string(),
+        58 => "icmpv6".to_string(),
+        132 => "sctp".to_string(),
+        other => format!("ip-proto-{other}"),
+    };
+    format!("{link}:{network}:{transport}")
+}
+
+/// Reads every packet of `reader` and summarizes the capture. The client
+/// and server of TCP conversations are inferred as
+/// `analysis::infer_roles` does.
+pub fn describe<R: Read>(reader: &mut Reader<R>) -> io::Result<Manifest> {
+    describe_with_roles(reader, &RoleOverrides::new())
+}
+
+/// Same as `describe`, taking the client and server of the conversations
+/// listed in `overrides` as given.
+pub fn describe_with_roles<R: Read>(
+    reader: &mut Reader<R>,
+    overrides: &RoleOverrides,
+) -> io::Result<Manifest> {
+    let link_type = reader.link_type();
+    let mut summary = Summary::default();
+    while let Some(packet) = reader.next_packet()? {
+        summary.add(link_type, &packet);
+    }
+    Ok(summary.into_manifest(link_type, None, overrides))
+}
+
+/// Same as `describe` for a pcapng capture, adding the provenance recorded
+/// by this crate if it wrote the capture. The link type is that of the
+/// first interface; each packet is decoded with the link type of its own.
+pub fn describe_pcapng<R: Read>(reader: &mut pcapng::Reader<R>) -> io::Result<Manifest> {
+    let mut summary = Summary::default();
+    let mut link_type = None;
+    let mut provenance = reader.provenance().cloned();
+    while let Some((iface, packet)) = reader.next_packet()? {
+        // A later section starts over with its own interfaces.
+        let packet_link_type = reader.interfaces()[iface.0 as usize].link_type as u32;
+        link_type.get_or_insert(packet_link_type);
+        provenance = provenance.or_else(|| reader.provenance().cloned());
+        summary.add(packet_link_type, &packet);
+    }
+    let link_type = link_type.or_else(|| reader.interfaces().first().map(|i| i.link_type as u32));
+    Ok(summary.into_manifest(link_type.unwrap_or(0), provenance, &RoleOverrides::new()))
+}
+
+/// Counters accumulated over the packets of a capture.
+#[derive(Default)]
+struct Summary {
+    packets: u64,
+    bytes: u64,
+    first: Option<u64>,
+    last: Option<u64>,
+    protocols: HashMap<String, (u64, u64)>,
+    conversations: HashMap<FiveTuple, (u64, u64)>,
+    next_sequence: HashMap<FiveTuple, u32>,
+    observers: HashMap<FiveTuple, RoleObserver>,
+    anomalies: TcpAnomalies,
+    warnings: HashMap<WarningCode, u64>,
+}
+
+impl Summary {
+    fn add(&mut self, link_type: u32, packet: &CapturedPacket) {
+        let len = packet.data.len() as u64;
+        let timestamp = packet.timestamp.as_nanos() as u64;
+        self.packets += 1;
+        self.bytes += len;
+        self.first.get_or_insert(timestamp);
+        self.last = Some(timestamp);
+
+        let counts = self
+            .protocols
+            .entry(protocol_stack(link_type, &packet.data))
+            .or_default();
+        counts.0 += 1;
+        counts.1 += len;
+
+        let mut codes: Vec<WarningCode> = DecodedStack::parse(link_type, &packet.data)
+            .warnings
+            .iter()
+            .map(|warning| warning.code)
+            .collect();
+        codes.sort();
+        codes.dedup();
+        for code in codes {
+            *self.warnings.entry(code).or_default() += 1;
+        }
+
+        let Some(key) = FiveTuple::from_frame(link_type, &packet.data) else {
+            return;
+        };
+        let counts = self.conversations.entry(key.normalized()).or_default();
+        counts.0 += 1;
+        counts.1 += len;
+
+        let Some(layout) = layout(link_type, &packet.data) else {
+            return;
+        };
+        let Some((offset, PROTOCOL_TCP)) = layout.transport else {
+            return;
+        };
+        let header = &packet.data[offset..];
+        let flags = OffsetAndFlags([header[12], header[13]]).get_flags();
+        self.observers.entry(key.normalized()).or_default().observe(
+            SocketAddr::new(key.source, key.source_port),
+            SocketAddr::new(key.destination, key.destination_port),
+            flags,
+            &packet.data[layout.payload_offset..layout.end],
+        );
+        if flags & tcp::flags::RST != 0 {
+            self.anomalies.resets += 1;
+        } else if header[14] == 0 && header[15] == 0 {
+            self.anomalies.zero_windows += 1;
+        }
+        let payload_len = (layout.end - layout.payload_offset) as u32;
+        if payload_len > 0 {
+            let sequence = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+            let end = sequence.wrapping_add(payload_len);
+            match self.next_sequence.get_mut(&key) {
+                Some(next) if (end.wrapping_sub(*next) as i32) <= 0 => {
+                    self.anomalies.retransmissions += 1;
+                }
+                Some(next) => *next = end,
+                None => {
+                    self.next_sequence.insert(key, end);
+                }
+            }
+        }
+    }
+
+    fn into_manifest(
+        self,
+        link_type: u32,
+        provenance: Option<Provenance>,
+        overrides: &RoleOverrides,
+    ) -> Manifest {
+        let mut protocols: Vec<ProtocolCount> = self
+            .protocols
+            .into_iter()
+            .map(|(stack, (packets, bytes))| ProtocolCount {
+                stack,
+                packets,
+                bytes,
+            })
+            .collect();
+        protocols.sort_by(|a, b| a.stack.cmp(&b.stack));
+        let mut conversations: Vec<(FiveTuple, (u64, u64))> =
+            self.conversations.into_iter().collect();
+        conversations.sort_by(|(a_key, a), (b_key, b)| b.1.cmp(&a.1).then(a_key.cmp(b_key)));
+        let observers = self.observers;
+        let top_conversations = conversations
+            .into_iter()
+            .take(TOP_CONVERSATIONS)
+            .map(|(key, (packets, bytes))| {
+                let roles = observers
+                    .get(&key)
+                    .and_then(|observer| overrides.resolve(&key, observer));
+                Conversation {
+                    protocol: key.protocol,
+                    endpoint_a: SocketAddr::new(key.source, key.source_port).to_string(),
+                    endpoint_b: SocketAddr::new(key.destination, key.destination_port).to_string(),
+                    client: roles.map(|roles| roles.client.to_string()),
+                    server: roles.map(|roles| roles.server.to_string()),
+                    packets,
+                    bytes,
+                }
+            })
+            .collect();
+
+        let mut warnings: Vec<WarningCount> = self
+            .warnings
+            .into_iter()
+            .map(|(code, packets)| WarningCount {
+                code,
+                severity: code.severity(),
+                packets,
+            })
+            .collect();
+        warnings.sort_by_key(|warning| warning.code);
+
+        let (first, last) = (self.first, self.last);
+        Manifest {
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            provenance,
+            link_type,
+            packets: self.packets,
+            bytes: self.bytes,
+            first_timestamp_ns: first,
+            last_timestamp_ns: last,
+            duration_ns: last
+                .zip(first)
+                .map_or(0, |(last, first)| last.saturating_sub(first)),
+            protocols,
+            top_conversations,
+            tcp_anomalies: self.anomalies,
+            warnings,
+        }
+    }
+}
+
+/// Describes the capture at `pcap_path`, pcap or pcapng, and writes the
+/// manifest as JSON to `<pcap_path>.manifest.json`. Returns the path of
+/// the manifest.
+pub fn write_alongside(pcap_path: impl AsRef<Path>) -> io::Result<PathBuf> {
+    let pcap_path = pcap_path.as_ref();
+    let mut magic = [0u8; 4];
+    File::open(pcap_path)?.read_exact(&mut magic)?;
+    let manifest = if u32::from_ne_bytes(magic) == pcapng::BLOCK_SECTION_HEADER {
+        describe_pcapng(&mut pcapng::Reader::open(pcap_path)?)?
+    } else {
+        describe(&mut Reader::open(pcap_path)?)?
+    };
+    let mut manifest_path = pcap_path.as_os_str().to_owned();
+    manifest_path.push(".manifest.json");
+    let manifest_path = PathBuf::from(manifest_path);
+    fs::write(&manifest_path, manifest.to_json())?;
+    Ok(manifest_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::net::Ipv4Addr;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::ipv4::IPv4;
+    use crate::pcap::{CapturedPacket, GlobalHeader, LINKTYPE_IPV4, Resolution, Writer};
+    use crate::tcp::TCP;
+    use crate::udp::{self, UDP};
+
+    const CLIENT: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 1);
+    const SERVER: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 2);
+
+    fn capture() -> Reader<Cursor<Vec<u8>>> {
+        // Mid-stream: no handshake, the server answers from port 443.
+        let request = TCP::segment(40000, 443, 1, 1, tcp::flags::ACK).set_data(vec![1; 100]);
+        let response = TCP::segment(443, 40000, 1, 101, tcp::flags::ACK).set_data(vec![2; 900]);
+        let frames = [
+            IPv4::with_payload(CLIENT, SERVER, PROTOCOL_TCP, request.to_bytes()),
+            IPv4::with_payload(SERVER, CLIENT, PROTOCOL_TCP, response.to_bytes()),
+            IPv4::with_payload(
+                CLIENT,
+                SERVER,
+                udp::IP_PROTOCOL,
+                UDP::new(5353, 5353, vec![0; 10]).to_bytes(),
+            ),
+        ];
+        let header = GlobalHeader::new(LINKTYPE_IPV4, 65535, Resolution::Micros);
+        let mut writer = Writer::new(Vec::new(), header).unwrap();
+        for (i, frame) in frames.iter().enumerate() {
+            let packet = CapturedPacket::new(Duration::from_millis(i as u64), frame.to_bytes());
+            writer.write_packet(&packet).unwrap();
+        }
+        Reader::new(Cursor::new(writer.into_inner())).unwrap()
+    }
+
+    #[test]
+    fn tcp_conversations_get_inferred_roles() {
+        let manifest = describe(&mut capture()).unwrap();
+        let tcp = &manifest.top_conversations[0];
+        assert_eq!(tcp.protocol, PROTOCOL_TCP);
+        assert_eq!(tcp.client.as_deref(), Some("10.0.0.1:40000"));
+        assert_eq!(tcp.server.as_deref(), Some("10.0.0.2:443"));
+        let udp = &manifest.top_conversations[1];
+        assert_eq!((&udp.client, &udp.server), (&None, &None));
+        let json = manifest.to_json();
+        assert!(json.contains("\"client\": \"10.0.0.1:40000\", \"server\": \"10.0.0.2:443\""));
+        assert!(json.contains("\"client\": null, \"server\": null"));
+
+        let text = manifest.to_string();
+        assert!(text.starts_with("3 packets, 1118 bytes in 0.002 s, link type 228\n"));
+        assert!(text.contains("  tcp  10.0.0.1:40000 -> 10.0.0.2:443"));
+        assert!(text.contains("  udp  10.0.0.1:5353 <-> 10.0.0.2:5353"));
+        assert!(text.ends_with("warning[bad-checksum] in 2 packets"));
+    }
+
+    #[test]
+    fn decoding_warnings_are_counted() {
+        // The TCP segments of the capture carry no checksum; the UDP
+        // datagram has none either, which IPv4 allows.
+        let manifest = describe(&mut capture()).unwrap();
+        assert_eq!(
+            manifest.warnings,
+            [WarningCount {
+                code: WarningCode::BadChecksum,
+                severity: Severity::Warning,
+                packets: 2,
+            }]
+        );
+        let json = manifest.to_json();
+        assert!(json.contains(
+            "\"warnings\": [\n    {\"code\": \"bad-checksum\", \"severity\": \"warning\", \"packets\": 2}\n  ]"
+        ));
+    }
+
+    #[test]
+    fn role_override_replaces_the_inference() {
+        let flow = FiveTuple::new(PROTOCOL_TCP, (SERVER, CLIENT).into(), 443, 40000);
+        let overrides =
+            RoleOverrides::new().set_server(&flow, SocketAddr::new(CLIENT.into(), 40000));
+        let manifest = describe_with_roles(&mut capture(), &overrides).unwrap();
+        let tcp = &manifest.top_conversations[0];
+        assert_eq!(tcp.client.as_deref(), Some("10.0.0.2:443"));
+        assert_eq!(tcp.server.as_deref(), Some("10.0.0.1:40000"));
+    }
+
+    #[test]
+    fn pcapng_provenance_reaches_the_manifest() {
+        let provenance = Provenance::new().add_parameter("seed", "7");
+        let mut writer = pcapng::Writer::with_provenance(Vec::new(), &provenance).unwrap();
+        let iface = writer.add_interface("lo", LINKTYPE_IPV4 as u16, 0).unwrap();
+        let mut frames = capture();
+        while let Some(packet) = frames.next_packet().unwrap() {
+            writer
+                .write_packet(iface, &packet.data, packet.timestamp, &[])
+                .unwrap();
+        }
+        let mut reader = pcapng::Reader::new(Cursor::new(writer.into_inner())).unwrap();
+        let manifest = describe_pcapng(&mut reader).unwrap();
+        assert_eq!(manifest.provenance, Some(provenance));
+        assert_eq!(manifest.link_type, LINKTYPE_IPV4);
+        assert_eq!(manifest.packets, 3);
+        assert_eq!(manifest.duration_ns, 2_000_000);
+        let json = manifest.to_json();
+        assert!(json.contains("\"parameters\": [[\"seed\", \"7\"]]"));
+        // A plain pcap carries no provenance.
+        assert!(
+            describe(&mut capture())
+                .unwrap()
+                .to_json()
+                .contains("\"provenance\": null")
+        );
+    }
+}
@@ -0,0 +1,568 @@
+use std::fmt;
+
+use crate::pcap::{DecodedStack, NetworkLayer, TransportLayer};
+
+// Field-level comparison of decoded packets. A frame is flattened into
+// (FieldId, value) pairs, one per header field of the layers
+// `DecodedStack` decodes, plus the layer names and the bytes left after
+// the last decoded header. Masking a field leaves it out of the
+// comparison, so that e.g. a TTL decremented by a router or a recomputed
+// checksum does not make two copies of a packet differ.
+
+/// A field of a decoded packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum FieldId {
+    /// Names of the decoded layers, e.g. `eth:vlan:ipv4:tcp`.
+    Layers,
+    EthDestination,
+    EthSource,
+    EthType,
+    VlanIds,
+    /// Outer headers of IP-in-IP tunnels.
+    Tunnels,
+    IpSource,
+    IpDestination,
+    /// Type of service of IPv4, traffic class of IPv6.
+    IpTos,
+    /// Total length of IPv4, payload length of IPv6.
+    IpLength,
+    IpIdentification,
+    IpFlags,
+    IpFragmentOffset,
+    /// TTL of IPv4, hop limit of IPv6.
+    IpTtl,
+    /// Protocol of IPv4, next header of IPv6.
+    IpProtocol,
+    IpChecksum,
+    IpOptions,
+    Ipv6FlowLabel,
+    SourcePort,
+    DestinationPort,
+    TcpSequence,
+    TcpAcknowledgment,
+    TcpFlags,
+    TcpWindow,
+    TcpUrgentPointer,
+    TcpOptions,
+    UdpLength,
+    /// TCP or UDP checksum.
+    TransportChecksum,
+    /// Bytes after the last decoded header.
+    Payload,
+}
+
+impl FieldId {
+    /// Every field, in comparison order.
+    pub const ALL: [FieldId; 29] = [
+        FieldId::Layers,
+        FieldId::EthDestination,
+        FieldId::EthSource,
+        FieldId::EthType,
+        FieldId::VlanIds,
+        FieldId::Tunnels,
+        FieldId::IpSource,
+        FieldId::IpDestination,
+        FieldId::IpTos,
+        FieldId::IpLength,
+        FieldId::IpIdentification,
+        FieldId::IpFlags,
+        FieldId::IpFragmentOffset,
+        FieldId::IpTtl,
+        FieldId::IpProtocol,
+        FieldId::IpChecksum,
+        FieldId::IpOptions,
+        FieldId::Ipv6FlowLabel,
+        FieldId::SourcePort,
+        FieldId::DestinationPort,
+        FieldId::TcpSequence,
+        FieldId::TcpAcknowledgment,
+        FieldId::TcpFlags,
+        FieldId::TcpWindow,
+        FieldId::TcpUrgentPointer,
+        FieldId::TcpOptions,
+        FieldId::UdpLength,
+        FieldId::TransportChecksum,
+        FieldId::Payload,
+    ];
+
+    /// Name of the field, e.g. `ip.ttl`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            FieldId::Layers => "layers",
+            FieldId::EthDestination => "eth.dst",
+            FieldId::EthSource => "eth.src",
+            FieldId::EthType => "eth.type",
+            FieldId::VlanIds => "vlan.id",
+            FieldId::Tunnels => "tunnels",
+            FieldId::IpSource => "ip.src",
+            FieldId::IpDestination => "ip.dst",
+            FieldId::IpTos => "ip.tos",
+            FieldId::IpLength => "ip.len",
+            FieldId::IpIdentification => "ip.id",
+            FieldId::IpFlags => "ip.flags",
+            FieldId::IpFragmentOffset => "ip.frag_offset",
+            FieldId::IpTtl => "ip.ttl",
+            FieldId::IpProtocol => "ip.proto",
+            FieldId::IpChecksum => "ip.checksum",
+            FieldId::IpOptions => "ip.options",
+            FieldId::Ipv6FlowLabel => "ipv6.flow_label",
+            FieldId::SourcePort => "srcport",
+            FieldId::DestinationPort => "dstport",
+            FieldId::TcpSequence => "tcp.seq",
+            FieldId::TcpAcknowledgment => "tcp.ack",
+            FieldId::TcpFlags => "tcp.flags",
+            FieldId::TcpWindow => "tcp.window",
+            FieldId::TcpUrgentPointer => "tcp.urgent",
+            FieldId::TcpOptions => "tcp.options",
+            FieldId::UdpLength => "udp.length",
+            FieldId::TransportChecksum => "l4.checksum",
+            FieldId::Payload => "payload",
+        }
+    }
+
+    /// Field named `name`, as returned by `name`.
+    pub fn from_name(name: &str) -> Option<FieldId> {
+        FieldId::ALL.into_iter().find(|field| field.name() == name)
+    }
+}
+
+impl fmt::Display for FieldId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Set of fields left out of a comparison.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct FieldMask(u32);
+
+impl FieldMask {
+    /// Constructor to create a mask ignoring no field.
+    pub fn new() -> Self {
+        FieldMask(0)
+    }
+
+    /// Fields that change on the way through a network or between two
+    /// capture points: MAC addresses, TTL, IP identification and
+    /// checksums.
+    pub fn volatile() -> Self {
+        FieldMask::new()
+            .ignore(FieldId::EthSource)
+            .ignore(FieldId::EthDestination)
+            .ignore(FieldId::IpTtl)
+            .ignore(FieldId::IpIdentification)
+            .ignore(FieldId::IpChecksum)
+            .ignore(FieldId::TransportChecksum)
+    }
+
+    /// Returns true if `field` is left out.
+    pub fn ignores(&self, field: FieldId) -> bool {
+        self.0 & 1 << field as u8 != 0
+    }
+
+    // --- SETTER METHODS ---
+
+    pub fn ignore(mut self, field: FieldId) -> Self {
+        self.0 |= 1 << field as u8;
+        self
+    }
+
+    pub fn compare(mut self, field: FieldId) -> Self {
+        self.0 &= !(1 << field as u8);
+        self
+    }
+
+    /// Values of the fields of `stack` this mask does not ignore, in
+    /// `FieldId::ALL` order.
+    pub fn fields(&self, stack: &DecodedStack) -> Vec<(FieldId, String)> {
+        self.retain(fields(stack))
+    }
+
+    /// Same as `fields` for `frame`, captured with `link_type`. A frame
+    /// that does not decode far enough to have a payload is compared as a
+    /// whole payload.
+    pub fn frame_fields(&self, link_type: u32, frame: &[u8]) -> Vec<(FieldId, String)> {
+        self.retain(frame_fields(link_type, frame))
+    }
+
+    fn retain(&self, mut fields: Vec<(FieldId, String)>) -> Vec<(FieldId, String)> {
+        fields.retain(|(field, _)| !self.ignores(*field));
+        fields
+    }
+}
+
+/// Error returned for a field name that `FieldId::from_name` does not know.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownField(pub String);
+
+impl fmt::Display for UnknownField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown field {:?}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownField {}
+
+/// A field whose value differs from the expected one; `None` when the
+/// field is missing from one of the packets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldMismatch {
+    pub field: FieldId,
+    pub expected: Option<String>,
+    pub actual: Option<String>,
+}
+
+/// Result of `PacketMatcher::matches`: the fields that differ, empty when
+/// the packet matches.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MatchResult {
+    pub mismatches: Vec<FieldMismatch>,
+}
+
+impl MatchResult {
+    /// Returns true if no compared field differs.
+    pub fn passed(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+impl fmt::Display for MatchResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.passed() {
+            return writeln!(f, "packet matches");
+        }
+        let value = |value: &Option<String>| value.clone().unwrap_or_else(|| "-".to_string());
+        for mismatch in &self.mismatches {
+            writeln!(
+                f,
+                "{}: expected {}, got {}",
+                mismatch.field,
+                value(&mismatch.expected),
+                value(&mismatch.actual)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares frames to an expected packet, field by field, leaving out the
+/// fields of its mask.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PacketMatcher {
+    /// Link type the expected and actual frames are decoded with.
+    pub link_type: u32,
+    pub mask: FieldMask,
+    expected: Vec<(FieldId, String)>,
+}
+
+impl PacketMatcher {
+    /// Constructor to create a matcher expecting `frame`, captured with
+    /// `link_type`, comparing every field.
+    pub fn new(link_type: u32, frame: &[u8]) -> Self {
+        PacketMatcher {
+            link_type,
+            mask: FieldMask::new(),
+            expected: frame_fields(link_type, frame),
+        }
+    }
+
+    /// Constructor to create a matcher expecting the packet `stack`; the
+    /// actual frames are decoded with `link_type`.
+    pub fn from_stack(link_type: u32, stack: &DecodedStack) -> Self {
+        PacketMatcher {
+            link_type,
+            mask: FieldMask::new(),
+            expected: fields(stack),
+        }
+    }
+
+    // --- SETTER METHODS ---
+
+    /// Leaves `field` out of the comparison.
+    pub fn ignore(mut self, field: FieldId) -> Self {
+        self.mask = self.mask.ignore(field);
+        self
+    }
+
+    /// Leaves the field named `name` out of the comparison.
+    pub fn ignore_name(self, name: &str) -> Result<Self, UnknownField> {
+        let field = FieldId::from_name(name).ok_or_else(|| UnknownField(name.to_string()))?;
+        Ok(self.ignore(field))
+    }
+
+    /// Leaves out the fields of `FieldMask::volatile`.
+    pub fn ignore_volatile(mut self) -> Self {
+        self.mask = FieldMask(self.mask.0 | FieldMask::volatile().0);
+        self
+    }
+
+    pub fn set_mask(mut self, mask: FieldMask) -> Self {
+        self.mask = mask;
+        self
+    }
+
+    /// Compares `actual_frame` to the expected packet.
+    pub fn matches(&self, actual_frame: &[u8]) -> MatchResult {
+        self.compare(self.mask.frame_fields(self.link_type, actual_frame))
+    }
+
+    /// Compares an already decoded packet to the expected one.
+    pub fn matches_stack(&self, actual: &DecodedStack) -> MatchResult {
+        self.compare(self.mask.fields(actual))
+    }
+
+    fn compare(&self, actual: Vec<(FieldId, String)>) -> MatchResult {
+        let expected = self
+            .expected
+            .iter()
+            .filter(|(field, _)| !self.mask.ignores(*field));
+        let mut mismatches = Vec::new();
+        let (mut expected, mut actual) = (expected.peekable(), actual.into_iter().peekable());
+        // Both lists are in `FieldId::ALL` order: merge them.
+        loop {
+            let mismatch = match (expected.peek(), actual.peek()) {
+                (None, None) => break,
+                (Some((a, _)), Some((b, _))) if a == b => {
+                    let ((field, expected_value), (_, actual_value)) =
+                        (expected.next().unwrap(), actual.next().unwrap());
+                    if *expected_value == actual_value {
+                        continue;
+                    }
+                    FieldMismatch {
+                        field: *field,
+                        expected: Some(expected_value.clone()),
+                        actual: Some(actual_value),
+                    }
+                }
+                (Some((a, _)), Some((b, _))) if a < b => {
+                    let (field, value) = expected.next().unwrap();
+                    FieldMismatch {
+                        field: *field,
+                        expected: Some(value.clone()),
+                        actual: None,
+                    }
+                }
+                (Some((field, value)), None) => {
+                    let mismatch = FieldMismatch {
+                        field: *field,
+                        expected: Some(value.clone()),
+                        actual: None,
+                    };
+                    expected.next();
+                    mismatch
+                }
+                _ => {
+                    let (field, value) = actual.next().unwrap();
+                    FieldMismatch {
+                        field,
+                        expected: None,
+                        actual: Some(value),
+                    }
+                }
+            };
+            mismatches.push(mismatch);
+        }
+        MatchResult { mismatches }
+    }
+}
+
+/// Fields of `frame`, the whole frame being the payload when no layer
+/// holding one decodes.
+fn frame_fields(link_type: u32, frame: &[u8]) -> Vec<(FieldId, String)> {
+    let mut fields = fields(&DecodedStack::decode(link_type, frame));
+    if fields
+        .last()
+        .is_none_or(|(field, _)| *field != FieldId::Payload)
+    {
+        fields.push((FieldId::Payload, hex(frame)));
+    }
+    fields
+}
+
+/// Flattens `stack` into its fields, in `FieldId::ALL` order.
+fn fields(stack: &DecodedStack) -> Vec<(FieldId, String)> {
+    let mut fields = Vec::new();
+    let mut push = |field: FieldId, value: String| fields.push((field, value));
+    let mut layers = Vec::new();
+    let mut payload: Option<&[u8]> = None;
+
+    if let Some(ethernet) = &stack.ethernet {
+        layers.push("eth");
+        push(FieldId::EthDestination, ethernet.destination.to_string());
+        push(FieldId::EthSource, ethernet.source.to_string());
+        push(FieldId::EthType, format!("{:#06x}", ethernet.ethertype));
+        if !stack.vlan_ids.is_empty() {
+            layers.push("vlan");
+            push(FieldId::VlanIds, format!("{:?}", stack.vlan_ids));
+        }
+        let tags = 4 * stack.vlan_ids.len();
+        payload = Some(ethernet.payload.get(tags..).unwrap_or_default());
+    }
+    if !stack.tunnels.is_empty() {
+        layers.push("tunnel");
+        push(FieldId::Tunnels, format!("{:?}", stack.tunnels));
+    }
+    match &stack.network {
+        NetworkLayer::Ipv4(ipv4) => {
+            layers.push("ipv4");
+            push(FieldId::IpSource, ipv4.source.to_string());
+            push(FieldId::IpDestination, ipv4.destination.to_string());
+            push(FieldId::IpTos, ipv4.tos.to_string());
+            push(FieldId::IpLength, ipv4.total_length.to_string());
+            push(FieldId::IpIdentification, ipv4.identification.to_string());
+            push(FieldId::IpFlags, format!("{:#05b}", ipv4.flags));
+            push(FieldId::IpFragmentOffset, ipv4.fragment_offset.to_string());
+            push(FieldId::IpTtl, ipv4.ttl.to_string());
+            push(FieldId::IpProtocol, ipv4.protocol.to_string());
+            push(FieldId::IpChecksum, format!("{:#06x}", ipv4.checksum));
+            push(FieldId::IpOptions, hex(&ipv4.options));
+            payload = Some(ipv4.payload.as_slice());
+        }
+        NetworkLayer::Ipv6(ipv6) => {
+            layers.push("ipv6");
+            push(FieldId::IpSource, ipv6.source.to_string());
+            push(FieldId::IpDestination, ipv6.destination.to_string());
+            push(FieldId::IpTos, ipv6.traffic_class.to_string());
+            push(FieldId::IpLength, ipv6.payload_length.to_string());
+            push(FieldId::IpTtl, ipv6.hop_limit.to_string());
+            push(FieldId::IpProtocol, ipv6.next_header.to_string());
+            push(FieldId::Ipv6FlowLabel, format!("{:#07x}", ipv6.flow_label));
+            payload = Some(ipv6.payload.as_slice());
+        }
+        NetworkLayer::None => {}
+    }
+    match &stack.transport {
+        TransportLayer::Tcp(tcp) => {
+            layers.push("tcp");
+            push(FieldId::SourcePort, tcp.source.to_string());
+            push(FieldId::DestinationPort, tcp.destination.to_string());
+            push(FieldId::TcpSequence, tcp.sequence.to_string());
+            push(FieldId::TcpAcknowledgment, tcp.acknowledgment.to_string());
+            push(FieldId::TcpFlags, format!("{:#05x}", tcp.flags));
+            push(FieldId::TcpWindow, tcp.window_size.to_string());
+            push(FieldId::TcpUrgentPointer, tcp.urgent_pointer.to_string());
+            push(FieldId::TcpOptions, hex(&tcp.options));
+            push(FieldId::TransportChecksum, format!("{:#06x}", tcp.checksum));
+            payload = Some(tcp.data.as_slice());
+        }
+        TransportLayer::Udp(udp) => {
+            layers.push("udp");
+            push(FieldId::SourcePort, udp.source.to_string());
+            push(FieldId::DestinationPort, udp.destination.to_string());
+            push(FieldId::UdpLength, udp.length.to_string());
+            push(FieldId::TransportChecksum, format!("{:#06x}", udp.checksum));
+            payload = Some(udp.data.as_slice());
+        }
+        TransportLayer::Other { payload: data, .. } => payload = Some(data.as_slice()),
+        TransportLayer::None => {}
+    }
+    if let Some(payload) = payload {
+        push(FieldId::Payload, hex(payload));
+    }
+    fields.insert(0, (FieldId::Layers, layers.join(":")));
+    fields.sort_by_key(|(field, _)| *field);
+    fields
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+    use crate::ethernet::{ETHERTYPE_IPV4, Ethernet, MacAddr};
+    use crate::ipv4::IPv4;
+    use crate::pcap::LINKTYPE_ETHERNET;
+    use crate::udp::UDP;
+
+    fn frame(source_mac: u8, ttl: u8, port: u16) -> Vec<u8> {
+        let udp = UDP::new(port, 53, b"query".to_vec());
+        let ip = IPv4::with_payload(
+            Ipv4Addr::new(10, 0, 0, 1),
+            Ipv4Addr::new(10, 0, 0, 2),
+            17,
+            udp.to_bytes(),
+        )
+        .set_ttl(ttl)
+        .with_checksum();
+        let source = MacAddr([2, 0, 0, 0, 0, source_mac]);
+        Ethernet::new(MacAddr([2; 6]), source, ETHERTYPE_IPV4, ip.to_bytes()).to_bytes()
+    }
+
+    #[test]
+    fn volatile_fields_are_ignored() {
+        let matcher = PacketMatcher::new(LINKTYPE_ETHERNET, &frame(1, 64, 5000));
+        let routed = frame(9, 63, 5000);
+        let result = matcher.matches(&routed);
+        let fields: Vec<FieldId> = result.mismatches.iter().map(|m| m.field).collect();
+        assert_eq!(
+            fields,
+            [FieldId::EthSource, FieldId::IpTtl, FieldId::IpChecksum]
+        );
+        assert!(result.to_string().contains("ip.ttl: expected 64, got 63"));
+        assert!(matcher.clone().ignore_volatile().matches(&routed).passed());
+    }
+
+    #[test]
+    fn reports_other_fields_and_missing_layers() {
+        let matcher = PacketMatcher::new(LINKTYPE_ETHERNET, &frame(1, 64, 5000))
+            .ignore_volatile()
+            .ignore_name("udp.length")
+            .unwrap();
+        let result = matcher.matches(&frame(1, 64, 5001));
+        assert_eq!(
+            result.mismatches,
+            [FieldMismatch {
+                field: FieldId::SourcePort,
+                expected: Some("5000".to_string()),
+                actual: Some("5001".to_string()),
+            }]
+        );
+        // An ARP frame has no IP or UDP layer.
+        let arp = Ethernet::new(MacAddr([2; 6]), MacAddr([3; 6]), 0x0806, vec![0; 28]);
+        let result = matcher.matches(&arp.to_bytes());
+        assert!(
+            result
+                .mismatches
+                .iter()
+                .any(|m| m.field == FieldId::IpSource
+                    && m.actual.is_none()
+                    && m.expected.as_deref() == Some("10.0.0.1"))
+        );
+        assert_eq!(
+            PacketMatcher::new(LINKTYPE_ETHERNET, &[]).ignore_name("ttl"),
+            Err(UnknownField("ttl".to_string()))
+        );
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for field in FieldId::ALL {
+            assert_eq!(FieldId::from_name(field.name()), Some(field));
+        }
+    }
+
+    #[test]
+    fn expectation_within_a_deadline() {
+        use crate::expect::{Evaluator, Expectation};
+        use crate::pcap::CapturedPacket;
+        use std::time::Duration;
+
+        let matcher = PacketMatcher::new(LINKTYPE_ETHERNET, &frame(1, 64, 5000)).ignore_volatile();
+        let packet =
+            |ms: u64, port: u16| CapturedPacket::new(Duration::from_millis(ms), frame(7, 60, port));
+        let mut evaluator = Evaluator::new(vec![
+            Expectation::matching("early", matcher.clone()).within(Duration::from_millis(5)),
+            Expectation::matching("late", matcher).within(Duration::from_millis(50)),
+        ]);
+        evaluator.feed(&packet(0, 5001));
+        evaluator.feed(&packet(20, 5000));
+        let report = evaluator.finish();
+        assert!(!report.results[0].passed);
+        assert_eq!(report.results[1].matching, [1]);
+    }
+}
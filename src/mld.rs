@@ -0,0 +1,384 @@
+use std::net::Ipv6Addr;
+
+use crate::ipv6::IPv6;
+use crate::ipv6::ext::{ExtensionHeader, Ipv6Option, OPTION_ROUTER_ALERT};
+use crate::util::{ParseError, ensure_len, ipv6_pseudo_header_checksum, read_ipv6};
+
+// MLDv1 (RFC 2710)
+//
+//  0                   1                   2                   3
+//  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |     Type      |     Code      |          Checksum             |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |     Maximum Response Delay    |          Reserved             |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                                                               |
+// +                       Multicast Address                       +
+// |                          (128 bits)                           |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+/// IPv6 next header value for ICMPv6, which carries every MLD message.
+pub use crate::icmpv6::NEXT_HEADER as ICMPV6_NEXT_HEADER;
+
+/// ICMPv6 type of an MLDv2 Listener Report (RFC 3810).
+pub const MLDV2_REPORT_TYPE: u8 = 143;
+
+/// Destination of MLDv2 reports.
+pub const ALL_MLDV2_ROUTERS: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0x16);
+
+/// Hop limit of every MLD message.
+pub const HOP_LIMIT: u8 = 1;
+
+// MLDv2 multicast address record types (RFC 3810 section 5.2.12).
+pub const MODE_IS_INCLUDE: u8 = 1;
+pub const MODE_IS_EXCLUDE: u8 = 2;
+pub const CHANGE_TO_INCLUDE_MODE: u8 = 3;
+pub const CHANGE_TO_EXCLUDE_MODE: u8 = 4;
+pub const ALLOW_NEW_SOURCES: u8 = 5;
+pub const BLOCK_OLD_SOURCES: u8 = 6;
+
+/// MLDv1 message types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MldType {
+    Query = 130,
+    Report = 131,
+    Done = 132,
+}
+
+impl TryFrom<u8> for MldType {
+    type Error = ParseError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            130 => Ok(MldType::Query),
+            131 => Ok(MldType::Report),
+            132 => Ok(MldType::Done),
+            _ => Err(ParseError::InvalidField("type")),
+        }
+    }
+}
+
+/// MLDv1 message (Query, Report or Done).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MldV1 {
+    pub type_: MldType,
+    pub max_response_delay: u16,
+    pub checksum: u16,
+    pub multicast_address: Ipv6Addr,
+}
+
+impl MldV1 {
+    /// Length of an MLDv1 message in bytes.
+    pub const LEN: usize = 24;
+
+    /// Constructor to create a new MLDv1 message.
+    pub fn new(
+        type_: MldType,
+        max_response_delay: u16,
+        checksum: u16,
+        multicast_address: Ipv6Addr,
+    ) -> Self {
+        MldV1 {
+            type_,
+            max_response_delay,
+            checksum,
+            multicast_address,
+        }
+    }
+
+    /// Sets the message type.
+    pub fn set_type(mut self, type_: MldType) -> Self {
+        self.type_ = type_;
+        self
+    }
+
+    /// Sets the maximum response delay (milliseconds).
+    pub fn set_max_response_delay(mut self, max_response_delay: u16) -> Self {
+        self.max_response_delay = max_response_delay;
+        self
+    }
+
+    /// Sets the checksum.
+    pub fn set_checksum(mut self, checksum: u16) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    /// Sets the multicast address.
+    pub fn set_multicast_address(mut self, multicast_address: Ipv6Addr) -> Self {
+        self.multicast_address = multicast_address;
+        self
+    }
+
+    /// Serializes the message, using the checksum field as is.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::LEN);
+        bytes.push(self.type_ as u8);
+        bytes.push(0);
+        bytes.extend_from_slice(&self.checksum.to_be_bytes());
+        bytes.extend_from_slice(&self.max_response_delay.to_be_bytes());
+        bytes.extend_from_slice(&[0, 0]);
+        bytes.extend_from_slice(&self.multicast_address.octets());
+        bytes
+    }
+
+    /// Parses an MLDv1 message from the ICMPv6 payload.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, Self::LEN)?;
+        Ok(MldV1 {
+            type_: MldType::try_from(buf[0])?,
+            checksum: u16::from_be_bytes([buf[2], buf[3]]),
+            max_response_delay: u16::from_be_bytes([buf[4], buf[5]]),
+            multicast_address: read_ipv6(buf, 8),
+        })
+    }
+
+    /// Computes the ICMPv6 checksum over the IPv6 pseudo-header.
+    pub fn compute_checksum(&self, source: Ipv6Addr, destination: Ipv6Addr) -> u16 {
+        let mut bytes = self.to_bytes();
+        bytes[2..4].fill(0);
+        ipv6_pseudo_header_checksum(source, destination, ICMPV6_NEXT_HEADER, &bytes)
+    }
+
+    /// Returns the message with its checksum computed for the given addresses.
+    pub fn with_checksum(mut self, source: Ipv6Addr, destination: Ipv6Addr) -> Self {
+        self.checksum = self.compute_checksum(source, destination);
+        self
+    }
+}
+
+// MLDv2 Listener Report (RFC 3810)
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |  Type = 143   |    Reserved   |           Checksum            |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |           Reserved            |Nr of Mcast Address Records (M)|
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                  Multicast Address Record [1..M]              |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+/// MLDv2 multicast address record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MldV2Record {
+    pub record_type: u8,
+    pub multicast_address: Ipv6Addr,
+    pub sources: Vec<Ipv6Addr>,
+}
+
+impl MldV2Record {
+    /// Constructor to create a new multicast address record.
+    pub fn new(record_type: u8, multicast_address: Ipv6Addr, sources: Vec<Ipv6Addr>) -> Self {
+        MldV2Record {
+            record_type,
+            multicast_address,
+            sources,
+        }
+    }
+
+    /// Serialized length of the record in bytes.
+    pub fn wire_len(&self) -> usize {
+        20 + 16 * self.sources.len()
+    }
+
+    /// Appends the record to `bytes`. Auxiliary data is never emitted.
+    pub fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        bytes.push(self.record_type);
+        bytes.push(0);
+        bytes.extend_from_slice(&(self.sources.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(&self.multicast_address.octets());
+        for source in &self.sources {
+            bytes.extend_from_slice(&source.octets());
+        }
+    }
+
+    /// Parses a record, returning it with the number of bytes consumed.
+    /// Auxiliary data is skipped.
+    pub fn from_bytes(buf: &[u8]) -> Result<(Self, usize), ParseError> {
+        ensure_len(buf, 20)?;
+        let aux_len = buf[1] as usize * 4;
+        let count = u16::from_be_bytes([buf[2], buf[3]]) as usize;
+        let len = 20 + count * 16 + aux_len;
+        ensure_len(buf, len)?;
+        let sources = (0..count).map(|i| read_ipv6(buf, 20 + i * 16)).collect();
+        Ok((
+            MldV2Record {
+                record_type: buf[0],
+                multicast_address: read_ipv6(buf, 4),
+                sources,
+            },
+            len,
+        ))
+    }
+}
+
+/// MLDv2 Listener Report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MldV2Report {
+    pub checksum: u16,
+    pub number_of_mcast_address_records: u16,
+    pub records: Vec<MldV2Record>,
+}
+
+impl MldV2Report {
+    /// Constructor to create a report; the record count is taken from `records`.
+    pub fn new(records: Vec<MldV2Record>) -> Self {
+        MldV2Report {
+            checksum: 0,
+            number_of_mcast_address_records: records.len() as u16,
+            records,
+        }
+    }
+
+    /// Sets the checksum.
+    pub fn set_checksum(mut self, checksum: u16) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    /// Sets the records and updates the record count.
+    pub fn set_records(mut self, records: Vec<MldV2Record>) -> Self {
+        self.number_of_mcast_address_records = records.len() as u16;
+        self.records = records;
+        self
+    }
+
+    /// Serializes the report, using the checksum and count fields as is.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let len = 8 + self
+            .records
+            .iter()
+            .map(MldV2Record::wire_len)
+            .sum::<usize>();
+        let mut bytes = Vec::with_capacity(len);
+        bytes.push(MLDV2_REPORT_TYPE);
+        bytes.push(0);
+        bytes.extend_from_slice(&self.checksum.to_be_bytes());
+        bytes.extend_from_slice(&[0, 0]);
+        bytes.extend_from_slice(&self.number_of_mcast_address_records.to_be_bytes());
+        for record in &self.records {
+            record.serialize_into(&mut bytes);
+        }
+        bytes
+    }
+
+    /// Parses an MLDv2 report from the ICMPv6 payload.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, 8)?;
+        if buf[0] != MLDV2_REPORT_TYPE {
+            return Err(ParseError::InvalidField("type"));
+        }
+        let count = u16::from_be_bytes([buf[6], buf[7]]);
+        let mut offset = 8;
+        // Records take at least 20 bytes: do not trust the count further.
+        let mut records = Vec::with_capacity((count as usize).min((buf.len() - 8) / 20));
+        for _ in 0..count {
+            let (record, len) = MldV2Record::from_bytes(&buf[offset..])?;
+            records.push(record);
+            offset += len;
+        }
+        Ok(MldV2Report {
+            checksum: u16::from_be_bytes([buf[2], buf[3]]),
+            number_of_mcast_address_records: count,
+            records,
+        })
+    }
+
+    /// Computes the ICMPv6 checksum over the IPv6 pseudo-header.
+    pub fn compute_checksum(&self, source: Ipv6Addr, destination: Ipv6Addr) -> u16 {
+        let mut bytes = self.to_bytes();
+        bytes[2..4].fill(0);
+        ipv6_pseudo_header_checksum(source, destination, ICMPV6_NEXT_HEADER, &bytes)
+    }
+
+    /// Returns the report with its checksum computed for the given addresses.
+    pub fn with_checksum(mut self, source: Ipv6Addr, destination: Ipv6Addr) -> Self {
+        self.checksum = self.compute_checksum(source, destination);
+        self
+    }
+
+    /// Wraps the report in an IPv6 packet sent to `ALL_MLDV2_ROUTERS`, with
+    /// a hop limit of 1 and the Router Alert option in a Hop-by-Hop header
+    /// (RFC 3810 section 5). The checksum is computed for that packet.
+    pub fn to_ipv6(&self, source: Ipv6Addr) -> IPv6 {
+        let report = self.clone().with_checksum(source, ALL_MLDV2_ROUTERS);
+        let router_alert = Ipv6Option::new(OPTION_ROUTER_ALERT, vec![0, 0]);
+        IPv6::with_payload(
+            source,
+            ALL_MLDV2_ROUTERS,
+            ICMPV6_NEXT_HEADER,
+            report.to_bytes(),
+        )
+        .set_hop_limit(HOP_LIMIT)
+        .with_extension_headers(&[ExtensionHeader::HopByHop(vec![router_alert])])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOST: Ipv6Addr = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+    const GROUP: Ipv6Addr = Ipv6Addr::new(0xff3e, 0, 0, 0, 0, 0, 0, 0x1234);
+
+    #[test]
+    fn mldv1_round_trip() {
+        for type_ in [MldType::Query, MldType::Report, MldType::Done] {
+            let message = MldV1::new(type_, 1000, 0, GROUP).with_checksum(HOST, GROUP);
+            let bytes = message.to_bytes();
+            assert_eq!(bytes.len(), MldV1::LEN);
+            assert_eq!(bytes[0], type_ as u8);
+            let parsed = MldV1::from_bytes(&bytes).unwrap();
+            assert_eq!(parsed, message);
+            assert_eq!(parsed.compute_checksum(HOST, GROUP), parsed.checksum);
+        }
+        assert!(MldV1::from_bytes(&[133; MldV1::LEN]).is_err());
+    }
+
+    #[test]
+    fn mldv2_round_trip() {
+        let sources = vec![
+            Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1),
+            Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2),
+        ];
+        let report = MldV2Report::new(vec![
+            MldV2Record::new(CHANGE_TO_INCLUDE_MODE, GROUP, sources),
+            MldV2Record::new(
+                CHANGE_TO_EXCLUDE_MODE,
+                Ipv6Addr::new(0xff05, 0, 0, 0, 0, 0, 0, 2),
+                vec![],
+            ),
+        ])
+        .with_checksum(HOST, ALL_MLDV2_ROUTERS);
+        let bytes = report.to_bytes();
+        assert_eq!(bytes.len(), 8 + 52 + 20);
+        assert_eq!(bytes[6..8], [0, 2]);
+        let parsed = MldV2Report::from_bytes(&bytes).unwrap();
+        assert_eq!(parsed, report);
+        assert_eq!(
+            parsed.compute_checksum(HOST, ALL_MLDV2_ROUTERS),
+            parsed.checksum
+        );
+    }
+
+    #[test]
+    fn auxiliary_data_is_skipped() {
+        let mut bytes = Vec::new();
+        MldV2Record::new(MODE_IS_EXCLUDE, GROUP, vec![]).serialize_into(&mut bytes);
+        bytes[1] = 1;
+        bytes.extend_from_slice(&[0xaa; 4]);
+        let (record, len) = MldV2Record::from_bytes(&bytes).unwrap();
+        assert_eq!(len, 24);
+        assert_eq!(record, MldV2Record::new(MODE_IS_EXCLUDE, GROUP, vec![]));
+    }
+
+    #[test]
+    fn record_count_beyond_the_buffer_is_truncated() {
+        let mut bytes = MldV2Report::new(vec![]).to_bytes();
+        bytes[6..8].copy_from_slice(&u16::MAX.to_be_bytes());
+        assert!(matches!(
+            MldV2Report::from_bytes(&bytes),
+            Err(ParseError::Truncated { .. })
+        ));
+    }
+}
@@ -0,0 +1,227 @@
+use crate::ipv4::IPv4;
+use crate::util::{ParseError, ensure_len};
+
+// Label stack entry (RFC 3032 section 2.1)
+//
+//  0                   1                   2                   3
+//  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                Label                  | TC  |S|      TTL      |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// S marks the bottom of the stack, after which the payload starts. With
+// Segment Routing (RFC 8660) each label is a segment identifier and the
+// top of the stack is the active segment.
+
+/// EtherType of MPLS unicast.
+pub const ETHERTYPE: u16 = 0x8847;
+
+/// Length of a label stack entry in bytes.
+pub const ENTRY_LEN: usize = 4;
+
+/// Largest label value (20 bits).
+pub const MAX_LABEL: u32 = 0xf_ffff;
+
+/// Default TTL of the entries built by this crate.
+pub const DEFAULT_TTL: u8 = 64;
+
+/// MPLS label stack entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MplsEntry {
+    pub label: u32,
+    /// Traffic class.
+    pub tc: u8,
+    pub bottom_of_stack: bool,
+    pub ttl: u8,
+}
+
+impl MplsEntry {
+    /// Constructor to create an entry for `label`, with traffic class 0 and
+    /// the default TTL.
+    pub fn new(label: u32) -> Self {
+        MplsEntry {
+            label: label & MAX_LABEL,
+            tc: 0,
+            bottom_of_stack: false,
+            ttl: DEFAULT_TTL,
+        }
+    }
+
+    // --- SETTER METHODS ---
+
+    pub fn set_tc(mut self, tc: u8) -> Self {
+        self.tc = tc & 0x07;
+        self
+    }
+
+    pub fn set_bottom_of_stack(mut self, bottom_of_stack: bool) -> Self {
+        self.bottom_of_stack = bottom_of_stack;
+        self
+    }
+
+    pub fn set_ttl(mut self, ttl: u8) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    // --- SERIALIZATION ---
+
+    pub fn to_bytes(&self) -> [u8; ENTRY_LEN] {
+        let word = (self.label & MAX_LABEL) << 12
+            | (self.tc as u32 & 0x07) << 9
+            | (self.bottom_of_stack as u32) << 8
+            | self.ttl as u32;
+        word.to_be_bytes()
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> Result<(Self, usize), ParseError> {
+        ensure_len(buf, ENTRY_LEN)?;
+        let word = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]);
+        let entry = MplsEntry {
+            label: word >> 12,
+            tc: (word >> 9 & 0x07) as u8,
+            bottom_of_stack: word & 0x100 != 0,
+            ttl: word as u8,
+        };
+        Ok((entry, ENTRY_LEN))
+    }
+}
+
+/// Name of a label stack entry as most tools know it.
+pub type MplsLabel = MplsEntry;
+
+/// Serializes `entries`, outermost first, with the S bit set on the last
+/// entry and cleared on the others.
+pub fn serialize_stack(entries: &[MplsEntry]) -> Vec<u8> {
+    let last = entries.len().saturating_sub(1);
+    entries
+        .iter()
+        .enumerate()
+        .flat_map(|(i, entry)| entry.set_bottom_of_stack(i == last).to_bytes())
+        .collect()
+}
+
+/// Parses a label stack up to and including its bottom entry. Returns the
+/// entries, outermost first, and the number of bytes they occupy.
+pub fn parse_stack(buf: &[u8]) -> Result<(Vec<MplsEntry>, usize), ParseError> {
+    let mut entries = Vec::new();
+    let mut offset = 0;
+    loop {
+        let (entry, len) = MplsEntry::from_bytes(&buf[offset..])?;
+        entries.push(entry);
+        offset += len;
+        if entry.bottom_of_stack {
+            return Ok((entries, offset));
+        }
+    }
+}
+
+/// SR-MPLS tunnel: a segment list encoded as an MPLS label stack, the
+/// outermost label being the active segment.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct SrMplsTunnel {
+    /// Segments, outermost (active) first.
+    pub segments: Vec<MplsEntry>,
+}
+
+impl SrMplsTunnel {
+    /// Constructor to create a tunnel over the segment identifiers `sids`,
+    /// in the order they are visited.
+    pub fn new(sids: &[u32]) -> Self {
+        SrMplsTunnel {
+            segments: sids.iter().map(|sid| MplsEntry::new(*sid)).collect(),
+        }
+    }
+
+    /// Adds `label` as the new outermost, active segment.
+    pub fn push_segment(mut self, label: u32) -> Self {
+        self.segments.insert(0, MplsEntry::new(label));
+        self
+    }
+
+    /// Label of the active segment, if any is left.
+    pub fn active_segment(&self) -> Option<u32> {
+        self.segments.first().map(|entry| entry.label)
+    }
+
+    /// Tunnel after the active segment is done, as seen by the next
+    /// segment endpoint. Returns `None` if no segment is left.
+    pub fn advance(&self) -> Option<SrMplsTunnel> {
+        let (_, rest) = self.segments.split_first()?;
+        Some(SrMplsTunnel {
+            segments: rest.to_vec(),
+        })
+    }
+
+    // --- SERIALIZATION ---
+
+    /// Serializes the label stack, with the S bit set on its last entry.
+    pub fn stack_bytes(&self) -> Vec<u8> {
+        serialize_stack(&self.segments)
+    }
+
+    /// Serializes the label stack followed by `inner`.
+    pub fn to_encapsulated_ipv4(&self, inner: &IPv4) -> Vec<u8> {
+        let mut bytes = self.stack_bytes();
+        bytes.extend_from_slice(&inner.to_bytes());
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    #[test]
+    fn three_label_stack_round_trip() {
+        let tunnel = SrMplsTunnel::new(&[16002, 16003]).push_segment(16001);
+        let bytes = tunnel.stack_bytes();
+        assert_eq!(
+            bytes,
+            [
+                0x03, 0xe8, 0x10, 0x40, // 16001, TTL 64
+                0x03, 0xe8, 0x20, 0x40, // 16002, TTL 64
+                0x03, 0xe8, 0x31, 0x40, // 16003, S, TTL 64
+            ]
+        );
+        let (entries, len) = parse_stack(&bytes).unwrap();
+        assert_eq!(len, 12);
+        let bottom: Vec<bool> = entries.iter().map(|entry| entry.bottom_of_stack).collect();
+        assert_eq!(bottom, [false, false, true]);
+        let labels: Vec<u32> = entries.iter().map(|entry| entry.label).collect();
+        assert_eq!(labels, [16001, 16002, 16003]);
+    }
+
+    #[test]
+    fn advance_pops_the_active_segment() {
+        let tunnel = SrMplsTunnel::new(&[16001, 16002, 16003]);
+        assert_eq!(tunnel.active_segment(), Some(16001));
+        let tunnel = tunnel.advance().unwrap();
+        assert_eq!(tunnel.active_segment(), Some(16002));
+        // The S bit moves with the bottom of the shorter stack.
+        assert_eq!(
+            tunnel.stack_bytes(),
+            [0x03, 0xe8, 0x20, 0x40, 0x03, 0xe8, 0x31, 0x40]
+        );
+        let tunnel = tunnel.advance().unwrap().advance().unwrap();
+        assert_eq!(tunnel.active_segment(), None);
+        assert!(tunnel.stack_bytes().is_empty());
+        assert_eq!(tunnel.advance(), None);
+    }
+
+    #[test]
+    fn encapsulated_ipv4_follows_the_stack() {
+        let inner = IPv4::with_payload(
+            Ipv4Addr::new(192, 0, 2, 1),
+            Ipv4Addr::new(192, 0, 2, 2),
+            17,
+            vec![0; 8],
+        );
+        let bytes = SrMplsTunnel::new(&[16001, 16002, 16003]).to_encapsulated_ipv4(&inner);
+        assert_eq!(bytes.len(), 12 + 28);
+        assert_eq!(bytes[11], 0x40);
+        assert_eq!(bytes[12..], inner.to_bytes()[..]);
+    }
+}
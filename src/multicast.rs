@@ -0,0 +1,57 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::flow;
+use crate::util::{read_ipv4, read_ipv6};
+
+// Multicast MAC mapping:
+//   IPv4 (RFC 1112): 01:00:5e followed by the low 23 bits of the group.
+//   IPv6 (RFC 2464): 33:33 followed by the low 32 bits of the group.
+
+/// Ethernet address an IPv4 multicast group maps to.
+pub fn ipv4_multicast_mac(group: Ipv4Addr) -> [u8; 6] {
+    let octets = group.octets();
+    [0x01, 0x00, 0x5e, octets[1] & 0x7f, octets[2], octets[3]]
+}
+
+/// Ethernet address an IPv6 multicast group maps to.
+pub fn ipv6_multicast_mac(group: Ipv6Addr) -> [u8; 6] {
+    let octets = group.octets();
+    [0x33, 0x33, octets[12], octets[13], octets[14], octets[15]]
+}
+
+/// Ethernet address a multicast group maps to, or `None` if `address` is
+/// not a multicast address.
+pub fn multicast_mac(address: IpAddr) -> Option<[u8; 6]> {
+    match address {
+        IpAddr::V4(group) if group.is_multicast() => Some(ipv4_multicast_mac(group)),
+        IpAddr::V6(group) if group.is_multicast() => Some(ipv6_multicast_mac(group)),
+        _ => None,
+    }
+}
+
+/// A frame whose destination MAC does not match its multicast IP destination.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MulticastMismatch {
+    pub group: IpAddr,
+    pub expected: [u8; 6],
+    pub actual: [u8; 6],
+}
+
+/// Checks an Ethernet frame carrying IPv4 or IPv6 to a multicast group.
+/// Returns the mismatch if its destination MAC is not the one derived from
+/// the group, and `None` for consistent or non-multicast frames.
+pub fn check_frame(frame: &[u8]) -> Option<MulticastMismatch> {
+    let ip = flow::ethernet_payload(frame)?;
+    let group: IpAddr = match ip.first()? >> 4 {
+        4 if ip.len() >= 20 => read_ipv4(ip, 16).into(),
+        6 if ip.len() >= 40 => read_ipv6(ip, 24).into(),
+        _ => return None,
+    };
+    let expected = multicast_mac(group)?;
+    let actual = [frame[0], frame[1], frame[2], frame[3], frame[4], frame[5]];
+    (expected != actual).then_some(MulticastMismatch {
+        group,
+        expected,
+        actual,
+    })
+}
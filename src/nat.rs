@@ -0,0 +1,333 @@
+use std::net::IpAddr;
+
+use crate::flow::FiveTuple;
+use crate::truncate::layout;
+use crate::util::{IpAddrPair, IpCidr, MixedAddressFamilies, PseudoHeader, update_checksum};
+
+const PROTOCOL_UDP: u8 = 17;
+const PROTOCOL_ICMPV6: u8 = 58;
+
+/// Endpoint of a packet a `NatRule` rewrites.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NatKind {
+    /// Source NAT: the source address, and port if given, are rewritten.
+    Source,
+    /// Destination NAT: the destination address, and port if given, are
+    /// rewritten.
+    Destination,
+}
+
+/// Stateless translation of one endpoint of the packets of one address
+/// family. Replies are not translated back: add the reverse rule for
+/// them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NatRule {
+    pub kind: NatKind,
+    /// Protocol of the packets translated, any when `None`.
+    pub protocol: Option<u8>,
+    /// Addresses translated, on the endpoint given by `kind`.
+    pub matching: IpCidr,
+    /// Port translated, on the same endpoint, any when `None`.
+    pub port: Option<u16>,
+    pub translated: IpAddr,
+    /// Port written along with the address, unchanged when `None`.
+    pub translated_port: Option<u16>,
+}
+
+impl NatRule {
+    /// Constructor to create a rule rewriting the `kind` address of the
+    /// packets inside `matching` to `translated`. Fails if `translated`
+    /// is not of the family of `matching`.
+    pub fn new(
+        kind: NatKind,
+        matching: IpCidr,
+        translated: IpAddr,
+    ) -> Result<Self, MixedAddressFamilies> {
+        IpAddrPair::new(matching.address(), translated)?;
+        Ok(NatRule {
+            kind,
+            protocol: None,
+            matching,
+            port: None,
+            translated,
+            translated_port: None,
+        })
+    }
+
+    // --- SETTER METHODS ---
+
+    pub fn set_protocol(mut self, protocol: u8) -> Self {
+        self.protocol = Some(protocol);
+        self
+    }
+
+    pub fn set_port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    pub fn set_translated_port(mut self, translated_port: u16) -> Self {
+        self.translated_port = Some(translated_port);
+        self
+    }
+
+    /// Returns the key of `key`'s packets once translated, or `None` if
+    /// the rule does not apply to them.
+    pub fn translate(&self, key: &FiveTuple) -> Option<FiveTuple> {
+        let (address, port) = match self.kind {
+            NatKind::Source => (key.source, key.source_port),
+            NatKind::Destination => (key.destination, key.destination_port),
+        };
+        if !self.matching.contains(address)
+            || self
+                .protocol
+                .is_some_and(|protocol| protocol != key.protocol)
+            || self.port.is_some_and(|expected| expected != port)
+        {
+            return None;
+        }
+        let mut translated = *key;
+        let (address, port) = match self.kind {
+            NatKind::Source => (&mut translated.source, &mut translated.source_port),
+            NatKind::Destination => (
+                &mut translated.destination,
+                &mut translated.destination_port,
+            ),
+        };
+        *address = self.translated;
+        if let Some(translated_port) = self.translated_port {
+            *port = translated_port;
+        }
+        Some(translated)
+    }
+}
+
+/// Ordered NAT rules: the first rule that applies to a packet translates
+/// it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NatRules {
+    pub rules: Vec<NatRule>,
+}
+
+impl NatRules {
+    /// Constructor to create an empty rule set, translating nothing.
+    pub fn new() -> Self {
+        NatRules::default()
+    }
+
+    pub fn add_rule(mut self, rule: NatRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Returns the index of the first rule applying to `key` with the
+    /// translated key, or `None` if no rule applies. Fails for a key whose
+    /// addresses are of different families.
+    pub fn translate(
+        &self,
+        key: &FiveTuple,
+    ) -> Result<Option<(usize, FiveTuple)>, MixedAddressFamilies> {
+        key.addresses()?;
+        Ok(self
+            .rules
+            .iter()
+            .enumerate()
+            .find_map(|(index, rule)| rule.translate(key).map(|key| (index, key))))
+    }
+
+    /// Translates the IPv4 or IPv6 packet in `frame`, captured with
+    /// `link_type`, in place. The IPv4 header checksum and the TCP, UDP
+    /// and ICMPv6 checksums are updated incrementally (RFC 1624), so
+    /// first fragments and truncated packets keep valid checksums; later
+    /// fragments carry no ports and only match port-less rules. Returns
+    /// the index of the rule applied, if any.
+    pub fn apply(
+        &self,
+        link_type: u32,
+        frame: &mut [u8],
+    ) -> Result<Option<usize>, MixedAddressFamilies> {
+        let Some(key) = FiveTuple::from_frame(link_type, frame) else {
+            return Ok(None);
+        };
+        let Some((index, translated)) = self.translate(&key)? else {
+            return Ok(None);
+        };
+        let Some(layout) = layout(link_type, frame) else {
+            return Ok(None);
+        };
+        let ip = layout.ip_offset;
+        let (addresses, old_addresses) = match translated.addresses()?.pseudo_header()? {
+            PseudoHeader::V4 {
+                source,
+                destination,
+            } => {
+                let old = frame[ip + 12..ip + 20].to_vec();
+                frame[ip + 12..ip + 16].copy_from_slice(&source.octets());
+                frame[ip + 16..ip + 20].copy_from_slice(&destination.octets());
+                let new = frame[ip + 12..ip + 20].to_vec();
+                update_checksum(&mut frame[ip..], 10, &old, &new, false);
+                (new, old)
+            }
+            PseudoHeader::V6 {
+                source,
+                destination,
+            } => {
+                let old = frame[ip + 8..ip + 40].to_vec();
+                frame[ip + 8..ip + 24].copy_from_slice(&source.octets());
+                frame[ip + 24..ip + 40].copy_from_slice(&destination.octets());
+                (frame[ip + 8..ip + 40].to_vec(), old)
+            }
+        };
+
+        match layout.transport {
+            Some((offset, protocol)) => {
+                let segment = &mut frame[offset..layout.end];
+                let old_ports = segment[..4].to_vec();
+                segment[..2].copy_from_slice(&translated.source_port.to_be_bytes());
+                segment[2..4].copy_from_slice(&translated.destination_port.to_be_bytes());
+                let (checksum_offset, udp) = match protocol {
+                    PROTOCOL_UDP => (6, true),
+                    _ => (16, false),
+                };
+                let old = [old_addresses, old_ports].concat();
+                let new = [addresses, segment[..4].to_vec()].concat();
+                update_checksum(segment, checksum_offset, &old, &new, udp);
+            }
+            None if layout.protocol == PROTOCOL_ICMPV6 => {
+                let segment = &mut frame[layout.payload_offset..layout.end];
+                update_checksum(segment, 2, &old_addresses, &addresses, false);
+            }
+            None => {}
+        }
+        Ok(Some(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use super::*;
+    use crate::ipv4::IPv4;
+    use crate::ipv6::IPv6;
+    use crate::pcap::LINKTYPE_RAW;
+    use crate::tcp::{TCP, flags};
+    use crate::udp::UDP;
+
+    const PROTOCOL_TCP: u8 = 6;
+
+    fn tcp_v4(destination: Ipv4Addr, destination_port: u16) -> Vec<u8> {
+        let source = Ipv4Addr::new(10, 0, 0, 1);
+        let pseudo_header = PseudoHeader::V4 {
+            source,
+            destination,
+        };
+        let tcp =
+            TCP::segment(40000, destination_port, 1, 0, flags::SYN).with_checksum(&pseudo_header);
+        IPv4::with_payload(source, destination, PROTOCOL_TCP, tcp.to_bytes())
+            .with_checksum()
+            .to_bytes()
+    }
+
+    #[test]
+    fn destination_nat_keeps_checksums_valid() {
+        let rules = NatRules::new()
+            .add_rule(
+                NatRule::new(
+                    NatKind::Destination,
+                    "10.0.0.5".parse().unwrap(),
+                    Ipv4Addr::new(192, 168, 1, 5).into(),
+                )
+                .unwrap()
+                .set_protocol(PROTOCOL_TCP)
+                .set_port(80)
+                .set_translated_port(8443),
+            )
+            .add_rule(
+                NatRule::new(
+                    NatKind::Source,
+                    "10.0.0.0/8".parse().unwrap(),
+                    Ipv4Addr::new(203, 0, 113, 1).into(),
+                )
+                .unwrap(),
+            );
+
+        let mut packet = tcp_v4(Ipv4Addr::new(10, 0, 0, 5), 80);
+        assert_eq!(rules.apply(LINKTYPE_RAW, &mut packet), Ok(Some(0)));
+        let ipv4 = IPv4::from_bytes(&packet).unwrap();
+        assert_eq!(ipv4.destination, Ipv4Addr::new(192, 168, 1, 5));
+        assert_eq!(ipv4.compute_checksum(), ipv4.checksum);
+        let tcp = TCP::from_bytes(&ipv4.payload).unwrap();
+        assert_eq!(tcp.destination, 8443);
+        let pseudo_header = IpAddrPair::from((ipv4.source, ipv4.destination))
+            .pseudo_header()
+            .unwrap();
+        assert_eq!(tcp.compute_checksum(&pseudo_header), tcp.checksum);
+
+        // Another port falls through to the source rule.
+        let mut packet = tcp_v4(Ipv4Addr::new(10, 0, 0, 5), 22);
+        assert_eq!(rules.apply(LINKTYPE_RAW, &mut packet), Ok(Some(1)));
+        let ipv4 = IPv4::from_bytes(&packet).unwrap();
+        assert_eq!(ipv4.source, Ipv4Addr::new(203, 0, 113, 1));
+        assert_eq!(ipv4.compute_checksum(), ipv4.checksum);
+
+        // A packet from and to outside 10.0.0.0/8 is left alone.
+        let mut packet = tcp_v4(Ipv4Addr::new(172, 16, 0, 5), 80);
+        packet[12] = 172;
+        let untouched = packet.clone();
+        assert_eq!(rules.apply(LINKTYPE_RAW, &mut packet), Ok(None));
+        assert_eq!(packet, untouched);
+    }
+
+    #[test]
+    fn ipv6_source_nat() {
+        let source: Ipv6Addr = "fd00::1".parse().unwrap();
+        let destination: Ipv6Addr = "2001:db8::53".parse().unwrap();
+        let pseudo_header = PseudoHeader::V6 {
+            source,
+            destination,
+        };
+        let udp = UDP::new(5353, 53, b"query".to_vec()).with_checksum(&pseudo_header);
+        let mut packet =
+            IPv6::with_payload(source, destination, PROTOCOL_UDP, udp.to_bytes()).to_bytes();
+        let translated: Ipv6Addr = "2001:db8:ffff::1".parse().unwrap();
+        let rules = NatRules::new().add_rule(
+            NatRule::new(
+                NatKind::Source,
+                "fd00::/8".parse().unwrap(),
+                translated.into(),
+            )
+            .unwrap(),
+        );
+        assert_eq!(rules.apply(LINKTYPE_RAW, &mut packet), Ok(Some(0)));
+        let ipv6 = IPv6::from_bytes(&packet).unwrap();
+        assert_eq!(ipv6.source, translated);
+        let udp = UDP::from_bytes(&ipv6.payload).unwrap();
+        let pseudo_header = PseudoHeader::V6 {
+            source: translated,
+            destination,
+        };
+        assert_eq!(udp.compute_checksum(&pseudo_header), udp.checksum);
+    }
+
+    #[test]
+    fn mixed_families_are_rejected() {
+        let v4 = Ipv4Addr::new(192, 0, 2, 1);
+        let v6 = Ipv6Addr::LOCALHOST;
+        assert_eq!(
+            NatRule::new(NatKind::Source, "10.0.0.0/8".parse().unwrap(), v6.into()),
+            Err(MixedAddressFamilies {
+                src: Ipv4Addr::new(10, 0, 0, 0).into(),
+                dst: v6.into()
+            })
+        );
+        let mixed = FiveTuple {
+            protocol: PROTOCOL_TCP,
+            source: v4.into(),
+            source_port: 1,
+            destination: v6.into(),
+            destination_port: 2,
+        };
+        assert!(NatRules::new().translate(&mixed).is_err());
+    }
+}
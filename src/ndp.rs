@@ -0,0 +1,339 @@
+use std::net::Ipv6Addr;
+
+use crate::ethernet::{MacAddr, read_mac};
+use crate::icmpv6::{Icmpv6, NEXT_HEADER};
+use crate::ipv6::IPv6;
+use crate::util::{ParseError, ensure_len, read_ipv6};
+
+// Neighbor Discovery (RFC 4861), carried in ICMPv6. After the ICMPv6
+// type, code and checksum:
+//
+// Router Solicitation         Reserved (32)
+// Router Advertisement        Cur Hop Limit (8) | M|O|Reserved (8) |
+//                             Router Lifetime (16), Reachable Time (32),
+//                             Retrans Timer (32)
+// Neighbor Solicitation       Reserved (32), Target Address (128)
+// Neighbor Advertisement      R|S|O|Reserved (32), Target Address (128)
+//
+// followed by options, each a multiple of 8 bytes:
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |     Type      |    Length     |              ...              |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// with the length in units of 8 bytes, type and length included.
+
+// ICMPv6 types of the Neighbor Discovery messages.
+pub const TYPE_ROUTER_SOLICITATION: u8 = 133;
+pub const TYPE_ROUTER_ADVERTISEMENT: u8 = 134;
+pub const TYPE_NEIGHBOR_SOLICITATION: u8 = 135;
+pub const TYPE_NEIGHBOR_ADVERTISEMENT: u8 = 136;
+
+// Option types.
+pub const OPTION_SOURCE_LINK_LAYER_ADDRESS: u8 = 1;
+pub const OPTION_TARGET_LINK_LAYER_ADDRESS: u8 = 2;
+pub const OPTION_PREFIX_INFORMATION: u8 = 3;
+pub const OPTION_MTU: u8 = 5;
+
+/// Hop limit of every Neighbor Discovery message, which receivers check
+/// to reject messages from off-link.
+pub const HOP_LIMIT: u8 = 255;
+
+/// Link-local scope all-nodes multicast address.
+pub const ALL_NODES: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1);
+
+/// Link-local scope all-routers multicast address.
+pub const ALL_ROUTERS: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 2);
+
+/// Solicited-node multicast address of `address`, to which neighbor
+/// solicitations for it are sent.
+pub fn solicited_node_multicast(address: Ipv6Addr) -> Ipv6Addr {
+    let octets = address.octets();
+    Ipv6Addr::new(
+        0xff02,
+        0,
+        0,
+        0,
+        0,
+        1,
+        0xff00 | octets[13] as u16,
+        u16::from_be_bytes([octets[14], octets[15]]),
+    )
+}
+
+/// Prefix Information option of router advertisements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PrefixInformation {
+    pub prefix_length: u8,
+    /// The prefix is on-link (L flag).
+    pub on_link: bool,
+    /// The prefix may be used for address autoconfiguration (A flag).
+    pub autonomous: bool,
+    pub valid_lifetime: u32,
+    pub preferred_lifetime: u32,
+    pub prefix: Ipv6Addr,
+}
+
+/// A Neighbor Discovery option.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum NdpOption {
+    SourceLinkLayerAddress(MacAddr),
+    TargetLinkLayerAddress(MacAddr),
+    PrefixInformation(PrefixInformation),
+    Mtu(u32),
+    /// Any other option; `data` excludes the type and length octets.
+    Unknown {
+        option_type: u8,
+        data: Vec<u8>,
+    },
+}
+
+impl NdpOption {
+    // --- SERIALIZATION ---
+
+    /// Appends the option to `bytes`, padded with zeros to a multiple of
+    /// 8 bytes.
+    pub fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        let start = bytes.len();
+        match self {
+            NdpOption::SourceLinkLayerAddress(mac) | NdpOption::TargetLinkLayerAddress(mac) => {
+                let option_type = match self {
+                    NdpOption::SourceLinkLayerAddress(_) => OPTION_SOURCE_LINK_LAYER_ADDRESS,
+                    _ => OPTION_TARGET_LINK_LAYER_ADDRESS,
+                };
+                bytes.extend_from_slice(&[option_type, 0]);
+                bytes.extend_from_slice(&mac.octets());
+            }
+            NdpOption::PrefixInformation(info) => {
+                bytes.extend_from_slice(&[OPTION_PREFIX_INFORMATION, 0, info.prefix_length]);
+                bytes.push((info.on_link as u8) << 7 | (info.autonomous as u8) << 6);
+                bytes.extend_from_slice(&info.valid_lifetime.to_be_bytes());
+                bytes.extend_from_slice(&info.preferred_lifetime.to_be_bytes());
+                bytes.extend_from_slice(&[0; 4]);
+                bytes.extend_from_slice(&info.prefix.octets());
+            }
+            NdpOption::Mtu(mtu) => {
+                bytes.extend_from_slice(&[OPTION_MTU, 0, 0, 0]);
+                bytes.extend_from_slice(&mtu.to_be_bytes());
+            }
+            NdpOption::Unknown { option_type, data } => {
+                bytes.extend_from_slice(&[*option_type, 0]);
+                bytes.extend_from_slice(data);
+            }
+        }
+        let len = (bytes.len() - start).div_ceil(8) * 8;
+        bytes.resize(start + len, 0);
+        bytes[start + 1] = (len / 8) as u8;
+    }
+
+    /// Parses an option, returning it with the number of bytes consumed.
+    pub fn from_bytes(buf: &[u8]) -> Result<(Self, usize), ParseError> {
+        ensure_len(buf, 2)?;
+        let len = buf[1] as usize * 8;
+        if len == 0 {
+            return Err(ParseError::InvalidField("option length"));
+        }
+        ensure_len(buf, len)?;
+        let option = match (buf[0], len) {
+            (OPTION_SOURCE_LINK_LAYER_ADDRESS, 8) => {
+                NdpOption::SourceLinkLayerAddress(read_mac(buf, 2))
+            }
+            (OPTION_TARGET_LINK_LAYER_ADDRESS, 8) => {
+                NdpOption::TargetLinkLayerAddress(read_mac(buf, 2))
+            }
+            (OPTION_PREFIX_INFORMATION, 32) => NdpOption::PrefixInformation(PrefixInformation {
+                prefix_length: buf[2],
+                on_link: buf[3] & 0x80 != 0,
+                autonomous: buf[3] & 0x40 != 0,
+                valid_lifetime: u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]),
+                preferred_lifetime: u32::from_be_bytes([buf[8], buf[9], buf[10], buf[11]]),
+                prefix: read_ipv6(buf, 16),
+            }),
+            (OPTION_MTU, 8) => NdpOption::Mtu(u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]])),
+            (option_type, _) => NdpOption::Unknown {
+                option_type,
+                data: buf[2..len].to_vec(),
+            },
+        };
+        Ok((option, len))
+    }
+
+    /// Parses the options filling `buf`.
+    pub fn parse_all(mut buf: &[u8]) -> Result<Vec<NdpOption>, ParseError> {
+        let mut options = Vec::new();
+        while !buf.is_empty() {
+            let (option, len) = NdpOption::from_bytes(buf)?;
+            options.push(option);
+            buf = &buf[len..];
+        }
+        Ok(options)
+    }
+}
+
+/// A Neighbor Discovery message.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum NdpMessage {
+    RouterSolicitation {
+        options: Vec<NdpOption>,
+    },
+    RouterAdvertisement {
+        cur_hop_limit: u8,
+        /// Addresses are available from DHCPv6 (M flag).
+        managed: bool,
+        /// Other configuration is available from DHCPv6 (O flag).
+        other: bool,
+        /// Lifetime as a default router in seconds; 0 if it is not one.
+        router_lifetime: u16,
+        reachable_time: u32,
+        retrans_timer: u32,
+        options: Vec<NdpOption>,
+    },
+    NeighborSolicitation {
+        target: Ipv6Addr,
+        options: Vec<NdpOption>,
+    },
+    NeighborAdvertisement {
+        router: bool,
+        solicited: bool,
+        override_: bool,
+        target: Ipv6Addr,
+        options: Vec<NdpOption>,
+    },
+}
+
+impl NdpMessage {
+    /// Builds the solicitation sent by the host at `source_mac` to
+    /// resolve `target`.
+    pub fn neighbor_solicitation(source_mac: MacAddr, target: Ipv6Addr) -> Self {
+        NdpMessage::NeighborSolicitation {
+            target,
+            options: vec![NdpOption::SourceLinkLayerAddress(source_mac)],
+        }
+    }
+
+    /// Builds the solicited advertisement answering a solicitation for
+    /// `target`, owned by the host at `target_mac`.
+    pub fn neighbor_advertisement(target_mac: MacAddr, target: Ipv6Addr, router: bool) -> Self {
+        NdpMessage::NeighborAdvertisement {
+            router,
+            solicited: true,
+            override_: true,
+            target,
+            options: vec![NdpOption::TargetLinkLayerAddress(target_mac)],
+        }
+    }
+
+    /// Returns the options of the message.
+    pub fn options(&self) -> &[NdpOption] {
+        match self {
+            NdpMessage::RouterSolicitation { options }
+            | NdpMessage::RouterAdvertisement { options, .. }
+            | NdpMessage::NeighborSolicitation { options, .. }
+            | NdpMessage::NeighborAdvertisement { options, .. } => options,
+        }
+    }
+
+    // --- SERIALIZATION ---
+
+    /// Builds the ICMPv6 message; the checksum is left to
+    /// `Icmpv6::with_checksum`.
+    pub fn to_icmpv6(&self) -> Icmpv6 {
+        let mut body = Vec::new();
+        let icmp_type = match self {
+            NdpMessage::RouterSolicitation { .. } => {
+                body.extend_from_slice(&[0; 4]);
+                TYPE_ROUTER_SOLICITATION
+            }
+            NdpMessage::RouterAdvertisement {
+                cur_hop_limit,
+                managed,
+                other,
+                router_lifetime,
+                reachable_time,
+                retrans_timer,
+                ..
+            } => {
+                body.push(*cur_hop_limit);
+                body.push((*managed as u8) << 7 | (*other as u8) << 6);
+                body.extend_from_slice(&router_lifetime.to_be_bytes());
+                body.extend_from_slice(&reachable_time.to_be_bytes());
+                body.extend_from_slice(&retrans_timer.to_be_bytes());
+                TYPE_ROUTER_ADVERTISEMENT
+            }
+            NdpMessage::NeighborSolicitation { target, .. } => {
+                body.extend_from_slice(&[0; 4]);
+                body.extend_from_slice(&target.octets());
+                TYPE_NEIGHBOR_SOLICITATION
+            }
+            NdpMessage::NeighborAdvertisement {
+                router,
+                solicited,
+                override_,
+                target,
+                ..
+            } => {
+                let flags =
+                    (*router as u8) << 7 | (*solicited as u8) << 6 | (*override_ as u8) << 5;
+                body.extend_from_slice(&[flags, 0, 0, 0]);
+                body.extend_from_slice(&target.octets());
+                TYPE_NEIGHBOR_ADVERTISEMENT
+            }
+        };
+        for option in self.options() {
+            option.serialize_into(&mut body);
+        }
+        Icmpv6::new(icmp_type, 0, 0, body)
+    }
+
+    /// Wraps the message in an IPv6 packet with the hop limit of 255 and
+    /// the ICMPv6 checksum computed.
+    pub fn to_ipv6(&self, source: Ipv6Addr, destination: Ipv6Addr) -> IPv6 {
+        let icmp = self.to_icmpv6().with_checksum(source, destination);
+        IPv6::with_payload(source, destination, NEXT_HEADER, icmp.to_bytes())
+            .set_hop_limit(HOP_LIMIT)
+    }
+
+    /// Decodes the Neighbor Discovery message carried by `icmp`.
+    pub fn from_icmpv6(icmp: &Icmpv6) -> Result<Self, ParseError> {
+        let body = &icmp.body;
+        let message = match icmp.icmp_type {
+            TYPE_ROUTER_SOLICITATION => {
+                ensure_len(body, 4)?;
+                NdpMessage::RouterSolicitation {
+                    options: NdpOption::parse_all(&body[4..])?,
+                }
+            }
+            TYPE_ROUTER_ADVERTISEMENT => {
+                ensure_len(body, 12)?;
+                NdpMessage::RouterAdvertisement {
+                    cur_hop_limit: body[0],
+                    managed: body[1] & 0x80 != 0,
+                    other: body[1] & 0x40 != 0,
+                    router_lifetime: u16::from_be_bytes([body[2], body[3]]),
+                    reachable_time: u32::from_be_bytes([body[4], body[5], body[6], body[7]]),
+                    retrans_timer: u32::from_be_bytes([body[8], body[9], body[10], body[11]]),
+                    options: NdpOption::parse_all(&body[12..])?,
+                }
+            }
+            TYPE_NEIGHBOR_SOLICITATION => {
+                ensure_len(body, 20)?;
+                NdpMessage::NeighborSolicitation {
+                    target: read_ipv6(body, 4),
+                    options: NdpOption::parse_all(&body[20..])?,
+                }
+            }
+            TYPE_NEIGHBOR_ADVERTISEMENT => {
+                ensure_len(body, 20)?;
+                NdpMessage::NeighborAdvertisement {
+                    router: body[0] & 0x80 != 0,
+                    solicited: body[0] & 0x40 != 0,
+                    override_: body[0] & 0x20 != 0,
+                    target: read_ipv6(body, 4),
+                    options: NdpOption::parse_all(&body[20..])?,
+                }
+            }
+            _ => return Err(ParseError::InvalidField("type")),
+        };
+        Ok(message)
+    }
+}
@@ -0,0 +1,281 @@
+use crate::util::{ParseError, ensure_len};
+
+// Network Service Header (RFC 8300)
+//
+//  0                   1                   2                   3
+//  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |Ver|O|U|    TTL    |   Length  |U|U|U|U|MD Type| Next Protocol |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |          Service Path Identifier (SPI)        | Service Index |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                                                               |
+// ~               Context Headers (MD Type 1: 4 words)            ~
+// |                                                               |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// Length is the length of the whole header in 4-byte words.
+
+/// EtherType of NSH.
+pub const ETHERTYPE: u16 = 0x894f;
+
+/// Default TTL (RFC 8300 section 2.2).
+pub const DEFAULT_TTL: u8 = 63;
+
+// MD types.
+pub const MD_TYPE_1: u8 = 0x1;
+pub const MD_TYPE_2: u8 = 0x2;
+
+// Next protocols.
+pub const NEXT_PROTOCOL_IPV4: u8 = 0x1;
+pub const NEXT_PROTOCOL_IPV6: u8 = 0x2;
+pub const NEXT_PROTOCOL_ETHERNET: u8 = 0x3;
+pub const NEXT_PROTOCOL_NSH: u8 = 0x4;
+
+/// What a service function does with a packet, returned by the handler of
+/// `Nsh::traverse_chain`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NshAction {
+    /// Pass the packet to the next hop.
+    Forward,
+    /// Drop the packet.
+    Drop,
+    /// Replace the MD type 1 context headers, then forward.
+    ModifyMetadata([u32; 4]),
+    /// Last service function: the packet leaves the chain and is returned
+    /// by `traverse_chain`. Not one of the RFC 8300 forwarding decisions:
+    /// without it a traversal ends only by a drop or by the service index
+    /// running out, and never yields the packet.
+    Deliver,
+}
+
+/// Header NSH, followed by its payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Nsh {
+    pub version: u8,
+    pub oam: bool,
+    pub ttl: u8,
+    pub length: u8,
+    pub md_type: u8,
+    pub next_protocol: u8,
+    pub service_path_id: u32,
+    pub service_index: u8,
+    /// Context headers of MD type 1.
+    pub context: [u32; 4],
+    /// Variable-length context headers of MD type 2.
+    pub metadata: Vec<u8>,
+    pub payload: Vec<u8>,
+}
+
+impl Nsh {
+    /// Builds an MD type 1 header with the default TTL.
+    pub fn new(
+        service_path_id: u32,
+        service_index: u8,
+        next_protocol: u8,
+        context: [u32; 4],
+        payload: Vec<u8>,
+    ) -> Self {
+        Nsh {
+            version: 0,
+            oam: false,
+            ttl: DEFAULT_TTL,
+            length: 6,
+            md_type: MD_TYPE_1,
+            next_protocol,
+            service_path_id,
+            service_index,
+            context,
+            metadata: Vec::new(),
+            payload,
+        }
+    }
+
+    // --- SETTER METHODS ---
+
+    /// Sets the service path identifier (only the low 24 bits are serialized).
+    pub fn set_service_path_id(mut self, service_path_id: u32) -> Self {
+        self.service_path_id = service_path_id;
+        self
+    }
+
+    /// Sets the service index.
+    pub fn set_service_index(mut self, service_index: u8) -> Self {
+        self.service_index = service_index;
+        self
+    }
+
+    /// Sets the TTL (only the low 6 bits are serialized).
+    pub fn set_ttl(mut self, ttl: u8) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Sets the MD type 1 context headers.
+    pub fn set_context(mut self, context: [u32; 4]) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Sets the payload.
+    pub fn set_payload(mut self, payload: Vec<u8>) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    // --- SERVICE FUNCTION CHAINING ---
+
+    /// Walks the packet along its service path. `handler` is called at each
+    /// hop with the current header; every forward decrements the service
+    /// index. Returns the packet once a hop delivers it (`NshAction::Deliver`,
+    /// which the last service function of a chain returns), or `None` if it
+    /// is dropped or its service index reaches 0 (end of the chain).
+    pub fn traverse_chain<F>(mut self, handler: F) -> Option<Nsh>
+    where
+        F: Fn(&Nsh) -> NshAction,
+    {
+        while self.service_index > 0 {
+            match handler(&self) {
+                NshAction::Forward => {}
+                NshAction::Drop => return None,
+                NshAction::ModifyMetadata(context) => self.context = context,
+                NshAction::Deliver => return Some(self),
+            }
+            self.service_index -= 1;
+        }
+        None
+    }
+
+    // --- SERIALIZATION ---
+
+    /// Serializes the header and payload, using the length field as is.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.length as usize * 4 + self.payload.len());
+        let first_word = (self.version as u32 & 0x3) << 30
+            | (self.oam as u32) << 29
+            | (self.ttl as u32 & 0x3f) << 22
+            | (self.length as u32 & 0x3f) << 16
+            | (self.md_type as u32 & 0xf) << 8
+            | self.next_protocol as u32;
+        bytes.extend_from_slice(&first_word.to_be_bytes());
+        let path = (self.service_path_id & 0x00ff_ffff) << 8 | self.service_index as u32;
+        bytes.extend_from_slice(&path.to_be_bytes());
+        if self.md_type == MD_TYPE_1 {
+            for word in self.context {
+                bytes.extend_from_slice(&word.to_be_bytes());
+            }
+        } else {
+            bytes.extend_from_slice(&self.metadata);
+        }
+        bytes.extend_from_slice(&self.payload);
+        bytes
+    }
+
+    /// Parses an NSH packet. Context headers other than MD type 1 are kept
+    /// as raw metadata.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, 8)?;
+        let first_word = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]);
+        let path = u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]);
+        let length = ((first_word >> 16) & 0x3f) as u8;
+        let md_type = ((first_word >> 8) & 0xf) as u8;
+        let header_len = length as usize * 4;
+        if header_len < 8 || (md_type == MD_TYPE_1 && header_len != 24) {
+            return Err(ParseError::InvalidField("length"));
+        }
+        ensure_len(buf, header_len)?;
+        let mut context = [0u32; 4];
+        let mut metadata = Vec::new();
+        if md_type == MD_TYPE_1 {
+            for (index, word) in context.iter_mut().enumerate() {
+                let offset = 8 + index * 4;
+                *word = u32::from_be_bytes([
+                    buf[offset],
+                    buf[offset + 1],
+                    buf[offset + 2],
+                    buf[offset + 3],
+                ]);
+            }
+        } else {
+            metadata = buf[8..header_len].to_vec();
+        }
+        Ok(Nsh {
+            version: (first_word >> 30) as u8,
+            oam: first_word & 0x2000_0000 != 0,
+            ttl: ((first_word >> 22) & 0x3f) as u8,
+            length,
+            md_type,
+            next_protocol: first_word as u8,
+            service_path_id: path >> 8,
+            service_index: path as u8,
+            context,
+            metadata,
+            payload: buf[header_len..].to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    fn packet(service_index: u8) -> Nsh {
+        Nsh::new(
+            0x1234,
+            service_index,
+            NEXT_PROTOCOL_IPV4,
+            [1, 2, 3, 4],
+            vec![0x45; 20],
+        )
+    }
+
+    #[test]
+    fn three_hop_chain_keeps_modified_metadata() {
+        let hops = Cell::new(0);
+        let delivered = packet(3)
+            .traverse_chain(|nsh| {
+                hops.set(hops.get() + 1);
+                match nsh.service_index {
+                    3 => NshAction::Forward,
+                    2 => NshAction::ModifyMetadata([nsh.context[0], 0xdead_beef, 7, 8]),
+                    _ => NshAction::Deliver,
+                }
+            })
+            .unwrap();
+        assert_eq!(hops.get(), 3);
+        assert_eq!(delivered.service_index, 1);
+        assert_eq!(delivered.context, [1, 0xdead_beef, 7, 8]);
+        let parsed = Nsh::from_bytes(&delivered.to_bytes()).unwrap();
+        assert_eq!(parsed.context, [1, 0xdead_beef, 7, 8]);
+        assert_eq!(parsed.payload, delivered.payload);
+    }
+
+    #[test]
+    fn chain_ends_when_service_index_runs_out() {
+        let hops = Cell::new(0);
+        let result = packet(3).traverse_chain(|_| {
+            hops.set(hops.get() + 1);
+            NshAction::ModifyMetadata([9; 4])
+        });
+        assert_eq!(result, None);
+        assert_eq!(hops.get(), 3);
+        assert_eq!(packet(0).traverse_chain(|_| NshAction::Deliver), None);
+    }
+
+    #[test]
+    fn dropped_packet_stops_the_chain() {
+        let hops = Cell::new(0);
+        let result = packet(5).traverse_chain(|nsh| {
+            hops.set(hops.get() + 1);
+            if nsh.service_index == 4 {
+                NshAction::Drop
+            } else {
+                NshAction::Forward
+            }
+        });
+        assert_eq!(result, None);
+        assert_eq!(hops.get(), 2);
+    }
+}
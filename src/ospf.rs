@@ -0,0 +1,1233 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::net::Ipv4Addr;
+
+use crate::util::{Ipv4Cidr, ParseError, checksum, ensure_len, fletcher_checksum, read_ipv4};
+
+// LSA header (RFC 2328 appendix A.4.1):
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |            LS age             |    Options    |    LS type    |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                        Link State ID                          |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                     Advertising Router                        |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                     LS sequence number                        |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |         LS checksum           |             length            |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+/// Length of the LSA header in bytes.
+pub const LSA_HEADER_LEN: usize = 20;
+
+/// Age at which an LSA is flushed from the database (RFC 2328 appendix B).
+pub const MAX_AGE: u16 = 3600;
+
+/// LSA type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum LsType {
+    Router = 1,
+    Network = 2,
+    SummaryNetwork = 3,
+    SummaryAsbr = 4,
+    AsExternal = 5,
+}
+
+impl TryFrom<u8> for LsType {
+    type Error = ParseError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(LsType::Router),
+            2 => Ok(LsType::Network),
+            3 => Ok(LsType::SummaryNetwork),
+            4 => Ok(LsType::SummaryAsbr),
+            5 => Ok(LsType::AsExternal),
+            _ => Err(ParseError::InvalidField("LS type")),
+        }
+    }
+}
+
+/// Type of a link described in a router LSA.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouterLinkType {
+    PointToPoint = 1,
+    Transit = 2,
+    Stub = 3,
+    Virtual = 4,
+}
+
+/// A link of a router LSA.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RouterLink {
+    pub link_type: RouterLinkType,
+    /// Neighbor router ID, designated router address or network number.
+    pub link_id: Ipv4Addr,
+    /// Interface address, or network mask for stub links.
+    pub link_data: Ipv4Addr,
+    pub metric: u16,
+}
+
+impl RouterLink {
+    /// Constructor to create a new router link.
+    pub fn new(
+        link_type: RouterLinkType,
+        link_id: Ipv4Addr,
+        link_data: Ipv4Addr,
+        metric: u16,
+    ) -> Self {
+        RouterLink {
+            link_type,
+            link_id,
+            link_data,
+            metric,
+        }
+    }
+}
+
+/// Contents of an LSA.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LsaBody {
+    Router(Vec<RouterLink>),
+    Network {
+        network_mask: Ipv4Addr,
+        attached_routers: Vec<Ipv4Addr>,
+    },
+    /// Body of the other LSA types, which SPF does not use.
+    Raw(Vec<u8>),
+}
+
+/// A link state advertisement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lsa {
+    pub age: u16,
+    pub options: u8,
+    pub ls_type: LsType,
+    pub link_state_id: Ipv4Addr,
+    pub advertising_router: Ipv4Addr,
+    pub sequence_number: i32,
+    pub checksum: u16,
+    pub body: LsaBody,
+}
+
+impl Lsa {
+    /// Initial sequence number of an LSA.
+    pub const INITIAL_SEQUENCE_NUMBER: i32 = 0x8000_0001_u32 as i32;
+
+    /// Builds a router LSA originated by `router_id`.
+    pub fn router(router_id: Ipv4Addr, sequence_number: i32, links: Vec<RouterLink>) -> Self {
+        Lsa {
+            age: 0,
+            options: 0,
+            ls_type: LsType::Router,
+            link_state_id: router_id,
+            advertising_router: router_id,
+            sequence_number,
+            checksum: 0,
+            body: LsaBody::Router(links),
+        }
+    }
+
+    /// Builds a network LSA originated by the designated router, whose
+    /// interface address on the network is `designated_router`.
+    pub fn network(
+        designated_router: Ipv4Addr,
+        advertising_router: Ipv4Addr,
+        sequence_number: i32,
+        network_mask: Ipv4Addr,
+        attached_routers: Vec<Ipv4Addr>,
+    ) -> Self {
+        Lsa {
+            age: 0,
+            options: 0,
+            ls_type: LsType::Network,
+            link_state_id: designated_router,
+            advertising_router,
+            sequence_number,
+            checksum: 0,
+            body: LsaBody::Network {
+                network_mask,
+                attached_routers,
+            },
+        }
+    }
+
+    /// Builds a summary LSA (type 3) originated by an area border router,
+    /// advertising `prefix` at `metric` (24 bits).
+    pub fn summary_network(
+        prefix: Ipv4Cidr,
+        advertising_router: Ipv4Addr,
+        sequence_number: i32,
+        metric: u32,
+    ) -> Self {
+        let mut body = prefix.mask().octets().to_vec();
+        body.extend_from_slice(&(metric & 0x00ff_ffff).to_be_bytes());
+        Lsa {
+            age: 0,
+            options: 0,
+            ls_type: LsType::SummaryNetwork,
+            link_state_id: prefix.network(),
+            advertising_router,
+            sequence_number,
+            checksum: 0,
+            body: LsaBody::Raw(body),
+        }
+    }
+
+    // --- SERIALIZATION ---
+
+    /// Serializes the header followed by the body, with the length filled
+    /// in and the checksum as stored.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(LSA_HEADER_LEN);
+        bytes.extend_from_slice(&self.age.to_be_bytes());
+        bytes.push(self.options);
+        bytes.push(self.ls_type as u8);
+        bytes.extend_from_slice(&self.link_state_id.octets());
+        bytes.extend_from_slice(&self.advertising_router.octets());
+        bytes.extend_from_slice(&self.sequence_number.to_be_bytes());
+        bytes.extend_from_slice(&self.checksum.to_be_bytes());
+        bytes.extend_from_slice(&[0; 2]);
+        match &self.body {
+            LsaBody::Router(links) => {
+                bytes.extend_from_slice(&[0, 0]);
+                bytes.extend_from_slice(&(links.len() as u16).to_be_bytes());
+                for link in links {
+                    bytes.extend_from_slice(&link.link_id.octets());
+                    bytes.extend_from_slice(&link.link_data.octets());
+                    bytes.push(link.link_type as u8);
+                    bytes.push(0);
+                    bytes.extend_from_slice(&link.metric.to_be_bytes());
+                }
+            }
+            LsaBody::Network {
+                network_mask,
+                attached_routers,
+            } => {
+                bytes.extend_from_slice(&network_mask.octets());
+                for router in attached_routers {
+                    bytes.extend_from_slice(&router.octets());
+                }
+            }
+            LsaBody::Raw(body) => bytes.extend_from_slice(body),
+        }
+        let length = bytes.len() as u16;
+        bytes[18..20].copy_from_slice(&length.to_be_bytes());
+        bytes
+    }
+
+    /// Fletcher checksum of the LSA, age excluded (RFC 2328 section
+    /// 12.1.7).
+    pub fn compute_checksum(&self) -> u16 {
+        let mut bytes = self.to_bytes();
+        bytes[16..18].fill(0);
+        fletcher_checksum(&bytes[2..], 14)
+    }
+
+    /// Returns the LSA with its checksum computed.
+    pub fn with_checksum(mut self) -> Self {
+        self.checksum = self.compute_checksum();
+        self
+    }
+
+    /// Key of the LSA in the database.
+    pub fn key(&self) -> LsaKey {
+        (self.ls_type, self.link_state_id, self.advertising_router)
+    }
+
+    /// Header of the LSA, with its length.
+    pub fn header(&self) -> LsaHeader {
+        LsaHeader {
+            age: self.age,
+            options: self.options,
+            ls_type: self.ls_type,
+            link_state_id: self.link_state_id,
+            advertising_router: self.advertising_router,
+            sequence_number: self.sequence_number,
+            checksum: self.checksum,
+            length: self.to_bytes().len() as u16,
+        }
+    }
+
+    /// Returns true if this LSA is a more recent instance than `other`.
+    pub fn is_newer_than(&self, other: &Lsa) -> bool {
+        self.sequence_number > other.sequence_number
+            || (self.sequence_number == other.sequence_number && self.age < other.age)
+    }
+
+    /// Router links of a router LSA; empty for other types.
+    fn links(&self) -> &[RouterLink] {
+        match &self.body {
+            LsaBody::Router(links) => links,
+            _ => &[],
+        }
+    }
+}
+
+/// Header of an LSA, as listed in Database Description and Link State
+/// Acknowledgment packets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LsaHeader {
+    pub age: u16,
+    pub options: u8,
+    pub ls_type: LsType,
+    pub link_state_id: Ipv4Addr,
+    pub advertising_router: Ipv4Addr,
+    pub sequence_number: i32,
+    pub checksum: u16,
+    /// Length of the whole LSA, header included.
+    pub length: u16,
+}
+
+impl LsaHeader {
+    /// Key of the LSA in the database.
+    pub fn key(&self) -> LsaKey {
+        (self.ls_type, self.link_state_id, self.advertising_router)
+    }
+
+    /// Returns true if this header describes a more recent instance than
+    /// `other`, as `Lsa::is_newer_than` does.
+    pub fn is_newer_than(&self, other: &LsaHeader) -> bool {
+        self.sequence_number > other.sequence_number
+            || (self.sequence_number == other.sequence_number && self.age < other.age)
+    }
+
+    // --- SERIALIZATION ---
+
+    pub fn to_bytes(&self) -> [u8; LSA_HEADER_LEN] {
+        let mut bytes = [0; LSA_HEADER_LEN];
+        bytes[0..2].copy_from_slice(&self.age.to_be_bytes());
+        bytes[2] = self.options;
+        bytes[3] = self.ls_type as u8;
+        bytes[4..8].copy_from_slice(&self.link_state_id.octets());
+        bytes[8..12].copy_from_slice(&self.advertising_router.octets());
+        bytes[12..16].copy_from_slice(&self.sequence_number.to_be_bytes());
+        bytes[16..18].copy_from_slice(&self.checksum.to_be_bytes());
+        bytes[18..20].copy_from_slice(&self.length.to_be_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, LSA_HEADER_LEN)?;
+        Ok(LsaHeader {
+            age: u16::from_be_bytes([buf[0], buf[1]]),
+            options: buf[2],
+            ls_type: LsType::try_from(buf[3])?,
+            link_state_id: read_ipv4(buf, 4),
+            advertising_router: read_ipv4(buf, 8),
+            sequence_number: i32::from_be_bytes([buf[12], buf[13], buf[14], buf[15]]),
+            checksum: u16::from_be_bytes([buf[16], buf[17]]),
+            length: u16::from_be_bytes([buf[18], buf[19]]),
+        })
+    }
+}
+
+/// Aggregates `routes` into the fewest prefixes covering exactly the same
+/// addresses: prefixes inside another one are dropped, and two sibling
+/// prefixes whose longest common prefix is one bit shorter are merged,
+/// until nothing changes. The result is sorted by address.
+pub fn covering_prefixes(routes: &[Ipv4Cidr]) -> Vec<Ipv4Cidr> {
+    let mut prefixes: Vec<(u32, u8)> = routes
+        .iter()
+        .map(|route| (u32::from(route.network()), route.prefix_len))
+        .collect();
+    loop {
+        prefixes.sort_unstable();
+        prefixes.dedup();
+        let mut merged: Vec<(u32, u8)> = Vec::with_capacity(prefixes.len());
+        for (network, len) in prefixes.iter().copied() {
+            let Some(&(last, last_len)) = merged.last() else {
+                merged.push((network, len));
+                continue;
+            };
+            let common = (last ^ network).leading_zeros() as u8;
+            if common >= last_len {
+                // Inside the previous prefix.
+                continue;
+            }
+            if len == last_len && common == len - 1 {
+                merged.pop();
+                merged.push((last, len - 1));
+            } else {
+                merged.push((network, len));
+            }
+        }
+        if merged.len() == prefixes.len() {
+            break;
+        }
+        prefixes = merged;
+    }
+    prefixes
+        .into_iter()
+        .map(|(network, len)| Ipv4Cidr {
+            address: Ipv4Addr::from(network),
+            prefix_len: len,
+        })
+        .collect()
+}
+
+/// Builds the summary LSAs an area border router originates for `routes`,
+/// each in its own Link State Update from `router_id` into `area_id`: one
+/// per covering prefix (see `covering_prefixes`), at `metric`, aged 0,
+/// with increasing sequence numbers starting at the initial one and with
+/// their checksum computed.
+pub fn summarize_routes(
+    routes: &[Ipv4Cidr],
+    area_id: Ipv4Addr,
+    router_id: Ipv4Addr,
+    metric: u32,
+) -> Vec<Ospf> {
+    covering_prefixes(routes)
+        .into_iter()
+        .zip(Lsa::INITIAL_SEQUENCE_NUMBER..)
+        .map(|(prefix, sequence_number)| {
+            let lsa =
+                Lsa::summary_network(prefix, router_id, sequence_number, metric).with_checksum();
+            Ospf::new(router_id, area_id, OspfBody::link_state_update(&[lsa]))
+        })
+        .collect()
+}
+
+/// LSA type, link state ID and advertising router.
+pub type LsaKey = (LsType, Ipv4Addr, Ipv4Addr);
+
+/// Route to a destination computed by SPF.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpfEntry {
+    /// Address of the first hop; unspecified for directly attached networks.
+    pub next_hop: Ipv4Addr,
+    pub metric: u32,
+}
+
+/// Vertex of the SPF graph.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum Vertex {
+    Router(Ipv4Addr),
+    /// Transit network, named by the designated router's address.
+    Network(Ipv4Addr),
+}
+
+/// Link state database of an OSPF area.
+#[derive(Debug, Clone, Default)]
+pub struct Lsdb {
+    pub lsas: HashMap<LsaKey, Lsa>,
+}
+
+impl Lsdb {
+    /// Constructor to create an empty database.
+    pub fn new() -> Self {
+        Lsdb::default()
+    }
+
+    /// Installs `lsa` unless the database holds a more recent instance.
+    /// Returns true if it was installed.
+    pub fn install(&mut self, lsa: Lsa) -> bool {
+        match self.lsas.get(&lsa.key()) {
+            Some(current) if !lsa.is_newer_than(current) => false,
+            _ => {
+                self.lsas.insert(lsa.key(), lsa);
+                true
+            }
+        }
+    }
+
+    /// Returns the LSA stored under `key`.
+    pub fn get(&self, key: &LsaKey) -> Option<&Lsa> {
+        self.lsas.get(key)
+    }
+
+    /// Ages every LSA by `seconds`, up to MaxAge.
+    pub fn advance_age(&mut self, seconds: u16) {
+        for lsa in self.lsas.values_mut() {
+            lsa.age = lsa.age.saturating_add(seconds).min(MAX_AGE);
+        }
+    }
+
+    /// Removes the LSAs that reached MaxAge.
+    pub fn max_age_flush(&mut self) {
+        self.lsas.retain(|_, lsa| lsa.age < MAX_AGE);
+    }
+
+    fn router_lsa(&self, router_id: Ipv4Addr) -> Option<&Lsa> {
+        self.lsas
+            .get(&(LsType::Router, router_id, router_id))
+            .filter(|lsa| lsa.age < MAX_AGE)
+    }
+
+    fn network_lsa(&self, designated_router: Ipv4Addr) -> Option<&Lsa> {
+        self.lsas
+            .values()
+            .find(|lsa| lsa.ls_type == LsType::Network && lsa.link_state_id == designated_router)
+            .filter(|lsa| lsa.age < MAX_AGE)
+    }
+
+    /// Edges leaving `vertex` as (neighbor, cost). An edge is only used if
+    /// the neighbor advertises a link back (RFC 2328 section 16.1).
+    fn edges(&self, vertex: Vertex) -> Vec<(Vertex, u32)> {
+        match vertex {
+            Vertex::Router(router_id) => {
+                let Some(lsa) = self.router_lsa(router_id) else {
+                    return Vec::new();
+                };
+                lsa.links()
+                    .iter()
+                    .filter_map(|link| match link.link_type {
+                        RouterLinkType::PointToPoint | RouterLinkType::Virtual => {
+                            let back =
+                                self.router_lsa(link.link_id)?.links().iter().any(|l| {
+                                    l.link_type == link.link_type && l.link_id == router_id
+                                });
+                            back.then_some((Vertex::Router(link.link_id), link.metric as u32))
+                        }
+                        RouterLinkType::Transit => {
+                            let back = match &self.network_lsa(link.link_id)?.body {
+                                LsaBody::Network {
+                                    attached_routers, ..
+                                } => attached_routers.contains(&router_id),
+                                _ => false,
+                            };
+                            back.then_some((Vertex::Network(link.link_id), link.metric as u32))
+                        }
+                        RouterLinkType::Stub => None,
+                    })
+                    .collect()
+            }
+            Vertex::Network(designated_router) => match self.network_lsa(designated_router) {
+                Some(Lsa {
+                    body:
+                        LsaBody::Network {
+                            attached_routers, ..
+                        },
+                    ..
+                }) => attached_routers
+                    .iter()
+                    .map(|router| (Vertex::Router(*router), 0))
+                    .collect(),
+                _ => Vec::new(),
+            },
+        }
+    }
+
+    /// Address of `router` on the link it shares with `via` (the root for
+    /// point-to-point links, or a transit network).
+    fn interface_address(&self, router: Ipv4Addr, via: Vertex) -> Ipv4Addr {
+        let (link_type, link_id) = match via {
+            Vertex::Router(root) => (RouterLinkType::PointToPoint, root),
+            Vertex::Network(designated_router) => (RouterLinkType::Transit, designated_router),
+        };
+        self.router_lsa(router)
+            .and_then(|lsa| {
+                lsa.links()
+                    .iter()
+                    .find(|link| link.link_type == link_type && link.link_id == link_id)
+            })
+            .map_or(router, |link| link.link_data)
+    }
+
+    /// Runs Dijkstra's algorithm from `router_id` over the router and
+    /// network LSAs. Returns a route to every reachable router (by router
+    /// ID) and network (by network address), except `router_id` itself.
+    pub fn run_spf(&self, router_id: Ipv4Addr) -> HashMap<Ipv4Addr, SpfEntry> {
+        let root = Vertex::Router(router_id);
+        let unspecified = Ipv4Addr::UNSPECIFIED;
+        let mut best: HashMap<Vertex, SpfEntry> = HashMap::new();
+        let mut done: HashMap<Vertex, SpfEntry> = HashMap::new();
+        let mut queue = BinaryHeap::new();
+        best.insert(
+            root,
+            SpfEntry {
+                next_hop: unspecified,
+                metric: 0,
+            },
+        );
+        queue.push(Reverse((0u32, root)));
+
+        while let Some(Reverse((metric, vertex))) = queue.pop() {
+            if done.contains_key(&vertex) || best[&vertex].metric != metric {
+                continue;
+            }
+            let entry = best[&vertex];
+            done.insert(vertex, entry);
+            for (neighbor, cost) in self.edges(vertex) {
+                if done.contains_key(&neighbor) {
+                    continue;
+                }
+                let next_hop = match (vertex, neighbor) {
+                    // Networks attached to the root are reached directly.
+                    (v, Vertex::Network(_)) if v == root => unspecified,
+                    (v, Vertex::Router(neighbor)) if v == root => {
+                        self.interface_address(neighbor, root)
+                    }
+                    (Vertex::Network(_), Vertex::Router(neighbor))
+                        if entry.next_hop == unspecified =>
+                    {
+                        self.interface_address(neighbor, vertex)
+                    }
+                    _ => entry.next_hop,
+                };
+                let candidate = SpfEntry {
+                    next_hop,
+                    metric: metric + cost,
+                };
+                if best
+                    .get(&neighbor)
+                    .is_none_or(|current| candidate.metric < current.metric)
+                {
+                    best.insert(neighbor, candidate);
+                    queue.push(Reverse((candidate.metric, neighbor)));
+                }
+            }
+        }
+
+        let mut routes: HashMap<Ipv4Addr, SpfEntry> = HashMap::new();
+        let mut add = |destination: Ipv4Addr, entry: SpfEntry| {
+            if routes
+                .get(&destination)
+                .is_none_or(|current| entry.metric < current.metric)
+            {
+                routes.insert(destination, entry);
+            }
+        };
+        for (vertex, entry) in &done {
+            match vertex {
+                Vertex::Router(id) => {
+                    if *id != router_id {
+                        add(*id, *entry);
+                    }
+                    for link in self.router_lsa(*id).map_or(&[][..], Lsa::links) {
+                        if link.link_type == RouterLinkType::Stub {
+                            let network =
+                                Ipv4Addr::from(u32::from(link.link_id) & u32::from(link.link_data));
+                            add(
+                                network,
+                                SpfEntry {
+                                    next_hop: entry.next_hop,
+                                    metric: entry.metric + link.metric as u32,
+                                },
+                            );
+                        }
+                    }
+                }
+                Vertex::Network(designated_router) => {
+                    if let Some(Lsa {
+                        body: LsaBody::Network { network_mask, .. },
+                        ..
+                    }) = self.network_lsa(*designated_router)
+                    {
+                        let network = Ipv4Addr::from(
+                            u32::from(*designated_router) & u32::from(*network_mask),
+                        );
+                        add(network, *entry);
+                    }
+                }
+            }
+        }
+        routes
+    }
+}
+
+// OSPF packet header (RFC 2328 appendix A.3.1)
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |   Version #   |     Type      |         Packet length         |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                          Router ID                            |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                           Area ID                             |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |           Checksum            |             AuType            |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                       Authentication (8)                      |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// Database Description body (appendix A.3.3)
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |         Interface MTU         |    Options    |0|0|0|0|0|I|M|MS
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                     DD sequence number                        |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// /                         LSA headers                           /
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// Link State Request body (appendix A.3.4): a list of (LS type as 32
+// bits, Link State ID, Advertising Router).
+//
+// Only null authentication (AuType 0) is built.
+
+/// IP protocol number of OSPF.
+pub const IP_PROTOCOL: u8 = 89;
+
+/// Length of the packet header in bytes.
+pub const PACKET_HEADER_LEN: usize = 24;
+
+/// Area ID of the backbone.
+pub const BACKBONE_AREA: Ipv4Addr = Ipv4Addr::UNSPECIFIED;
+
+// Packet types.
+pub const TYPE_HELLO: u8 = 1;
+pub const TYPE_DATABASE_DESCRIPTION: u8 = 2;
+pub const TYPE_LINK_STATE_REQUEST: u8 = 3;
+pub const TYPE_LINK_STATE_UPDATE: u8 = 4;
+pub const TYPE_LINK_STATE_ACK: u8 = 5;
+
+// Database Description flags.
+const DD_FLAG_INIT: u8 = 0x04;
+const DD_FLAG_MORE: u8 = 0x02;
+const DD_FLAG_MASTER: u8 = 0x01;
+
+/// Length of the fixed part of a Database Description body.
+const DD_FIXED_LEN: usize = 8;
+
+/// Length of a Link State Request entry.
+const LSR_ENTRY_LEN: usize = 12;
+
+/// Body of a Database Description packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatabaseDescription {
+    pub interface_mtu: u16,
+    pub options: u8,
+    /// I bit: first packet of the exchange.
+    pub init: bool,
+    /// M bit: more packets follow.
+    pub more: bool,
+    /// MS bit: sent by the master.
+    pub master: bool,
+    pub sequence_number: u32,
+    pub lsa_headers: Vec<LsaHeader>,
+}
+
+/// Body of an OSPF packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OspfBody {
+    DatabaseDescription(DatabaseDescription),
+    /// LSAs asked for in a Link State Request.
+    LinkStateRequest(Vec<LsaKey>),
+    /// Body of the other packet types.
+    Raw {
+        packet_type: u8,
+        body: Vec<u8>,
+    },
+}
+
+impl OspfBody {
+    /// Link State Update body carrying `lsas` as serialized.
+    pub fn link_state_update(lsas: &[Lsa]) -> Self {
+        let mut body = (lsas.len() as u32).to_be_bytes().to_vec();
+        for lsa in lsas {
+            body.extend_from_slice(&lsa.to_bytes());
+        }
+        OspfBody::Raw {
+            packet_type: TYPE_LINK_STATE_UPDATE,
+            body,
+        }
+    }
+
+    pub fn packet_type(&self) -> u8 {
+        match self {
+            OspfBody::DatabaseDescription(_) => TYPE_DATABASE_DESCRIPTION,
+            OspfBody::LinkStateRequest(_) => TYPE_LINK_STATE_REQUEST,
+            OspfBody::Raw { packet_type, .. } => *packet_type,
+        }
+    }
+
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        match self {
+            OspfBody::DatabaseDescription(dd) => {
+                bytes.extend_from_slice(&dd.interface_mtu.to_be_bytes());
+                bytes.push(dd.options);
+                bytes.push(
+                    (dd.init as u8 * DD_FLAG_INIT)
+                        | (dd.more as u8 * DD_FLAG_MORE)
+                        | (dd.master as u8 * DD_FLAG_MASTER),
+                );
+                bytes.extend_from_slice(&dd.sequence_number.to_be_bytes());
+                for header in &dd.lsa_headers {
+                    bytes.extend_from_slice(&header.to_bytes());
+                }
+            }
+            OspfBody::LinkStateRequest(keys) => {
+                for (ls_type, link_state_id, advertising_router) in keys {
+                    bytes.extend_from_slice(&(*ls_type as u32).to_be_bytes());
+                    bytes.extend_from_slice(&link_state_id.octets());
+                    bytes.extend_from_slice(&advertising_router.octets());
+                }
+            }
+            OspfBody::Raw { body, .. } => bytes.extend_from_slice(body),
+        }
+    }
+
+    fn parse(packet_type: u8, buf: &[u8]) -> Result<Self, ParseError> {
+        match packet_type {
+            TYPE_DATABASE_DESCRIPTION => {
+                ensure_len(buf, DD_FIXED_LEN)?;
+                let headers = &buf[DD_FIXED_LEN..];
+                if !headers.len().is_multiple_of(LSA_HEADER_LEN) {
+                    return Err(ParseError::InvalidField("packet length"));
+                }
+                Ok(OspfBody::DatabaseDescription(DatabaseDescription {
+                    interface_mtu: u16::from_be_bytes([buf[0], buf[1]]),
+                    options: buf[2],
+                    init: buf[3] & DD_FLAG_INIT != 0,
+                    more: buf[3] & DD_FLAG_MORE != 0,
+                    master: buf[3] & DD_FLAG_MASTER != 0,
+                    sequence_number: u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]),
+                    lsa_headers: headers
+                        .chunks(LSA_HEADER_LEN)
+                        .map(LsaHeader::from_bytes)
+                        .collect::<Result<_, _>>()?,
+                }))
+            }
+            TYPE_LINK_STATE_REQUEST => {
+                if !buf.len().is_multiple_of(LSR_ENTRY_LEN) {
+                    return Err(ParseError::InvalidField("packet length"));
+                }
+                let keys = buf
+                    .chunks(LSR_ENTRY_LEN)
+                    .map(|entry| {
+                        let ls_type = u32::from_be_bytes([entry[0], entry[1], entry[2], entry[3]]);
+                        let ls_type = u8::try_from(ls_type)
+                            .map_err(|_| ParseError::InvalidField("LS type"))?;
+                        Ok((
+                            LsType::try_from(ls_type)?,
+                            read_ipv4(entry, 4),
+                            read_ipv4(entry, 8),
+                        ))
+                    })
+                    .collect::<Result<_, ParseError>>()?;
+                Ok(OspfBody::LinkStateRequest(keys))
+            }
+            _ => Ok(OspfBody::Raw {
+                packet_type,
+                body: buf.to_vec(),
+            }),
+        }
+    }
+}
+
+/// OSPFv2 packet with null authentication.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ospf {
+    pub version: u8,
+    pub router_id: Ipv4Addr,
+    pub area_id: Ipv4Addr,
+    pub checksum: u16,
+    pub body: OspfBody,
+}
+
+impl Ospf {
+    /// Builds a version 2 packet from `router_id` with its checksum
+    /// computed.
+    pub fn new(router_id: Ipv4Addr, area_id: Ipv4Addr, body: OspfBody) -> Self {
+        Ospf {
+            version: 2,
+            router_id,
+            area_id,
+            checksum: 0,
+            body,
+        }
+        .with_checksum()
+    }
+
+    /// Checksum over the whole packet (with the checksum field zeroed); the
+    /// authentication field, all zeros, does not change it.
+    pub fn compute_checksum(&self) -> u16 {
+        let mut bytes = self.to_bytes();
+        bytes[12..14].fill(0);
+        checksum(&bytes)
+    }
+
+    /// Returns the packet with its checksum computed.
+    pub fn with_checksum(mut self) -> Self {
+        self.checksum = self.compute_checksum();
+        self
+    }
+
+    // --- SERIALIZATION ---
+
+    /// Serializes the header and body, with the length filled in.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(PACKET_HEADER_LEN);
+        bytes.push(self.version);
+        bytes.push(self.body.packet_type());
+        bytes.extend_from_slice(&[0; 2]);
+        bytes.extend_from_slice(&self.router_id.octets());
+        bytes.extend_from_slice(&self.area_id.octets());
+        bytes.extend_from_slice(&self.checksum.to_be_bytes());
+        bytes.extend_from_slice(&[0; 10]);
+        self.body.serialize_into(&mut bytes);
+        let length = bytes.len() as u16;
+        bytes[2..4].copy_from_slice(&length.to_be_bytes());
+        bytes
+    }
+
+    /// Parses a packet up to its packet length; trailing bytes are ignored.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, PACKET_HEADER_LEN)?;
+        let length = u16::from_be_bytes([buf[2], buf[3]]) as usize;
+        if length < PACKET_HEADER_LEN {
+            return Err(ParseError::InvalidField("packet length"));
+        }
+        ensure_len(buf, length)?;
+        Ok(Ospf {
+            version: buf[0],
+            router_id: read_ipv4(buf, 4),
+            area_id: read_ipv4(buf, 8),
+            checksum: u16::from_be_bytes([buf[12], buf[13]]),
+            body: OspfBody::parse(buf[1], &buf[PACKET_HEADER_LEN..length])?,
+        })
+    }
+}
+
+/// State of a neighbor during adjacency forming (RFC 2328 section 10.1),
+/// from the point where Database Description packets are exchanged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExchangeState {
+    /// Choosing the master and the initial DD sequence number.
+    ExStart,
+    /// Describing the databases to each other.
+    Exchange,
+    /// Waiting for the LSAs requested from the neighbor.
+    Loading,
+    /// The databases are synchronized.
+    Full,
+}
+
+/// Flags, options and sequence number of a Database Description packet,
+/// compared to spot duplicates.
+type DdSummary = (bool, bool, bool, u8, u32);
+
+/// Database exchange with one neighbor (RFC 2328 sections 10.6 and 10.8):
+/// the router with the higher ID becomes master and polls the slave, each
+/// side describes its database one packet at a time and collects the LSAs
+/// its neighbor has a more recent instance of.
+#[derive(Debug, Clone)]
+pub struct DatabaseExchange {
+    pub router_id: Ipv4Addr,
+    pub neighbor_id: Ipv4Addr,
+    pub area_id: Ipv4Addr,
+    pub interface_mtu: u16,
+    pub options: u8,
+    state: ExchangeState,
+    master: bool,
+    sequence_number: u32,
+    /// Headers of the local database.
+    database: HashMap<LsaKey, LsaHeader>,
+    /// Headers not yet described to the neighbor.
+    summary_list: VecDeque<LsaHeader>,
+    request_list: Vec<LsaHeader>,
+    last_sent: Option<Ospf>,
+    last_received: Option<DdSummary>,
+}
+
+impl DatabaseExchange {
+    /// Constructor to create the exchange of `lsdb` with `neighbor_id` in
+    /// the backbone, over an Ethernet MTU.
+    pub fn new(router_id: Ipv4Addr, neighbor_id: Ipv4Addr, lsdb: &Lsdb) -> Self {
+        DatabaseExchange {
+            router_id,
+            neighbor_id,
+            area_id: BACKBONE_AREA,
+            interface_mtu: 1500,
+            options: 0,
+            state: ExchangeState::ExStart,
+            master: true,
+            sequence_number: 0,
+            database: lsdb
+                .lsas
+                .iter()
+                .map(|(key, lsa)| (*key, lsa.header()))
+                .collect(),
+            summary_list: VecDeque::new(),
+            request_list: Vec::new(),
+            last_sent: None,
+            last_received: None,
+        }
+    }
+
+    pub fn get_state(&self) -> ExchangeState {
+        self.state
+    }
+
+    /// Returns true once this router is known to be the master.
+    pub fn is_master(&self) -> bool {
+        self.master && self.state != ExchangeState::ExStart
+    }
+
+    /// LSAs the neighbor holds a more recent instance of, still to be
+    /// received.
+    pub fn request_list(&self) -> &[LsaHeader] {
+        &self.request_list
+    }
+
+    /// Enters ExStart and builds the first Database Description packet,
+    /// with the I, M and MS bits set and sequence number 0.
+    pub fn initiate_exstart(&mut self) -> Ospf {
+        self.exstart(0)
+    }
+
+    /// Handles a Database Description packet from the neighbor and returns
+    /// the packet to answer with, if any. Packets that do not fit the
+    /// current state restart the exchange from ExStart.
+    pub fn process_dd(&mut self, incoming: &Ospf) -> Option<Ospf> {
+        let OspfBody::DatabaseDescription(dd) = &incoming.body else {
+            return None;
+        };
+        if incoming.router_id != self.neighbor_id || dd.interface_mtu > self.interface_mtu {
+            return None;
+        }
+        let summary = (dd.init, dd.more, dd.master, dd.options, dd.sequence_number);
+        match self.state {
+            ExchangeState::ExStart => {
+                if dd.init
+                    && dd.more
+                    && dd.master
+                    && dd.lsa_headers.is_empty()
+                    && self.neighbor_id > self.router_id
+                {
+                    // The neighbor is master: adopt its sequence number.
+                    self.master = false;
+                    self.state = ExchangeState::Exchange;
+                    self.accept(dd, summary)
+                } else if !dd.init
+                    && !dd.master
+                    && dd.sequence_number == self.sequence_number
+                    && self.neighbor_id < self.router_id
+                {
+                    self.master = true;
+                    self.state = ExchangeState::Exchange;
+                    self.accept(dd, summary)
+                } else {
+                    None
+                }
+            }
+            ExchangeState::Exchange => {
+                if self.last_received == Some(summary) {
+                    return self.retransmit();
+                }
+                let expected = match self.master {
+                    true => self.sequence_number,
+                    false => self.sequence_number.wrapping_add(1),
+                };
+                let last_options = self.last_received.map(|last| last.3);
+                if dd.master == self.master
+                    || dd.init
+                    || last_options != Some(dd.options)
+                    || dd.sequence_number != expected
+                {
+                    return Some(self.sequence_number_mismatch());
+                }
+                self.accept(dd, summary)
+            }
+            ExchangeState::Loading | ExchangeState::Full => {
+                if self.last_received == Some(summary) {
+                    self.retransmit()
+                } else {
+                    Some(self.sequence_number_mismatch())
+                }
+            }
+        }
+    }
+
+    /// Builds a Link State Request for `missing_lsas`.
+    pub fn generate_link_state_requests(&self, missing_lsas: &[LsaHeader]) -> Ospf {
+        let keys = missing_lsas.iter().map(LsaHeader::key).collect();
+        Ospf::new(
+            self.router_id,
+            self.area_id,
+            OspfBody::LinkStateRequest(keys),
+        )
+    }
+
+    /// Records `lsa`, received from the neighbor, removing it from the
+    /// request list; the adjacency is full once the list is empty.
+    pub fn process_lsa(&mut self, lsa: &Lsa) {
+        let header = lsa.header();
+        self.request_list.retain(|requested| {
+            requested.key() != header.key() || requested.is_newer_than(&header)
+        });
+        self.database.insert(header.key(), header);
+        if self.state == ExchangeState::Loading && self.request_list.is_empty() {
+            self.state = ExchangeState::Full;
+        }
+    }
+
+    fn exstart(&mut self, sequence_number: u32) -> Ospf {
+        self.state = ExchangeState::ExStart;
+        self.master = true;
+        self.sequence_number = sequence_number;
+        let mut headers: Vec<LsaHeader> = self
+            .database
+            .values()
+            .filter(|header| header.age < MAX_AGE)
+            .copied()
+            .collect();
+        headers.sort_unstable_by_key(LsaHeader::key);
+        self.summary_list = headers.into();
+        self.request_list.clear();
+        self.last_received = None;
+        let packet = self.database_description(true, true, Vec::new());
+        self.last_sent = Some(packet.clone());
+        packet
+    }
+
+    /// Restarts the exchange after an unexpected packet, with a new
+    /// sequence number.
+    fn sequence_number_mismatch(&mut self) -> Ospf {
+        self.exstart(self.sequence_number.wrapping_add(1))
+    }
+
+    /// Only the slave answers duplicates, by sending its last packet again.
+    fn retransmit(&self) -> Option<Ospf> {
+        match self.master {
+            true => None,
+            false => self.last_sent.clone(),
+        }
+    }
+
+    /// Processes an accepted packet and builds the next one to send; the
+    /// master sends nothing once both sides are done.
+    fn accept(&mut self, dd: &DatabaseDescription, summary: DdSummary) -> Option<Ospf> {
+        self.last_received = Some(summary);
+        for header in &dd.lsa_headers {
+            match self.database.get(&header.key()) {
+                Some(current) if !header.is_newer_than(current) => {}
+                _ => self.request_list.push(*header),
+            }
+        }
+        let sent_all = match &self.last_sent {
+            Some(Ospf {
+                body: OspfBody::DatabaseDescription(last),
+                ..
+            }) => !last.init && !last.more,
+            _ => false,
+        };
+        if self.master {
+            self.sequence_number = self.sequence_number.wrapping_add(1);
+            if sent_all && !dd.more {
+                self.exchange_done();
+                return None;
+            }
+            Some(self.next_database_description())
+        } else {
+            self.sequence_number = dd.sequence_number;
+            let packet = self.next_database_description();
+            if !dd.more && self.summary_list.is_empty() {
+                self.exchange_done();
+            }
+            Some(packet)
+        }
+    }
+
+    fn exchange_done(&mut self) {
+        self.state = match self.request_list.is_empty() {
+            true => ExchangeState::Full,
+            false => ExchangeState::Loading,
+        };
+    }
+
+    /// Builds the next packet of the exchange with as many headers from
+    /// the summary list as the MTU allows.
+    fn next_database_description(&mut self) -> Ospf {
+        // IP header, OSPF header and fixed part of the body.
+        let room =
+            (self.interface_mtu as usize).saturating_sub(20 + PACKET_HEADER_LEN + DD_FIXED_LEN);
+        let count = (room / LSA_HEADER_LEN).max(1).min(self.summary_list.len());
+        let headers: Vec<LsaHeader> = self.summary_list.drain(..count).collect();
+        let more = !self.summary_list.is_empty();
+        let packet = self.database_description(false, more, headers);
+        self.last_sent = Some(packet.clone());
+        packet
+    }
+
+    fn database_description(&self, init: bool, more: bool, lsa_headers: Vec<LsaHeader>) -> Ospf {
+        Ospf::new(
+            self.router_id,
+            self.area_id,
+            OspfBody::DatabaseDescription(DatabaseDescription {
+                interface_mtu: self.interface_mtu,
+                options: self.options,
+                init,
+                more,
+                master: self.master,
+                sequence_number: self.sequence_number,
+                lsa_headers,
+            }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cidr(address: [u8; 4], prefix_len: u8) -> Ipv4Cidr {
+        Ipv4Cidr {
+            address: Ipv4Addr::from(address),
+            prefix_len,
+        }
+    }
+
+    #[test]
+    fn four_slash_26_summarize_to_one_slash_24() {
+        let routes: Vec<Ipv4Cidr> = [0, 64, 128, 192]
+            .into_iter()
+            .map(|offset| cidr([10, 1, 2, offset], 26))
+            .collect();
+        let area = Ipv4Addr::new(0, 0, 0, 1);
+        let router = Ipv4Addr::new(192, 0, 2, 1);
+        let packets = summarize_routes(&routes, area, router, 20);
+        assert_eq!(packets.len(), 1);
+
+        let packet = Ospf::from_bytes(&packets[0].to_bytes()).unwrap();
+        assert_eq!(packet.area_id, area);
+        assert_eq!(packet.router_id, router);
+        assert_eq!(packet.checksum, packet.compute_checksum());
+        let OspfBody::Raw {
+            packet_type: TYPE_LINK_STATE_UPDATE,
+            body,
+        } = &packet.body
+        else {
+            panic!("not a Link State Update: {:?}", packet.body);
+        };
+        assert_eq!(body[..4], 1u32.to_be_bytes());
+
+        let lsa = &body[4..];
+        let header = LsaHeader::from_bytes(lsa).unwrap();
+        assert_eq!(header.age, 0);
+        assert_eq!(header.ls_type, LsType::SummaryNetwork);
+        assert_eq!(header.link_state_id, Ipv4Addr::new(10, 1, 2, 0));
+        assert_eq!(header.advertising_router, router);
+        assert_eq!(header.sequence_number, Lsa::INITIAL_SEQUENCE_NUMBER);
+        assert_eq!(usize::from(header.length), lsa.len());
+        assert_eq!(lsa[20..24], [255, 255, 255, 0]);
+        assert_eq!(lsa[24..28], 20u32.to_be_bytes());
+        let expected =
+            Lsa::summary_network(cidr([10, 1, 2, 0], 24), router, header.sequence_number, 20);
+        assert_eq!(header.checksum, expected.compute_checksum());
+        assert_ne!(header.checksum, 0);
+    }
+
+    #[test]
+    fn disjoint_routes_get_increasing_sequence_numbers() {
+        let routes = [cidr([10, 0, 0, 0], 24), cidr([172, 16, 0, 0], 16)];
+        let packets = summarize_routes(&routes, BACKBONE_AREA, Ipv4Addr::new(1, 1, 1, 1), 5);
+        let sequence_numbers: Vec<i32> = packets
+            .iter()
+            .map(|packet| {
+                let OspfBody::Raw { body, .. } = &packet.body else {
+                    unreachable!()
+                };
+                LsaHeader::from_bytes(&body[4..]).unwrap().sequence_number
+            })
+            .collect();
+        assert_eq!(
+            sequence_numbers,
+            [
+                Lsa::INITIAL_SEQUENCE_NUMBER,
+                Lsa::INITIAL_SEQUENCE_NUMBER + 1
+            ]
+        );
+    }
+}
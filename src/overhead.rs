@@ -0,0 +1,210 @@
+use crate::gre::Gre;
+use crate::gtp::GtpU;
+use crate::ipv4::IPv4;
+use crate::ipv6::{self, IPv6};
+use crate::sctp::Sctp;
+use crate::tcp::TCP;
+
+/// Kind of a protocol layer, used for header sizes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LayerKind {
+    Ethernet,
+    /// One 802.1Q or 802.1ad tag; QinQ is two of them.
+    Vlan,
+    /// One MPLS label stack entry.
+    Mpls,
+    Ipv4,
+    Ipv6,
+    Gre,
+    Udp,
+    Tcp,
+    Sctp,
+    GtpU,
+    Vxlan,
+}
+
+impl LayerKind {
+    /// Header length without options or extensions.
+    pub fn header_len(&self) -> usize {
+        match self {
+            LayerKind::Ethernet => 14,
+            LayerKind::Vlan | LayerKind::Mpls | LayerKind::Gre => 4,
+            LayerKind::Ipv4 | LayerKind::Tcp => 20,
+            LayerKind::Ipv6 => ipv6::HEADER_LEN,
+            LayerKind::Udp | LayerKind::GtpU | LayerKind::Vxlan => 8,
+            LayerKind::Sctp => Sctp::HEADER_LEN,
+        }
+    }
+
+    /// Returns true for layers below IP, which do not count against the MTU.
+    pub fn is_link_layer(&self) -> bool {
+        matches!(
+            self,
+            LayerKind::Ethernet | LayerKind::Vlan | LayerKind::Mpls
+        )
+    }
+}
+
+/// A layer of a stack with its actual header length.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layer {
+    pub kind: LayerKind,
+    pub header_len: usize,
+}
+
+impl Layer {
+    /// Constructor to create a layer with an explicit header length.
+    pub fn new(kind: LayerKind, header_len: usize) -> Self {
+        Layer { kind, header_len }
+    }
+}
+
+impl From<LayerKind> for Layer {
+    fn from(kind: LayerKind) -> Self {
+        Layer::new(kind, kind.header_len())
+    }
+}
+
+impl From<&TCP> for Layer {
+    fn from(tcp: &TCP) -> Self {
+        Layer::new(LayerKind::Tcp, tcp.data_offset as usize * 4)
+    }
+}
+
+impl From<&IPv4> for Layer {
+    fn from(ipv4: &IPv4) -> Self {
+        Layer::new(LayerKind::Ipv4, ipv4.header_len())
+    }
+}
+
+impl From<&IPv6> for Layer {
+    fn from(_: &IPv6) -> Self {
+        Layer::from(LayerKind::Ipv6)
+    }
+}
+
+impl From<&Gre> for Layer {
+    fn from(gre: &Gre) -> Self {
+        Layer::new(LayerKind::Gre, gre.header_len())
+    }
+}
+
+impl From<&GtpU> for Layer {
+    fn from(gtp: &GtpU) -> Self {
+        let header_len = 8 + gtp.compute_length() as usize - gtp.payload.len();
+        Layer::new(LayerKind::GtpU, header_len)
+    }
+}
+
+/// Header bytes per layer and in total.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OverheadBreakdown {
+    pub layers: Vec<Layer>,
+    pub total: usize,
+}
+
+impl OverheadBreakdown {
+    /// Header bytes of the layers counted against the MTU: everything
+    /// from the outer IP header on, including the link-layer headers of
+    /// tunnelled frames.
+    pub fn network_total(&self) -> usize {
+        self.layers
+            .iter()
+            .skip_while(|layer| layer.kind.is_link_layer())
+            .map(|layer| layer.header_len)
+            .sum()
+    }
+}
+
+/// Overhead of a stack of layers, outermost first, with base header sizes.
+///
+/// Header bytes added by common stacks (without FCS):
+///
+/// ```
+/// use ethercrafter::overhead::{self, LayerKind::*};
+///
+/// let sizes = |stack| {
+///     let breakdown = overhead::of(stack);
+///     let layers: Vec<usize> = breakdown
+///         .layers
+///         .iter()
+///         .map(|layer| layer.header_len)
+///         .collect();
+///     (layers, breakdown.total)
+/// };
+///
+/// assert_eq!(sizes(&[Ethernet, Ipv4, Tcp]), (vec![14, 20, 20], 54));
+/// assert_eq!(sizes(&[Ethernet, Ipv6, Tcp]), (vec![14, 40, 20], 74));
+/// assert_eq!(sizes(&[Ethernet, Ipv4, Udp]), (vec![14, 20, 8], 42));
+/// assert_eq!(sizes(&[Ethernet, Vlan, Ipv4, Udp]), (vec![14, 4, 20, 8], 46));
+/// assert_eq!(
+///     sizes(&[Ethernet, Ipv4, Udp, Vxlan, Ethernet]),
+///     (vec![14, 20, 8, 8, 14], 64)
+/// );
+/// assert_eq!(
+///     sizes(&[Ethernet, Ipv4, Udp, GtpU, Ipv4]),
+///     (vec![14, 20, 8, 8, 20], 70)
+/// );
+/// assert_eq!(sizes(&[Ethernet, Ipv4, Gre, Ipv4]), (vec![14, 20, 4, 20], 58));
+/// ```
+pub fn of(stack: &[LayerKind]) -> OverheadBreakdown {
+    of_layers(
+        &stack
+            .iter()
+            .map(|&kind| Layer::from(kind))
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// Overhead of a stack of layers with actual header lengths.
+pub fn of_layers(stack: &[Layer]) -> OverheadBreakdown {
+    OverheadBreakdown {
+        layers: stack.to_vec(),
+        total: stack.iter().map(|layer| layer.header_len).sum(),
+    }
+}
+
+/// Per-frame overhead of the physical layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinkOverhead {
+    /// Preamble and start frame delimiter.
+    pub preamble: usize,
+    pub fcs: usize,
+    pub inter_frame_gap: usize,
+    /// Minimum frame length without FCS; shorter frames are padded.
+    pub min_frame: usize,
+}
+
+/// Ethernet: 8 bytes of preamble and SFD, 4 of FCS, a 12-byte gap and
+/// 64-byte minimum frames.
+pub const ETHERNET: LinkOverhead = LinkOverhead {
+    preamble: 8,
+    fcs: 4,
+    inter_frame_gap: 12,
+    min_frame: 60,
+};
+
+/// Share of the bytes on the wire that carry `payload_len` bytes of payload
+/// over `stack`, including the physical layer overhead of `link`.
+pub fn goodput_ratio(payload_len: usize, stack: &[LayerKind], link: &LinkOverhead) -> f64 {
+    let frame = (of(stack).total + payload_len).max(link.min_frame);
+    let wire = link.preamble + frame + link.fcs + link.inter_frame_gap;
+    payload_len as f64 / wire as f64
+}
+
+/// Largest payload that fits in one packet of `mtu` bytes over `stack`.
+/// Layers below the outer IP header do not count against the MTU. Returns
+/// `None` if the headers alone exceed it.
+///
+/// ```
+/// use ethercrafter::overhead::{LayerKind::*, max_payload_for_mtu};
+///
+/// assert_eq!(max_payload_for_mtu(&[Ethernet, Ipv4, Tcp], 1500), Some(1460));
+/// // The inner Ethernet header of a VXLAN tunnel counts.
+/// let vxlan = [Ethernet, Ipv4, Udp, Vxlan, Ethernet, Ipv4, Udp];
+/// assert_eq!(max_payload_for_mtu(&vxlan, 1500), Some(1422));
+/// assert_eq!(max_payload_for_mtu(&[Ipv6, Tcp], 40), None);
+/// ```
+pub fn max_payload_for_mtu(stack: &[LayerKind], mtu: usize) -> Option<usize> {
+    mtu.checked_sub(of(stack).network_total())
+}
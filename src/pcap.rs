@@ -0,0 +1,807 @@
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+mod anonymize;
+mod filter;
+mod indexed;
+mod latency;
+mod parsed;
+mod reassembly;
+
+pub use anonymize::{AnonymizationPolicy, anonymize};
+pub use filter::{ClosureFilter, DecodedStack, NetworkLayer, TransportLayer, closures_to_pcap};
+pub use indexed::{FlowPackets, IndexedReader, index, index_with_options};
+pub use latency::{LatencyHistogram, histogram_latency};
+pub use parsed::{ParseStats, Parsed, Severity, Warning, WarningCode};
+pub use reassembly::{
+    ReassembledFlow, ReassembledStream, ReassemblyError, tcp_stream_reassembly,
+    tcp_stream_reassembly_with_limits,
+};
+
+use crate::flow::FiveTuple;
+
+// Global header (24 bytes), followed by records:
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                    Timestamp (seconds)                        |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |            Timestamp (microseconds or nanoseconds)            |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                    Captured Packet Length                     |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                    Original Packet Length                     |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// /                          Packet Data                          /
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+/// Magic number of captures with microsecond timestamps.
+pub const MAGIC_MICROS: u32 = 0xa1b2_c3d4;
+/// Magic number of captures with nanosecond timestamps.
+pub const MAGIC_NANOS: u32 = 0xa1b2_3c4d;
+
+// Link types (http://www.tcpdump.org/linktypes.html).
+/// BSD loopback: the address family of the packet as a 4-byte integer
+/// in the byte order of the capturing host, then the IP packet.
+pub const LINKTYPE_NULL: u32 = 0;
+pub const LINKTYPE_ETHERNET: u32 = 1;
+pub const LINKTYPE_RAW: u32 = 101;
+pub const LINKTYPE_IPV4: u32 = 228;
+pub const LINKTYPE_IPV6: u32 = 229;
+
+/// Length of the global header in bytes.
+pub const GLOBAL_HEADER_LEN: u64 = 24;
+/// Length of a record header in bytes.
+pub const RECORD_HEADER_LEN: u64 = 16;
+
+/// Records claiming more captured bytes than this are rejected as corrupt.
+pub const MAX_RECORD_LEN: u32 = 64 * 1024 * 1024;
+
+/// Resolution of the record timestamps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    Micros,
+    Nanos,
+}
+
+/// Global header of a capture file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GlobalHeader {
+    pub version_major: u16,
+    pub version_minor: u16,
+    pub thiszone: i32,
+    pub sigfigs: u32,
+    pub snaplen: u32,
+    pub link_type: u32,
+    pub resolution: Resolution,
+    pub big_endian: bool,
+}
+
+impl GlobalHeader {
+    /// Header of a version 2.4 capture in native byte order.
+    pub fn new(link_type: u32, snaplen: u32, resolution: Resolution) -> Self {
+        GlobalHeader {
+            version_major: 2,
+            version_minor: 4,
+            thiszone: 0,
+            sigfigs: 0,
+            snaplen,
+            link_type,
+            resolution,
+            big_endian: cfg!(target_endian = "big"),
+        }
+    }
+
+    /// Serializes the header in its byte order.
+    pub fn to_bytes(&self) -> [u8; GLOBAL_HEADER_LEN as usize] {
+        let magic = match self.resolution {
+            Resolution::Micros => MAGIC_MICROS,
+            Resolution::Nanos => MAGIC_NANOS,
+        };
+        let mut bytes = [0u8; GLOBAL_HEADER_LEN as usize];
+        let u16_bytes = |value: u16| {
+            if self.big_endian {
+                value.to_be_bytes()
+            } else {
+                value.to_le_bytes()
+            }
+        };
+        let u32_bytes = |value: u32| {
+            if self.big_endian {
+                value.to_be_bytes()
+            } else {
+                value.to_le_bytes()
+            }
+        };
+        bytes[0..4].copy_from_slice(&u32_bytes(magic));
+        bytes[4..6].copy_from_slice(&u16_bytes(self.version_major));
+        bytes[6..8].copy_from_slice(&u16_bytes(self.version_minor));
+        bytes[8..12].copy_from_slice(&u32_bytes(self.thiszone as u32));
+        bytes[12..16].copy_from_slice(&u32_bytes(self.sigfigs));
+        bytes[16..20].copy_from_slice(&u32_bytes(self.snaplen));
+        bytes[20..24].copy_from_slice(&u32_bytes(self.link_type));
+        bytes
+    }
+
+    /// Parses the global header, detecting byte order and resolution from
+    /// the magic number.
+    pub fn from_bytes(bytes: &[u8; GLOBAL_HEADER_LEN as usize]) -> io::Result<Self> {
+        let magic = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        let (big_endian, resolution) = match (u32::from_le_bytes(magic), u32::from_be_bytes(magic))
+        {
+            (MAGIC_MICROS, _) => (false, Resolution::Micros),
+            (MAGIC_NANOS, _) => (false, Resolution::Nanos),
+            (_, MAGIC_MICROS) => (true, Resolution::Micros),
+            (_, MAGIC_NANOS) => (true, Resolution::Nanos),
+            _ => return Err(invalid_data("not a pcap file")),
+        };
+        let u16_at = |offset: usize| {
+            let raw = [bytes[offset], bytes[offset + 1]];
+            if big_endian {
+                u16::from_be_bytes(raw)
+            } else {
+                u16::from_le_bytes(raw)
+            }
+        };
+        let u32_at = |offset: usize| read_u32(bytes, offset, big_endian);
+        Ok(GlobalHeader {
+            version_major: u16_at(4),
+            version_minor: u16_at(6),
+            thiszone: u32_at(8) as i32,
+            sigfigs: u32_at(12),
+            snaplen: u32_at(16),
+            link_type: u32_at(20),
+            resolution,
+            big_endian,
+        })
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize, big_endian: bool) -> u32 {
+    let raw = [
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ];
+    if big_endian {
+        u32::from_be_bytes(raw)
+    } else {
+        u32::from_le_bytes(raw)
+    }
+}
+
+pub(crate) fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// A packet read from or written to a capture file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapturedPacket {
+    /// Time since the Unix epoch.
+    pub timestamp: Duration,
+    /// Length of the packet on the wire, which may exceed `data.len()`.
+    pub original_len: u32,
+    pub data: Vec<u8>,
+}
+
+impl CapturedPacket {
+    /// Constructor to create a packet captured in full.
+    pub fn new(timestamp: Duration, data: Vec<u8>) -> Self {
+        CapturedPacket {
+            timestamp,
+            original_len: data.len() as u32,
+            data,
+        }
+    }
+}
+
+/// Streaming reader of pcap files.
+#[derive(Debug)]
+pub struct Reader<R> {
+    inner: R,
+    header: GlobalHeader,
+    position: u64,
+    recovery: bool,
+    trailing_bytes: u64,
+}
+
+impl Reader<BufReader<File>> {
+    /// Opens the capture file at `path`.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Reader::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> Reader<R> {
+    /// Reads the global header from `inner` and returns a reader positioned
+    /// on the first record.
+    pub fn new(mut inner: R) -> io::Result<Self> {
+        let mut bytes = [0u8; GLOBAL_HEADER_LEN as usize];
+        inner.read_exact(&mut bytes)?;
+        Ok(Reader {
+            inner,
+            header: GlobalHeader::from_bytes(&bytes)?,
+            position: GLOBAL_HEADER_LEN,
+            recovery: false,
+            trailing_bytes: 0,
+        })
+    }
+
+    /// Enables or disables recovery mode. In recovery mode a record cut
+    /// short by the end of file (e.g. after a crash of the capturing
+    /// process) ends the capture cleanly instead of returning an error, and
+    /// its bytes are counted in `trailing_bytes`.
+    pub fn set_recovery(mut self, recovery: bool) -> Self {
+        self.recovery = recovery;
+        self
+    }
+
+    /// Returns the global header.
+    pub fn header(&self) -> &GlobalHeader {
+        &self.header
+    }
+
+    /// Returns the link type of the capture.
+    pub fn link_type(&self) -> u32 {
+        self.header.link_type
+    }
+
+    /// Byte offset of the next record in the file. After the end of a
+    /// capture, this is the end of its last complete record.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Number of bytes of an incomplete final record ignored in recovery
+    /// mode.
+    pub fn trailing_bytes(&self) -> u64 {
+        self.trailing_bytes
+    }
+
+    /// Handles a record cut short after `read` bytes.
+    fn truncated_record(&mut self, read: u64) -> io::Result<Option<CapturedPacket>> {
+        if !self.recovery {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.trailing_bytes = read;
+        Ok(None)
+    }
+
+    /// Reads the next record. Returns `Ok(None)` at a clean end of file and
+    /// an `UnexpectedEof` error if the file ends inside a record, unless
+    /// recovery mode is enabled.
+    pub fn next_packet(&mut self) -> io::Result<Option<CapturedPacket>> {
+        if self.trailing_bytes > 0 {
+            return Ok(None);
+        }
+        let mut record = [0u8; RECORD_HEADER_LEN as usize];
+        let read = read_full(&mut self.inner, &mut record)?;
+        if read == 0 {
+            return Ok(None);
+        }
+        if read < record.len() {
+            return self.truncated_record(read as u64);
+        }
+        let big_endian = self.header.big_endian;
+        let seconds = read_u32(&record, 0, big_endian) as u64;
+        let fraction = read_u32(&record, 4, big_endian);
+        let captured_len = read_u32(&record, 8, big_endian);
+        let original_len = read_u32(&record, 12, big_endian);
+        if captured_len > MAX_RECORD_LEN {
+            return Err(invalid_data("record length exceeds the maximum"));
+        }
+        let mut data = vec![0u8; captured_len as usize];
+        let read = read_full(&mut self.inner, &mut data)?;
+        if read < data.len() {
+            return self.truncated_record(RECORD_HEADER_LEN + read as u64);
+        }
+        self.position += RECORD_HEADER_LEN + captured_len as u64;
+
+        let timestamp = match self.header.resolution {
+            Resolution::Micros => {
+                Duration::from_secs(seconds) + Duration::from_micros(fraction as u64)
+            }
+            Resolution::Nanos => {
+                Duration::from_secs(seconds) + Duration::from_nanos(fraction as u64)
+            }
+        };
+        Ok(Some(CapturedPacket {
+            timestamp,
+            original_len,
+            data,
+        }))
+    }
+
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read + Seek> Reader<R> {
+    /// Moves to the record starting at byte `offset`.
+    pub fn seek(&mut self, offset: u64) -> io::Result<()> {
+        self.inner.seek(SeekFrom::Start(offset))?;
+        self.position = offset;
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for Reader<R> {
+    type Item = io::Result<CapturedPacket>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_packet().transpose()
+    }
+}
+
+/// How often a writer forces its data to stable storage. A sync happens
+/// when either limit is reached; with neither set, only explicit calls to
+/// `flush_and_sync` do.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SyncPolicy {
+    pub every_packets: Option<u64>,
+    pub every: Option<Duration>,
+}
+
+impl SyncPolicy {
+    /// Constructor to create a policy that never syncs on its own.
+    pub fn new() -> Self {
+        SyncPolicy::default()
+    }
+
+    pub fn set_every_packets(mut self, packets: u64) -> Self {
+        self.every_packets = Some(packets);
+        self
+    }
+
+    pub fn set_every(mut self, interval: Duration) -> Self {
+        self.every = Some(interval);
+        self
+    }
+}
+
+/// Streaming writer of pcap files.
+#[derive(Debug)]
+pub struct Writer<W: Write> {
+    inner: W,
+    header: GlobalHeader,
+    sync_policy: SyncPolicy,
+    /// Forces the data of `inner` to storage; set for file writers only.
+    sync: Option<fn(&mut W) -> io::Result<()>>,
+    unsynced: u64,
+    last_sync: Instant,
+}
+
+fn sync_buffered_file(inner: &mut BufWriter<File>) -> io::Result<()> {
+    inner.flush()?;
+    inner.get_ref().sync_data()
+}
+
+impl Writer<BufWriter<File>> {
+    /// Creates the capture file at `path` and writes its global header.
+    pub fn create(path: impl AsRef<Path>, header: GlobalHeader) -> io::Result<Self> {
+        Writer::new(BufWriter::new(File::create(path)?), header).map(Writer::with_file_sync)
+    }
+
+    /// Opens the capture file at `path` to append records, creating it with
+    /// `header` if it is missing or empty.
+    ///
+    /// The link type and resolution of an existing file must match
+    /// `header`; records are then written in the file's byte order. A
+    /// partial record at the end of the file (left by a crash) is an error
+    /// unless `truncate_partial` is set, in which case it is cut off.
+    pub fn append(
+        path: impl AsRef<Path>,
+        header: GlobalHeader,
+        truncate_partial: bool,
+    ) -> io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        if file.metadata()?.len() == 0 {
+            return Writer::new(BufWriter::new(file), header).map(Writer::with_file_sync);
+        }
+        let mut reader = Reader::new(BufReader::new(&file))?.set_recovery(true);
+        let existing = *reader.header();
+        if existing.link_type != header.link_type || existing.resolution != header.resolution {
+            return Err(invalid_data(
+                "existing capture has a different link type or resolution",
+            ));
+        }
+        while reader.next_packet()?.is_some() {}
+        if reader.trailing_bytes() > 0 && !truncate_partial {
+            return Err(invalid_data("existing capture ends with a partial record"));
+        }
+        let end = Reader::position(&reader);
+        file.set_len(end)?;
+        file.seek(SeekFrom::Start(end))?;
+        Ok(Writer::with_header(BufWriter::new(file), existing).with_file_sync())
+    }
+
+    /// Opens a capture written by this process with `header` to append
+    /// records, without reading it back.
+    fn reopen(path: &Path, header: GlobalHeader) -> io::Result<Self> {
+        let file = OpenOptions::new().append(true).open(path)?;
+        Ok(Writer::with_header(BufWriter::new(file), header).with_file_sync())
+    }
+
+    fn with_file_sync(mut self) -> Self {
+        self.sync = Some(sync_buffered_file);
+        self
+    }
+
+    /// Flushes buffered records and forces them to stable storage.
+    pub fn flush_and_sync(&mut self) -> io::Result<()> {
+        sync_buffered_file(&mut self.inner)?;
+        self.unsynced = 0;
+        self.last_sync = Instant::now();
+        Ok(())
+    }
+}
+
+impl<W: Write> Writer<W> {
+    /// Writes the global header to `inner`.
+    pub fn new(mut inner: W, header: GlobalHeader) -> io::Result<Self> {
+        inner.write_all(&header.to_bytes())?;
+        Ok(Writer::with_header(inner, header))
+    }
+
+    /// Writer for an `inner` positioned after an existing global header.
+    fn with_header(inner: W, header: GlobalHeader) -> Self {
+        Writer {
+            inner,
+            header,
+            sync_policy: SyncPolicy::default(),
+            sync: None,
+            unsynced: 0,
+            last_sync: Instant::now(),
+        }
+    }
+
+    /// Sets when records are forced to storage. Only file writers sync;
+    /// the policy has no effect on other writers.
+    pub fn set_sync_policy(mut self, sync_policy: SyncPolicy) -> Self {
+        self.sync_policy = sync_policy;
+        self
+    }
+
+    /// Returns the global header.
+    pub fn header(&self) -> &GlobalHeader {
+        &self.header
+    }
+
+    /// Writes a record, in the byte order and resolution of the header.
+    pub fn write_packet(&mut self, packet: &CapturedPacket) -> io::Result<()> {
+        let fraction = match self.header.resolution {
+            Resolution::Micros => packet.timestamp.subsec_micros(),
+            Resolution::Nanos => packet.timestamp.subsec_nanos(),
+        };
+        let fields = [
+            packet.timestamp.as_secs() as u32,
+            fraction,
+            packet.data.len() as u32,
+            packet.original_len,
+        ];
+        for field in fields {
+            let bytes = if self.header.big_endian {
+                field.to_be_bytes()
+            } else {
+                field.to_le_bytes()
+            };
+            self.inner.write_all(&bytes)?;
+        }
+        self.inner.write_all(&packet.data)?;
+
+        let Some(sync) = self.sync else {
+            return Ok(());
+        };
+        self.unsynced += 1;
+        let policy = self.sync_policy;
+        let due = policy.every_packets.is_some_and(|n| self.unsynced >= n)
+            || policy.every.is_some_and(|d| self.last_sync.elapsed() >= d);
+        if due {
+            sync(&mut self.inner)?;
+            self.unsynced = 0;
+            self.last_sync = Instant::now();
+        }
+        Ok(())
+    }
+
+    /// Flushes the underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    /// Returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+/// Most flow captures `split_by_flow` keeps open at once, well below the
+/// usual limit of 1024 file descriptors.
+pub const MAX_OPEN_FLOW_FILES: usize = 256;
+
+/// Writes each packet of `reader` to a capture per flow in `output_dir`,
+/// named `{proto}_{src_ip}_{src_port}-{dst_ip}_{dst_port}.pcap`, with the
+/// colons of IPv6 addresses replaced by dots. Both directions of a flow
+/// share a file, keyed by `FiveTuple::normalized`. Packets other than TCP
+/// and UDP go to `other.pcap`. Output files keep the link type of the
+/// input. Returns the file of each flow.
+///
+/// At most `MAX_OPEN_FLOW_FILES` captures are open at a time; the least
+/// recently written one is closed to make room and reopened to append
+/// when its flow comes back.
+pub fn split_by_flow<R: Read>(
+    reader: &mut Reader<R>,
+    output_dir: &Path,
+) -> io::Result<HashMap<FiveTuple, PathBuf>> {
+    split_by_flow_with_max_open(reader, output_dir, MAX_OPEN_FLOW_FILES)
+}
+
+fn split_by_flow_with_max_open<R: Read>(
+    reader: &mut Reader<R>,
+    output_dir: &Path,
+    max_open: usize,
+) -> io::Result<HashMap<FiveTuple, PathBuf>> {
+    let header = *reader.header();
+    let mut paths: HashMap<FiveTuple, PathBuf> = HashMap::new();
+    // Open writers with the packet count at their last write.
+    let mut writers: HashMap<FiveTuple, (Writer<BufWriter<File>>, u64)> = HashMap::new();
+    let mut other = None;
+    let mut packets = 0u64;
+    while let Some(packet) = reader.next_packet()? {
+        packets += 1;
+        let key = FiveTuple::from_frame(header.link_type, &packet.data)
+            .filter(|key| matches!(key.protocol, PROTOCOL_TCP | PROTOCOL_UDP))
+            .map(|key| key.normalized());
+        let writer = match key {
+            Some(key) => {
+                if !writers.contains_key(&key) {
+                    if writers.len() >= max_open.max(1) {
+                        let oldest = writers
+                            .iter()
+                            .min_by_key(|(_, (_, last_write))| *last_write)
+                            .map(|(key, _)| *key);
+                        if let Some((mut writer, _)) = oldest.and_then(|key| writers.remove(&key)) {
+                            writer.flush()?;
+                        }
+                    }
+                    let writer = match paths.entry(key) {
+                        Entry::Occupied(entry) => Writer::reopen(entry.get(), header)?,
+                        Entry::Vacant(entry) => {
+                            let path = output_dir.join(flow_file_name(&key));
+                            Writer::create(entry.insert(path), header)?
+                        }
+                    };
+                    writers.insert(key, (writer, packets));
+                }
+                let (writer, last_write) = writers.get_mut(&key).unwrap();
+                *last_write = packets;
+                writer
+            }
+            None => match &mut other {
+                Some(writer) => writer,
+                None => other.insert(Writer::create(output_dir.join("other.pcap"), header)?),
+            },
+        };
+        writer.write_packet(&packet)?;
+    }
+    for (writer, _) in writers.values_mut() {
+        writer.flush()?;
+    }
+    if let Some(writer) = &mut other {
+        writer.flush()?;
+    }
+    Ok(paths)
+}
+
+const PROTOCOL_TCP: u8 = 6;
+const PROTOCOL_UDP: u8 = 17;
+
+fn flow_file_name(key: &FiveTuple) -> String {
+    let proto = if key.protocol == PROTOCOL_TCP {
+        "tcp"
+    } else {
+        "udp"
+    };
+    // Colons are not allowed in Windows file names.
+    let address = |address: IpAddr| address.to_string().replace(':', ".");
+    format!(
+        "{proto}_{}_{}-{}_{}.pcap",
+        address(key.source),
+        key.source_port,
+        address(key.destination),
+        key.destination_port
+    )
+}
+
+/// Reads until `buf` is full or the end of file, returning the bytes read.
+pub(crate) fn read_full(inner: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match inner.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(read)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::Cursor;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use super::*;
+    use crate::ipv4::IPv4;
+    use crate::ipv6::IPv6;
+    use crate::udp::{self, UDP};
+
+    fn udp_ipv4(source_port: u16) -> Vec<u8> {
+        let udp = UDP::new(source_port, 53, vec![0; 4]);
+        IPv4::with_payload(
+            Ipv4Addr::new(10, 0, 0, 1),
+            Ipv4Addr::new(10, 0, 0, 2),
+            udp::IP_PROTOCOL,
+            udp.to_bytes(),
+        )
+        .to_bytes()
+    }
+
+    fn capture(link_type: u32, frames: &[Vec<u8>]) -> Reader<Cursor<Vec<u8>>> {
+        let header = GlobalHeader::new(link_type, 65535, Resolution::Micros);
+        let mut writer = Writer::new(Vec::new(), header).unwrap();
+        for (i, frame) in frames.iter().enumerate() {
+            let packet = CapturedPacket::new(Duration::from_millis(i as u64), frame.clone());
+            writer.write_packet(&packet).unwrap();
+        }
+        Reader::new(Cursor::new(writer.into_inner())).unwrap()
+    }
+
+    fn output_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ethercrafter-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn packet_ports(path: &Path) -> Vec<u16> {
+        Reader::open(path)
+            .unwrap()
+            .map(|packet| {
+                let data = packet.unwrap().data;
+                u16::from_be_bytes([data[20], data[21]])
+            })
+            .collect()
+    }
+
+    /// A three-record capture and the offset where its last record starts.
+    fn crash_fixture() -> (Vec<u8>, usize) {
+        let frames: Vec<Vec<u8>> = (1000..1003).map(udp_ipv4).collect();
+        let bytes = capture(LINKTYPE_IPV4, &frames).into_inner().into_inner();
+        let last_record = bytes.len() - RECORD_HEADER_LEN as usize - frames[2].len();
+        (bytes, last_record)
+    }
+
+    /// Offsets inside the last record: in its header, at the end of its
+    /// header and in its data.
+    fn crash_offsets(bytes: &[u8], last_record: usize) -> Vec<usize> {
+        vec![
+            last_record + 1,
+            last_record + 8,
+            last_record + RECORD_HEADER_LEN as usize,
+            last_record + RECORD_HEADER_LEN as usize + 5,
+            bytes.len() - 1,
+        ]
+    }
+
+    #[test]
+    fn recovery_stops_at_last_complete_record() {
+        let (bytes, last_record) = crash_fixture();
+        for cut in crash_offsets(&bytes, last_record) {
+            let truncated = bytes[..cut].to_vec();
+            let mut strict = Reader::new(Cursor::new(truncated.clone())).unwrap();
+            assert!(strict.next_packet().unwrap().is_some());
+            assert!(strict.next_packet().unwrap().is_some());
+            let err = strict.next_packet().unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof, "cut at {cut}");
+
+            let mut reader = Reader::new(Cursor::new(truncated))
+                .unwrap()
+                .set_recovery(true);
+            let mut count = 0;
+            while reader.next_packet().unwrap().is_some() {
+                count += 1;
+            }
+            assert_eq!(count, 2, "cut at {cut}");
+            assert_eq!(reader.position(), last_record as u64);
+            assert_eq!(reader.trailing_bytes(), (cut - last_record) as u64);
+            assert!(reader.next_packet().unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn append_after_crash_truncates_partial_record_on_request() {
+        let dir = output_dir("append-crash");
+        let (bytes, last_record) = crash_fixture();
+        let header = GlobalHeader::new(LINKTYPE_IPV4, 65535, Resolution::Micros);
+        for cut in crash_offsets(&bytes, last_record) {
+            let path = dir.join(format!("cut-{cut}.pcap"));
+            fs::write(&path, &bytes[..cut]).unwrap();
+            let err = Writer::append(&path, header, false).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+            assert_eq!(fs::metadata(&path).unwrap().len(), cut as u64);
+
+            let mut writer = Writer::append(&path, header, true).unwrap();
+            let packet = CapturedPacket::new(Duration::from_secs(1), udp_ipv4(2000));
+            writer.write_packet(&packet).unwrap();
+            writer.flush_and_sync().unwrap();
+            drop(writer);
+            assert_eq!(packet_ports(&path), [1000, 1001, 2000], "cut at {cut}");
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn append_rejects_mismatched_header() {
+        let dir = output_dir("append-mismatch");
+        let (bytes, _) = crash_fixture();
+        let path = dir.join("capture.pcap");
+        fs::write(&path, &bytes).unwrap();
+        let nanos = GlobalHeader::new(LINKTYPE_IPV4, 65535, Resolution::Nanos);
+        assert!(Writer::append(&path, nanos, true).is_err());
+        let ipv6 = GlobalHeader::new(LINKTYPE_IPV6, 65535, Resolution::Micros);
+        assert!(Writer::append(&path, ipv6, true).is_err());
+        assert_eq!(fs::read(&path).unwrap(), bytes);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn evicted_flows_are_reopened_to_append() {
+        let dir = output_dir("split-evict");
+        let ports = [1000, 1001, 1002, 1000, 1003, 1001, 1000];
+        let frames: Vec<Vec<u8>> = ports.iter().map(|port| udp_ipv4(*port)).collect();
+        let paths =
+            split_by_flow_with_max_open(&mut capture(LINKTYPE_IPV4, &frames), &dir, 2).unwrap();
+        assert_eq!(paths.len(), 4);
+        for path in paths.values() {
+            let written = packet_ports(path);
+            let expected = ports.iter().filter(|port| **port == written[0]).count();
+            assert_eq!(written.len(), expected);
+            assert!(written.iter().all(|port| *port == written[0]));
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn ipv6_file_names_have_no_colons() {
+        let dir = output_dir("split-ipv6");
+        let udp = UDP::new(5353, 53, vec![0; 4]);
+        let frame = IPv6::with_payload(
+            "2001:db8::1".parse::<Ipv6Addr>().unwrap(),
+            "2001:db8::2".parse::<Ipv6Addr>().unwrap(),
+            udp::IP_PROTOCOL,
+            udp.to_bytes(),
+        )
+        .to_bytes();
+        let paths = split_by_flow(&mut capture(LINKTYPE_IPV6, &[frame]), &dir).unwrap();
+        let path = paths.values().next().unwrap();
+        let name = path.file_name().unwrap().to_str().unwrap();
+        assert_eq!(name, "udp_2001.db8..1_5353-2001.db8..2_53.pcap");
+        assert!(path.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,371 @@
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher, RandomState};
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use siphasher::sip::SipHasher13;
+
+use super::{
+    LINKTYPE_ETHERNET, LINKTYPE_IPV4, LINKTYPE_IPV6, LINKTYPE_NULL, LINKTYPE_RAW, Reader, Writer,
+};
+use crate::ethernet::{
+    ETHERTYPE_ARP, ETHERTYPE_IPV4, ETHERTYPE_IPV6, ETHERTYPE_QINQ, ETHERTYPE_VLAN,
+};
+use crate::util::update_checksum;
+
+// Addresses are replaced bit by bit, as in Crypto-PAn (Xu et al., 2002)
+// with a keyed SipHash instead of AES: bit i of the pseudonym is bit i of
+// the address flipped by a pseudorandom function of the i bits before it.
+// Two addresses sharing a k-bit prefix get pseudonyms sharing a k-bit
+// prefix, and distinct addresses never collide. With `preserve_subnet`
+// the subnet bits are kept as they are and only host bits are replaced.
+//
+// Ports, protocols and ICMP types and codes are left alone; the IPv4
+// header checksum and the TCP, UDP and ICMPv6 checksums are updated
+// incrementally (RFC 1624), so they stay valid even for truncated
+// packets.
+
+const PROTOCOL_TCP: u8 = 6;
+const PROTOCOL_UDP: u8 = 17;
+const PROTOCOL_ICMPV6: u8 = 58;
+
+/// What `anonymize` hides.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnonymizationPolicy {
+    /// Source IP addresses, and ARP sender addresses.
+    pub anonymize_src_ip: bool,
+    /// Destination IP addresses, and ARP target addresses.
+    pub anonymize_dst_ip: bool,
+    /// Source MAC addresses, and ARP sender hardware addresses.
+    pub anonymize_src_mac: bool,
+    /// Destination MAC addresses, and ARP target hardware addresses.
+    pub anonymize_dst_mac: bool,
+    /// Zeroes TCP payloads, keeping their length.
+    pub anonymize_tcp_payload: bool,
+    /// Keeps the subnet part of IP addresses and replaces only host bits.
+    pub preserve_subnet: bool,
+    /// Subnet prefix length kept for IPv4 addresses.
+    pub ipv4_subnet_len: u8,
+    /// Subnet prefix length kept for IPv6 addresses.
+    pub ipv6_subnet_len: u8,
+    /// Secret key of the pseudonyms: with the same key, an address always
+    /// gets the same pseudonym.
+    pub key: [u8; 16],
+}
+
+impl Default for AnonymizationPolicy {
+    fn default() -> Self {
+        AnonymizationPolicy::new()
+    }
+}
+
+impl AnonymizationPolicy {
+    /// Constructor to create a policy hiding every address and TCP payload,
+    /// with a random key and /24 and /64 subnets.
+    pub fn new() -> Self {
+        let random = RandomState::new();
+        let mut key = [0; 16];
+        key[..8].copy_from_slice(&random.hash_one(0u8).to_ne_bytes());
+        key[8..].copy_from_slice(&random.hash_one(1u8).to_ne_bytes());
+        AnonymizationPolicy {
+            anonymize_src_ip: true,
+            anonymize_dst_ip: true,
+            anonymize_src_mac: true,
+            anonymize_dst_mac: true,
+            anonymize_tcp_payload: true,
+            preserve_subnet: false,
+            ipv4_subnet_len: 24,
+            ipv6_subnet_len: 64,
+            key,
+        }
+    }
+
+    // --- SETTER METHODS ---
+
+    pub fn set_anonymize_src_ip(mut self, anonymize_src_ip: bool) -> Self {
+        self.anonymize_src_ip = anonymize_src_ip;
+        self
+    }
+
+    pub fn set_anonymize_dst_ip(mut self, anonymize_dst_ip: bool) -> Self {
+        self.anonymize_dst_ip = anonymize_dst_ip;
+        self
+    }
+
+    pub fn set_anonymize_src_mac(mut self, anonymize_src_mac: bool) -> Self {
+        self.anonymize_src_mac = anonymize_src_mac;
+        self
+    }
+
+    pub fn set_anonymize_dst_mac(mut self, anonymize_dst_mac: bool) -> Self {
+        self.anonymize_dst_mac = anonymize_dst_mac;
+        self
+    }
+
+    pub fn set_anonymize_tcp_payload(mut self, anonymize_tcp_payload: bool) -> Self {
+        self.anonymize_tcp_payload = anonymize_tcp_payload;
+        self
+    }
+
+    /// Keeps the first `ipv4_subnet_len` or `ipv6_subnet_len` bits of the
+    /// addresses.
+    pub fn set_preserve_subnet(
+        mut self,
+        preserve_subnet: bool,
+        ipv4_subnet_len: u8,
+        ipv6_subnet_len: u8,
+    ) -> Self {
+        self.preserve_subnet = preserve_subnet;
+        self.ipv4_subnet_len = ipv4_subnet_len.min(32);
+        self.ipv6_subnet_len = ipv6_subnet_len.min(128);
+        self
+    }
+
+    /// Sets the key, e.g. to keep pseudonyms consistent across captures.
+    pub fn set_key(mut self, key: [u8; 16]) -> Self {
+        self.key = key;
+        self
+    }
+}
+
+/// Copies every packet of `reader` to `writer` with the fields selected by
+/// `policy` anonymized. Frames of other link types than Ethernet and raw
+/// IP are copied unchanged. Returns the number of packets written.
+pub fn anonymize<R: Read, W: Write>(
+    reader: &mut Reader<R>,
+    writer: &mut Writer<W>,
+    policy: AnonymizationPolicy,
+) -> io::Result<u64> {
+    let link_type = reader.link_type();
+    let mut anonymizer = Anonymizer {
+        policy,
+        pseudonyms: HashMap::new(),
+    };
+    let mut count = 0;
+    while let Some(mut packet) = reader.next_packet()? {
+        match link_type {
+            LINKTYPE_ETHERNET => anonymizer.ethernet(&mut packet.data),
+            LINKTYPE_RAW | LINKTYPE_IPV4 | LINKTYPE_IPV6 => anonymizer.ip(&mut packet.data),
+            LINKTYPE_NULL if packet.data.len() >= 4 => anonymizer.ip(&mut packet.data[4..]),
+            _ => {}
+        }
+        writer.write_packet(&packet)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+struct Anonymizer {
+    policy: AnonymizationPolicy,
+    pseudonyms: HashMap<IpAddr, IpAddr>,
+}
+
+impl Anonymizer {
+    fn ethernet(&mut self, frame: &mut [u8]) {
+        if frame.len() < 12 {
+            return;
+        }
+        if self.policy.anonymize_dst_mac {
+            self.mac(&mut frame[0..6]);
+        }
+        if self.policy.anonymize_src_mac {
+            self.mac(&mut frame[6..12]);
+        }
+        let mut offset = 12;
+        while let Some(bytes) = frame.get(offset..offset + 2) {
+            match u16::from_be_bytes([bytes[0], bytes[1]]) {
+                ETHERTYPE_VLAN | ETHERTYPE_QINQ => offset += 4,
+                ETHERTYPE_IPV4 | ETHERTYPE_IPV6 => return self.ip(&mut frame[offset + 2..]),
+                ETHERTYPE_ARP => return self.arp(&mut frame[offset + 2..]),
+                _ => return,
+            }
+        }
+    }
+
+    /// Ethernet/IPv4 ARP packet.
+    fn arp(&mut self, packet: &mut [u8]) {
+        if packet.len() < 28 || packet[4] != 6 || packet[5] != 4 {
+            return;
+        }
+        if self.policy.anonymize_src_mac {
+            self.mac(&mut packet[8..14]);
+        }
+        if self.policy.anonymize_src_ip {
+            self.address(&mut packet[14..18]);
+        }
+        if self.policy.anonymize_dst_mac {
+            self.mac(&mut packet[18..24]);
+        }
+        if self.policy.anonymize_dst_ip {
+            self.address(&mut packet[24..28]);
+        }
+    }
+
+    fn ip(&mut self, packet: &mut [u8]) {
+        match packet.first().map(|byte| byte >> 4) {
+            Some(4) => self.ipv4(packet),
+            Some(6) => self.ipv6(packet),
+            _ => {}
+        }
+    }
+
+    fn ipv4(&mut self, packet: &mut [u8]) {
+        if packet.len() < 20 {
+            return;
+        }
+        let header_len = (packet[0] & 0x0f) as usize * 4;
+        let total_len = u16::from_be_bytes([packet[2], packet[3]]) as usize;
+        let protocol = packet[9];
+        let first_fragment = u16::from_be_bytes([packet[6], packet[7]]) & 0x1fff == 0;
+        let old: [u8; 8] = packet[12..20].try_into().unwrap();
+        if self.policy.anonymize_src_ip {
+            self.address(&mut packet[12..16]);
+        }
+        if self.policy.anonymize_dst_ip {
+            self.address(&mut packet[16..20]);
+        }
+        let new: [u8; 8] = packet[12..20].try_into().unwrap();
+        update_checksum(packet, 10, &old, &new, false);
+        if !first_fragment || header_len < 20 || header_len > packet.len() {
+            return;
+        }
+        let end = total_len.clamp(header_len, packet.len());
+        self.transport(&mut packet[header_len..end], protocol, &old, &new);
+    }
+
+    fn ipv6(&mut self, packet: &mut [u8]) {
+        if packet.len() < 40 {
+            return;
+        }
+        let end = (40 + u16::from_be_bytes([packet[4], packet[5]]) as usize).min(packet.len());
+        let old: [u8; 32] = packet[8..40].try_into().unwrap();
+        if self.policy.anonymize_src_ip {
+            self.address(&mut packet[8..24]);
+        }
+        if self.policy.anonymize_dst_ip {
+            self.address(&mut packet[24..40]);
+        }
+        let mut new: [u8; 32] = packet[8..40].try_into().unwrap();
+        let Some((protocol, offset, routed)) = upper_layer(packet) else {
+            return;
+        };
+        // With a routing header, the pseudo-header holds the final
+        // destination rather than the destination field.
+        if routed {
+            new[16..].copy_from_slice(&old[16..]);
+        }
+        if offset <= end {
+            self.transport(&mut packet[offset..end], protocol, &old, &new);
+        }
+    }
+
+    /// Updates the checksum of `segment` for the pseudo-header change from
+    /// `old` to `new` addresses, and zeroes its TCP payload if requested.
+    fn transport(&mut self, segment: &mut [u8], protocol: u8, old: &[u8], new: &[u8]) {
+        match protocol {
+            PROTOCOL_TCP => {
+                update_checksum(segment, 16, old, new, false);
+                if !self.policy.anonymize_tcp_payload || segment.len() < 20 {
+                    return;
+                }
+                let data_offset = (segment[12] >> 4) as usize * 4;
+                if data_offset < 20 || data_offset >= segment.len() {
+                    return;
+                }
+                let payload = segment[data_offset..].to_vec();
+                segment[data_offset..].fill(0);
+                update_checksum(segment, 16, &payload, &vec![0; payload.len()], false);
+            }
+            PROTOCOL_UDP => update_checksum(segment, 6, old, new, true),
+            PROTOCOL_ICMPV6 => update_checksum(segment, 2, old, new, false),
+            _ => {}
+        }
+    }
+
+    /// Replaces the IPv4 or IPv6 address in `bytes` by its pseudonym.
+    fn address(&mut self, bytes: &mut [u8]) {
+        let address = match bytes.len() {
+            4 => IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(&*bytes).unwrap())),
+            _ => IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(&*bytes).unwrap())),
+        };
+        let policy = &self.policy;
+        let pseudonym = *self
+            .pseudonyms
+            .entry(address)
+            .or_insert_with(|| pseudonym(policy, address));
+        match pseudonym {
+            IpAddr::V4(pseudonym) => bytes.copy_from_slice(&pseudonym.octets()),
+            IpAddr::V6(pseudonym) => bytes.copy_from_slice(&pseudonym.octets()),
+        }
+    }
+
+    /// Replaces a unicast MAC address by a locally administered pseudonym;
+    /// group addresses are kept.
+    fn mac(&self, bytes: &mut [u8]) {
+        if bytes[0] & 0x01 != 0 {
+            return;
+        }
+        let mut hasher = SipHasher13::new_with_key(&self.policy.key);
+        hasher.write(b"mac");
+        hasher.write(bytes);
+        let hash = hasher.finish().to_be_bytes();
+        bytes.copy_from_slice(&hash[..6]);
+        bytes[0] = (bytes[0] & 0xfc) | 0x02;
+    }
+}
+
+/// Prefix-preserving pseudonym of `address`.
+fn pseudonym(policy: &AnonymizationPolicy, address: IpAddr) -> IpAddr {
+    let (value, bits, kept) = match address {
+        IpAddr::V4(address) => (u32::from(address) as u128, 32, policy.ipv4_subnet_len),
+        IpAddr::V6(address) => (u128::from(address), 128, policy.ipv6_subnet_len),
+    };
+    let kept = if policy.preserve_subnet { kept } else { 0 };
+    let mut result = value;
+    for i in kept..bits {
+        // The i leading bits of the original address.
+        let prefix = value.checked_shr((bits - i) as u32).unwrap_or(0);
+        let mut hasher = SipHasher13::new_with_key(&policy.key);
+        hasher.write_u8(i);
+        hasher.write_u128(prefix);
+        result ^= ((hasher.finish() & 1) as u128) << (bits - 1 - i);
+    }
+    match address {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::from(result as u32)),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::from(result)),
+    }
+}
+
+/// Finds the upper-layer protocol of an IPv6 packet, its offset and
+/// whether a routing header precedes it. Returns `None` for non-first
+/// fragments and truncated extension headers.
+fn upper_layer(packet: &[u8]) -> Option<(u8, usize, bool)> {
+    let mut next_header = packet[6];
+    let mut offset = 40;
+    let mut routed = false;
+    loop {
+        match next_header {
+            0 | 43 | 60 => {
+                routed |= next_header == 43;
+                let length = (*packet.get(offset + 1)? as usize + 1) * 8;
+                next_header = *packet.get(offset)?;
+                offset += length;
+            }
+            44 => {
+                let fragment_offset =
+                    u16::from_be_bytes([*packet.get(offset + 2)?, *packet.get(offset + 3)?]) >> 3;
+                if fragment_offset != 0 {
+                    return None;
+                }
+                next_header = *packet.get(offset)?;
+                offset += 8;
+            }
+            51 => {
+                let length = (*packet.get(offset + 1)? as usize + 2) * 4;
+                next_header = *packet.get(offset)?;
+                offset += length;
+            }
+            _ => return Some((next_header, offset, routed)),
+        }
+    }
+}
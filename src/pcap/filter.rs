@@ -0,0 +1,574 @@
+use std::io::{self, Read, Write};
+use std::net::IpAddr;
+use std::ops::Range;
+
+use super::{
+    CapturedPacket, LINKTYPE_ETHERNET, LINKTYPE_IPV4, LINKTYPE_IPV6, LINKTYPE_NULL, LINKTYPE_RAW,
+    ParseStats, Parsed, Reader, WarningCode, Writer,
+};
+use crate::ethernet::{ETHERTYPE_IPV4, ETHERTYPE_IPV6, ETHERTYPE_QINQ, ETHERTYPE_VLAN, Ethernet};
+use crate::ipv4::{FLAG_MORE_FRAGMENTS, IPv4};
+use crate::ipv6::IPv6;
+use crate::limits::{Limit, LimitExceeded, Limits};
+use crate::tcp::TCP;
+use crate::truncate::ipv6_upper_layer;
+use crate::udp::{ChecksumStatus, UDP};
+use crate::util::{IpAddrPair, ParseError, PseudoHeader};
+
+const PROTOCOL_TCP: u8 = 6;
+const PROTOCOL_UDP: u8 = 17;
+// IPv4 and IPv6 encapsulated in IP.
+const PROTOCOL_IPIP: u8 = 4;
+const PROTOCOL_IPV6: u8 = 41;
+
+/// Network layer of a decoded frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetworkLayer {
+    Ipv4(IPv4),
+    Ipv6(IPv6),
+    /// Neither IPv4 nor IPv6, or not decodable (e.g. truncated).
+    None,
+}
+
+impl NetworkLayer {
+    pub fn as_ipv4(&self) -> Option<&IPv4> {
+        match self {
+            NetworkLayer::Ipv4(ipv4) => Some(ipv4),
+            _ => None,
+        }
+    }
+
+    pub fn as_ipv6(&self) -> Option<&IPv6> {
+        match self {
+            NetworkLayer::Ipv6(ipv6) => Some(ipv6),
+            _ => None,
+        }
+    }
+
+    /// Returns the source address of an IP packet.
+    pub fn source(&self) -> Option<IpAddr> {
+        match self {
+            NetworkLayer::Ipv4(ipv4) => Some(ipv4.source.into()),
+            NetworkLayer::Ipv6(ipv6) => Some(ipv6.source.into()),
+            NetworkLayer::None => None,
+        }
+    }
+
+    /// Returns the destination address of an IP packet.
+    pub fn destination(&self) -> Option<IpAddr> {
+        match self {
+            NetworkLayer::Ipv4(ipv4) => Some(ipv4.destination.into()),
+            NetworkLayer::Ipv6(ipv6) => Some(ipv6.destination.into()),
+            NetworkLayer::None => None,
+        }
+    }
+
+    /// Returns the source and destination addresses of an IP packet.
+    pub fn addresses(&self) -> Option<IpAddrPair> {
+        match self {
+            NetworkLayer::Ipv4(ipv4) => Some((ipv4.source, ipv4.destination).into()),
+            NetworkLayer::Ipv6(ipv6) => Some((ipv6.source, ipv6.destination).into()),
+            NetworkLayer::None => None,
+        }
+    }
+}
+
+/// Transport layer of a decoded frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransportLayer {
+    Tcp(TCP),
+    Udp(UDP),
+    /// Another upper-layer protocol, with its payload.
+    Other {
+        protocol: u8,
+        payload: Vec<u8>,
+    },
+    /// No transport header: no IP layer, a non-initial fragment or a
+    /// header that could not be decoded.
+    None,
+}
+
+impl TransportLayer {
+    pub fn as_tcp(&self) -> Option<&TCP> {
+        match self {
+            TransportLayer::Tcp(tcp) => Some(tcp),
+            _ => None,
+        }
+    }
+
+    pub fn as_udp(&self) -> Option<&UDP> {
+        match self {
+            TransportLayer::Udp(udp) => Some(udp),
+            _ => None,
+        }
+    }
+
+    /// Returns the source and destination ports of a TCP or UDP header.
+    pub fn ports(&self) -> Option<(u16, u16)> {
+        match self {
+            TransportLayer::Tcp(tcp) => Some((tcp.source, tcp.destination)),
+            TransportLayer::Udp(udp) => Some((udp.source, udp.destination)),
+            _ => None,
+        }
+    }
+}
+
+/// The layers of a captured frame, decoded as far as possible.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedStack {
+    /// Ethernet header, for captures of Ethernet link type.
+    pub ethernet: Option<Ethernet>,
+    /// VLAN IDs of the 802.1Q and 802.1ad tags, the outer tag first.
+    pub vlan_ids: Vec<u16>,
+    /// Outer IP headers of IP-in-IP tunnels, the outermost first, with
+    /// their payload left out; `network` is the innermost header.
+    pub tunnels: Vec<NetworkLayer>,
+    pub network: NetworkLayer,
+    pub transport: TransportLayer,
+}
+
+impl DecodedStack {
+    /// Decodes `frame`, captured with `link_type`, following IP-in-IP
+    /// tunnels. Decoding stops at the first layer that is unknown or does
+    /// not parse, leaving the layers above it as `None`, or at the layer
+    /// crossing the default limits.
+    pub fn decode(link_type: u32, frame: &[u8]) -> Self {
+        DecodedStack::parse(link_type, frame).layers
+    }
+
+    /// Same as `decode`, failing when more than `limits.max_decode_depth`
+    /// IP headers are nested.
+    pub fn decode_with_limits(
+        link_type: u32,
+        frame: &[u8],
+        limits: &Limits,
+    ) -> Result<Self, LimitExceeded> {
+        match DecodedStack::decode_layers(link_type, frame, limits) {
+            (parsed, None) => Ok(parsed.layers),
+            (_, Some(err)) => Err(err),
+        }
+    }
+
+    /// Same as `decode`, also returning what the decoder found along the
+    /// way: truncated or malformed headers, bad checksums, padding and
+    /// layers it does not decode, with the bytes each layer took.
+    pub fn parse(link_type: u32, frame: &[u8]) -> Parsed {
+        DecodedStack::parse_with_limits(link_type, frame, &Limits::default())
+    }
+
+    /// Same as `parse`, reporting nesting deeper than
+    /// `limits.max_decode_depth` as an error-level warning.
+    pub fn parse_with_limits(link_type: u32, frame: &[u8], limits: &Limits) -> Parsed {
+        DecodedStack::decode_layers(link_type, frame, limits).0
+    }
+
+    /// Decodes the layers, returning the limit that stopped decoding, if
+    /// any.
+    fn decode_layers(
+        link_type: u32,
+        frame: &[u8],
+        limits: &Limits,
+    ) -> (Parsed, Option<LimitExceeded>) {
+        let mut parsed = Parsed {
+            layers: DecodedStack {
+                ethernet: None,
+                vlan_ids: Vec::new(),
+                tunnels: Vec::new(),
+                network: NetworkLayer::None,
+                transport: TransportLayer::None,
+            },
+            warnings: Vec::new(),
+            stats: ParseStats::default(),
+        };
+        let mut packet = match link_type {
+            LINKTYPE_ETHERNET => parsed.decode_ethernet(frame),
+            LINKTYPE_RAW | LINKTYPE_IPV4 | LINKTYPE_IPV6 => Some(0..frame.len()),
+            LINKTYPE_NULL if frame.len() >= 4 => Some(4..frame.len()),
+            _ => {
+                let message = format!("link type {link_type} not decoded");
+                parsed.warn(WarningCode::UnknownLayer, message, 0..frame.len());
+                None
+            }
+        };
+        let mut exceeded = None;
+        while let Some(range) = packet {
+            let depth = parsed.layers.tunnels.len() + 1;
+            if let Err(err) = limits.check(Limit::DecodeDepth, depth) {
+                parsed.warn(WarningCode::DepthLimit, err.to_string(), range);
+                exceeded = Some(err);
+                break;
+            }
+            let Some(inner) = parsed.decode_ip(frame, range) else {
+                break;
+            };
+            let layers = &mut parsed.layers;
+            let mut outer = std::mem::replace(&mut layers.network, NetworkLayer::None);
+            match &mut outer {
+                NetworkLayer::Ipv4(ipv4) => ipv4.payload.clear(),
+                NetworkLayer::Ipv6(ipv6) => ipv6.payload.clear(),
+                NetworkLayer::None => {}
+            }
+            layers.tunnels.push(outer);
+            packet = Some(inner);
+        }
+        parsed.stats.unparsed = frame.len() - parsed.stats.parsed();
+        (parsed, exceeded)
+    }
+}
+
+impl Parsed {
+    /// Decodes the Ethernet header and VLAN tags, returning the range of
+    /// the IP packet they carry.
+    fn decode_ethernet(&mut self, frame: &[u8]) -> Option<Range<usize>> {
+        let ethernet = match Ethernet::from_bytes(frame) {
+            Ok(ethernet) => ethernet,
+            Err(err) => {
+                self.warn_parse_error("Ethernet header", err, 0..frame.len());
+                return None;
+            }
+        };
+        let mut ethertype = ethernet.ethertype;
+        self.layers.ethernet = Some(ethernet);
+        self.consume("eth", 14);
+        let mut offset = 14;
+        while matches!(ethertype, ETHERTYPE_VLAN | ETHERTYPE_QINQ) {
+            let Some(tag) = frame.get(offset..offset + 4) else {
+                let message = "VLAN tag cut short".to_string();
+                self.warn(WarningCode::Truncated, message, offset..frame.len());
+                return None;
+            };
+            self.layers
+                .vlan_ids
+                .push(u16::from_be_bytes([tag[0], tag[1]]) & 0x0fff);
+            ethertype = u16::from_be_bytes([tag[2], tag[3]]);
+            self.consume("vlan", 4);
+            offset += 4;
+        }
+        match ethertype {
+            ETHERTYPE_IPV4 | ETHERTYPE_IPV6 => Some(offset..frame.len()),
+            _ => {
+                let message = format!("EtherType {ethertype:#06x} not decoded");
+                self.warn(WarningCode::UnknownLayer, message, offset..frame.len());
+                None
+            }
+        }
+    }
+
+    /// Decodes the IP packet at `range` of `frame` and its transport
+    /// header. Returns the range of the packet it encapsulates, if it is
+    /// an IP-in-IP tunnel, instead of decoding it as a transport payload.
+    fn decode_ip(&mut self, frame: &[u8], range: Range<usize>) -> Option<Range<usize>> {
+        let start = range.start;
+        let packet = &frame[range.clone()];
+        let (protocol, payload, fragment) = match packet.first().map(|byte| byte >> 4) {
+            Some(4) => {
+                let ipv4 = match IPv4::from_bytes(packet) {
+                    Ok(ipv4) => ipv4,
+                    Err(err) => {
+                        self.warn_parse_error("IPv4 packet", err, range);
+                        return None;
+                    }
+                };
+                if ipv4.compute_checksum() != ipv4.checksum {
+                    let message = format!(
+                        "IPv4 header checksum {:#06x}, expected {:#06x}",
+                        ipv4.checksum,
+                        ipv4.compute_checksum()
+                    );
+                    self.warn(WarningCode::BadChecksum, message, start + 10..start + 12);
+                }
+                let header_len = ipv4.header_len();
+                let end = start + ipv4.total_length as usize;
+                let fragment = (
+                    ipv4.fragment_offset != 0,
+                    ipv4.fragment_offset != 0 || ipv4.flags & FLAG_MORE_FRAGMENTS != 0,
+                );
+                let protocol = ipv4.protocol;
+                self.layers.network = NetworkLayer::Ipv4(ipv4);
+                self.warn_trailing("IPv4", end..range.end);
+                self.consume("ipv4", header_len);
+                (protocol, start + header_len..end, fragment)
+            }
+            Some(6) => {
+                let ipv6 = match IPv6::from_bytes(packet) {
+                    Ok(ipv6) => ipv6,
+                    Err(err) => {
+                        self.warn_parse_error("IPv6 packet", err, range);
+                        return None;
+                    }
+                };
+                let len = 40 + ipv6.payload_length as usize;
+                self.layers.network = NetworkLayer::Ipv6(ipv6);
+                let Some((offset, protocol, fragment)) = ipv6_upper_layer(&packet[..len]) else {
+                    let message = "IPv6 extension headers run past the packet".to_string();
+                    self.warn(WarningCode::Malformed, message, start + 40..start + len);
+                    return None;
+                };
+                self.warn_trailing("IPv6", start + len..range.end);
+                self.consume("ipv6", offset);
+                (protocol, start + offset..start + len, fragment)
+            }
+            Some(version) => {
+                let message = format!("IP version {version}");
+                self.warn(WarningCode::Malformed, message, range);
+                return None;
+            }
+            None => {
+                let message = "no IP header".to_string();
+                self.warn(WarningCode::Truncated, message, range);
+                return None;
+            }
+        };
+        if fragment.0 {
+            let message = "non-initial fragment, transport header not decoded".to_string();
+            self.warn(WarningCode::LaterFragment, message, payload);
+            return None;
+        }
+        if matches!(protocol, PROTOCOL_IPIP | PROTOCOL_IPV6) {
+            return Some(payload);
+        }
+        self.decode_transport(protocol, frame, payload, fragment.1);
+        None
+    }
+
+    /// Decodes the transport header at `range` of `frame`, checking its
+    /// checksum unless the packet is a fragment.
+    fn decode_transport(
+        &mut self,
+        protocol: u8,
+        frame: &[u8],
+        range: Range<usize>,
+        fragment: bool,
+    ) {
+        let start = range.start;
+        let payload = &frame[range.clone()];
+        let pseudo_header = self
+            .layers
+            .network
+            .addresses()
+            .and_then(|addresses| PseudoHeader::try_from(addresses).ok());
+        let checksum_range = |offset: usize| start + offset..start + offset + 2;
+        self.layers.transport = match protocol {
+            PROTOCOL_TCP => match TCP::from_bytes(payload) {
+                Ok(tcp) => {
+                    let expected = pseudo_header.map(|pseudo| tcp.compute_checksum(&pseudo));
+                    if let Some(expected) = expected.filter(|sum| !fragment && *sum != tcp.checksum)
+                    {
+                        let message = format!(
+                            "TCP checksum {:#06x}, expected {expected:#06x}",
+                            tcp.checksum
+                        );
+                        self.warn(WarningCode::BadChecksum, message, checksum_range(16));
+                    }
+                    self.consume("tcp", tcp.data_offset as usize * 4);
+                    TransportLayer::Tcp(tcp)
+                }
+                Err(err) => {
+                    self.warn_parse_error("TCP header", err, range);
+                    TransportLayer::None
+                }
+            },
+            PROTOCOL_UDP => match parse_udp(payload, fragment) {
+                Ok(udp) => {
+                    let status = pseudo_header.map(|pseudo| udp.checksum_status(&pseudo));
+                    let message = match status {
+                        _ if fragment => None,
+                        Some(ChecksumStatus::Invalid) => Some(format!(
+                            "UDP checksum {:#06x}, expected {:#06x}",
+                            udp.checksum,
+                            udp.compute_checksum(&pseudo_header.unwrap())
+                        )),
+                        Some(ChecksumStatus::ZeroNotPermitted) => {
+                            Some("zero UDP checksum over IPv6".to_string())
+                        }
+                        _ => None,
+                    };
+                    if let Some(message) = message {
+                        self.warn(WarningCode::BadChecksum, message, checksum_range(6));
+                    }
+                    self.consume("udp", 8);
+                    TransportLayer::Udp(udp)
+                }
+                Err(err) => {
+                    self.warn_parse_error("UDP header", err, range);
+                    TransportLayer::None
+                }
+            },
+            _ => TransportLayer::Other {
+                protocol,
+                payload: payload.to_vec(),
+            },
+        };
+        let payload_len = match &self.layers.transport {
+            TransportLayer::Tcp(tcp) => tcp.data.len(),
+            TransportLayer::Udp(udp) => udp.data.len(),
+            TransportLayer::Other { payload, .. } => payload.len(),
+            TransportLayer::None => 0,
+        };
+        if payload_len > 0 {
+            self.consume("payload", payload_len);
+        }
+    }
+
+    /// Records `err`, from decoding the `what` at `range` of the frame.
+    fn warn_parse_error(&mut self, what: &str, err: ParseError, range: Range<usize>) {
+        let code = match err {
+            ParseError::Truncated { .. } => WarningCode::Truncated,
+            _ => WarningCode::Malformed,
+        };
+        self.warn(code, format!("{what}: {err}"), range);
+    }
+
+    /// Records the bytes at `range`, after an IP packet, if any.
+    fn warn_trailing(&mut self, what: &str, range: Range<usize>) {
+        if !range.is_empty() {
+            let message = format!("{} bytes after the {what} packet", range.len());
+            self.warn(WarningCode::TrailingBytes, message, range);
+        }
+    }
+}
+
+/// Packets of a capture whose decoded layers match a predicate, e.g.
+/// `|stack| stack.transport.as_tcp().is_some_and(|tcp| tcp.flags & flags::SYN != 0)`
+/// for TCP segments with SYN set.
+pub struct ClosureFilter<R, F> {
+    reader: Reader<R>,
+    predicate: F,
+}
+
+impl<R: Read, F: Fn(&DecodedStack) -> bool> ClosureFilter<R, F> {
+    /// Constructor to create a filter over the remaining packets of
+    /// `reader`.
+    pub fn new(reader: Reader<R>, predicate: F) -> Self {
+        ClosureFilter { reader, predicate }
+    }
+
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> Reader<R> {
+        self.reader
+    }
+}
+
+impl<R: Read, F: Fn(&DecodedStack) -> bool> Iterator for ClosureFilter<R, F> {
+    type Item = io::Result<CapturedPacket>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let link_type = self.reader.link_type();
+        loop {
+            match self.reader.next_packet() {
+                Ok(Some(packet)) => {
+                    if (self.predicate)(&DecodedStack::decode(link_type, &packet.data)) {
+                        return Some(Ok(packet));
+                    }
+                }
+                Ok(None) => return None,
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+/// Writes the packets of `reader` matching `predicate` to `writer`.
+/// Returns the number of packets written.
+pub fn closures_to_pcap<R: Read, W: Write>(
+    reader: &mut Reader<R>,
+    writer: &mut Writer<W>,
+    predicate: impl Fn(&DecodedStack) -> bool,
+) -> io::Result<u64> {
+    let link_type = reader.link_type();
+    let mut count = 0;
+    while let Some(packet) = reader.next_packet()? {
+        if predicate(&DecodedStack::decode(link_type, &packet.data)) {
+            writer.write_packet(&packet)?;
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// Parses the UDP header of `payload`. The first fragment of a datagram
+/// carries its header but not all the data the length field covers: the
+/// data stops at the end of the fragment.
+fn parse_udp(payload: &[u8], fragment: bool) -> Result<UDP, ParseError> {
+    match UDP::from_bytes(payload) {
+        Err(ParseError::Truncated { .. }) if fragment && payload.len() >= 8 => Ok(UDP {
+            source: u16::from_be_bytes([payload[0], payload[1]]),
+            destination: u16::from_be_bytes([payload[2], payload[3]]),
+            length: u16::from_be_bytes([payload[4], payload[5]]),
+            checksum: u16::from_be_bytes([payload[6], payload[7]]),
+            data: payload[8..].to_vec(),
+        }),
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    /// `depth` IPv4 headers nested in each other around a UDP datagram.
+    fn ipip(depth: usize) -> Vec<u8> {
+        let udp = UDP::new(5000, 53, b"query".to_vec());
+        let mut packet = IPv4::with_payload(
+            Ipv4Addr::new(10, 0, 0, 1),
+            Ipv4Addr::new(10, 0, 0, 2),
+            PROTOCOL_UDP,
+            udp.to_bytes(),
+        )
+        .to_bytes();
+        for hop in 1..depth {
+            let source = Ipv4Addr::new(192, 0, 2, hop as u8);
+            let destination = Ipv4Addr::new(198, 51, 100, 1);
+            packet = IPv4::with_payload(source, destination, PROTOCOL_IPIP, packet).to_bytes();
+        }
+        packet
+    }
+
+    #[test]
+    fn follows_ip_in_ip() {
+        let stack = DecodedStack::decode(LINKTYPE_RAW, &ipip(3));
+        assert_eq!(stack.tunnels.len(), 2);
+        assert_eq!(
+            stack.tunnels[0].source(),
+            Some(Ipv4Addr::new(192, 0, 2, 2).into())
+        );
+        assert_eq!(
+            stack.network.source(),
+            Some(Ipv4Addr::new(10, 0, 0, 1).into())
+        );
+        assert_eq!(stack.transport.ports(), Some((5000, 53)));
+    }
+
+    #[test]
+    fn deep_nesting_fails_fast() {
+        let packet = ipip(1000);
+        assert_eq!(
+            DecodedStack::decode_with_limits(LINKTYPE_RAW, &packet, &Limits::default()),
+            Err(LimitExceeded {
+                limit: Limit::DecodeDepth,
+                max: 64
+            })
+        );
+        // Without limits every header is decoded.
+        let stack =
+            DecodedStack::decode_with_limits(LINKTYPE_RAW, &packet, &Limits::unlimited()).unwrap();
+        assert_eq!(stack.tunnels.len(), 999);
+    }
+
+    #[test]
+    fn first_fragments_keep_their_udp_header() {
+        let udp = UDP::new(5000, 53, vec![0; 40]);
+        let mut packet = IPv4::with_payload(
+            Ipv4Addr::new(10, 0, 0, 1),
+            Ipv4Addr::new(10, 0, 0, 2),
+            PROTOCOL_UDP,
+            udp.to_bytes()[..24].to_vec(),
+        );
+        packet.flags = crate::ipv4::FLAG_MORE_FRAGMENTS;
+        let parsed = DecodedStack::parse(LINKTYPE_RAW, &packet.with_checksum().to_bytes());
+        assert!(parsed.warnings.is_empty());
+        let header = parsed.layers.transport.as_udp().unwrap();
+        assert_eq!((header.length, header.data.len()), (48, 16));
+    }
+}
@@ -0,0 +1,302 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use super::{CapturedPacket, GlobalHeader, Reader, invalid_data};
+use crate::codec::{read_varint, unzigzag, write_varint, zigzag};
+use crate::flow::FiveTuple;
+
+// Sidecar index of a capture file.
+//
+// Header: magic "ECPX", version, flags, two reserved bytes. It is followed
+// by one entry per packet, each field a LEB128 varint:
+//
+//   offset delta     byte offset of the record minus that of the previous one
+//   timestamp delta  zigzag-encoded nanoseconds since the previous record
+//   flow reference   (only with FLAG_FLOW_KEYS) 0 = no flow key,
+//                    1 = new flow whose encoded FiveTuple follows,
+//                    n + 2 = flow number n
+
+const MAGIC: &[u8; 4] = b"ECPX";
+const VERSION: u8 = 1;
+const FLAG_FLOW_KEYS: u8 = 0x01;
+const HEADER_LEN: usize = 8;
+
+/// Builds the index of the capture at `input`, with flow keys, and writes
+/// it to `index_path`. Returns the number of packets indexed.
+pub fn index(input: impl AsRef<Path>, index_path: impl AsRef<Path>) -> io::Result<u64> {
+    index_with_options(input, index_path, true)
+}
+
+/// Same as `index`; flow keys are only recorded when `flow_keys` is set.
+///
+/// The capture is read in a single streaming pass, so its size is not
+/// bounded by the available memory.
+pub fn index_with_options(
+    input: impl AsRef<Path>,
+    index_path: impl AsRef<Path>,
+    flow_keys: bool,
+) -> io::Result<u64> {
+    let mut reader = Reader::open(input)?;
+    let link_type = reader.link_type();
+    let mut out = BufWriter::new(File::create(index_path)?);
+    let flags = if flow_keys { FLAG_FLOW_KEYS } else { 0 };
+    out.write_all(MAGIC)?;
+    out.write_all(&[VERSION, flags, 0, 0])?;
+
+    let mut flows: HashMap<FiveTuple, u64> = HashMap::new();
+    let mut last_offset = 0u64;
+    let mut last_timestamp = 0u64;
+    let mut entry = Vec::new();
+    let mut count = 0;
+    loop {
+        let offset = reader.position();
+        let Some(packet) = reader.next_packet()? else {
+            break;
+        };
+        let timestamp = packet.timestamp.as_nanos() as u64;
+        entry.clear();
+        write_varint(&mut entry, offset - last_offset);
+        write_varint(
+            &mut entry,
+            zigzag(timestamp.wrapping_sub(last_timestamp) as i64),
+        );
+        if flow_keys {
+            match FiveTuple::from_frame(link_type, &packet.data) {
+                None => write_varint(&mut entry, 0),
+                Some(key) => match flows.get(&key) {
+                    Some(&id) => write_varint(&mut entry, id + 2),
+                    None => {
+                        flows.insert(key, flows.len() as u64);
+                        write_varint(&mut entry, 1);
+                        key.serialize_into(&mut entry);
+                    }
+                },
+            }
+        }
+        out.write_all(&entry)?;
+        last_offset = offset;
+        last_timestamp = timestamp;
+        count += 1;
+    }
+    out.flush()?;
+    Ok(count)
+}
+
+/// Random access reader over a capture and its index.
+#[derive(Debug)]
+pub struct IndexedReader<R> {
+    reader: Reader<R>,
+    offsets: Vec<u64>,
+    timestamps: Vec<Duration>,
+    flows: HashMap<FiveTuple, Vec<usize>>,
+    next: usize,
+}
+
+impl IndexedReader<BufReader<File>> {
+    /// Opens a capture together with the index built by `pcap::index`.
+    pub fn open(pcap: impl AsRef<Path>, index: impl AsRef<Path>) -> io::Result<Self> {
+        let reader = Reader::open(pcap)?;
+        let mut bytes = Vec::new();
+        File::open(index)?.read_to_end(&mut bytes)?;
+        IndexedReader::from_parts(reader, &bytes)
+    }
+}
+
+impl<R: Read + Seek> IndexedReader<R> {
+    /// Builds an indexed reader from an open capture and the raw index bytes.
+    pub fn from_parts(reader: Reader<R>, index: &[u8]) -> io::Result<Self> {
+        if index.len() < HEADER_LEN || &index[..4] != MAGIC || index[4] != VERSION {
+            return Err(invalid_data("not a capture index"));
+        }
+        let with_flows = index[5] & FLAG_FLOW_KEYS != 0;
+        let truncated = || invalid_data("truncated capture index");
+
+        let mut offsets = Vec::new();
+        let mut timestamps = Vec::new();
+        let mut keys: Vec<FiveTuple> = Vec::new();
+        let mut flows: HashMap<FiveTuple, Vec<usize>> = HashMap::new();
+        let mut offset = 0u64;
+        let mut timestamp = 0u64;
+        let mut pos = HEADER_LEN;
+        while pos < index.len() {
+            offset = offset
+                .checked_add(next_varint(index, &mut pos).ok_or_else(truncated)?)
+                .ok_or_else(|| invalid_data("record offset overflow in capture index"))?;
+            let delta = unzigzag(next_varint(index, &mut pos).ok_or_else(truncated)?);
+            timestamp = timestamp.wrapping_add(delta as u64);
+            if with_flows {
+                let key = match next_varint(index, &mut pos).ok_or_else(truncated)? {
+                    0 => None,
+                    1 => {
+                        let (key, len) = FiveTuple::from_bytes(&index[pos..])
+                            .map_err(|_| invalid_data("invalid flow key in capture index"))?;
+                        pos += len;
+                        keys.push(key);
+                        Some(key)
+                    }
+                    id => Some(
+                        *keys
+                            .get(id as usize - 2)
+                            .ok_or_else(|| invalid_data("unknown flow in capture index"))?,
+                    ),
+                };
+                if let Some(key) = key {
+                    flows.entry(key).or_default().push(offsets.len());
+                }
+            }
+            offsets.push(offset);
+            timestamps.push(Duration::from_nanos(timestamp));
+        }
+        Ok(IndexedReader {
+            reader,
+            offsets,
+            timestamps,
+            flows,
+            next: 0,
+        })
+    }
+
+    /// Returns the global header of the capture.
+    pub fn header(&self) -> &GlobalHeader {
+        self.reader.header()
+    }
+
+    /// Number of packets in the capture.
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    /// Returns true if the capture holds no packets.
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    /// Timestamp of packet `n`, if it exists.
+    pub fn timestamp(&self, n: usize) -> Option<Duration> {
+        self.timestamps.get(n).copied()
+    }
+
+    /// Flow keys seen in the capture (empty if the index has none).
+    pub fn flows(&self) -> impl Iterator<Item = &FiveTuple> {
+        self.flows.keys()
+    }
+
+    /// Makes packet `n` (zero-based) the next one returned by `next_packet`.
+    pub fn seek_to_packet(&mut self, n: usize) -> io::Result<()> {
+        if n > self.offsets.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("packet {n} is past the end of the capture"),
+            ));
+        }
+        self.next = n;
+        Ok(())
+    }
+
+    /// Makes the first packet captured at or after `timestamp` the next one
+    /// returned by `next_packet`, and returns its number. Assumes the capture
+    /// is in chronological order.
+    pub fn seek_to_time(&mut self, timestamp: Duration) -> io::Result<usize> {
+        let n = self.timestamps.partition_point(|t| *t < timestamp);
+        self.seek_to_packet(n)?;
+        Ok(n)
+    }
+
+    /// Reads the next packet in capture order.
+    pub fn next_packet(&mut self) -> io::Result<Option<CapturedPacket>> {
+        let Some(&offset) = self.offsets.get(self.next) else {
+            return Ok(None);
+        };
+        if self.reader.position() != offset {
+            self.reader.seek(offset)?;
+        }
+        self.next += 1;
+        self.reader.next_packet()
+    }
+
+    /// Returns the packets of a flow, in either direction, reading only
+    /// their records from the capture.
+    pub fn read_flow(&mut self, flow_key: &FiveTuple) -> FlowPackets<'_, R> {
+        let mut packets: Vec<usize> = [*flow_key, flow_key.reversed()]
+            .iter()
+            .filter_map(|key| self.flows.get(key))
+            .flatten()
+            .copied()
+            .collect();
+        packets.sort_unstable();
+        packets.dedup();
+        FlowPackets {
+            reader: self,
+            packets: packets.into_iter(),
+        }
+    }
+}
+
+/// Iterator over the packets of one flow, returned by `IndexedReader::read_flow`.
+#[derive(Debug)]
+pub struct FlowPackets<'a, R> {
+    reader: &'a mut IndexedReader<R>,
+    packets: std::vec::IntoIter<usize>,
+}
+
+impl<R: Read + Seek> Iterator for FlowPackets<'_, R> {
+    type Item = io::Result<CapturedPacket>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let n = self.packets.next()?;
+        let result = self
+            .reader
+            .seek_to_packet(n)
+            .and_then(|_| self.reader.next_packet());
+        result.transpose()
+    }
+}
+
+/// Decodes the varint at `pos` and moves past it.
+fn next_varint(bytes: &[u8], pos: &mut usize) -> Option<u64> {
+    let (value, len) = read_varint(&bytes[*pos..]).ok()?;
+    *pos += len;
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::pcap::{LINKTYPE_IPV4, Resolution, Writer};
+
+    fn empty_capture() -> Reader<Cursor<Vec<u8>>> {
+        let header = GlobalHeader::new(LINKTYPE_IPV4, 65535, Resolution::Micros);
+        let writer = Writer::new(Vec::new(), header).unwrap();
+        Reader::new(Cursor::new(writer.into_inner())).unwrap()
+    }
+
+    fn index_bytes(entries: &[(u64, u64)]) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&[VERSION, 0, 0, 0]);
+        for (offset_delta, timestamp_delta) in entries {
+            write_varint(&mut bytes, *offset_delta);
+            write_varint(&mut bytes, *timestamp_delta);
+        }
+        bytes
+    }
+
+    #[test]
+    fn overflowing_offsets_are_invalid_data() {
+        let index = index_bytes(&[(u64::MAX, 0), (1, 0)]);
+        let err = IndexedReader::from_parts(empty_capture(), &index)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn offsets_accumulate_up_to_the_maximum() {
+        let index = index_bytes(&[(u64::MAX - 1, 0), (1, 0)]);
+        assert!(IndexedReader::from_parts(empty_capture(), &index).is_ok());
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::io::{self, Read};
+use std::time::Duration;
+
+use super::{CapturedPacket, Reader};
+
+/// One bucket per power of two of nanoseconds: bucket `i` holds gaps in
+/// `[2^i, 2^(i+1))` ns, except bucket 0 which also holds zero.
+const BUCKETS: usize = 64;
+
+/// Histogram of inter-packet gaps with log2-spaced buckets, covering
+/// nanoseconds to centuries in a fixed 64 counters. Exact minimum, maximum,
+/// mean and standard deviation are tracked next to the buckets.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LatencyHistogram {
+    counts: [u64; BUCKETS],
+    count: u64,
+    min: u64,
+    max: u64,
+    sum: u128,
+    sum_squares: f64,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        LatencyHistogram::new()
+    }
+}
+
+impl LatencyHistogram {
+    /// Constructor to create an empty histogram.
+    pub fn new() -> Self {
+        LatencyHistogram {
+            counts: [0; BUCKETS],
+            count: 0,
+            min: u64::MAX,
+            max: 0,
+            sum: 0,
+            sum_squares: 0.0,
+        }
+    }
+
+    /// Records one gap.
+    pub fn record(&mut self, gap: Duration) {
+        let nanos = u64::try_from(gap.as_nanos()).unwrap_or(u64::MAX);
+        self.counts[bucket_of(nanos)] += 1;
+        self.count += 1;
+        self.min = self.min.min(nanos);
+        self.max = self.max.max(nanos);
+        self.sum += nanos as u128;
+        self.sum_squares += (nanos as f64) * (nanos as f64);
+    }
+
+    /// Adds the gaps recorded in `other`, e.g. to combine flows.
+    pub fn merge(&mut self, other: &LatencyHistogram) {
+        for (count, other) in self.counts.iter_mut().zip(other.counts) {
+            *count += other;
+        }
+        self.count += other.count;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.sum += other.sum;
+        self.sum_squares += other.sum_squares;
+    }
+
+    /// Number of gaps recorded.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns true if no gap was recorded.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Smallest gap, or zero if the histogram is empty.
+    pub fn min(&self) -> Duration {
+        if self.is_empty() {
+            return Duration::ZERO;
+        }
+        Duration::from_nanos(self.min)
+    }
+
+    /// Largest gap, or zero if the histogram is empty.
+    pub fn max(&self) -> Duration {
+        Duration::from_nanos(self.max)
+    }
+
+    /// Mean gap, or zero if the histogram is empty.
+    pub fn mean(&self) -> Duration {
+        if self.is_empty() {
+            return Duration::ZERO;
+        }
+        Duration::from_nanos((self.sum / self.count as u128) as u64)
+    }
+
+    /// Population standard deviation of the gaps, in seconds.
+    pub fn standard_deviation(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let count = self.count as f64;
+        let mean = self.sum as f64 / count;
+        let variance = (self.sum_squares / count - mean * mean).max(0.0);
+        variance.sqrt() / 1e9
+    }
+
+    /// Gap below which `p` percent of the gaps fall, for `p` in 0..=100.
+    /// The value is interpolated linearly inside its bucket, so it is
+    /// accurate to within a factor of two, and clamped to the exact
+    /// minimum and maximum.
+    pub fn percentile(&self, p: f64) -> Duration {
+        if self.is_empty() {
+            return Duration::ZERO;
+        }
+        let rank = (p.clamp(0.0, 100.0) / 100.0 * self.count as f64).max(1.0);
+        let mut below = 0u64;
+        for (index, &count) in self.counts.iter().enumerate() {
+            if count == 0 || ((below + count) as f64) < rank {
+                below += count;
+                continue;
+            }
+            let (low, high) = bucket_bounds(index);
+            let fraction = (rank - below as f64) / count as f64;
+            let nanos = low as f64 + fraction * (high - low) as f64;
+            return Duration::from_nanos((nanos as u64).clamp(self.min, self.max));
+        }
+        self.max()
+    }
+
+    /// Lower bound and count of each bucket, from the lowest to the
+    /// highest non-empty bucket. Bounds double from one bucket to the next.
+    pub fn buckets(&self) -> Vec<(Duration, u64)> {
+        let Some(first) = self.counts.iter().position(|&count| count > 0) else {
+            return Vec::new();
+        };
+        let last = self
+            .counts
+            .iter()
+            .rposition(|&count| count > 0)
+            .unwrap_or(first);
+        (first..=last)
+            .map(|index| {
+                let (low, _) = bucket_bounds(index);
+                (Duration::from_nanos(low), self.counts[index])
+            })
+            .collect()
+    }
+}
+
+fn bucket_of(nanos: u64) -> usize {
+    (u64::BITS - 1).saturating_sub(nanos.leading_zeros()) as usize
+}
+
+/// Inclusive lower and exclusive upper bound of a bucket, in nanoseconds.
+fn bucket_bounds(index: usize) -> (u64, u64) {
+    let low = if index == 0 { 0 } else { 1u64 << index };
+    let high = 1u64.checked_shl(index as u32 + 1).unwrap_or(u64::MAX);
+    (low, high)
+}
+
+/// Computes the inter-arrival time histogram of each flow of `reader`.
+/// `extractor` returns the flow key of a packet, or `None` to skip it;
+/// e.g. `|packet| FiveTuple::from_frame(link_type, &packet.data)`.
+///
+/// A packet whose timestamp is earlier than the previous one of its flow
+/// counts as a gap of zero. Flows with a single packet get an empty
+/// histogram.
+pub fn histogram_latency<R, K, F>(
+    reader: &mut Reader<R>,
+    extractor: F,
+) -> io::Result<HashMap<K, LatencyHistogram>>
+where
+    R: Read,
+    K: Eq + Hash,
+    F: Fn(&CapturedPacket) -> Option<K>,
+{
+    let mut flows: HashMap<K, (Duration, LatencyHistogram)> = HashMap::new();
+    while let Some(packet) = reader.next_packet()? {
+        let Some(key) = extractor(&packet) else {
+            continue;
+        };
+        match flows.get_mut(&key) {
+            Some((last, histogram)) => {
+                histogram.record(packet.timestamp.saturating_sub(*last));
+                *last = packet.timestamp;
+            }
+            None => {
+                flows.insert(key, (packet.timestamp, LatencyHistogram::new()));
+            }
+        }
+    }
+    Ok(flows
+        .into_iter()
+        .map(|(key, (_, histogram))| (key, histogram))
+        .collect())
+}
@@ -0,0 +1,300 @@
+use std::fmt;
+use std::ops::Range;
+
+use super::DecodedStack;
+
+/// How much a `Warning` should worry a caller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Severity {
+    /// Expected on the wire, e.g. Ethernet padding or a layer the decoder
+    /// does not know.
+    Info,
+    /// The frame is usable but not as sent, e.g. truncated or with a bad
+    /// checksum.
+    Warning,
+    /// A header is invalid or decoding was cut short.
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// What a `Warning` is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WarningCode {
+    /// The capture ends before the layer does: snaplen or a short frame.
+    Truncated,
+    /// A header field holds a value the decoder does not accept.
+    Malformed,
+    /// An IPv4 header, TCP or UDP checksum does not match.
+    BadChecksum,
+    /// Bytes after the IP packet, usually Ethernet padding.
+    TrailingBytes,
+    /// A link type, EtherType or header the decoder does not decode.
+    UnknownLayer,
+    /// A non-initial fragment: its transport header is in another packet.
+    LaterFragment,
+    /// More IP headers nested than the limits allow.
+    DepthLimit,
+}
+
+impl WarningCode {
+    /// Short name of the code, e.g. `bad-checksum`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            WarningCode::Truncated => "truncated",
+            WarningCode::Malformed => "malformed",
+            WarningCode::BadChecksum => "bad-checksum",
+            WarningCode::TrailingBytes => "trailing-bytes",
+            WarningCode::UnknownLayer => "unknown-layer",
+            WarningCode::LaterFragment => "later-fragment",
+            WarningCode::DepthLimit => "depth-limit",
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self {
+            WarningCode::TrailingBytes | WarningCode::UnknownLayer | WarningCode::LaterFragment => {
+                Severity::Info
+            }
+            WarningCode::Truncated | WarningCode::BadChecksum => Severity::Warning,
+            WarningCode::Malformed | WarningCode::DepthLimit => Severity::Error,
+        }
+    }
+}
+
+impl fmt::Display for WarningCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Something non-fatal found while decoding a frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Warning {
+    pub code: WarningCode,
+    pub severity: Severity,
+    pub message: String,
+    /// Index in `ParseStats::layers` of the layer the warning is about, or
+    /// the number of layers decoded for the layer that stopped decoding.
+    pub layer: usize,
+    /// Bytes of the frame the warning is about.
+    pub range: Range<usize>,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}[{}] layer {}, bytes {}..{}: {}",
+            self.severity, self.code, self.layer, self.range.start, self.range.end, self.message
+        )
+    }
+}
+
+impl std::error::Error for Warning {}
+
+/// Bytes each decoded layer of a frame took.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ParseStats {
+    /// Layers in decoding order, with the bytes of their header: `eth`,
+    /// `vlan`, `ipv4`, `ipv6` (extension headers included), `tcp`, `udp`,
+    /// then `payload` for what the transport header carries.
+    pub layers: Vec<(&'static str, usize)>,
+    /// Bytes after the last layer decoded.
+    pub unparsed: usize,
+}
+
+impl ParseStats {
+    /// Bytes taken by the decoded layers.
+    pub fn parsed(&self) -> usize {
+        self.layers.iter().map(|(_, bytes)| bytes).sum()
+    }
+}
+
+/// A decoded frame, with everything non-fatal the decoder found along
+/// the way.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Parsed {
+    pub layers: DecodedStack,
+    pub warnings: Vec<Warning>,
+    pub stats: ParseStats,
+}
+
+impl Parsed {
+    /// Returns true if no warning is of `Severity::Warning` or above:
+    /// padding and undecoded layers do not count.
+    pub fn is_clean(&self) -> bool {
+        self.warnings_at(Severity::Warning).next().is_none()
+    }
+
+    /// Warnings of `threshold` or above.
+    pub fn warnings_at(&self, threshold: Severity) -> impl Iterator<Item = &Warning> {
+        self.warnings
+            .iter()
+            .filter(move |warning| warning.severity >= threshold)
+    }
+
+    /// Returns the layers, or the first warning of `threshold` or above,
+    /// for callers that treat warnings as errors.
+    pub fn check(self, threshold: Severity) -> Result<DecodedStack, Warning> {
+        let first = self.warnings_at(threshold).next().cloned();
+        match first {
+            Some(warning) => Err(warning),
+            None => Ok(self.layers),
+        }
+    }
+
+    /// Records a warning about the layer consumed next.
+    pub(super) fn warn(&mut self, code: WarningCode, message: String, range: Range<usize>) {
+        self.warnings.push(Warning {
+            code,
+            severity: code.severity(),
+            message,
+            layer: self.stats.layers.len(),
+            range,
+        });
+    }
+
+    /// Records a layer of `bytes` bytes.
+    pub(super) fn consume(&mut self, name: &'static str, bytes: usize) {
+        self.stats.layers.push((name, bytes));
+    }
+}
+
+/// Writes the layers, e.g. `eth(14) ipv4(20) tcp(20) payload(5)`, then
+/// the warnings, one per line.
+impl fmt::Display for Parsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let layers: Vec<String> = self
+            .stats
+            .layers
+            .iter()
+            .map(|(name, bytes)| format!("{name}({bytes})"))
+            .collect();
+        f.write_str(&layers.join(" "))?;
+        if self.stats.unparsed > 0 {
+            write!(f, " +{} bytes", self.stats.unparsed)?;
+        }
+        for warning in &self.warnings {
+            write!(f, "\n  {warning}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+    use crate::ethernet::{ETHERTYPE_IPV4, Ethernet, MacAddr};
+    use crate::ipv4::IPv4;
+    use crate::limits::Limits;
+    use crate::pcap::{LINKTYPE_ETHERNET, LINKTYPE_RAW};
+    use crate::tcp::{TCP, flags};
+    use crate::udp::UDP;
+    use crate::util::PseudoHeader;
+
+    const SOURCE: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 1);
+    const DESTINATION: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 2);
+    const PSEUDO_HEADER: PseudoHeader = PseudoHeader::V4 {
+        source: SOURCE,
+        destination: DESTINATION,
+    };
+
+    fn ethernet(ethertype: u16, payload: Vec<u8>) -> Vec<u8> {
+        let mac = MacAddr([2, 0, 0, 0, 0, 1]);
+        Ethernet::new(MacAddr::BROADCAST, mac, ethertype, payload).to_bytes()
+    }
+
+    fn tcp(data: &[u8]) -> Vec<u8> {
+        let tcp = TCP::segment(40000, 80, 1, 0, flags::PSH | flags::ACK)
+            .set_data(data.to_vec())
+            .with_checksum(&PSEUDO_HEADER);
+        IPv4::with_payload(SOURCE, DESTINATION, 6, tcp.to_bytes())
+            .with_checksum()
+            .to_bytes()
+    }
+
+    #[test]
+    fn padding_is_noted_and_layers_are_counted() {
+        let udp = UDP::new(5000, 53, vec![7]).with_checksum(&PSEUDO_HEADER);
+        let packet = IPv4::with_payload(SOURCE, DESTINATION, 17, udp.to_bytes()).with_checksum();
+        let mut frame = ethernet(ETHERTYPE_IPV4, packet.to_bytes());
+        frame.resize(60, 0);
+
+        let parsed = DecodedStack::parse(LINKTYPE_ETHERNET, &frame);
+        assert!(parsed.is_clean());
+        assert_eq!(
+            parsed.stats.layers,
+            [("eth", 14), ("ipv4", 20), ("udp", 8), ("payload", 1)]
+        );
+        assert_eq!(parsed.stats.unparsed, 17);
+        assert_eq!(parsed.warnings.len(), 1);
+        assert_eq!(parsed.warnings[0].code, WarningCode::TrailingBytes);
+        assert_eq!(parsed.warnings[0].range, 43..60);
+        assert_eq!(
+            parsed.to_string(),
+            "eth(14) ipv4(20) udp(8) payload(1) +17 bytes\n  \
+             info[trailing-bytes] layer 1, bytes 43..60: 17 bytes after the IPv4 packet"
+        );
+        assert!(parsed.check(Severity::Info).is_err());
+    }
+
+    #[test]
+    fn bad_checksums_and_truncation() {
+        let mut packet = tcp(b"hello");
+        packet[37] ^= 0xff;
+        let parsed = DecodedStack::parse(LINKTYPE_RAW, &packet);
+        assert_eq!(parsed.warnings.len(), 1);
+        let warning = &parsed.warnings[0];
+        assert_eq!(
+            (warning.code, warning.severity, warning.layer),
+            (WarningCode::BadChecksum, Severity::Warning, 1)
+        );
+        assert_eq!(warning.range, 36..38);
+        assert!(!parsed.is_clean());
+        assert!(parsed.layers.transport.as_tcp().is_some());
+        assert!(parsed.clone().check(Severity::Error).is_ok());
+        assert_eq!(
+            parsed.check(Severity::Warning).unwrap_err().code,
+            WarningCode::BadChecksum
+        );
+
+        // Cut by the snaplen: the IP layer is reported and not decoded.
+        let packet = tcp(b"hello");
+        let parsed = DecodedStack::parse(LINKTYPE_RAW, &packet[..30]);
+        assert_eq!(parsed.warnings[0].code, WarningCode::Truncated);
+        assert_eq!(parsed.warnings[0].layer, 0);
+        assert_eq!(parsed.stats.unparsed, 30);
+    }
+
+    #[test]
+    fn unknown_layers_and_limits() {
+        let frame = ethernet(0x88cc, vec![0; 46]);
+        let parsed = DecodedStack::parse(LINKTYPE_ETHERNET, &frame);
+        assert!(parsed.is_clean());
+        assert_eq!(parsed.warnings[0].code, WarningCode::UnknownLayer);
+        assert_eq!(parsed.warnings[0].message, "EtherType 0x88cc not decoded");
+
+        let inner = tcp(b"");
+        let outer = IPv4::with_payload(SOURCE, DESTINATION, 4, inner).with_checksum();
+        let limits = Limits::new().set_max_decode_depth(1);
+        let parsed = DecodedStack::parse_with_limits(LINKTYPE_RAW, &outer.to_bytes(), &limits);
+        let warning = parsed.check(Severity::Error).unwrap_err();
+        assert_eq!(warning.code, WarningCode::DepthLimit);
+        assert_eq!(warning.range, 20..60);
+    }
+}
@@ -0,0 +1,424 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Read};
+use std::net::SocketAddr;
+
+use super::Reader;
+use crate::analysis::{RoleInference, RoleObserver, RoleOverrides};
+use crate::flow::FiveTuple;
+use crate::limits::{Limit, Limits};
+use crate::tcp::{self, OffsetAndFlags};
+use crate::truncate::layout;
+
+const PROTOCOL_TCP: u8 = 6;
+
+/// Problem found while reassembling one direction of a TCP stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReassemblyError {
+    /// Two segments carry different bytes for the same sequence numbers;
+    /// the bytes seen first are kept.
+    OverlappingData,
+    /// No segment covers the bytes starting at `gap_at_seq`; the stream
+    /// continues with the next segment.
+    MissingData { gap_at_seq: u32 },
+}
+
+impl fmt::Display for ReassemblyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReassemblyError::OverlappingData => write!(f, "segments overlap with different data"),
+            ReassemblyError::MissingData { gap_at_seq } => {
+                write!(f, "missing data at sequence number {gap_at_seq}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReassemblyError {}
+
+/// Payload of one direction of a TCP stream, in sequence order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReassembledStream {
+    pub data: Vec<u8>,
+    pub errors: Vec<ReassemblyError>,
+    /// Segments carrying bytes that were already received.
+    pub retransmissions: u64,
+    pub retransmitted_bytes: u64,
+}
+
+/// Both directions of a TCP stream, keyed by its normalized five-tuple:
+/// `forward` holds the bytes sent from the key's source.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReassembledFlow {
+    pub forward: ReassembledStream,
+    pub reverse: ReassembledStream,
+    /// Client and server of the flow, as overridden or inferred.
+    pub roles: Option<RoleInference>,
+}
+
+impl ReassembledFlow {
+    /// Bytes sent by the client, if the roles are known.
+    pub fn client_stream(&self, key: &FiveTuple) -> Option<&ReassembledStream> {
+        self.stream_from(key, self.roles?.client)
+    }
+
+    /// Bytes sent by the server, if the roles are known.
+    pub fn server_stream(&self, key: &FiveTuple) -> Option<&ReassembledStream> {
+        self.stream_from(key, self.roles?.server)
+    }
+
+    /// Direction of the flow of normalized `key` sent from `endpoint`.
+    fn stream_from(&self, key: &FiveTuple, endpoint: SocketAddr) -> Option<&ReassembledStream> {
+        if endpoint == SocketAddr::new(key.source, key.source_port) {
+            Some(&self.forward)
+        } else if endpoint == SocketAddr::new(key.destination, key.destination_port) {
+            Some(&self.reverse)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Default)]
+struct Segments {
+    /// Sequence number following the SYN, if one was captured.
+    initial: Option<u32>,
+    /// (sequence number of the first payload byte, payload)
+    segments: Vec<(u32, Vec<u8>)>,
+}
+
+impl Segments {
+    fn reassemble(self) -> ReassembledStream {
+        let mut stream = ReassembledStream::default();
+        let Some(reference) = self
+            .initial
+            .or_else(|| self.segments.first().map(|(sequence, _)| *sequence))
+        else {
+            return stream;
+        };
+        // Offsets relative to the reference, so that sequence numbers
+        // wrapping around sort correctly.
+        let mut segments: Vec<(i64, Vec<u8>)> = self
+            .segments
+            .into_iter()
+            .map(|(sequence, payload)| (sequence.wrapping_sub(reference) as i32 as i64, payload))
+            .collect();
+        segments.sort_by_key(|(offset, _)| *offset);
+        let mut next = match self.initial {
+            Some(_) => 0,
+            None => segments.first().map_or(0, |(offset, _)| *offset),
+        };
+        // Start of the contiguous run of bytes ending at `next`, as an
+        // offset and as an index into the data.
+        let (mut run_offset, mut run_index) = (next, 0);
+        for (offset, payload) in segments {
+            let end = offset + payload.len() as i64;
+            if offset > next {
+                stream.errors.push(ReassemblyError::MissingData {
+                    gap_at_seq: reference.wrapping_add(next as u32),
+                });
+                next = offset;
+                (run_offset, run_index) = (offset, stream.data.len());
+            }
+            if offset < next {
+                let from = offset.max(run_offset);
+                let to = end.min(next);
+                if from < to {
+                    let received = &stream.data[run_index + (from - run_offset) as usize..]
+                        [..(to - from) as usize];
+                    let resent = &payload[(from - offset) as usize..(to - offset) as usize];
+                    if resent != received
+                        && !stream.errors.contains(&ReassemblyError::OverlappingData)
+                    {
+                        stream.errors.push(ReassemblyError::OverlappingData);
+                    }
+                }
+                stream.retransmissions += 1;
+                stream.retransmitted_bytes += (end.min(next) - offset) as u64;
+            }
+            if end > next {
+                stream
+                    .data
+                    .extend_from_slice(&payload[(next - offset) as usize..]);
+                next = end;
+            }
+        }
+        stream
+    }
+}
+
+/// Reassembles the payload of every TCP stream of `reader`. Segments are
+/// ordered by sequence number, so out-of-order delivery is undone and
+/// retransmitted bytes are kept once. Gaps and conflicting overlaps are
+/// recorded in the errors of the affected direction.
+///
+/// Sequence numbers are taken relative to the SYN when it was captured, or
+/// to the first segment of the direction otherwise; streams spanning more
+/// than 2 GiB are not supported.
+pub fn tcp_stream_reassembly<R: Read>(
+    reader: &mut Reader<R>,
+) -> io::Result<HashMap<FiveTuple, ReassembledFlow>> {
+    tcp_stream_reassembly_with_limits(reader, &Limits::default())
+}
+
+/// Same as `tcp_stream_reassembly`, buffering at most
+/// `limits.max_reassembly_bytes` of payload over all streams. Crossing it
+/// fails with an `InvalidData` error wrapping a `LimitExceeded`.
+pub fn tcp_stream_reassembly_with_limits<R: Read>(
+    reader: &mut Reader<R>,
+    limits: &Limits,
+) -> io::Result<HashMap<FiveTuple, ReassembledFlow>> {
+    tcp_stream_reassembly_with_roles(reader, limits, &RoleOverrides::new())
+}
+
+/// Same as `tcp_stream_reassembly_with_limits`, taking the client and
+/// server of the flows listed in `overrides` as given. The roles of the
+/// other flows are inferred as `analysis::infer_roles` does.
+pub fn tcp_stream_reassembly_with_roles<R: Read>(
+    reader: &mut Reader<R>,
+    limits: &Limits,
+    overrides: &RoleOverrides,
+) -> io::Result<HashMap<FiveTuple, ReassembledFlow>> {
+    let link_type = reader.link_type();
+    let mut directions: HashMap<FiveTuple, Segments> = HashMap::new();
+    let mut observers: HashMap<FiveTuple, RoleObserver> = HashMap::new();
+    let mut buffered = 0;
+    while let Some(packet) = reader.next_packet()? {
+        let Some(layout) = layout(link_type, &packet.data) else {
+            continue;
+        };
+        let Some((offset, PROTOCOL_TCP)) = layout.transport else {
+            continue;
+        };
+        let Some(key) = FiveTuple::from_frame(link_type, &packet.data) else {
+            continue;
+        };
+        let header = &packet.data[offset..];
+        let mut sequence = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+        let direction = directions.entry(key).or_default();
+        let flags = OffsetAndFlags([header[12], header[13]]).get_flags();
+        if flags & tcp::flags::SYN != 0 {
+            sequence = sequence.wrapping_add(1);
+            direction.initial = Some(sequence);
+        }
+        let payload = &packet.data[layout.payload_offset..layout.end];
+        observers.entry(key.normalized()).or_default().observe(
+            SocketAddr::new(key.source, key.source_port),
+            SocketAddr::new(key.destination, key.destination_port),
+            flags,
+            payload,
+        );
+        if !payload.is_empty() {
+            buffered += payload.len();
+            limits
+                .check(Limit::ReassemblyBytes, buffered)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            direction.segments.push((sequence, payload.to_vec()));
+        }
+    }
+
+    let mut flows: HashMap<FiveTuple, ReassembledFlow> = HashMap::new();
+    for (key, segments) in directions {
+        let normalized = key.normalized();
+        let flow = flows.entry(normalized).or_insert_with(|| ReassembledFlow {
+            roles: overrides.resolve(&normalized, &observers[&normalized]),
+            ..ReassembledFlow::default()
+        });
+        if normalized == key {
+            flow.forward = segments.reassemble();
+        } else {
+            flow.reverse = segments.reassemble();
+        }
+    }
+    Ok(flows)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::net::Ipv4Addr;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::analysis::RoleEvidence;
+    use crate::ipv4::IPv4;
+    use crate::limits::LimitExceeded;
+    use crate::pcap::{CapturedPacket, GlobalHeader, LINKTYPE_IPV4, Resolution, Writer};
+    use crate::tcp::TCP;
+
+    const ISN: u32 = 0xffff_fff0;
+
+    /// Client to server packet with sequence number `ISN + 1 + offset`.
+    fn segment(offset: u32, flags: u16, payload: &[u8]) -> Vec<u8> {
+        let sequence = ISN.wrapping_add(1).wrapping_add(offset);
+        let tcp = TCP::new(
+            40000,
+            80,
+            sequence,
+            0,
+            5,
+            0,
+            flags,
+            65535,
+            0,
+            0,
+            Vec::new(),
+            Vec::new(),
+            payload.to_vec(),
+        );
+        IPv4::with_payload(
+            Ipv4Addr::new(10, 0, 0, 1),
+            Ipv4Addr::new(10, 0, 0, 2),
+            PROTOCOL_TCP,
+            tcp.to_bytes(),
+        )
+        .to_bytes()
+    }
+
+    fn syn() -> Vec<u8> {
+        segment(u32::MAX, tcp::flags::SYN, &[])
+    }
+
+    fn capture(frames: &[Vec<u8>]) -> Reader<Cursor<Vec<u8>>> {
+        let header = GlobalHeader::new(LINKTYPE_IPV4, 65535, Resolution::Micros);
+        let mut writer = Writer::new(Vec::new(), header).unwrap();
+        for (i, frame) in frames.iter().enumerate() {
+            let packet = CapturedPacket::new(Duration::from_millis(i as u64), frame.clone());
+            writer.write_packet(&packet).unwrap();
+        }
+        Reader::new(Cursor::new(writer.into_inner())).unwrap()
+    }
+
+    /// Client to server direction of the only flow of `frames`.
+    fn client_stream(frames: &[Vec<u8>]) -> ReassembledStream {
+        let flows = tcp_stream_reassembly(&mut capture(frames)).unwrap();
+        assert_eq!(flows.len(), 1);
+        let key = FiveTuple::from_frame(LINKTYPE_IPV4, &frames[0]).unwrap();
+        let flow = &flows[&key.normalized()];
+        if key.normalized() == key {
+            flow.forward.clone()
+        } else {
+            flow.reverse.clone()
+        }
+    }
+
+    #[test]
+    fn http_request_in_ten_segments() {
+        let request: &[u8] =
+            b"GET /index.html HTTP/1.1\r\nHost: example.com\r\nUser-Agent: test\r\n\r\n";
+        let chunk = request.len().div_ceil(10);
+        let mut frames = vec![syn()];
+        for (i, part) in request.chunks(chunk).enumerate() {
+            frames.push(segment((i * chunk) as u32, tcp::flags::ACK, part));
+        }
+        assert_eq!(frames.len(), 11);
+        let stream = client_stream(&frames);
+        assert_eq!(stream.data, request);
+        assert!(stream.errors.is_empty());
+        assert!(stream.data.starts_with(b"GET /index.html HTTP/1.1\r\n"));
+    }
+
+    #[test]
+    fn out_of_order_segments_across_sequence_wrap() {
+        let frames = vec![
+            syn(),
+            segment(10, tcp::flags::ACK, b"klmnopqrst"),
+            segment(20, tcp::flags::ACK, b"uvwxyz"),
+            segment(0, tcp::flags::ACK, b"abcdefghij"),
+        ];
+        let stream = client_stream(&frames);
+        assert_eq!(stream.data, b"abcdefghijklmnopqrstuvwxyz");
+        assert!(stream.errors.is_empty());
+        assert_eq!(stream.retransmissions, 0);
+    }
+
+    #[test]
+    fn retransmission_and_overlap() {
+        let frames = vec![
+            syn(),
+            segment(0, tcp::flags::ACK, b"abcdef"),
+            segment(0, tcp::flags::ACK, b"abcdef"),
+            segment(4, tcp::flags::ACK, b"XXghij"),
+        ];
+        let stream = client_stream(&frames);
+        assert_eq!(stream.data, b"abcdefghij");
+        assert_eq!(stream.errors, vec![ReassemblyError::OverlappingData]);
+        assert_eq!(stream.retransmissions, 2);
+        assert_eq!(stream.retransmitted_bytes, 8);
+    }
+
+    #[test]
+    fn gap_is_reported() {
+        let frames = vec![
+            syn(),
+            segment(0, tcp::flags::ACK, b"abc"),
+            segment(6, tcp::flags::ACK, b"ghi"),
+        ];
+        let stream = client_stream(&frames);
+        assert_eq!(stream.data, b"abcghi");
+        assert_eq!(
+            stream.errors,
+            vec![ReassemblyError::MissingData {
+                gap_at_seq: ISN.wrapping_add(4)
+            }]
+        );
+    }
+
+    #[test]
+    fn buffered_bytes_limit() {
+        let frames = vec![
+            syn(),
+            segment(0, tcp::flags::ACK, &[0; 600]),
+            segment(600, tcp::flags::ACK, &[0; 600]),
+        ];
+        let limits = Limits::default().set_max_reassembly_bytes(1000);
+        let err = tcp_stream_reassembly_with_limits(&mut capture(&frames), &limits).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let inner = err.into_inner().unwrap();
+        assert_eq!(
+            inner.downcast_ref::<LimitExceeded>(),
+            Some(&LimitExceeded {
+                limit: Limit::ReassemblyBytes,
+                max: 1000
+            })
+        );
+        let limits = Limits::default().set_max_reassembly_bytes(1200);
+        assert!(tcp_stream_reassembly_with_limits(&mut capture(&frames), &limits).is_ok());
+    }
+
+    #[test]
+    fn mid_stream_roles_are_inferred() {
+        let frames = vec![
+            segment(0, tcp::flags::ACK, b"abc"),
+            segment(3, tcp::flags::ACK, b"def"),
+        ];
+        let flows = tcp_stream_reassembly(&mut capture(&frames)).unwrap();
+        let (key, flow) = flows.iter().next().unwrap();
+        let roles = flow.roles.unwrap();
+        assert_eq!(roles.server, "10.0.0.2:80".parse().unwrap());
+        assert_eq!(roles.evidence, RoleEvidence::WellKnownPort(80));
+        assert_eq!(flow.client_stream(key).unwrap().data, b"abcdef");
+        assert!(flow.server_stream(key).unwrap().data.is_empty());
+    }
+
+    #[test]
+    fn role_override_wins_over_the_handshake() {
+        let frames = vec![syn(), segment(0, tcp::flags::ACK, b"abc")];
+        let key = FiveTuple::from_frame(LINKTYPE_IPV4, &frames[0]).unwrap();
+        let client: SocketAddr = "10.0.0.1:40000".parse().unwrap();
+        let overrides = RoleOverrides::new().set_server(&key, client);
+        let flows =
+            tcp_stream_reassembly_with_roles(&mut capture(&frames), &Limits::default(), &overrides)
+                .unwrap();
+        let flow = &flows[&key.normalized()];
+        let roles = flow.roles.unwrap();
+        assert_eq!(roles.server, client);
+        assert_eq!(roles.evidence, RoleEvidence::Override);
+        assert_eq!(flow.server_stream(&key.normalized()).unwrap().data, b"abc");
+
+        let flows = tcp_stream_reassembly(&mut capture(&frames)).unwrap();
+        let roles = flows[&key.normalized()].roles.unwrap();
+        assert_eq!(roles.client, client);
+        assert_eq!(roles.evidence, RoleEvidence::Handshake);
+    }
+}
@@ -0,0 +1,734 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::pcap::{CapturedPacket, MAX_RECORD_LEN, invalid_data, read_full};
+
+// pcapng (draft-ietf-opsawg-pcapng). The file is a sequence of blocks:
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                          Block Type                           |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                      Block Total Length                       |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// /                  Block Body (padded to 32 bits)               /
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                      Block Total Length                       |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// A Section Header Block comes first, then one Interface Description
+// Block per interface, numbered from 0 in order of appearance, which the
+// Enhanced Packet Blocks refer to. Options are (code, length, value)
+// triples padded to 32 bits and ended by opt_endofopt. This writer uses
+// the host byte order and nanosecond timestamps; the reader takes either
+// byte order and any if_tsresol.
+//
+// The writer records its origin in an opt_comment of the Section Header
+// Block: `PROVENANCE_MARKER` on the first line, then `version=<crate
+// version>` and one `key=value` line per generation parameter.
+
+// Block types.
+pub const BLOCK_SECTION_HEADER: u32 = 0x0a0d_0d0a;
+pub const BLOCK_INTERFACE_DESCRIPTION: u32 = 0x0000_0001;
+pub const BLOCK_SIMPLE_PACKET: u32 = 0x0000_0003;
+pub const BLOCK_ENHANCED_PACKET: u32 = 0x0000_0006;
+/// Custom Block that may be copied to other files.
+pub const BLOCK_CUSTOM: u32 = 0x0000_0bad;
+
+/// Byte-order magic of the Section Header Block.
+pub const BYTE_ORDER_MAGIC: u32 = 0x1a2b_3c4d;
+
+// Option codes.
+const OPT_ENDOFOPT: u16 = 0;
+const OPT_COMMENT: u16 = 1;
+const SHB_USERAPPL: u16 = 4;
+const IF_NAME: u16 = 2;
+const IF_TSRESOL: u16 = 9;
+const EPB_FLAGS: u16 = 2;
+const EPB_DROPCOUNT: u16 = 4;
+const EPB_PACKETID: u16 = 5;
+
+/// if_tsresol value for nanoseconds (10^-9).
+const TSRESOL_NANOS: u8 = 9;
+
+/// if_tsresol value assumed when the option is missing (10^-6).
+const TSRESOL_DEFAULT: u8 = 6;
+
+/// First line of the section comment recording the provenance of a
+/// capture written by this crate.
+pub const PROVENANCE_MARKER: &str = "ethercrafter-provenance";
+
+/// Crate version and generation parameters of a capture written by this
+/// crate, recorded in its Section Header Block.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Provenance {
+    pub crate_version: String,
+    /// Parameters in the order they were added.
+    pub parameters: Vec<(String, String)>,
+}
+
+impl Default for Provenance {
+    fn default() -> Self {
+        Provenance::new()
+    }
+}
+
+impl Provenance {
+    /// Constructor to create the provenance of this version of the crate,
+    /// without parameters.
+    pub fn new() -> Self {
+        Provenance {
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            parameters: Vec::new(),
+        }
+    }
+
+    /// Adds a generation parameter, e.g. `("seed", "42")`.
+    pub fn add_parameter(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.parameters.push((key.into(), value.into()));
+        self
+    }
+
+    /// Value of the first parameter named `key`.
+    pub fn get_parameter(&self, key: &str) -> Option<&str> {
+        self.parameters
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Encodes the provenance as a section comment. Backslashes, line
+    /// breaks and `=` in keys and values are escaped with a backslash.
+    pub fn to_comment(&self) -> String {
+        let mut comment = String::from(PROVENANCE_MARKER);
+        let version = ("version", self.crate_version.as_str());
+        let parameters = self
+            .parameters
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()));
+        for (key, value) in [version].into_iter().chain(parameters) {
+            comment.push('\n');
+            escape_into(&mut comment, key);
+            comment.push('=');
+            escape_into(&mut comment, value);
+        }
+        comment
+    }
+
+    /// Decodes a section comment written by `to_comment`; `None` for any
+    /// other comment.
+    pub fn from_comment(comment: &str) -> Option<Self> {
+        let mut lines = comment.split('\n');
+        if lines.next()? != PROVENANCE_MARKER {
+            return None;
+        }
+        let mut pairs = lines.map(split_pair);
+        let (key, crate_version) = pairs.next()??;
+        if key != "version" {
+            return None;
+        }
+        Some(Provenance {
+            crate_version,
+            parameters: pairs.collect::<Option<_>>()?,
+        })
+    }
+}
+
+fn escape_into(escaped: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '=' => escaped.push_str("\\="),
+            c => escaped.push(c),
+        }
+    }
+}
+
+/// Splits a `key=value` line at its first unescaped `=`, unescaping both
+/// sides.
+fn split_pair(line: &str) -> Option<(String, String)> {
+    let mut key = String::new();
+    let mut value = String::new();
+    let mut in_value = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        let current = if in_value { &mut value } else { &mut key };
+        match c {
+            '\\' => current.push(match chars.next()? {
+                'n' => '\n',
+                other => other,
+            }),
+            '=' if !in_value => in_value = true,
+            c => current.push(c),
+        }
+    }
+    in_value.then_some((key, value))
+}
+
+/// Option of an Enhanced Packet Block.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PcapNgOption {
+    /// opt_comment, shown by Wireshark as the packet comment.
+    Comment(String),
+    /// epb_flags: direction, reception type, FCS length and link errors.
+    Flags(u32),
+    /// epb_dropcount: packets lost between this one and the previous one.
+    DropCount(u64),
+    /// epb_packetid: identifies the same packet seen on several interfaces.
+    PacketId(u64),
+    /// Any other option, with its value unpadded.
+    Raw { code: u16, value: Vec<u8> },
+}
+
+impl PcapNgOption {
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        match self {
+            PcapNgOption::Comment(comment) => {
+                serialize_option_into(bytes, OPT_COMMENT, comment.as_bytes())
+            }
+            PcapNgOption::Flags(flags) => {
+                serialize_option_into(bytes, EPB_FLAGS, &flags.to_ne_bytes())
+            }
+            PcapNgOption::DropCount(count) => {
+                serialize_option_into(bytes, EPB_DROPCOUNT, &count.to_ne_bytes())
+            }
+            PcapNgOption::PacketId(id) => {
+                serialize_option_into(bytes, EPB_PACKETID, &id.to_ne_bytes())
+            }
+            PcapNgOption::Raw { code, value } => serialize_option_into(bytes, *code, value),
+        }
+    }
+}
+
+/// Index of an interface within the section, as returned by
+/// `Writer::add_interface`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct InterfaceId(pub u32);
+
+/// Interface declared in the section.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interface {
+    pub name: String,
+    pub link_type: u16,
+    /// Longest data kept per packet; 0 for no limit.
+    pub snaplen: u32,
+}
+
+/// Streaming writer of pcapng files with any number of interfaces.
+#[derive(Debug)]
+pub struct Writer<W: Write> {
+    inner: W,
+    interfaces: Vec<Interface>,
+    /// Private Enterprise Number of the Custom Blocks.
+    enterprise_number: u32,
+}
+
+impl Writer<BufWriter<File>> {
+    /// Creates the capture file at `path` and writes its Section Header
+    /// Block.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Writer::new(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W: Write> Writer<W> {
+    /// Writes a Section Header Block of unknown length to `inner`, naming
+    /// this crate as the writing application and recording its version as
+    /// the provenance of the capture.
+    pub fn new(inner: W) -> io::Result<Self> {
+        Writer::with_provenance(inner, &Provenance::new())
+    }
+
+    /// Same as `new`, recording `provenance` in a comment of the Section
+    /// Header Block, which `Reader::provenance` returns.
+    pub fn with_provenance(mut inner: W, provenance: &Provenance) -> io::Result<Self> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&BYTE_ORDER_MAGIC.to_ne_bytes());
+        body.extend_from_slice(&1u16.to_ne_bytes());
+        body.extend_from_slice(&0u16.to_ne_bytes());
+        body.extend_from_slice(&(-1i64).to_ne_bytes());
+        let application = concat!("ethercrafter ", env!("CARGO_PKG_VERSION"));
+        serialize_option_into(&mut body, SHB_USERAPPL, application.as_bytes());
+        serialize_option_into(&mut body, OPT_COMMENT, provenance.to_comment().as_bytes());
+        serialize_option_into(&mut body, OPT_ENDOFOPT, &[]);
+        write_block(&mut inner, BLOCK_SECTION_HEADER, &body)?;
+        Ok(Writer {
+            inner,
+            interfaces: Vec::new(),
+            enterprise_number: 0,
+        })
+    }
+
+    /// Sets the Private Enterprise Number (IANA) identifying the Custom
+    /// Blocks written by `write_comment`. Defaults to 0.
+    pub fn set_enterprise_number(mut self, enterprise_number: u32) -> Self {
+        self.enterprise_number = enterprise_number;
+        self
+    }
+
+    /// Interfaces declared so far, in `InterfaceId` order.
+    pub fn interfaces(&self) -> &[Interface] {
+        &self.interfaces
+    }
+
+    /// Writes an Interface Description Block and returns the identifier
+    /// packets of that interface are written with. A `snaplen` of 0 keeps
+    /// packets whole.
+    pub fn add_interface(
+        &mut self,
+        name: &str,
+        link_type: u16,
+        snaplen: u32,
+    ) -> io::Result<InterfaceId> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&link_type.to_ne_bytes());
+        body.extend_from_slice(&0u16.to_ne_bytes());
+        body.extend_from_slice(&snaplen.to_ne_bytes());
+        serialize_option_into(&mut body, IF_NAME, name.as_bytes());
+        serialize_option_into(&mut body, IF_TSRESOL, &[TSRESOL_NANOS]);
+        serialize_option_into(&mut body, OPT_ENDOFOPT, &[]);
+        write_block(&mut self.inner, BLOCK_INTERFACE_DESCRIPTION, &body)?;
+        self.interfaces.push(Interface {
+            name: name.to_string(),
+            link_type,
+            snaplen,
+        });
+        Ok(InterfaceId(self.interfaces.len() as u32 - 1))
+    }
+
+    /// Writes `data` captured on `iface` at `ts` (since the Unix epoch) as
+    /// an Enhanced Packet Block. Data beyond the snaplen of the interface
+    /// is cut off; the original length is kept.
+    pub fn write_packet(
+        &mut self,
+        iface: InterfaceId,
+        data: &[u8],
+        ts: Duration,
+        options: &[PcapNgOption],
+    ) -> io::Result<()> {
+        let interface = self.interfaces.get(iface.0 as usize).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("interface {} was not added", iface.0),
+            )
+        })?;
+        let captured = match interface.snaplen {
+            0 => data,
+            snaplen => &data[..data.len().min(snaplen as usize)],
+        };
+        let timestamp = ts.as_nanos() as u64;
+        let mut body = Vec::with_capacity(20 + captured.len() + 3);
+        body.extend_from_slice(&iface.0.to_ne_bytes());
+        body.extend_from_slice(&((timestamp >> 32) as u32).to_ne_bytes());
+        body.extend_from_slice(&(timestamp as u32).to_ne_bytes());
+        body.extend_from_slice(&(captured.len() as u32).to_ne_bytes());
+        body.extend_from_slice(&(data.len() as u32).to_ne_bytes());
+        body.extend_from_slice(captured);
+        pad_into(&mut body);
+        if !options.is_empty() {
+            for option in options {
+                option.serialize_into(&mut body);
+            }
+            serialize_option_into(&mut body, OPT_ENDOFOPT, &[]);
+        }
+        write_block(&mut self.inner, BLOCK_ENHANCED_PACKET, &body)
+    }
+
+    /// Writes `comment` in a Custom Block, which readers that do not know
+    /// the enterprise number skip.
+    pub fn write_comment(&mut self, comment: &str) -> io::Result<()> {
+        let mut body = Vec::with_capacity(4 + comment.len() + 3);
+        body.extend_from_slice(&self.enterprise_number.to_ne_bytes());
+        body.extend_from_slice(comment.as_bytes());
+        pad_into(&mut body);
+        write_block(&mut self.inner, BLOCK_CUSTOM, &body)
+    }
+
+    /// Flushes the underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    /// Returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+/// Streaming reader of pcapng files, in either byte order. A new Section
+/// Header Block starts a new section, with its own interfaces and
+/// provenance.
+#[derive(Debug)]
+pub struct Reader<R> {
+    inner: R,
+    big_endian: bool,
+    application: Option<String>,
+    provenance: Option<Provenance>,
+    interfaces: Vec<Interface>,
+    /// Timestamp units per second of each interface.
+    resolutions: Vec<u128>,
+}
+
+impl Reader<BufReader<File>> {
+    /// Opens the capture file at `path`.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Reader::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> Reader<R> {
+    /// Reads the Section Header Block from `inner` and returns a reader
+    /// positioned on the block after it.
+    pub fn new(inner: R) -> io::Result<Self> {
+        let mut reader = Reader {
+            inner,
+            big_endian: cfg!(target_endian = "big"),
+            application: None,
+            provenance: None,
+            interfaces: Vec::new(),
+            resolutions: Vec::new(),
+        };
+        match reader.read_block()? {
+            Some((BLOCK_SECTION_HEADER, body)) => reader.start_section(&body)?,
+            _ => return Err(invalid_data("missing section header block")),
+        }
+        Ok(reader)
+    }
+
+    /// shb_userappl of the current section.
+    pub fn application(&self) -> Option<&str> {
+        self.application.as_deref()
+    }
+
+    /// Provenance recorded by this crate in the current section, if it
+    /// wrote the capture.
+    pub fn provenance(&self) -> Option<&Provenance> {
+        self.provenance.as_ref()
+    }
+
+    /// Interfaces of the current section read so far, in `InterfaceId`
+    /// order.
+    pub fn interfaces(&self) -> &[Interface] {
+        &self.interfaces
+    }
+
+    /// Reads up to the next Enhanced or Simple Packet Block, returning the
+    /// packet with its interface; `Ok(None)` at a clean end of file. Blocks
+    /// of other types are skipped.
+    pub fn next_packet(&mut self) -> io::Result<Option<(InterfaceId, CapturedPacket)>> {
+        loop {
+            let Some((block_type, body)) = self.read_block()? else {
+                return Ok(None);
+            };
+            match block_type {
+                BLOCK_SECTION_HEADER => self.start_section(&body)?,
+                BLOCK_INTERFACE_DESCRIPTION => self.add_interface(&body)?,
+                BLOCK_ENHANCED_PACKET => return self.enhanced_packet(&body).map(Some),
+                BLOCK_SIMPLE_PACKET => return self.simple_packet(&body).map(Some),
+                _ => {}
+            }
+        }
+    }
+
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Reads a block, returning its type and body; `Ok(None)` at a clean
+    /// end of file. The byte order of a Section Header Block is taken
+    /// from its magic before its length is read.
+    fn read_block(&mut self) -> io::Result<Option<(u32, Vec<u8>)>> {
+        let mut header = [0u8; 12];
+        let read = read_full(&mut self.inner, &mut header[..8])?;
+        if read == 0 {
+            return Ok(None);
+        }
+        if read < 8 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        // The section header block type reads the same in both orders.
+        let block_type = self.u32_at(&header, 0);
+        let mut prefix = 0;
+        if block_type == BLOCK_SECTION_HEADER {
+            self.inner.read_exact(&mut header[8..12])?;
+            self.big_endian = match u32::from_be_bytes(header[8..12].try_into().unwrap()) {
+                BYTE_ORDER_MAGIC => true,
+                magic if magic.swap_bytes() == BYTE_ORDER_MAGIC => false,
+                _ => return Err(invalid_data("bad byte-order magic")),
+            };
+            prefix = 4;
+        }
+        let total_len = self.u32_at(&header, 4);
+        if !total_len.is_multiple_of(4) || total_len < 12 + prefix as u32 {
+            return Err(invalid_data("bad block length"));
+        }
+        if total_len - 12 > MAX_RECORD_LEN {
+            return Err(invalid_data("block length exceeds the maximum"));
+        }
+        let mut body = vec![0u8; total_len as usize - 8];
+        body[..prefix].copy_from_slice(&header[8..8 + prefix]);
+        self.inner.read_exact(&mut body[prefix..])?;
+        let trailer = body.split_off(body.len() - 4);
+        if self.u32_at(&trailer, 0) != total_len {
+            return Err(invalid_data("block lengths differ"));
+        }
+        Ok(Some((block_type, body)))
+    }
+
+    fn start_section(&mut self, body: &[u8]) -> io::Result<()> {
+        if body.len() < 16 {
+            return Err(invalid_data("section header block too short"));
+        }
+        self.application = None;
+        self.provenance = None;
+        self.interfaces.clear();
+        self.resolutions.clear();
+        for (code, value) in self.options(&body[16..])? {
+            match code {
+                SHB_USERAPPL => self.application = Some(text(value)),
+                OPT_COMMENT => {
+                    let comment = text(value);
+                    if let Some(provenance) = Provenance::from_comment(&comment) {
+                        self.provenance = Some(provenance);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn add_interface(&mut self, body: &[u8]) -> io::Result<()> {
+        if body.len() < 8 {
+            return Err(invalid_data("interface description block too short"));
+        }
+        let mut interface = Interface {
+            name: String::new(),
+            link_type: self.u16_at(body, 0),
+            snaplen: self.u32_at(body, 4),
+        };
+        let mut tsresol = TSRESOL_DEFAULT;
+        for (code, value) in self.options(&body[8..])? {
+            match (code, value) {
+                (IF_NAME, _) => interface.name = text(value),
+                (IF_TSRESOL, [resolution]) => tsresol = *resolution,
+                _ => {}
+            }
+        }
+        // The high bit selects a power of two instead of a power of ten.
+        let exponent = (tsresol & 0x7f) as u32;
+        let base: u128 = if tsresol & 0x80 != 0 { 2 } else { 10 };
+        let resolution = base
+            .checked_pow(exponent)
+            .filter(|&resolution| resolution <= u64::MAX as u128)
+            .ok_or_else(|| invalid_data("unsupported if_tsresol"))?;
+        self.interfaces.push(interface);
+        self.resolutions.push(resolution);
+        Ok(())
+    }
+
+    fn enhanced_packet(&self, body: &[u8]) -> io::Result<(InterfaceId, CapturedPacket)> {
+        if body.len() < 20 {
+            return Err(invalid_data("enhanced packet block too short"));
+        }
+        let iface = self.u32_at(body, 0);
+        let resolution = *self
+            .resolutions
+            .get(iface as usize)
+            .ok_or_else(|| invalid_data("packet of an undeclared interface"))?;
+        let ticks = (self.u32_at(body, 4) as u128) << 32 | self.u32_at(body, 8) as u128;
+        let captured_len = self.u32_at(body, 12) as usize;
+        let original_len = self.u32_at(body, 16);
+        let data = body
+            .get(20..20 + captured_len)
+            .ok_or_else(|| invalid_data("packet data beyond the block"))?;
+        let nanos = ticks * 1_000_000_000 / resolution;
+        let packet = CapturedPacket {
+            timestamp: Duration::from_nanos(nanos.min(u64::MAX as u128) as u64),
+            original_len,
+            data: data.to_vec(),
+        };
+        Ok((InterfaceId(iface), packet))
+    }
+
+    /// A Simple Packet Block belongs to the first interface and has no
+    /// timestamp.
+    fn simple_packet(&self, body: &[u8]) -> io::Result<(InterfaceId, CapturedPacket)> {
+        if body.len() < 4 {
+            return Err(invalid_data("simple packet block too short"));
+        }
+        let interface = self
+            .interfaces
+            .first()
+            .ok_or_else(|| invalid_data("packet of an undeclared interface"))?;
+        let original_len = self.u32_at(body, 0);
+        let captured_len = match interface.snaplen {
+            0 => original_len,
+            snaplen => original_len.min(snaplen),
+        } as usize;
+        let data = body
+            .get(4..4 + captured_len)
+            .ok_or_else(|| invalid_data("packet data beyond the block"))?;
+        let packet = CapturedPacket {
+            timestamp: Duration::ZERO,
+            original_len,
+            data: data.to_vec(),
+        };
+        Ok((InterfaceId(0), packet))
+    }
+
+    /// Splits an option list into codes and unpadded values, up to
+    /// opt_endofopt or the end of `bytes`.
+    fn options<'a>(&self, mut bytes: &'a [u8]) -> io::Result<Vec<(u16, &'a [u8])>> {
+        let mut options = Vec::new();
+        while bytes.len() >= 4 {
+            let code = self.u16_at(bytes, 0);
+            let len = self.u16_at(bytes, 2) as usize;
+            if code == OPT_ENDOFOPT {
+                break;
+            }
+            let value = bytes
+                .get(4..4 + len)
+                .ok_or_else(|| invalid_data("option beyond the block"))?;
+            options.push((code, value));
+            bytes = bytes
+                .get((4 + len).next_multiple_of(4)..)
+                .unwrap_or_default();
+        }
+        Ok(options)
+    }
+
+    fn u16_at(&self, bytes: &[u8], offset: usize) -> u16 {
+        let raw = [bytes[offset], bytes[offset + 1]];
+        if self.big_endian {
+            u16::from_be_bytes(raw)
+        } else {
+            u16::from_le_bytes(raw)
+        }
+    }
+
+    fn u32_at(&self, bytes: &[u8], offset: usize) -> u32 {
+        let raw = bytes[offset..offset + 4].try_into().unwrap();
+        if self.big_endian {
+            u32::from_be_bytes(raw)
+        } else {
+            u32::from_le_bytes(raw)
+        }
+    }
+}
+
+/// Text of a string option, which is not NUL-terminated but may be padded
+/// with NULs by some writers.
+fn text(value: &[u8]) -> String {
+    String::from_utf8_lossy(value)
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+/// Pads `bytes` with zeros to a multiple of 4 bytes.
+fn pad_into(bytes: &mut Vec<u8>) {
+    bytes.resize(bytes.len().next_multiple_of(4), 0);
+}
+
+fn serialize_option_into(bytes: &mut Vec<u8>, code: u16, value: &[u8]) {
+    bytes.extend_from_slice(&code.to_ne_bytes());
+    bytes.extend_from_slice(&(value.len() as u16).to_ne_bytes());
+    bytes.extend_from_slice(value);
+    pad_into(bytes);
+}
+
+/// Writes a block around `body`, which must be padded to 32 bits.
+fn write_block(inner: &mut impl Write, block_type: u32, body: &[u8]) -> io::Result<()> {
+    let total_len = (body.len() + 12) as u32;
+    inner.write_all(&block_type.to_ne_bytes())?;
+    inner.write_all(&total_len.to_ne_bytes())?;
+    inner.write_all(body)?;
+    inner.write_all(&total_len.to_ne_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn provenance_survives_escaping() {
+        let provenance = Provenance::new()
+            .add_parameter("seed", "42")
+            .add_parameter("a=b", "line\nbreak \\ and = sign");
+        let comment = provenance.to_comment();
+        assert!(comment.starts_with("ethercrafter-provenance\nversion="));
+        assert_eq!(Provenance::from_comment(&comment), Some(provenance));
+        assert_eq!(Provenance::from_comment("captured on eth0"), None);
+    }
+
+    #[test]
+    fn reader_recovers_what_the_writer_wrote() {
+        let provenance = Provenance::new().add_parameter("scenario", "syn-flood");
+        let mut writer = Writer::with_provenance(Vec::new(), &provenance).unwrap();
+        let eth = writer.add_interface("eth0", 1, 0).unwrap();
+        let raw = writer.add_interface("tun0", 101, 4).unwrap();
+        let ts = Duration::new(1_700_000_000, 123_456_789);
+        writer.write_comment("skipped").unwrap();
+        writer.write_packet(eth, &[1, 2, 3], ts, &[]).unwrap();
+        let comment = [PcapNgOption::Comment("cut".to_string())];
+        writer.write_packet(raw, &[4; 10], ts, &comment).unwrap();
+
+        let mut reader = Reader::new(Cursor::new(writer.into_inner())).unwrap();
+        assert_eq!(reader.provenance(), Some(&provenance));
+        assert_eq!(
+            reader.provenance().unwrap().get_parameter("scenario"),
+            Some("syn-flood")
+        );
+        assert!(reader.application().unwrap().starts_with("ethercrafter "));
+        let (iface, packet) = reader.next_packet().unwrap().unwrap();
+        assert_eq!(
+            (iface, packet.timestamp, packet.data),
+            (eth, ts, vec![1, 2, 3])
+        );
+        let (iface, packet) = reader.next_packet().unwrap().unwrap();
+        assert_eq!(
+            (iface, packet.original_len, packet.data),
+            (raw, 10, vec![4; 4])
+        );
+        assert_eq!(reader.interfaces()[1].name, "tun0");
+        assert_eq!(reader.next_packet().unwrap(), None);
+    }
+
+    #[test]
+    fn reads_big_endian_microsecond_captures() {
+        let block = |block_type: u32, body: &[u8]| {
+            let total_len = (body.len() + 12) as u32;
+            let mut bytes = block_type.to_be_bytes().to_vec();
+            bytes.extend_from_slice(&total_len.to_be_bytes());
+            bytes.extend_from_slice(body);
+            bytes.extend_from_slice(&total_len.to_be_bytes());
+            bytes
+        };
+        let mut shb = BYTE_ORDER_MAGIC.to_be_bytes().to_vec();
+        shb.extend_from_slice(&[0, 1, 0, 0]);
+        shb.extend_from_slice(&(-1i64).to_be_bytes());
+        // Link type 1, no options: microsecond timestamps.
+        let idb = [0, 1, 0, 0, 0, 0, 0, 0];
+        let mut epb = vec![0; 4];
+        epb.extend_from_slice(&0u32.to_be_bytes());
+        epb.extend_from_slice(&2_500_000u32.to_be_bytes());
+        epb.extend_from_slice(&4u32.to_be_bytes());
+        epb.extend_from_slice(&4u32.to_be_bytes());
+        epb.extend_from_slice(&[9; 4]);
+        let mut capture = block(BLOCK_SECTION_HEADER, &shb);
+        capture.extend(block(BLOCK_INTERFACE_DESCRIPTION, &idb));
+        capture.extend(block(BLOCK_ENHANCED_PACKET, &epb));
+
+        let mut reader = Reader::new(Cursor::new(capture)).unwrap();
+        assert_eq!(reader.provenance(), None);
+        let (_, packet) = reader.next_packet().unwrap().unwrap();
+        assert_eq!(packet.timestamp, Duration::from_millis(2500));
+        assert_eq!(reader.interfaces()[0].link_type, 1);
+    }
+}
@@ -0,0 +1,318 @@
+use std::net::Ipv4Addr;
+
+use crate::util::{Ipv4Cidr, ParseError, checksum, ensure_len, read_ipv4};
+
+// PIM header (RFC 7761 section 4.9)
+//
+//  0                   1                   2                   3
+//  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |PIM Ver| Type  |   Reserved    |           Checksum            |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// Bootstrap message body (RFC 5059 section 4.1)
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |         Fragment Tag          | Hash Mask Len | BSR Priority  |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |             BSR Address (Encoded-Unicast format)              |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |         Group Address 1 (Encoded-Group format)                |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// | RP Count 1    | Frag RP Cnt 1 |         Reserved              |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |             RP Address 1 (Encoded-Unicast format)             |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |          RP1 Holdtime         | RP1 Priority  |   Reserved    |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                              ...                              |
+//
+// Encoded-Unicast: family (1 = IPv4), encoding type, address.
+// Encoded-Group: family, encoding type, B/Z flags, mask length, group.
+
+/// IP protocol number of PIM.
+pub const IP_PROTOCOL: u8 = 103;
+
+/// Group all PIM routers listen on (224.0.0.13).
+pub const ALL_PIM_ROUTERS: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 13);
+
+// Message types.
+pub const TYPE_HELLO: u8 = 0;
+pub const TYPE_REGISTER: u8 = 1;
+pub const TYPE_REGISTER_STOP: u8 = 2;
+pub const TYPE_JOIN_PRUNE: u8 = 3;
+pub const TYPE_BOOTSTRAP: u8 = 4;
+pub const TYPE_ASSERT: u8 = 5;
+pub const TYPE_CANDIDATE_RP_ADVERTISEMENT: u8 = 8;
+
+/// Address family number of IPv4 in encoded addresses.
+const FAMILY_IPV4: u8 = 1;
+
+/// Header PIM, followed by the message body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pim {
+    pub version: u8,
+    pub pim_type: u8,
+    pub reserved: u8,
+    pub checksum: u16,
+    pub body: Vec<u8>,
+}
+
+impl Pim {
+    /// Builds a version 2 message with its checksum computed.
+    pub fn new(pim_type: u8, body: Vec<u8>) -> Self {
+        Pim {
+            version: 2,
+            pim_type,
+            reserved: 0,
+            checksum: 0,
+            body,
+        }
+        .with_checksum()
+    }
+
+    /// Builds a Bootstrap message.
+    pub fn bootstrap(bootstrap: &Bootstrap) -> Self {
+        Pim::new(TYPE_BOOTSTRAP, bootstrap.to_bytes())
+    }
+
+    /// Parses the body of a Bootstrap message.
+    pub fn parse_bootstrap(&self) -> Result<Bootstrap, ParseError> {
+        if self.pim_type != TYPE_BOOTSTRAP {
+            return Err(ParseError::InvalidField("type"));
+        }
+        Bootstrap::from_bytes(&self.body)
+    }
+
+    /// Checksum over the whole message (with the checksum field zeroed).
+    pub fn compute_checksum(&self) -> u16 {
+        let mut bytes = self.to_bytes();
+        bytes[2..4].fill(0);
+        checksum(&bytes)
+    }
+
+    /// Returns the message with its checksum computed.
+    pub fn with_checksum(mut self) -> Self {
+        self.checksum = self.compute_checksum();
+        self
+    }
+
+    /// Serializes the header and body.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(4 + self.body.len());
+        bytes.push((self.version & 0x0f) << 4 | (self.pim_type & 0x0f));
+        bytes.push(self.reserved);
+        bytes.extend_from_slice(&self.checksum.to_be_bytes());
+        bytes.extend_from_slice(&self.body);
+        bytes
+    }
+
+    /// Parses a PIM message.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, 4)?;
+        Ok(Pim {
+            version: buf[0] >> 4,
+            pim_type: buf[0] & 0x0f,
+            reserved: buf[1],
+            checksum: u16::from_be_bytes([buf[2], buf[3]]),
+            body: buf[4..].to_vec(),
+        })
+    }
+}
+
+/// A candidate RP listed in a Bootstrap message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BootstrapRp {
+    pub address: Ipv4Addr,
+    pub holdtime: u16,
+    pub priority: u8,
+}
+
+/// A group prefix of a Bootstrap message with its candidate RPs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BootstrapGroup {
+    pub group: Ipv4Cidr,
+    pub rps: Vec<BootstrapRp>,
+}
+
+/// Body of a Bootstrap message (unfragmented: each group carries all of
+/// its RPs).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bootstrap {
+    pub fragment_tag: u16,
+    pub hash_mask_len: u8,
+    pub bsr_priority: u8,
+    pub bsr_address: Ipv4Addr,
+    pub groups: Vec<BootstrapGroup>,
+}
+
+impl Bootstrap {
+    /// Serializes the message body.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&self.fragment_tag.to_be_bytes());
+        bytes.push(self.hash_mask_len);
+        bytes.push(self.bsr_priority);
+        bytes.extend_from_slice(&[FAMILY_IPV4, 0]);
+        bytes.extend_from_slice(&self.bsr_address.octets());
+        for group in &self.groups {
+            bytes.extend_from_slice(&[FAMILY_IPV4, 0, 0, group.group.prefix_len]);
+            bytes.extend_from_slice(&group.group.address.octets());
+            let count = group.rps.len() as u8;
+            bytes.extend_from_slice(&[count, count, 0, 0]);
+            for rp in &group.rps {
+                bytes.extend_from_slice(&[FAMILY_IPV4, 0]);
+                bytes.extend_from_slice(&rp.address.octets());
+                bytes.extend_from_slice(&rp.holdtime.to_be_bytes());
+                bytes.extend_from_slice(&[rp.priority, 0]);
+            }
+        }
+        bytes
+    }
+
+    /// Parses a Bootstrap body carrying IPv4 addresses.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, 10)?;
+        if buf[4] != FAMILY_IPV4 {
+            return Err(ParseError::InvalidField("address family"));
+        }
+        let mut bootstrap = Bootstrap {
+            fragment_tag: u16::from_be_bytes([buf[0], buf[1]]),
+            hash_mask_len: buf[2],
+            bsr_priority: buf[3],
+            bsr_address: read_ipv4(buf, 6),
+            groups: Vec::new(),
+        };
+        let mut offset = 10;
+        while offset < buf.len() {
+            ensure_len(buf, offset + 12)?;
+            if buf[offset] != FAMILY_IPV4 {
+                return Err(ParseError::InvalidField("address family"));
+            }
+            let group = Ipv4Cidr::new(read_ipv4(buf, offset + 4), buf[offset + 3])?;
+            let fragment_rp_count = buf[offset + 9] as usize;
+            offset += 12;
+            ensure_len(buf, offset + fragment_rp_count * 10)?;
+            let rps = (0..fragment_rp_count)
+                .map(|index| {
+                    let rp = offset + index * 10;
+                    BootstrapRp {
+                        address: read_ipv4(buf, rp + 2),
+                        holdtime: u16::from_be_bytes([buf[rp + 6], buf[rp + 7]]),
+                        priority: buf[rp + 8],
+                    }
+                })
+                .collect();
+            offset += fragment_rp_count * 10;
+            bootstrap.groups.push(BootstrapGroup { group, rps });
+        }
+        Ok(bootstrap)
+    }
+}
+
+/// Group-to-RP mapping learned from the bootstrap mechanism.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RpEntry {
+    pub rp_address: Ipv4Addr,
+    pub group_prefix: Ipv4Cidr,
+    /// Lower values are preferred.
+    pub priority: u8,
+    pub holdtime: u16,
+}
+
+/// RP set of a PIM-SM router.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RpDatabase {
+    pub entries: Vec<RpEntry>,
+    /// Address and priority of the elected BSR.
+    pub bsr: Option<(Ipv4Addr, u8)>,
+}
+
+impl RpDatabase {
+    /// Constructor to create an empty database.
+    pub fn new() -> Self {
+        RpDatabase::default()
+    }
+
+    /// Adds a group-to-RP mapping.
+    pub fn add(&mut self, entry: RpEntry) {
+        self.entries.push(entry);
+    }
+
+    /// Returns the RP of `group`: the entry with the longest matching
+    /// prefix, then the best (lowest) priority, then the highest address.
+    /// Entries with a zero holdtime are ignored.
+    pub fn lookup_rp(&self, group: Ipv4Addr) -> Option<Ipv4Addr> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.holdtime > 0 && entry.group_prefix.contains(group))
+            .max_by_key(|entry| {
+                (
+                    entry.group_prefix.prefix_len,
+                    u8::MAX - entry.priority,
+                    entry.rp_address,
+                )
+            })
+            .map(|entry| entry.rp_address)
+    }
+
+    /// Processes a Bootstrap message. It is accepted if it comes from the
+    /// current BSR or from a preferred one (higher priority, then higher
+    /// address), in which case its RP set replaces the database. Returns
+    /// true if the message was accepted.
+    pub fn process_bootstrap(&mut self, bsr_msg: &Pim) -> bool {
+        let Ok(bootstrap) = bsr_msg.parse_bootstrap() else {
+            return false;
+        };
+        let candidate = (bootstrap.bsr_address, bootstrap.bsr_priority);
+        let accepted = match self.bsr {
+            None => true,
+            Some((address, priority)) => {
+                address == candidate.0 || (candidate.1, candidate.0) > (priority, address)
+            }
+        };
+        if !accepted {
+            return false;
+        }
+        self.bsr = Some(candidate);
+        self.entries = bootstrap
+            .groups
+            .iter()
+            .flat_map(|group| {
+                group.rps.iter().map(|rp| RpEntry {
+                    rp_address: rp.address,
+                    group_prefix: group.group,
+                    priority: rp.priority,
+                    holdtime: rp.holdtime,
+                })
+            })
+            .collect();
+        true
+    }
+
+    /// Builds a Bootstrap message advertising the database as BSR `bsr_ip`.
+    pub fn generate_bootstrap(&self, bsr_ip: Ipv4Addr, priority: u8) -> Pim {
+        let mut groups: Vec<BootstrapGroup> = Vec::new();
+        for entry in &self.entries {
+            let rp = BootstrapRp {
+                address: entry.rp_address,
+                holdtime: entry.holdtime,
+                priority: entry.priority,
+            };
+            match groups.iter_mut().find(|g| g.group == entry.group_prefix) {
+                Some(group) => group.rps.push(rp),
+                None => groups.push(BootstrapGroup {
+                    group: entry.group_prefix,
+                    rps: vec![rp],
+                }),
+            }
+        }
+        Pim::bootstrap(&Bootstrap {
+            fragment_tag: 0,
+            hash_mask_len: 30,
+            bsr_priority: priority,
+            bsr_address: bsr_ip,
+            groups,
+        })
+    }
+}
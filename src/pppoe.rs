@@ -0,0 +1,300 @@
+use crate::ethernet::{Ethernet, MacAddr};
+use crate::util::{ParseError, ensure_len};
+
+// PPPoE header (RFC 2516 section 4)
+//
+//  0                   1                   2                   3
+//  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |  VER  | TYPE  |      CODE     |          SESSION_ID           |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |            LENGTH             |           payload             ~
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// Discovery packets carry tags:
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |          TAG_TYPE             |        TAG_LENGTH             |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |          TAG_VALUE ...                                        ~
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// and session packets a PPP frame, reduced to its protocol field
+// (RFC 1661 section 2) followed by the information.
+
+// EtherTypes.
+pub const ETHERTYPE_DISCOVERY: u16 = 0x8863;
+pub const ETHERTYPE_SESSION: u16 = 0x8864;
+
+/// Length of the PPPoE header in bytes.
+pub const HEADER_LEN: usize = 6;
+
+// Codes.
+pub const CODE_SESSION: u8 = 0x00;
+pub const CODE_PADO: u8 = 0x07;
+pub const CODE_PADI: u8 = 0x09;
+pub const CODE_PADR: u8 = 0x19;
+pub const CODE_PADS: u8 = 0x65;
+pub const CODE_PADT: u8 = 0xa7;
+
+// Tag types.
+pub const TAG_END_OF_LIST: u16 = 0x0000;
+pub const TAG_SERVICE_NAME: u16 = 0x0101;
+pub const TAG_AC_NAME: u16 = 0x0102;
+pub const TAG_HOST_UNIQ: u16 = 0x0103;
+pub const TAG_AC_COOKIE: u16 = 0x0104;
+pub const TAG_RELAY_SESSION_ID: u16 = 0x0110;
+
+// PPP protocols.
+pub const PPP_IPV4: u16 = 0x0021;
+pub const PPP_IPV6: u16 = 0x0057;
+pub const PPP_IPCP: u16 = 0x8021;
+pub const PPP_LCP: u16 = 0xc021;
+
+/// A discovery tag.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PppoeTag {
+    pub tag_type: u16,
+    pub value: Vec<u8>,
+}
+
+impl PppoeTag {
+    /// Constructor to create a new tag.
+    pub fn new(tag_type: u16, value: Vec<u8>) -> Self {
+        PppoeTag { tag_type, value }
+    }
+
+    // --- SERIALIZATION ---
+
+    pub fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.tag_type.to_be_bytes());
+        bytes.extend_from_slice(&(self.value.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(&self.value);
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> Result<(Self, usize), ParseError> {
+        ensure_len(buf, 4)?;
+        let len = 4 + u16::from_be_bytes([buf[2], buf[3]]) as usize;
+        ensure_len(buf, len)?;
+        let tag = PppoeTag {
+            tag_type: u16::from_be_bytes([buf[0], buf[1]]),
+            value: buf[4..len].to_vec(),
+        };
+        Ok((tag, len))
+    }
+}
+
+/// PPP frame as carried in a PPPoE session: protocol and information.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ppp {
+    pub protocol: u16,
+    pub payload: Vec<u8>,
+}
+
+impl Ppp {
+    /// Constructor to create a new PPP frame.
+    pub fn new(protocol: u16, payload: Vec<u8>) -> Self {
+        Ppp { protocol, payload }
+    }
+
+    // --- SERIALIZATION ---
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(2 + self.payload.len());
+        bytes.extend_from_slice(&self.protocol.to_be_bytes());
+        bytes.extend_from_slice(&self.payload);
+        bytes
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, 2)?;
+        Ok(Ppp {
+            protocol: u16::from_be_bytes([buf[0], buf[1]]),
+            payload: buf[2..].to_vec(),
+        })
+    }
+}
+
+/// PPPoE packet of the discovery or session stage.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pppoe {
+    pub version: u8,
+    pub type_: u8,
+    pub code: u8,
+    pub session_id: u16,
+    pub length: u16,
+    /// Tags of a discovery packet or PPP frame of a session packet.
+    pub payload: Vec<u8>,
+}
+
+impl Pppoe {
+    /// Constructor to create a version 1, type 1 packet with the length
+    /// filled in.
+    pub fn new(code: u8, session_id: u16, payload: Vec<u8>) -> Self {
+        Pppoe {
+            version: 1,
+            type_: 1,
+            code,
+            session_id,
+            length: payload.len() as u16,
+            payload,
+        }
+    }
+
+    /// Discovery packet carrying `tags`.
+    pub fn discovery(code: u8, session_id: u16, tags: &[PppoeTag]) -> Self {
+        let mut payload = Vec::new();
+        for tag in tags {
+            tag.serialize_into(&mut payload);
+        }
+        Pppoe::new(code, session_id, payload)
+    }
+
+    /// PADI broadcast by a host looking for `service_name` (empty for any
+    /// service), with an optional Host-Uniq tag.
+    pub fn padi(service_name: &str, host_uniq: Option<&[u8]>) -> Self {
+        let mut tags = vec![PppoeTag::new(
+            TAG_SERVICE_NAME,
+            service_name.as_bytes().to_vec(),
+        )];
+        tags.extend(host_uniq.map(|value| PppoeTag::new(TAG_HOST_UNIQ, value.to_vec())));
+        Pppoe::discovery(CODE_PADI, 0, &tags)
+    }
+
+    /// PADO answering `padi` from the access concentrator `ac_name`,
+    /// echoing its tags and adding the AC-Name and an AC-Cookie.
+    pub fn pado(padi: &Pppoe, ac_name: &str, cookie: &[u8]) -> Result<Self, ParseError> {
+        let mut tags = padi.tags()?;
+        tags.push(PppoeTag::new(TAG_AC_NAME, ac_name.as_bytes().to_vec()));
+        tags.push(PppoeTag::new(TAG_AC_COOKIE, cookie.to_vec()));
+        Ok(Pppoe::discovery(CODE_PADO, 0, &tags))
+    }
+
+    /// PADR requesting the session offered by `pado`, echoing its tags
+    /// except the AC-Name.
+    pub fn padr(pado: &Pppoe) -> Result<Self, ParseError> {
+        let tags: Vec<PppoeTag> = pado
+            .tags()?
+            .into_iter()
+            .filter(|tag| tag.tag_type != TAG_AC_NAME)
+            .collect();
+        Ok(Pppoe::discovery(CODE_PADR, 0, &tags))
+    }
+
+    /// PADS confirming `padr` with `session_id`, echoing its tags.
+    pub fn pads(padr: &Pppoe, session_id: u16) -> Result<Self, ParseError> {
+        Ok(Pppoe::discovery(CODE_PADS, session_id, &padr.tags()?))
+    }
+
+    /// PADT terminating `session_id`.
+    pub fn padt(session_id: u16) -> Self {
+        Pppoe::discovery(CODE_PADT, session_id, &[])
+    }
+
+    /// Session packet carrying `ppp` in `session_id`.
+    pub fn session(session_id: u16, ppp: &Ppp) -> Self {
+        Pppoe::new(CODE_SESSION, session_id, ppp.to_bytes())
+    }
+
+    /// Returns true for packets of the discovery stage.
+    pub fn is_discovery(&self) -> bool {
+        self.code != CODE_SESSION
+    }
+
+    /// Parses the tags of a discovery packet, up to an End-Of-List tag.
+    pub fn tags(&self) -> Result<Vec<PppoeTag>, ParseError> {
+        let mut tags = Vec::new();
+        let mut buf = &self.payload[..];
+        while !buf.is_empty() {
+            let (tag, len) = PppoeTag::from_bytes(buf)?;
+            if tag.tag_type == TAG_END_OF_LIST {
+                break;
+            }
+            tags.push(tag);
+            buf = &buf[len..];
+        }
+        Ok(tags)
+    }
+
+    /// Returns the first tag of type `tag_type`.
+    pub fn find_tag(&self, tag_type: u16) -> Result<Option<PppoeTag>, ParseError> {
+        Ok(self
+            .tags()?
+            .into_iter()
+            .find(|tag| tag.tag_type == tag_type))
+    }
+
+    /// Parses the PPP frame of a session packet.
+    pub fn ppp(&self) -> Result<Ppp, ParseError> {
+        Ppp::from_bytes(&self.payload)
+    }
+
+    /// Wraps the packet in an Ethernet frame with the EtherType of its
+    /// stage. PADI is sent to the broadcast address.
+    pub fn to_ethernet(&self, source: MacAddr, destination: MacAddr) -> Ethernet {
+        let ethertype = if self.is_discovery() {
+            ETHERTYPE_DISCOVERY
+        } else {
+            ETHERTYPE_SESSION
+        };
+        let destination = if self.code == CODE_PADI {
+            MacAddr::BROADCAST
+        } else {
+            destination
+        };
+        Ethernet::new(destination, source, ethertype, self.to_bytes())
+    }
+
+    // --- SETTER METHODS ---
+
+    pub fn set_code(mut self, code: u8) -> Self {
+        self.code = code;
+        self
+    }
+
+    pub fn set_session_id(mut self, session_id: u16) -> Self {
+        self.session_id = session_id;
+        self
+    }
+
+    pub fn set_length(mut self, length: u16) -> Self {
+        self.length = length;
+        self
+    }
+
+    /// Sets the payload and updates the length.
+    pub fn set_payload(mut self, payload: Vec<u8>) -> Self {
+        self.length = payload.len() as u16;
+        self.payload = payload;
+        self
+    }
+
+    // --- SERIALIZATION ---
+
+    /// Serializes the packet, using the length field as is.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.payload.len());
+        bytes.push((self.version & 0x0f) << 4 | self.type_ & 0x0f);
+        bytes.push(self.code);
+        bytes.extend_from_slice(&self.session_id.to_be_bytes());
+        bytes.extend_from_slice(&self.length.to_be_bytes());
+        bytes.extend_from_slice(&self.payload);
+        bytes
+    }
+
+    /// Parses a PPPoE packet. The payload is bounded by the length field;
+    /// trailing bytes (e.g. Ethernet padding) are ignored.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, HEADER_LEN)?;
+        let length = u16::from_be_bytes([buf[4], buf[5]]);
+        ensure_len(buf, HEADER_LEN + length as usize)?;
+        Ok(Pppoe {
+            version: buf[0] >> 4,
+            type_: buf[0] & 0x0f,
+            code: buf[1],
+            session_id: u16::from_be_bytes([buf[2], buf[3]]),
+            length,
+            payload: buf[HEADER_LEN..HEADER_LEN + length as usize].to_vec(),
+        })
+    }
+}
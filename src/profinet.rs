@@ -0,0 +1,359 @@
+use std::net::Ipv4Addr;
+
+use crate::util::{ParseError, ensure_len, read_ipv4};
+
+// PROFINET frames follow the Ethernet header (EtherType 0x8892) and start
+// with a big-endian frame ID selecting the protocol.
+//
+// RT (cyclic real-time) frame:
+//
+// +----------+---------------------+---------------+-------------+-----------------+
+// | Frame ID | Cyclic data (40..   | Cycle Counter | Data Status | Transfer Status |
+// | 2 bytes  |  1440 bytes)        |    2 bytes    |   1 byte    |     1 byte      |
+// +----------+---------------------+---------------+-------------+-----------------+
+//
+// DCP (Discovery and Configuration Protocol) frame:
+//
+// +----------+------------+--------------+-----+----------------+-------------+--------+
+// | Frame ID | Service ID | Service Type | Xid | Response Delay | Data Length | Blocks |
+// | 2 bytes  |   1 byte   |    1 byte    |  4  |    2 bytes     |   2 bytes   |        |
+// +----------+------------+--------------+-----+----------------+-------------+--------+
+//
+// DCP block: Option (1), Suboption (1), Block Length (2), [Block Info or
+// Qualifier (2)], data, padded to an even length.
+
+/// EtherType of PROFINET.
+pub const ETHERTYPE: u16 = 0x8892;
+
+/// Shortest cyclic data of an RT frame, so the frame reaches the Ethernet
+/// minimum.
+pub const MIN_RT_DATA_LEN: usize = 40;
+
+// Frame IDs.
+pub const FRAME_ID_RT_CLASS1_START: u16 = 0x8000;
+pub const FRAME_ID_RT_CLASS1_END: u16 = 0xbfff;
+pub const FRAME_ID_ACYCLIC_RT_START: u16 = 0xc000;
+pub const FRAME_ID_ACYCLIC_RT_END: u16 = 0xfbff;
+pub const FRAME_ID_DCP_HELLO: u16 = 0xfefc;
+pub const FRAME_ID_DCP_GET_SET: u16 = 0xfefd;
+pub const FRAME_ID_DCP_IDENTIFY_REQUEST: u16 = 0xfefe;
+pub const FRAME_ID_DCP_IDENTIFY_RESPONSE: u16 = 0xfeff;
+
+// Data status bits.
+pub const DATA_STATUS_PRIMARY: u8 = 0x01;
+pub const DATA_STATUS_DATA_VALID: u8 = 0x04;
+pub const DATA_STATUS_RUN: u8 = 0x10;
+pub const DATA_STATUS_STATION_OK: u8 = 0x20;
+
+// DCP service IDs and types.
+pub const DCP_SERVICE_GET: u8 = 3;
+pub const DCP_SERVICE_SET: u8 = 4;
+pub const DCP_SERVICE_IDENTIFY: u8 = 5;
+pub const DCP_SERVICE_HELLO: u8 = 6;
+pub const DCP_SERVICE_TYPE_REQUEST: u8 = 0;
+pub const DCP_SERVICE_TYPE_RESPONSE: u8 = 1;
+
+// DCP options.
+pub const DCP_OPTION_IP: u8 = 1;
+pub const DCP_OPTION_DEVICE_PROPERTIES: u8 = 2;
+pub const DCP_OPTION_DEVICE_INITIATIVE: u8 = 6;
+pub const DCP_OPTION_ALL: u8 = 0xff;
+
+/// Suboption of the IP option carrying address, mask and gateway.
+pub const DCP_SUBOPTION_IP_PARAMETER: u8 = 2;
+
+// Suboptions of the device properties option.
+pub const DCP_SUBOPTION_TYPE_OF_STATION: u8 = 1;
+pub const DCP_SUBOPTION_NAME_OF_STATION: u8 = 2;
+pub const DCP_SUBOPTION_DEVICE_ID: u8 = 3;
+pub const DCP_SUBOPTION_DEVICE_ROLE: u8 = 4;
+pub const DCP_SUBOPTION_DEVICE_OPTIONS: u8 = 5;
+pub const DCP_SUBOPTION_ALIAS_NAME: u8 = 6;
+
+/// PROFINET RT frame (cyclic or acyclic real-time data).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfinetRt {
+    pub frame_id: u16,
+    pub cycle_counter: u16,
+    pub data_status: u8,
+    pub transfer_status: u8,
+    pub payload: Vec<u8>,
+}
+
+impl ProfinetRt {
+    /// Constructor to create a frame whose provider is running with valid
+    /// data.
+    pub fn new(frame_id: u16, cycle_counter: u16, payload: Vec<u8>) -> Self {
+        ProfinetRt {
+            frame_id,
+            cycle_counter,
+            data_status: DATA_STATUS_PRIMARY
+                | DATA_STATUS_DATA_VALID
+                | DATA_STATUS_RUN
+                | DATA_STATUS_STATION_OK,
+            transfer_status: 0,
+            payload,
+        }
+    }
+
+    /// Returns true for frame IDs of cyclic RT class 1 data.
+    pub fn is_cyclic(&self) -> bool {
+        (FRAME_ID_RT_CLASS1_START..=FRAME_ID_RT_CLASS1_END).contains(&self.frame_id)
+    }
+
+    /// Returns true for frame IDs of acyclic RT data (alarms).
+    pub fn is_acyclic(&self) -> bool {
+        (FRAME_ID_ACYCLIC_RT_START..=FRAME_ID_ACYCLIC_RT_END).contains(&self.frame_id)
+    }
+
+    /// Serializes the frame, padding the payload with zeros to
+    /// `MIN_RT_DATA_LEN`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let data_len = self.payload.len().max(MIN_RT_DATA_LEN);
+        let mut bytes = Vec::with_capacity(6 + data_len);
+        bytes.extend_from_slice(&self.frame_id.to_be_bytes());
+        bytes.extend_from_slice(&self.payload);
+        bytes.resize(2 + data_len, 0);
+        bytes.extend_from_slice(&self.cycle_counter.to_be_bytes());
+        bytes.push(self.data_status);
+        bytes.push(self.transfer_status);
+        bytes
+    }
+
+    /// Parses an RT frame. The APDU status is taken from the last four
+    /// bytes, so `buf` must not include Ethernet padding or the FCS.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, 6)?;
+        let status = buf.len() - 4;
+        Ok(ProfinetRt {
+            frame_id: u16::from_be_bytes([buf[0], buf[1]]),
+            cycle_counter: u16::from_be_bytes([buf[status], buf[status + 1]]),
+            data_status: buf[status + 2],
+            transfer_status: buf[status + 3],
+            payload: buf[2..status].to_vec(),
+        })
+    }
+}
+
+/// Device properties block of DCP.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DevicePropBlock {
+    pub suboption: u8,
+    pub data: Vec<u8>,
+}
+
+impl DevicePropBlock {
+    /// NameOfStation block.
+    pub fn name_of_station(name: &str) -> Self {
+        DevicePropBlock {
+            suboption: DCP_SUBOPTION_NAME_OF_STATION,
+            data: name.as_bytes().to_vec(),
+        }
+    }
+
+    /// DeviceID block.
+    pub fn device_id(vendor_id: u16, device_id: u16) -> Self {
+        let mut data = vendor_id.to_be_bytes().to_vec();
+        data.extend_from_slice(&device_id.to_be_bytes());
+        DevicePropBlock {
+            suboption: DCP_SUBOPTION_DEVICE_ID,
+            data,
+        }
+    }
+}
+
+/// IP parameter block of DCP.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpParamBlock {
+    pub ip: Ipv4Addr,
+    pub subnet_mask: Ipv4Addr,
+    pub gateway: Ipv4Addr,
+}
+
+/// A block of a DCP message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DcpBlock {
+    DeviceProperties(DevicePropBlock),
+    IpParameter(IpParamBlock),
+    /// Device initiative value; bit 0 set means the device sends Hello at
+    /// startup.
+    DeviceInitiative(u16),
+    /// The "all selector" of Identify requests.
+    All,
+    Raw {
+        option: u8,
+        suboption: u8,
+        data: Vec<u8>,
+    },
+}
+
+impl DcpBlock {
+    fn option(&self) -> (u8, u8) {
+        match self {
+            DcpBlock::DeviceProperties(block) => (DCP_OPTION_DEVICE_PROPERTIES, block.suboption),
+            DcpBlock::IpParameter(_) => (DCP_OPTION_IP, DCP_SUBOPTION_IP_PARAMETER),
+            DcpBlock::DeviceInitiative(_) => (DCP_OPTION_DEVICE_INITIATIVE, 1),
+            DcpBlock::All => (DCP_OPTION_ALL, DCP_OPTION_ALL),
+            DcpBlock::Raw {
+                option, suboption, ..
+            } => (*option, *suboption),
+        }
+    }
+
+    fn data(&self) -> Vec<u8> {
+        match self {
+            DcpBlock::DeviceProperties(block) => block.data.clone(),
+            DcpBlock::IpParameter(block) => [block.ip, block.subnet_mask, block.gateway]
+                .iter()
+                .flat_map(|address| address.octets())
+                .collect(),
+            DcpBlock::DeviceInitiative(value) => value.to_be_bytes().to_vec(),
+            DcpBlock::All => Vec::new(),
+            DcpBlock::Raw { data, .. } => data.clone(),
+        }
+    }
+
+    /// Serializes the block, preceded by `block_info` (the BlockInfo of
+    /// responses or the BlockQualifier of Set requests) when given.
+    pub fn serialize_into(&self, bytes: &mut Vec<u8>, block_info: Option<u16>) {
+        let (option, suboption) = self.option();
+        let data = self.data();
+        let length = data.len() + if block_info.is_some() { 2 } else { 0 };
+        bytes.push(option);
+        bytes.push(suboption);
+        bytes.extend_from_slice(&(length as u16).to_be_bytes());
+        if let Some(block_info) = block_info {
+            bytes.extend_from_slice(&block_info.to_be_bytes());
+        }
+        bytes.extend_from_slice(&data);
+        if length % 2 == 1 {
+            bytes.push(0);
+        }
+    }
+
+    /// Parses a block, skipping its BlockInfo or BlockQualifier when
+    /// `has_block_info` is set. Returns the block and the bytes consumed,
+    /// padding included.
+    pub fn from_bytes(buf: &[u8], has_block_info: bool) -> Result<(Self, usize), ParseError> {
+        ensure_len(buf, 4)?;
+        let (option, suboption) = (buf[0], buf[1]);
+        let length = u16::from_be_bytes([buf[2], buf[3]]) as usize;
+        ensure_len(buf, 4 + length)?;
+        let skip = if has_block_info && length >= 2 { 2 } else { 0 };
+        let data = &buf[4 + skip..4 + length];
+        let block = match (option, suboption) {
+            (DCP_OPTION_DEVICE_PROPERTIES, _) => DcpBlock::DeviceProperties(DevicePropBlock {
+                suboption,
+                data: data.to_vec(),
+            }),
+            (DCP_OPTION_IP, DCP_SUBOPTION_IP_PARAMETER) if data.len() >= 12 => {
+                DcpBlock::IpParameter(IpParamBlock {
+                    ip: read_ipv4(data, 0),
+                    subnet_mask: read_ipv4(data, 4),
+                    gateway: read_ipv4(data, 8),
+                })
+            }
+            (DCP_OPTION_DEVICE_INITIATIVE, 1) if data.len() >= 2 => {
+                DcpBlock::DeviceInitiative(u16::from_be_bytes([data[0], data[1]]))
+            }
+            (DCP_OPTION_ALL, DCP_OPTION_ALL) => DcpBlock::All,
+            _ => DcpBlock::Raw {
+                option,
+                suboption,
+                data: data.to_vec(),
+            },
+        };
+        let consumed = (4 + length).next_multiple_of(2).min(buf.len());
+        Ok((block, consumed))
+    }
+}
+
+/// PROFINET DCP message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfinetDcp {
+    pub frame_id: u16,
+    pub service_id: u8,
+    pub service_type: u8,
+    pub xid: u32,
+    /// Response delay factor of Identify requests; reserved otherwise.
+    pub response_delay: u16,
+    pub blocks: Vec<DcpBlock>,
+}
+
+impl ProfinetDcp {
+    /// Identify request for all devices, or for the device named `name`.
+    pub fn identify_request(xid: u32, name: Option<&str>) -> Self {
+        let block = match name {
+            Some(name) => DcpBlock::DeviceProperties(DevicePropBlock::name_of_station(name)),
+            None => DcpBlock::All,
+        };
+        ProfinetDcp {
+            frame_id: FRAME_ID_DCP_IDENTIFY_REQUEST,
+            service_id: DCP_SERVICE_IDENTIFY,
+            service_type: DCP_SERVICE_TYPE_REQUEST,
+            xid,
+            response_delay: 1,
+            blocks: vec![block],
+        }
+    }
+
+    /// Identify response of a device describing itself with `blocks`.
+    pub fn identify_response(xid: u32, blocks: Vec<DcpBlock>) -> Self {
+        ProfinetDcp {
+            frame_id: FRAME_ID_DCP_IDENTIFY_RESPONSE,
+            service_id: DCP_SERVICE_IDENTIFY,
+            service_type: DCP_SERVICE_TYPE_RESPONSE,
+            xid,
+            response_delay: 0,
+            blocks,
+        }
+    }
+
+    /// Returns true if the blocks of this message carry a BlockInfo or
+    /// BlockQualifier field.
+    pub fn has_block_info(&self) -> bool {
+        self.service_type == DCP_SERVICE_TYPE_RESPONSE
+            || matches!(self.service_id, DCP_SERVICE_SET | DCP_SERVICE_HELLO)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let block_info = self.has_block_info().then_some(0);
+        let mut blocks = Vec::new();
+        for block in &self.blocks {
+            block.serialize_into(&mut blocks, block_info);
+        }
+        let mut bytes = Vec::with_capacity(12 + blocks.len());
+        bytes.extend_from_slice(&self.frame_id.to_be_bytes());
+        bytes.push(self.service_id);
+        bytes.push(self.service_type);
+        bytes.extend_from_slice(&self.xid.to_be_bytes());
+        bytes.extend_from_slice(&self.response_delay.to_be_bytes());
+        bytes.extend_from_slice(&(blocks.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(&blocks);
+        bytes
+    }
+
+    /// Parses a DCP message; blocks are bounded by the data length field.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, 12)?;
+        let data_len = u16::from_be_bytes([buf[10], buf[11]]) as usize;
+        ensure_len(buf, 12 + data_len)?;
+        let mut dcp = ProfinetDcp {
+            frame_id: u16::from_be_bytes([buf[0], buf[1]]),
+            service_id: buf[2],
+            service_type: buf[3],
+            xid: u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]),
+            response_delay: u16::from_be_bytes([buf[8], buf[9]]),
+            blocks: Vec::new(),
+        };
+        let has_block_info = dcp.has_block_info();
+        let data = &buf[12..12 + data_len];
+        let mut offset = 0;
+        while offset < data.len() {
+            let (block, consumed) = DcpBlock::from_bytes(&data[offset..], has_block_info)?;
+            dcp.blocks.push(block);
+            offset += consumed;
+        }
+        Ok(dcp)
+    }
+}
@@ -0,0 +1,286 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::util::{ParseError, ensure_len, read_ipv4, read_ipv6};
+
+// SOCKS5 (RFC 1928) connect request and reply:
+//
+// +----+-----+-------+------+----------+----------+
+// |VER | CMD |  RSV  | ATYP | DST.ADDR | DST.PORT |
+// +----+-----+-------+------+----------+----------+
+// | 1  |  1  | X'00' |  1   | Variable |    2     |
+// +----+-----+-------+------+----------+----------+
+//
+// The reply has the same layout with REP in place of CMD and the bound
+// address. DST.ADDR is 4 bytes (ATYP 1), a length-prefixed name (ATYP 3) or
+// 16 bytes (ATYP 4).
+
+/// SOCKS protocol version.
+pub const SOCKS_VERSION: u8 = 5;
+
+// Authentication methods.
+pub const METHOD_NO_AUTH: u8 = 0x00;
+pub const METHOD_GSSAPI: u8 = 0x01;
+pub const METHOD_USERNAME_PASSWORD: u8 = 0x02;
+pub const METHOD_NO_ACCEPTABLE: u8 = 0xff;
+
+// Commands.
+pub const CMD_CONNECT: u8 = 0x01;
+pub const CMD_BIND: u8 = 0x02;
+pub const CMD_UDP_ASSOCIATE: u8 = 0x03;
+
+// Address types.
+pub const ATYP_IPV4: u8 = 0x01;
+pub const ATYP_DOMAIN: u8 = 0x03;
+pub const ATYP_IPV6: u8 = 0x04;
+
+/// Reply code of a successful request.
+pub const REPLY_SUCCEEDED: u8 = 0x00;
+
+/// Address of a SOCKS5 request or reply.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SocksAddr {
+    Ipv4(Ipv4Addr),
+    /// Domain name, at most 255 bytes; longer names are truncated.
+    Domain(String),
+    Ipv6(Ipv6Addr),
+}
+
+impl From<IpAddr> for SocksAddr {
+    fn from(address: IpAddr) -> Self {
+        match address {
+            IpAddr::V4(address) => SocksAddr::Ipv4(address),
+            IpAddr::V6(address) => SocksAddr::Ipv6(address),
+        }
+    }
+}
+
+impl SocksAddr {
+    /// Appends the address type and address to `bytes`.
+    pub fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        match self {
+            SocksAddr::Ipv4(address) => {
+                bytes.push(ATYP_IPV4);
+                bytes.extend_from_slice(&address.octets());
+            }
+            SocksAddr::Domain(name) => {
+                let name = &name.as_bytes()[..name.len().min(255)];
+                bytes.push(ATYP_DOMAIN);
+                bytes.push(name.len() as u8);
+                bytes.extend_from_slice(name);
+            }
+            SocksAddr::Ipv6(address) => {
+                bytes.push(ATYP_IPV6);
+                bytes.extend_from_slice(&address.octets());
+            }
+        }
+    }
+
+    /// Parses an address type and address, returning the address with the
+    /// number of bytes consumed.
+    pub fn from_bytes(buf: &[u8]) -> Result<(Self, usize), ParseError> {
+        ensure_len(buf, 1)?;
+        match buf[0] {
+            ATYP_IPV4 => {
+                ensure_len(buf, 5)?;
+                Ok((SocksAddr::Ipv4(read_ipv4(buf, 1)), 5))
+            }
+            ATYP_DOMAIN => {
+                ensure_len(buf, 2)?;
+                let end = 2 + buf[1] as usize;
+                ensure_len(buf, end)?;
+                let name = String::from_utf8(buf[2..end].to_vec())
+                    .map_err(|_| ParseError::InvalidField("domain name"))?;
+                Ok((SocksAddr::Domain(name), end))
+            }
+            ATYP_IPV6 => {
+                ensure_len(buf, 17)?;
+                Ok((SocksAddr::Ipv6(read_ipv6(buf, 1)), 17))
+            }
+            _ => Err(ParseError::InvalidField("address type")),
+        }
+    }
+}
+
+/// Client greeting offering `methods`.
+pub fn socks5_greeting(methods: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![SOCKS_VERSION, methods.len() as u8];
+    bytes.extend_from_slice(methods);
+    bytes
+}
+
+/// Server choice of authentication method.
+pub fn socks5_method_selection(method: u8) -> Vec<u8> {
+    vec![SOCKS_VERSION, method]
+}
+
+/// Parses the server choice of authentication method.
+pub fn parse_method_selection(buf: &[u8]) -> Result<u8, ParseError> {
+    ensure_len(buf, 2)?;
+    if buf[0] != SOCKS_VERSION {
+        return Err(ParseError::InvalidField("version"));
+    }
+    Ok(buf[1])
+}
+
+/// SOCKS5 request sent by the client after authentication.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Socks5Request {
+    pub command: u8,
+    pub address: SocksAddr,
+    pub port: u16,
+}
+
+impl Socks5Request {
+    /// Constructor to create a new request.
+    pub fn new(command: u8, address: SocksAddr, port: u16) -> Self {
+        Socks5Request {
+            command,
+            address,
+            port,
+        }
+    }
+
+    /// Builds a CONNECT request.
+    pub fn connect(address: SocksAddr, port: u16) -> Self {
+        Socks5Request::new(CMD_CONNECT, address, port)
+    }
+
+    /// Serializes the request.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![SOCKS_VERSION, self.command, 0];
+        self.address.serialize_into(&mut bytes);
+        bytes.extend_from_slice(&self.port.to_be_bytes());
+        bytes
+    }
+
+    /// Parses a request, returning it with the number of bytes consumed.
+    pub fn from_bytes(buf: &[u8]) -> Result<(Self, usize), ParseError> {
+        let (command, address, port, len) = parse_message(buf)?;
+        Ok((Socks5Request::new(command, address, port), len))
+    }
+}
+
+/// SOCKS5 reply, carrying the address the server bound for the request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Socks5Reply {
+    pub reply: u8,
+    pub bound_address: SocksAddr,
+    pub bound_port: u16,
+}
+
+impl Socks5Reply {
+    /// Constructor to create a new reply.
+    pub fn new(reply: u8, bound_address: SocksAddr, bound_port: u16) -> Self {
+        Socks5Reply {
+            reply,
+            bound_address,
+            bound_port,
+        }
+    }
+
+    /// Returns true if the request succeeded.
+    pub fn is_success(&self) -> bool {
+        self.reply == REPLY_SUCCEEDED
+    }
+
+    /// Serializes the reply.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        Socks5Request::new(self.reply, self.bound_address.clone(), self.bound_port).to_bytes()
+    }
+
+    /// Parses a reply, returning it with the number of bytes consumed. A
+    /// reply split across segments yields `ParseError::Truncated` until the
+    /// concatenated data holds all of it.
+    pub fn from_bytes(buf: &[u8]) -> Result<(Self, usize), ParseError> {
+        let (reply, address, port, len) = parse_message(buf)?;
+        Ok((Socks5Reply::new(reply, address, port), len))
+    }
+}
+
+/// Parses the common request/reply layout.
+fn parse_message(buf: &[u8]) -> Result<(u8, SocksAddr, u16, usize), ParseError> {
+    ensure_len(buf, 4)?;
+    if buf[0] != SOCKS_VERSION {
+        return Err(ParseError::InvalidField("version"));
+    }
+    let (address, len) = SocksAddr::from_bytes(&buf[3..])?;
+    let offset = 3 + len;
+    ensure_len(buf, offset + 2)?;
+    let port = u16::from_be_bytes([buf[offset], buf[offset + 1]]);
+    Ok((buf[1], address, port, offset + 2))
+}
+
+// --- HTTP CONNECT ---
+
+/// HTTP/1.1 CONNECT request for `host:port`, with extra `headers`.
+pub fn http_connect_request(host: &str, port: u16, headers: &[(&str, &str)]) -> Vec<u8> {
+    let authority = if host.contains(':') {
+        format!("[{host}]:{port}")
+    } else {
+        format!("{host}:{port}")
+    };
+    let mut request = format!("CONNECT {authority} HTTP/1.1\r\nHost: {authority}\r\n");
+    for (name, value) in headers {
+        request.push_str(&format!("{name}: {value}\r\n"));
+    }
+    request.push_str("\r\n");
+    request.into_bytes()
+}
+
+/// HTTP response to a CONNECT request, without headers.
+pub fn http_connect_response(status: u16, reason: &str) -> Vec<u8> {
+    format!("HTTP/1.1 {status} {reason}\r\n\r\n").into_bytes()
+}
+
+/// Status line and headers of a CONNECT response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpConnectResponse {
+    pub status: u16,
+    pub reason: String,
+    pub headers: Vec<(String, String)>,
+}
+
+impl HttpConnectResponse {
+    /// Returns true for 2xx responses, after which the tunnel is open.
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// Parses a response, returning it with the length of its head (the
+    /// tunnelled data starts there). Yields `ParseError::Truncated` until
+    /// the blank line ending the head has been received.
+    pub fn from_bytes(buf: &[u8]) -> Result<(Self, usize), ParseError> {
+        let Some(end) = buf.windows(4).position(|window| window == b"\r\n\r\n") else {
+            return Err(ParseError::Truncated {
+                needed: buf.len() + 1,
+                available: buf.len(),
+            });
+        };
+        let head = std::str::from_utf8(&buf[..end])
+            .map_err(|_| ParseError::InvalidField("response head"))?;
+        let mut lines = head.split("\r\n");
+        let status_line = lines.next().unwrap_or_default();
+        let mut parts = status_line.splitn(3, ' ');
+        if !parts
+            .next()
+            .is_some_and(|version| version.starts_with("HTTP/"))
+        {
+            return Err(ParseError::InvalidField("status line"));
+        }
+        let status = parts
+            .next()
+            .and_then(|status| status.parse().ok())
+            .ok_or(ParseError::InvalidField("status code"))?;
+        let reason = parts.next().unwrap_or_default().to_string();
+        let headers = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+            .collect();
+        let response = HttpConnectResponse {
+            status,
+            reason,
+            headers,
+        };
+        Ok((response, end + 4))
+    }
+}
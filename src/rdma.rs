@@ -0,0 +1,274 @@
+use std::net::Ipv4Addr;
+
+use crate::ipv4::IPv4;
+use crate::udp::{self, UDP};
+use crate::util::{ParseError, crc32, ensure_len};
+
+// RoCE v2 packet: IP | UDP (port 4791) | BTH | payload | pad | ICRC
+//
+// Base Transport Header (InfiniBand Architecture Specification 9.2)
+//
+//  0                   1                   2                   3
+//  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |    OpCode     |S|M|Pad| TVer  |         Partition Key         |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |F|B|   Resv6   |              Destination QP                   |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |A|   Resv7     |           Packet Sequence Number              |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// The invariant CRC covers the IP, UDP and BTH headers with their variant
+// fields (TOS, TTL, checksums, FECN/BECN/Resv6) set to ones, preceded by
+// eight bytes of ones standing in for the InfiniBand LRH.
+
+/// UDP destination port of RoCE v2.
+pub const UDP_PORT: u16 = 4791;
+
+/// Length of the Base Transport Header in bytes.
+pub const BTH_LEN: usize = 12;
+
+/// Length of the invariant CRC in bytes.
+pub const ICRC_LEN: usize = 4;
+
+/// Default partition key (full membership of the default partition).
+pub const DEFAULT_P_KEY: u16 = 0xffff;
+
+// Reliable Connection opcodes.
+pub const SEND_FIRST: u8 = 0x00;
+pub const SEND_MIDDLE: u8 = 0x01;
+pub const SEND_LAST: u8 = 0x02;
+pub const SEND_ONLY: u8 = 0x04;
+
+/// Packet sequence numbers are 24 bits wide.
+const PSN_MASK: u32 = 0x00ff_ffff;
+
+/// Base Transport Header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bth {
+    pub opcode: u8,
+    pub solicited_event: bool,
+    pub migration: bool,
+    pub pad_count: u8,
+    pub transport_version: u8,
+    pub p_key: u16,
+    pub fecn: bool,
+    pub becn: bool,
+    pub dest_qp: u32,
+    pub ack_request: bool,
+    pub psn: u32,
+}
+
+impl Bth {
+    /// Constructor to create a header for the default partition.
+    pub fn new(opcode: u8, dest_qp: u32, psn: u32) -> Self {
+        Bth {
+            opcode,
+            solicited_event: false,
+            migration: true,
+            pad_count: 0,
+            transport_version: 0,
+            p_key: DEFAULT_P_KEY,
+            fecn: false,
+            becn: false,
+            dest_qp: dest_qp & 0x00ff_ffff,
+            ack_request: false,
+            psn: psn & PSN_MASK,
+        }
+    }
+
+    pub fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        bytes.push(self.opcode);
+        bytes.push(
+            (self.solicited_event as u8) << 7
+                | (self.migration as u8) << 6
+                | (self.pad_count & 0x3) << 4
+                | (self.transport_version & 0x0f),
+        );
+        bytes.extend_from_slice(&self.p_key.to_be_bytes());
+        let qp_word =
+            (self.fecn as u32) << 31 | (self.becn as u32) << 30 | self.dest_qp & 0x00ff_ffff;
+        bytes.extend_from_slice(&qp_word.to_be_bytes());
+        let psn_word = (self.ack_request as u32) << 31 | self.psn & PSN_MASK;
+        bytes.extend_from_slice(&psn_word.to_be_bytes());
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, BTH_LEN)?;
+        let qp_word = u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]);
+        let psn_word = u32::from_be_bytes([buf[8], buf[9], buf[10], buf[11]]);
+        Ok(Bth {
+            opcode: buf[0],
+            solicited_event: buf[1] & 0x80 != 0,
+            migration: buf[1] & 0x40 != 0,
+            pad_count: (buf[1] >> 4) & 0x3,
+            transport_version: buf[1] & 0x0f,
+            p_key: u16::from_be_bytes([buf[2], buf[3]]),
+            fecn: qp_word & 0x8000_0000 != 0,
+            becn: qp_word & 0x4000_0000 != 0,
+            dest_qp: qp_word & 0x00ff_ffff,
+            ack_request: psn_word & 0x8000_0000 != 0,
+            psn: psn_word & PSN_MASK,
+        })
+    }
+}
+
+/// RoCE v2 transport packet carried in UDP: the BTH, the payload (without
+/// its padding) and the ICRC.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoceV2 {
+    pub bth: Bth,
+    pub payload: Vec<u8>,
+    pub icrc: u32,
+}
+
+impl RoceV2 {
+    /// Serializes the packet, padding the payload to the BTH pad count.
+    /// The ICRC is sent in little-endian order, like the Ethernet FCS.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let pad = self.bth.pad_count as usize;
+        let mut bytes = Vec::with_capacity(BTH_LEN + self.payload.len() + pad + ICRC_LEN);
+        self.bth.serialize_into(&mut bytes);
+        bytes.extend_from_slice(&self.payload);
+        bytes.resize(bytes.len() + pad, 0);
+        bytes.extend_from_slice(&self.icrc.to_le_bytes());
+        bytes
+    }
+
+    /// Parses the UDP payload of a RoCE v2 packet.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        let bth = Bth::from_bytes(buf)?;
+        let end = buf
+            .len()
+            .checked_sub(ICRC_LEN + bth.pad_count as usize)
+            .filter(|end| *end >= BTH_LEN)
+            .ok_or(ParseError::InvalidField("pad count"))?;
+        let icrc = &buf[buf.len() - ICRC_LEN..];
+        Ok(RoceV2 {
+            bth,
+            payload: buf[BTH_LEN..end].to_vec(),
+            icrc: u32::from_le_bytes([icrc[0], icrc[1], icrc[2], icrc[3]]),
+        })
+    }
+
+    /// Invariant CRC of the packet carried in `ip` and `udp`, whose length
+    /// fields must already account for this packet.
+    pub fn compute_icrc(&self, ip: &IPv4, udp: &UDP) -> u32 {
+        let mut bytes = vec![0xff; 8];
+        let mut ip_header = ip.header_bytes();
+        ip_header[1] = 0xff;
+        ip_header[8] = 0xff;
+        ip_header[10..12].fill(0xff);
+        bytes.extend_from_slice(&ip_header);
+        let mut udp_header = udp.to_bytes();
+        udp_header.truncate(udp::HEADER_LEN);
+        udp_header[6..8].fill(0xff);
+        bytes.extend_from_slice(&udp_header);
+        let mut packet = self.to_bytes();
+        packet.truncate(packet.len() - ICRC_LEN);
+        packet[4] = 0xff;
+        bytes.extend_from_slice(&packet);
+        crc32(&bytes)
+    }
+
+    /// Returns the packet with its ICRC computed.
+    pub fn with_icrc(mut self, ip: &IPv4, udp: &UDP) -> Self {
+        self.icrc = self.compute_icrc(ip, udp);
+        self
+    }
+}
+
+/// UDP source port for a queue pair. RoCE v2 uses the source port for ECMP
+/// entropy, so it is derived from the QP numbers within 0xc000..=0xffff.
+fn source_port(src_qp: u32, dst_qp: u32) -> u16 {
+    0xc000 | ((src_qp ^ dst_qp) & 0x3fff) as u16
+}
+
+/// Builds a RoCE v2 packet from `source` to `destination`, padding
+/// `payload` to 4 bytes and computing the lengths, IP checksum and ICRC.
+/// The UDP checksum is left at zero, as RoCE v2 senders usually do.
+pub fn roce_v2_packet(
+    source: Ipv4Addr,
+    destination: Ipv4Addr,
+    source_port: u16,
+    mut bth: Bth,
+    payload: Vec<u8>,
+) -> (IPv4, UDP, RoceV2) {
+    bth.pad_count = ((4 - payload.len() % 4) % 4) as u8;
+    let roce = RoceV2 {
+        bth,
+        payload,
+        icrc: 0,
+    };
+    let mut udp = UDP::new(source_port, UDP_PORT, roce.to_bytes());
+    let mut ip = IPv4::with_payload(source, destination, udp::IP_PROTOCOL, udp.to_bytes());
+    let roce = roce.with_icrc(&ip, &udp);
+    udp.data = roce.to_bytes();
+    ip.payload = udp.to_bytes();
+    (ip, udp, roce)
+}
+
+/// An RDMA SEND work request. `lkey` names the local buffer and, like
+/// `remote_addr` and `rkey`, is not carried by SEND packets; they are kept
+/// so that one request describes the whole operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SendMessage {
+    pub source: Ipv4Addr,
+    pub destination: Ipv4Addr,
+    pub lkey: u32,
+    pub remote_addr: u64,
+    pub rkey: u32,
+    pub payload: Vec<u8>,
+}
+
+impl SendMessage {
+    /// Builds the message as a single SEND_ONLY packet.
+    pub fn to_roce_v2(&self, src_qp: u32, dst_qp: u32, psn: u32) -> (IPv4, UDP, RoceV2) {
+        let mut bth = Bth::new(SEND_ONLY, dst_qp, psn);
+        bth.ack_request = true;
+        roce_v2_packet(
+            self.source,
+            self.destination,
+            source_port(src_qp, dst_qp),
+            bth,
+            self.payload.clone(),
+        )
+    }
+
+    /// Splits the message into packets of at most `mtu` payload bytes:
+    /// SEND_ONLY if it fits, SEND_FIRST, SEND_MIDDLE... and SEND_LAST
+    /// otherwise, with consecutive PSNs starting at `psn`.
+    pub fn to_roce_v2_packets(
+        &self,
+        src_qp: u32,
+        dst_qp: u32,
+        psn: u32,
+        mtu: usize,
+    ) -> Vec<(IPv4, UDP, RoceV2)> {
+        if self.payload.len() <= mtu {
+            return vec![self.to_roce_v2(src_qp, dst_qp, psn)];
+        }
+        let chunks: Vec<&[u8]> = self.payload.chunks(mtu.max(1)).collect();
+        let last = chunks.len() - 1;
+        chunks
+            .iter()
+            .enumerate()
+            .map(|(index, chunk)| {
+                let opcode = match index {
+                    0 => SEND_FIRST,
+                    i if i == last => SEND_LAST,
+                    _ => SEND_MIDDLE,
+                };
+                let mut bth = Bth::new(opcode, dst_qp, psn.wrapping_add(index as u32));
+                bth.ack_request = index == last;
+                roce_v2_packet(
+                    self.source,
+                    self.destination,
+                    source_port(src_qp, dst_qp),
+                    bth,
+                    chunk.to_vec(),
+                )
+            })
+            .collect()
+    }
+}
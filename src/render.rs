@@ -0,0 +1,198 @@
+// RFC-style ASCII header diagrams, as found in the comments of the protocol
+// modules. Fields are packed into 32-bit rows; each bit takes two columns.
+
+/// Number of bits drawn on each row of a diagram.
+pub const ROW_BITS: usize = 32;
+
+/// A named field of a header, `bits` wide.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldSpec<'a> {
+    pub name: &'a str,
+    pub bits: usize,
+}
+
+impl<'a> FieldSpec<'a> {
+    /// Constructor to create a new field specification.
+    pub const fn new(name: &'a str, bits: usize) -> Self {
+        FieldSpec { name, bits }
+    }
+}
+
+/// Part of a field that falls on a single row.
+struct Segment<'a> {
+    name: &'a str,
+    field: usize,
+    bits: usize,
+    first: bool,
+}
+
+/// Renders the fields as an RFC-style diagram with a bit-position ruler.
+/// Fields wider than the remaining space on a row continue on the next one;
+/// names that do not fit their column are wrapped over several lines.
+pub fn ascii_diagram(fields: &[FieldSpec]) -> String {
+    let rows = split_rows(fields);
+    let mut out = ruler();
+    let mut above: Option<&[Segment]> = None;
+    for row in &rows {
+        out.push_str(&border(above, Some(row)));
+        for line in row_lines(row) {
+            out.push_str(&line);
+            out.push('\n');
+        }
+        above = Some(row);
+    }
+    out.push_str(&border(above, None));
+    out
+}
+
+/// Packs the fields into rows of at most `ROW_BITS` bits.
+fn split_rows<'a>(fields: &[FieldSpec<'a>]) -> Vec<Vec<Segment<'a>>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut used = 0;
+    for (index, spec) in fields.iter().enumerate() {
+        let mut remaining = spec.bits;
+        let mut first = true;
+        while remaining > 0 {
+            let bits = remaining.min(ROW_BITS - used);
+            row.push(Segment {
+                name: spec.name,
+                field: index,
+                bits,
+                first,
+            });
+            first = false;
+            remaining -= bits;
+            used += bits;
+            if used == ROW_BITS {
+                rows.push(std::mem::take(&mut row));
+                used = 0;
+            }
+        }
+    }
+    if !row.is_empty() {
+        rows.push(row);
+    }
+    rows
+}
+
+/// Bit-position labels drawn above the diagram.
+fn ruler() -> String {
+    let mut tens = String::new();
+    let mut units = String::new();
+    for bit in 0..ROW_BITS {
+        tens.push(' ');
+        units.push(' ');
+        if bit % 10 == 0 {
+            tens.push_str(&(bit / 10).to_string());
+        } else {
+            tens.push(' ');
+        }
+        units.push_str(&(bit % 10).to_string());
+    }
+    format!("{}\n{}\n", tens.trim_end(), units)
+}
+
+/// Returns the field index drawn at `bit` on `row`, if any.
+fn field_at(row: &[Segment], bit: usize) -> Option<usize> {
+    let mut start = 0;
+    for segment in row {
+        if bit < start + segment.bits {
+            return Some(segment.field);
+        }
+        start += segment.bits;
+    }
+    None
+}
+
+fn row_width(row: Option<&[Segment]>) -> usize {
+    row.map_or(0, |row| row.iter().map(|segment| segment.bits).sum())
+}
+
+/// Draws the `+-+-+` line between two rows. Bits belonging to a field that
+/// continues from one row to the next are left open.
+fn border(above: Option<&[Segment]>, below: Option<&[Segment]>) -> String {
+    let width = row_width(above).max(row_width(below));
+    let continues = |bit: usize| match (above, below) {
+        (Some(above), Some(below)) => {
+            field_at(above, bit).is_some() && field_at(above, bit) == field_at(below, bit)
+        }
+        _ => false,
+    };
+    let mut line = String::with_capacity(2 * width + 2);
+    for bit in 0..width {
+        let open = bit > 0 && continues(bit - 1) && continues(bit);
+        line.push(if open { ' ' } else { '+' });
+        line.push(if continues(bit) { ' ' } else { '-' });
+    }
+    line.push_str("+\n");
+    line
+}
+
+/// Draws the text lines of one row, centering each name in its column.
+fn row_lines(row: &[Segment]) -> Vec<String> {
+    let cells: Vec<(usize, Vec<String>)> = row
+        .iter()
+        .map(|segment| {
+            let width = 2 * segment.bits - 1;
+            let lines = if segment.first {
+                wrap(segment.name, width)
+            } else {
+                Vec::new()
+            };
+            (width, lines)
+        })
+        .collect();
+    let height = cells
+        .iter()
+        .map(|(_, lines)| lines.len())
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    (0..height)
+        .map(|index| {
+            let mut line = String::from("|");
+            for (width, lines) in &cells {
+                let top = (height - lines.len()) / 2;
+                let text = index
+                    .checked_sub(top)
+                    .and_then(|i| lines.get(i))
+                    .map_or("", String::as_str);
+                let left = (width - text.chars().count()) / 2;
+                line.push_str(&format!("{:left$}{text:<rest$}|", "", rest = width - left));
+            }
+            line
+        })
+        .collect()
+}
+
+/// Wraps `name` on word boundaries into lines of at most `width` characters,
+/// breaking words that are longer than a whole line.
+fn wrap(name: &str, width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for word in name.split_whitespace() {
+        let chars: Vec<char> = word.chars().collect();
+        if chars.len() > width {
+            if !current.is_empty() {
+                lines.push(std::mem::take(&mut current));
+            }
+            for chunk in chars.chunks(width) {
+                lines.push(chunk.iter().collect());
+            }
+            continue;
+        }
+        if current.is_empty() {
+            current.push_str(word);
+        } else if current.chars().count() + 1 + chars.len() <= width {
+            current.push(' ');
+            current.push_str(word);
+        } else {
+            lines.push(std::mem::replace(&mut current, word.to_string()));
+        }
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
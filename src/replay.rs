@@ -0,0 +1,392 @@
+// Replay of Ethernet captures onto a `RawSender`.
+//
+// Switches with IGMP/MLD snooping drop multicast traffic for groups no
+// port has joined, so a replayed capture of multicast streams goes
+// nowhere unless the replay joins the groups itself. With `igmp_priming`,
+// an IGMPv3 or MLDv2 report joining each group is sent right before the
+// first packet to it, and again before a later packet once `refresh` has
+// passed, so long replays outlive the snooping timers (260 s by default,
+// RFC 3376 section 8.4).
+
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::ethernet::{ETHERTYPE_IPV4, ETHERTYPE_IPV6, MacAddr};
+use crate::flow;
+use crate::igmp::{ALL_IGMPV3_ROUTERS, Igmp, IgmpV3Report};
+use crate::mld::{ALL_MLDV2_ROUTERS, CHANGE_TO_EXCLUDE_MODE, MldV2Record, MldV2Report};
+use crate::multicast::{self, MulticastMismatch, ipv4_multicast_mac, ipv6_multicast_mac};
+use crate::pcap::{LINKTYPE_ETHERNET, Reader};
+use crate::transport::RawSender;
+use crate::util::{read_ipv4, read_ipv6};
+
+/// Default interval between two reports for the same group.
+pub const DEFAULT_REFRESH: Duration = Duration::from_secs(60);
+
+/// Addresses and timing of the membership reports of `igmp_priming`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IgmpPriming {
+    /// Ethernet source of the reports.
+    pub source_mac: MacAddr,
+    /// Source of IGMPv3 reports; 0.0.0.0 is allowed (RFC 3376 section 4.2.13).
+    pub ipv4_source: Ipv4Addr,
+    /// Source of MLDv2 reports, a link-local address.
+    pub ipv6_source: Ipv6Addr,
+    /// A group's report is repeated before its next packet once this
+    /// much time has passed since the last one.
+    pub refresh: Duration,
+}
+
+impl IgmpPriming {
+    /// Constructor to create priming options sending from `source_mac`,
+    /// with an unspecified IPv4 source, the EUI-64 link-local address of
+    /// `source_mac` as IPv6 source and the default refresh interval.
+    pub fn new(source_mac: MacAddr) -> Self {
+        let mac = source_mac.0;
+        let link_local = Ipv6Addr::new(
+            0xfe80,
+            0,
+            0,
+            0,
+            u16::from_be_bytes([mac[0] ^ 0x02, mac[1]]),
+            u16::from_be_bytes([mac[2], 0xff]),
+            u16::from_be_bytes([0xfe, mac[3]]),
+            u16::from_be_bytes([mac[4], mac[5]]),
+        );
+        IgmpPriming {
+            source_mac,
+            ipv4_source: Ipv4Addr::UNSPECIFIED,
+            ipv6_source: link_local,
+            refresh: DEFAULT_REFRESH,
+        }
+    }
+
+    // --- SETTER METHODS ---
+
+    pub fn set_ipv4_source(mut self, ipv4_source: Ipv4Addr) -> Self {
+        self.ipv4_source = ipv4_source;
+        self
+    }
+
+    pub fn set_ipv6_source(mut self, ipv6_source: Ipv6Addr) -> Self {
+        self.ipv6_source = ipv6_source;
+        self
+    }
+
+    pub fn set_refresh(mut self, refresh: Duration) -> Self {
+        self.refresh = refresh;
+        self
+    }
+}
+
+/// Returns true if hosts report membership of `group`. Link-local
+/// control groups (224.0.0.0/24, RFC 3376 section 5) and the IPv6
+/// interface-local scope and all-nodes group (RFC 3810 section 6) are
+/// never reported, and snooping switches flood them anyway.
+fn is_reported(group: IpAddr) -> bool {
+    match group {
+        IpAddr::V4(group) => group.is_multicast() && !matches!(group.octets(), [224, 0, 0, _]),
+        IpAddr::V6(group) => {
+            group.is_multicast()
+                && group.segments()[0] & 0x000f > 1
+                && group != Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1)
+        }
+    }
+}
+
+/// Tracks the groups joined during a replay and builds the reports to
+/// send before each frame. Usable on its own with another send loop.
+#[derive(Debug, Clone)]
+pub struct GroupPrimer {
+    pub priming: IgmpPriming,
+    /// Time of the last report sent for each group.
+    reported: HashMap<IpAddr, Duration>,
+}
+
+impl GroupPrimer {
+    /// Constructor to create a primer that has joined no group yet.
+    pub fn new(priming: IgmpPriming) -> Self {
+        GroupPrimer {
+            priming,
+            reported: HashMap::new(),
+        }
+    }
+
+    /// Groups joined so far.
+    pub fn groups(&self) -> impl Iterator<Item = IpAddr> + '_ {
+        self.reported.keys().copied()
+    }
+
+    /// Returns the report frame to send before the Ethernet `frame`, sent
+    /// at `now` (any monotonic clock, e.g. the time since the start of the
+    /// replay): a join of the frame's multicast group if it was never
+    /// reported or its last report is `refresh` old. The report carries
+    /// the VLAN tags of `frame`.
+    pub fn report_before(&mut self, frame: &[u8], now: Duration) -> Option<Vec<u8>> {
+        let ip = flow::ethernet_payload(frame)?;
+        let (group, ethertype) = match ip.first()? >> 4 {
+            4 if ip.len() >= 20 => (IpAddr::from(read_ipv4(ip, 16)), ETHERTYPE_IPV4),
+            6 if ip.len() >= 40 => (IpAddr::from(read_ipv6(ip, 24)), ETHERTYPE_IPV6),
+            _ => return None,
+        };
+        if !is_reported(group) {
+            return None;
+        }
+        if let Some(last) = self.reported.get(&group)
+            && now.saturating_sub(*last) < self.priming.refresh
+        {
+            return None;
+        }
+        self.reported.insert(group, now);
+
+        let (destination, packet) = match group {
+            IpAddr::V4(group) => {
+                let report = IgmpV3Report::exclude_source_join(group, Vec::new());
+                let packet = Igmp::V3Report(report).to_ipv4(self.priming.ipv4_source);
+                (ipv4_multicast_mac(ALL_IGMPV3_ROUTERS), packet.to_bytes())
+            }
+            IpAddr::V6(group) => {
+                let record = MldV2Record::new(CHANGE_TO_EXCLUDE_MODE, group, Vec::new());
+                let packet = MldV2Report::new(vec![record]).to_ipv6(self.priming.ipv6_source);
+                (ipv6_multicast_mac(ALL_MLDV2_ROUTERS), packet.to_bytes())
+            }
+        };
+        let tags = &frame[12..frame.len() - ip.len() - 2];
+        let mut report = Vec::with_capacity(16 + tags.len() + packet.len());
+        report.extend_from_slice(&destination);
+        report.extend_from_slice(&self.priming.source_mac.0);
+        report.extend_from_slice(tags);
+        report.extend_from_slice(&ethertype.to_be_bytes());
+        report.extend_from_slice(&packet);
+        Some(report)
+    }
+}
+
+/// Options of `replay`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReplayOptions {
+    /// Pacing relative to the capture timestamps (2.0 replays twice as
+    /// fast); frames are sent back to back when `None`.
+    pub speed: Option<f64>,
+    /// Joins the multicast groups of the capture before their packets.
+    pub igmp_priming: Option<IgmpPriming>,
+}
+
+impl ReplayOptions {
+    /// Constructor to create options replaying back to back, without
+    /// priming.
+    pub fn new() -> Self {
+        ReplayOptions::default()
+    }
+
+    // --- SETTER METHODS ---
+
+    pub fn set_speed(mut self, speed: f64) -> Self {
+        self.speed = Some(speed);
+        self
+    }
+
+    pub fn set_igmp_priming(mut self, igmp_priming: IgmpPriming) -> Self {
+        self.igmp_priming = Some(igmp_priming);
+        self
+    }
+}
+
+/// Counters of a replay.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplayStats {
+    /// Frames of the capture sent.
+    pub packets: u64,
+    pub bytes: u64,
+    /// Membership reports sent by `igmp_priming`.
+    pub reports: u64,
+    /// Frames whose destination MAC does not match their multicast IP
+    /// destination, by index in the capture (from 0). They are sent as
+    /// they are, but a switch will likely drop them.
+    pub mismatches: Vec<(u64, MulticastMismatch)>,
+}
+
+/// Sends every frame of the Ethernet capture read by `reader` through
+/// `socket`, paced and primed as `options` says. Stops at the first error
+/// of the reader or the socket.
+pub fn replay<R: Read, S: RawSender + ?Sized>(
+    reader: Reader<R>,
+    socket: &S,
+    options: &ReplayOptions,
+) -> io::Result<ReplayStats> {
+    if reader.link_type() != LINKTYPE_ETHERNET {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("cannot replay link type {}", reader.link_type()),
+        ));
+    }
+    let mut primer = options.igmp_priming.map(GroupPrimer::new);
+    let mut stats = ReplayStats::default();
+    let start = Instant::now();
+    let mut first_timestamp = None;
+    for packet in reader {
+        let packet = packet?;
+        if let Some(speed) = options.speed {
+            let first = *first_timestamp.get_or_insert(packet.timestamp);
+            let due = start + packet.timestamp.saturating_sub(first).div_f64(speed);
+            let now = Instant::now();
+            if due > now {
+                thread::sleep(due - now);
+            }
+        }
+        if let Some(report) = primer
+            .as_mut()
+            .and_then(|primer| primer.report_before(&packet.data, start.elapsed()))
+        {
+            socket.send(&report)?;
+            stats.reports += 1;
+        }
+        if let Some(mismatch) = multicast::check_frame(&packet.data) {
+            stats.mismatches.push((stats.packets, mismatch));
+        }
+        socket.send(&packet.data)?;
+        stats.packets += 1;
+        stats.bytes += packet.data.len() as u64;
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ethernet::{ETHERTYPE_VLAN, Ethernet};
+    use crate::ipv4::IPv4;
+    use crate::ipv6::IPv6;
+    use crate::mld::ICMPV6_NEXT_HEADER;
+    use crate::pcap::{CapturedPacket, GlobalHeader, Resolution, Writer};
+    use crate::transport::Loopback;
+    use crate::udp::UDP;
+    use crate::vlan::VlanTag;
+
+    const PROTOCOL_UDP: u8 = 17;
+    const HOST: MacAddr = MacAddr([0x02, 0, 0, 0, 0, 0x01]);
+
+    fn ipv4_frame(group: Ipv4Addr) -> Vec<u8> {
+        let udp = UDP::new(5000, 5000, vec![0; 32]).to_bytes();
+        let ip = IPv4::with_payload(Ipv4Addr::new(10, 0, 0, 1), group, PROTOCOL_UDP, udp)
+            .with_checksum()
+            .to_bytes();
+        Ethernet::new(MacAddr(ipv4_multicast_mac(group)), HOST, ETHERTYPE_IPV4, ip).to_bytes()
+    }
+
+    #[test]
+    fn reports_precede_first_packets_and_refresh() {
+        let group = Ipv4Addr::new(239, 1, 2, 3);
+        let mut primer =
+            GroupPrimer::new(IgmpPriming::new(HOST).set_refresh(Duration::from_secs(30)));
+        let frame = ipv4_frame(group);
+        let report = primer.report_before(&frame, Duration::ZERO).unwrap();
+        let ethernet = Ethernet::from_bytes(&report).unwrap();
+        assert_eq!(ethernet.destination.0, [0x01, 0x00, 0x5e, 0, 0, 0x16]);
+        let ip = IPv4::from_bytes(&ethernet.payload).unwrap();
+        assert_eq!(ip.destination, ALL_IGMPV3_ROUTERS);
+        assert_eq!(ip.ttl, 1);
+        let Igmp::V3Report(igmp) = Igmp::from_bytes(&ip.payload).unwrap() else {
+            panic!("not an IGMPv3 report");
+        };
+        assert_eq!(igmp.records[0].multicast_address, group);
+        assert_eq!(igmp.compute_checksum(), igmp.checksum);
+
+        assert_eq!(primer.report_before(&frame, Duration::from_secs(29)), None);
+        assert!(
+            primer
+                .report_before(&frame, Duration::from_secs(30))
+                .is_some()
+        );
+        // Control groups are flooded, never reported.
+        let control = ipv4_frame(Ipv4Addr::new(224, 0, 0, 5));
+        assert_eq!(primer.report_before(&control, Duration::ZERO), None);
+        assert_eq!(primer.groups().count(), 1);
+    }
+
+    #[test]
+    fn mld_reports_keep_vlan_tags() {
+        let group: Ipv6Addr = "ff3e::8000:1".parse().unwrap();
+        let source: Ipv6Addr = "2001:db8::1".parse().unwrap();
+        let udp = UDP::new(5000, 5000, vec![0; 16]).to_bytes();
+        let ip = IPv6::with_payload(source, group, PROTOCOL_UDP, udp).to_bytes();
+        let frame = Ethernet::new(MacAddr(ipv6_multicast_mac(group)), HOST, ETHERTYPE_IPV6, ip)
+            .push_vlan_tag(VlanTag::new(0, false, 42))
+            .to_bytes();
+
+        let priming = IgmpPriming::new(HOST);
+        assert_eq!(
+            priming.ipv6_source,
+            "fe80::ff:fe00:1".parse::<Ipv6Addr>().unwrap()
+        );
+        let report = GroupPrimer::new(priming)
+            .report_before(&frame, Duration::ZERO)
+            .unwrap();
+        assert_eq!(report[..6], [0x33, 0x33, 0, 0, 0, 0x16]);
+        assert_eq!(report[12..14], ETHERTYPE_VLAN.to_be_bytes());
+        assert_eq!(report[14..16], frame[14..16]);
+        let ip = IPv6::from_bytes(&report[18..]).unwrap();
+        assert_eq!(ip.hop_limit, 1);
+        let (headers, next_header, offset) = ip.extension_headers().unwrap();
+        assert_eq!(headers.len(), 1);
+        assert_eq!(next_header, ICMPV6_NEXT_HEADER);
+        let mld = MldV2Report::from_bytes(&ip.payload[offset..]).unwrap();
+        assert_eq!(mld.records[0].multicast_address, group);
+        assert_eq!(
+            mld.compute_checksum(priming.ipv6_source, ALL_MLDV2_ROUTERS),
+            mld.checksum
+        );
+    }
+
+    #[test]
+    fn replay_primes_groups_and_flags_mismatches() {
+        let first = Ipv4Addr::new(239, 1, 1, 1);
+        let second = Ipv4Addr::new(239, 1, 1, 2);
+        let mut mangled = ipv4_frame(second);
+        mangled[5] = 0x99;
+        let mut writer = Writer::new(
+            Vec::new(),
+            GlobalHeader::new(LINKTYPE_ETHERNET, 65535, Resolution::Micros),
+        )
+        .unwrap();
+        for (index, data) in [ipv4_frame(first), ipv4_frame(first), mangled]
+            .into_iter()
+            .enumerate()
+        {
+            let timestamp = Duration::from_millis(index as u64);
+            writer
+                .write_packet(&CapturedPacket::new(timestamp, data))
+                .unwrap();
+        }
+        let capture = writer.into_inner();
+
+        let socket = Loopback::new();
+        let options = ReplayOptions::new()
+            .set_speed(10.0)
+            .set_igmp_priming(IgmpPriming::new(HOST));
+        let stats = replay(Reader::new(capture.as_slice()).unwrap(), &socket, &options).unwrap();
+        assert_eq!(stats.packets, 3);
+        assert_eq!(stats.reports, 2);
+        assert_eq!(stats.mismatches.len(), 1);
+        assert_eq!(stats.mismatches[0].0, 2);
+        assert_eq!(stats.mismatches[0].1.group, IpAddr::V4(second));
+
+        let sent = socket.drain();
+        assert_eq!(sent.len(), 5);
+        let is_report = |frame: &Vec<u8>| frame[..6] == ipv4_multicast_mac(ALL_IGMPV3_ROUTERS);
+        let pattern: Vec<bool> = sent.iter().map(is_report).collect();
+        assert_eq!(pattern, [true, false, false, true, false]);
+
+        let without = replay(
+            Reader::new(capture.as_slice()).unwrap(),
+            &socket,
+            &ReplayOptions::new(),
+        )
+        .unwrap();
+        assert_eq!(without.reports, 0);
+        assert_eq!(socket.len(), 3);
+    }
+}
@@ -0,0 +1,919 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::ethernet::MacAddr;
+use crate::filter::{Filter, FilterError};
+use crate::matcher::FieldId;
+use crate::pcap::{CapturedPacket, LINKTYPE_ETHERNET, Reader, Writer};
+use crate::truncate::{Layout, layout};
+use crate::util::{IpAddrPair, PseudoHeader, checksum, read_ipv4, read_ipv6, update_checksum};
+use crate::validate::{FieldPath, ValidationError, ValidationErrors};
+
+// Capture rewriting in the spirit of tcprewrite: packets matching a filter
+// expression get header fields assigned, and the checksums and lengths
+// that depend on them are fixed up.
+//
+// Fields are named as `matcher::FieldId` names them (`ip.dst`, `dstport`,
+// `tcp.seq`, ...). Two pseudo-fields act on the record timestamp:
+// `time.shift` adds seconds (possibly negative) and `time.scale`
+// stretches the time since the first packet of the capture. Fields are
+// written in place and the checksums covering them are updated
+// incrementally (RFC 1624), so a packet truncated at capture keeps
+// checksums that were valid; a new payload has them recomputed, which
+// needs the whole packet.
+
+const PROTOCOL_UDP: u8 = 17;
+const PROTOCOL_ICMPV6: u8 = 58;
+
+/// Width in bits of the numeric fields `set_field` writes.
+fn number_bits(field: FieldId) -> Option<u32> {
+    match field {
+        FieldId::IpTos | FieldId::IpTtl => Some(8),
+        FieldId::TcpFlags => Some(9),
+        FieldId::IpIdentification
+        | FieldId::SourcePort
+        | FieldId::DestinationPort
+        | FieldId::TcpWindow
+        | FieldId::TcpUrgentPointer => Some(16),
+        FieldId::Ipv6FlowLabel => Some(20),
+        FieldId::TcpSequence | FieldId::TcpAcknowledgment => Some(32),
+        _ => None,
+    }
+}
+
+/// Returns true if `set_field` can write `field`. Lengths and checksums
+/// are not: they are fixed up after the other fields.
+pub fn is_writable(field: FieldId) -> bool {
+    number_bits(field).is_some()
+        || matches!(
+            field,
+            FieldId::EthDestination
+                | FieldId::EthSource
+                | FieldId::IpSource
+                | FieldId::IpDestination
+                | FieldId::Payload
+        )
+}
+
+/// Value written to a field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldValue {
+    Mac(MacAddr),
+    Address(IpAddr),
+    Number(u32),
+    Bytes(Vec<u8>),
+}
+
+impl FieldValue {
+    /// Parses `value` as a value of `field`: a MAC address, an IP address,
+    /// a decimal or `0x` hexadecimal number that fits the field, or hex
+    /// bytes for the payload.
+    pub fn parse(field: FieldId, value: &str) -> Result<Self, RewriteError> {
+        let invalid = || RewriteError::InvalidValue {
+            field: field.name().to_string(),
+            value: value.to_string(),
+        };
+        match field {
+            FieldId::EthDestination | FieldId::EthSource => {
+                value.parse().map(FieldValue::Mac).map_err(|_| invalid())
+            }
+            FieldId::IpSource | FieldId::IpDestination => value
+                .parse()
+                .map(FieldValue::Address)
+                .map_err(|_| invalid()),
+            FieldId::Payload => parse_hex(value).map(FieldValue::Bytes).ok_or_else(invalid),
+            _ => {
+                let bits = number_bits(field).ok_or(RewriteError::ReadOnly(field))?;
+                let number = match value.strip_prefix("0x") {
+                    Some(hex) => u64::from_str_radix(hex, 16),
+                    None => value.parse(),
+                };
+                number
+                    .ok()
+                    .filter(|number| number >> bits == 0)
+                    .map(|number| FieldValue::Number(number as u32))
+                    .ok_or_else(invalid)
+            }
+        }
+    }
+}
+
+fn parse_hex(value: &str) -> Option<Vec<u8>> {
+    if !value.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(value.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+/// Error returned by `set_field` for a frame the assignment does not fit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetFieldError {
+    /// The frame has no such field, e.g. `tcp.seq` in a UDP packet or an
+    /// IPv4 address in an IPv6 packet.
+    Missing(FieldId),
+    /// The value is not of the field's type.
+    InvalidValue(FieldId),
+    /// The field is computed from the others.
+    ReadOnly(FieldId),
+    /// The payload of a packet truncated at capture cannot be replaced.
+    Truncated,
+    /// The payload of a fragment cannot be replaced.
+    Fragmented,
+}
+
+impl fmt::Display for SetFieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SetFieldError::Missing(field) => write!(f, "packet has no {field} field"),
+            SetFieldError::InvalidValue(field) => write!(f, "value of the wrong type for {field}"),
+            SetFieldError::ReadOnly(field) => write!(f, "{field} cannot be set"),
+            SetFieldError::Truncated => {
+                write!(f, "cannot replace the payload of a truncated packet")
+            }
+            SetFieldError::Fragmented => write!(f, "cannot replace the payload of a fragment"),
+        }
+    }
+}
+
+impl std::error::Error for SetFieldError {}
+
+/// Sets `field` of `frame`, captured with `link_type`, to `value`. The
+/// fields of the outermost IP header and of the TCP or UDP header after
+/// it can be set. The IPv4 header checksum and the TCP, UDP and ICMPv6
+/// checksums covering the field are updated; a new payload also updates
+/// the IP and UDP lengths and drops any link-layer padding.
+pub fn set_field(
+    link_type: u32,
+    frame: &mut Vec<u8>,
+    field: FieldId,
+    value: &FieldValue,
+) -> Result<(), SetFieldError> {
+    if !is_writable(field) {
+        return Err(SetFieldError::ReadOnly(field));
+    }
+    if let FieldId::EthDestination | FieldId::EthSource = field {
+        let FieldValue::Mac(mac) = value else {
+            return Err(SetFieldError::InvalidValue(field));
+        };
+        if link_type != LINKTYPE_ETHERNET || frame.len() < 14 {
+            return Err(SetFieldError::Missing(field));
+        }
+        let offset = if field == FieldId::EthDestination {
+            0
+        } else {
+            6
+        };
+        frame[offset..offset + 6].copy_from_slice(&mac.0);
+        return Ok(());
+    }
+
+    let missing = SetFieldError::Missing(field);
+    let layout = layout(link_type, frame).ok_or(missing)?;
+    let ip = layout.ip_offset;
+    let transport = |tcp_only: bool| {
+        layout
+            .transport
+            .filter(|(_, protocol)| !tcp_only || *protocol != PROTOCOL_UDP)
+            .map(|(offset, _)| offset)
+            .ok_or(missing)
+    };
+    let number = match value {
+        FieldValue::Number(number) => *number,
+        FieldValue::Address(address) => {
+            let source = field == FieldId::IpSource;
+            let (offset, octets) = match (address, layout.ipv6) {
+                (IpAddr::V4(address), false) => {
+                    (if source { 12 } else { 16 }, address.octets().to_vec())
+                }
+                (IpAddr::V6(address), true) => {
+                    (if source { 8 } else { 24 }, address.octets().to_vec())
+                }
+                _ => return Err(missing),
+            };
+            write(frame, &layout, ip + offset, &octets, true);
+            return Ok(());
+        }
+        FieldValue::Bytes(payload) if field == FieldId::Payload => {
+            return replace_payload(frame, &layout, payload);
+        }
+        _ => return Err(SetFieldError::InvalidValue(field)),
+    };
+    let (offset, bytes) = match field {
+        FieldId::IpTos | FieldId::Ipv6FlowLabel if layout.ipv6 => {
+            let word = u32::from_be_bytes([frame[ip], frame[ip + 1], frame[ip + 2], frame[ip + 3]]);
+            let word = match field {
+                FieldId::IpTos => word & !0x0ff0_0000 | number << 20,
+                _ => word & !0x000f_ffff | number,
+            };
+            (ip, word.to_be_bytes().to_vec())
+        }
+        FieldId::IpTos => (ip + 1, vec![number as u8]),
+        FieldId::IpIdentification if !layout.ipv6 => {
+            (ip + 4, (number as u16).to_be_bytes().to_vec())
+        }
+        FieldId::IpTtl => (ip + if layout.ipv6 { 7 } else { 8 }, vec![number as u8]),
+        FieldId::SourcePort => (transport(false)?, (number as u16).to_be_bytes().to_vec()),
+        FieldId::DestinationPort => (
+            transport(false)? + 2,
+            (number as u16).to_be_bytes().to_vec(),
+        ),
+        FieldId::TcpSequence => (transport(true)? + 4, number.to_be_bytes().to_vec()),
+        FieldId::TcpAcknowledgment => (transport(true)? + 8, number.to_be_bytes().to_vec()),
+        FieldId::TcpFlags => {
+            let offset = transport(true)? + 12;
+            let ns = frame[offset] & !0x01 | (number >> 8) as u8;
+            (offset, vec![ns, number as u8])
+        }
+        FieldId::TcpWindow => (
+            transport(true)? + 14,
+            (number as u16).to_be_bytes().to_vec(),
+        ),
+        FieldId::TcpUrgentPointer => (
+            transport(true)? + 18,
+            (number as u16).to_be_bytes().to_vec(),
+        ),
+        _ => return Err(missing),
+    };
+    write(frame, &layout, offset, &bytes, false);
+    Ok(())
+}
+
+/// Writes `bytes` at `offset` of `frame` and updates the checksums that
+/// cover them: the IPv4 header checksum for bytes of the IPv4 header, and
+/// the TCP, UDP or ICMPv6 checksum for bytes of the transport header or,
+/// with `pseudo_header`, for the addresses.
+fn write(frame: &mut [u8], layout: &Layout, offset: usize, bytes: &[u8], pseudo_header: bool) {
+    let ip = layout.ip_offset;
+    // Checksums sum 16-bit words, aligned on the start of the IP header
+    // since IP headers have an even length.
+    let start = ip + ((offset - ip) & !1);
+    let end = (ip + ((offset + bytes.len() - ip + 1) & !1)).min(frame.len());
+    let old = frame[start..end].to_vec();
+    frame[offset..offset + bytes.len()].copy_from_slice(bytes);
+    let new = frame[start..end].to_vec();
+
+    let header_len = (frame[ip] & 0x0f) as usize * 4;
+    if !layout.ipv6 && offset < ip + header_len {
+        update_checksum(&mut frame[ip..], 10, &old, &new, false);
+    }
+    match layout.transport {
+        Some((transport, protocol)) if pseudo_header || offset >= transport => {
+            let (checksum_offset, udp) = match protocol {
+                PROTOCOL_UDP => (6, true),
+                _ => (16, false),
+            };
+            let segment = &mut frame[transport..layout.end];
+            update_checksum(segment, checksum_offset, &old, &new, udp);
+        }
+        None if pseudo_header && layout.protocol == PROTOCOL_ICMPV6 => {
+            let segment = &mut frame[layout.payload_offset..layout.end];
+            update_checksum(segment, 2, &old, &new, false);
+        }
+        _ => {}
+    }
+}
+
+/// Replaces the bytes after the last header `layout` locates with
+/// `payload`, then fixes the lengths and recomputes the checksums.
+fn replace_payload(
+    frame: &mut Vec<u8>,
+    layout: &Layout,
+    payload: &[u8],
+) -> Result<(), SetFieldError> {
+    let ip = layout.ip_offset;
+    let declared = match layout.ipv6 {
+        true => 40 + u16::from_be_bytes([frame[ip + 4], frame[ip + 5]]) as usize,
+        false => u16::from_be_bytes([frame[ip + 2], frame[ip + 3]]) as usize,
+    };
+    if ip + declared > frame.len() {
+        return Err(SetFieldError::Truncated);
+    }
+    if layout.fragmented {
+        return Err(SetFieldError::Fragmented);
+    }
+    frame.truncate(layout.payload_offset);
+    frame.extend_from_slice(payload);
+
+    let ip_len = frame.len() - ip;
+    let addresses = if layout.ipv6 {
+        frame[ip + 4..ip + 6].copy_from_slice(&((ip_len - 40) as u16).to_be_bytes());
+        IpAddrPair::from((read_ipv6(frame, ip + 8), read_ipv6(frame, ip + 24)))
+    } else {
+        let header_len = (frame[ip] & 0x0f) as usize * 4;
+        frame[ip + 2..ip + 4].copy_from_slice(&(ip_len as u16).to_be_bytes());
+        frame[ip + 10..ip + 12].fill(0);
+        let sum = checksum(&frame[ip..ip + header_len]);
+        frame[ip + 10..ip + 12].copy_from_slice(&sum.to_be_bytes());
+        IpAddrPair::from((read_ipv4(frame, ip + 12), read_ipv4(frame, ip + 16)))
+    };
+    let Some((transport, protocol)) = layout.transport else {
+        return Ok(());
+    };
+    let segment_len = frame.len() - transport;
+    let checksum_offset = match protocol {
+        PROTOCOL_UDP => {
+            frame[transport + 4..transport + 6]
+                .copy_from_slice(&(segment_len as u16).to_be_bytes());
+            // A zero UDP checksum over IPv4 means none: keep it that way.
+            if !layout.ipv6 && frame[transport + 6..transport + 8] == [0, 0] {
+                return Ok(());
+            }
+            transport + 6
+        }
+        _ => transport + 16,
+    };
+    frame[checksum_offset..checksum_offset + 2].fill(0);
+    let pseudo_header = PseudoHeader::try_from(addresses).expect("addresses of one IP header");
+    let mut sum = pseudo_header.checksum(protocol, &frame[transport..]);
+    if protocol == PROTOCOL_UDP && sum == 0 {
+        sum = 0xffff;
+    }
+    frame[checksum_offset..checksum_offset + 2].copy_from_slice(&sum.to_be_bytes());
+    Ok(())
+}
+
+/// One `name = value` assignment of a rewrite rule.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Assignment {
+    /// A header field, set with `set_field`.
+    Field(FieldId, FieldValue),
+    /// `time.shift`: seconds added to the timestamp, possibly negative.
+    /// Timestamps before the Unix epoch become the epoch.
+    TimeShift(f64),
+    /// `time.scale`: factor applied to the time elapsed since the first
+    /// packet of the capture.
+    TimeScale(f64),
+}
+
+impl FromStr for Assignment {
+    type Err = RewriteError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, value) = s
+            .split_once('=')
+            .ok_or_else(|| RewriteError::Syntax(s.trim().to_string()))?;
+        let (name, value) = (name.trim(), value.trim());
+        let seconds = || {
+            value
+                .parse::<f64>()
+                .ok()
+                .filter(|seconds| seconds.is_finite())
+                .ok_or_else(|| RewriteError::InvalidValue {
+                    field: name.to_string(),
+                    value: value.to_string(),
+                })
+        };
+        match name {
+            "time.shift" => seconds().map(Assignment::TimeShift),
+            "time.scale" => match seconds()? {
+                factor if factor >= 0.0 => Ok(Assignment::TimeScale(factor)),
+                _ => Err(RewriteError::InvalidValue {
+                    field: name.to_string(),
+                    value: value.to_string(),
+                }),
+            },
+            _ => {
+                let field = FieldId::from_name(name)
+                    .ok_or_else(|| RewriteError::UnknownField(name.to_string()))?;
+                if !is_writable(field) {
+                    return Err(RewriteError::ReadOnly(field));
+                }
+                Ok(Assignment::Field(field, FieldValue::parse(field, value)?))
+            }
+        }
+    }
+}
+
+/// Assignments applied to the packets a filter matches.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RewriteRule {
+    pub filter: Filter,
+    pub assignments: Vec<Assignment>,
+}
+
+impl RewriteRule {
+    /// Constructor to create a rule with no assignment.
+    pub fn new(filter: Filter) -> Self {
+        RewriteRule {
+            filter,
+            assignments: Vec::new(),
+        }
+    }
+
+    /// Parses a filter expression and comma-separated assignments, e.g.
+    /// `dst host 10.0.0.5 and tcp` and `ip.dst = 192.168.1.5, dstport =
+    /// 8443`.
+    pub fn parse(filter: &str, assignments: &str) -> Result<Self, RewriteError> {
+        let assignments = assignments
+            .split(',')
+            .filter(|assignment| !assignment.trim().is_empty())
+            .map(str::parse)
+            .collect::<Result<_, _>>()?;
+        Ok(RewriteRule {
+            filter: Filter::parse(filter)?,
+            assignments,
+        })
+    }
+
+    pub fn add_assignment(mut self, assignment: Assignment) -> Self {
+        self.assignments.push(assignment);
+        self
+    }
+
+    /// Applies the assignments, in order, to `packet`, captured with
+    /// `link_type`, whose capture started at `first_timestamp`. Does not
+    /// check the filter.
+    pub fn apply(
+        &self,
+        link_type: u32,
+        packet: &mut CapturedPacket,
+        first_timestamp: Duration,
+    ) -> Result<(), SetFieldError> {
+        let captured_len = packet.data.len();
+        for assignment in &self.assignments {
+            match assignment {
+                Assignment::Field(field, value) => {
+                    set_field(link_type, &mut packet.data, *field, value)?
+                }
+                Assignment::TimeShift(seconds) => {
+                    let shift = Duration::from_secs_f64(seconds.abs());
+                    packet.timestamp = match *seconds < 0.0 {
+                        true => packet.timestamp.saturating_sub(shift),
+                        false => packet.timestamp + shift,
+                    };
+                }
+                Assignment::TimeScale(factor) => {
+                    let elapsed = packet.timestamp.saturating_sub(first_timestamp);
+                    packet.timestamp = first_timestamp + elapsed.mul_f64(*factor);
+                }
+            }
+        }
+        // Only payload replacement, refused on truncated packets, changes
+        // the length.
+        if packet.data.len() != captured_len {
+            packet.original_len = packet.data.len() as u32;
+        }
+        Ok(())
+    }
+}
+
+/// Parses `(filter, assignments)` pairs as `RewriteRule::parse` does,
+/// reporting every invalid filter and assignment rather than the first,
+/// with paths like `rules[1].assignments[0].ip.ttl`.
+pub fn parse_rules(rules: &[(&str, &str)]) -> Result<Vec<RewriteRule>, ValidationErrors> {
+    let mut errors = ValidationErrors::default();
+    let mut parsed = Vec::with_capacity(rules.len());
+    for (i, (filter, assignments)) in rules.iter().enumerate() {
+        let path = FieldPath::new().field("rules").index(i);
+        let filter = Filter::parse(filter).map_err(|err| {
+            let constraint = format!("a filter expression ({err})");
+            errors.push(ValidationError::new(
+                path.field("filter"),
+                filter,
+                constraint,
+            ));
+        });
+        let assignments = assignments
+            .split(',')
+            .filter(|assignment| !assignment.trim().is_empty())
+            .enumerate()
+            .filter_map(|(j, assignment)| {
+                let path = path.field("assignments").index(j);
+                assignment
+                    .parse()
+                    .map_err(|err| errors.push(assignment_error(path, assignment, err)))
+                    .ok()
+            })
+            .collect();
+        if let Ok(filter) = filter {
+            parsed.push(RewriteRule {
+                filter,
+                assignments,
+            });
+        }
+    }
+    errors.into_result(parsed)
+}
+
+/// Describes why `assignment`, at `path`, failed to parse with `err`.
+fn assignment_error(path: FieldPath, assignment: &str, err: RewriteError) -> ValidationError {
+    match err {
+        RewriteError::UnknownField(name) => {
+            let error = ValidationError::new(path, &name, "a writable field name");
+            match closest_field(&name) {
+                Some(field) => error.set_suggestion(field),
+                None => error,
+            }
+        }
+        RewriteError::ReadOnly(field) => ValidationError::new(
+            path.field(field.name()),
+            field.name(),
+            "a writable field, not one computed when writing",
+        ),
+        RewriteError::InvalidValue { field, value } => {
+            let constraint = match FieldId::from_name(&field) {
+                Some(FieldId::EthDestination | FieldId::EthSource) => "a MAC address".to_string(),
+                Some(FieldId::IpSource | FieldId::IpDestination) => "an IP address".to_string(),
+                Some(FieldId::Payload) => "hex bytes".to_string(),
+                Some(id) => match number_bits(id) {
+                    Some(bits) => format!("a number of at most {}", (1u64 << bits) - 1),
+                    None => "a writable field".to_string(),
+                },
+                None if field == "time.scale" => "a non-negative number of seconds".to_string(),
+                None => "a number of seconds".to_string(),
+            };
+            ValidationError::new(path.field(&field), value, constraint)
+        }
+        _ => ValidationError::new(path, assignment.trim(), "`field = value`"),
+    }
+}
+
+/// Writable field name, or pseudo-field, at most two edits from `name`.
+fn closest_field(name: &str) -> Option<&'static str> {
+    FieldId::ALL
+        .into_iter()
+        .filter(|field| is_writable(*field))
+        .map(|field| field.name())
+        .chain(["time.shift", "time.scale"])
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= 2)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+/// Levenshtein distance between `a` and `b`, in characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, b) in b.iter().enumerate() {
+            let substitution = diagonal + (a != *b) as usize;
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Error returned by `pcap` and the rule parsers.
+#[derive(Debug)]
+pub enum RewriteError {
+    Io(io::Error),
+    Filter(FilterError),
+    /// An assignment without `=`.
+    Syntax(String),
+    UnknownField(String),
+    /// A field that is computed and cannot be assigned.
+    ReadOnly(FieldId),
+    InvalidValue {
+        field: String,
+        value: String,
+    },
+    /// A rule could not be applied to a packet it matched.
+    Packet {
+        /// Index of the packet in the capture, from 0.
+        packet: u64,
+        /// Index of the rule.
+        rule: usize,
+        error: SetFieldError,
+    },
+}
+
+impl fmt::Display for RewriteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RewriteError::Io(err) => err.fmt(f),
+            RewriteError::Filter(err) => err.fmt(f),
+            RewriteError::Syntax(assignment) => {
+                write!(f, "expected `field = value`, found `{assignment}`")
+            }
+            RewriteError::UnknownField(name) => write!(f, "unknown field `{name}`"),
+            RewriteError::ReadOnly(field) => write!(f, "{field} is computed and cannot be set"),
+            RewriteError::InvalidValue { field, value } => {
+                write!(f, "invalid value `{value}` for {field}")
+            }
+            RewriteError::Packet {
+                packet,
+                rule,
+                error,
+            } => {
+                write!(f, "rule {rule} on packet {packet}: {error}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RewriteError {}
+
+impl From<io::Error> for RewriteError {
+    fn from(err: io::Error) -> Self {
+        RewriteError::Io(err)
+    }
+}
+
+impl From<FilterError> for RewriteError {
+    fn from(err: FilterError) -> Self {
+        RewriteError::Filter(err)
+    }
+}
+
+/// Counters of a `pcap` run.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RewriteSummary {
+    pub packets: u64,
+    /// Packets at least one rule applied to.
+    pub rewritten: u64,
+    /// Packets each rule applied to, by rule index.
+    pub per_rule: Vec<u64>,
+}
+
+impl fmt::Display for RewriteSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} of {} packets rewritten",
+            self.rewritten, self.packets
+        )?;
+        for (rule, count) in self.per_rule.iter().enumerate() {
+            writeln!(f, "rule {rule}: {count} packets")?;
+        }
+        Ok(())
+    }
+}
+
+/// Copies the capture of `reader` to `writer`, applying to each packet
+/// every rule whose filter matches it, in order, each rule seeing the
+/// packet as the previous ones left it. Packets no rule matches are
+/// copied untouched. Stops at the first packet a matching rule cannot be
+/// applied to.
+pub fn pcap<R: Read, W: Write>(
+    reader: &mut Reader<R>,
+    writer: &mut Writer<W>,
+    rules: &[RewriteRule],
+) -> Result<RewriteSummary, RewriteError> {
+    let link_type = reader.link_type();
+    let mut summary = RewriteSummary {
+        per_rule: vec![0; rules.len()],
+        ..RewriteSummary::default()
+    };
+    let mut first_timestamp = None;
+    while let Some(mut packet) = reader.next_packet()? {
+        let first = *first_timestamp.get_or_insert(packet.timestamp);
+        let mut rewritten = false;
+        for (index, rule) in rules.iter().enumerate() {
+            if !rule.filter.matches_frame(link_type, &packet.data) {
+                continue;
+            }
+            rule.apply(link_type, &mut packet, first)
+                .map_err(|error| RewriteError::Packet {
+                    packet: summary.packets,
+                    rule: index,
+                    error,
+                })?;
+            summary.per_rule[index] += 1;
+            rewritten = true;
+        }
+        writer.write_packet(&packet)?;
+        summary.packets += 1;
+        summary.rewritten += rewritten as u64;
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+    use crate::ethernet::{ETHERTYPE_IPV4, Ethernet};
+    use crate::ipv4::IPv4;
+    use crate::pcap::{GlobalHeader, Resolution};
+    use crate::tcp::{TCP, flags};
+    use crate::udp::UDP;
+
+    const PROTOCOL_TCP: u8 = 6;
+
+    fn ethernet(ip: IPv4) -> Vec<u8> {
+        Ethernet::new(
+            MacAddr([2, 0, 0, 0, 0, 2]),
+            MacAddr([2, 0, 0, 0, 0, 1]),
+            ETHERTYPE_IPV4,
+            ip.with_checksum().to_bytes(),
+        )
+        .to_bytes()
+    }
+
+    fn tcp(destination: Ipv4Addr, port: u16) -> Vec<u8> {
+        let source = Ipv4Addr::new(10, 0, 0, 1);
+        let pseudo_header = PseudoHeader::V4 {
+            source,
+            destination,
+        };
+        let tcp = TCP::segment(40000, port, 1, 0, flags::SYN).with_checksum(&pseudo_header);
+        ethernet(IPv4::with_payload(
+            source,
+            destination,
+            PROTOCOL_TCP,
+            tcp.to_bytes(),
+        ))
+    }
+
+    fn udp(payload: &[u8]) -> Vec<u8> {
+        let (source, destination) = (Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 9));
+        let pseudo_header = PseudoHeader::V4 {
+            source,
+            destination,
+        };
+        let udp = UDP::new(5000, 53, payload.to_vec()).with_checksum(&pseudo_header);
+        ethernet(IPv4::with_payload(
+            source,
+            destination,
+            PROTOCOL_UDP,
+            udp.to_bytes(),
+        ))
+    }
+
+    fn capture(frames: Vec<Vec<u8>>) -> Vec<u8> {
+        let header = GlobalHeader::new(LINKTYPE_ETHERNET, 65535, Resolution::Micros);
+        let mut writer = Writer::new(Vec::new(), header).unwrap();
+        for (index, data) in frames.into_iter().enumerate() {
+            let timestamp = Duration::from_secs(100 + index as u64);
+            writer
+                .write_packet(&CapturedPacket::new(timestamp, data))
+                .unwrap();
+        }
+        writer.into_inner()
+    }
+
+    fn rewrite(
+        frames: Vec<Vec<u8>>,
+        rules: &[RewriteRule],
+    ) -> (RewriteSummary, Vec<CapturedPacket>) {
+        let input = capture(frames);
+        let mut reader = Reader::new(input.as_slice()).unwrap();
+        let header = GlobalHeader::new(LINKTYPE_ETHERNET, 65535, Resolution::Micros);
+        let mut writer = Writer::new(Vec::new(), header).unwrap();
+        let summary = pcap(&mut reader, &mut writer, rules).unwrap();
+        let output = writer.into_inner();
+        let packets = Reader::new(output.as_slice())
+            .unwrap()
+            .collect::<io::Result<_>>()
+            .unwrap();
+        (summary, packets)
+    }
+
+    fn ipv4(frame: &[u8]) -> IPv4 {
+        IPv4::from_bytes(&Ethernet::from_bytes(frame).unwrap().payload).unwrap()
+    }
+
+    #[test]
+    fn rewrites_matching_packets_with_valid_checksums() {
+        let rules = [
+            RewriteRule::parse(
+                "dst host 10.0.0.5 and tcp",
+                "ip.dst = 192.168.1.5, dstport = 8443",
+            )
+            .unwrap(),
+            RewriteRule::parse(
+                "tcp",
+                "ip.ttl = 7, tcp.flags = 0x12, eth.src = 02:00:00:00:00:aa",
+            )
+            .unwrap(),
+        ];
+        let untouched = udp(b"query");
+        let (summary, packets) = rewrite(
+            vec![tcp(Ipv4Addr::new(10, 0, 0, 5), 80), untouched.clone()],
+            &rules,
+        );
+        assert_eq!(summary.packets, 2);
+        assert_eq!(summary.rewritten, 1);
+        assert_eq!(summary.per_rule, [1, 1]);
+        assert_eq!(packets[1].data, untouched);
+
+        let ip = ipv4(&packets[0].data);
+        assert_eq!(ip.destination, Ipv4Addr::new(192, 168, 1, 5));
+        assert_eq!(ip.ttl, 7);
+        assert_eq!(ip.compute_checksum(), ip.checksum);
+        let segment = TCP::from_bytes(&ip.payload).unwrap();
+        assert_eq!(segment.destination, 8443);
+        assert_eq!(segment.flags, flags::SYN | flags::ACK);
+        let pseudo_header = PseudoHeader::V4 {
+            source: ip.source,
+            destination: ip.destination,
+        };
+        assert_eq!(segment.compute_checksum(&pseudo_header), segment.checksum);
+        assert_eq!(packets[0].data[6..12], [2, 0, 0, 0, 0, 0xaa]);
+    }
+
+    #[test]
+    fn new_payloads_fix_lengths_and_checksums() {
+        let rules = [RewriteRule::parse("udp", "payload = 68656c6c6f20776f726c64").unwrap()];
+        let (_, packets) = rewrite(vec![udp(b"query")], &rules);
+        let ip = ipv4(&packets[0].data);
+        assert_eq!(ip.total_length as usize, 20 + 8 + 11);
+        assert_eq!(ip.compute_checksum(), ip.checksum);
+        let datagram = UDP::from_bytes(&ip.payload).unwrap();
+        assert_eq!(datagram.data, b"hello world");
+        assert_eq!(datagram.length, 19);
+        let pseudo_header = PseudoHeader::V4 {
+            source: ip.source,
+            destination: ip.destination,
+        };
+        assert_eq!(datagram.compute_checksum(&pseudo_header), datagram.checksum);
+        assert_eq!(packets[0].original_len as usize, packets[0].data.len());
+
+        // The payload of a truncated packet is not all there to replace.
+        let mut truncated = udp(b"a longer query");
+        truncated.truncate(truncated.len() - 4);
+        let value = FieldValue::Bytes(b"x".to_vec());
+        assert_eq!(
+            set_field(LINKTYPE_ETHERNET, &mut truncated, FieldId::Payload, &value),
+            Err(SetFieldError::Truncated)
+        );
+        // Fields the packet lacks are reported.
+        let mut frame = udp(b"query");
+        assert_eq!(
+            set_field(
+                LINKTYPE_ETHERNET,
+                &mut frame,
+                FieldId::TcpSequence,
+                &FieldValue::Number(1)
+            ),
+            Err(SetFieldError::Missing(FieldId::TcpSequence))
+        );
+    }
+
+    #[test]
+    fn timestamps_shift_and_scale() {
+        let rules = [RewriteRule::parse("udp", "time.scale = 0.5, time.shift = -50").unwrap()];
+        assert_eq!(rules[0].assignments.len(), 2);
+        let rules = [RewriteRule::new(Filter::parse("udp").unwrap())
+            .add_assignment("time.scale = 0.5".parse().unwrap())
+            .add_assignment("time.shift = -50".parse().unwrap())];
+        let frames = vec![udp(b"a"), udp(b"b"), udp(b"c")];
+        let (_, packets) = rewrite(frames, &rules);
+        let seconds: Vec<f64> = packets.iter().map(|p| p.timestamp.as_secs_f64()).collect();
+        assert_eq!(seconds, [50.0, 50.5, 51.0]);
+    }
+
+    #[test]
+    fn assignments_are_checked_when_parsed() {
+        assert!(matches!(
+            "ip.len = 40".parse::<Assignment>(),
+            Err(RewriteError::ReadOnly(FieldId::IpLength))
+        ));
+        assert!(matches!(
+            "ip.ttl = 300".parse::<Assignment>(),
+            Err(RewriteError::InvalidValue { .. })
+        ));
+        assert!(matches!(
+            "ipv4.color = 1".parse::<Assignment>(),
+            Err(RewriteError::UnknownField(_))
+        ));
+        assert!(matches!(
+            "ip.ttl".parse::<Assignment>(),
+            Err(RewriteError::Syntax(_))
+        ));
+        assert!(matches!(
+            RewriteRule::parse("tcp and", "ip.ttl = 1"),
+            Err(RewriteError::Filter(FilterError::UnexpectedEnd))
+        ));
+    }
+
+    #[test]
+    fn rule_lists_report_every_error() {
+        let rules = [
+            ("tcp", "ip.ttl = 300, ip.tll = 1"),
+            ("tcp and", "ip.len = 40"),
+            ("udp", "dstport = 53, time.scale = -1"),
+        ];
+        let errors = parse_rules(&rules).unwrap_err();
+        let errors: Vec<String> = errors.errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            errors,
+            [
+                "rules[0].assignments[0].ip.ttl: `300` must be a number of at most 255",
+                "rules[0].assignments[1]: `ip.tll` must be a writable field name \
+                 (did you mean `ip.ttl`?)",
+                "rules[1].filter: `tcp and` must be a filter expression \
+                 (unexpected end of filter expression)",
+                "rules[1].assignments[0].ip.len: `ip.len` must be a writable field, \
+                 not one computed when writing",
+                "rules[2].assignments[1].time.scale: `-1` must be a non-negative number of \
+                 seconds",
+            ]
+        );
+
+        let rules = parse_rules(&[("udp", "dstport = 53"), ("tcp", "")]).unwrap();
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].assignments.len(), 1);
+    }
+}
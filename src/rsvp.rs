@@ -0,0 +1,548 @@
+use std::net::Ipv4Addr;
+
+use crate::util::{ParseError, checksum, ensure_len, read_ipv4};
+
+// RSVP common header (RFC 2205 section 3.1.1)
+//
+//  0                   1                   2                   3
+//  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// | Vers  | Flags |   Msg Type    |         RSVP Checksum         |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |   Send_TTL    |   Reserved    |          RSVP Length          |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// Object (RFC 2205 section 3.1.2)
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |         Length (bytes)        |   Class-Num   |    C-Type     |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                   Object contents (padded)                    |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+/// IP protocol number of RSVP.
+pub const IP_PROTOCOL: u8 = 46;
+
+// Message types.
+pub const MSG_PATH: u8 = 1;
+pub const MSG_RESV: u8 = 2;
+pub const MSG_PATH_ERR: u8 = 3;
+pub const MSG_RESV_ERR: u8 = 4;
+pub const MSG_PATH_TEAR: u8 = 5;
+pub const MSG_RESV_TEAR: u8 = 6;
+
+// Object classes used by RSVP-TE (RFC 3209).
+pub const CLASS_SESSION: u8 = 1;
+pub const CLASS_RSVP_HOP: u8 = 3;
+pub const CLASS_TIME_VALUES: u8 = 5;
+pub const CLASS_SENDER_TEMPLATE: u8 = 11;
+pub const CLASS_SENDER_TSPEC: u8 = 12;
+pub const CLASS_LABEL_REQUEST: u8 = 19;
+pub const CLASS_EXPLICIT_ROUTE: u8 = 20;
+pub const CLASS_FAST_REROUTE: u8 = 205;
+pub const CLASS_SESSION_ATTRIBUTE: u8 = 207;
+
+/// C-Type of the LSP_TUNNEL_IPv4 SESSION and SENDER_TEMPLATE objects.
+const C_TYPE_LSP_TUNNEL_IPV4: u8 = 7;
+
+/// Default refresh period of PATH state, in milliseconds.
+pub const DEFAULT_REFRESH_PERIOD: u32 = 30_000;
+
+/// Default TTL of RSVP messages.
+pub const DEFAULT_TTL: u8 = 64;
+
+/// An RSVP object.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RsvpObject {
+    pub class_num: u8,
+    pub c_type: u8,
+    pub data: Vec<u8>,
+}
+
+impl RsvpObject {
+    /// Constructor to create a new object.
+    pub fn new(class_num: u8, c_type: u8, data: Vec<u8>) -> Self {
+        RsvpObject {
+            class_num,
+            c_type,
+            data,
+        }
+    }
+
+    /// Serializes the object, padding its contents to a multiple of 4 bytes.
+    pub fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        let padded = self.data.len().next_multiple_of(4);
+        bytes.extend_from_slice(&((4 + padded) as u16).to_be_bytes());
+        bytes.push(self.class_num);
+        bytes.push(self.c_type);
+        bytes.extend_from_slice(&self.data);
+        bytes.resize(bytes.len() + padded - self.data.len(), 0);
+    }
+
+    /// Parses an object, returning it and the number of bytes consumed.
+    pub fn from_bytes(buf: &[u8]) -> Result<(Self, usize), ParseError> {
+        ensure_len(buf, 4)?;
+        let length = u16::from_be_bytes([buf[0], buf[1]]) as usize;
+        if length < 4 || !length.is_multiple_of(4) {
+            return Err(ParseError::InvalidField("object length"));
+        }
+        ensure_len(buf, length)?;
+        Ok((
+            RsvpObject::new(buf[2], buf[3], buf[4..length].to_vec()),
+            length,
+        ))
+    }
+}
+
+/// RSVP message: the common header followed by its objects.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rsvp {
+    pub version: u8,
+    pub flags: u8,
+    pub msg_type: u8,
+    pub checksum: u16,
+    pub send_ttl: u8,
+    pub reserved: u8,
+    pub objects: Vec<RsvpObject>,
+}
+
+impl Rsvp {
+    /// Builds a version 1 message with its checksum computed.
+    pub fn new(msg_type: u8, objects: Vec<RsvpObject>) -> Self {
+        Rsvp {
+            version: 1,
+            flags: 0,
+            msg_type,
+            checksum: 0,
+            send_ttl: DEFAULT_TTL,
+            reserved: 0,
+            objects,
+        }
+        .with_checksum()
+    }
+
+    /// Returns the first object of class `class_num`.
+    pub fn object(&self, class_num: u8) -> Option<&RsvpObject> {
+        self.objects.iter().find(|obj| obj.class_num == class_num)
+    }
+
+    /// Checksum over the whole message (with the checksum field zeroed).
+    pub fn compute_checksum(&self) -> u16 {
+        let mut bytes = self.to_bytes();
+        bytes[2..4].fill(0);
+        checksum(&bytes)
+    }
+
+    /// Returns the message with its checksum computed.
+    pub fn with_checksum(mut self) -> Self {
+        self.checksum = self.compute_checksum();
+        self
+    }
+
+    /// Serializes the header and objects.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![
+            (self.version & 0x0f) << 4 | (self.flags & 0x0f),
+            self.msg_type,
+        ];
+        bytes.extend_from_slice(&self.checksum.to_be_bytes());
+        bytes.extend_from_slice(&[self.send_ttl, self.reserved, 0, 0]);
+        for obj in &self.objects {
+            obj.serialize_into(&mut bytes);
+        }
+        let length = bytes.len() as u16;
+        bytes[6..8].copy_from_slice(&length.to_be_bytes());
+        bytes
+    }
+
+    /// Parses an RSVP message.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, 8)?;
+        let length = u16::from_be_bytes([buf[6], buf[7]]) as usize;
+        if length < 8 {
+            return Err(ParseError::InvalidField("length"));
+        }
+        ensure_len(buf, length)?;
+        let mut objects = Vec::new();
+        let mut offset = 8;
+        while offset < length {
+            let (obj, consumed) = RsvpObject::from_bytes(&buf[offset..length])?;
+            objects.push(obj);
+            offset += consumed;
+        }
+        Ok(Rsvp {
+            version: buf[0] >> 4,
+            flags: buf[0] & 0x0f,
+            msg_type: buf[1],
+            checksum: u16::from_be_bytes([buf[2], buf[3]]),
+            send_ttl: buf[4],
+            reserved: buf[5],
+            objects,
+        })
+    }
+}
+
+// --- RSVP-TE OBJECTS ---
+
+/// A hop of an explicit route.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EroHop {
+    LooseHop(Ipv4Addr),
+    StrictHop(Ipv4Addr),
+}
+
+impl EroHop {
+    pub fn address(&self) -> Ipv4Addr {
+        match self {
+            EroHop::LooseHop(address) | EroHop::StrictHop(address) => *address,
+        }
+    }
+}
+
+/// EXPLICIT_ROUTE object made of IPv4 /32 subobjects (RFC 3209 section 4.3).
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ExplicitRouteObject {
+    pub hops: Vec<EroHop>,
+}
+
+impl ExplicitRouteObject {
+    /// Subobject type of an IPv4 prefix.
+    const SUBOBJECT_IPV4: u8 = 1;
+
+    pub fn to_object(&self) -> RsvpObject {
+        let mut data = Vec::with_capacity(self.hops.len() * 8);
+        for hop in &self.hops {
+            let loose = matches!(hop, EroHop::LooseHop(_)) as u8;
+            data.extend_from_slice(&[loose << 7 | Self::SUBOBJECT_IPV4, 8]);
+            data.extend_from_slice(&hop.address().octets());
+            data.extend_from_slice(&[32, 0]);
+        }
+        RsvpObject::new(CLASS_EXPLICIT_ROUTE, 1, data)
+    }
+
+    /// Parses the object, rejecting subobjects other than IPv4 prefixes.
+    pub fn from_object(obj: &RsvpObject) -> Result<Self, ParseError> {
+        let data = &obj.data;
+        let mut hops = Vec::new();
+        let mut offset = 0;
+        while offset < data.len() {
+            ensure_len(data, offset + 2)?;
+            let length = data[offset + 1] as usize;
+            if data[offset] & 0x7f != Self::SUBOBJECT_IPV4 || length != 8 {
+                return Err(ParseError::InvalidField("explicit route subobject"));
+            }
+            ensure_len(data, offset + length)?;
+            let address = read_ipv4(data, offset + 2);
+            hops.push(if data[offset] & 0x80 != 0 {
+                EroHop::LooseHop(address)
+            } else {
+                EroHop::StrictHop(address)
+            });
+            offset += length;
+        }
+        Ok(ExplicitRouteObject { hops })
+    }
+}
+
+/// SENDER_TSPEC object with an IntServ token bucket (RFC 2210).
+///
+/// Rates are in bits per second here; the wire format carries bytes per
+/// second. The token bucket rate and the peak rate are both set to
+/// `peak_rate_bps`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SenderTspec {
+    pub peak_rate_bps: f32,
+    /// Bucket size in bytes.
+    pub bucket_size: f32,
+    pub min_policed_unit: u32,
+    pub max_packet_size: u32,
+}
+
+impl SenderTspec {
+    /// Builds a Tspec for a flow of `bandwidth_bps` with the given MTU.
+    pub fn new(bandwidth_bps: f32, mtu: u32) -> Self {
+        SenderTspec {
+            peak_rate_bps: bandwidth_bps,
+            bucket_size: mtu as f32,
+            min_policed_unit: 20,
+            max_packet_size: mtu,
+        }
+    }
+
+    pub fn to_object(&self) -> RsvpObject {
+        let rate = self.peak_rate_bps / 8.0;
+        let mut data = Vec::with_capacity(32);
+        // Message header (version 0, 7 words), then the default service
+        // header (6 words) and the token bucket parameter (127, 5 words).
+        data.extend_from_slice(&[0, 0, 0, 7, 1, 0, 0, 6, 127, 0, 0, 5]);
+        data.extend_from_slice(&rate.to_be_bytes());
+        data.extend_from_slice(&self.bucket_size.to_be_bytes());
+        data.extend_from_slice(&rate.to_be_bytes());
+        data.extend_from_slice(&self.min_policed_unit.to_be_bytes());
+        data.extend_from_slice(&self.max_packet_size.to_be_bytes());
+        RsvpObject::new(CLASS_SENDER_TSPEC, 2, data)
+    }
+
+    pub fn from_object(obj: &RsvpObject) -> Result<Self, ParseError> {
+        let data = &obj.data;
+        ensure_len(data, 32)?;
+        if data[8] != 127 {
+            return Err(ParseError::InvalidField("tspec parameter"));
+        }
+        let word = |offset: usize| {
+            u32::from_be_bytes([
+                data[offset],
+                data[offset + 1],
+                data[offset + 2],
+                data[offset + 3],
+            ])
+        };
+        Ok(SenderTspec {
+            peak_rate_bps: f32::from_bits(word(20)) * 8.0,
+            bucket_size: f32::from_bits(word(16)),
+            min_policed_unit: word(24),
+            max_packet_size: word(28),
+        })
+    }
+}
+
+/// FAST_REROUTE object (RFC 4090 section 4.1).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FastReroute {
+    pub setup_priority: u8,
+    pub hold_priority: u8,
+    pub hop_limit: u8,
+    /// Bandwidth of the backup path in bits per second.
+    pub bandwidth: f32,
+}
+
+impl FastReroute {
+    pub fn to_object(&self) -> RsvpObject {
+        let mut data = vec![self.setup_priority, self.hold_priority, self.hop_limit, 0];
+        data.extend_from_slice(&(self.bandwidth / 8.0).to_be_bytes());
+        // Include-any, exclude-any and include-all affinities.
+        data.extend_from_slice(&[0; 12]);
+        RsvpObject::new(CLASS_FAST_REROUTE, 1, data)
+    }
+
+    pub fn from_object(obj: &RsvpObject) -> Result<Self, ParseError> {
+        let data = &obj.data;
+        ensure_len(data, 8)?;
+        Ok(FastReroute {
+            setup_priority: data[0],
+            hold_priority: data[1],
+            hop_limit: data[2],
+            bandwidth: f32::from_be_bytes([data[4], data[5], data[6], data[7]]) * 8.0,
+        })
+    }
+}
+
+/// SESSION_ATTRIBUTE object without resource affinities (RFC 3209
+/// section 4.7).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionAttribute {
+    pub setup_priority: u8,
+    pub hold_priority: u8,
+    pub flags: u8,
+    pub session_name: String,
+}
+
+impl SessionAttribute {
+    // Flags.
+    pub const LOCAL_PROTECTION_DESIRED: u8 = 0x01;
+    pub const LABEL_RECORDING_DESIRED: u8 = 0x02;
+    pub const SE_STYLE_DESIRED: u8 = 0x04;
+
+    pub fn to_object(&self) -> RsvpObject {
+        let name = &self.session_name.as_bytes()[..self.session_name.len().min(255)];
+        let mut data = vec![
+            self.setup_priority,
+            self.hold_priority,
+            self.flags,
+            name.len() as u8,
+        ];
+        data.extend_from_slice(name);
+        RsvpObject::new(CLASS_SESSION_ATTRIBUTE, 7, data)
+    }
+
+    pub fn from_object(obj: &RsvpObject) -> Result<Self, ParseError> {
+        let data = &obj.data;
+        ensure_len(data, 4)?;
+        let name_len = data[3] as usize;
+        ensure_len(data, 4 + name_len)?;
+        Ok(SessionAttribute {
+            setup_priority: data[0],
+            hold_priority: data[1],
+            flags: data[2],
+            session_name: String::from_utf8_lossy(&data[4..4 + name_len]).into_owned(),
+        })
+    }
+}
+
+/// PATH message of an RSVP-TE LSP: the base RSVP objects for an
+/// LSP_TUNNEL_IPv4 session plus the TE objects.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RsvpTePath {
+    /// Ingress LSR, also used as the previous hop and extended tunnel ID.
+    pub source: Ipv4Addr,
+    /// Tunnel endpoint (egress LSR).
+    pub destination: Ipv4Addr,
+    pub tunnel_id: u16,
+    pub lsp_id: u16,
+    pub refresh_period: u32,
+    pub explicit_route: ExplicitRouteObject,
+    pub sender_tspec: SenderTspec,
+    pub session_attribute: SessionAttribute,
+    pub fast_reroute: Option<FastReroute>,
+}
+
+impl RsvpTePath {
+    /// Constructor to create a new PATH message for tunnel 1, LSP 1.
+    pub fn new(source: Ipv4Addr, destination: Ipv4Addr, sender_tspec: SenderTspec) -> Self {
+        RsvpTePath {
+            source,
+            destination,
+            tunnel_id: 1,
+            lsp_id: 1,
+            refresh_period: DEFAULT_REFRESH_PERIOD,
+            explicit_route: ExplicitRouteObject::default(),
+            sender_tspec,
+            session_attribute: SessionAttribute {
+                setup_priority: 7,
+                hold_priority: 0,
+                flags: SessionAttribute::SE_STYLE_DESIRED,
+                session_name: format!("{source}->{destination}"),
+            },
+            fast_reroute: None,
+        }
+    }
+
+    // --- SETTER METHODS ---
+
+    pub fn set_tunnel_id(mut self, tunnel_id: u16) -> Self {
+        self.tunnel_id = tunnel_id;
+        self
+    }
+
+    pub fn set_lsp_id(mut self, lsp_id: u16) -> Self {
+        self.lsp_id = lsp_id;
+        self
+    }
+
+    pub fn set_explicit_route(mut self, explicit_route: ExplicitRouteObject) -> Self {
+        self.explicit_route = explicit_route;
+        self
+    }
+
+    pub fn set_session_attribute(mut self, session_attribute: SessionAttribute) -> Self {
+        self.session_attribute = session_attribute;
+        self
+    }
+
+    pub fn set_fast_reroute(mut self, fast_reroute: Option<FastReroute>) -> Self {
+        self.fast_reroute = fast_reroute;
+        self
+    }
+
+    // --- SERIALIZATION ---
+
+    /// Builds the PATH message, with objects in the order of RFC 3209
+    /// section 4.3.6 (the ERO is omitted when empty).
+    pub fn to_rsvp(&self) -> Rsvp {
+        let mut session = self.destination.octets().to_vec();
+        session.extend_from_slice(&[0, 0]);
+        session.extend_from_slice(&self.tunnel_id.to_be_bytes());
+        session.extend_from_slice(&self.source.octets());
+
+        let mut hop = self.source.octets().to_vec();
+        hop.extend_from_slice(&0u32.to_be_bytes());
+
+        let mut sender_template = self.source.octets().to_vec();
+        sender_template.extend_from_slice(&[0, 0]);
+        sender_template.extend_from_slice(&self.lsp_id.to_be_bytes());
+
+        let mut objects = vec![
+            RsvpObject::new(CLASS_SESSION, C_TYPE_LSP_TUNNEL_IPV4, session),
+            RsvpObject::new(CLASS_RSVP_HOP, 1, hop),
+            RsvpObject::new(
+                CLASS_TIME_VALUES,
+                1,
+                self.refresh_period.to_be_bytes().to_vec(),
+            ),
+        ];
+        if !self.explicit_route.hops.is_empty() {
+            objects.push(self.explicit_route.to_object());
+        }
+        // Label request for IPv4 payload.
+        objects.push(RsvpObject::new(
+            CLASS_LABEL_REQUEST,
+            1,
+            vec![0, 0, 0x08, 0x00],
+        ));
+        objects.push(self.session_attribute.to_object());
+        if let Some(fast_reroute) = &self.fast_reroute {
+            objects.push(fast_reroute.to_object());
+        }
+        objects.push(RsvpObject::new(
+            CLASS_SENDER_TEMPLATE,
+            C_TYPE_LSP_TUNNEL_IPV4,
+            sender_template,
+        ));
+        objects.push(self.sender_tspec.to_object());
+        Rsvp::new(MSG_PATH, objects)
+    }
+
+    /// Extracts an RSVP-TE PATH from a parsed message.
+    pub fn from_rsvp(rsvp: &Rsvp) -> Result<Self, ParseError> {
+        if rsvp.msg_type != MSG_PATH {
+            return Err(ParseError::InvalidField("message type"));
+        }
+        let required = |class_num: u8| {
+            rsvp.object(class_num)
+                .ok_or(ParseError::InvalidField("missing object"))
+        };
+        let session = &required(CLASS_SESSION)?.data;
+        let sender_template = &required(CLASS_SENDER_TEMPLATE)?.data;
+        let time_values = &required(CLASS_TIME_VALUES)?.data;
+        ensure_len(session, 12)?;
+        ensure_len(sender_template, 8)?;
+        ensure_len(time_values, 4)?;
+        Ok(RsvpTePath {
+            source: read_ipv4(sender_template, 0),
+            destination: read_ipv4(session, 0),
+            tunnel_id: u16::from_be_bytes([session[6], session[7]]),
+            lsp_id: u16::from_be_bytes([sender_template[6], sender_template[7]]),
+            refresh_period: u32::from_be_bytes([
+                time_values[0],
+                time_values[1],
+                time_values[2],
+                time_values[3],
+            ]),
+            explicit_route: match rsvp.object(CLASS_EXPLICIT_ROUTE) {
+                Some(obj) => ExplicitRouteObject::from_object(obj)?,
+                None => ExplicitRouteObject::default(),
+            },
+            sender_tspec: SenderTspec::from_object(required(CLASS_SENDER_TSPEC)?)?,
+            session_attribute: SessionAttribute::from_object(required(CLASS_SESSION_ATTRIBUTE)?)?,
+            fast_reroute: rsvp
+                .object(CLASS_FAST_REROUTE)
+                .map(FastReroute::from_object)
+                .transpose()?,
+        })
+    }
+}
+
+/// Builds a PATH message for an LSP from `src` to `dst` reserving
+/// `bandwidth_bps` along `explicit_route`.
+pub fn build_path_message(
+    src: Ipv4Addr,
+    dst: Ipv4Addr,
+    bandwidth_bps: f32,
+    explicit_route: Vec<EroHop>,
+) -> Rsvp {
+    RsvpTePath::new(src, dst, SenderTspec::new(bandwidth_bps, 1500))
+        .set_explicit_route(ExplicitRouteObject {
+            hops: explicit_route,
+        })
+        .to_rsvp()
+}
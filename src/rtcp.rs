@@ -0,0 +1,275 @@
+use crate::util::{ParseError, ensure_len};
+
+// RTCP feedback message (RFC 4585 section 6.1)
+//
+//  0                   1                   2                   3
+//  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |V=2|P|   FMT   |       PT      |          length               |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                  SSRC of packet sender                        |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                  SSRC of media source                         |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// :            Feedback Control Information (FCI)                 :
+//
+// Length is the packet length in 32-bit words minus one.
+
+/// Payload type of transport layer feedback.
+pub const PT_RTPFB: u8 = 205;
+/// Payload type of payload-specific feedback.
+pub const PT_PSFB: u8 = 206;
+
+// Feedback message types (FMT).
+pub const FMT_NACK: u8 = 1;
+pub const FMT_TMMBR: u8 = 3;
+pub const FMT_PLI: u8 = 1;
+pub const FMT_FIR: u8 = 4;
+pub const FMT_AFB: u8 = 15;
+
+/// Identifier of a REMB application layer feedback message.
+const REMB_IDENTIFIER: &[u8; 4] = b"REMB";
+
+/// A TMMBR request for one media sender (RFC 5104 section 4.2.1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TmmbrEntry {
+    pub ssrc: u32,
+    /// Maximum total media bit rate in bits per second.
+    pub bitrate: u64,
+    /// Measured per-packet overhead in bytes (9 bits).
+    pub overhead: u16,
+}
+
+/// A FIR request for one media sender (RFC 5104 section 4.3.1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FirEntry {
+    pub ssrc: u32,
+    pub sequence_number: u8,
+}
+
+/// RTCP feedback message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RtcpFeedback {
+    /// Generic NACK: (PID, BLP) pairs, the lost packet ID and a bitmask of
+    /// the following lost packets.
+    Nack {
+        media_ssrc: u32,
+        blps: Vec<(u16, u16)>,
+    },
+    /// Temporary Maximum Media Stream Bit Rate Request.
+    Tmmbr {
+        media_ssrc: u32,
+        entries: Vec<TmmbrEntry>,
+    },
+    /// Picture Loss Indication.
+    Pli { media_ssrc: u32 },
+    /// Full Intra Request.
+    Fir { fir_entries: Vec<FirEntry> },
+    /// Receiver Estimated Maximum Bitrate (draft-alvestrand-rmcat-remb).
+    Remb { bitrate: u32, ssrcs: Vec<u32> },
+}
+
+/// Splits `value` into an exponent and a mantissa of `mantissa_bits` bits.
+fn to_exp_mantissa(mut value: u64, mantissa_bits: u32) -> (u8, u32) {
+    let mut exp = 0u8;
+    while value >= 1 << mantissa_bits {
+        value >>= 1;
+        exp += 1;
+    }
+    (exp, value as u32)
+}
+
+/// Value of `mantissa` times 2 to the `exp`, saturating at `u64::MAX`
+/// since 6-bit exponents go far beyond it.
+fn from_exp_mantissa(mantissa: u32, exp: u32) -> u64 {
+    1u64.checked_shl(exp)
+        .and_then(|scale| scale.checked_mul(mantissa as u64))
+        .unwrap_or(u64::MAX)
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes([
+        buf[offset],
+        buf[offset + 1],
+        buf[offset + 2],
+        buf[offset + 3],
+    ])
+}
+
+impl RtcpFeedback {
+    /// Payload type (PT) of the message.
+    pub fn payload_type(&self) -> u8 {
+        match self {
+            RtcpFeedback::Nack { .. } | RtcpFeedback::Tmmbr { .. } => PT_RTPFB,
+            _ => PT_PSFB,
+        }
+    }
+
+    /// Feedback message type (FMT) of the message.
+    pub fn fmt(&self) -> u8 {
+        match self {
+            RtcpFeedback::Nack { .. } => FMT_NACK,
+            RtcpFeedback::Tmmbr { .. } => FMT_TMMBR,
+            RtcpFeedback::Pli { .. } => FMT_PLI,
+            RtcpFeedback::Fir { .. } => FMT_FIR,
+            RtcpFeedback::Remb { .. } => FMT_AFB,
+        }
+    }
+
+    /// Serializes the message as an RTCP packet sent by `sender_ssrc`.
+    pub fn to_bytes(&self, sender_ssrc: u32) -> Vec<u8> {
+        let mut bytes = vec![0x80 | self.fmt(), self.payload_type(), 0, 0];
+        bytes.extend_from_slice(&sender_ssrc.to_be_bytes());
+        match self {
+            RtcpFeedback::Nack { media_ssrc, blps } => {
+                bytes.extend_from_slice(&media_ssrc.to_be_bytes());
+                for (pid, blp) in blps {
+                    bytes.extend_from_slice(&pid.to_be_bytes());
+                    bytes.extend_from_slice(&blp.to_be_bytes());
+                }
+            }
+            RtcpFeedback::Tmmbr {
+                media_ssrc,
+                entries,
+            } => {
+                bytes.extend_from_slice(&media_ssrc.to_be_bytes());
+                for entry in entries {
+                    let (exp, mantissa) = to_exp_mantissa(entry.bitrate, 17);
+                    let word = (exp as u32) << 26 | mantissa << 9 | (entry.overhead as u32 & 0x1ff);
+                    bytes.extend_from_slice(&entry.ssrc.to_be_bytes());
+                    bytes.extend_from_slice(&word.to_be_bytes());
+                }
+            }
+            RtcpFeedback::Pli { media_ssrc } => {
+                bytes.extend_from_slice(&media_ssrc.to_be_bytes());
+            }
+            RtcpFeedback::Fir { fir_entries } => {
+                bytes.extend_from_slice(&0u32.to_be_bytes());
+                for entry in fir_entries {
+                    bytes.extend_from_slice(&entry.ssrc.to_be_bytes());
+                    bytes.extend_from_slice(&[entry.sequence_number, 0, 0, 0]);
+                }
+            }
+            RtcpFeedback::Remb { bitrate, ssrcs } => {
+                bytes.extend_from_slice(&0u32.to_be_bytes());
+                bytes.extend_from_slice(REMB_IDENTIFIER);
+                let (exp, mantissa) = to_exp_mantissa(*bitrate as u64, 18);
+                let word = (ssrcs.len() as u32) << 24 | (exp as u32) << 18 | mantissa;
+                bytes.extend_from_slice(&word.to_be_bytes());
+                for ssrc in ssrcs {
+                    bytes.extend_from_slice(&ssrc.to_be_bytes());
+                }
+            }
+        }
+        let length = (bytes.len() / 4 - 1) as u16;
+        bytes[2..4].copy_from_slice(&length.to_be_bytes());
+        bytes
+    }
+
+    /// Parses a feedback packet, returning the sender SSRC and the message.
+    pub fn from_bytes(buf: &[u8]) -> Result<(u32, Self), ParseError> {
+        ensure_len(buf, 12)?;
+        if buf[0] >> 6 != 2 {
+            return Err(ParseError::InvalidField("version"));
+        }
+        let end = (u16::from_be_bytes([buf[2], buf[3]]) as usize + 1) * 4;
+        if end < 12 {
+            return Err(ParseError::InvalidField("length"));
+        }
+        ensure_len(buf, end)?;
+        let sender_ssrc = read_u32(buf, 4);
+        let media_ssrc = read_u32(buf, 8);
+        let fci = &buf[12..end];
+        let feedback = match (buf[1], buf[0] & 0x1f) {
+            (PT_RTPFB, FMT_NACK) => RtcpFeedback::Nack {
+                media_ssrc,
+                blps: fci
+                    .chunks_exact(4)
+                    .map(|c| {
+                        (
+                            u16::from_be_bytes([c[0], c[1]]),
+                            u16::from_be_bytes([c[2], c[3]]),
+                        )
+                    })
+                    .collect(),
+            },
+            (PT_RTPFB, FMT_TMMBR) => RtcpFeedback::Tmmbr {
+                media_ssrc,
+                entries: fci
+                    .chunks_exact(8)
+                    .map(|c| {
+                        let word = read_u32(c, 4);
+                        TmmbrEntry {
+                            ssrc: read_u32(c, 0),
+                            bitrate: from_exp_mantissa((word >> 9) & 0x1ffff, word >> 26),
+                            overhead: (word & 0x1ff) as u16,
+                        }
+                    })
+                    .collect(),
+            },
+            (PT_PSFB, FMT_PLI) => RtcpFeedback::Pli { media_ssrc },
+            (PT_PSFB, FMT_FIR) => RtcpFeedback::Fir {
+                fir_entries: fci
+                    .chunks_exact(8)
+                    .map(|c| FirEntry {
+                        ssrc: read_u32(c, 0),
+                        sequence_number: c[4],
+                    })
+                    .collect(),
+            },
+            (PT_PSFB, FMT_AFB) if fci.starts_with(REMB_IDENTIFIER) => {
+                ensure_len(fci, 8)?;
+                let word = read_u32(fci, 4);
+                let count = (word >> 24) as usize;
+                ensure_len(fci, 8 + count * 4)?;
+                let bitrate = from_exp_mantissa(word & 0x3ffff, (word >> 18) & 0x3f);
+                RtcpFeedback::Remb {
+                    bitrate: bitrate.min(u32::MAX as u64) as u32,
+                    ssrcs: (0..count).map(|i| read_u32(fci, 8 + i * 4)).collect(),
+                }
+            }
+            _ => return Err(ParseError::InvalidField("feedback message type")),
+        };
+        Ok((sender_ssrc, feedback))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn length_shorter_than_header_is_rejected() {
+        let buf = [0x81, 0xcd, 0, 0, 0, 0, 0, 1, 0, 0, 0, 2];
+        assert!(RtcpFeedback::from_bytes(&buf).is_err());
+        let buf = [0x81, 0xcd, 0, 1, 0, 0, 0, 1, 0, 0, 0, 2];
+        assert!(RtcpFeedback::from_bytes(&buf).is_err());
+    }
+
+    #[test]
+    fn remb_bitrate_saturates() {
+        let mut buf = vec![0x80 | FMT_AFB, PT_PSFB, 0, 4, 0, 0, 0, 1, 0, 0, 0, 0];
+        buf.extend_from_slice(REMB_IDENTIFIER);
+        buf.extend_from_slice(&(63u32 << 18 | 0x3ffff).to_be_bytes());
+        let (_, feedback) = RtcpFeedback::from_bytes(&buf).unwrap();
+        assert!(matches!(
+            feedback,
+            RtcpFeedback::Remb {
+                bitrate: u32::MAX,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn tmmbr_bitrate_saturates() {
+        let mut buf = vec![0x80 | FMT_TMMBR, PT_RTPFB, 0, 4, 0, 0, 0, 1, 0, 0, 0, 0];
+        buf.extend_from_slice(&3u32.to_be_bytes());
+        buf.extend_from_slice(&(63u32 << 26 | 0x1ffff << 9).to_be_bytes());
+        let (_, feedback) = RtcpFeedback::from_bytes(&buf).unwrap();
+        match feedback {
+            RtcpFeedback::Tmmbr { entries, .. } => assert_eq!(entries[0].bitrate, u64::MAX),
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use crate::util::{ParseError, ensure_len};
+
+// RTP fixed header (RFC 3550 section 5.1)
+//
+//  0                   1                   2                   3
+//  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |V=2|P|X|  CC   |M|     PT      |       sequence number         |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                           timestamp                           |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |           synchronization source (SSRC) identifier            |
+// +=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+
+// |            contributing source (CSRC) identifiers             |
+// |                             ....                              |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// With X set, a header extension follows: a 16-bit profile-defined value,
+// a 16-bit length in 32-bit words, and the extension data.
+
+/// RTP version carried in every packet.
+pub const VERSION: u8 = 2;
+
+/// Length of the fixed header in bytes.
+pub const HEADER_LEN: usize = 12;
+
+/// RTP header extension.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RtpExtension {
+    pub profile: u16,
+    /// Extension data, a multiple of 4 bytes.
+    pub data: Vec<u8>,
+}
+
+/// RTP packet. With `padding` set, the payload ends with the padding and
+/// its count, as on the wire.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rtp {
+    pub version: u8,
+    pub padding: bool,
+    pub marker: bool,
+    pub payload_type: u8,
+    pub sequence_number: u16,
+    pub timestamp: u32,
+    pub ssrc: u32,
+    pub csrcs: Vec<u32>,
+    pub extension: Option<RtpExtension>,
+    pub payload: Vec<u8>,
+}
+
+impl Rtp {
+    /// Constructor to create a packet without CSRCs or extension.
+    pub fn new(
+        payload_type: u8,
+        sequence_number: u16,
+        timestamp: u32,
+        ssrc: u32,
+        payload: Vec<u8>,
+    ) -> Self {
+        Rtp {
+            version: VERSION,
+            padding: false,
+            marker: false,
+            payload_type,
+            sequence_number,
+            timestamp,
+            ssrc,
+            csrcs: Vec::new(),
+            extension: None,
+            payload,
+        }
+    }
+
+    // --- SETTER METHODS ---
+
+    pub fn set_marker(mut self, marker: bool) -> Self {
+        self.marker = marker;
+        self
+    }
+
+    pub fn set_csrcs(mut self, csrcs: Vec<u32>) -> Self {
+        self.csrcs = csrcs;
+        self
+    }
+
+    pub fn set_extension(mut self, extension: Option<RtpExtension>) -> Self {
+        self.extension = extension;
+        self
+    }
+
+    // --- SERIALIZATION ---
+
+    /// Length of the serialized header, CSRCs and extension included, the
+    /// extension data padded to a multiple of 4 bytes.
+    pub fn header_len(&self) -> usize {
+        HEADER_LEN
+            + 4 * self.csrcs.len()
+            + self
+                .extension
+                .as_ref()
+                .map_or(0, |extension| 4 + extension.data.len().next_multiple_of(4))
+    }
+
+    pub fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        bytes.push(
+            (self.version & 0x3) << 6
+                | (self.padding as u8) << 5
+                | (self.extension.is_some() as u8) << 4
+                | (self.csrcs.len() as u8 & 0x0f),
+        );
+        bytes.push((self.marker as u8) << 7 | self.payload_type & 0x7f);
+        bytes.extend_from_slice(&self.sequence_number.to_be_bytes());
+        bytes.extend_from_slice(&self.timestamp.to_be_bytes());
+        bytes.extend_from_slice(&self.ssrc.to_be_bytes());
+        for csrc in &self.csrcs {
+            bytes.extend_from_slice(&csrc.to_be_bytes());
+        }
+        if let Some(extension) = &self.extension {
+            bytes.extend_from_slice(&extension.profile.to_be_bytes());
+            bytes.extend_from_slice(&(extension.data.len().div_ceil(4) as u16).to_be_bytes());
+            bytes.extend_from_slice(&extension.data);
+            bytes.resize(bytes.len().next_multiple_of(4), 0);
+        }
+        bytes.extend_from_slice(&self.payload);
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.header_len() + self.payload.len());
+        self.serialize_into(&mut bytes);
+        bytes
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, HEADER_LEN)?;
+        let version = buf[0] >> 6;
+        if version != VERSION {
+            return Err(ParseError::InvalidField("version"));
+        }
+        let csrc_count = (buf[0] & 0x0f) as usize;
+        let mut offset = HEADER_LEN + 4 * csrc_count;
+        ensure_len(buf, offset)?;
+        let csrcs = buf[HEADER_LEN..offset]
+            .chunks_exact(4)
+            .map(|csrc| u32::from_be_bytes([csrc[0], csrc[1], csrc[2], csrc[3]]))
+            .collect();
+        let extension = if buf[0] & 0x10 != 0 {
+            ensure_len(buf, offset + 4)?;
+            let length = u16::from_be_bytes([buf[offset + 2], buf[offset + 3]]) as usize * 4;
+            ensure_len(buf, offset + 4 + length)?;
+            let extension = RtpExtension {
+                profile: u16::from_be_bytes([buf[offset], buf[offset + 1]]),
+                data: buf[offset + 4..offset + 4 + length].to_vec(),
+            };
+            offset += 4 + length;
+            Some(extension)
+        } else {
+            None
+        };
+        Ok(Rtp {
+            version,
+            padding: buf[0] & 0x20 != 0,
+            marker: buf[1] & 0x80 != 0,
+            payload_type: buf[1] & 0x7f,
+            sequence_number: u16::from_be_bytes([buf[2], buf[3]]),
+            timestamp: u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]),
+            ssrc: u32::from_be_bytes([buf[8], buf[9], buf[10], buf[11]]),
+            csrcs,
+            extension,
+            payload: buf[offset..].to_vec(),
+        })
+    }
+}
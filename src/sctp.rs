@@ -0,0 +1,565 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::time::{Duration, Instant};
+
+use crate::util::{ParseError, crc32c, ensure_len, read_ipv4};
+
+// SCTP common header (RFC 9260)
+//
+//  0                   1                   2                   3
+//  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |     Source Port Number        |     Destination Port Number   |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                      Verification Tag                         |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                           Checksum                            |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |   Chunk Type  | Chunk  Flags  |        Chunk Length           |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// \                                                               \
+// /                          Chunk Value                          /
+// \                                                               \
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+/// IP protocol number of SCTP.
+pub const IP_PROTOCOL: u8 = 132;
+
+// Chunk types used in this module.
+pub const CHUNK_DATA: u8 = 0;
+pub const CHUNK_SACK: u8 = 3;
+pub const CHUNK_HEARTBEAT: u8 = 4;
+pub const CHUNK_HEARTBEAT_ACK: u8 = 5;
+
+// DATA chunk flags.
+pub const DATA_FLAG_END: u8 = 0x01;
+pub const DATA_FLAG_BEGINNING: u8 = 0x02;
+pub const DATA_FLAG_UNORDERED: u8 = 0x04;
+
+/// Parameter type of the Heartbeat Info TLV.
+pub const PARAM_HEARTBEAT_INFO: u16 = 1;
+
+/// Length of the Heartbeat Info parameter sent by `heartbeat_packet`: type,
+/// length, timestamp and path addresses.
+const HEARTBEAT_INFO_LEN: usize = 20;
+
+/// Error building an SCTP packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SctpError {
+    /// The chunk header and value exceed the 16-bit Chunk Length field.
+    ChunkTooLong { len: usize },
+}
+
+impl fmt::Display for SctpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SctpError::ChunkTooLong { len } => {
+                write!(
+                    f,
+                    "chunk of {len} bytes exceeds the {} bytes of Chunk Length",
+                    u16::MAX
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for SctpError {}
+
+/// A single SCTP chunk. `value` excludes the 4-byte chunk header and padding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SctpChunk {
+    pub chunk_type: u8,
+    pub flags: u8,
+    pub value: Vec<u8>,
+}
+
+impl SctpChunk {
+    /// Constructor to create a new chunk.
+    pub fn new(chunk_type: u8, flags: u8, value: Vec<u8>) -> Self {
+        SctpChunk {
+            chunk_type,
+            flags,
+            value,
+        }
+    }
+
+    /// Builds a DATA chunk carrying a whole user message (B and E set).
+    pub fn data(
+        tsn: u32,
+        stream_id: u16,
+        stream_sequence: u16,
+        payload_protocol_id: u32,
+        user_data: &[u8],
+    ) -> Self {
+        let mut value = Vec::with_capacity(12 + user_data.len());
+        value.extend_from_slice(&tsn.to_be_bytes());
+        value.extend_from_slice(&stream_id.to_be_bytes());
+        value.extend_from_slice(&stream_sequence.to_be_bytes());
+        value.extend_from_slice(&payload_protocol_id.to_be_bytes());
+        value.extend_from_slice(user_data);
+        SctpChunk::new(CHUNK_DATA, DATA_FLAG_BEGINNING | DATA_FLAG_END, value)
+    }
+
+    /// Builds a SACK chunk. Gap ack blocks are (start, end) offsets from
+    /// `cumulative_tsn_ack`.
+    pub fn sack(
+        cumulative_tsn_ack: u32,
+        a_rwnd: u32,
+        gap_ack_blocks: &[(u16, u16)],
+        duplicate_tsns: &[u32],
+    ) -> Self {
+        let mut value =
+            Vec::with_capacity(12 + gap_ack_blocks.len() * 4 + duplicate_tsns.len() * 4);
+        value.extend_from_slice(&cumulative_tsn_ack.to_be_bytes());
+        value.extend_from_slice(&a_rwnd.to_be_bytes());
+        value.extend_from_slice(&(gap_ack_blocks.len() as u16).to_be_bytes());
+        value.extend_from_slice(&(duplicate_tsns.len() as u16).to_be_bytes());
+        for (start, end) in gap_ack_blocks {
+            value.extend_from_slice(&start.to_be_bytes());
+            value.extend_from_slice(&end.to_be_bytes());
+        }
+        for tsn in duplicate_tsns {
+            value.extend_from_slice(&tsn.to_be_bytes());
+        }
+        SctpChunk::new(CHUNK_SACK, 0, value)
+    }
+
+    /// Chunk length as carried in the header (without padding), or an
+    /// error if the value is too long for the field.
+    pub fn length(&self) -> Result<u16, SctpError> {
+        let len = 4 + self.value.len();
+        u16::try_from(len).map_err(|_| SctpError::ChunkTooLong { len })
+    }
+
+    /// Appends the chunk to `bytes`, padded to a 4-byte boundary. Nothing
+    /// is appended if the chunk is too long.
+    pub fn serialize_into(&self, bytes: &mut Vec<u8>) -> Result<(), SctpError> {
+        let length = self.length()?;
+        bytes.push(self.chunk_type);
+        bytes.push(self.flags);
+        bytes.extend_from_slice(&length.to_be_bytes());
+        bytes.extend_from_slice(&self.value);
+        bytes.resize(bytes.len() + padding(self.value.len()), 0);
+        Ok(())
+    }
+
+    /// Parses a chunk, returning it with the number of bytes consumed
+    /// (including padding, when present).
+    pub fn from_bytes(buf: &[u8]) -> Result<(Self, usize), ParseError> {
+        ensure_len(buf, 4)?;
+        let length = u16::from_be_bytes([buf[2], buf[3]]) as usize;
+        if length < 4 {
+            return Err(ParseError::InvalidField("chunk length"));
+        }
+        ensure_len(buf, length)?;
+        let consumed = (length + padding(length)).min(buf.len());
+        Ok((
+            SctpChunk::new(buf[0], buf[1], buf[4..length].to_vec()),
+            consumed,
+        ))
+    }
+}
+
+/// Number of zero bytes needed to pad `len` to a multiple of 4.
+fn padding(len: usize) -> usize {
+    (4 - len % 4) % 4
+}
+
+/// Bundles `chunks` into one packet behind a single common header, each
+/// chunk padded to a 4-byte boundary (RFC 9260 section 3.2), with the
+/// CRC-32C computed over the whole packet. The ports and verification tag
+/// are zero; recompute the checksum with `with_checksum` after setting
+/// them.
+pub fn bundle(chunks: Vec<SctpChunk>) -> Sctp {
+    Sctp::new(0, 0, 0, 0, chunks).with_checksum()
+}
+
+/// SCTP packet: common header followed by chunks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sctp {
+    pub source_port: u16,
+    pub destination_port: u16,
+    pub verification_tag: u32,
+    pub checksum: u32,
+    pub chunks: Vec<SctpChunk>,
+}
+
+impl Sctp {
+    /// Length of the common header in bytes.
+    pub const HEADER_LEN: usize = 12;
+
+    /// Constructor to create a new SCTP packet.
+    pub fn new(
+        source_port: u16,
+        destination_port: u16,
+        verification_tag: u32,
+        checksum: u32,
+        chunks: Vec<SctpChunk>,
+    ) -> Self {
+        Sctp {
+            source_port,
+            destination_port,
+            verification_tag,
+            checksum,
+            chunks,
+        }
+    }
+
+    /// Sets the source port.
+    pub fn set_source_port(mut self, source_port: u16) -> Self {
+        self.source_port = source_port;
+        self
+    }
+
+    /// Sets the destination port.
+    pub fn set_destination_port(mut self, destination_port: u16) -> Self {
+        self.destination_port = destination_port;
+        self
+    }
+
+    /// Sets the verification tag.
+    pub fn set_verification_tag(mut self, verification_tag: u32) -> Self {
+        self.verification_tag = verification_tag;
+        self
+    }
+
+    /// Sets the checksum.
+    pub fn set_checksum(mut self, checksum: u32) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    /// Sets the chunks.
+    pub fn set_chunks(mut self, chunks: Vec<SctpChunk>) -> Self {
+        self.chunks = chunks;
+        self
+    }
+
+    /// Serializes the packet, using the checksum field as is.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a chunk is too long for its Chunk Length field; see
+    /// `try_to_bytes`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.try_to_bytes().unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Serializes the packet, using the checksum field as is, or refuses a
+    /// chunk too long for its Chunk Length field.
+    pub fn try_to_bytes(&self) -> Result<Vec<u8>, SctpError> {
+        let mut bytes = Vec::with_capacity(Self::HEADER_LEN);
+        bytes.extend_from_slice(&self.source_port.to_be_bytes());
+        bytes.extend_from_slice(&self.destination_port.to_be_bytes());
+        bytes.extend_from_slice(&self.verification_tag.to_be_bytes());
+        bytes.extend_from_slice(&self.checksum.to_be_bytes());
+        for chunk in &self.chunks {
+            chunk.serialize_into(&mut bytes)?;
+        }
+        Ok(bytes)
+    }
+
+    /// Parses an SCTP packet and all of its chunks.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, Self::HEADER_LEN)?;
+        let mut chunks = Vec::new();
+        let mut offset = Self::HEADER_LEN;
+        while offset < buf.len() {
+            let (chunk, consumed) = SctpChunk::from_bytes(&buf[offset..])?;
+            chunks.push(chunk);
+            offset += consumed;
+        }
+        Ok(Sctp {
+            source_port: u16::from_be_bytes([buf[0], buf[1]]),
+            destination_port: u16::from_be_bytes([buf[2], buf[3]]),
+            verification_tag: u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]),
+            checksum: u32::from_be_bytes([buf[8], buf[9], buf[10], buf[11]]),
+            chunks,
+        })
+    }
+
+    /// Returns the chunks as a receiver parses them from the serialized
+    /// packet, up to the first malformed one.
+    pub fn unbundle(&self) -> Vec<SctpChunk> {
+        let bytes = self.to_bytes();
+        let mut chunks = Vec::new();
+        let mut offset = Self::HEADER_LEN;
+        while let Ok((chunk, consumed)) = SctpChunk::from_bytes(&bytes[offset..]) {
+            chunks.push(chunk);
+            offset += consumed;
+        }
+        chunks
+    }
+
+    /// Computes the CRC-32C checksum of the packet. The value is returned as
+    /// it reads from the wire, i.e. with the CRC stored in little-endian order.
+    pub fn compute_checksum(&self) -> u32 {
+        let mut bytes = self.to_bytes();
+        bytes[8..12].fill(0);
+        crc32c(&bytes).swap_bytes()
+    }
+
+    /// Returns the packet with its checksum computed.
+    pub fn with_checksum(mut self) -> Self {
+        self.checksum = self.compute_checksum();
+        self
+    }
+}
+
+// --- MULTI-HOMING ---
+
+/// A transport path, as (local address, remote address).
+pub type SctpPath = (Ipv4Addr, Ipv4Addr);
+
+/// RTO.Initial, RTO.Min and RTO.Max from RFC 9260 section 16.
+pub const RTO_INITIAL: Duration = Duration::from_secs(1);
+pub const RTO_MIN: Duration = Duration::from_secs(1);
+pub const RTO_MAX: Duration = Duration::from_secs(60);
+
+/// Path MTU assumed when computing the initial congestion window.
+pub const DEFAULT_PMTU: u32 = 1500;
+
+/// State of a multi-homed SCTP association: the primary and alternate
+/// paths, with per-path retransmission timeout and congestion window.
+#[derive(Debug, Clone)]
+pub struct SctpAssociation {
+    pub source_port: u16,
+    pub destination_port: u16,
+    pub peer_verification_tag: u32,
+    pub primary_path: SctpPath,
+    pub alternate_paths: Vec<SctpPath>,
+    pub rto: HashMap<SctpPath, Duration>,
+    pub cwnd: HashMap<SctpPath, u32>,
+    /// Smoothed RTT and RTT variation per path, once measured.
+    srtt: HashMap<SctpPath, (Duration, Duration)>,
+    /// Reference point of the heartbeat timestamps.
+    epoch: Instant,
+}
+
+impl SctpAssociation {
+    /// Constructor to create an association over `primary_path`.
+    pub fn new(
+        source_port: u16,
+        destination_port: u16,
+        peer_verification_tag: u32,
+        primary_path: SctpPath,
+    ) -> Self {
+        let mut association = SctpAssociation {
+            source_port,
+            destination_port,
+            peer_verification_tag,
+            primary_path,
+            alternate_paths: Vec::new(),
+            rto: HashMap::new(),
+            cwnd: HashMap::new(),
+            srtt: HashMap::new(),
+            epoch: Instant::now(),
+        };
+        association.init_path(primary_path);
+        association
+    }
+
+    /// Adds an alternate path to the association.
+    pub fn add_alternate_path(mut self, path: SctpPath) -> Self {
+        if path != self.primary_path && !self.alternate_paths.contains(&path) {
+            self.alternate_paths.push(path);
+            self.init_path(path);
+        }
+        self
+    }
+
+    /// Sets the initial RTO and congestion window of a path, if unknown.
+    fn init_path(&mut self, path: SctpPath) {
+        self.rto.entry(path).or_insert(RTO_INITIAL);
+        let initial_cwnd = (4 * DEFAULT_PMTU).min((2 * DEFAULT_PMTU).max(4380));
+        self.cwnd.entry(path).or_insert(initial_cwnd);
+    }
+
+    /// Builds a HEARTBEAT probing `path`. The Heartbeat Info parameter holds
+    /// the send timestamp (microseconds since the association was created)
+    /// followed by the path addresses.
+    pub fn heartbeat_packet(&self, path: SctpPath) -> Sctp {
+        let timestamp = self.epoch.elapsed().as_micros() as u64;
+        let mut info = Vec::with_capacity(16);
+        info.extend_from_slice(&timestamp.to_be_bytes());
+        info.extend_from_slice(&path.0.octets());
+        info.extend_from_slice(&path.1.octets());
+
+        let mut value = Vec::with_capacity(4 + info.len());
+        value.extend_from_slice(&PARAM_HEARTBEAT_INFO.to_be_bytes());
+        value.extend_from_slice(&((4 + info.len()) as u16).to_be_bytes());
+        value.extend_from_slice(&info);
+
+        Sctp::new(
+            self.source_port,
+            self.destination_port,
+            self.peer_verification_tag,
+            0,
+            vec![SctpChunk::new(CHUNK_HEARTBEAT, 0, value)],
+        )
+        .with_checksum()
+    }
+
+    /// Measures the round-trip time from a HEARTBEAT ACK echoing one of our
+    /// heartbeats, and updates the RTO of the probed path (RFC 9260 6.3.1).
+    /// Returns `None` if the packet carries no Heartbeat Info of the layout
+    /// `heartbeat_packet` sends, or if it names a path that is neither the
+    /// primary nor an alternate one.
+    pub fn process_heartbeat_ack(&mut self, ack: &Sctp) -> Option<Duration> {
+        let chunk = ack
+            .chunks
+            .iter()
+            .find(|chunk| chunk.chunk_type == CHUNK_HEARTBEAT_ACK)?;
+        let value = &chunk.value;
+        if value.len() < HEARTBEAT_INFO_LEN
+            || u16::from_be_bytes([value[0], value[1]]) != PARAM_HEARTBEAT_INFO
+            || u16::from_be_bytes([value[2], value[3]]) as usize != HEARTBEAT_INFO_LEN
+        {
+            return None;
+        }
+        let mut timestamp = [0u8; 8];
+        timestamp.copy_from_slice(&value[4..12]);
+        let sent = Duration::from_micros(u64::from_be_bytes(timestamp));
+        let path = (read_ipv4(value, 12), read_ipv4(value, 16));
+        if path != self.primary_path && !self.alternate_paths.contains(&path) {
+            return None;
+        }
+        let rtt = self.epoch.elapsed().checked_sub(sent)?;
+        self.update_rto(path, rtt);
+        Some(rtt)
+    }
+
+    /// Updates SRTT, RTTVAR and RTO of `path` with a new measurement.
+    fn update_rto(&mut self, path: SctpPath, rtt: Duration) {
+        let (srtt, rttvar) = match self.srtt.get(&path) {
+            None => (rtt, rtt / 2),
+            Some(&(srtt, rttvar)) => {
+                let delta = srtt.abs_diff(rtt);
+                let rttvar = rttvar * 3 / 4 + delta / 4;
+                (srtt * 7 / 8 + rtt / 8, rttvar)
+            }
+        };
+        self.srtt.insert(path, (srtt, rttvar));
+        let rto = (srtt + rttvar * 4).clamp(RTO_MIN, RTO_MAX);
+        self.rto.insert(path, rto);
+    }
+
+    /// Makes `new_primary` the primary path. The previous primary becomes
+    /// an alternate path.
+    pub fn failover_to(&mut self, new_primary: SctpPath) {
+        if new_primary == self.primary_path {
+            return;
+        }
+        self.alternate_paths.retain(|path| *path != new_primary);
+        let previous = std::mem::replace(&mut self.primary_path, new_primary);
+        self.alternate_paths.insert(0, previous);
+        self.init_path(new_primary);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_data_and_sack_on_the_wire() {
+        let packet = bundle(vec![
+            SctpChunk::data(7, 1, 2, 51, b"hello"),
+            SctpChunk::sack(7, 0x10000, &[], &[]),
+        ])
+        .set_source_port(5000)
+        .set_destination_port(3000)
+        .set_verification_tag(0xdead_beef)
+        .with_checksum();
+
+        #[rustfmt::skip]
+        let expected = [
+            // Common header, CRC-32C over the whole packet.
+            0x13, 0x88, 0x0b, 0xb8,
+            0xde, 0xad, 0xbe, 0xef,
+            0xfb, 0x33, 0xdf, 0x0a,
+            // DATA, B and E set, length 21, then 3 bytes of padding.
+            0x00, 0x03, 0x00, 0x15,
+            0x00, 0x00, 0x00, 0x07,
+            0x00, 0x01, 0x00, 0x02,
+            0x00, 0x00, 0x00, 0x33,
+            b'h', b'e', b'l', b'l',
+            b'o', 0x00, 0x00, 0x00,
+            // SACK, length 16, no gap blocks or duplicates.
+            0x03, 0x00, 0x00, 0x10,
+            0x00, 0x00, 0x00, 0x07,
+            0x00, 0x01, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00,
+        ];
+        assert_eq!(packet.to_bytes(), expected);
+
+        let parsed = Sctp::from_bytes(&expected).unwrap();
+        assert_eq!(parsed, packet);
+        assert_eq!(parsed.compute_checksum(), parsed.checksum);
+        assert_eq!(packet.unbundle(), packet.chunks);
+    }
+
+    const PRIMARY: SctpPath = (Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 1, 1));
+    const ALTERNATE: SctpPath = (Ipv4Addr::new(10, 0, 0, 2), Ipv4Addr::new(10, 0, 1, 2));
+
+    /// The HEARTBEAT ACK a peer sends back for `heartbeat`.
+    fn echo(heartbeat: &Sctp) -> Sctp {
+        let mut ack = heartbeat.clone();
+        ack.chunks[0].chunk_type = CHUNK_HEARTBEAT_ACK;
+        ack
+    }
+
+    #[test]
+    fn heartbeat_ack_measures_configured_paths() {
+        let mut association =
+            SctpAssociation::new(5000, 5000, 1, PRIMARY).add_alternate_path(ALTERNATE);
+        for path in [PRIMARY, ALTERNATE] {
+            let ack = echo(&association.heartbeat_packet(path));
+            assert!(association.process_heartbeat_ack(&ack).is_some());
+            assert_eq!(association.rto[&path], RTO_MIN);
+        }
+    }
+
+    #[test]
+    fn heartbeat_ack_for_unknown_path_is_ignored() {
+        let other = SctpAssociation::new(5000, 5000, 1, PRIMARY).add_alternate_path(ALTERNATE);
+        let mut association = SctpAssociation::new(5000, 5000, 1, PRIMARY);
+        let ack = echo(&other.heartbeat_packet(ALTERNATE));
+        assert_eq!(association.process_heartbeat_ack(&ack), None);
+        assert!(!association.rto.contains_key(&ALTERNATE));
+    }
+
+    #[test]
+    fn heartbeat_info_length_must_be_20() {
+        let mut association = SctpAssociation::new(5000, 5000, 1, PRIMARY);
+        let mut ack = echo(&association.heartbeat_packet(PRIMARY));
+        ack.chunks[0].value.extend_from_slice(&[0; 4]);
+        ack.chunks[0].value[3] = 24;
+        assert_eq!(association.process_heartbeat_ack(&ack), None);
+        ack.chunks[0].value.truncate(20);
+        ack.chunks[0].value[3] = 16;
+        assert_eq!(association.process_heartbeat_ack(&ack), None);
+        ack.chunks[0].value[3] = 20;
+        assert!(association.process_heartbeat_ack(&ack).is_some());
+    }
+
+    #[test]
+    fn oversized_chunk_is_refused() {
+        let fits = SctpChunk::new(CHUNK_DATA, 0, vec![0; 65531]);
+        assert_eq!(fits.length(), Ok(u16::MAX));
+        let chunk = SctpChunk::new(CHUNK_DATA, 0, vec![0; 65532]);
+        assert_eq!(chunk.length(), Err(SctpError::ChunkTooLong { len: 65536 }));
+        let mut bytes = Vec::new();
+        assert!(chunk.serialize_into(&mut bytes).is_err());
+        assert!(bytes.is_empty());
+
+        let packet = Sctp::new(1, 2, 3, 0, vec![chunk]);
+        assert_eq!(
+            packet.try_to_bytes(),
+            Err(SctpError::ChunkTooLong { len: 65536 })
+        );
+        assert!(std::panic::catch_unwind(|| packet.to_bytes()).is_err());
+    }
+}
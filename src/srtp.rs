@@ -0,0 +1,271 @@
+use std::fmt;
+
+use aes::Aes128;
+use ctr::Ctr128BE;
+use ctr::cipher::{KeyIvInit, StreamCipher};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+
+use crate::rtp::Rtp;
+use crate::util::ParseError;
+
+// SRTP packet (RFC 3711 section 3.1), AES_CM_128_HMAC_SHA1_80 profile:
+//
+// +----------------------------+------------------------+----------+
+// | RTP header (in the clear)  | payload (AES-CM)       | auth tag |
+// +----------------------------+------------------------+----------+
+//
+// The tag is the HMAC-SHA1 of the header, the encrypted payload and the
+// 32-bit rollover counter, truncated to 80 bits. The keystream of a packet
+// starts at IV = salt * 2^16 XOR SSRC * 2^64 XOR index * 2^16, where the
+// 48-bit index is ROC * 2^16 + SEQ.
+
+/// Length of the master and session cipher keys in bytes.
+pub const KEY_LEN: usize = 16;
+
+/// Length of the master and session salts in bytes.
+pub const SALT_LEN: usize = 14;
+
+/// Length of the session authentication key in bytes.
+pub const AUTH_KEY_LEN: usize = 20;
+
+/// Length of the HMAC-SHA1-80 authentication tag in bytes.
+pub const AUTH_TAG_LEN: usize = 10;
+
+// Key derivation labels (RFC 3711 section 4.3.2).
+const LABEL_CIPHER_KEY: u8 = 0x00;
+const LABEL_AUTH_KEY: u8 = 0x01;
+const LABEL_SALT: u8 = 0x02;
+
+/// Error returned when an SRTP packet cannot be unprotected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SrtpError {
+    /// The authentication tag does not match the packet.
+    AuthenticationFailed,
+    /// The packet belongs to another SSRC than the context.
+    SsrcMismatch { expected: u32, found: u32 },
+    /// The packet is too short or its RTP header is malformed.
+    Parse(ParseError),
+}
+
+impl fmt::Display for SrtpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SrtpError::AuthenticationFailed => write!(f, "SRTP authentication failed"),
+            SrtpError::SsrcMismatch { expected, found } => {
+                write!(
+                    f,
+                    "SRTP packet for SSRC {found:#010x}, expected {expected:#010x}"
+                )
+            }
+            SrtpError::Parse(err) => write!(f, "invalid SRTP packet: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for SrtpError {}
+
+impl From<ParseError> for SrtpError {
+    fn from(err: ParseError) -> Self {
+        SrtpError::Parse(err)
+    }
+}
+
+/// Cryptographic context of one SRTP stream (one SSRC and direction), with
+/// the AES_CM_128_HMAC_SHA1_80 profile and a key derivation rate of zero.
+///
+/// The context tracks the rollover counter across sequence number wraps.
+/// Replay protection is not implemented.
+#[derive(Clone)]
+pub struct SrtpCryptoContext {
+    pub ssrc: u32,
+    cipher_key: [u8; KEY_LEN],
+    salt: [u8; SALT_LEN],
+    auth_key: [u8; AUTH_KEY_LEN],
+    roc: u32,
+    highest_sequence: Option<u16>,
+}
+
+impl fmt::Debug for SrtpCryptoContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SrtpCryptoContext")
+            .field("ssrc", &self.ssrc)
+            .field("roc", &self.roc)
+            .field("highest_sequence", &self.highest_sequence)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SrtpCryptoContext {
+    /// Derives the session keys from the master key and salt.
+    pub fn new(master_key: [u8; KEY_LEN], master_salt: [u8; SALT_LEN], ssrc: u32) -> Self {
+        let mut cipher_key = [0u8; KEY_LEN];
+        let mut salt = [0u8; SALT_LEN];
+        let mut auth_key = [0u8; AUTH_KEY_LEN];
+        derive_key(&master_key, &master_salt, LABEL_CIPHER_KEY, &mut cipher_key);
+        derive_key(&master_key, &master_salt, LABEL_SALT, &mut salt);
+        derive_key(&master_key, &master_salt, LABEL_AUTH_KEY, &mut auth_key);
+        SrtpCryptoContext {
+            ssrc,
+            cipher_key,
+            salt,
+            auth_key,
+            roc: 0,
+            highest_sequence: None,
+        }
+    }
+
+    /// Returns the rollover counter.
+    pub fn get_roc(&self) -> u32 {
+        self.roc
+    }
+
+    /// Protects `rtp`: encrypts its payload and appends the auth tag.
+    pub fn encrypt(&mut self, rtp: &Rtp) -> Vec<u8> {
+        let roc = self.estimate_roc(rtp.sequence_number);
+        let mut bytes = rtp.to_bytes();
+        // Only the payload is encrypted; the header ends where it starts.
+        let header_len = bytes.len() - rtp.payload.len();
+        self.apply_keystream(rtp.ssrc, roc, rtp.sequence_number, &mut bytes[header_len..]);
+        let tag = self.auth_tag(&bytes, roc);
+        bytes.extend_from_slice(&tag[..AUTH_TAG_LEN]);
+        self.update_roc(roc, rtp.sequence_number);
+        bytes
+    }
+
+    /// Unprotects an SRTP packet: verifies its auth tag, then decrypts it.
+    pub fn decrypt(&mut self, data: &[u8]) -> Result<Rtp, SrtpError> {
+        let authenticated_len =
+            data.len()
+                .checked_sub(AUTH_TAG_LEN)
+                .ok_or(ParseError::Truncated {
+                    needed: AUTH_TAG_LEN,
+                    available: data.len(),
+                })?;
+        let (authenticated, tag) = data.split_at(authenticated_len);
+        let mut rtp = Rtp::from_bytes(authenticated)?;
+        if rtp.ssrc != self.ssrc {
+            return Err(SrtpError::SsrcMismatch {
+                expected: self.ssrc,
+                found: rtp.ssrc,
+            });
+        }
+        let roc = self.estimate_roc(rtp.sequence_number);
+        let mut mac = self.mac();
+        mac.update(authenticated);
+        mac.update(&roc.to_be_bytes());
+        mac.verify_truncated_left(tag)
+            .map_err(|_| SrtpError::AuthenticationFailed)?;
+        self.apply_keystream(rtp.ssrc, roc, rtp.sequence_number, &mut rtp.payload);
+        self.update_roc(roc, rtp.sequence_number);
+        Ok(rtp)
+    }
+
+    /// Guesses the rollover counter of a packet from its sequence number
+    /// (RFC 3711 section 3.3.1).
+    fn estimate_roc(&self, sequence: u16) -> u32 {
+        let Some(highest) = self.highest_sequence else {
+            return self.roc;
+        };
+        if highest < 0x8000 {
+            if sequence > highest && sequence - highest > 0x8000 {
+                return self.roc.wrapping_sub(1);
+            }
+        } else if highest - 0x8000 > sequence {
+            return self.roc.wrapping_add(1);
+        }
+        self.roc
+    }
+
+    fn update_roc(&mut self, roc: u32, sequence: u16) {
+        match self.highest_sequence {
+            Some(highest) if roc == self.roc && sequence <= highest => {}
+            _ if roc == self.roc || roc == self.roc.wrapping_add(1) => {
+                self.roc = roc;
+                self.highest_sequence = Some(sequence);
+            }
+            _ => {}
+        }
+    }
+
+    fn apply_keystream(&self, ssrc: u32, roc: u32, sequence: u16, payload: &mut [u8]) {
+        let mut iv = [0u8; 16];
+        iv[..SALT_LEN].copy_from_slice(&self.salt);
+        for (byte, value) in iv[4..8].iter_mut().zip(ssrc.to_be_bytes()) {
+            *byte ^= value;
+        }
+        let index = (roc as u64) << 16 | sequence as u64;
+        for (byte, value) in iv[8..14].iter_mut().zip(&index.to_be_bytes()[2..]) {
+            *byte ^= value;
+        }
+        Ctr128BE::<Aes128>::new(&self.cipher_key.into(), &iv.into()).apply_keystream(payload);
+    }
+
+    fn mac(&self) -> Hmac<Sha1> {
+        Hmac::<Sha1>::new_from_slice(&self.auth_key).expect("HMAC accepts keys of any length")
+    }
+
+    fn auth_tag(&self, authenticated: &[u8], roc: u32) -> [u8; AUTH_KEY_LEN] {
+        let mut mac = self.mac();
+        mac.update(authenticated);
+        mac.update(&roc.to_be_bytes());
+        mac.finalize().into_bytes().into()
+    }
+}
+
+/// SRTP key derivation with a rate of zero (RFC 3711 section 4.3.1): the
+/// AES-CM keystream of the master key from IV = (label * 2^48 XOR salt) *
+/// 2^16.
+fn derive_key(master_key: &[u8; KEY_LEN], master_salt: &[u8; SALT_LEN], label: u8, out: &mut [u8]) {
+    let mut iv = [0u8; 16];
+    iv[..SALT_LEN].copy_from_slice(master_salt);
+    iv[7] ^= label;
+    out.fill(0);
+    Ctr128BE::<Aes128>::new(master_key.into(), &iv.into()).apply_keystream(out);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtp::RtpExtension;
+
+    const SSRC: u32 = 0xcafe_babe;
+
+    fn context() -> SrtpCryptoContext {
+        SrtpCryptoContext::new([0x2b; KEY_LEN], [0x5c; SALT_LEN], SSRC)
+    }
+
+    #[test]
+    fn unaligned_extension_stays_in_the_clear() {
+        let rtp = Rtp::new(96, 1, 160, SSRC, b"payload bytes".to_vec()).set_extension(Some(
+            RtpExtension {
+                profile: 0xbede,
+                data: vec![0x10, 0xaa, 0x21, 0xbb, 0xcc],
+            },
+        ));
+        let plain = rtp.to_bytes();
+        assert_eq!(rtp.header_len(), 12 + 4 + 8);
+        assert_eq!(plain.len(), rtp.header_len() + rtp.payload.len());
+
+        let protected = context().encrypt(&rtp);
+        let header_len = rtp.header_len();
+        assert_eq!(protected[..header_len], plain[..header_len]);
+        assert_ne!(protected[header_len..plain.len()], plain[header_len..]);
+
+        let decrypted = context().decrypt(&protected).unwrap();
+        assert_eq!(decrypted.payload, rtp.payload);
+        assert_eq!(decrypted.to_bytes(), plain);
+    }
+
+    #[test]
+    fn tampered_packet_is_rejected() {
+        let rtp = Rtp::new(96, 7, 160, SSRC, vec![1, 2, 3, 4]);
+        let mut protected = context().encrypt(&rtp);
+        assert_eq!(context().decrypt(&protected).unwrap(), rtp);
+        protected[12] ^= 1;
+        assert_eq!(
+            context().decrypt(&protected),
+            Err(SrtpError::AuthenticationFailed)
+        );
+    }
+}
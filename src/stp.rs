@@ -0,0 +1,331 @@
+use crate::ethernet::{Ethernet, MacAddr, read_mac};
+use crate::llc::{LlcFrame, SAP_STP, STP_MULTICAST};
+use crate::util::{ParseError, ensure_len};
+
+// BPDUs (IEEE 802.1D-2004 clause 9.3), carried in LLC UI frames between
+// SAPs 0x42 to the group address 01:80:c2:00:00:00:
+//
+// +----------------------+--------+---------------------------------+
+// | Protocol Identifier  | 2      | 0                               |
+// | Protocol Version     | 1      | 0 STP, 2 RSTP                   |
+// | BPDU Type            | 1      | 0x00 Config, 0x80 TCN, 0x02 RST |
+// | Flags                | 1      | Config and RST only             |
+// | Root Identifier      | 8      |                                 |
+// | Root Path Cost       | 4      |                                 |
+// | Bridge Identifier    | 8      |                                 |
+// | Port Identifier      | 2      |                                 |
+// | Message Age          | 2      |                                 |
+// | Max Age              | 2      |                                 |
+// | Hello Time           | 2      |                                 |
+// | Forward Delay        | 2      |                                 |
+// | Version 1 Length     | 1      | RST only, 0                     |
+// +----------------------+--------+---------------------------------+
+//
+// A TCN BPDU stops after the BPDU type. Timers are in 1/256 seconds.
+// Bridge identifiers are a 4-bit priority, a 12-bit system ID extension
+// (the VLAN for PVST and MSTP) and a MAC address; port identifiers a
+// 4-bit priority and a 12-bit port number.
+
+// Protocol versions.
+pub const VERSION_STP: u8 = 0;
+pub const VERSION_RSTP: u8 = 2;
+
+// BPDU types.
+pub const TYPE_CONFIG: u8 = 0x00;
+pub const TYPE_RST: u8 = 0x02;
+pub const TYPE_TCN: u8 = 0x80;
+
+// Flags.
+pub const FLAG_TOPOLOGY_CHANGE: u8 = 0x01;
+pub const FLAG_PROPOSAL: u8 = 0x02;
+pub const FLAG_LEARNING: u8 = 0x10;
+pub const FLAG_FORWARDING: u8 = 0x20;
+pub const FLAG_AGREEMENT: u8 = 0x40;
+pub const FLAG_TOPOLOGY_CHANGE_ACK: u8 = 0x80;
+
+/// Length of a Configuration BPDU in bytes.
+pub const CONFIG_BPDU_LEN: usize = 35;
+
+/// Length of an RST BPDU in bytes.
+pub const RST_BPDU_LEN: usize = 36;
+
+/// Length of a TCN BPDU in bytes.
+pub const TCN_BPDU_LEN: usize = 4;
+
+// Default timers in seconds (IEEE 802.1D-2004 table 17-1).
+pub const DEFAULT_MAX_AGE: u16 = 20;
+pub const DEFAULT_HELLO_TIME: u16 = 2;
+pub const DEFAULT_FORWARD_DELAY: u16 = 15;
+
+// Default bridge and port priorities.
+pub const DEFAULT_BRIDGE_PRIORITY: u16 = 32768;
+pub const DEFAULT_PORT_PRIORITY: u8 = 128;
+
+/// Converts seconds to the 1/256 second units of BPDU timers.
+pub fn timer_from_seconds(seconds: u16) -> u16 {
+    seconds.saturating_mul(256)
+}
+
+/// Bridge identifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BridgeId {
+    /// Priority in its upper 4 bits, system ID extension in the lower 12.
+    pub priority: u16,
+    pub address: MacAddr,
+}
+
+impl BridgeId {
+    /// Constructor to create an identifier from its priority, a multiple of
+    /// 4096, the system ID extension and the bridge address.
+    pub fn new(priority: u16, system_id_extension: u16, address: MacAddr) -> Self {
+        BridgeId {
+            priority: priority & 0xf000 | system_id_extension & 0x0fff,
+            address,
+        }
+    }
+
+    pub fn get_system_id_extension(&self) -> u16 {
+        self.priority & 0x0fff
+    }
+
+    // --- SERIALIZATION ---
+
+    pub fn to_bytes(&self) -> [u8; 8] {
+        let mut bytes = [0u8; 8];
+        bytes[..2].copy_from_slice(&self.priority.to_be_bytes());
+        bytes[2..].copy_from_slice(&self.address.octets());
+        bytes
+    }
+
+    /// Parses an identifier. The caller must have checked the length
+    /// beforehand.
+    fn read(buf: &[u8], offset: usize) -> Self {
+        BridgeId {
+            priority: u16::from_be_bytes([buf[offset], buf[offset + 1]]),
+            address: read_mac(buf, offset + 2),
+        }
+    }
+}
+
+/// Port identifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PortId {
+    /// Priority, a multiple of 16.
+    pub priority: u8,
+    /// Port number (12 bits).
+    pub number: u16,
+}
+
+impl PortId {
+    /// Constructor to create a new port identifier.
+    pub fn new(priority: u8, number: u16) -> Self {
+        PortId {
+            priority: priority & 0xf0,
+            number: number & 0x0fff,
+        }
+    }
+
+    pub fn to_u16(&self) -> u16 {
+        (self.priority as u16 & 0xf0) << 8 | self.number & 0x0fff
+    }
+
+    pub fn from_u16(value: u16) -> Self {
+        PortId::new((value >> 8) as u8, value)
+    }
+}
+
+/// Role of the sending port, encoded in the flags of RST BPDUs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PortRole {
+    Unknown = 0,
+    AlternateOrBackup = 1,
+    Root = 2,
+    Designated = 3,
+}
+
+impl From<u8> for PortRole {
+    /// Decodes the role bits of the flags.
+    fn from(flags: u8) -> Self {
+        match flags >> 2 & 0x03 {
+            1 => PortRole::AlternateOrBackup,
+            2 => PortRole::Root,
+            3 => PortRole::Designated,
+            _ => PortRole::Unknown,
+        }
+    }
+}
+
+/// Configuration BPDU of STP, or RST BPDU when the version is RSTP.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ConfigBpdu {
+    pub version: u8,
+    pub flags: u8,
+    pub root_id: BridgeId,
+    pub root_path_cost: u32,
+    pub bridge_id: BridgeId,
+    pub port_id: PortId,
+    /// Timers, in 1/256 seconds.
+    pub message_age: u16,
+    pub max_age: u16,
+    pub hello_time: u16,
+    pub forward_delay: u16,
+}
+
+impl ConfigBpdu {
+    /// Constructor to create an STP Configuration BPDU with no flags, a
+    /// message age of 0 and the default timers.
+    pub fn new(
+        root_id: BridgeId,
+        root_path_cost: u32,
+        bridge_id: BridgeId,
+        port_id: PortId,
+    ) -> Self {
+        ConfigBpdu {
+            version: VERSION_STP,
+            flags: 0,
+            root_id,
+            root_path_cost,
+            bridge_id,
+            port_id,
+            message_age: 0,
+            max_age: timer_from_seconds(DEFAULT_MAX_AGE),
+            hello_time: timer_from_seconds(DEFAULT_HELLO_TIME),
+            forward_delay: timer_from_seconds(DEFAULT_FORWARD_DELAY),
+        }
+    }
+
+    /// Configuration BPDU of a root bridge, sent on its designated port
+    /// `port_id`.
+    pub fn root(bridge_id: BridgeId, port_id: PortId) -> Self {
+        ConfigBpdu::new(bridge_id, 0, bridge_id, port_id)
+    }
+
+    /// Returns true for RST BPDUs.
+    pub fn is_rstp(&self) -> bool {
+        self.version >= VERSION_RSTP
+    }
+
+    /// Returns true if all of `flags` are set.
+    pub fn has_flags(&self, flags: u8) -> bool {
+        self.flags & flags == flags
+    }
+
+    pub fn get_port_role(&self) -> PortRole {
+        PortRole::from(self.flags)
+    }
+
+    // --- SETTER METHODS ---
+
+    /// Makes the BPDU an RST BPDU.
+    pub fn set_rstp(mut self) -> Self {
+        self.version = VERSION_RSTP;
+        self
+    }
+
+    pub fn set_flags(mut self, flags: u8) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Sets the port role bits of the flags.
+    pub fn set_port_role(mut self, role: PortRole) -> Self {
+        self.flags = self.flags & !0x0c | (role as u8) << 2;
+        self
+    }
+
+    pub fn set_message_age(mut self, message_age: u16) -> Self {
+        self.message_age = message_age;
+        self
+    }
+
+    /// Sets the Max Age, Hello Time and Forward Delay, in seconds.
+    pub fn set_timers(mut self, max_age: u16, hello_time: u16, forward_delay: u16) -> Self {
+        self.max_age = timer_from_seconds(max_age);
+        self.hello_time = timer_from_seconds(hello_time);
+        self.forward_delay = timer_from_seconds(forward_delay);
+        self
+    }
+}
+
+/// A BPDU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Bpdu {
+    Config(ConfigBpdu),
+    /// Topology Change Notification, sent towards the root.
+    Tcn,
+}
+
+impl Bpdu {
+    /// Wraps the BPDU in an LLC frame to the STP group address.
+    pub fn to_ethernet(&self, source: MacAddr) -> Ethernet {
+        LlcFrame::ui(SAP_STP, self.to_bytes()).to_ethernet(STP_MULTICAST, source)
+    }
+
+    /// Parses the BPDU of an 802.3 frame.
+    pub fn from_ethernet(frame: &Ethernet) -> Result<Self, ParseError> {
+        let llc = LlcFrame::from_ethernet(frame)?;
+        if llc.llc.dsap != SAP_STP {
+            return Err(ParseError::InvalidField("DSAP"));
+        }
+        Bpdu::from_bytes(&llc.payload)
+    }
+
+    // --- SERIALIZATION ---
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let config = match self {
+            Bpdu::Config(config) => config,
+            Bpdu::Tcn => return vec![0, 0, VERSION_STP, TYPE_TCN],
+        };
+        let mut bytes = Vec::with_capacity(RST_BPDU_LEN);
+        bytes.extend_from_slice(&[0, 0, config.version]);
+        bytes.push(if config.is_rstp() {
+            TYPE_RST
+        } else {
+            TYPE_CONFIG
+        });
+        bytes.push(config.flags);
+        bytes.extend_from_slice(&config.root_id.to_bytes());
+        bytes.extend_from_slice(&config.root_path_cost.to_be_bytes());
+        bytes.extend_from_slice(&config.bridge_id.to_bytes());
+        bytes.extend_from_slice(&config.port_id.to_u16().to_be_bytes());
+        for timer in [
+            config.message_age,
+            config.max_age,
+            config.hello_time,
+            config.forward_delay,
+        ] {
+            bytes.extend_from_slice(&timer.to_be_bytes());
+        }
+        if config.is_rstp() {
+            bytes.push(0);
+        }
+        bytes
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, TCN_BPDU_LEN)?;
+        if buf[..2] != [0, 0] {
+            return Err(ParseError::InvalidField("protocol identifier"));
+        }
+        match buf[3] {
+            TYPE_TCN => return Ok(Bpdu::Tcn),
+            TYPE_CONFIG | TYPE_RST => {}
+            _ => return Err(ParseError::InvalidField("BPDU type")),
+        }
+        ensure_len(buf, CONFIG_BPDU_LEN)?;
+        let u16_at = |offset: usize| u16::from_be_bytes([buf[offset], buf[offset + 1]]);
+        Ok(Bpdu::Config(ConfigBpdu {
+            version: buf[2],
+            flags: buf[4],
+            root_id: BridgeId::read(buf, 5),
+            root_path_cost: u32::from_be_bytes([buf[13], buf[14], buf[15], buf[16]]),
+            bridge_id: BridgeId::read(buf, 17),
+            port_id: PortId::from_u16(u16_at(25)),
+            message_age: u16_at(27),
+            max_age: u16_at(29),
+            hello_time: u16_at(31),
+            forward_delay: u16_at(33),
+        }))
+    }
+}
@@ -1,4 +1,20 @@
-use std::{net::Ipv4Addr, u8};
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::net::Ipv4Addr;
+use std::time::SystemTime;
+
+use crate::ipv4::IPv4;
+use crate::limits::{Limit, Limits};
+use crate::render::{FieldSpec, ascii_diagram};
+use crate::util::{ParseError, PseudoHeader, ensure_len};
+
+pub mod ecn;
+pub mod negotiate;
+pub mod responder;
+pub mod timestamps;
+
+use negotiate::ConnectionOptions;
+use timestamps::TcpTimestampState;
 
 // +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
 // |          Source Port          |       Destination Port        |
@@ -18,6 +34,207 @@ use std::{net::Ipv4Addr, u8};
 // |                             data                              |
 // +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
 
+/// IP protocol number (and IPv6 next header) of TCP.
+pub const IP_PROTOCOL: u8 = 6;
+
+/// TCP control flags, as stored in the `flags` field.
+pub mod flags {
+    pub const FIN: u16 = 0x001;
+    pub const SYN: u16 = 0x002;
+    pub const RST: u16 = 0x004;
+    pub const PSH: u16 = 0x008;
+    pub const ACK: u16 = 0x010;
+    pub const URG: u16 = 0x020;
+    pub const ECE: u16 = 0x040;
+    pub const CWR: u16 = 0x080;
+    pub const NS: u16 = 0x100;
+}
+
+crate::bitfields! {
+    /// Bytes 12 and 13 of the header: data offset (in 32-bit words),
+    /// reserved bits and control flags, NS included.
+    pub struct OffsetAndFlags([u8; 2]) {
+        get_data_offset, set_data_offset: u8 = 0, 4;
+        get_reserved, set_reserved: u8 = 4, 3;
+        get_flags, set_flags: u16 = 7, 9;
+    }
+}
+
+impl OffsetAndFlags {
+    /// Reads the field from a serialized header, or returns `None` if
+    /// the header is shorter than 14 bytes.
+    pub fn from_header(header: &[u8]) -> Option<Self> {
+        Some(OffsetAndFlags([*header.get(12)?, *header.get(13)?]))
+    }
+}
+
+/// Window size advertised by the segments generated in this module.
+pub const DEFAULT_WINDOW_SIZE: u16 = 65535;
+
+/// Layout of the TCP header, as drawn by `TCP::header_diagram`.
+pub const HEADER_FIELDS: &[FieldSpec<'static>] = &[
+    FieldSpec::new("Source Port", 16),
+    FieldSpec::new("Destination Port", 16),
+    FieldSpec::new("Sequence Number", 32),
+    FieldSpec::new("Acknowledgment Number", 32),
+    FieldSpec::new("Data Offset", 4),
+    FieldSpec::new("Reserved", 3),
+    FieldSpec::new("NS", 1),
+    FieldSpec::new("CWR", 1),
+    FieldSpec::new("ECE", 1),
+    FieldSpec::new("URG", 1),
+    FieldSpec::new("ACK", 1),
+    FieldSpec::new("PSH", 1),
+    FieldSpec::new("RST", 1),
+    FieldSpec::new("SYN", 1),
+    FieldSpec::new("FIN", 1),
+    FieldSpec::new("Window", 16),
+    FieldSpec::new("Checksum", 16),
+    FieldSpec::new("Urgent Pointer", 16),
+    FieldSpec::new("Options", 24),
+    FieldSpec::new("Padding", 8),
+    FieldSpec::new("data", 32),
+];
+
+// Option kinds (IANA "TCP Option Kind Numbers").
+pub const OPTION_END_OF_LIST: u8 = 0;
+pub const OPTION_NOP: u8 = 1;
+pub const OPTION_MSS: u8 = 2;
+pub const OPTION_WINDOW_SCALE: u8 = 3;
+pub const OPTION_SACK_PERMITTED: u8 = 4;
+pub const OPTION_SACK: u8 = 5;
+pub const OPTION_TIMESTAMPS: u8 = 8;
+
+/// A TCP option.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TcpOption {
+    EndOfList,
+    Nop,
+    Mss(u16),
+    WindowScale(u8),
+    SackPermitted,
+    /// SACK blocks as (left edge, right edge).
+    Sack(Vec<(u32, u32)>),
+    Timestamps {
+        tsval: u32,
+        tsecr: u32,
+    },
+    /// Any other option; `data` excludes the kind and length octets.
+    Unknown {
+        kind: u8,
+        data: Vec<u8>,
+    },
+}
+
+impl TcpOption {
+    /// Appends the option to `bytes`.
+    pub fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        match self {
+            TcpOption::EndOfList => bytes.push(OPTION_END_OF_LIST),
+            TcpOption::Nop => bytes.push(OPTION_NOP),
+            TcpOption::Mss(mss) => {
+                bytes.extend_from_slice(&[OPTION_MSS, 4]);
+                bytes.extend_from_slice(&mss.to_be_bytes());
+            }
+            TcpOption::WindowScale(shift) => {
+                bytes.extend_from_slice(&[OPTION_WINDOW_SCALE, 3, *shift])
+            }
+            TcpOption::SackPermitted => bytes.extend_from_slice(&[OPTION_SACK_PERMITTED, 2]),
+            TcpOption::Sack(blocks) => {
+                bytes.extend_from_slice(&[OPTION_SACK, (2 + 8 * blocks.len()) as u8]);
+                for (left, right) in blocks {
+                    bytes.extend_from_slice(&left.to_be_bytes());
+                    bytes.extend_from_slice(&right.to_be_bytes());
+                }
+            }
+            TcpOption::Timestamps { tsval, tsecr } => {
+                bytes.extend_from_slice(&[OPTION_TIMESTAMPS, 10]);
+                bytes.extend_from_slice(&tsval.to_be_bytes());
+                bytes.extend_from_slice(&tsecr.to_be_bytes());
+            }
+            TcpOption::Unknown { kind, data } => {
+                bytes.extend_from_slice(&[*kind, (2 + data.len()) as u8]);
+                bytes.extend_from_slice(data);
+            }
+        }
+    }
+
+    /// Serializes a list of options, without padding.
+    pub fn serialize_all(options: &[TcpOption]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for option in options {
+            option.serialize_into(&mut bytes);
+        }
+        bytes
+    }
+
+    /// Parses an option, returning it with the number of bytes consumed.
+    pub fn from_bytes(buf: &[u8]) -> Result<(Self, usize), ParseError> {
+        ensure_len(buf, 1)?;
+        match buf[0] {
+            OPTION_END_OF_LIST => return Ok((TcpOption::EndOfList, 1)),
+            OPTION_NOP => return Ok((TcpOption::Nop, 1)),
+            _ => {}
+        }
+        ensure_len(buf, 2)?;
+        let len = buf[1] as usize;
+        if len < 2 {
+            return Err(ParseError::InvalidField("option length"));
+        }
+        ensure_len(buf, len)?;
+        let data = &buf[2..len];
+        let option = match (buf[0], data.len()) {
+            (OPTION_MSS, 2) => TcpOption::Mss(u16::from_be_bytes([data[0], data[1]])),
+            (OPTION_WINDOW_SCALE, 1) => TcpOption::WindowScale(data[0]),
+            (OPTION_SACK_PERMITTED, 0) => TcpOption::SackPermitted,
+            (OPTION_SACK, n) if n % 8 == 0 => TcpOption::Sack(
+                data.chunks_exact(8)
+                    .map(|block| {
+                        (
+                            u32::from_be_bytes([block[0], block[1], block[2], block[3]]),
+                            u32::from_be_bytes([block[4], block[5], block[6], block[7]]),
+                        )
+                    })
+                    .collect(),
+            ),
+            (OPTION_TIMESTAMPS, 8) => TcpOption::Timestamps {
+                tsval: u32::from_be_bytes([data[0], data[1], data[2], data[3]]),
+                tsecr: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+            },
+            (kind, _) => TcpOption::Unknown {
+                kind,
+                data: data.to_vec(),
+            },
+        };
+        Ok((option, len))
+    }
+
+    /// Parses an option list up to and excluding End of Option List.
+    pub fn parse_all(buf: &[u8]) -> Result<Vec<TcpOption>, ParseError> {
+        TcpOption::parse_all_with_limits(buf, &Limits::default())
+    }
+
+    /// Same as `parse_all`, failing after `limits.max_options_per_layer`
+    /// options.
+    pub fn parse_all_with_limits(
+        buf: &[u8],
+        limits: &Limits,
+    ) -> Result<Vec<TcpOption>, ParseError> {
+        let mut options = Vec::new();
+        let mut offset = 0;
+        while offset < buf.len() {
+            let (option, len) = TcpOption::from_bytes(&buf[offset..])?;
+            if option == TcpOption::EndOfList {
+                break;
+            }
+            limits.check(Limit::OptionsPerLayer, options.len() + 1)?;
+            options.push(option);
+            offset += len;
+        }
+        Ok(options)
+    }
+}
+
 /// |----------------------|-------------|----------------------------------------------------------------------------------|
 /// | Field                | Size (bits) | Description                                                                      |
 /// |----------------------|-------------|----------------------------------------------------------------------------------|
@@ -35,12 +252,12 @@ use std::{net::Ipv4Addr, u8};
 /// | Padding              | Variable    | Added to ensure the header is a multiple of 32 bits in length.                   |
 /// | Data                 | Variable    | Contains the application data being transmitted.                                 |
 /// |----------------------|-------------|----------------------------------------------------------------------------------|
-
+///
 /// Header TCP
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TCP {
-    pub source: Ipv4Addr,
-    pub destination: Ipv4Addr,
+    pub source: u16,
+    pub destination: u16,
     pub sequence: u32,
     pub acknowledgment: u32,
     pub data_offset: u8,
@@ -59,9 +276,10 @@ pub struct TCP {
 impl TCP {
     /// Constructor to create a new instance of a TCP packet.
     /// All fields must be provided at creation time.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        source: Ipv4Addr,
-        destination: Ipv4Addr,
+        source: u16,
+        destination: u16,
         sequence: u32,
         acknowledgment: u32,
         data_offset: u8,
@@ -93,13 +311,13 @@ impl TCP {
 
     // --- GETTER METHODS ---
 
-    /// Returns the source IP address.
-    pub fn get_source(&mut self) -> Ipv4Addr {
+    /// Returns the source port.
+    pub fn get_source(&mut self) -> u16 {
         self.source
     }
 
-    /// Returns the destination IP address.
-    pub fn get_destination(&mut self) -> Ipv4Addr {
+    /// Returns the destination port.
+    pub fn get_destination(&mut self) -> u16 {
         self.destination
     }
 
@@ -160,14 +378,14 @@ impl TCP {
 
     // --- SETTER METHODS ---
 
-    /// Sets the source IP address.
-    pub fn set_source(mut self, source: Ipv4Addr) -> Self {
+    /// Sets the source port.
+    pub fn set_source(mut self, source: u16) -> Self {
         self.source = source;
         self
     }
 
-    /// Sets the destination IP address.
-    pub fn set_destination(mut self, destination: Ipv4Addr) -> Self {
+    /// Sets the destination port.
+    pub fn set_destination(mut self, destination: u16) -> Self {
         self.destination = destination;
         self
     }
@@ -237,4 +455,410 @@ impl TCP {
         self.data = data;
         self
     }
+
+    /// Returns the RFC-style ASCII diagram of the TCP header.
+    pub fn header_diagram() -> String {
+        ascii_diagram(HEADER_FIELDS)
+    }
+
+    // --- SERIALIZATION ---
+
+    /// Serializes the header, options and padding, followed by the data,
+    /// e.g. as the payload of an `IPv4` packet. The checksum field is used
+    /// as is.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes =
+            Vec::with_capacity(20 + self.options.len() + self.padding.len() + self.data.len());
+        bytes.extend_from_slice(&self.source.to_be_bytes());
+        bytes.extend_from_slice(&self.destination.to_be_bytes());
+        bytes.extend_from_slice(&self.sequence.to_be_bytes());
+        bytes.extend_from_slice(&self.acknowledgment.to_be_bytes());
+        let offset_and_flags = OffsetAndFlags::default()
+            .set_data_offset(self.data_offset)
+            .set_reserved(self.reserved)
+            .set_flags(self.flags);
+        bytes.extend_from_slice(&offset_and_flags.0);
+        bytes.extend_from_slice(&self.window_size.to_be_bytes());
+        bytes.extend_from_slice(&self.checksum.to_be_bytes());
+        bytes.extend_from_slice(&self.urgent_pointer.to_be_bytes());
+        bytes.extend_from_slice(&self.options);
+        bytes.extend_from_slice(&self.padding);
+        bytes.extend_from_slice(&self.data);
+        bytes
+    }
+
+    /// Checksum over the pseudo-header of the IPv4 or IPv6 packet
+    /// carrying the segment and the segment itself.
+    pub fn compute_checksum(&self, pseudo_header: &PseudoHeader) -> u16 {
+        let mut bytes = self.to_bytes();
+        bytes[16..18].fill(0);
+        pseudo_header.checksum(IP_PROTOCOL, &bytes)
+    }
+
+    /// Returns the segment with its checksum computed.
+    pub fn with_checksum(mut self, pseudo_header: &PseudoHeader) -> Self {
+        self.checksum = self.compute_checksum(pseudo_header);
+        self
+    }
+
+    /// Wraps the segment, with its checksum computed, in an IPv4 packet
+    /// from `source` to `destination`.
+    pub fn to_ipv4(&self, source: Ipv4Addr, destination: Ipv4Addr) -> IPv4 {
+        let pseudo_header = PseudoHeader::V4 {
+            source,
+            destination,
+        };
+        let segment = self.clone().with_checksum(&pseudo_header);
+        IPv4::with_payload(source, destination, IP_PROTOCOL, segment.to_bytes())
+    }
+
+    /// Parses a TCP segment. Everything between the fixed header and the
+    /// data offset goes to `options`, padding included; the data runs to
+    /// the end of `buf`.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, 20)?;
+        let offset_and_flags = OffsetAndFlags([buf[12], buf[13]]);
+        let data_offset = offset_and_flags.get_data_offset();
+        let header_len = data_offset as usize * 4;
+        if header_len < 20 {
+            return Err(ParseError::InvalidField("data offset"));
+        }
+        ensure_len(buf, header_len)?;
+        Ok(TCP {
+            source: u16::from_be_bytes([buf[0], buf[1]]),
+            destination: u16::from_be_bytes([buf[2], buf[3]]),
+            sequence: u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]),
+            acknowledgment: u32::from_be_bytes([buf[8], buf[9], buf[10], buf[11]]),
+            data_offset,
+            reserved: offset_and_flags.get_reserved(),
+            flags: offset_and_flags.get_flags(),
+            window_size: u16::from_be_bytes([buf[14], buf[15]]),
+            checksum: u16::from_be_bytes([buf[16], buf[17]]),
+            urgent_pointer: u16::from_be_bytes([buf[18], buf[19]]),
+            options: buf[20..header_len].to_vec(),
+            padding: Vec::new(),
+            data: buf[header_len..].to_vec(),
+        })
+    }
+
+    // --- OPTIONS ---
+
+    /// Parses the `options` field.
+    pub fn tcp_options(&self) -> Result<Vec<TcpOption>, ParseError> {
+        TcpOption::parse_all(&self.options)
+    }
+
+    /// Replaces the options, padding them with zeros to a 32-bit boundary
+    /// and updating the data offset accordingly.
+    pub fn set_tcp_options(mut self, options: &[TcpOption]) -> Self {
+        self.options = TcpOption::serialize_all(options);
+        self.padding = vec![0; (4 - self.options.len() % 4) % 4];
+        self.data_offset = (5 + (self.options.len() + self.padding.len()) / 4) as u8;
+        self
+    }
+
+    // --- SESSION HELPERS ---
+
+    /// Builds a header-only segment (data offset 5, no options) with the
+    /// given ports, sequence numbers and flags.
+    pub fn segment(
+        source: u16,
+        destination: u16,
+        sequence: u32,
+        acknowledgment: u32,
+        flags: u16,
+    ) -> Self {
+        TCP::new(
+            source,
+            destination,
+            sequence,
+            acknowledgment,
+            5,
+            0,
+            flags,
+            DEFAULT_WINDOW_SIZE,
+            0,
+            0,
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+        )
+    }
+
+    /// Builds a SYN carrying MSS, SACK-Permitted, Window Scale and, if
+    /// `use_timestamps` is set, Timestamps, laid out as Linux sends them:
+    ///
+    /// ```text
+    /// MSS, SACK-Permitted, Timestamps, NOP, Window Scale        (20 bytes)
+    /// MSS, NOP, NOP, SACK-Permitted, NOP, Window Scale          (12 bytes)
+    /// ```
+    ///
+    /// The TSval is random, as Linux offsets its clock per connection, and
+    /// the TSecr is 0.
+    pub fn syn_with_options(
+        src_port: u16,
+        dst_port: u16,
+        seq: u32,
+        mss: u16,
+        wscale: u8,
+        use_timestamps: bool,
+    ) -> Self {
+        let tsval = use_timestamps.then(random_isn);
+        TCP::syn_with_options_and_tsval(src_port, dst_port, seq, mss, wscale, tsval)
+    }
+
+    /// Same as `syn_with_options`, with an explicit TSval; `None` leaves
+    /// the Timestamps option out.
+    pub fn syn_with_options_and_tsval(
+        src_port: u16,
+        dst_port: u16,
+        seq: u32,
+        mss: u16,
+        wscale: u8,
+        tsval: Option<u32>,
+    ) -> Self {
+        let mut options = vec![TcpOption::Mss(mss)];
+        if let Some(tsval) = tsval {
+            options.extend([
+                TcpOption::SackPermitted,
+                TcpOption::Timestamps { tsval, tsecr: 0 },
+            ]);
+        } else {
+            options.extend([TcpOption::Nop, TcpOption::Nop, TcpOption::SackPermitted]);
+        }
+        options.extend([TcpOption::Nop, TcpOption::WindowScale(wscale)]);
+        TCP::segment(src_port, dst_port, seq, 0, flags::SYN)
+            .set_window_size(DEFAULT_WINDOW_SIZE)
+            .set_tcp_options(&options)
+    }
+
+    /// Generates the three-way handshake `[syn, syn_ack, ack]` for a client
+    /// using `isn`. The server's initial sequence number is chosen at random.
+    pub fn connection_setup(src_port: u16, dst_port: u16, isn: u32) -> [TCP; 3] {
+        TCP::connection_setup_with_server_isn(src_port, dst_port, isn, random_isn())
+    }
+
+    /// Same as `connection_setup`, with an explicit server initial sequence number.
+    pub fn connection_setup_with_server_isn(
+        src_port: u16,
+        dst_port: u16,
+        isn: u32,
+        server_isn: u32,
+    ) -> [TCP; 3] {
+        let client_next = isn.wrapping_add(1);
+        let server_next = server_isn.wrapping_add(1);
+        [
+            TCP::segment(src_port, dst_port, isn, 0, flags::SYN),
+            TCP::segment(
+                dst_port,
+                src_port,
+                server_isn,
+                client_next,
+                flags::SYN | flags::ACK,
+            ),
+            TCP::segment(src_port, dst_port, client_next, server_next, flags::ACK),
+        ]
+    }
+
+    /// Generates the four-way close of an established connection, initiated
+    /// by the `src_port` side: `[fin_ack, ack, fin_ack, ack]`.
+    /// `established_seq` and `established_ack` are the initiator's next
+    /// sequence number and the acknowledgment number it currently sends.
+    pub fn connection_teardown(
+        src_port: u16,
+        dst_port: u16,
+        established_seq: u32,
+        established_ack: u32,
+    ) -> [TCP; 4] {
+        let fin = flags::FIN | flags::ACK;
+        let initiator_next = established_seq.wrapping_add(1);
+        let responder_next = established_ack.wrapping_add(1);
+        [
+            TCP::segment(src_port, dst_port, established_seq, established_ack, fin),
+            TCP::segment(
+                dst_port,
+                src_port,
+                established_ack,
+                initiator_next,
+                flags::ACK,
+            ),
+            TCP::segment(dst_port, src_port, established_ack, initiator_next, fin),
+            TCP::segment(
+                src_port,
+                dst_port,
+                initiator_next,
+                responder_next,
+                flags::ACK,
+            ),
+        ]
+    }
+}
+
+/// Splits a buffer into the data segments of one direction of an
+/// established connection. Each segment carries at most `mss` bytes and
+/// has ACK set; the last one also has PSH.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TcpSegmentStream {
+    pub source: u16,
+    pub destination: u16,
+    /// Sequence number of the first byte of `data`.
+    pub isn: u32,
+    /// Acknowledgment number of every segment.
+    pub acknowledgment: u32,
+    pub data: Vec<u8>,
+    pub mss: usize,
+    /// Window field of every segment, already scaled.
+    pub window_size: u16,
+    /// Shift applied by `set_receive_window`.
+    pub window_scale: u8,
+    /// Timestamps carried by every segment, when negotiated.
+    pub timestamps: Option<TcpTimestampState>,
+    offset: usize,
+}
+
+impl TcpSegmentStream {
+    /// Constructor to create a stream sending `data` from `src_port` to
+    /// `dst_port`, starting at sequence number `isn`. An `mss` of 0 is
+    /// treated as 1.
+    pub fn new(src_port: u16, dst_port: u16, isn: u32, data: Vec<u8>, mss: usize) -> Self {
+        TcpSegmentStream {
+            source: src_port,
+            destination: dst_port,
+            isn,
+            acknowledgment: 0,
+            data,
+            mss: mss.max(1),
+            window_size: DEFAULT_WINDOW_SIZE,
+            window_scale: 0,
+            timestamps: None,
+            offset: 0,
+        }
+    }
+
+    pub fn set_acknowledgment(mut self, acknowledgment: u32) -> Self {
+        self.acknowledgment = acknowledgment;
+        self
+    }
+
+    /// Makes the segments follow the options negotiated for the connection,
+    /// as sent by the client if `from_client` is set and by the server
+    /// otherwise: the payload fits in the MSS announced by the peer, the
+    /// window is scaled by the sender's shift, and segments carry
+    /// Timestamps (starting at 0 until `set_timestamps`) only when they
+    /// were negotiated.
+    pub fn set_connection_options(
+        mut self,
+        options: &ConnectionOptions,
+        from_client: bool,
+    ) -> Self {
+        self.mss = options.send_mss(from_client).max(1) as usize;
+        self.window_scale = options.window_scale(from_client);
+        self.timestamps = options
+            .timestamps
+            .then(|| self.timestamps.unwrap_or_default());
+        self
+    }
+
+    /// Sets the window field announcing `bytes` of buffer space, scaled by
+    /// `window_scale`.
+    pub fn set_receive_window(mut self, bytes: u32) -> Self {
+        self.window_size = ConnectionOptions::window_field(bytes, self.window_scale);
+        self
+    }
+
+    /// Sets the TSval and TSecr of the segments; ignored unless Timestamps
+    /// were negotiated.
+    pub fn set_timestamps(mut self, state: TcpTimestampState) -> Self {
+        if self.timestamps.is_some() {
+            self.timestamps = Some(state);
+        }
+        self
+    }
+
+    /// Acknowledgment number the receiver sends once it got all the data.
+    pub fn ack_next_seq(&self) -> u32 {
+        self.isn.wrapping_add(self.data.len() as u32)
+    }
+}
+
+impl Iterator for TcpSegmentStream {
+    type Item = TCP;
+
+    fn next(&mut self) -> Option<TCP> {
+        if self.offset >= self.data.len() {
+            return None;
+        }
+        let end = (self.offset + self.mss).min(self.data.len());
+        let mut flags = flags::ACK;
+        if end == self.data.len() {
+            flags |= flags::PSH;
+        }
+        let mut segment = TCP::segment(
+            self.source,
+            self.destination,
+            self.isn.wrapping_add(self.offset as u32),
+            self.acknowledgment,
+            flags,
+        )
+        .set_window_size(self.window_size);
+        if let Some(state) = &self.timestamps {
+            segment = timestamps::inject_into(segment, state);
+        }
+        segment.data = self.data[self.offset..end].to_vec();
+        self.offset = end;
+        Some(segment)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = (self.data.len() - self.offset).div_ceil(self.mss);
+        (remaining, Some(remaining))
+    }
+}
+
+/// Returns an unpredictable initial sequence number.
+fn random_isn() -> u32 {
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos())
+        .unwrap_or_default();
+    RandomState::new().hash_one(nanos) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn syn_options_match_linux_with_timestamps() {
+        let syn = TCP::syn_with_options_and_tsval(40000, 80, 1, 1460, 7, Some(0x0102_0304));
+        // tcpdump -XX of a Linux connect(): MSS 1460, SACK-Permitted,
+        // TS val 16909060 ecr 0, NOP, Window Scale 7.
+        assert_eq!(
+            syn.options,
+            [
+                0x02, 0x04, 0x05, 0xb4, 0x04, 0x02, 0x08, 0x0a, 0x01, 0x02, 0x03, 0x04, 0x00, 0x00,
+                0x00, 0x00, 0x01, 0x03, 0x03, 0x07
+            ]
+        );
+        assert!(syn.padding.is_empty());
+        assert_eq!(syn.data_offset, 10);
+        assert_eq!(syn.window_size, 65535);
+        assert_eq!(syn.flags, flags::SYN);
+        let bytes = syn.to_bytes();
+        assert_eq!(bytes.len(), 40);
+        assert_eq!(bytes[20..], syn.options[..]);
+    }
+
+    #[test]
+    fn syn_options_match_linux_without_timestamps() {
+        let syn = TCP::syn_with_options_and_tsval(40000, 80, 1, 1460, 7, None);
+        assert_eq!(
+            syn.options,
+            [
+                0x02, 0x04, 0x05, 0xb4, 0x01, 0x01, 0x04, 0x02, 0x01, 0x03, 0x03, 0x07
+            ]
+        );
+        assert_eq!(syn.data_offset, 8);
+        assert_eq!(TCP::syn_with_options(40000, 80, 1, 1460, 7, false), syn);
+    }
 }
@@ -0,0 +1,110 @@
+use super::{TCP, flags};
+use crate::util::Ecn;
+
+// ECN in TCP (RFC 3168 section 6.1)
+//
+//   SYN      ECE|CWR    client asks for ECN
+//   SYN-ACK  ECE        server agrees
+//   data     IP ECT(0)  sender marks packets as ECN-capable
+//   ...      IP CE      a router signals congestion
+//   ACK      ECE        receiver echoes CE until it sees CWR
+//   data     CWR        sender has reduced its congestion window
+
+/// Returns true for a SYN asking for ECN (ECE and CWR set).
+pub fn is_ecn_setup_syn(tcp: &TCP) -> bool {
+    tcp.flags & (flags::SYN | flags::ACK | flags::ECE | flags::CWR)
+        == flags::SYN | flags::ECE | flags::CWR
+}
+
+/// Returns true for a SYN-ACK accepting ECN (ECE set, CWR clear).
+pub fn is_ecn_setup_syn_ack(tcp: &TCP) -> bool {
+    tcp.flags & (flags::SYN | flags::ACK | flags::ECE | flags::CWR)
+        == flags::SYN | flags::ACK | flags::ECE
+}
+
+/// ECN state of one TCP endpoint, tracking both the TCP flags and the ECN
+/// codepoint of the IP header carrying each segment.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EcnEndpoint {
+    /// This endpoint wants to use ECN.
+    pub enabled: bool,
+    /// The peer asked for ECN in its SYN.
+    pub peer_requested: bool,
+    /// ECN was negotiated during the handshake.
+    pub negotiated: bool,
+    /// A CE mark was received and is echoed with ECE until the peer's CWR.
+    pub echo_ce: bool,
+    /// An ECE was received; the next data segment carries CWR.
+    pub send_cwr: bool,
+}
+
+impl EcnEndpoint {
+    /// Constructor to create an endpoint that offers or accepts ECN.
+    pub fn new(enabled: bool) -> Self {
+        EcnEndpoint {
+            enabled,
+            ..Default::default()
+        }
+    }
+
+    /// Processes a received segment and the ECN codepoint of its IP header.
+    pub fn receive(&mut self, tcp: &TCP, ip_ecn: Ecn) {
+        if is_ecn_setup_syn(tcp) {
+            self.peer_requested = true;
+            return;
+        }
+        if is_ecn_setup_syn_ack(tcp) {
+            self.negotiated = self.enabled;
+            return;
+        }
+        if !self.negotiated {
+            return;
+        }
+        if ip_ecn == Ecn::Ce {
+            self.echo_ce = true;
+        }
+        if tcp.flags & flags::CWR != 0 {
+            self.echo_ce = false;
+        }
+        if tcp.flags & flags::ECE != 0 {
+            self.send_cwr = true;
+        }
+    }
+
+    /// Sets the ECN flags of an outgoing segment and returns it with the ECN
+    /// codepoint to put in its IP header. Only data segments are marked
+    /// ECN-capable, as RFC 3168 requires.
+    pub fn send(&mut self, tcp: TCP) -> (TCP, Ecn) {
+        let mut segment_flags = tcp.flags & !(flags::ECE | flags::CWR);
+        let syn_ack = tcp.flags & (flags::SYN | flags::ACK);
+        if syn_ack == flags::SYN {
+            if self.enabled {
+                segment_flags |= flags::ECE | flags::CWR;
+            }
+            return (tcp.set_flags(segment_flags), Ecn::NotEct);
+        }
+        if syn_ack == flags::SYN | flags::ACK {
+            self.negotiated = self.enabled && self.peer_requested;
+            if self.negotiated {
+                segment_flags |= flags::ECE;
+            }
+            return (tcp.set_flags(segment_flags), Ecn::NotEct);
+        }
+        if !self.negotiated {
+            return (tcp.set_flags(segment_flags), Ecn::NotEct);
+        }
+        if self.echo_ce {
+            segment_flags |= flags::ECE;
+        }
+        let ecn = if tcp.data.is_empty() {
+            Ecn::NotEct
+        } else {
+            if self.send_cwr {
+                segment_flags |= flags::CWR;
+                self.send_cwr = false;
+            }
+            Ecn::Ect0
+        };
+        (tcp.set_flags(segment_flags), ecn)
+    }
+}
@@ -0,0 +1,327 @@
+use super::{DEFAULT_WINDOW_SIZE, TCP, TcpOption, flags};
+
+// Options of the SYN-ACK, given those of the SYN:
+//
+//   MSS             independent, each side announces its own (RFC 9293)
+//   Window Scale    only if the SYN carried it (RFC 7323 section 2.2)
+//   SACK-Permitted  only if the SYN carried it (RFC 2018)
+//   Timestamps      only if the SYN carried it, echoing its TSval (RFC 7323
+//                   section 3.2)
+
+/// MSS assumed when the peer sends none (RFC 9293 section 3.7.1).
+pub const DEFAULT_MSS: u16 = 536;
+
+/// Largest window scale shift; larger values are treated as 14 (RFC 7323
+/// section 2.3).
+pub const MAX_WINDOW_SCALE: u8 = 14;
+
+/// Bytes of the Timestamps option with its two alignment NOPs.
+const TIMESTAMPS_OPTION_LEN: u16 = 12;
+
+/// Options supported by a simulated server and the values it prefers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OptionPolicy {
+    /// MSS announced by the server, if any.
+    pub mss: Option<u16>,
+    /// Window scale shift of the server, if it supports window scaling.
+    pub window_scale: Option<u8>,
+    pub sack: bool,
+    pub timestamps: bool,
+    /// TSval sent in the SYN-ACK.
+    pub tsval: u32,
+    /// Unscaled window advertised in the SYN-ACK.
+    pub window: u16,
+}
+
+impl Default for OptionPolicy {
+    fn default() -> Self {
+        OptionPolicy::new()
+    }
+}
+
+impl OptionPolicy {
+    /// Constructor to create a server supporting every option, with an MSS
+    /// of 1460 and a window scale of 7.
+    pub fn new() -> Self {
+        OptionPolicy {
+            mss: Some(1460),
+            window_scale: Some(7),
+            sack: true,
+            timestamps: true,
+            tsval: 0,
+            window: DEFAULT_WINDOW_SIZE,
+        }
+    }
+
+    // --- SETTER METHODS ---
+
+    pub fn set_mss(mut self, mss: Option<u16>) -> Self {
+        self.mss = mss;
+        self
+    }
+
+    pub fn set_window_scale(mut self, window_scale: Option<u8>) -> Self {
+        self.window_scale = window_scale;
+        self
+    }
+
+    pub fn set_sack(mut self, sack: bool) -> Self {
+        self.sack = sack;
+        self
+    }
+
+    pub fn set_timestamps(mut self, timestamps: bool) -> Self {
+        self.timestamps = timestamps;
+        self
+    }
+
+    pub fn set_tsval(mut self, tsval: u32) -> Self {
+        self.tsval = tsval;
+        self
+    }
+
+    pub fn set_window(mut self, window: u16) -> Self {
+        self.window = window;
+        self
+    }
+}
+
+/// Outcome of the option negotiation of a connection, which the segments
+/// following the handshake must respect.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConnectionOptions {
+    /// MSS the client may send, as announced by the server.
+    pub client_mss: u16,
+    /// MSS the server may send, as announced by the client.
+    pub server_mss: u16,
+    /// Both sides sent Window Scale.
+    pub window_scaling: bool,
+    /// Shift applied to the windows advertised by the client; 0 when
+    /// window scaling was not negotiated.
+    pub client_window_scale: u8,
+    /// Shift applied to the windows advertised by the server.
+    pub server_window_scale: u8,
+    pub sack: bool,
+    pub timestamps: bool,
+}
+
+impl ConnectionOptions {
+    /// Negotiates the options of a connection opened by `client_syn` with a
+    /// server following `server_policy`. Options of the SYN that cannot be
+    /// parsed are ignored.
+    pub fn negotiate(client_syn: &TCP, server_policy: &OptionPolicy) -> Self {
+        let mut client_mss = None;
+        let mut client_scale = None;
+        let mut client_sack = false;
+        let mut client_timestamps = false;
+        for option in client_syn.tcp_options().unwrap_or_default() {
+            match option {
+                TcpOption::Mss(mss) => client_mss = Some(mss),
+                TcpOption::WindowScale(shift) => client_scale = Some(shift),
+                TcpOption::SackPermitted => client_sack = true,
+                TcpOption::Timestamps { .. } => client_timestamps = true,
+                _ => {}
+            }
+        }
+        let scales = client_scale.zip(server_policy.window_scale);
+        let (client_window_scale, server_window_scale) = scales
+            .map_or((0, 0), |(client, server)| {
+                (client.min(MAX_WINDOW_SCALE), server.min(MAX_WINDOW_SCALE))
+            });
+        ConnectionOptions {
+            client_mss: server_policy.mss.unwrap_or(DEFAULT_MSS),
+            server_mss: client_mss.unwrap_or(DEFAULT_MSS),
+            window_scaling: scales.is_some(),
+            client_window_scale,
+            server_window_scale,
+            sack: client_sack && server_policy.sack,
+            timestamps: client_timestamps && server_policy.timestamps,
+        }
+    }
+
+    /// Smaller of both MSS values.
+    pub fn effective_mss(&self) -> u16 {
+        self.client_mss.min(self.server_mss)
+    }
+
+    /// Payload bytes a segment may carry with an MSS of `mss`, leaving
+    /// room for the Timestamps option when it is in use.
+    pub fn max_payload(&self, mss: u16) -> u16 {
+        if self.timestamps {
+            mss.saturating_sub(TIMESTAMPS_OPTION_LEN)
+        } else {
+            mss
+        }
+    }
+
+    /// Payload bytes a segment from the client (if `from_client` is set)
+    /// or from the server may carry: the MSS announced by the other side,
+    /// less room for the Timestamps option when it is in use.
+    pub fn send_mss(&self, from_client: bool) -> u16 {
+        let mss = if from_client {
+            self.client_mss
+        } else {
+            self.server_mss
+        };
+        self.max_payload(mss)
+    }
+
+    /// Shift applied to the windows advertised by the client (if
+    /// `from_client` is set) or by the server.
+    pub fn window_scale(&self, from_client: bool) -> u8 {
+        if from_client {
+            self.client_window_scale
+        } else {
+            self.server_window_scale
+        }
+    }
+
+    /// Window field announcing `bytes` of buffer space, scaled by `shift`
+    /// (`client_window_scale` or `server_window_scale`) and rounded down.
+    pub fn window_field(bytes: u32, shift: u8) -> u16 {
+        (bytes >> shift).min(u16::MAX as u32) as u16
+    }
+}
+
+/// Options of the SYN-ACK answering `client_syn`, for a server following
+/// `server_policy`. They are padded with NOPs so that each multi-byte
+/// option stays 32-bit aligned, as common stacks send them.
+pub fn synack_options(client_syn: &TCP, server_policy: &OptionPolicy) -> Vec<TcpOption> {
+    let negotiated = ConnectionOptions::negotiate(client_syn, server_policy);
+    let client_tsval = client_syn
+        .tcp_options()
+        .unwrap_or_default()
+        .into_iter()
+        .find_map(|option| match option {
+            TcpOption::Timestamps { tsval, .. } => Some(tsval),
+            _ => None,
+        });
+
+    let mut options = Vec::new();
+    if let Some(mss) = server_policy.mss {
+        options.push(TcpOption::Mss(mss));
+    }
+    let timestamps =
+        client_tsval
+            .filter(|_| negotiated.timestamps)
+            .map(|tsecr| TcpOption::Timestamps {
+                tsval: server_policy.tsval,
+                tsecr,
+            });
+    match (negotiated.sack, timestamps) {
+        (true, Some(timestamps)) => options.extend([TcpOption::SackPermitted, timestamps]),
+        (true, None) => options.extend([TcpOption::Nop, TcpOption::Nop, TcpOption::SackPermitted]),
+        (false, Some(timestamps)) => options.extend([TcpOption::Nop, TcpOption::Nop, timestamps]),
+        (false, None) => {}
+    }
+    if let Some(shift) = server_policy
+        .window_scale
+        .filter(|_| negotiated.window_scaling)
+    {
+        options.extend([TcpOption::Nop, TcpOption::WindowScale(shift)]);
+    }
+    options
+}
+
+/// SYN-ACK answering `client_syn` from `server_isn`, with the options of
+/// `synack_options` and the window of `server_policy`. The window of a
+/// SYN-ACK is never scaled.
+pub fn synack(client_syn: &TCP, server_policy: &OptionPolicy, server_isn: u32) -> TCP {
+    TCP::segment(
+        client_syn.destination,
+        client_syn.source,
+        server_isn,
+        client_syn.sequence.wrapping_add(1),
+        flags::SYN | flags::ACK,
+    )
+    .set_window_size(server_policy.window)
+    .set_tcp_options(&synack_options(client_syn, server_policy))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// SYN with MSS 1400 and, as asked, Window Scale 8, SACK-Permitted and
+    /// Timestamps (TSval 1000).
+    fn syn(window_scale: bool, sack: bool, timestamps: bool) -> TCP {
+        let mut options = vec![TcpOption::Mss(1400)];
+        if sack {
+            options.push(TcpOption::SackPermitted);
+        }
+        if timestamps {
+            options.push(TcpOption::Timestamps {
+                tsval: 1000,
+                tsecr: 0,
+            });
+        }
+        if window_scale {
+            options.extend([TcpOption::Nop, TcpOption::WindowScale(8)]);
+        }
+        TCP::segment(40000, 80, 1, 0, flags::SYN).set_tcp_options(&options)
+    }
+
+    #[test]
+    fn client_window_scale_without_server_support() {
+        let policy = OptionPolicy::new().set_window_scale(None);
+        let client_syn = syn(true, true, true);
+        let negotiated = ConnectionOptions::negotiate(&client_syn, &policy);
+        assert!(!negotiated.window_scaling);
+        assert_eq!(negotiated.client_window_scale, 0);
+        assert_eq!(negotiated.server_window_scale, 0);
+        assert!(negotiated.sack && negotiated.timestamps);
+
+        let options = synack_options(&client_syn, &policy);
+        assert!(
+            !options
+                .iter()
+                .any(|option| matches!(option, TcpOption::WindowScale(_)))
+        );
+        assert_eq!(
+            options,
+            [
+                TcpOption::Mss(1460),
+                TcpOption::SackPermitted,
+                TcpOption::Timestamps {
+                    tsval: 0,
+                    tsecr: 1000
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn server_sack_without_client_offer() {
+        let policy = OptionPolicy::new().set_sack(true);
+        let client_syn = syn(true, false, false);
+        let negotiated = ConnectionOptions::negotiate(&client_syn, &policy);
+        assert!(!negotiated.sack);
+        assert!(!negotiated.timestamps);
+        assert_eq!(negotiated.client_window_scale, 8);
+        assert_eq!(negotiated.server_window_scale, 7);
+
+        let options = synack_options(&client_syn, &policy);
+        assert_eq!(
+            options,
+            [
+                TcpOption::Mss(1460),
+                TcpOption::Nop,
+                TcpOption::WindowScale(7),
+            ]
+        );
+        let synack = synack(&client_syn, &policy, 5000);
+        assert_eq!(synack.tcp_options().unwrap(), options);
+        assert_eq!(synack.acknowledgment, 2);
+    }
+
+    #[test]
+    fn mss_is_independent_per_direction() {
+        let policy = OptionPolicy::new().set_mss(None);
+        let negotiated = ConnectionOptions::negotiate(&syn(false, false, true), &policy);
+        assert_eq!(negotiated.client_mss, DEFAULT_MSS);
+        assert_eq!(negotiated.server_mss, 1400);
+        assert_eq!(negotiated.send_mss(true), DEFAULT_MSS - 12);
+        assert_eq!(negotiated.send_mss(false), 1400 - 12);
+        assert_eq!(negotiated.effective_mss(), DEFAULT_MSS);
+    }
+}
@@ -0,0 +1,212 @@
+use super::negotiate::{ConnectionOptions, OptionPolicy, synack};
+use super::timestamps::{TcpTimestampState, inject_into};
+use super::{TCP, TcpOption, TcpSegmentStream, flags};
+
+/// Userspace server side of a single TCP connection: answers the SYN with
+/// a SYN-ACK following its `OptionPolicy`, then acknowledges in-order data
+/// and FINs, keeping every segment consistent with the negotiated options.
+/// Retransmission, reordering and congestion control are not modelled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TcpResponder {
+    pub policy: OptionPolicy,
+    /// Buffer space announced in the window of every segment after the
+    /// SYN-ACK, before scaling.
+    pub receive_window: u32,
+    /// Negotiated options, once a SYN was received.
+    pub options: Option<ConnectionOptions>,
+    /// Data received in order from the client.
+    pub received: Vec<u8>,
+    /// Next sequence number of the server.
+    pub send_next: u32,
+    /// Next sequence number expected from the client.
+    pub receive_next: u32,
+    /// Last TSval received from the client.
+    peer_tsval: u32,
+}
+
+impl TcpResponder {
+    /// Constructor to create a responder sending from `server_isn`.
+    pub fn new(policy: OptionPolicy, server_isn: u32) -> Self {
+        TcpResponder {
+            policy,
+            receive_window: policy.window as u32,
+            options: None,
+            received: Vec::new(),
+            send_next: server_isn,
+            receive_next: 0,
+            peer_tsval: 0,
+        }
+    }
+
+    pub fn set_receive_window(mut self, receive_window: u32) -> Self {
+        self.receive_window = receive_window;
+        self
+    }
+
+    /// Handles a segment from the client, returning the answer if it calls
+    /// for one: a SYN-ACK for a SYN, an ACK for data, a FIN-ACK for a FIN.
+    /// Segments before the SYN, out of order or with nothing to
+    /// acknowledge get no answer.
+    pub fn respond(&mut self, segment: &TCP) -> Option<TCP> {
+        if let Some(tsval) = tsval(segment) {
+            self.peer_tsval = tsval;
+        }
+        if segment.flags & flags::SYN != 0 && segment.flags & flags::ACK == 0 {
+            self.options = Some(ConnectionOptions::negotiate(segment, &self.policy));
+            self.received.clear();
+            self.receive_next = segment.sequence.wrapping_add(1);
+            let answer = synack(segment, &self.policy, self.send_next);
+            self.send_next = self.send_next.wrapping_add(1);
+            return Some(answer);
+        }
+        let options = self.options?;
+        if segment.sequence != self.receive_next {
+            return None;
+        }
+        let fin = segment.flags & flags::FIN != 0;
+        if segment.data.is_empty() && !fin {
+            return None;
+        }
+        self.received.extend_from_slice(&segment.data);
+        self.receive_next = self
+            .receive_next
+            .wrapping_add(segment.data.len() as u32 + fin as u32);
+
+        let mut answer_flags = flags::ACK;
+        if fin {
+            answer_flags |= flags::FIN;
+        }
+        let answer = TCP::segment(
+            segment.destination,
+            segment.source,
+            self.send_next,
+            self.receive_next,
+            answer_flags,
+        )
+        .set_window_size(ConnectionOptions::window_field(
+            self.receive_window,
+            options.server_window_scale,
+        ));
+        if fin {
+            self.send_next = self.send_next.wrapping_add(1);
+        }
+        Some(match self.timestamps() {
+            Some(state) => inject_into(answer, &state),
+            None => answer,
+        })
+    }
+
+    /// Segments sending `data` to the client from the current sequence
+    /// number, following the negotiated options; `None` before the SYN.
+    /// The data counts as sent.
+    pub fn send(
+        &mut self,
+        client_port: u16,
+        server_port: u16,
+        data: Vec<u8>,
+    ) -> Option<TcpSegmentStream> {
+        let options = self.options?;
+        let mut stream = TcpSegmentStream::new(server_port, client_port, self.send_next, data, 0)
+            .set_acknowledgment(self.receive_next)
+            .set_connection_options(&options, false)
+            .set_receive_window(self.receive_window);
+        if let Some(state) = self.timestamps() {
+            stream = stream.set_timestamps(state);
+        }
+        self.send_next = stream.ack_next_seq();
+        Some(stream)
+    }
+
+    /// Timestamps of the next segment, when they were negotiated.
+    fn timestamps(&self) -> Option<TcpTimestampState> {
+        self.options
+            .filter(|options| options.timestamps)
+            .map(|_| TcpTimestampState::new(self.policy.tsval, self.peer_tsval))
+    }
+}
+
+/// TSval of the Timestamps option of `segment`, if any.
+fn tsval(segment: &TCP) -> Option<u32> {
+    segment
+        .tcp_options()
+        .unwrap_or_default()
+        .into_iter()
+        .find_map(|option| match option {
+            TcpOption::Timestamps { tsval, .. } => Some(tsval),
+            _ => None,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client_syn() -> TCP {
+        TCP::syn_with_options_and_tsval(40000, 80, 100, 1000, 2, Some(77))
+    }
+
+    #[test]
+    fn answers_follow_the_negotiation() {
+        let policy = OptionPolicy::new().set_tsval(500);
+        let mut responder = TcpResponder::new(policy, 9000).set_receive_window(1 << 20);
+        let synack = responder.respond(&client_syn()).unwrap();
+        assert_eq!(synack.flags, flags::SYN | flags::ACK);
+        assert_eq!(synack.acknowledgment, 101);
+
+        let data =
+            TCP::segment(40000, 80, 101, 9001, flags::ACK | flags::PSH).set_data(b"GET /".to_vec());
+        let data = inject_into(data, &TcpTimestampState::new(78, 500));
+        let ack = responder.respond(&data).unwrap();
+        assert_eq!((ack.sequence, ack.acknowledgment), (9001, 106));
+        // 1 MiB announced with the server's shift of 7.
+        assert_eq!(ack.window_size, 8192);
+        assert!(ack.tcp_options().unwrap().contains(&TcpOption::Timestamps {
+            tsval: 500,
+            tsecr: 78
+        }));
+        assert_eq!(responder.received, b"GET /");
+
+        // A retransmission is not acknowledged again.
+        assert_eq!(responder.respond(&data), None);
+
+        let segments: Vec<TCP> = responder.send(40000, 80, vec![0; 2500]).unwrap().collect();
+        // The client announced an MSS of 1000, less 12 bytes of Timestamps.
+        let sizes: Vec<usize> = segments.iter().map(|segment| segment.data.len()).collect();
+        assert_eq!(sizes, [988, 988, 524]);
+        assert_eq!(segments[1].sequence, 9001 + 988);
+        assert!(segments.iter().all(|segment| segment.window_size == 8192
+            && segment.acknowledgment == 106
+            && segment.data_offset == 8));
+        assert_eq!(responder.send_next, 9001 + 2500);
+
+        let fin = TCP::segment(40000, 80, 106, responder.send_next, flags::FIN | flags::ACK);
+        let fin_ack = responder.respond(&fin).unwrap();
+        assert_eq!(fin_ack.flags, flags::FIN | flags::ACK);
+        assert_eq!(fin_ack.acknowledgment, 107);
+    }
+
+    #[test]
+    fn no_timestamps_or_scaling_when_not_negotiated() {
+        let policy = OptionPolicy::new()
+            .set_timestamps(false)
+            .set_window_scale(None);
+        let mut responder = TcpResponder::new(policy, 0).set_receive_window(1 << 20);
+        responder.respond(&client_syn()).unwrap();
+        let data = TCP::segment(40000, 80, 101, 1, flags::ACK).set_data(vec![1; 10]);
+        let ack = responder.respond(&data).unwrap();
+        assert_eq!(ack.window_size, u16::MAX);
+        assert_eq!(ack.tcp_options().unwrap(), []);
+
+        let segments: Vec<TCP> = responder.send(40000, 80, vec![0; 1500]).unwrap().collect();
+        assert_eq!(segments[0].data.len(), 1000);
+        assert_eq!(segments[0].data_offset, 5);
+    }
+
+    #[test]
+    fn ignores_segments_before_the_syn() {
+        let mut responder = TcpResponder::new(OptionPolicy::new(), 0);
+        let data = TCP::segment(40000, 80, 1, 1, flags::ACK).set_data(vec![1]);
+        assert_eq!(responder.respond(&data), None);
+        assert!(responder.send(40000, 80, vec![1]).is_none());
+    }
+}
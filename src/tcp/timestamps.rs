@@ -0,0 +1,82 @@
+use std::time::Instant;
+
+use super::{TCP, TcpOption};
+
+// TCP Timestamps option (RFC 7323)
+//
+// +-------+-------+---------------------+---------------------+
+// |Kind=8 |  10   |   TS Value (TSval)  |TS Echo Reply (TSecr)|
+// +-------+-------+---------------------+---------------------+
+//     1       1              4                     4
+
+/// Timestamp clock of a simulated TCP stack.
+#[derive(Debug, Clone, Copy)]
+pub struct TcpTimestampClock {
+    pub start: Instant,
+    /// Milliseconds per tick, usually 1.
+    pub tick_ms: u32,
+}
+
+impl TcpTimestampClock {
+    /// Constructor to create a clock starting now.
+    pub fn new(tick_ms: u32) -> Self {
+        TcpTimestampClock {
+            start: Instant::now(),
+            tick_ms,
+        }
+    }
+
+    /// Current TSval, in ticks since the clock started (wrapping).
+    pub fn tsval(&self) -> u32 {
+        (self.start.elapsed().as_millis() / self.tick_ms.max(1) as u128) as u32
+    }
+
+    /// Returns `state` with its TSval set to the current clock value.
+    pub fn stamp(&self, state: TcpTimestampState) -> TcpTimestampState {
+        TcpTimestampState {
+            tsval: self.tsval(),
+            ..state
+        }
+    }
+}
+
+/// Timestamp values of one connection.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TcpTimestampState {
+    pub tsval: u32,
+    pub tsecr: u32,
+}
+
+impl TcpTimestampState {
+    /// Constructor to create a new state.
+    pub fn new(tsval: u32, tsecr: u32) -> Self {
+        TcpTimestampState { tsval, tsecr }
+    }
+
+    /// Echoes the last TSval seen from the peer.
+    pub fn update_tsecr(self, peer_tsval: u32) -> TcpTimestampState {
+        TcpTimestampState {
+            tsecr: peer_tsval,
+            ..self
+        }
+    }
+}
+
+/// Adds the Timestamps option to `tcp`, or replaces the one it carries.
+/// A new option is preceded by two NOPs to keep it 32-bit aligned.
+/// Options that cannot be parsed are dropped.
+pub fn inject_into(tcp: TCP, state: &TcpTimestampState) -> TCP {
+    let timestamps = TcpOption::Timestamps {
+        tsval: state.tsval,
+        tsecr: state.tsecr,
+    };
+    let mut options = tcp.tcp_options().unwrap_or_default();
+    match options
+        .iter_mut()
+        .find(|option| matches!(option, TcpOption::Timestamps { .. }))
+    {
+        Some(option) => *option = timestamps,
+        None => options.extend([TcpOption::Nop, TcpOption::Nop, timestamps]),
+    }
+    tcp.set_tcp_options(&options)
+}
@@ -0,0 +1,302 @@
+use std::net::Ipv4Addr;
+
+use crate::arp::Arp;
+use crate::dns::{Dns, DnsType};
+use crate::ethernet::MacAddr;
+use crate::icmpv4::Icmpv4;
+use crate::ipv4::{FLAG_MORE_FRAGMENTS, IPv4};
+use crate::pcap::{DecodedStack, LINKTYPE_ETHERNET};
+use crate::tcp::{TCP, TcpOption, flags};
+use crate::udp::UDP;
+use crate::util::PseudoHeader;
+use crate::vlan::VlanTag;
+
+// Known-good frames for tests, as byte arrays and as the structs they
+// parse to. Every vector is a complete Ethernet frame (link type
+// `LINKTYPE_ETHERNET`), unpadded as captured on the sending host, between
+// the documentation addresses below. The round-trip tests at the bottom
+// build each struct with the crate's constructors and check it serializes
+// to the bytes and parses back from them.
+//
+// Unless noted, IPv4 headers have no options, identification 0, DF set
+// and TTL 64, and checksums are correct.
+
+/// MAC address of the client, the sender of every vector.
+pub const CLIENT_MAC: MacAddr = MacAddr([0x02, 0, 0, 0, 0, 0x01]);
+/// MAC address of the server.
+pub const SERVER_MAC: MacAddr = MacAddr([0x02, 0, 0, 0, 0, 0x02]);
+/// IPv4 address of the client (RFC 5737 TEST-NET-1).
+pub const CLIENT_IP: Ipv4Addr = Ipv4Addr::new(192, 0, 2, 1);
+/// IPv4 address of the server.
+pub const SERVER_IP: Ipv4Addr = Ipv4Addr::new(192, 0, 2, 2);
+
+const PSEUDO_HEADER: PseudoHeader = PseudoHeader::V4 {
+    source: CLIENT_IP,
+    destination: SERVER_IP,
+};
+
+/// TCP SYN from port 49152 to 80, sequence number 1000, window 65535,
+/// no options. 54 bytes.
+pub const MINIMAL_SYN: &[u8] = &[
+    0x02, 0x00, 0x00, 0x00, 0x00, 0x02, 0x02, 0x00, 0x00, 0x00, 0x00, 0x01, 0x08, 0x00, 0x45, 0x00,
+    0x00, 0x28, 0x00, 0x00, 0x40, 0x00, 0x40, 0x06, 0xb6, 0xcc, 0xc0, 0x00, 0x02, 0x01, 0xc0, 0x00,
+    0x02, 0x02, 0xc0, 0x00, 0x00, 0x50, 0x00, 0x00, 0x03, 0xe8, 0x00, 0x00, 0x00, 0x00, 0x50, 0x02,
+    0xff, 0xff, 0x67, 0xa6, 0x00, 0x00,
+];
+
+/// TCP SYN from port 49152 to 80, sequence number 1000, window 65535,
+/// with the options in Linux order: MSS 1460, SACK permitted, timestamps
+/// (TSval 1, TSecr 0), NOP, window scale 7. 74 bytes.
+pub const SYN_WITH_OPTIONS: &[u8] = &[
+    0x02, 0x00, 0x00, 0x00, 0x00, 0x02, 0x02, 0x00, 0x00, 0x00, 0x00, 0x01, 0x08, 0x00, 0x45, 0x00,
+    0x00, 0x3c, 0x00, 0x00, 0x40, 0x00, 0x40, 0x06, 0xb6, 0xb8, 0xc0, 0x00, 0x02, 0x01, 0xc0, 0x00,
+    0x02, 0x02, 0xc0, 0x00, 0x00, 0x50, 0x00, 0x00, 0x03, 0xe8, 0x00, 0x00, 0x00, 0x00, 0xa0, 0x02,
+    0xff, 0xff, 0xff, 0xc2, 0x00, 0x00, 0x02, 0x04, 0x05, 0xb4, 0x04, 0x02, 0x08, 0x0a, 0x00, 0x00,
+    0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x01, 0x03, 0x03, 0x07,
+];
+
+/// TCP PSH+ACK from port 49152 to 80, sequence number 1001,
+/// acknowledgment number 5001, window 65535, carrying the 18 bytes
+/// `GET / HTTP/1.0\r\n\r\n`. 72 bytes.
+pub const TCP_PAYLOAD: &[u8] = &[
+    0x02, 0x00, 0x00, 0x00, 0x00, 0x02, 0x02, 0x00, 0x00, 0x00, 0x00, 0x01, 0x08, 0x00, 0x45, 0x00,
+    0x00, 0x3a, 0x00, 0x00, 0x40, 0x00, 0x40, 0x06, 0xb6, 0xba, 0xc0, 0x00, 0x02, 0x01, 0xc0, 0x00,
+    0x02, 0x02, 0xc0, 0x00, 0x00, 0x50, 0x00, 0x00, 0x03, 0xe9, 0x00, 0x00, 0x13, 0x89, 0x50, 0x18,
+    0xff, 0xff, 0x75, 0x54, 0x00, 0x00, 0x47, 0x45, 0x54, 0x20, 0x2f, 0x20, 0x48, 0x54, 0x54, 0x50,
+    0x2f, 0x31, 0x2e, 0x30, 0x0d, 0x0a, 0x0d, 0x0a,
+];
+
+/// First fragment of a UDP datagram from port 5000 to 6000 with 40 bytes
+/// of data, `0x00` to `0x27`: identification 0x1234, MF set, offset 0,
+/// the UDP header and the first 16 data bytes. 58 bytes.
+pub const UDP_FRAGMENT_1: &[u8] = &[
+    0x02, 0x00, 0x00, 0x00, 0x00, 0x02, 0x02, 0x00, 0x00, 0x00, 0x00, 0x01, 0x08, 0x00, 0x45, 0x00,
+    0x00, 0x2c, 0x12, 0x34, 0x20, 0x00, 0x40, 0x11, 0xc4, 0x89, 0xc0, 0x00, 0x02, 0x01, 0xc0, 0x00,
+    0x02, 0x02, 0x13, 0x88, 0x17, 0x70, 0x00, 0x30, 0xd3, 0x00, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05,
+    0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f,
+];
+
+/// Second and last fragment of the datagram of `UDP_FRAGMENT_1`: offset
+/// 3 (24 bytes), the last 24 data bytes. 58 bytes.
+pub const UDP_FRAGMENT_2: &[u8] = &[
+    0x02, 0x00, 0x00, 0x00, 0x00, 0x02, 0x02, 0x00, 0x00, 0x00, 0x00, 0x01, 0x08, 0x00, 0x45, 0x00,
+    0x00, 0x2c, 0x12, 0x34, 0x00, 0x03, 0x40, 0x11, 0xe4, 0x86, 0xc0, 0x00, 0x02, 0x01, 0xc0, 0x00,
+    0x02, 0x02, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d,
+    0x1e, 0x1f, 0x20, 0x21, 0x22, 0x23, 0x24, 0x25, 0x26, 0x27,
+];
+
+/// ARP request from the client asking for the server's MAC address,
+/// broadcast with an 802.1Q tag: VLAN 100, priority 0. 46 bytes.
+pub const VLAN_ARP: &[u8] = &[
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x02, 0x00, 0x00, 0x00, 0x00, 0x01, 0x81, 0x00, 0x00, 0x64,
+    0x08, 0x06, 0x00, 0x01, 0x08, 0x00, 0x06, 0x04, 0x00, 0x01, 0x02, 0x00, 0x00, 0x00, 0x00, 0x01,
+    0xc0, 0x00, 0x02, 0x01, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xc0, 0x00, 0x02, 0x02,
+];
+
+/// ICMP echo request from the client to the server, identifier 0x1234,
+/// sequence number 1, 16 data bytes `abcdefghijklmnop`. 58 bytes.
+pub const ICMP_ECHO: &[u8] = &[
+    0x02, 0x00, 0x00, 0x00, 0x00, 0x02, 0x02, 0x00, 0x00, 0x00, 0x00, 0x01, 0x08, 0x00, 0x45, 0x00,
+    0x00, 0x2c, 0x00, 0x00, 0x40, 0x00, 0x40, 0x01, 0xb6, 0xcd, 0xc0, 0x00, 0x02, 0x01, 0xc0, 0x00,
+    0x02, 0x02, 0x08, 0x00, 0xa2, 0x7f, 0x12, 0x34, 0x00, 0x01, 0x61, 0x62, 0x63, 0x64, 0x65, 0x66,
+    0x67, 0x68, 0x69, 0x6a, 0x6b, 0x6c, 0x6d, 0x6e, 0x6f, 0x70,
+];
+
+/// DNS query with ID 0x2a01 and recursion desired for the A record of
+/// `example.com`, from port 49153 to 53. 71 bytes.
+pub const DNS_QUERY: &[u8] = &[
+    0x02, 0x00, 0x00, 0x00, 0x00, 0x02, 0x02, 0x00, 0x00, 0x00, 0x00, 0x01, 0x08, 0x00, 0x45, 0x00,
+    0x00, 0x39, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11, 0xb6, 0xb0, 0xc0, 0x00, 0x02, 0x01, 0xc0, 0x00,
+    0x02, 0x02, 0xc0, 0x01, 0x00, 0x35, 0x00, 0x25, 0xc1, 0xfa, 0x2a, 0x01, 0x01, 0x00, 0x00, 0x01,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x07, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65, 0x03, 0x63,
+    0x6f, 0x6d, 0x00, 0x00, 0x01, 0x00, 0x01,
+];
+
+fn tcp_packet(tcp: TCP) -> (IPv4, TCP) {
+    let tcp = tcp.with_checksum(&PSEUDO_HEADER);
+    let ip = IPv4::with_payload(
+        CLIENT_IP,
+        SERVER_IP,
+        crate::tcp::IP_PROTOCOL,
+        tcp.to_bytes(),
+    );
+    (ip, tcp)
+}
+
+/// Headers of `MINIMAL_SYN`.
+pub fn minimal_syn() -> (IPv4, TCP) {
+    tcp_packet(TCP::segment(49152, 80, 1000, 0, flags::SYN))
+}
+
+/// Headers of `SYN_WITH_OPTIONS`.
+pub fn syn_with_options() -> (IPv4, TCP) {
+    let options = [
+        TcpOption::Mss(1460),
+        TcpOption::SackPermitted,
+        TcpOption::Timestamps { tsval: 1, tsecr: 0 },
+        TcpOption::Nop,
+        TcpOption::WindowScale(7),
+    ];
+    tcp_packet(TCP::segment(49152, 80, 1000, 0, flags::SYN).set_tcp_options(&options))
+}
+
+/// Headers of `TCP_PAYLOAD`.
+pub fn tcp_payload() -> (IPv4, TCP) {
+    let tcp = TCP::segment(49152, 80, 1001, 5001, flags::PSH | flags::ACK)
+        .set_data(b"GET / HTTP/1.0\r\n\r\n".to_vec());
+    tcp_packet(tcp)
+}
+
+/// Fragments of `UDP_FRAGMENT_1` and `UDP_FRAGMENT_2`, and the datagram
+/// they reassemble to.
+pub fn udp_fragments() -> ([IPv4; 2], UDP) {
+    let udp = UDP::new(5000, 6000, (0..40).collect()).with_checksum(&PSEUDO_HEADER);
+    let datagram = udp.to_bytes();
+    let fragment = |payload: &[u8], more: bool, offset: u16| {
+        let mut ip = IPv4::with_payload(CLIENT_IP, SERVER_IP, 17, payload.to_vec());
+        ip.identification = 0x1234;
+        ip.flags = if more { FLAG_MORE_FRAGMENTS } else { 0 };
+        ip.fragment_offset = offset;
+        ip.with_checksum()
+    };
+    let fragments = [
+        fragment(&datagram[..24], true, 0),
+        fragment(&datagram[24..], false, 3),
+    ];
+    (fragments, udp)
+}
+
+/// Tag and ARP packet of `VLAN_ARP`.
+pub fn vlan_arp() -> (VlanTag, Arp) {
+    (
+        VlanTag::new(0, false, 100),
+        Arp::request(CLIENT_MAC, CLIENT_IP, SERVER_IP),
+    )
+}
+
+/// Headers of `ICMP_ECHO`.
+pub fn icmp_echo() -> (IPv4, Icmpv4) {
+    let icmp = Icmpv4::echo_request(0x1234, 1, b"abcdefghijklmnop".to_vec());
+    (icmp.to_ipv4(CLIENT_IP, SERVER_IP), icmp)
+}
+
+/// Headers and message of `DNS_QUERY`.
+pub fn dns_query() -> (IPv4, UDP, Dns) {
+    let dns = Dns::query(0x2a01, "example.com", DnsType::A);
+    let udp = UDP::new(49153, 53, dns.to_bytes()).with_checksum(&PSEUDO_HEADER);
+    let ip = IPv4::with_payload(CLIENT_IP, SERVER_IP, 17, udp.to_bytes());
+    (ip, udp, dns)
+}
+
+/// Every vector with its name (the constant's, in lower case) and its
+/// decoded layers, for sweeping them all in a test.
+pub fn all() -> impl Iterator<Item = (&'static str, &'static [u8], DecodedStack)> {
+    [
+        ("minimal_syn", MINIMAL_SYN),
+        ("syn_with_options", SYN_WITH_OPTIONS),
+        ("tcp_payload", TCP_PAYLOAD),
+        ("udp_fragment_1", UDP_FRAGMENT_1),
+        ("udp_fragment_2", UDP_FRAGMENT_2),
+        ("vlan_arp", VLAN_ARP),
+        ("icmp_echo", ICMP_ECHO),
+        ("dns_query", DNS_QUERY),
+    ]
+    .into_iter()
+    .map(|(name, bytes)| (name, bytes, DecodedStack::decode(LINKTYPE_ETHERNET, bytes)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ethernet::{ETHERTYPE_ARP, ETHERTYPE_IPV4, Ethernet};
+    use crate::fragment::FragmentReassembler;
+    use crate::pcap::{NetworkLayer, TransportLayer};
+    use crate::udp::ChecksumStatus;
+
+    fn frame(destination: MacAddr, ip: &IPv4) -> Vec<u8> {
+        Ethernet::new(destination, CLIENT_MAC, ETHERTYPE_IPV4, ip.to_bytes()).to_bytes()
+    }
+
+    fn tcp_frame((ip, _): (IPv4, TCP)) -> Vec<u8> {
+        frame(SERVER_MAC, &ip)
+    }
+
+    #[test]
+    fn tcp_vectors_round_trip() {
+        let vectors = [
+            (MINIMAL_SYN, minimal_syn()),
+            (SYN_WITH_OPTIONS, syn_with_options()),
+            (TCP_PAYLOAD, tcp_payload()),
+        ];
+        for (bytes, headers) in vectors {
+            assert_eq!(tcp_frame(headers.clone()), bytes);
+            let (ip, tcp) = headers;
+            let decoded = DecodedStack::decode(LINKTYPE_ETHERNET, bytes);
+            assert_eq!(decoded.network, NetworkLayer::Ipv4(ip));
+            assert_eq!(decoded.transport, TransportLayer::Tcp(tcp));
+        }
+        let (_, tcp) = syn_with_options();
+        assert_eq!(tcp.tcp_options().unwrap().len(), 5);
+        assert_eq!(tcp.data_offset, 10);
+    }
+
+    #[test]
+    fn fragments_reassemble_to_the_datagram() {
+        let ([first, second], udp) = udp_fragments();
+        assert_eq!(frame(SERVER_MAC, &first), UDP_FRAGMENT_1);
+        assert_eq!(frame(SERVER_MAC, &second), UDP_FRAGMENT_2);
+
+        // The first fragment has the UDP header, with the data it carries.
+        let decoded = DecodedStack::decode(LINKTYPE_ETHERNET, UDP_FRAGMENT_1);
+        let header = decoded.transport.as_udp().unwrap();
+        assert_eq!((header.length, header.data.len()), (48, 16));
+        let decoded = DecodedStack::decode(LINKTYPE_ETHERNET, UDP_FRAGMENT_2);
+        assert_eq!(decoded.transport, TransportLayer::None);
+
+        let mut reassembler = FragmentReassembler::new();
+        for bytes in [UDP_FRAGMENT_1, UDP_FRAGMENT_2] {
+            let decoded = DecodedStack::decode(LINKTYPE_ETHERNET, bytes);
+            let ip = decoded.network.as_ipv4().unwrap().clone();
+            if let Some(datagram) = reassembler.push_ipv4(ip).unwrap() {
+                assert_eq!(UDP::from_bytes(&datagram.payload).unwrap(), udp);
+                assert_eq!(udp.checksum_status(&PSEUDO_HEADER), ChecksumStatus::Valid);
+                return;
+            }
+        }
+        panic!("fragments did not reassemble");
+    }
+
+    #[test]
+    fn other_vectors_round_trip() {
+        let (tag, arp) = vlan_arp();
+        let mut ethernet = Ethernet::from_bytes(VLAN_ARP).unwrap();
+        assert_eq!(ethernet.clone().to_bytes(), VLAN_ARP);
+        assert_eq!(ethernet.pop_vlan_tag(), Some(tag));
+        assert_eq!(ethernet.ethertype, ETHERTYPE_ARP);
+        assert_eq!(Arp::from_bytes(&ethernet.payload).unwrap(), arp);
+        assert_eq!(arp.to_ethernet().push_vlan_tag(tag).to_bytes(), VLAN_ARP);
+
+        let (ip, icmp) = icmp_echo();
+        assert_eq!(frame(SERVER_MAC, &ip), ICMP_ECHO);
+        let decoded = DecodedStack::decode(LINKTYPE_ETHERNET, ICMP_ECHO);
+        let payload = &decoded.network.as_ipv4().unwrap().payload;
+        assert_eq!(Icmpv4::from_bytes(payload).unwrap(), icmp);
+
+        let (ip, udp, dns) = dns_query();
+        assert_eq!(frame(SERVER_MAC, &ip), DNS_QUERY);
+        let decoded = DecodedStack::decode(LINKTYPE_ETHERNET, DNS_QUERY);
+        assert_eq!(decoded.transport, TransportLayer::Udp(udp.clone()));
+        assert_eq!(Dns::from_bytes(&udp.data).unwrap(), dns);
+    }
+
+    #[test]
+    fn all_vectors_decode_cleanly() {
+        assert_eq!(all().count(), 8);
+        for (name, bytes, layers) in all() {
+            let parsed = DecodedStack::parse(LINKTYPE_ETHERNET, bytes);
+            assert!(parsed.is_clean(), "{name}: {parsed}");
+            assert_eq!(parsed.layers, layers, "{name}");
+            assert_eq!(layers.ethernet.unwrap().source, CLIENT_MAC, "{name}");
+        }
+    }
+}
@@ -0,0 +1,303 @@
+// Raw link-layer frame I/O: the traits senders and receivers of complete
+// frames implement, an in-memory loopback, and the OS backends, with the
+// parser of BSD BPF device reads the macOS backend uses.
+//
+// Like `std::net::UdpSocket`, the methods take `&self`: a socket can be
+// shared by a sender and a receiver thread.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::filter::Filter;
+
+mod bpf_records;
+
+#[cfg(all(target_os = "linux", feature = "transport"))]
+mod linux;
+
+#[cfg(all(target_os = "macos", feature = "transport-macos"))]
+mod macos;
+
+#[cfg(all(windows, feature = "transport"))]
+mod windows;
+
+pub use bpf_records::{BpfRecord, BpfRecords};
+
+#[cfg(all(target_os = "linux", feature = "transport"))]
+pub use linux::RawSocket;
+
+#[cfg(all(target_os = "macos", feature = "transport-macos"))]
+pub use macos::RawSocket;
+
+#[cfg(all(windows, feature = "transport"))]
+pub use windows::{Interface, RawSocket, interfaces};
+
+/// Sends complete link-layer frames.
+pub trait RawSender {
+    /// Sends one frame, link-layer header included.
+    fn send(&self, frame: &[u8]) -> io::Result<()>;
+
+    /// Sends `frames` in order, stopping at the first failure. Returns
+    /// the number of frames sent, like `io::Write::write` does for bytes:
+    /// the error is only returned when no frame was sent.
+    fn send_batch(&self, frames: &[&[u8]]) -> io::Result<usize> {
+        for (sent, frame) in frames.iter().enumerate() {
+            if let Err(err) = self.send(frame) {
+                return match sent {
+                    0 => Err(err),
+                    _ => Ok(sent),
+                };
+            }
+        }
+        Ok(frames.len())
+    }
+}
+
+/// Frame received by `RawReceiver::recv_into`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RecvMeta {
+    /// Bytes written to the buffer.
+    pub len: usize,
+    /// Set if the frame was longer than the buffer and got cut.
+    pub truncated: bool,
+    /// Time the frame was received, since the Unix epoch, if the receiver
+    /// reports it.
+    pub timestamp: Option<Duration>,
+}
+
+/// Receives complete link-layer frames.
+pub trait RawReceiver {
+    /// Receives one frame into `buf` and returns its length. A frame
+    /// longer than `buf` is truncated.
+    fn recv(&self, buf: &mut [u8]) -> io::Result<usize>;
+
+    /// Receives frames into the buffers of `bufs`, one each, waiting for
+    /// the first only, and describes frame `i` in `meta[i]`. Returns the
+    /// number of frames received, at most the length of the shorter
+    /// slice. The buffers belong to the caller and are reused from call to
+    /// call, so nothing is allocated per frame. The default receives one
+    /// frame through `recv`, without timestamp and never marked truncated.
+    fn recv_into(&self, bufs: &mut [&mut [u8]], meta: &mut [RecvMeta]) -> io::Result<usize> {
+        let (Some(buf), Some(meta)) = (bufs.first_mut(), meta.first_mut()) else {
+            return Ok(0);
+        };
+        let len = self.recv(buf)?;
+        *meta = RecvMeta {
+            len,
+            truncated: false,
+            timestamp: None,
+        };
+        Ok(1)
+    }
+
+    /// Makes the receiver drop the frames `filter` does not match before
+    /// they are queued, by compiling it to BPF for the kernel. Fails with
+    /// `Unsupported` if the receiver cannot filter, and with
+    /// `InvalidInput` if `filter` cannot be compiled: match frames with
+    /// `Filter::matches_frame` after receiving them then.
+    fn set_filter(&self, filter: &Filter) -> io::Result<()> {
+        let _ = filter;
+        Err(io::ErrorKind::Unsupported.into())
+    }
+}
+
+impl<T: RawSender + ?Sized> RawSender for &T {
+    fn send(&self, frame: &[u8]) -> io::Result<()> {
+        (**self).send(frame)
+    }
+
+    fn send_batch(&self, frames: &[&[u8]]) -> io::Result<usize> {
+        (**self).send_batch(frames)
+    }
+}
+
+impl<T: RawReceiver + ?Sized> RawReceiver for &T {
+    fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        (**self).recv(buf)
+    }
+
+    fn recv_into(&self, bufs: &mut [&mut [u8]], meta: &mut [RecvMeta]) -> io::Result<usize> {
+        (**self).recv_into(bufs, meta)
+    }
+
+    fn set_filter(&self, filter: &Filter) -> io::Result<()> {
+        (**self).set_filter(filter)
+    }
+}
+
+/// Sends `frames` through `socket` in batches of `batch` frames (see
+/// `RawSender::send_batch`; 0 is taken as 1), paced to `rate_pps` frames
+/// per second against a fixed schedule, so a late batch does not delay
+/// the following ones; a rate of 0 sends them back to back. Returns the
+/// number of frames sent.
+pub fn send_paced<S, I>(socket: &S, frames: I, rate_pps: u64, batch: usize) -> io::Result<u64>
+where
+    S: RawSender + ?Sized,
+    I: IntoIterator<Item = Vec<u8>>,
+{
+    let start = Instant::now();
+    let mut frames = frames.into_iter().peekable();
+    let mut pending: Vec<Vec<u8>> = Vec::with_capacity(batch.max(1));
+    let mut count = 0u64;
+    while frames.peek().is_some() {
+        pending.clear();
+        pending.extend(frames.by_ref().take(batch.max(1)));
+        if let Some(nanos) = (count * 1_000_000_000).checked_div(rate_pps) {
+            let due = start + Duration::from_nanos(nanos);
+            let now = Instant::now();
+            if due > now {
+                thread::sleep(due - now);
+            }
+        }
+        let mut batch: Vec<&[u8]> = pending.iter().map(Vec::as_slice).collect();
+        while !batch.is_empty() {
+            let sent = socket.send_batch(&batch)?;
+            batch.drain(..sent);
+            count += sent as u64;
+        }
+    }
+    Ok(count)
+}
+
+/// In-memory transport: frames sent are queued and received back in
+/// order. Receiving from an empty queue fails with `WouldBlock`.
+#[derive(Debug, Default)]
+pub struct Loopback {
+    frames: RefCell<VecDeque<Vec<u8>>>,
+}
+
+impl Loopback {
+    /// Constructor to create an empty loopback.
+    pub fn new() -> Self {
+        Loopback::default()
+    }
+
+    /// Number of frames sent and not received yet.
+    pub fn len(&self) -> usize {
+        self.frames.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.borrow().is_empty()
+    }
+
+    /// Removes and returns the frames sent and not received yet.
+    pub fn drain(&self) -> Vec<Vec<u8>> {
+        self.frames.borrow_mut().drain(..).collect()
+    }
+}
+
+impl RawSender for Loopback {
+    fn send(&self, frame: &[u8]) -> io::Result<()> {
+        self.frames.borrow_mut().push_back(frame.to_vec());
+        Ok(())
+    }
+}
+
+impl RawReceiver for Loopback {
+    fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        let frame = self
+            .frames
+            .borrow_mut()
+            .pop_front()
+            .ok_or(io::ErrorKind::WouldBlock)?;
+        let len = frame.len().min(buf.len());
+        buf[..len].copy_from_slice(&frame[..len]);
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fails every send after the first `limit`.
+    struct Limited {
+        inner: Loopback,
+        limit: usize,
+    }
+
+    impl RawSender for Limited {
+        fn send(&self, frame: &[u8]) -> io::Result<()> {
+            if self.inner.len() == self.limit {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            self.inner.send(frame)
+        }
+    }
+
+    #[test]
+    fn batches_stop_at_the_first_failure() {
+        let frames: [&[u8]; 3] = [b"one", b"two", b"three"];
+        let loopback = Loopback::new();
+        assert_eq!(loopback.send_batch(&frames).unwrap(), 3);
+        let mut buf = [0; 4];
+        assert_eq!(loopback.recv(&mut buf).unwrap(), 3);
+        assert_eq!(&buf[..3], b"one");
+        // Truncated to the buffer.
+        loopback.recv(&mut buf).unwrap();
+        assert_eq!(loopback.recv(&mut buf).unwrap(), 4);
+        assert_eq!(&buf, b"thre");
+        assert_eq!(
+            loopback.recv(&mut buf).unwrap_err().kind(),
+            io::ErrorKind::WouldBlock
+        );
+
+        let limited = Limited {
+            inner: Loopback::new(),
+            limit: 2,
+        };
+        assert_eq!(limited.send_batch(&frames).unwrap(), 2);
+        assert_eq!(
+            limited.send_batch(&frames).unwrap_err().kind(),
+            io::ErrorKind::WouldBlock
+        );
+    }
+
+    #[test]
+    fn recv_into_defaults_to_one_frame() {
+        let loopback = Loopback::new();
+        loopback.send_batch(&[b"one", b"two"]).unwrap();
+        let mut storage = [0; 8];
+        let mut bufs: Vec<&mut [u8]> = storage.chunks_mut(4).collect();
+        let mut meta = [RecvMeta::default(); 2];
+        assert_eq!(loopback.recv_into(&mut bufs, &mut meta).unwrap(), 1);
+        assert_eq!(&bufs[0][..meta[0].len], b"one");
+        assert_eq!(meta[0].timestamp, None);
+        assert_eq!(loopback.recv_into(&mut bufs, &mut []).unwrap(), 0);
+    }
+
+    #[test]
+    fn paced_batches_keep_the_rate() {
+        let frames = (0..100u8).map(|i| vec![i; 60]);
+        let limited = Limited {
+            inner: Loopback::new(),
+            limit: usize::MAX,
+        };
+        let start = Instant::now();
+        assert_eq!(send_paced(&limited, frames, 10_000, 8).unwrap(), 100);
+        // The last batch starts at frame 96, 9.6 ms in.
+        assert!(start.elapsed() >= Duration::from_micros(9_600));
+        let frames = limited.inner.drain();
+        assert_eq!(frames.len(), 100);
+        assert!(
+            frames
+                .iter()
+                .enumerate()
+                .all(|(i, frame)| frame[0] == i as u8)
+        );
+
+        // Partial batches are completed.
+        let limited = Limited {
+            inner: Loopback::new(),
+            limit: 5,
+        };
+        let frames = (0..10u8).map(|i| vec![i]);
+        let err = send_paced(&limited, frames, 0, 4).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+        assert_eq!(limited.inner.len(), 5);
+    }
+}
@@ -0,0 +1,173 @@
+use std::time::Duration;
+
+use crate::util::{ParseError, ensure_len};
+
+// A read from a BSD BPF device (/dev/bpfN) returns every frame that fits
+// the buffer, each behind a header, in host byte order (macOS layout):
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                       Timestamp seconds                       |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                    Timestamp microseconds                     |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                        Captured length                        |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                        Original length                        |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |         Header length         |    Padding, then the frame    |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// The header length includes padding the kernel adds so the network
+// header of the frame is aligned. The next record starts after the
+// captured bytes, on a 4-byte boundary (BPF_WORDALIGN).
+
+/// Header fields up to the header length.
+const HEADER_LEN: usize = 18;
+/// Alignment of the records.
+const RECORD_ALIGNMENT: usize = 4;
+
+/// Frame of a BPF device read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BpfRecord<'a> {
+    /// Time the frame was captured, since the Unix epoch.
+    pub timestamp: Duration,
+    /// Length of the frame on the wire, more than `data` holds if the
+    /// capture cut it.
+    pub original_len: usize,
+    pub data: &'a [u8],
+}
+
+/// Iterator over the records of a BPF device read. A record that does
+/// not fit the buffer yields an error and ends the iteration.
+#[derive(Debug, Clone)]
+pub struct BpfRecords<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> BpfRecords<'a> {
+    /// Constructor to create an iterator over the bytes returned by one
+    /// `read` of a BPF device.
+    pub fn new(buf: &'a [u8]) -> Self {
+        BpfRecords { buf }
+    }
+
+    /// Bytes of the records not returned yet.
+    pub fn remainder(&self) -> &'a [u8] {
+        self.buf
+    }
+}
+
+impl<'a> Iterator for BpfRecords<'a> {
+    type Item = Result<BpfRecord<'a>, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buf.is_empty() {
+            return None;
+        }
+        match parse_record(self.buf) {
+            Ok((record, len)) => {
+                self.buf = self.buf.get(len..).unwrap_or_default();
+                Some(Ok(record))
+            }
+            Err(err) => {
+                self.buf = &[];
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// Parses the record at the start of `buf`, returning it with the offset
+/// of the next one.
+fn parse_record(buf: &[u8]) -> Result<(BpfRecord<'_>, usize), ParseError> {
+    ensure_len(buf, HEADER_LEN)?;
+    let word = |offset: usize| {
+        u32::from_ne_bytes([
+            buf[offset],
+            buf[offset + 1],
+            buf[offset + 2],
+            buf[offset + 3],
+        ])
+    };
+    let (seconds, micros) = (word(0), word(4));
+    let (captured_len, original_len) = (word(8) as usize, word(12) as usize);
+    let header_len = u16::from_ne_bytes([buf[16], buf[17]]) as usize;
+    if header_len < HEADER_LEN {
+        return Err(ParseError::InvalidField("bh_hdrlen"));
+    }
+    if micros >= 1_000_000 {
+        return Err(ParseError::InvalidField("bh_tstamp"));
+    }
+    let end = header_len + captured_len;
+    ensure_len(buf, end)?;
+    let record = BpfRecord {
+        timestamp: Duration::new(seconds.into(), micros * 1000),
+        original_len,
+        data: &buf[header_len..end],
+    };
+    Ok((record, end.next_multiple_of(RECORD_ALIGNMENT)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Appends a record as the kernel writes it, with the 2 bytes of
+    /// padding macOS puts after the 18-byte header.
+    fn push_record(buf: &mut Vec<u8>, seconds: u32, micros: u32, data: &[u8], original_len: u32) {
+        for word in [seconds, micros, data.len() as u32, original_len] {
+            buf.extend(word.to_ne_bytes());
+        }
+        buf.extend(20u16.to_ne_bytes());
+        buf.extend([0; 2]);
+        buf.extend(data);
+        buf.resize(buf.len().next_multiple_of(4), 0);
+    }
+
+    #[test]
+    fn reads_are_split_into_records() {
+        let mut buf = Vec::new();
+        push_record(&mut buf, 1_700_000_000, 250_000, &[1; 60], 60);
+        push_record(&mut buf, 1_700_000_000, 250_001, &[2; 43], 43);
+        // Cut to the snapshot length.
+        push_record(&mut buf, 1_700_000_001, 0, &[3; 64], 1514);
+        let records: Vec<_> = BpfRecords::new(&buf).map(Result::unwrap).collect();
+        assert_eq!(records.len(), 3);
+        assert_eq!(
+            records[0].timestamp,
+            Duration::new(1_700_000_000, 250_000_000)
+        );
+        assert_eq!(records[0].data, [1; 60]);
+        // 43 bytes, padded to 44.
+        assert_eq!(records[1].data, [2; 43]);
+        assert_eq!(records[2].data, [3; 64]);
+        assert_eq!(records[2].original_len, 1514);
+        assert_eq!(BpfRecords::new(&[]).next(), None);
+    }
+
+    #[test]
+    fn broken_records_end_the_iteration() {
+        let mut buf = Vec::new();
+        push_record(&mut buf, 1, 0, &[1; 8], 8);
+        let complete = buf.len();
+        push_record(&mut buf, 2, 0, &[2; 40], 40);
+        let mut records = BpfRecords::new(&buf[..complete + 30]);
+        assert_eq!(records.next().unwrap().unwrap().data, [1; 8]);
+        assert_eq!(records.remainder().len(), 30);
+        assert_eq!(
+            records.next(),
+            Some(Err(ParseError::Truncated {
+                needed: 60,
+                available: 30
+            }))
+        );
+        assert_eq!(records.next(), None);
+
+        buf[complete + 16..complete + 18].copy_from_slice(&4u16.to_ne_bytes());
+        let mut records = BpfRecords::new(&buf[complete..]);
+        assert_eq!(
+            records.next(),
+            Some(Err(ParseError::InvalidField("bh_hdrlen")))
+        );
+    }
+}
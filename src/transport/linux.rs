@@ -0,0 +1,496 @@
+// AF_PACKET sockets (Linux only, `transport` feature).
+
+use std::ffi::CString;
+use std::io;
+use std::mem;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::ptr;
+use std::time::Duration;
+
+use super::{RawReceiver, RawSender, RecvMeta};
+use crate::ethernet::MacAddr;
+use crate::filter::{Filter, SockFilter};
+use crate::pcap::LINKTYPE_ETHERNET;
+
+/// ETH_P_ALL, in network byte order: every protocol is received.
+const ALL_PROTOCOLS: u16 = (libc::ETH_P_ALL as u16).to_be();
+
+/// Most frames `recv_into` receives per system call; its message headers
+/// live on the stack.
+const RECV_BATCH: usize = 64;
+
+/// Control buffer of one message, in 8-byte words for the alignment of
+/// `cmsghdr`: room for an SCM_TIMESTAMPNS message and then some.
+const CONTROL_WORDS: usize = 8;
+
+/// AF_PACKET socket bound to one interface, sending and receiving whole
+/// Ethernet frames. Blocking unless `set_nonblocking` is called.
+#[derive(Debug)]
+pub struct RawSocket {
+    fd: OwnedFd,
+    ifindex: u32,
+}
+
+impl RawSocket {
+    /// Constructor to create a socket bound to `iface`. Requires
+    /// CAP_NET_RAW.
+    pub fn new(iface: &str) -> io::Result<RawSocket> {
+        let name = CString::new(iface)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid interface name"))?;
+        // SAFETY: `name` is a valid C string.
+        let ifindex = unsafe { libc::if_nametoindex(name.as_ptr()) };
+        if ifindex == 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // SAFETY: plain system call; the descriptor is owned right away.
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_PACKET,
+                libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+                ALL_PROTOCOLS.into(),
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `fd` is a new descriptor owned by nobody else.
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        // SAFETY: all-zero is a valid value of this plain C struct.
+        let mut address: libc::sockaddr_ll = unsafe { mem::zeroed() };
+        address.sll_family = libc::AF_PACKET as u16;
+        address.sll_protocol = ALL_PROTOCOLS;
+        address.sll_ifindex = ifindex as i32;
+        // SAFETY: `address` is a valid sockaddr_ll of the given length.
+        let ret = unsafe {
+            libc::bind(
+                fd.as_raw_fd(),
+                (&raw const address).cast(),
+                mem::size_of_val(&address) as libc::socklen_t,
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(RawSocket { fd, ifindex })
+    }
+
+    /// Index of the interface the socket is bound to.
+    pub fn ifindex(&self) -> u32 {
+        self.ifindex
+    }
+
+    /// Makes `send` and `recv` fail with `WouldBlock` instead of waiting.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        let fd = self.fd.as_raw_fd();
+        // SAFETY: plain system calls on a descriptor we own.
+        unsafe {
+            let flags = libc::fcntl(fd, libc::F_GETFL);
+            if flags < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let flags = match nonblocking {
+                true => flags | libc::O_NONBLOCK,
+                false => flags & !libc::O_NONBLOCK,
+            };
+            if libc::fcntl(fd, libc::F_SETFL, flags) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+
+    /// Makes `recv` fail with `WouldBlock` after waiting `timeout` for a
+    /// frame; `None` waits forever. Fails with `InvalidInput` for a zero
+    /// timeout, as `std::net::UdpSocket::set_read_timeout` does.
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        if timeout == Some(Duration::ZERO) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "zero timeout"));
+        }
+        let timeout = timeout.unwrap_or_default();
+        let timeval = libc::timeval {
+            tv_sec: timeout.as_secs().min(libc::time_t::MAX as u64) as libc::time_t,
+            tv_usec: timeout.subsec_micros() as libc::suseconds_t,
+        };
+        // SAFETY: `timeval` is a valid struct of the given length.
+        let ret = unsafe {
+            libc::setsockopt(
+                self.fd.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_RCVTIMEO,
+                (&raw const timeval).cast(),
+                mem::size_of_val(&timeval) as libc::socklen_t,
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Makes the kernel report the time each frame was received
+    /// (SO_TIMESTAMPNS), which `recv_into` returns.
+    pub fn set_timestamps(&self, enable: bool) -> io::Result<()> {
+        let enable = libc::c_int::from(enable);
+        // SAFETY: `enable` is a valid int of the given length.
+        let ret = unsafe {
+            libc::setsockopt(
+                self.fd.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_TIMESTAMPNS,
+                (&raw const enable).cast(),
+                mem::size_of_val(&enable) as libc::socklen_t,
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Hardware address of the interface, all zeros for `lo`.
+    pub fn mac_address(&self) -> io::Result<MacAddr> {
+        // SAFETY: all-zero is a valid value of this plain C struct.
+        let mut request: libc::ifreq = unsafe { mem::zeroed() };
+        // SAFETY: `ifr_name` has room for IF_NAMESIZE bytes.
+        let name = unsafe { libc::if_indextoname(self.ifindex, request.ifr_name.as_mut_ptr()) };
+        if name.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: SIOCGIFHWADDR reads the name and writes the address into
+        // `request`, which outlives the call.
+        let ret = unsafe {
+            libc::ioctl(
+                self.fd.as_raw_fd(),
+                libc::SIOCGIFHWADDR as _,
+                &raw mut request,
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: SIOCGIFHWADDR filled the hardware address member.
+        let address = unsafe { request.ifr_ifru.ifru_hwaddr.sa_data };
+        let mut octets = [0; 6];
+        for (octet, byte) in octets.iter_mut().zip(address) {
+            *octet = byte as u8;
+        }
+        Ok(MacAddr(octets))
+    }
+
+    /// Attaches a classic BPF program, replacing the previous one. Frames
+    /// it returns 0 for are dropped by the kernel. Fails with
+    /// `InvalidInput` if the kernel rejects the program.
+    pub fn attach_filter(&self, program: &[SockFilter]) -> io::Result<()> {
+        let len = u16::try_from(program.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "BPF program too long"))?;
+        let fprog = libc::sock_fprog {
+            len,
+            // The kernel copies the program and does not write to it.
+            filter: program.as_ptr().cast_mut().cast(),
+        };
+        // SAFETY: `fprog` points to `len` instructions laid out as `struct
+        // sock_filter`, valid for the duration of the call.
+        let ret = unsafe {
+            libc::setsockopt(
+                self.fd.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_ATTACH_FILTER,
+                (&raw const fprog).cast(),
+                mem::size_of_val(&fprog) as libc::socklen_t,
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+impl RawSender for RawSocket {
+    fn send(&self, frame: &[u8]) -> io::Result<()> {
+        // SAFETY: `frame` is readable for its length.
+        let ret = unsafe { libc::send(self.fd.as_raw_fd(), frame.as_ptr().cast(), frame.len(), 0) };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+impl RawReceiver for RawSocket {
+    fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        // SAFETY: `buf` is writable for its length.
+        let ret = unsafe { libc::recv(self.fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len(), 0) };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(ret as usize)
+    }
+
+    /// Receives up to 64 frames with one `recvmmsg` call, timestamped if
+    /// `set_timestamps` was called.
+    fn recv_into(&self, bufs: &mut [&mut [u8]], meta: &mut [RecvMeta]) -> io::Result<usize> {
+        let count = bufs.len().min(meta.len()).min(RECV_BATCH);
+        if count == 0 {
+            return Ok(0);
+        }
+        // SAFETY: all-zero is a valid value of these plain C structs.
+        let mut iovecs: [libc::iovec; RECV_BATCH] = unsafe { mem::zeroed() };
+        let mut headers: [libc::mmsghdr; RECV_BATCH] = unsafe { mem::zeroed() };
+        let mut controls = [[0u64; CONTROL_WORDS]; RECV_BATCH];
+        for (i, buf) in bufs[..count].iter_mut().enumerate() {
+            iovecs[i] = libc::iovec {
+                iov_base: buf.as_mut_ptr().cast(),
+                iov_len: buf.len(),
+            };
+            let header = &mut headers[i].msg_hdr;
+            header.msg_iov = &raw mut iovecs[i];
+            header.msg_iovlen = 1;
+            header.msg_control = controls[i].as_mut_ptr().cast();
+            header.msg_controllen = mem::size_of_val(&controls[i]) as _;
+        }
+        // SAFETY: the first `count` headers point to buffers and control
+        // buffers writable for the lengths given, which outlive the call.
+        let ret = unsafe {
+            libc::recvmmsg(
+                self.fd.as_raw_fd(),
+                headers.as_mut_ptr(),
+                count as libc::c_uint,
+                libc::MSG_WAITFORONE,
+                ptr::null_mut(),
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let received = ret as usize;
+        for (meta, header) in meta.iter_mut().zip(&headers[..received]) {
+            *meta = RecvMeta {
+                len: header.msg_len as usize,
+                truncated: header.msg_hdr.msg_flags & libc::MSG_TRUNC != 0,
+                // SAFETY: the kernel filled the control buffer of the
+                // message and set its length.
+                timestamp: unsafe { timestamp(&header.msg_hdr) },
+            };
+        }
+        Ok(received)
+    }
+
+    /// Compiles `filter` for Ethernet frames and attaches it. Frames
+    /// queued before the call are still received.
+    fn set_filter(&self, filter: &Filter) -> io::Result<()> {
+        let program = filter
+            .compile_bpf(LINKTYPE_ETHERNET)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        self.attach_filter(&program)
+    }
+}
+
+/// Reads the SCM_TIMESTAMPNS control message of a received message.
+///
+/// # Safety
+///
+/// `header` must have been filled by `recvmsg` or `recvmmsg`, its control
+/// buffer still alive.
+unsafe fn timestamp(header: &libc::msghdr) -> Option<Duration> {
+    // SAFETY: per the function contract, the control messages are valid.
+    unsafe {
+        let mut cmsg = libc::CMSG_FIRSTHDR(header);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_TIMESTAMPNS
+            {
+                let time: libc::timespec = ptr::read_unaligned(libc::CMSG_DATA(cmsg).cast());
+                return Some(Duration::new(time.tv_sec as u64, time.tv_nsec as u32));
+            }
+            cmsg = libc::CMSG_NXTHDR(header, cmsg);
+        }
+    }
+    None
+}
+
+impl AsRawFd for RawSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+    use crate::ethernet::{ETHERTYPE_IPV4, Ethernet, MacAddr};
+    use crate::ipv4::IPv4;
+    use crate::udp::UDP;
+
+    #[test]
+    fn loopback_interface_round_trip() {
+        // Needs CAP_NET_RAW; there is nothing to test without it.
+        let socket = match RawSocket::new("lo") {
+            Ok(socket) => socket,
+            Err(err) => {
+                eprintln!("cannot open an AF_PACKET socket on lo: {err}, skipping");
+                return;
+            }
+        };
+        let mut frame = vec![0xff; 6];
+        frame.extend([0x02, 0, 0, 0, 0, 0x2a]);
+        frame.extend([0x88, 0xb5]);
+        frame.extend(b"ethercrafter transport test".repeat(2));
+        socket.send(&frame).unwrap();
+
+        // Frames of other processes may show up on lo as well.
+        socket.set_nonblocking(true).unwrap();
+        let mut buf = [0; 2048];
+        let found = (0..1000).any(|_| match socket.recv(&mut buf) {
+            Ok(len) => buf[..len] == frame[..],
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                std::thread::sleep(std::time::Duration::from_millis(1));
+                false
+            }
+            Err(err) => panic!("recv failed: {err}"),
+        });
+        assert!(found);
+
+        assert_eq!(socket.mac_address().unwrap(), MacAddr([0; 6]));
+        socket.set_nonblocking(false).unwrap();
+        socket
+            .set_read_timeout(Some(std::time::Duration::from_millis(10)))
+            .unwrap();
+        // Drain lo, then time out.
+        let err = loop {
+            if let Err(err) = socket.recv(&mut buf) {
+                break err;
+            }
+        };
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+        assert!(socket.set_read_timeout(Some(Duration::ZERO)).is_err());
+    }
+
+    #[test]
+    fn kernel_runs_compiled_filters() {
+        let socket = match RawSocket::new("lo") {
+            Ok(socket) => socket,
+            Err(err) => {
+                eprintln!("cannot open an AF_PACKET socket on lo: {err}, skipping");
+                return;
+            }
+        };
+        let frame = |port: u16| {
+            let udp = UDP::new(47000, port, b"bpf".to_vec());
+            let ip = IPv4::with_payload(
+                Ipv4Addr::new(127, 0, 0, 1),
+                Ipv4Addr::new(127, 0, 0, 1),
+                17,
+                udp.to_bytes(),
+            );
+            let mac = MacAddr([0; 6]);
+            Ethernet::new(mac, mac, ETHERTYPE_IPV4, ip.with_checksum().to_bytes()).to_bytes()
+        };
+        let filter = Filter::parse("udp and dst port 47001").unwrap();
+        socket.set_filter(&filter).unwrap();
+        // Frames captured before the filter was attached may be queued.
+        socket.set_nonblocking(true).unwrap();
+        let mut buf = [0; 2048];
+        while socket.recv(&mut buf).is_ok() {}
+
+        socket.send(&frame(47002)).unwrap();
+        socket.send(&frame(47001)).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        let mut received = Vec::new();
+        while let Ok(len) = socket.recv(&mut buf) {
+            received.push(buf[..len].to_vec());
+        }
+        assert!(!received.is_empty());
+        assert!(received.iter().all(|data| *data == frame(47001)));
+
+        // The kernel checks programs: one not ending with a return is
+        // rejected.
+        let invalid = [SockFilter::new(0x28, 0, 0, 12)];
+        let err = socket.attach_filter(&invalid).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let vlan = Filter::parse("vlan").unwrap();
+        let err = socket.set_filter(&vlan).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn batches_are_received_with_timestamps() {
+        let socket = match RawSocket::new("lo") {
+            Ok(socket) => socket,
+            Err(err) => {
+                eprintln!("cannot open an AF_PACKET socket on lo: {err}, skipping");
+                return;
+            }
+        };
+        socket
+            .set_filter(&Filter::parse("ether proto 0x88b6").unwrap())
+            .unwrap();
+        socket.set_timestamps(true).unwrap();
+        socket.set_nonblocking(true).unwrap();
+        let mut buf = [0; 2048];
+        while socket.recv(&mut buf).is_ok() {}
+
+        let frames: Vec<Vec<u8>> = (0..5u8)
+            .map(|i| {
+                let mut frame = vec![0; 12];
+                frame.extend([0x88, 0xb6]);
+                frame.extend([i; 50]);
+                frame
+            })
+            .collect();
+        for frame in &frames {
+            socket.send(frame).unwrap();
+        }
+        std::thread::sleep(Duration::from_millis(20));
+
+        // The first buffer is too short for the frames.
+        let mut storage = vec![0; 8 * 64];
+        let (short, rest) = storage.split_at_mut(64);
+        let mut bufs: Vec<&mut [u8]> = vec![&mut short[..20]];
+        bufs.extend(rest.chunks_mut(64));
+        let mut meta = [RecvMeta::default(); 8];
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap();
+        let mut received = 0;
+        loop {
+            let count = match socket.recv_into(&mut bufs, &mut meta) {
+                Ok(count) => count,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => panic!("recvmmsg failed: {err}"),
+            };
+            assert!(count > 0);
+            for (buf, meta) in bufs.iter().zip(&meta[..count]) {
+                let frame = &buf[..meta.len];
+                assert_eq!(meta.truncated, meta.len == 20);
+                assert!(frames.iter().any(|sent| sent.starts_with(frame)));
+                let timestamp = meta.timestamp.unwrap();
+                assert!(timestamp.abs_diff(now) < Duration::from_secs(60));
+            }
+            received += count;
+        }
+        // lo shows each frame going out and coming back in.
+        assert!(received >= frames.len());
+    }
+
+    #[test]
+    fn kernel_accepts_long_programs() {
+        let socket = match RawSocket::new("lo") {
+            Ok(socket) => socket,
+            Err(err) => {
+                eprintln!("cannot open an AF_PACKET socket on lo: {err}, skipping");
+                return;
+            }
+        };
+        // Long enough for jumps to need the unconditional form.
+        let hosts = (1..=300)
+            .map(|host| format!("host 10.1.{}.{}", host / 250, host % 250))
+            .collect::<Vec<_>>()
+            .join(" or ");
+        let filter = Filter::parse(&format!("tcp[tcpflags] & tcp-syn != 0 and ({hosts})")).unwrap();
+        socket.set_filter(&filter).unwrap();
+    }
+}
@@ -0,0 +1,395 @@
+// BPF devices (macOS only, `transport-macos` feature).
+//
+// The socket opens the first free /dev/bpfN, attaches it to the interface
+// and reads in immediate mode, so frames are returned as they arrive
+// rather than when the buffer fills. One read returns a batch of records
+// (see `BpfRecords`), kept until `recv` or `recv_into` has returned them
+// all.
+//
+// Opening a BPF device takes root, or read-write access to /dev/bpf*:
+// Wireshark's ChmodBPF installer gives it to the `access_bpf` group.
+//
+// The loopback interface, lo0, uses DLT_NULL framing (`LINKTYPE_NULL`)
+// instead of Ethernet: frames start with the address family of the
+// packet as a 4-byte integer in host byte order, 2 for IPv4 and 30 for
+// IPv6, which is also what `send` must put in front of the packet.
+
+use std::ffi::{CStr, CString, c_uint};
+use std::io;
+use std::mem;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use super::{BpfRecords, RawReceiver, RawSender, RecvMeta};
+use crate::ethernet::MacAddr;
+use crate::filter::{Filter, SockFilter};
+
+/// Read buffer asked for; the kernel may grant less.
+const BUFFER_LEN: c_uint = 1 << 20;
+
+/// Units tried, /dev/bpf0 to /dev/bpf255.
+const MAX_DEVICES: u32 = 256;
+
+/// `struct bpf_program`.
+#[repr(C)]
+struct BpfProgram {
+    bf_len: c_uint,
+    bf_insns: *const SockFilter,
+}
+
+/// Runs ioctl `request` on `fd`.
+///
+/// # Safety
+///
+/// `arg` must be of the type `request` reads or writes.
+unsafe fn ioctl<T>(fd: &OwnedFd, request: libc::c_ulong, arg: &mut T) -> io::Result<()> {
+    // SAFETY: per the function contract.
+    if unsafe { libc::ioctl(fd.as_raw_fd(), request, arg as *mut T) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Opens the first BPF device no other process holds.
+fn open_device() -> io::Result<OwnedFd> {
+    for unit in 0..MAX_DEVICES {
+        let path = CString::new(format!("/dev/bpf{unit}")).expect("no NUL in the path");
+        // SAFETY: `path` is a C string.
+        let fd = unsafe { libc::open(path.as_ptr(), libc::O_RDWR | libc::O_CLOEXEC) };
+        if fd >= 0 {
+            // SAFETY: `fd` is a new descriptor owned by nobody else.
+            return Ok(unsafe { OwnedFd::from_raw_fd(fd) });
+        }
+        let err = io::Error::last_os_error();
+        match err.raw_os_error() {
+            Some(libc::EBUSY) => continue,
+            Some(libc::EACCES | libc::EPERM) => {
+                let message = format!(
+                    "cannot open /dev/bpf{unit}: {err}; run as root or join the group \
+                     owning /dev/bpf* (access_bpf with Wireshark's ChmodBPF)"
+                );
+                return Err(io::Error::new(io::ErrorKind::PermissionDenied, message));
+            }
+            Some(libc::ENOENT) => break,
+            _ => return Err(err),
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        "no free /dev/bpf device",
+    ))
+}
+
+/// Last read of the device and the offset of the first record not
+/// returned yet.
+#[derive(Debug)]
+struct ReadBuffer {
+    data: Vec<u8>,
+    start: usize,
+    end: usize,
+}
+
+impl ReadBuffer {
+    /// Copies pending records into `bufs`, describing them in `meta`, and
+    /// returns how many. A broken record drops the rest of the read.
+    fn take(
+        &mut self,
+        bufs: &mut [&mut [u8]],
+        meta: &mut [RecvMeta],
+        timestamps: bool,
+    ) -> io::Result<usize> {
+        let mut records = BpfRecords::new(&self.data[self.start..self.end]);
+        let mut taken = 0;
+        for (buf, meta) in bufs.iter_mut().zip(meta) {
+            let record = match records.next() {
+                Some(Ok(record)) => record,
+                Some(Err(err)) if taken == 0 => {
+                    self.start = self.end;
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, err));
+                }
+                Some(Err(_)) | None => break,
+            };
+            let len = record.data.len().min(buf.len());
+            buf[..len].copy_from_slice(&record.data[..len]);
+            *meta = RecvMeta {
+                len,
+                truncated: record.original_len > len,
+                timestamp: timestamps.then_some(record.timestamp),
+            };
+            taken += 1;
+        }
+        self.start = self.end - records.remainder().len();
+        Ok(taken)
+    }
+}
+
+/// BPF device attached to one interface, sending and receiving whole
+/// frames. Blocking unless `set_nonblocking` is called.
+#[derive(Debug)]
+pub struct RawSocket {
+    fd: OwnedFd,
+    iface: String,
+    link_type: u32,
+    buffer: Mutex<ReadBuffer>,
+    timestamps: AtomicBool,
+}
+
+impl RawSocket {
+    /// Constructor to create a socket on `iface`, e.g. `en0` or `lo0`.
+    /// Fails with `PermissionDenied` without access to /dev/bpf*.
+    pub fn new(iface: &str) -> io::Result<RawSocket> {
+        let name = CString::new(iface)
+            .ok()
+            .filter(|name| name.as_bytes().len() < libc::IFNAMSIZ)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid interface name"))?;
+        let fd = open_device()?;
+
+        // SAFETY: all-zero is a valid value of this plain C struct.
+        let mut request: libc::ifreq = unsafe { mem::zeroed() };
+        for (dst, src) in request.ifr_name.iter_mut().zip(name.as_bytes()) {
+            *dst = *src as libc::c_char;
+        }
+        let mut buffer_len = BUFFER_LEN;
+        let mut one: c_uint = 1;
+        let mut link_type: c_uint = 0;
+        // SAFETY: each request is given the type it expects. The buffer
+        // length has to be set before the interface.
+        unsafe {
+            ioctl(&fd, libc::BIOCSBLEN, &mut buffer_len)?;
+            ioctl(&fd, libc::BIOCSETIF, &mut request)?;
+            ioctl(&fd, libc::BIOCIMMEDIATE, &mut one)?;
+            // Frames are sent with their source address as given.
+            ioctl(&fd, libc::BIOCSHDRCMPLT, &mut one)?;
+            ioctl(&fd, libc::BIOCGDLT, &mut link_type)?;
+            ioctl(&fd, libc::BIOCGBLEN, &mut buffer_len)?;
+        }
+        Ok(RawSocket {
+            fd,
+            iface: iface.to_string(),
+            link_type,
+            buffer: Mutex::new(ReadBuffer {
+                data: vec![0; buffer_len as usize],
+                start: 0,
+                end: 0,
+            }),
+            timestamps: AtomicBool::new(false),
+        })
+    }
+
+    /// Link type of the frames: Ethernet, or `LINKTYPE_NULL` on lo0.
+    pub fn link_type(&self) -> u32 {
+        self.link_type
+    }
+
+    /// Makes `send` and `recv` fail with `WouldBlock` instead of waiting.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        let fd = self.fd.as_raw_fd();
+        // SAFETY: plain system calls on a descriptor we own.
+        unsafe {
+            let flags = libc::fcntl(fd, libc::F_GETFL);
+            if flags < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let flags = match nonblocking {
+                true => flags | libc::O_NONBLOCK,
+                false => flags & !libc::O_NONBLOCK,
+            };
+            if libc::fcntl(fd, libc::F_SETFL, flags) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+
+    /// Makes `recv` fail with `WouldBlock` after waiting `timeout` for a
+    /// frame; `None` waits forever. Fails with `InvalidInput` for a zero
+    /// timeout, as `std::net::UdpSocket::set_read_timeout` does.
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        if timeout == Some(Duration::ZERO) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "zero timeout"));
+        }
+        let timeout = timeout.unwrap_or_default();
+        let mut timeval = libc::timeval {
+            tv_sec: timeout.as_secs().min(libc::time_t::MAX as u64) as libc::time_t,
+            tv_usec: timeout.subsec_micros() as libc::suseconds_t,
+        };
+        // SAFETY: BIOCSRTIMEOUT reads a timeval.
+        unsafe { ioctl(&self.fd, libc::BIOCSRTIMEOUT, &mut timeval) }
+    }
+
+    /// Makes `recv_into` return the time each frame was received, as the
+    /// kernel stamped it, to the microsecond.
+    pub fn set_timestamps(&self, enable: bool) -> io::Result<()> {
+        self.timestamps.store(enable, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Hardware address of the interface, all zeros for lo0.
+    pub fn mac_address(&self) -> io::Result<MacAddr> {
+        let mut addresses = std::ptr::null_mut();
+        // SAFETY: `addresses` receives a list freed below.
+        if unsafe { libc::getifaddrs(&mut addresses) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut mac = None;
+        let mut entry = addresses;
+        while !entry.is_null() {
+            // SAFETY: the list stays valid until freed; AF_LINK addresses
+            // are `sockaddr_dl`, with the name and then the address in
+            // `sdl_data`.
+            unsafe {
+                let address = (*entry).ifa_addr;
+                let name = CStr::from_ptr((*entry).ifa_name);
+                if !address.is_null()
+                    && (*address).sa_family as i32 == libc::AF_LINK
+                    && name.to_bytes() == self.iface.as_bytes()
+                {
+                    let link = address.cast::<libc::sockaddr_dl>();
+                    let mut octets = [0; 6];
+                    if (*link).sdl_alen == 6 {
+                        let data = (&raw const (*link).sdl_data).cast::<u8>();
+                        let offset = (*link).sdl_nlen as usize;
+                        std::ptr::copy_nonoverlapping(data.add(offset), octets.as_mut_ptr(), 6);
+                    }
+                    mac = Some(MacAddr(octets));
+                }
+                entry = (*entry).ifa_next;
+            }
+        }
+        // SAFETY: `addresses` came from getifaddrs and is not used after.
+        unsafe { libc::freeifaddrs(addresses) };
+        mac.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "interface has no link address"))
+    }
+
+    /// Attaches a classic BPF program, replacing the previous one. Frames
+    /// it returns 0 for are dropped by the kernel, which also discards the
+    /// frames it holds; those already read are still returned. Fails with
+    /// `InvalidInput` if the kernel rejects the program.
+    pub fn attach_filter(&self, program: &[SockFilter]) -> io::Result<()> {
+        let mut program = BpfProgram {
+            bf_len: program.len() as c_uint,
+            bf_insns: program.as_ptr(),
+        };
+        // SAFETY: BIOCSETF reads a `struct bpf_program`, whose
+        // instructions are laid out as `struct bpf_insn` and copied.
+        unsafe { ioctl(&self.fd, libc::BIOCSETF, &mut program) }
+    }
+}
+
+impl RawSender for RawSocket {
+    fn send(&self, frame: &[u8]) -> io::Result<()> {
+        // SAFETY: `frame` is readable for its length.
+        let ret = unsafe { libc::write(self.fd.as_raw_fd(), frame.as_ptr().cast(), frame.len()) };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+impl RawReceiver for RawSocket {
+    fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut meta = [RecvMeta::default()];
+        self.recv_into(&mut [buf], &mut meta)?;
+        Ok(meta[0].len)
+    }
+
+    /// Returns the records left from the last read of the device, or
+    /// reads it again, at most as many as there are buffers.
+    fn recv_into(&self, bufs: &mut [&mut [u8]], meta: &mut [RecvMeta]) -> io::Result<usize> {
+        if bufs.is_empty() || meta.is_empty() {
+            return Ok(0);
+        }
+        let timestamps = self.timestamps.load(Ordering::Relaxed);
+        let mut buffer = self.buffer.lock().unwrap();
+        loop {
+            let taken = buffer.take(bufs, meta, timestamps)?;
+            if taken > 0 {
+                return Ok(taken);
+            }
+            // Reads have to be of the whole buffer length.
+            let ReadBuffer { data, start, end } = &mut *buffer;
+            // SAFETY: `data` is writable for its length.
+            let ret =
+                unsafe { libc::read(self.fd.as_raw_fd(), data.as_mut_ptr().cast(), data.len()) };
+            match ret {
+                ..0 => return Err(io::Error::last_os_error()),
+                // The read timeout expired.
+                0 => return Err(io::ErrorKind::WouldBlock.into()),
+                len => (*start, *end) = (0, len as usize),
+            }
+        }
+    }
+
+    /// Compiles `filter` for the link type of the interface and attaches
+    /// it.
+    fn set_filter(&self, filter: &Filter) -> io::Result<()> {
+        let program = filter
+            .compile_bpf(self.link_type)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        self.attach_filter(&program)
+    }
+}
+
+impl AsRawFd for RawSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+    use crate::ipv4::IPv4;
+    use crate::pcap::LINKTYPE_NULL;
+    use crate::udp::UDP;
+
+    #[test]
+    fn loopback_interface_round_trip() {
+        // Needs access to /dev/bpf*; there is nothing to test without it.
+        let socket = match RawSocket::new("lo0") {
+            Ok(socket) => socket,
+            Err(err) => {
+                eprintln!("cannot open a BPF device on lo0: {err}, skipping");
+                return;
+            }
+        };
+        assert_eq!(socket.link_type(), LINKTYPE_NULL);
+        assert_eq!(socket.mac_address().unwrap(), MacAddr([0; 6]));
+
+        let localhost = Ipv4Addr::LOCALHOST;
+        let udp = UDP::new(47000, 47003, b"ethercrafter transport test".to_vec());
+        let ip = IPv4::with_payload(localhost, localhost, 17, udp.to_bytes());
+        let mut frame = (libc::AF_INET as u32).to_ne_bytes().to_vec();
+        frame.extend(ip.with_checksum().to_bytes());
+
+        socket
+            .set_filter(&Filter::parse("udp and dst port 47003").unwrap())
+            .unwrap();
+        socket.set_timestamps(true).unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+        socket.send(&frame).unwrap();
+
+        let mut storage = vec![0; 4 * 2048];
+        let mut bufs: Vec<&mut [u8]> = storage.chunks_mut(2048).collect();
+        let mut meta = [RecvMeta::default(); 4];
+        let count = socket.recv_into(&mut bufs, &mut meta).unwrap();
+        assert!(count > 0);
+        assert_eq!(&bufs[0][..meta[0].len], &frame[..]);
+        assert!(meta[0].timestamp.is_some());
+
+        // Drain lo0, then time out.
+        let err = loop {
+            if let Err(err) = socket.recv_into(&mut bufs, &mut meta) {
+                break err;
+            }
+        };
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+    }
+}
@@ -0,0 +1,726 @@
+// Npcap adapters (Windows only, `transport` feature).
+//
+// wpcap.dll is loaded when the first socket is opened, from the Npcap
+// directory of System32 and then from the DLL search path (WinPcap
+// compatible installs). Programs build and start without Npcap; only
+// `interfaces` and `RawSocket::new` fail, with `NotFound`.
+//
+// Each socket holds two pcap handles on the adapter, one sending and one
+// receiving, each behind a mutex: a pcap handle is not thread-safe, and a
+// thread blocked receiving must not hold up one sending.
+//
+// Manual test checklist (CI cannot run it), on a machine with Npcap and
+// one without:
+//
+//  1. `interfaces()` lists every adapter with the friendly name of the
+//     Network Connections panel, its GUID and MAC address.
+//  2. `RawSocket::new` opens the same adapter by friendly name, by
+//     `{GUID}`, by the GUID without braces and by `\Device\NPF_{GUID}`,
+//     and fails with `NotFound` for an unknown name.
+//  3. Without Npcap, `RawSocket::new` fails with `NotFound` and a message
+//     naming Npcap, and the program otherwise runs.
+//  4. Without administrator rights and with Npcap installed in
+//     admin-only mode, `RawSocket::new` fails with `PermissionDenied`.
+//  5. A frame sent with `send` shows up byte for byte in Wireshark on
+//     that adapter.
+//  6. `recv` returns frames between two other hosts (promiscuous mode),
+//     e.g. behind a hub or a mirrored switch port.
+//  7. During a ping flood, `recv_into` fills several buffers per call;
+//     the timestamps are within a second of the system clock once
+//     `set_timestamps(true)` is called, and absent before.
+//  8. `set_read_timeout(Some(100 ms))` makes `recv` fail with
+//     `WouldBlock` after about 100 ms without traffic, and
+//     `set_nonblocking(true)` makes it fail at once.
+//  9. After `set_filter(&Filter::parse("icmp")?)`, only ICMP frames are
+//     received.
+// 10. On the Npcap Loopback Adapter, `link_type` is 0 (DLT_NULL), frames
+//     start with the 4-byte address family, and `set_filter` fails with
+//     `InvalidInput`.
+// 11. One thread sending in a loop while another blocks in `recv`:
+//     neither waits for the other.
+
+use std::collections::HashMap;
+use std::ffi::{CStr, CString, c_char, c_int, c_void};
+use std::io;
+use std::mem;
+use std::ptr;
+use std::slice;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, Instant};
+
+use super::{RawReceiver, RawSender, RecvMeta};
+use crate::ethernet::MacAddr;
+use crate::filter::{Filter, SockFilter};
+
+/// Prefix of the pcap names of Npcap adapters, before the GUID.
+const NPF_PREFIX: &str = r"\Device\NPF_";
+
+const PCAP_ERRBUF_SIZE: usize = 256;
+const PCAP_ERROR_NO_SUCH_DEVICE: c_int = -5;
+const PCAP_ERROR_PERM_DENIED: c_int = -8;
+
+/// Longest frame captured, the default of Wireshark.
+const SNAPLEN: c_int = 262_144;
+
+/// Longest a receive waits in the driver before checking the socket
+/// settings again, in milliseconds.
+const POLL_MS: c_int = 100;
+
+const LOAD_WITH_ALTERED_SEARCH_PATH: u32 = 0x8;
+const ERROR_BUFFER_OVERFLOW: u32 = 111;
+const AF_UNSPEC: u32 = 0;
+/// GAA_FLAG_SKIP_UNICAST, _ANYCAST, _MULTICAST and _DNS_SERVER.
+const GAA_FLAGS: u32 = 0xf;
+
+#[link(name = "kernel32")]
+unsafe extern "system" {
+    fn LoadLibraryExW(name: *const u16, file: *mut c_void, flags: u32) -> *mut c_void;
+    fn GetProcAddress(module: *mut c_void, name: *const c_char) -> *mut c_void;
+    fn GetSystemDirectoryW(buffer: *mut u16, size: u32) -> u32;
+}
+
+#[link(name = "iphlpapi")]
+unsafe extern "system" {
+    fn GetAdaptersAddresses(
+        family: u32,
+        flags: u32,
+        reserved: *mut c_void,
+        addresses: *mut IpAdapterAddresses,
+        size: *mut u32,
+    ) -> u32;
+}
+
+/// `IP_ADAPTER_ADDRESSES`, up to the members read.
+#[repr(C)]
+struct IpAdapterAddresses {
+    length: u32,
+    if_index: u32,
+    next: *mut IpAdapterAddresses,
+    adapter_name: *const c_char,
+    first_unicast_address: *mut c_void,
+    first_anycast_address: *mut c_void,
+    first_multicast_address: *mut c_void,
+    first_dns_server_address: *mut c_void,
+    dns_suffix: *const u16,
+    description: *const u16,
+    friendly_name: *const u16,
+    physical_address: [u8; 8],
+    physical_address_length: u32,
+}
+
+/// `pcap_if_t`.
+#[repr(C)]
+struct PcapIf {
+    next: *mut PcapIf,
+    name: *const c_char,
+    description: *const c_char,
+    addresses: *mut c_void,
+    flags: u32,
+}
+
+/// `struct pcap_pkthdr`; `long` is 32 bits on Windows.
+#[repr(C)]
+struct PcapPkthdr {
+    tv_sec: i32,
+    tv_usec: i32,
+    caplen: u32,
+    len: u32,
+}
+
+/// `struct bpf_program`.
+#[repr(C)]
+struct BpfProgram {
+    bf_len: u32,
+    bf_insns: *const SockFilter,
+}
+
+type Pcap = c_void;
+type PcapHandler = unsafe extern "C" fn(*mut u8, *const PcapPkthdr, *const u8);
+
+/// Functions of wpcap.dll.
+#[derive(Debug)]
+struct Wpcap {
+    findalldevs: unsafe extern "C" fn(*mut *mut PcapIf, *mut c_char) -> c_int,
+    freealldevs: unsafe extern "C" fn(*mut PcapIf),
+    create: unsafe extern "C" fn(*const c_char, *mut c_char) -> *mut Pcap,
+    set_snaplen: unsafe extern "C" fn(*mut Pcap, c_int) -> c_int,
+    set_promisc: unsafe extern "C" fn(*mut Pcap, c_int) -> c_int,
+    set_timeout: unsafe extern "C" fn(*mut Pcap, c_int) -> c_int,
+    set_immediate_mode: unsafe extern "C" fn(*mut Pcap, c_int) -> c_int,
+    activate: unsafe extern "C" fn(*mut Pcap) -> c_int,
+    close: unsafe extern "C" fn(*mut Pcap),
+    geterr: unsafe extern "C" fn(*mut Pcap) -> *const c_char,
+    datalink: unsafe extern "C" fn(*mut Pcap) -> c_int,
+    setnonblock: unsafe extern "C" fn(*mut Pcap, c_int, *mut c_char) -> c_int,
+    sendpacket: unsafe extern "C" fn(*mut Pcap, *const u8, c_int) -> c_int,
+    dispatch: unsafe extern "C" fn(*mut Pcap, c_int, PcapHandler, *mut u8) -> c_int,
+    setfilter: unsafe extern "C" fn(*mut Pcap, *mut BpfProgram) -> c_int,
+}
+
+/// Loads wpcap.dll on first use; the error says why it could not be.
+fn wpcap() -> io::Result<&'static Wpcap> {
+    static WPCAP: OnceLock<Result<Wpcap, String>> = OnceLock::new();
+    WPCAP
+        .get_or_init(load_wpcap)
+        .as_ref()
+        .map_err(|err| io::Error::new(io::ErrorKind::NotFound, err.clone()))
+}
+
+fn load_wpcap() -> Result<Wpcap, String> {
+    let library = open_wpcap()?;
+    // SAFETY: the Npcap SDK declares each function with the signature of
+    // the field it is stored in.
+    unsafe {
+        Ok(Wpcap {
+            findalldevs: symbol(library, c"pcap_findalldevs")?,
+            freealldevs: symbol(library, c"pcap_freealldevs")?,
+            create: symbol(library, c"pcap_create")?,
+            set_snaplen: symbol(library, c"pcap_set_snaplen")?,
+            set_promisc: symbol(library, c"pcap_set_promisc")?,
+            set_timeout: symbol(library, c"pcap_set_timeout")?,
+            set_immediate_mode: symbol(library, c"pcap_set_immediate_mode")?,
+            activate: symbol(library, c"pcap_activate")?,
+            close: symbol(library, c"pcap_close")?,
+            geterr: symbol(library, c"pcap_geterr")?,
+            datalink: symbol(library, c"pcap_datalink")?,
+            setnonblock: symbol(library, c"pcap_setnonblock")?,
+            sendpacket: symbol(library, c"pcap_sendpacket")?,
+            dispatch: symbol(library, c"pcap_dispatch")?,
+            setfilter: symbol(library, c"pcap_setfilter")?,
+        })
+    }
+}
+
+/// Looks up function `name` of `library`.
+///
+/// # Safety
+///
+/// `F` must be a function pointer type matching the declaration of the
+/// function.
+unsafe fn symbol<F>(library: *mut c_void, name: &CStr) -> Result<F, String> {
+    assert_eq!(mem::size_of::<F>(), mem::size_of::<*mut c_void>());
+    // SAFETY: `library` is a loaded module and `name` a C string.
+    let address = unsafe { GetProcAddress(library, name.as_ptr()) };
+    if address.is_null() {
+        return Err(format!(
+            "wpcap.dll has no {}, Npcap is too old",
+            name.to_string_lossy()
+        ));
+    }
+    // SAFETY: per the function contract.
+    Ok(unsafe { mem::transmute_copy::<*mut c_void, F>(&address) })
+}
+
+/// Loads wpcap.dll from `System32\Npcap`, letting it find Packet.dll
+/// next to it, then from the search path.
+fn open_wpcap() -> Result<*mut c_void, String> {
+    let mut system = [0u16; 260];
+    // SAFETY: `system` is writable for the size given.
+    let len = unsafe { GetSystemDirectoryW(system.as_mut_ptr(), system.len() as u32) } as usize;
+    let mut candidates = Vec::new();
+    if len > 0 && len < system.len() {
+        let directory = String::from_utf16_lossy(&system[..len]);
+        candidates.push((
+            format!(r"{directory}\Npcap\wpcap.dll"),
+            LOAD_WITH_ALTERED_SEARCH_PATH,
+        ));
+    }
+    candidates.push(("wpcap.dll".to_string(), 0));
+    for (path, flags) in candidates {
+        let path: Vec<u16> = path.encode_utf16().chain([0]).collect();
+        // SAFETY: `path` is a NUL-terminated wide string.
+        let library = unsafe { LoadLibraryExW(path.as_ptr(), ptr::null_mut(), flags) };
+        if !library.is_null() {
+            return Ok(library);
+        }
+    }
+    Err("Npcap is not installed (wpcap.dll not found), see https://npcap.com".to_string())
+}
+
+/// Copies a NUL-terminated C string, empty for a null pointer.
+///
+/// # Safety
+///
+/// `ptr` must be null or point to a NUL-terminated string.
+unsafe fn c_string(ptr: *const c_char) -> String {
+    match ptr.is_null() {
+        true => String::new(),
+        // SAFETY: per the function contract.
+        false => unsafe { CStr::from_ptr(ptr) }
+            .to_string_lossy()
+            .into_owned(),
+    }
+}
+
+/// Copies a NUL-terminated wide string, empty for a null pointer.
+///
+/// # Safety
+///
+/// `ptr` must be null or point to a NUL-terminated wide string.
+unsafe fn wide_string(ptr: *const u16) -> String {
+    if ptr.is_null() {
+        return String::new();
+    }
+    // SAFETY: per the function contract, every unit up to the NUL is
+    // readable.
+    unsafe {
+        let len = (0..).take_while(|&i| *ptr.add(i) != 0).count();
+        String::from_utf16_lossy(slice::from_raw_parts(ptr, len))
+    }
+}
+
+fn errbuf_error(errbuf: &[c_char; PCAP_ERRBUF_SIZE]) -> io::Error {
+    // SAFETY: libpcap writes a NUL-terminated message into the buffer,
+    // which starts zeroed.
+    io::Error::other(unsafe { c_string(errbuf.as_ptr()) })
+}
+
+/// Network adapter Npcap can open.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interface {
+    /// pcap name, `\Device\NPF_{GUID}`.
+    pub device: String,
+    /// GUID of the adapter, braces included; `None` for the Npcap
+    /// loopback adapter.
+    pub guid: Option<String>,
+    /// Name of the Network Connections panel, such as `Ethernet 2`.
+    pub friendly_name: Option<String>,
+    /// Driver description, such as `Intel(R) Ethernet Connection`.
+    pub description: String,
+    pub mac_address: Option<MacAddr>,
+}
+
+impl Interface {
+    /// Returns true if `name` designates the interface: its friendly name
+    /// (ignoring ASCII case), its GUID with or without braces, or its pcap
+    /// name.
+    pub fn matches(&self, name: &str) -> bool {
+        let name = name.trim();
+        let guid = name.trim_start_matches('{').trim_end_matches('}');
+        self.device == name
+            || self.guid.as_deref().is_some_and(|own| {
+                own.trim_start_matches('{')
+                    .trim_end_matches('}')
+                    .eq_ignore_ascii_case(guid)
+            })
+            || self
+                .friendly_name
+                .as_deref()
+                .is_some_and(|own| own.eq_ignore_ascii_case(name))
+    }
+}
+
+/// GUID of a pcap adapter name, `\Device\NPF_{GUID}`.
+fn device_guid(device: &str) -> Option<&str> {
+    device
+        .strip_prefix(NPF_PREFIX)
+        .filter(|guid| guid.starts_with('{') && guid.ends_with('}'))
+}
+
+/// Friendly names and MAC addresses of the adapters, by upper-case GUID.
+fn adapters() -> io::Result<HashMap<String, (String, Option<MacAddr>)>> {
+    let mut size = 16 * 1024u32;
+    let mut buffer: Vec<u64>;
+    loop {
+        buffer = vec![0; (size as usize).div_ceil(8)];
+        // SAFETY: `buffer` is writable for `size` bytes and aligned for
+        // the structures written into it.
+        let ret = unsafe {
+            GetAdaptersAddresses(
+                AF_UNSPEC,
+                GAA_FLAGS,
+                ptr::null_mut(),
+                buffer.as_mut_ptr().cast(),
+                &mut size,
+            )
+        };
+        match ret {
+            0 => break,
+            ERROR_BUFFER_OVERFLOW => continue,
+            err => return Err(io::Error::from_raw_os_error(err as i32)),
+        }
+    }
+
+    let mut adapters = HashMap::new();
+    let mut adapter = buffer.as_ptr().cast::<IpAdapterAddresses>();
+    while !adapter.is_null() {
+        // SAFETY: the list lives in `buffer`, filled by the call above.
+        let (guid, friendly_name, mac, next) = unsafe {
+            let entry = &*adapter;
+            let mac = match entry.physical_address_length {
+                6 => {
+                    let mut octets = [0; 6];
+                    octets.copy_from_slice(&entry.physical_address[..6]);
+                    Some(MacAddr(octets))
+                }
+                _ => None,
+            };
+            (
+                c_string(entry.adapter_name),
+                wide_string(entry.friendly_name),
+                mac,
+                entry.next,
+            )
+        };
+        adapters.insert(guid.to_ascii_uppercase(), (friendly_name, mac));
+        adapter = next;
+    }
+    Ok(adapters)
+}
+
+/// Lists the adapters Npcap can open, with their friendly names.
+pub fn interfaces() -> io::Result<Vec<Interface>> {
+    let wpcap = wpcap()?;
+    let adapters = adapters()?;
+    let mut errbuf = [0; PCAP_ERRBUF_SIZE];
+    let mut list = ptr::null_mut();
+    // SAFETY: `list` receives a list freed below; `errbuf` has the size
+    // libpcap expects.
+    if unsafe { (wpcap.findalldevs)(&mut list, errbuf.as_mut_ptr()) } < 0 {
+        return Err(errbuf_error(&errbuf));
+    }
+    let mut interfaces = Vec::new();
+    let mut device = list;
+    while !device.is_null() {
+        // SAFETY: the list stays valid until freed.
+        let (name, description, next) = unsafe {
+            let entry = &*device;
+            (
+                c_string(entry.name),
+                c_string(entry.description),
+                entry.next,
+            )
+        };
+        let guid = device_guid(&name).map(str::to_string);
+        let adapter = guid
+            .as_ref()
+            .and_then(|guid| adapters.get(&guid.to_ascii_uppercase()));
+        interfaces.push(Interface {
+            device: name,
+            guid,
+            friendly_name: adapter.map(|(name, _)| name.clone()),
+            description,
+            mac_address: adapter.and_then(|(_, mac)| *mac),
+        });
+        device = next;
+    }
+    // SAFETY: `list` came from pcap_findalldevs and is not used after.
+    unsafe { (wpcap.freealldevs)(list) };
+    Ok(interfaces)
+}
+
+/// Activated pcap handle, closed on drop.
+#[derive(Debug)]
+struct Handle {
+    pcap: *mut Pcap,
+    wpcap: &'static Wpcap,
+}
+
+// SAFETY: a pcap handle can be used from any thread, one at a time, which
+// the mutex each handle is kept behind ensures.
+unsafe impl Send for Handle {}
+
+impl Handle {
+    /// Opens `device` for capture in promiscuous and immediate mode.
+    fn open(wpcap: &'static Wpcap, device: &str) -> io::Result<Handle> {
+        let name = CString::new(device)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid interface name"))?;
+        let mut errbuf = [0; PCAP_ERRBUF_SIZE];
+        // SAFETY: `name` is a C string and `errbuf` has the size libpcap
+        // expects.
+        let pcap = unsafe { (wpcap.create)(name.as_ptr(), errbuf.as_mut_ptr()) };
+        if pcap.is_null() {
+            return Err(errbuf_error(&errbuf));
+        }
+        let handle = Handle { pcap, wpcap };
+        // SAFETY: `pcap` is a created, not yet activated handle. The
+        // setters only fail on an activated one.
+        let ret = unsafe {
+            (wpcap.set_snaplen)(pcap, SNAPLEN);
+            (wpcap.set_promisc)(pcap, 1);
+            (wpcap.set_timeout)(pcap, POLL_MS);
+            (wpcap.set_immediate_mode)(pcap, 1);
+            (wpcap.activate)(pcap)
+        };
+        // Positive values are warnings.
+        if ret < 0 {
+            let kind = match ret {
+                PCAP_ERROR_NO_SUCH_DEVICE => io::ErrorKind::NotFound,
+                PCAP_ERROR_PERM_DENIED => io::ErrorKind::PermissionDenied,
+                _ => io::ErrorKind::Other,
+            };
+            return Err(io::Error::new(kind, handle.error().to_string()));
+        }
+        Ok(handle)
+    }
+
+    /// Last error of the handle.
+    fn error(&self) -> io::Error {
+        // SAFETY: pcap_geterr returns a C string owned by the handle.
+        io::Error::other(unsafe { c_string((self.wpcap.geterr)(self.pcap)) })
+    }
+}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        // SAFETY: the handle is not used after.
+        unsafe { (self.wpcap.close)(self.pcap) };
+    }
+}
+
+/// Npcap adapter, sending and receiving whole frames, in promiscuous
+/// mode. Blocking unless `set_nonblocking` is called.
+#[derive(Debug)]
+pub struct RawSocket {
+    sender: Mutex<Handle>,
+    receiver: Mutex<Handle>,
+    interface: Interface,
+    link_type: u32,
+    read_timeout: Mutex<Option<Duration>>,
+    nonblocking: AtomicBool,
+    timestamps: AtomicBool,
+}
+
+impl RawSocket {
+    /// Constructor to create a socket on `iface`: a friendly name such as
+    /// `Ethernet`, a GUID or an Npcap device name (see `interfaces`).
+    /// Fails with `NotFound` if Npcap is not installed or there is no such
+    /// interface. Npcap may be set to require administrator rights.
+    pub fn new(iface: &str) -> io::Result<RawSocket> {
+        let wpcap = wpcap()?;
+        let interface = interfaces()?
+            .into_iter()
+            .find(|interface| interface.matches(iface))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no Npcap interface named {iface}"),
+                )
+            })?;
+        let sender = Handle::open(wpcap, &interface.device)?;
+        let receiver = Handle::open(wpcap, &interface.device)?;
+        // SAFETY: `receiver` is activated.
+        let link_type = unsafe { (wpcap.datalink)(receiver.pcap) } as u32;
+        Ok(RawSocket {
+            sender: Mutex::new(sender),
+            receiver: Mutex::new(receiver),
+            interface,
+            link_type,
+            read_timeout: Mutex::new(None),
+            nonblocking: AtomicBool::new(false),
+            timestamps: AtomicBool::new(false),
+        })
+    }
+
+    /// Interface the socket is open on.
+    pub fn interface(&self) -> &Interface {
+        &self.interface
+    }
+
+    /// Link type of the frames: Ethernet, or 0 (DLT_NULL) on the Npcap
+    /// loopback adapter.
+    pub fn link_type(&self) -> u32 {
+        self.link_type
+    }
+
+    /// Makes `recv` fail with `WouldBlock` instead of waiting.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        let receiver = self.receiver();
+        let mut errbuf = [0; PCAP_ERRBUF_SIZE];
+        // SAFETY: the handle is activated and `errbuf` has the size
+        // libpcap expects.
+        let ret = unsafe {
+            (receiver.wpcap.setnonblock)(receiver.pcap, nonblocking.into(), errbuf.as_mut_ptr())
+        };
+        if ret < 0 {
+            return Err(errbuf_error(&errbuf));
+        }
+        self.nonblocking.store(nonblocking, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Makes `recv` fail with `WouldBlock` after waiting `timeout` for a
+    /// frame, give or take 100 ms; `None` waits forever. Fails with
+    /// `InvalidInput` for a zero timeout, as
+    /// `std::net::UdpSocket::set_read_timeout` does.
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        if timeout == Some(Duration::ZERO) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "zero timeout"));
+        }
+        *self.read_timeout.lock().unwrap() = timeout;
+        Ok(())
+    }
+
+    /// Makes `recv_into` return the time each frame was received, as the
+    /// driver stamped it, to the microsecond.
+    pub fn set_timestamps(&self, enable: bool) -> io::Result<()> {
+        self.timestamps.store(enable, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Hardware address of the interface, all zeros for the loopback
+    /// adapter.
+    pub fn mac_address(&self) -> io::Result<MacAddr> {
+        Ok(self.interface.mac_address.unwrap_or(MacAddr([0; 6])))
+    }
+
+    /// Attaches a classic BPF program, replacing the previous one. Frames
+    /// it returns 0 for are dropped by the driver. Fails with
+    /// `InvalidInput` if the driver rejects the program.
+    pub fn attach_filter(&self, program: &[SockFilter]) -> io::Result<()> {
+        let mut program = BpfProgram {
+            bf_len: program.len() as u32,
+            bf_insns: program.as_ptr(),
+        };
+        let receiver = self.receiver();
+        // SAFETY: `program` points to `bf_len` instructions laid out as
+        // `struct bpf_insn`, which libpcap copies.
+        let ret = unsafe { (receiver.wpcap.setfilter)(receiver.pcap, &mut program) };
+        if ret < 0 {
+            let err = receiver.error();
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, err.to_string()));
+        }
+        Ok(())
+    }
+
+    fn receiver(&self) -> MutexGuard<'_, Handle> {
+        self.receiver.lock().unwrap()
+    }
+}
+
+impl RawSender for RawSocket {
+    fn send(&self, frame: &[u8]) -> io::Result<()> {
+        let len = c_int::try_from(frame.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "frame too long"))?;
+        let sender = self.sender.lock().unwrap();
+        // SAFETY: `frame` is readable for its length.
+        let ret = unsafe { (sender.wpcap.sendpacket)(sender.pcap, frame.as_ptr(), len) };
+        if ret != 0 {
+            return Err(sender.error());
+        }
+        Ok(())
+    }
+}
+
+/// Buffers `collect` copies frames into.
+struct Batch<'a, 'b> {
+    bufs: &'a mut [&'b mut [u8]],
+    meta: &'a mut [RecvMeta],
+    filled: usize,
+    timestamps: bool,
+}
+
+/// pcap_dispatch callback: copies a frame into the next buffer of the
+/// `Batch` behind `user`.
+unsafe extern "C" fn collect(user: *mut u8, header: *const PcapPkthdr, data: *const u8) {
+    // SAFETY: `user` is the batch `recv_into` passed to pcap_dispatch, and
+    // `header` describes the `caplen` bytes at `data`, valid for the call.
+    let (batch, header, frame) = unsafe {
+        let header = &*header;
+        (
+            &mut *user.cast::<Batch>(),
+            header,
+            slice::from_raw_parts(data, header.caplen as usize),
+        )
+    };
+    let Some(buf) = batch.bufs.get_mut(batch.filled) else {
+        return;
+    };
+    let len = frame.len().min(buf.len());
+    buf[..len].copy_from_slice(&frame[..len]);
+    batch.meta[batch.filled] = RecvMeta {
+        len,
+        truncated: header.len as usize > len,
+        timestamp: batch
+            .timestamps
+            .then(|| Duration::new(header.tv_sec as u32 as u64, header.tv_usec as u32 * 1000)),
+    };
+    batch.filled += 1;
+}
+
+impl RawReceiver for RawSocket {
+    fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut meta = [RecvMeta::default()];
+        self.recv_into(&mut [buf], &mut meta)?;
+        Ok(meta[0].len)
+    }
+
+    /// Receives the frames of one driver read, at most as many as there
+    /// are buffers. The receiving handle is released every 100 ms while
+    /// waiting, for the setters.
+    fn recv_into(&self, bufs: &mut [&mut [u8]], meta: &mut [RecvMeta]) -> io::Result<usize> {
+        let count = bufs.len().min(meta.len());
+        if count == 0 {
+            return Ok(0);
+        }
+        let deadline = self
+            .read_timeout
+            .lock()
+            .unwrap()
+            .map(|timeout| Instant::now() + timeout);
+        let mut batch = Batch {
+            bufs: &mut bufs[..count],
+            meta: &mut meta[..count],
+            filled: 0,
+            timestamps: self.timestamps.load(Ordering::Relaxed),
+        };
+        loop {
+            let receiver = self.receiver();
+            // SAFETY: `collect` is given `batch`, which outlives the call.
+            let ret = unsafe {
+                (receiver.wpcap.dispatch)(
+                    receiver.pcap,
+                    count as c_int,
+                    collect,
+                    (&raw mut batch).cast(),
+                )
+            };
+            if ret < 0 {
+                return Err(receiver.error());
+            }
+            if batch.filled > 0 {
+                return Ok(batch.filled);
+            }
+            if self.nonblocking.load(Ordering::Relaxed)
+                || deadline.is_some_and(|deadline| Instant::now() >= deadline)
+            {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+        }
+    }
+
+    /// Compiles `filter` for the link type of the adapter and attaches
+    /// it; only Ethernet adapters can be filtered.
+    fn set_filter(&self, filter: &Filter) -> io::Result<()> {
+        let program = filter
+            .compile_bpf(self.link_type)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        self.attach_filter(&program)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interfaces_match_by_name_and_guid() {
+        let guid = "{4D36E972-E325-11CE-BFC1-08002BE10318}";
+        let interface = Interface {
+            device: format!("{NPF_PREFIX}{guid}"),
+            guid: device_guid(&format!("{NPF_PREFIX}{guid}")).map(str::to_string),
+            friendly_name: Some("Ethernet 2".to_string()),
+            description: "Intel(R) Ethernet Connection".to_string(),
+            mac_address: None,
+        };
+        assert_eq!(interface.guid.as_deref(), Some(guid));
+        assert!(interface.matches(r"\Device\NPF_{4D36E972-E325-11CE-BFC1-08002BE10318}"));
+        assert!(interface.matches(guid));
+        assert!(interface.matches("4d36e972-e325-11ce-bfc1-08002be10318"));
+        assert!(interface.matches("ethernet 2"));
+        assert!(!interface.matches("Ethernet"));
+        assert!(!interface.matches("Intel(R) Ethernet Connection"));
+        assert_eq!(device_guid(r"\Device\NPF_Loopback"), None);
+    }
+}
@@ -0,0 +1,281 @@
+use std::io::{self, Read, Write};
+
+use crate::pcap::{self, CapturedPacket, Reader, Writer};
+use crate::tcp::OffsetAndFlags;
+use crate::util::checksum;
+
+// EtherTypes walked to reach the IP header.
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const ETHERTYPE_QINQ: u16 = 0x88a8;
+
+const PROTOCOL_TCP: u8 = 6;
+const PROTOCOL_UDP: u8 = 17;
+
+/// Where the headers of a frame end.
+pub(crate) struct Layout {
+    pub(crate) ip_offset: usize,
+    pub(crate) ipv6: bool,
+    /// IP protocol (or IPv6 upper-layer next header) of the packet.
+    pub(crate) protocol: u8,
+    /// The packet is a fragment, the first one included.
+    pub(crate) fragmented: bool,
+    /// Offset and protocol of a TCP or UDP header, if the frame has one.
+    pub(crate) transport: Option<(usize, u8)>,
+    pub(crate) payload_offset: usize,
+    /// End of the IP packet, excluding any link layer padding.
+    pub(crate) end: usize,
+}
+
+/// Keeps the headers of an Ethernet frame, through the TCP or UDP header
+/// and its options, plus at most `keep_payload` payload bytes. The IP and
+/// UDP lengths are fixed to describe the shortened packet, the IPv4 header
+/// checksum is recomputed and the transport checksum, which can no longer
+/// be valid, is zeroed. Packets short enough to be kept whole are left
+/// unchanged, apart from any link layer padding.
+///
+/// Frames that do not carry IP keep their Ethernet header and at most
+/// `keep_payload` bytes after it.
+pub fn to_headers(frame: &[u8], keep_payload: usize) -> Vec<u8> {
+    to_headers_with_options(pcap::LINKTYPE_ETHERNET, frame, keep_payload, true)
+}
+
+/// Same as `to_headers` for a frame of `link_type`. Without `fix_lengths`,
+/// the headers are left untouched, so the result matches what a capture
+/// with a snaplen ending after the kept payload would contain.
+pub fn to_headers_with_options(
+    link_type: u32,
+    frame: &[u8],
+    keep_payload: usize,
+    fix_lengths: bool,
+) -> Vec<u8> {
+    let Some(layout) = layout(link_type, frame) else {
+        let header_len: usize = match link_type {
+            pcap::LINKTYPE_ETHERNET => 14,
+            pcap::LINKTYPE_NULL => 4,
+            _ => 0,
+        };
+        let end = header_len.saturating_add(keep_payload).min(frame.len());
+        return frame[..end].to_vec();
+    };
+    let available = layout.end - layout.payload_offset;
+    let kept = available.min(keep_payload);
+    let mut bytes = frame[..layout.payload_offset + kept].to_vec();
+    if !fix_lengths || kept == available {
+        return bytes;
+    }
+    let ip = layout.ip_offset;
+    let ip_len = bytes.len() - ip;
+    if layout.ipv6 {
+        bytes[ip + 4..ip + 6].copy_from_slice(&((ip_len - 40) as u16).to_be_bytes());
+    } else {
+        let header_len = (bytes[ip] & 0x0f) as usize * 4;
+        bytes[ip + 2..ip + 4].copy_from_slice(&(ip_len as u16).to_be_bytes());
+        bytes[ip + 10..ip + 12].fill(0);
+        let sum = checksum(&bytes[ip..ip + header_len]);
+        bytes[ip + 10..ip + 12].copy_from_slice(&sum.to_be_bytes());
+    }
+    match layout.transport {
+        Some((offset, PROTOCOL_TCP)) => bytes[offset + 16..offset + 18].fill(0),
+        Some((offset, _)) => {
+            let udp_len = (bytes.len() - offset) as u16;
+            bytes[offset + 4..offset + 6].copy_from_slice(&udp_len.to_be_bytes());
+            bytes[offset + 6..offset + 8].fill(0);
+        }
+        None => {}
+    }
+    bytes
+}
+
+/// Locates the IP, transport and payload of a frame of `link_type`.
+pub(crate) fn layout(link_type: u32, frame: &[u8]) -> Option<Layout> {
+    let ip_offset = match link_type {
+        pcap::LINKTYPE_ETHERNET => {
+            let mut offset = 12;
+            loop {
+                let ethertype = u16::from_be_bytes([*frame.get(offset)?, *frame.get(offset + 1)?]);
+                match ethertype {
+                    ETHERTYPE_VLAN | ETHERTYPE_QINQ => offset += 4,
+                    ETHERTYPE_IPV4 | ETHERTYPE_IPV6 => break offset + 2,
+                    _ => return None,
+                }
+            }
+        }
+        pcap::LINKTYPE_RAW | pcap::LINKTYPE_IPV4 | pcap::LINKTYPE_IPV6 => 0,
+        pcap::LINKTYPE_NULL => 4,
+        _ => return None,
+    };
+    let packet = frame.get(ip_offset..)?;
+    let (header_end, protocol, (fragment, fragmented), total_len, ipv6) = match packet.first()? >> 4
+    {
+        4 => {
+            let header_len = (packet[0] & 0x0f) as usize * 4;
+            if header_len < 20 || packet.len() < header_len {
+                return None;
+            }
+            let fragment_offset = u16::from_be_bytes([packet[6], packet[7]]) & 0x1fff;
+            let more_fragments = packet[6] & 0x20 != 0;
+            let total_len = u16::from_be_bytes([packet[2], packet[3]]) as usize;
+            (
+                header_len,
+                packet[9],
+                (fragment_offset != 0, fragment_offset != 0 || more_fragments),
+                total_len,
+                false,
+            )
+        }
+        6 => {
+            if packet.len() < 40 {
+                return None;
+            }
+            let total_len = 40 + u16::from_be_bytes([packet[4], packet[5]]) as usize;
+            let (offset, next_header, fragment) = ipv6_upper_layer(packet)?;
+            (offset, next_header, fragment, total_len, true)
+        }
+        _ => return None,
+    };
+    let end = ip_offset + total_len.clamp(header_end, packet.len());
+    let transport_offset = ip_offset + header_end;
+    let transport_len = match protocol {
+        _ if fragment => None,
+        PROTOCOL_TCP => frame
+            .get(transport_offset..)
+            .and_then(OffsetAndFlags::from_header)
+            .map(|field| field.get_data_offset() as usize * 4)
+            .filter(|len| *len >= 20),
+        PROTOCOL_UDP => Some(8),
+        _ => None,
+    };
+    let (transport, payload_offset) = match transport_len {
+        Some(len) if transport_offset + len <= end => {
+            (Some((transport_offset, protocol)), transport_offset + len)
+        }
+        _ => (None, transport_offset),
+    };
+    Some(Layout {
+        ip_offset,
+        ipv6,
+        protocol,
+        fragmented,
+        transport,
+        payload_offset,
+        end,
+    })
+}
+
+/// Walks the IPv6 extension headers, returning the offset and protocol of
+/// the upper layer, whether the packet is a non-initial fragment and
+/// whether it is a fragment at all.
+pub(crate) fn ipv6_upper_layer(packet: &[u8]) -> Option<(usize, u8, (bool, bool))> {
+    let mut next_header = packet[6];
+    let mut offset = 40;
+    let mut fragment = (false, false);
+    loop {
+        match next_header {
+            0 | 43 | 60 => {
+                let length = (*packet.get(offset + 1)? as usize + 1) * 8;
+                next_header = *packet.get(offset)?;
+                offset += length;
+            }
+            44 => {
+                let fragment_offset =
+                    u16::from_be_bytes([*packet.get(offset + 2)?, *packet.get(offset + 3)?]) >> 3;
+                fragment = (fragment_offset != 0, true);
+                next_header = *packet.get(offset)?;
+                offset += 8;
+            }
+            51 => {
+                let length = (*packet.get(offset + 1)? as usize + 2) * 4;
+                next_header = *packet.get(offset)?;
+                offset += length;
+            }
+            _ => break,
+        }
+    }
+    if offset > packet.len() {
+        return None;
+    }
+    Some((offset, next_header, fragment))
+}
+
+/// How much payload `truncate_capture` keeps per packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeepRules {
+    /// Payload bytes kept by default.
+    pub default_payload: usize,
+    /// (protocol, port, payload bytes) rules, matched against either port
+    /// of a TCP or UDP packet; the first match wins.
+    pub ports: Vec<(u8, u16, usize)>,
+    /// Rewrite lengths and zero checksums, as `to_headers` does.
+    pub fix_lengths: bool,
+}
+
+impl KeepRules {
+    /// Constructor to keep `default_payload` bytes of every packet and fix
+    /// the lengths.
+    pub fn new(default_payload: usize) -> Self {
+        KeepRules {
+            default_payload,
+            ports: Vec::new(),
+            fix_lengths: true,
+        }
+    }
+
+    /// Keeps `payload` bytes of packets to or from `port`; `usize::MAX`
+    /// keeps them whole.
+    pub fn set_port(mut self, protocol: u8, port: u16, payload: usize) -> Self {
+        self.ports.push((protocol, port, payload));
+        self
+    }
+
+    pub fn set_fix_lengths(mut self, fix_lengths: bool) -> Self {
+        self.fix_lengths = fix_lengths;
+        self
+    }
+
+    fn keep_payload(&self, link_type: u32, frame: &[u8]) -> usize {
+        let transport = layout(link_type, frame).and_then(|layout| layout.transport);
+        let Some((offset, protocol)) = transport else {
+            return self.default_payload;
+        };
+        let source = u16::from_be_bytes([frame[offset], frame[offset + 1]]);
+        let destination = u16::from_be_bytes([frame[offset + 2], frame[offset + 3]]);
+        self.ports
+            .iter()
+            .find(|(rule_protocol, port, _)| {
+                *rule_protocol == protocol && (*port == source || *port == destination)
+            })
+            .map_or(self.default_payload, |(_, _, payload)| *payload)
+    }
+}
+
+/// Copies every packet of `reader` to `writer`, truncated according to
+/// `rules`. With fixed lengths, the original length of each record is its
+/// new length; otherwise it is left as read, like in a snaplen-limited
+/// capture. Returns the number of packets written.
+pub fn truncate_capture<R: Read, W: Write>(
+    reader: &mut Reader<R>,
+    writer: &mut Writer<W>,
+    rules: &KeepRules,
+) -> io::Result<u64> {
+    let link_type = reader.link_type();
+    let mut count = 0;
+    while let Some(packet) = reader.next_packet()? {
+        let keep_payload = rules.keep_payload(link_type, &packet.data);
+        let data =
+            to_headers_with_options(link_type, &packet.data, keep_payload, rules.fix_lengths);
+        let original_len = if rules.fix_lengths {
+            data.len() as u32
+        } else {
+            packet.original_len
+        };
+        writer.write_packet(&CapturedPacket {
+            timestamp: packet.timestamp,
+            original_len,
+            data,
+        })?;
+        count += 1;
+    }
+    Ok(count)
+}
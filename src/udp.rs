@@ -0,0 +1,270 @@
+use std::fmt;
+use std::net::Ipv4Addr;
+
+use crate::ipv4::IPv4;
+use crate::util::{ParseError, PseudoHeader, ensure_len};
+
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |          Source Port          |       Destination Port        |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |            Length             |           Checksum            |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+/// IP protocol number of UDP.
+pub const IP_PROTOCOL: u8 = 17;
+
+/// Length of the UDP header in bytes.
+pub const HEADER_LEN: usize = 8;
+
+/// Destination ports of the tunnel protocols whose UDP checksum may be
+/// zero over IPv6 (RFC 6935, RFC 6936): VXLAN, GENEVE, LISP data, GRE-in-UDP
+/// and MPLS-in-UDP.
+pub const ZERO_CHECKSUM_TUNNEL_PORTS: &[u16] = &[4789, 6081, 4341, 4754, 6635];
+
+/// How the checksum of an encapsulating UDP datagram is filled in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum UdpChecksum {
+    #[default]
+    Compute,
+    /// Checksum left zero: disabled over IPv4, only allowed for tunnel
+    /// protocols over IPv6.
+    Zero,
+    /// Checksum computed on this datagram and zero on the UDP layers of
+    /// the tunnels nested inside it.
+    ComputeOuterOnly,
+}
+
+impl UdpChecksum {
+    /// Policy to apply to the UDP layer of a tunnel nested inside a
+    /// datagram built with this policy.
+    pub fn inner(&self) -> UdpChecksum {
+        match self {
+            UdpChecksum::ComputeOuterOnly => UdpChecksum::Zero,
+            policy => *policy,
+        }
+    }
+}
+
+/// State of the checksum of a received datagram.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChecksumStatus {
+    Valid,
+    Invalid,
+    /// Zero checksum over IPv4, which means no checksum was computed.
+    Disabled,
+    /// Zero checksum over IPv6 on a tunnel port allowed by RFC 6936.
+    ZeroTunnel,
+    /// Zero checksum over IPv6 for a payload that must be checksummed.
+    ZeroNotPermitted,
+}
+
+/// Error returned when a zero checksum over IPv6 is requested for a
+/// datagram that is not a tunnel allowed by RFC 6936.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ZeroChecksumNotPermitted {
+    pub destination_port: u16,
+}
+
+impl fmt::Display for ZeroChecksumNotPermitted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "zero UDP checksum over IPv6 not permitted for port {}",
+            self.destination_port
+        )
+    }
+}
+
+impl std::error::Error for ZeroChecksumNotPermitted {}
+
+/// Header UDP, followed by its data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UDP {
+    pub source: u16,
+    pub destination: u16,
+    pub length: u16,
+    pub checksum: u16,
+    pub data: Vec<u8>,
+}
+
+impl UDP {
+    /// Constructor to create a datagram with the length filled in and no
+    /// checksum.
+    pub fn new(source: u16, destination: u16, data: Vec<u8>) -> Self {
+        UDP {
+            source,
+            destination,
+            length: (HEADER_LEN + data.len()) as u16,
+            checksum: 0,
+            data,
+        }
+    }
+
+    // --- GETTER METHODS ---
+
+    /// Returns the source port.
+    pub fn get_source(&self) -> u16 {
+        self.source
+    }
+
+    /// Returns the destination port.
+    pub fn get_destination(&self) -> u16 {
+        self.destination
+    }
+
+    /// Returns the length of header and data.
+    pub fn get_length(&self) -> u16 {
+        self.length
+    }
+
+    /// Returns the checksum.
+    pub fn get_checksum(&self) -> u16 {
+        self.checksum
+    }
+
+    /// Returns the data.
+    pub fn get_data(&self) -> &Vec<u8> {
+        &self.data
+    }
+
+    // --- SETTER METHODS ---
+
+    /// Sets the source port.
+    pub fn set_source(mut self, source: u16) -> Self {
+        self.source = source;
+        self
+    }
+
+    /// Sets the destination port.
+    pub fn set_destination(mut self, destination: u16) -> Self {
+        self.destination = destination;
+        self
+    }
+
+    /// Sets the length of header and data.
+    pub fn set_length(mut self, length: u16) -> Self {
+        self.length = length;
+        self
+    }
+
+    /// Sets the checksum.
+    pub fn set_checksum(mut self, checksum: u16) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    /// Sets the data and updates the length.
+    pub fn set_data(mut self, data: Vec<u8>) -> Self {
+        self.length = (HEADER_LEN + data.len()) as u16;
+        self.data = data;
+        self
+    }
+
+    // --- SERIALIZATION ---
+
+    /// Serializes the header followed by the data.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.data.len());
+        bytes.extend_from_slice(&self.source.to_be_bytes());
+        bytes.extend_from_slice(&self.destination.to_be_bytes());
+        bytes.extend_from_slice(&self.length.to_be_bytes());
+        bytes.extend_from_slice(&self.checksum.to_be_bytes());
+        bytes.extend_from_slice(&self.data);
+        bytes
+    }
+
+    /// Checksum over the pseudo-header and the datagram. A computed value
+    /// of zero is sent as 0xffff (RFC 768, RFC 8200 section 8.1).
+    pub fn compute_checksum(&self, pseudo_header: &PseudoHeader) -> u16 {
+        let mut bytes = self.to_bytes();
+        bytes[6..8].fill(0);
+        match pseudo_header.checksum(IP_PROTOCOL, &bytes) {
+            0 => 0xffff,
+            sum => sum,
+        }
+    }
+
+    /// Returns the datagram with its checksum computed.
+    pub fn with_checksum(mut self, pseudo_header: &PseudoHeader) -> Self {
+        self.checksum = self.compute_checksum(pseudo_header);
+        self
+    }
+
+    /// Wraps the datagram, with its checksum computed, in an IPv4 packet
+    /// from `source` to `destination`.
+    pub fn to_ipv4(&self, source: Ipv4Addr, destination: Ipv4Addr) -> IPv4 {
+        let pseudo_header = PseudoHeader::V4 {
+            source,
+            destination,
+        };
+        let datagram = self.clone().with_checksum(&pseudo_header);
+        IPv4::with_payload(source, destination, IP_PROTOCOL, datagram.to_bytes())
+    }
+
+    /// Returns true if the destination port is a tunnel protocol that may
+    /// use a zero checksum over IPv6.
+    pub fn is_zero_checksum_tunnel(&self) -> bool {
+        ZERO_CHECKSUM_TUNNEL_PORTS.contains(&self.destination)
+    }
+
+    /// Returns the datagram with its checksum filled in according to
+    /// `policy`. A zero checksum over IPv6 is refused unless the datagram
+    /// is a tunnel allowed by RFC 6936 or `allow_noncompliant` is set, for
+    /// deliberately invalid test packets.
+    pub fn with_checksum_policy(
+        mut self,
+        policy: UdpChecksum,
+        pseudo_header: &PseudoHeader,
+        allow_noncompliant: bool,
+    ) -> Result<Self, ZeroChecksumNotPermitted> {
+        match policy {
+            UdpChecksum::Compute | UdpChecksum::ComputeOuterOnly => {
+                Ok(self.with_checksum(pseudo_header))
+            }
+            UdpChecksum::Zero => {
+                let ipv6 = matches!(pseudo_header, PseudoHeader::V6 { .. });
+                if ipv6 && !allow_noncompliant && !self.is_zero_checksum_tunnel() {
+                    return Err(ZeroChecksumNotPermitted {
+                        destination_port: self.destination,
+                    });
+                }
+                self.checksum = 0;
+                Ok(self)
+            }
+        }
+    }
+
+    /// Classifies the checksum of a received datagram. Zero checksums are
+    /// reported apart from invalid ones, depending on the IP version and
+    /// on whether the datagram is a tunnel allowed by RFC 6936.
+    pub fn checksum_status(&self, pseudo_header: &PseudoHeader) -> ChecksumStatus {
+        match (self.checksum, pseudo_header) {
+            (0, PseudoHeader::V4 { .. }) => ChecksumStatus::Disabled,
+            (0, PseudoHeader::V6 { .. }) if self.is_zero_checksum_tunnel() => {
+                ChecksumStatus::ZeroTunnel
+            }
+            (0, PseudoHeader::V6 { .. }) => ChecksumStatus::ZeroNotPermitted,
+            (checksum, _) if checksum == self.compute_checksum(pseudo_header) => {
+                ChecksumStatus::Valid
+            }
+            _ => ChecksumStatus::Invalid,
+        }
+    }
+
+    /// Parses a UDP datagram. The data is bounded by the length field.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, HEADER_LEN)?;
+        let length = u16::from_be_bytes([buf[4], buf[5]]);
+        if (length as usize) < HEADER_LEN {
+            return Err(ParseError::InvalidField("length"));
+        }
+        ensure_len(buf, length as usize)?;
+        Ok(UDP {
+            source: u16::from_be_bytes([buf[0], buf[1]]),
+            destination: u16::from_be_bytes([buf[2], buf[3]]),
+            length,
+            checksum: u16::from_be_bytes([buf[6], buf[7]]),
+            data: buf[HEADER_LEN..length as usize].to_vec(),
+        })
+    }
+}
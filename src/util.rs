@@ -1,13 +1,665 @@
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
-
+use crate::limits::LimitExceeded;
 
 // Checksum calculation
-pub fn checksum() {
-    
+
+/// Adds `data` to a running one's complement sum, treating it as a sequence
+/// of big-endian 16-bit words (an odd trailing byte is padded with zero).
+pub fn checksum_add(mut sum: u32, data: &[u8]) -> u32 {
+    let mut chunks = data.chunks_exact(2);
+    for word in &mut chunks {
+        sum += u16::from_be_bytes([word[0], word[1]]) as u32;
+    }
+    if let [last] = chunks.remainder() {
+        sum += u16::from_be_bytes([*last, 0]) as u32;
+    }
+    sum
+}
+
+/// Folds a running sum into 16 bits and returns its one's complement.
+pub fn checksum_finish(mut sum: u32) -> u16 {
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Internet checksum (RFC 1071) of `data`.
+pub fn checksum(data: &[u8]) -> u16 {
+    checksum_finish(checksum_add(0, data))
+}
+
+/// Updates the checksum at `offset` of `data`, if captured, for 16-bit
+/// aligned bytes changed from `old` to `new` (RFC 1624). A zero UDP
+/// checksum means none and is left alone; a computed zero is sent as
+/// 0xffff.
+pub(crate) fn update_checksum(data: &mut [u8], offset: usize, old: &[u8], new: &[u8], udp: bool) {
+    let Some(field) = data.get_mut(offset..offset + 2) else {
+        return;
+    };
+    let checksum = u16::from_be_bytes([field[0], field[1]]);
+    if udp && checksum == 0 {
+        return;
+    }
+    let word = |chunk: &[u8]| u16::from_be_bytes([chunk[0], chunk.get(1).copied().unwrap_or(0)]);
+    let mut sum = !checksum as u64;
+    for (old, new) in old.chunks(2).zip(new.chunks(2)) {
+        sum += !word(old) as u64 + word(new) as u64;
+    }
+    while sum >> 32 != 0 {
+        sum = (sum & 0xffff_ffff) + (sum >> 32);
+    }
+    let mut updated = checksum_finish(sum as u32);
+    if udp && updated == 0 {
+        updated = 0xffff;
+    }
+    field.copy_from_slice(&updated.to_be_bytes());
+}
+
+/// Fletcher checksum (ISO 8473 annex C, RFC 905) of `data`, whose two
+/// checksum bytes at `offset` must be zero. The result, stored there,
+/// makes both running sums of `data` zero.
+pub fn fletcher_checksum(data: &[u8], offset: usize) -> u16 {
+    let (mut c0, mut c1) = (0i32, 0i32);
+    for byte in data {
+        c0 = (c0 + *byte as i32) % 255;
+        c1 = (c1 + c0) % 255;
+    }
+    let mut x = ((data.len() - offset - 1) as i32 * c0 - c1).rem_euclid(255);
+    if x == 0 {
+        x = 255;
+    }
+    let mut y = 510 - c0 - x;
+    if y > 255 {
+        y -= 255;
+    }
+    ((x as u16) << 8) | y as u16
+}
+
+/// Checksum of an upper-layer message carried over IPv4, including the
+/// IPv4 pseudo-header (RFC 793 / RFC 768).
+pub fn ipv4_pseudo_header_checksum(
+    source: Ipv4Addr,
+    destination: Ipv4Addr,
+    protocol: u8,
+    data: &[u8],
+) -> u16 {
+    let mut sum = checksum_add(0, &source.octets());
+    sum = checksum_add(sum, &destination.octets());
+    sum += protocol as u32;
+    sum += data.len() as u32;
+    checksum_finish(checksum_add(sum, data))
+}
+
+/// Checksum of an upper-layer message carried over IPv6, including the
+/// IPv6 pseudo-header (RFC 8200 section 8.1).
+pub fn ipv6_pseudo_header_checksum(
+    source: Ipv6Addr,
+    destination: Ipv6Addr,
+    next_header: u8,
+    data: &[u8],
+) -> u16 {
+    let mut sum = checksum_add(0, &source.octets());
+    sum = checksum_add(sum, &destination.octets());
+    sum = checksum_add(sum, &(data.len() as u32).to_be_bytes());
+    sum += next_header as u32;
+    checksum_finish(checksum_add(sum, data))
+}
+
+/// Source and destination addresses of a packet, of either family.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IpAddrPair {
+    pub src: IpAddr,
+    pub dst: IpAddr,
+}
+
+impl IpAddrPair {
+    /// Constructor to create a pair. Fails if the addresses are not of the
+    /// same family.
+    pub fn new(src: IpAddr, dst: IpAddr) -> Result<Self, MixedAddressFamilies> {
+        let pair = IpAddrPair { src, dst };
+        pair.pseudo_header()?;
+        Ok(pair)
+    }
+
+    /// Returns the pair with source and destination swapped.
+    pub fn reversed(&self) -> Self {
+        IpAddrPair {
+            src: self.dst,
+            dst: self.src,
+        }
+    }
+
+    /// Returns the pseudo-header addresses of the pair.
+    pub fn pseudo_header(&self) -> Result<PseudoHeader, MixedAddressFamilies> {
+        PseudoHeader::try_from(*self)
+    }
+}
+
+impl From<(Ipv4Addr, Ipv4Addr)> for IpAddrPair {
+    fn from((src, dst): (Ipv4Addr, Ipv4Addr)) -> Self {
+        IpAddrPair {
+            src: src.into(),
+            dst: dst.into(),
+        }
+    }
+}
+
+impl From<(Ipv6Addr, Ipv6Addr)> for IpAddrPair {
+    fn from((src, dst): (Ipv6Addr, Ipv6Addr)) -> Self {
+        IpAddrPair {
+            src: src.into(),
+            dst: dst.into(),
+        }
+    }
+}
+
+impl From<PseudoHeader> for IpAddrPair {
+    fn from(pseudo_header: PseudoHeader) -> Self {
+        match pseudo_header {
+            PseudoHeader::V4 {
+                source,
+                destination,
+            } => (source, destination).into(),
+            PseudoHeader::V6 {
+                source,
+                destination,
+            } => (source, destination).into(),
+        }
+    }
+}
+
+/// Error returned when an IPv4 address is paired with an IPv6 address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MixedAddressFamilies {
+    pub src: IpAddr,
+    pub dst: IpAddr,
+}
+
+impl fmt::Display for MixedAddressFamilies {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "mixed address families: {} and {}", self.src, self.dst)
+    }
+}
+
+impl std::error::Error for MixedAddressFamilies {}
+
+/// Addresses of the pseudo-header covered by upper-layer checksums, which
+/// are always of one family.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PseudoHeader {
+    V4 {
+        source: Ipv4Addr,
+        destination: Ipv4Addr,
+    },
+    V6 {
+        source: Ipv6Addr,
+        destination: Ipv6Addr,
+    },
+}
+
+impl PseudoHeader {
+    /// Checksum of `data` carried as `protocol` (the IPv6 next header),
+    /// including the pseudo-header of the matching family.
+    pub fn checksum(&self, protocol: u8, data: &[u8]) -> u16 {
+        match *self {
+            PseudoHeader::V4 {
+                source,
+                destination,
+            } => ipv4_pseudo_header_checksum(source, destination, protocol, data),
+            PseudoHeader::V6 {
+                source,
+                destination,
+            } => ipv6_pseudo_header_checksum(source, destination, protocol, data),
+        }
+    }
+}
+
+impl TryFrom<IpAddrPair> for PseudoHeader {
+    type Error = MixedAddressFamilies;
+
+    fn try_from(pair: IpAddrPair) -> Result<Self, Self::Error> {
+        match (pair.src, pair.dst) {
+            (IpAddr::V4(source), IpAddr::V4(destination)) => Ok(PseudoHeader::V4 {
+                source,
+                destination,
+            }),
+            (IpAddr::V6(source), IpAddr::V6(destination)) => Ok(PseudoHeader::V6 {
+                source,
+                destination,
+            }),
+            (src, dst) => Err(MixedAddressFamilies { src, dst }),
+        }
+    }
+}
+
+/// Builds the lookup table of a reflected CRC-32 with the given polynomial.
+const fn crc32_table(polynomial: u32) -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut index = 0;
+    while index < 256 {
+        let mut crc = index as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ polynomial
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[index] = crc;
+        index += 1;
+    }
+    table
+}
+
+const CRC32_TABLE: [u32; 256] = crc32_table(0xedb8_8320);
+const CRC32C_TABLE: [u32; 256] = crc32_table(0x82f6_3b78);
+
+/// CRC-32 (IEEE 802.3) of `data`, as used by the Ethernet FCS.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc = CRC32_TABLE[((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+/// CRC-32C (Castagnoli) of `data`, as used by SCTP (RFC 9260 appendix A).
+pub fn crc32c(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc = CRC32C_TABLE[((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    !crc
 }
 
 // Serialization and deserialization
 
+/// Error returned when a header cannot be decoded from raw bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The buffer ended before the structure was complete.
+    Truncated { needed: usize, available: usize },
+    /// A field held a value the parser does not accept.
+    InvalidField(&'static str),
+    /// The input crossed one of the `Limits` given to the parser.
+    LimitExceeded(LimitExceeded),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Truncated { needed, available } => {
+                write!(f, "truncated input: needed {needed} bytes, got {available}")
+            }
+            ParseError::InvalidField(field) => write!(f, "invalid value for field `{field}`"),
+            ParseError::LimitExceeded(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<LimitExceeded> for ParseError {
+    fn from(err: LimitExceeded) -> Self {
+        ParseError::LimitExceeded(err)
+    }
+}
+
+/// Checks that `buf` holds at least `needed` bytes.
+pub fn ensure_len(buf: &[u8], needed: usize) -> Result<(), ParseError> {
+    if buf.len() < needed {
+        return Err(ParseError::Truncated {
+            needed,
+            available: buf.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Reads a big-endian IPv6 address starting at `offset`.
+/// The caller must have checked the length beforehand.
+pub fn read_ipv6(buf: &[u8], offset: usize) -> Ipv6Addr {
+    let mut octets = [0u8; 16];
+    octets.copy_from_slice(&buf[offset..offset + 16]);
+    Ipv6Addr::from(octets)
+}
+
+/// Reads a big-endian IPv4 address starting at `offset`.
+/// The caller must have checked the length beforehand.
+pub fn read_ipv4(buf: &[u8], offset: usize) -> Ipv4Addr {
+    Ipv4Addr::new(
+        buf[offset],
+        buf[offset + 1],
+        buf[offset + 2],
+        buf[offset + 3],
+    )
+}
+
+// Traffic class (DSCP and ECN)
+
+/// ECN codepoint of the IP header (RFC 3168 section 5).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Ecn {
+    NotEct = 0b00,
+    Ect1 = 0b01,
+    Ect0 = 0b10,
+    Ce = 0b11,
+}
+
+impl Ecn {
+    /// Decodes the two low bits of `bits`.
+    pub fn from_bits(bits: u8) -> Self {
+        match bits & 0b11 {
+            0b00 => Ecn::NotEct,
+            0b01 => Ecn::Ect1,
+            0b10 => Ecn::Ect0,
+            _ => Ecn::Ce,
+        }
+    }
+
+    /// Returns true for ECN-capable transports (ECT(0), ECT(1) or CE).
+    pub fn is_ect(&self) -> bool {
+        *self != Ecn::NotEct
+    }
+}
+
+/// DSCP of a traffic class or TOS byte.
+pub fn dscp_of(traffic_class: u8) -> u8 {
+    traffic_class >> 2
+}
+
+/// ECN codepoint of a traffic class or TOS byte.
+pub fn ecn_of(traffic_class: u8) -> Ecn {
+    Ecn::from_bits(traffic_class)
+}
+
+/// Builds a traffic class or TOS byte from a 6-bit DSCP and an ECN codepoint.
+pub fn traffic_class(dscp: u8, ecn: Ecn) -> u8 {
+    (dscp & 0x3f) << 2 | ecn as u8
+}
 
 // IP validation
 //
+
+/// An IPv4 prefix such as `10.0.0.0/8`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Ipv4Cidr {
+    pub address: Ipv4Addr,
+    pub prefix_len: u8,
+}
+
+impl Ipv4Cidr {
+    /// Constructor to create a prefix. Fails if `prefix_len` exceeds 32.
+    pub fn new(address: Ipv4Addr, prefix_len: u8) -> Result<Self, ParseError> {
+        if prefix_len > 32 {
+            return Err(ParseError::InvalidField("prefix length"));
+        }
+        Ok(Ipv4Cidr {
+            address,
+            prefix_len,
+        })
+    }
+
+    /// Returns the network mask. Prefix lengths beyond 32 are taken as 32.
+    pub fn mask(&self) -> Ipv4Addr {
+        Ipv4Addr::from(
+            u32::MAX
+                .checked_shl(32 - self.prefix_len.min(32) as u32)
+                .unwrap_or(0),
+        )
+    }
+
+    /// Returns the network address (host bits cleared).
+    pub fn network(&self) -> Ipv4Addr {
+        Ipv4Addr::from(u32::from(self.address) & u32::from(self.mask()))
+    }
+
+    /// Returns true if `address` is inside the prefix.
+    pub fn contains(&self, address: Ipv4Addr) -> bool {
+        u32::from(address) & u32::from(self.mask()) == u32::from(self.network())
+    }
+
+    /// Host addresses of the prefix, in increasing order: every address
+    /// but the network and broadcast ones, except for /31 prefixes whose
+    /// two addresses are both hosts (RFC 3021) and /32 prefixes whose
+    /// single address is.
+    pub fn hosts(&self) -> impl Iterator<Item = Ipv4Addr> + use<> {
+        let first = u32::from(self.network());
+        let last = first | !u32::from(self.mask());
+        let (first, last) = if self.prefix_len >= 31 {
+            (first, last)
+        } else {
+            (first + 1, last - 1)
+        };
+        (first..=last).map(Ipv4Addr::from)
+    }
+}
+
+impl fmt::Display for Ipv4Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.address, self.prefix_len)
+    }
+}
+
+impl std::str::FromStr for Ipv4Cidr {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (address, prefix_len) = s.split_once('/').unwrap_or((s, "32"));
+        let address = address
+            .parse()
+            .map_err(|_| ParseError::InvalidField("address"))?;
+        let prefix_len = prefix_len
+            .parse()
+            .map_err(|_| ParseError::InvalidField("prefix length"))?;
+        Ipv4Cidr::new(address, prefix_len)
+    }
+}
+
+/// An IPv6 prefix such as `2001:db8::/32`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Ipv6Cidr {
+    pub address: Ipv6Addr,
+    pub prefix_len: u8,
+}
+
+impl Ipv6Cidr {
+    /// Constructor to create a prefix. Fails if `prefix_len` exceeds 128.
+    pub fn new(address: Ipv6Addr, prefix_len: u8) -> Result<Self, ParseError> {
+        if prefix_len > 128 {
+            return Err(ParseError::InvalidField("prefix length"));
+        }
+        Ok(Ipv6Cidr {
+            address,
+            prefix_len,
+        })
+    }
+
+    /// Returns the network mask. Prefix lengths beyond 128 are taken as 128.
+    pub fn mask(&self) -> Ipv6Addr {
+        Ipv6Addr::from(
+            u128::MAX
+                .checked_shl(128 - self.prefix_len.min(128) as u32)
+                .unwrap_or(0),
+        )
+    }
+
+    /// Returns the network address (host bits cleared).
+    pub fn network(&self) -> Ipv6Addr {
+        Ipv6Addr::from(u128::from(self.address) & u128::from(self.mask()))
+    }
+
+    /// Returns true if `address` is inside the prefix.
+    pub fn contains(&self, address: Ipv6Addr) -> bool {
+        u128::from(address) & u128::from(self.mask()) == u128::from(self.network())
+    }
+}
+
+impl fmt::Display for Ipv6Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.address, self.prefix_len)
+    }
+}
+
+impl std::str::FromStr for Ipv6Cidr {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (address, prefix_len) = s.split_once('/').unwrap_or((s, "128"));
+        let address = address
+            .parse()
+            .map_err(|_| ParseError::InvalidField("address"))?;
+        let prefix_len = prefix_len
+            .parse()
+            .map_err(|_| ParseError::InvalidField("prefix length"))?;
+        Ipv6Cidr::new(address, prefix_len)
+    }
+}
+
+/// A prefix of either family.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum IpCidr {
+    V4(Ipv4Cidr),
+    V6(Ipv6Cidr),
+}
+
+impl IpCidr {
+    /// Returns the prefix containing only `address`.
+    pub fn host(address: IpAddr) -> Self {
+        match address {
+            IpAddr::V4(address) => IpCidr::V4(Ipv4Cidr {
+                address,
+                prefix_len: 32,
+            }),
+            IpAddr::V6(address) => IpCidr::V6(Ipv6Cidr {
+                address,
+                prefix_len: 128,
+            }),
+        }
+    }
+
+    /// Returns the address the prefix was written with.
+    pub fn address(&self) -> IpAddr {
+        match self {
+            IpCidr::V4(cidr) => cidr.address.into(),
+            IpCidr::V6(cidr) => cidr.address.into(),
+        }
+    }
+
+    pub fn prefix_len(&self) -> u8 {
+        match self {
+            IpCidr::V4(cidr) => cidr.prefix_len,
+            IpCidr::V6(cidr) => cidr.prefix_len,
+        }
+    }
+
+    /// Returns true if `address` is inside the prefix; an address of the
+    /// other family never is.
+    pub fn contains(&self, address: IpAddr) -> bool {
+        match (self, address) {
+            (IpCidr::V4(cidr), IpAddr::V4(address)) => cidr.contains(address),
+            (IpCidr::V6(cidr), IpAddr::V6(address)) => cidr.contains(address),
+            (IpCidr::V4(_), IpAddr::V6(_)) | (IpCidr::V6(_), IpAddr::V4(_)) => false,
+        }
+    }
+}
+
+impl fmt::Display for IpCidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IpCidr::V4(cidr) => cidr.fmt(f),
+            IpCidr::V6(cidr) => cidr.fmt(f),
+        }
+    }
+}
+
+impl std::str::FromStr for IpCidr {
+    type Err = ParseError;
+
+    /// Parses `address/length`, or a bare address as a host prefix.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.contains(':') {
+            s.parse().map(IpCidr::V6)
+        } else {
+            s.parse().map(IpCidr::V4)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cidr(text: &str) -> Ipv4Cidr {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn hosts_skip_network_and_broadcast() {
+        let hosts: Vec<Ipv4Addr> = cidr("192.168.1.0/30").hosts().collect();
+        assert_eq!(
+            hosts,
+            [Ipv4Addr::new(192, 168, 1, 1), Ipv4Addr::new(192, 168, 1, 2)]
+        );
+        assert_eq!(cidr("192.168.1.77/24").hosts().count(), 254);
+    }
+
+    #[test]
+    fn point_to_point_and_host_prefixes() {
+        let hosts: Vec<Ipv4Addr> = cidr("10.0.0.1/31").hosts().collect();
+        assert_eq!(
+            hosts,
+            [Ipv4Addr::new(10, 0, 0, 0), Ipv4Addr::new(10, 0, 0, 1)]
+        );
+        let hosts: Vec<Ipv4Addr> = cidr("10.0.0.7/32").hosts().collect();
+        assert_eq!(hosts, [Ipv4Addr::new(10, 0, 0, 7)]);
+    }
+
+    #[test]
+    fn mask_and_containment() {
+        assert_eq!(cidr("10.1.2.3/8").mask(), Ipv4Addr::new(255, 0, 0, 0));
+        assert_eq!(cidr("10.1.2.3/8").network(), Ipv4Addr::new(10, 0, 0, 0));
+        assert_eq!(cidr("0.0.0.0/0").mask(), Ipv4Addr::UNSPECIFIED);
+        assert!(cidr("10.0.0.0/8").contains(Ipv4Addr::new(10, 255, 0, 1)));
+        assert!(!cidr("10.0.0.0/8").contains(Ipv4Addr::new(11, 0, 0, 1)));
+        assert!(Ipv4Cidr::new(Ipv4Addr::LOCALHOST, 33).is_err());
+    }
+
+    #[test]
+    fn dual_stack_prefixes() {
+        let v6: IpCidr = "2001:db8::/32".parse().unwrap();
+        assert!(v6.contains("2001:db8:1::1".parse().unwrap()));
+        assert!(!v6.contains("2001:db9::1".parse().unwrap()));
+        assert!(!v6.contains(Ipv4Addr::new(32, 1, 13, 184).into()));
+        let v4: IpCidr = "10.0.0.0/8".parse().unwrap();
+        assert!(!v4.contains("::ffff:10.0.0.1".parse().unwrap()));
+        assert_eq!(v4.to_string(), "10.0.0.0/8");
+        assert!("::/129".parse::<IpCidr>().is_err());
+
+        let pair = IpAddrPair::new(Ipv4Addr::LOCALHOST.into(), Ipv6Addr::LOCALHOST.into());
+        assert_eq!(
+            pair,
+            Err(MixedAddressFamilies {
+                src: Ipv4Addr::LOCALHOST.into(),
+                dst: Ipv6Addr::LOCALHOST.into()
+            })
+        );
+        let pair = IpAddrPair::from((Ipv6Addr::LOCALHOST, Ipv6Addr::UNSPECIFIED));
+        assert_eq!(IpAddrPair::from(pair.pseudo_header().unwrap()), pair);
+    }
+
+    #[test]
+    fn mask_of_out_of_range_prefix_length() {
+        let prefix = Ipv4Cidr {
+            address: Ipv4Addr::new(10, 0, 0, 1),
+            prefix_len: 200,
+        };
+        assert_eq!(prefix.mask(), Ipv4Addr::BROADCAST);
+        assert_eq!(prefix.hosts().count(), 1);
+    }
+}
@@ -0,0 +1,565 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::ipv4::IPv4;
+use crate::ipv6::IPv6;
+use crate::tcp::{TCP, TcpOption, flags};
+use crate::udp::UDP;
+use crate::vlan::{MAX_VID, VlanTag};
+
+// Validation of hand-built headers before they are sent or written.
+//
+// Headers here are plain structs whose fields can hold anything, on
+// purpose: a malformed packet is sometimes the point. `Validate` reports
+// what would not survive a strict receiver, every problem at once, each
+// with the path of the field (`packets[3].tcp.options[1].mss`), its value,
+// the constraint it breaks and, when there is an obvious one, a fix.
+// Validation never changes the value, so validating twice reports the
+// same errors.
+
+/// One step of a `FieldPath`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PathSegment {
+    Field(String),
+    Index(usize),
+}
+
+/// Location of a field in a value, e.g. `packets[3].tcp.options[1].mss`.
+/// Serialized as that string.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "String", try_from = "String")
+)]
+pub struct FieldPath(Vec<PathSegment>);
+
+impl FieldPath {
+    /// Constructor to create the empty path, the value itself.
+    pub fn new() -> Self {
+        FieldPath(Vec::new())
+    }
+
+    /// Returns the path of field `name` of this one. Dots in `name`
+    /// separate nested fields, so `ip.ttl` adds two segments.
+    pub fn field(&self, name: &str) -> Self {
+        let mut path = self.clone();
+        path.0.extend(
+            name.split('.')
+                .map(|name| PathSegment::Field(name.to_string())),
+        );
+        path
+    }
+
+    /// Returns the path of element `index` of this one.
+    pub fn index(&self, index: usize) -> Self {
+        let mut path = self.clone();
+        path.0.push(PathSegment::Index(index));
+        path
+    }
+
+    pub fn segments(&self) -> &[PathSegment] {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Display for FieldPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, segment) in self.0.iter().enumerate() {
+            match segment {
+                PathSegment::Field(name) if i == 0 => f.write_str(name)?,
+                PathSegment::Field(name) => write!(f, ".{name}")?,
+                PathSegment::Index(index) => write!(f, "[{index}]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Error returned when parsing a malformed `FieldPath`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldPathError(pub String);
+
+impl fmt::Display for FieldPathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid field path `{}`", self.0)
+    }
+}
+
+impl std::error::Error for FieldPathError {}
+
+impl FromStr for FieldPath {
+    type Err = FieldPathError;
+
+    /// Parses the form `Display` writes; the empty string is the empty
+    /// path.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || FieldPathError(s.to_string());
+        let mut segments = Vec::new();
+        if s.is_empty() {
+            return Ok(FieldPath(segments));
+        }
+        for (i, part) in s.split('.').enumerate() {
+            let (name, mut indices) = part.split_once('[').unwrap_or((part, ""));
+            // Only the first segment may start with an index.
+            if name.is_empty() && (i > 0 || indices.is_empty()) {
+                return Err(invalid());
+            }
+            if !name.is_empty() {
+                segments.push(PathSegment::Field(name.to_string()));
+            }
+            while !indices.is_empty() {
+                let (index, rest) = indices.split_once(']').ok_or_else(invalid)?;
+                segments.push(PathSegment::Index(index.parse().map_err(|_| invalid())?));
+                indices = match rest {
+                    "" => "",
+                    rest => rest.strip_prefix('[').ok_or_else(invalid)?,
+                };
+            }
+        }
+        Ok(FieldPath(segments))
+    }
+}
+
+impl From<FieldPath> for String {
+    fn from(path: FieldPath) -> Self {
+        path.to_string()
+    }
+}
+
+impl TryFrom<String> for FieldPath {
+    type Error = FieldPathError;
+
+    fn try_from(path: String) -> Result<Self, Self::Error> {
+        path.parse()
+    }
+}
+
+/// A field that breaks a constraint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ValidationError {
+    pub path: FieldPath,
+    /// The offending value, as written in the source or by `Display`.
+    pub value: String,
+    /// What the value must be, phrased to follow "must be", e.g.
+    /// `at most 14`.
+    pub constraint: String,
+    /// A value that would pass, when there is an obvious one.
+    pub suggestion: Option<String>,
+}
+
+impl ValidationError {
+    /// Constructor to create an error without suggestion.
+    pub fn new(path: FieldPath, value: impl ToString, constraint: impl Into<String>) -> Self {
+        ValidationError {
+            path,
+            value: value.to_string(),
+            constraint: constraint.into(),
+            suggestion: None,
+        }
+    }
+
+    // --- SETTER METHODS ---
+
+    pub fn set_suggestion(mut self, suggestion: impl ToString) -> Self {
+        self.suggestion = Some(suggestion.to_string());
+        self
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.path.is_empty() {
+            write!(f, "{}: ", self.path)?;
+        }
+        write!(f, "`{}` must be {}", self.value, self.constraint)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, " (did you mean `{suggestion}`?)")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// Every error found in one validation pass, in the order the fields were
+/// visited.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ValidationErrors {
+    pub errors: Vec<ValidationError>,
+}
+
+impl ValidationErrors {
+    pub fn push(&mut self, error: ValidationError) {
+        self.errors.push(error);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.errors.len()
+    }
+
+    /// Returns `value` if no error was found, the errors otherwise.
+    pub fn into_result<T>(self, value: T) -> Result<T, Self> {
+        match self.is_empty() {
+            true => Ok(value),
+            false => Err(self),
+        }
+    }
+}
+
+/// Writes one error per line.
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, error) in self.errors.iter().enumerate() {
+            if i > 0 {
+                f.write_str("\n")?;
+            }
+            error.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ValidationErrors {}
+
+/// A value whose fields can be checked against the constraints of the
+/// protocol.
+pub trait Validate {
+    /// Adds to `errors` every constraint the value breaks, with paths
+    /// under `path`. Headers add their own name first, e.g. `tcp`.
+    fn validate_at(&self, path: &FieldPath, errors: &mut ValidationErrors);
+
+    /// Validates the value on its own.
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        self.validate_at(&FieldPath::new(), &mut errors);
+        errors.into_result(())
+    }
+}
+
+/// Validates each element under `path[index]`.
+impl<T: Validate> Validate for [T] {
+    fn validate_at(&self, path: &FieldPath, errors: &mut ValidationErrors) {
+        for (i, value) in self.iter().enumerate() {
+            value.validate_at(&path.index(i), errors);
+        }
+    }
+}
+
+impl<T: Validate> Validate for Vec<T> {
+    fn validate_at(&self, path: &FieldPath, errors: &mut ValidationErrors) {
+        self.as_slice().validate_at(path, errors);
+    }
+}
+
+/// Checks that `value` fits in `bits` bits.
+fn check_bits(path: FieldPath, value: u32, bits: u32, errors: &mut ValidationErrors) {
+    let max = (1u64 << bits) - 1;
+    if value as u64 > max {
+        errors.push(ValidationError::new(path, value, format!("at most {max}")));
+    }
+}
+
+/// Checks a length field against the length of what it covers.
+fn check_length(path: FieldPath, value: u16, expected: usize, errors: &mut ValidationErrors) {
+    if value as usize != expected {
+        let error = ValidationError::new(path, value, format!("{expected}, the actual length"));
+        errors.push(match u16::try_from(expected) {
+            Ok(expected) => error.set_suggestion(expected),
+            Err(_) => error,
+        });
+    }
+}
+
+impl Validate for IPv4 {
+    fn validate_at(&self, path: &FieldPath, errors: &mut ValidationErrors) {
+        let path = path.field("ipv4");
+        if self.version != 4 {
+            errors.push(
+                ValidationError::new(path.field("version"), self.version, "4").set_suggestion(4),
+            );
+        }
+        let options_len = self.options.len();
+        if options_len > 40 {
+            errors.push(ValidationError::new(
+                path.field("options"),
+                format!("{options_len} bytes"),
+                "at most 40 bytes",
+            ));
+        }
+        let ihl = 5 + options_len.div_ceil(4);
+        if self.ihl as usize != ihl {
+            let error = ValidationError::new(
+                path.field("ihl"),
+                self.ihl,
+                format!("{ihl}, 5 words plus the options"),
+            );
+            errors.push(match ihl <= 15 {
+                true => error.set_suggestion(ihl),
+                false => error,
+            });
+        }
+        let header_len = (self.ihl as usize * 4).max(20);
+        check_length(
+            path.field("total_length"),
+            self.total_length,
+            header_len + self.payload.len(),
+            errors,
+        );
+        check_bits(path.field("flags"), self.flags as u32, 3, errors);
+        check_bits(
+            path.field("fragment_offset"),
+            self.fragment_offset as u32,
+            13,
+            errors,
+        );
+    }
+}
+
+impl Validate for IPv6 {
+    fn validate_at(&self, path: &FieldPath, errors: &mut ValidationErrors) {
+        let path = path.field("ipv6");
+        if self.version != 6 {
+            errors.push(
+                ValidationError::new(path.field("version"), self.version, "6").set_suggestion(6),
+            );
+        }
+        check_bits(path.field("flow_label"), self.flow_label, 20, errors);
+        check_length(
+            path.field("payload_length"),
+            self.payload_length,
+            self.payload.len(),
+            errors,
+        );
+    }
+}
+
+impl Validate for UDP {
+    fn validate_at(&self, path: &FieldPath, errors: &mut ValidationErrors) {
+        let path = path.field("udp");
+        check_length(
+            path.field("length"),
+            self.length,
+            8 + self.data.len(),
+            errors,
+        );
+    }
+}
+
+impl Validate for VlanTag {
+    fn validate_at(&self, path: &FieldPath, errors: &mut ValidationErrors) {
+        let path = path.field("vlan");
+        check_bits(path.field("pcp"), self.pcp as u32, 3, errors);
+        if self.vid > MAX_VID {
+            let error = ValidationError::new(
+                path.field("vid"),
+                self.vid,
+                format!("at most {MAX_VID}, 4095 being reserved"),
+            );
+            errors.push(error);
+        }
+    }
+}
+
+/// Name of the option in field paths.
+fn option_name(option: &TcpOption) -> &'static str {
+    match option {
+        TcpOption::EndOfList => "eol",
+        TcpOption::Nop => "nop",
+        TcpOption::Mss(_) => "mss",
+        TcpOption::WindowScale(_) => "wscale",
+        TcpOption::SackPermitted => "sack_permitted",
+        TcpOption::Sack(_) => "sack",
+        TcpOption::Timestamps { .. } => "ts",
+        TcpOption::Unknown { .. } => "unknown",
+    }
+}
+
+/// Checks the option on its own; whether it may appear in the segment is
+/// up to `TCP`.
+impl Validate for TcpOption {
+    fn validate_at(&self, path: &FieldPath, errors: &mut ValidationErrors) {
+        let path = path.field(option_name(self));
+        match self {
+            // RFC 7323, section 2.3.
+            TcpOption::WindowScale(shift) if *shift > 14 => {
+                errors.push(ValidationError::new(path, shift, "at most 14").set_suggestion(14));
+            }
+            // Four blocks fill the 40 bytes of options.
+            TcpOption::Sack(blocks) if blocks.is_empty() || blocks.len() > 4 => {
+                errors.push(ValidationError::new(
+                    path,
+                    format!("{} blocks", blocks.len()),
+                    "1 to 4 blocks",
+                ));
+            }
+            TcpOption::Unknown { data, .. } if data.len() > 38 => {
+                errors.push(ValidationError::new(
+                    path.field("data"),
+                    format!("{} bytes", data.len()),
+                    "at most 38 bytes",
+                ));
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Validate for TCP {
+    fn validate_at(&self, path: &FieldPath, errors: &mut ValidationErrors) {
+        let path = path.field("tcp");
+        let options_len = self.options.len() + self.padding.len();
+        if !options_len.is_multiple_of(4) {
+            let missing = 4 - options_len % 4;
+            errors.push(
+                ValidationError::new(
+                    path.field("padding"),
+                    format!("{} bytes", self.padding.len()),
+                    "padding the options to a multiple of 4 bytes",
+                )
+                .set_suggestion(format!("{} bytes", self.padding.len() + missing)),
+            );
+        }
+        if options_len > 40 {
+            errors.push(ValidationError::new(
+                path.field("options"),
+                format!("{options_len} bytes"),
+                "at most 40 bytes with padding",
+            ));
+        }
+        let data_offset = 5 + options_len.div_ceil(4);
+        if self.data_offset as usize != data_offset {
+            let error = ValidationError::new(
+                path.field("data_offset"),
+                self.data_offset,
+                format!("{data_offset}, 5 words plus the options"),
+            );
+            errors.push(match data_offset <= 15 {
+                true => error.set_suggestion(data_offset),
+                false => error,
+            });
+        }
+        check_bits(path.field("reserved"), self.reserved as u32, 3, errors);
+        check_bits(path.field("flags"), self.flags as u32, 9, errors);
+
+        let options_path = path.field("options");
+        let mut offset = 0;
+        let mut index = 0;
+        while offset < self.options.len() {
+            let option_path = options_path.index(index);
+            let (option, len) = match TcpOption::from_bytes(&self.options[offset..]) {
+                Ok(option) => option,
+                Err(err) => {
+                    let value = format!("{:02x?}", &self.options[offset..]);
+                    let constraint = format!("a complete option ({err})");
+                    errors.push(ValidationError::new(option_path, value, constraint));
+                    break;
+                }
+            };
+            if option == TcpOption::EndOfList {
+                break;
+            }
+            option.validate_at(&option_path, errors);
+            // RFC 9293, section 3.7.1, and RFC 7323 and 2018.
+            let syn_only = matches!(
+                option,
+                TcpOption::Mss(_) | TcpOption::WindowScale(_) | TcpOption::SackPermitted
+            );
+            if syn_only && self.flags & flags::SYN == 0 {
+                errors.push(ValidationError::new(
+                    option_path.field(option_name(&option)),
+                    "present",
+                    "sent only on SYN segments",
+                ));
+            }
+            offset += len;
+            index += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    #[test]
+    fn paths_round_trip() {
+        let path = FieldPath::new()
+            .field("packets")
+            .index(3)
+            .field("tcp.options")
+            .index(1)
+            .field("mss");
+        assert_eq!(path.to_string(), "packets[3].tcp.options[1].mss");
+        assert_eq!(path.to_string().parse::<FieldPath>().unwrap(), path);
+        for path in ["", "rules[0][2]", "[1].a", "a.b"] {
+            assert_eq!(path.parse::<FieldPath>().unwrap().to_string(), path);
+        }
+        for path in ["a..b", "a[x]", "a[1", "a[1]b", ".a"] {
+            assert!(path.parse::<FieldPath>().is_err(), "{path}");
+        }
+    }
+
+    #[test]
+    fn every_error_is_reported_with_its_path() {
+        let options = TcpOption::serialize_all(&[
+            TcpOption::Nop,
+            TcpOption::Mss(1460),
+            TcpOption::WindowScale(15),
+        ]);
+        let mut tcp = TCP::segment(40000, 80, 1, 1, flags::ACK);
+        tcp.options = options;
+        let packets = vec![TCP::segment(40000, 80, 0, 0, flags::SYN), tcp];
+
+        let path = FieldPath::new().field("packets");
+        let mut errors = ValidationErrors::default();
+        packets.validate_at(&path, &mut errors);
+        let errors: Vec<String> = errors.errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            errors,
+            [
+                "packets[1].tcp.data_offset: `5` must be 7, 5 words plus the options \
+                 (did you mean `7`?)",
+                "packets[1].tcp.options[1].mss: `present` must be sent only on SYN segments",
+                "packets[1].tcp.options[2].wscale: `15` must be at most 14 (did you mean `14`?)",
+                "packets[1].tcp.options[2].wscale: `present` must be sent only on SYN segments",
+            ]
+        );
+
+        // Validation does not change anything: the same errors come back.
+        assert_eq!(packets.validate(), packets.validate());
+        assert_eq!(packets.validate().unwrap_err().len(), 4);
+    }
+
+    #[test]
+    fn lengths_and_bit_widths() {
+        let source = Ipv4Addr::new(10, 0, 0, 1);
+        let mut ip = IPv4::with_payload(source, source, 17, vec![0; 8]);
+        assert!(ip.validate().is_ok());
+        ip.total_length = 30;
+        ip.fragment_offset = 0x2000;
+        let errors = ip.validate().unwrap_err();
+        assert_eq!(errors.errors[0].path.to_string(), "ipv4.total_length");
+        assert_eq!(errors.errors[0].suggestion.as_deref(), Some("28"));
+        assert_eq!(errors.errors[1].constraint, "at most 8191");
+
+        let udp = UDP::new(1, 2, vec![0; 4]);
+        assert!(udp.validate().is_ok());
+        let tag = VlanTag::new(8, false, 4095);
+        let errors = tag.validate().unwrap_err();
+        let paths: Vec<String> = errors.errors.iter().map(|e| e.path.to_string()).collect();
+        assert_eq!(paths, ["vlan.pcp", "vlan.vid"]);
+    }
+}
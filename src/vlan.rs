@@ -0,0 +1,238 @@
+use std::fmt;
+use std::io;
+
+use crate::ethernet::{ETHERTYPE_QINQ, ETHERTYPE_VLAN};
+use crate::transport::{RawSender, send_paced};
+use crate::util::{ParseError, ensure_len};
+
+// 802.1Q tag, inserted after the source address:
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |         TPID (0x8100)         | PCP |D|         VID           |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+/// Lowest usable VLAN ID; 0 marks priority-tagged frames.
+pub const MIN_VID: u16 = 1;
+/// Highest usable VLAN ID; 4095 is reserved.
+pub const MAX_VID: u16 = 4094;
+
+/// Offset of the tag in an Ethernet frame, after both addresses.
+const TAG_OFFSET: usize = 12;
+
+crate::bitfields! {
+    /// Tag control information of an 802.1Q tag.
+    pub struct Tci([u8; 2]) {
+        get_pcp, set_pcp: u8 = 0, 3;
+        get_dei, set_dei: u8 = 3, 1;
+        get_vid, set_vid: u16 = 4, 12;
+    }
+}
+
+/// Length of a VLAN tag in bytes.
+pub const TAG_LEN: usize = 4;
+
+/// An 802.1Q tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VlanTag {
+    pub tpid: u16,
+    pub pcp: u8,
+    pub dei: bool,
+    pub vid: u16,
+}
+
+impl VlanTag {
+    /// Constructor to create an 802.1Q tag (TPID 0x8100), also the inner
+    /// customer tag of provider bridging.
+    pub fn new(pcp: u8, dei: bool, vid: u16) -> Self {
+        VlanTag {
+            tpid: ETHERTYPE_VLAN,
+            pcp,
+            dei,
+            vid,
+        }
+    }
+
+    /// Constructor to create an 802.1ad service tag (TPID 0x88a8), the
+    /// outer tag of provider bridging.
+    pub fn s_tag(pcp: u8, dei: bool, vid: u16) -> Self {
+        VlanTag::new(pcp, dei, vid).set_tpid(ETHERTYPE_QINQ)
+    }
+
+    /// Returns the tag control information.
+    pub fn get_tci(&self) -> Tci {
+        Tci::default()
+            .set_pcp(self.pcp)
+            .set_dei(self.dei as u8)
+            .set_vid(self.vid)
+    }
+
+    // --- SETTER METHODS ---
+
+    pub fn set_tpid(mut self, tpid: u16) -> Self {
+        self.tpid = tpid;
+        self
+    }
+
+    pub fn set_pcp(mut self, pcp: u8) -> Self {
+        self.pcp = pcp;
+        self
+    }
+
+    pub fn set_dei(mut self, dei: bool) -> Self {
+        self.dei = dei;
+        self
+    }
+
+    pub fn set_vid(mut self, vid: u16) -> Self {
+        self.vid = vid;
+        self
+    }
+
+    // --- SERIALIZATION ---
+
+    /// Serializes the tag: TPID then TCI. PCP and VID are truncated to
+    /// their 3 and 12 bits.
+    pub fn to_bytes(&self) -> [u8; TAG_LEN] {
+        let tpid = self.tpid.to_be_bytes();
+        let tci = self.get_tci().0;
+        [tpid[0], tpid[1], tci[0], tci[1]]
+    }
+
+    /// Parses a tag, whatever its TPID.
+    pub fn from_bytes(buf: &[u8]) -> Result<(Self, usize), ParseError> {
+        ensure_len(buf, TAG_LEN)?;
+        let tci = Tci([buf[2], buf[3]]);
+        let tag = VlanTag {
+            tpid: u16::from_be_bytes([buf[0], buf[1]]),
+            pcp: tci.get_pcp(),
+            dei: tci.get_dei() != 0,
+            vid: tci.get_vid(),
+        };
+        Ok((tag, TAG_LEN))
+    }
+}
+
+/// Error returned for an invalid VLAN range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VlanError {
+    /// VID outside `MIN_VID..=MAX_VID`.
+    ReservedVid(u16),
+    /// Range whose start is above its end.
+    EmptyRange { start: u16, end: u16 },
+}
+
+impl fmt::Display for VlanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VlanError::ReservedVid(vid) => write!(f, "VLAN ID {vid} is reserved"),
+            VlanError::EmptyRange { start, end } => {
+                write!(f, "empty VLAN range {start}-{end}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for VlanError {}
+
+/// Inclusive range of usable VLAN IDs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VlanRange {
+    start: u16,
+    end: u16,
+}
+
+impl VlanRange {
+    /// Constructor to create the range `start..=end`. Fails unless
+    /// `MIN_VID <= start <= end <= MAX_VID`.
+    pub fn new(start: u16, end: u16) -> Result<Self, VlanError> {
+        for vid in [start, end] {
+            if !(MIN_VID..=MAX_VID).contains(&vid) {
+                return Err(VlanError::ReservedVid(vid));
+            }
+        }
+        if start > end {
+            return Err(VlanError::EmptyRange { start, end });
+        }
+        Ok(VlanRange { start, end })
+    }
+
+    pub fn get_start(&self) -> u16 {
+        self.start
+    }
+
+    pub fn get_end(&self) -> u16 {
+        self.end
+    }
+
+    /// Number of VLAN IDs in the range.
+    pub fn len(&self) -> usize {
+        (self.end - self.start) as usize + 1
+    }
+
+    /// Always false: a range holds at least one VLAN ID.
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// VLAN IDs of the range, in increasing order.
+    pub fn iter(&self) -> impl Iterator<Item = u16> {
+        self.start..=self.end
+    }
+
+    /// Copies of the untagged Ethernet frame `inner`, one per VLAN ID of
+    /// the range, each with an 802.1Q tag of priority `pcp` inserted after
+    /// the source address.
+    pub fn generate_tagged_frames(&self, inner: &[u8], pcp: u8) -> impl Iterator<Item = Vec<u8>> {
+        let (addresses, rest) = inner.split_at(TAG_OFFSET.min(inner.len()));
+        self.iter().map(move |vid| {
+            let tci = Tci::default().set_pcp(pcp).set_vid(vid);
+            let mut frame = Vec::with_capacity(inner.len() + 4);
+            frame.extend_from_slice(addresses);
+            frame.extend_from_slice(&ETHERTYPE_VLAN.to_be_bytes());
+            frame.extend_from_slice(&tci.0);
+            frame.extend_from_slice(rest);
+            frame
+        })
+    }
+}
+
+/// Sends `inner` tagged with every VLAN ID of `range` (see
+/// `VlanRange::generate_tagged_frames`, priority 0) through `socket`,
+/// paced to `rate_pps` frames per second as `send_paced` does. Returns the
+/// number of frames sent.
+pub fn bulk_inject<S: RawSender + ?Sized>(
+    socket: &S,
+    inner: &[u8],
+    range: &VlanRange,
+    rate_pps: u64,
+) -> io::Result<u64> {
+    send_paced(socket, range.generate_tagged_frames(inner, 0), rate_pps, 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::transport::Loopback;
+
+    #[test]
+    fn bulk_inject_keeps_the_rate() {
+        let inner = [[0xff; 6], [0x02, 0, 0, 0, 0, 1]].concat();
+        let inner = [inner, vec![0x88, 0xb5], vec![0; 46]].concat();
+        let range = VlanRange::new(100, 299).unwrap();
+        let socket = Loopback::new();
+        let start = Instant::now();
+        assert_eq!(bulk_inject(&socket, &inner, &range, 10_000).unwrap(), 200);
+        // 199 intervals of 100 µs; sleeping never ends early.
+        assert!(start.elapsed() >= Duration::from_micros(19_900));
+
+        let frames = socket.drain();
+        assert_eq!(frames.len(), 200);
+        for (frame, vid) in frames.iter().zip(range.iter()) {
+            assert_eq!(frame.len(), inner.len() + 4);
+            assert_eq!(frame[12..14], ETHERTYPE_VLAN.to_be_bytes());
+            assert_eq!(Tci([frame[14], frame[15]]).get_vid(), vid);
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use std::net::Ipv4Addr;
+
+use crate::ethernet::{ETHERTYPE_IPV4, Ethernet, MacAddr};
+use crate::ipv4::IPv4;
+use crate::udp::{self, UDP};
+use crate::util::{ParseError, crc32c, ensure_len};
+
+// VXLAN header (RFC 7348 section 5)
+//
+//  0                   1                   2                   3
+//  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |R|R|R|R|I|R|R|R|            Reserved                           |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                VXLAN Network Identifier (VNI) |   Reserved    |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// followed by the inner Ethernet frame, without FCS.
+
+/// UDP port of VXLAN.
+pub const UDP_PORT: u16 = 4789;
+
+/// Length of the header in bytes.
+pub const HEADER_LEN: usize = 8;
+
+/// I flag: the VNI is valid. Always set by senders.
+pub const FLAG_VNI: u8 = 0x08;
+
+/// Header VXLAN, followed by the encapsulated frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Vxlan {
+    pub flags: u8,
+    /// Virtual network identifier (24 bits).
+    pub vni: u32,
+    pub payload: Vec<u8>,
+}
+
+impl Vxlan {
+    /// Builds a header with the I flag set carrying `payload` in `vni`.
+    pub fn new(vni: u32, payload: Vec<u8>) -> Self {
+        Vxlan {
+            flags: FLAG_VNI,
+            vni: vni & 0x00ff_ffff,
+            payload,
+        }
+    }
+
+    /// Encapsulates an Ethernet frame in `vni`.
+    pub fn over_ethernet(vni: u32, frame: &Ethernet) -> Self {
+        Vxlan::new(vni, frame.to_bytes())
+    }
+
+    /// Parses the encapsulated Ethernet frame.
+    pub fn inner_frame(&self) -> Result<Ethernet, ParseError> {
+        Ethernet::from_bytes(&self.payload)
+    }
+
+    // --- GETTER METHODS ---
+
+    pub fn get_flags(&self) -> u8 {
+        self.flags
+    }
+
+    pub fn get_vni(&self) -> u32 {
+        self.vni
+    }
+
+    pub fn get_payload(&self) -> &Vec<u8> {
+        &self.payload
+    }
+
+    // --- SETTER METHODS ---
+
+    pub fn set_flags(mut self, flags: u8) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Sets the VNI, keeping its low 24 bits.
+    pub fn set_vni(mut self, vni: u32) -> Self {
+        self.vni = vni & 0x00ff_ffff;
+        self
+    }
+
+    pub fn set_payload(mut self, payload: Vec<u8>) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    // --- SERIALIZATION ---
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.payload.len());
+        bytes.extend_from_slice(&[self.flags, 0, 0, 0]);
+        bytes.extend_from_slice(&(self.vni << 8).to_be_bytes());
+        bytes.extend_from_slice(&self.payload);
+        bytes
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, HEADER_LEN)?;
+        Ok(Vxlan {
+            flags: buf[0],
+            vni: u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]) >> 8,
+            payload: buf[HEADER_LEN..].to_vec(),
+        })
+    }
+}
+
+/// UDP source port for an inner frame. VXLAN uses the source port for
+/// ECMP entropy, so it is a hash of the inner Ethernet header within
+/// 0xc000..=0xffff, the same for every frame of a flow.
+fn source_port(inner: &Ethernet) -> u16 {
+    let mut header = Vec::with_capacity(14);
+    header.extend_from_slice(&inner.destination.octets());
+    header.extend_from_slice(&inner.source.octets());
+    header.extend_from_slice(&inner.ethertype.to_be_bytes());
+    0xc000 | (crc32c(&header) & 0x3fff) as u16
+}
+
+/// Builds the overlay packet carrying `inner` in `vni` between the tunnel
+/// endpoints `source` and `destination`: outer Ethernet, IPv4, UDP to
+/// port 4789 and VXLAN headers, with the lengths and IP checksum
+/// computed. The UDP checksum is left at zero, as RFC 7348 recommends
+/// over IPv4.
+pub fn encapsulate(
+    inner: &Ethernet,
+    vni: u32,
+    outer_source_mac: MacAddr,
+    outer_destination_mac: MacAddr,
+    source: Ipv4Addr,
+    destination: Ipv4Addr,
+) -> Ethernet {
+    let vxlan = Vxlan::over_ethernet(vni, inner);
+    let udp = UDP::new(source_port(inner), UDP_PORT, vxlan.to_bytes());
+    let ip = IPv4::with_payload(source, destination, udp::IP_PROTOCOL, udp.to_bytes());
+    Ethernet::new(
+        outer_destination_mac,
+        outer_source_mac,
+        ETHERTYPE_IPV4,
+        ip.to_bytes(),
+    )
+}
@@ -0,0 +1,457 @@
+// AF_XDP sockets (Linux only, `xdp` feature).
+//
+// The socket shares a UMEM, a memory area split into fixed-size frames,
+// with the kernel. Four single-producer single-consumer rings carry frame
+// addresses between the two:
+//
+//   FILL        user -> kernel  frames the kernel may receive into
+//   RX          kernel -> user  received frames (address and length)
+//   TX          user -> kernel  frames to transmit
+//   COMPLETION  kernel -> user  transmitted frames, free again
+//
+// Each ring is a producer index, a consumer index, a flags word and an
+// array of entries, mmap()ed from the socket. Indexes grow without bound
+// and are masked with the ring size (a power of two).
+//
+// Sending works on its own. Receiving needs an XDP program attached to
+// the interface that redirects the queue's packets to this socket through
+// an XSKMAP; loading one is left to the caller.
+
+use std::ffi::CString;
+use std::fmt;
+use std::io;
+use std::mem;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::ptr;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Size of a UMEM frame, and the largest frame that can be sent.
+pub const FRAME_SIZE: usize = 2048;
+
+/// Number of entries of each ring.
+pub const RING_SIZE: u32 = 2048;
+
+/// Error returned when sending or receiving a frame.
+#[derive(Debug)]
+pub enum XdpError {
+    Io(io::Error),
+    /// The frame does not fit in a UMEM frame.
+    FrameTooLarge {
+        len: usize,
+        max: usize,
+    },
+    /// Every TX frame is in flight; the kernel has not completed them yet.
+    TxFull,
+    /// No frame was received.
+    RxEmpty,
+}
+
+impl fmt::Display for XdpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            XdpError::Io(err) => err.fmt(f),
+            XdpError::FrameTooLarge { len, max } => {
+                write!(f, "frame of {len} bytes exceeds the {max}-byte UMEM frames")
+            }
+            XdpError::TxFull => write!(f, "no free TX frame"),
+            XdpError::RxEmpty => write!(f, "no frame received"),
+        }
+    }
+}
+
+impl std::error::Error for XdpError {}
+
+impl From<io::Error> for XdpError {
+    fn from(err: io::Error) -> Self {
+        XdpError::Io(err)
+    }
+}
+
+/// Shared memory mapping, unmapped on drop.
+#[derive(Debug)]
+struct Mmap {
+    addr: *mut u8,
+    len: usize,
+}
+
+impl Mmap {
+    fn new(len: usize, flags: libc::c_int, fd: RawFd, offset: u64) -> io::Result<Self> {
+        // SAFETY: a new mapping is requested; no existing memory is touched.
+        let addr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                flags | libc::MAP_POPULATE,
+                fd,
+                offset as libc::off_t,
+            )
+        };
+        if addr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Mmap {
+            addr: addr.cast(),
+            len,
+        })
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        // SAFETY: the mapping was created by `Mmap::new` and is not used
+        // after this point.
+        unsafe {
+            libc::munmap(self.addr.cast(), self.len);
+        }
+    }
+}
+
+/// One of the four rings, with entries of type `T`.
+#[derive(Debug)]
+struct Ring<T> {
+    /// Keeps the ring mapped.
+    _map: Mmap,
+    producer: *const AtomicU32,
+    consumer: *const AtomicU32,
+    flags: *const AtomicU32,
+    entries: *mut T,
+}
+
+impl<T: Copy> Ring<T> {
+    /// Maps the ring registered on `fd` at page offset `pgoff`.
+    fn map(fd: RawFd, offsets: &libc::xdp_ring_offset, pgoff: u64) -> io::Result<Self> {
+        let len = offsets.desc as usize + RING_SIZE as usize * mem::size_of::<T>();
+        let map = Mmap::new(len, libc::MAP_SHARED, fd, pgoff)?;
+        // SAFETY: the kernel placed the indexes, flags and entries at these
+        // offsets within the mapping.
+        unsafe {
+            Ok(Ring {
+                producer: map.addr.add(offsets.producer as usize).cast(),
+                consumer: map.addr.add(offsets.consumer as usize).cast(),
+                flags: map.addr.add(offsets.flags as usize).cast(),
+                entries: map.addr.add(offsets.desc as usize).cast(),
+                _map: map,
+            })
+        }
+    }
+
+    fn producer(&self) -> &AtomicU32 {
+        // SAFETY: the index lives as long as the mapping.
+        unsafe { &*self.producer }
+    }
+
+    fn consumer(&self) -> &AtomicU32 {
+        // SAFETY: the index lives as long as the mapping.
+        unsafe { &*self.consumer }
+    }
+
+    /// Returns true if the kernel must be woken up to process the ring.
+    fn needs_wakeup(&self) -> bool {
+        // SAFETY: the flags word lives as long as the mapping.
+        unsafe { &*self.flags }.load(Ordering::Relaxed) & libc::XDP_RING_NEED_WAKEUP != 0
+    }
+
+    /// Produces `entry`; returns false if the ring is full.
+    fn push(&mut self, entry: T) -> bool {
+        let producer = self.producer().load(Ordering::Relaxed);
+        let consumer = self.consumer().load(Ordering::Acquire);
+        if producer.wrapping_sub(consumer) >= RING_SIZE {
+            return false;
+        }
+        // SAFETY: the slot is within the ring and owned by the producer.
+        unsafe {
+            self.entries
+                .add((producer & (RING_SIZE - 1)) as usize)
+                .write_volatile(entry);
+        }
+        self.producer()
+            .store(producer.wrapping_add(1), Ordering::Release);
+        true
+    }
+
+    /// Consumes the next entry, if the kernel produced one.
+    fn pop(&mut self) -> Option<T> {
+        let consumer = self.consumer().load(Ordering::Relaxed);
+        let producer = self.producer().load(Ordering::Acquire);
+        if producer == consumer {
+            return None;
+        }
+        // SAFETY: the slot is within the ring and was published by the
+        // producer.
+        let entry = unsafe {
+            self.entries
+                .add((consumer & (RING_SIZE - 1)) as usize)
+                .read_volatile()
+        };
+        self.consumer()
+            .store(consumer.wrapping_add(1), Ordering::Release);
+        Some(entry)
+    }
+}
+
+/// AF_XDP socket bound to one queue of an interface, with its own UMEM.
+/// Half of the UMEM frames are given to the kernel for reception and half
+/// are kept for transmission.
+#[derive(Debug)]
+pub struct XdpSocket {
+    // Rings and UMEM are unmapped before the socket is closed.
+    fill: Ring<u64>,
+    completion: Ring<u64>,
+    rx: Ring<libc::xdp_desc>,
+    tx: Ring<libc::xdp_desc>,
+    umem: Mmap,
+    fd: OwnedFd,
+    zero_copy: bool,
+    /// UMEM addresses of the frames free for transmission.
+    free_frames: Vec<u64>,
+    /// Frame returned by the last `recv`, given back to the kernel on the
+    /// next call.
+    received: Option<u64>,
+}
+
+// SAFETY: the mappings are owned by the socket and only accessed through
+// `&mut self`, or `&self` for reads of kernel-owned indexes.
+unsafe impl Send for XdpSocket {}
+
+impl XdpSocket {
+    /// Constructor to create a socket on queue `queue_id` of `iface`, with a
+    /// UMEM of `umem_size` bytes (a multiple of `FRAME_SIZE`). Zero-copy
+    /// mode is used when the driver supports it, copy mode otherwise.
+    /// Requires CAP_NET_RAW.
+    pub fn new(iface: &str, queue_id: u32, umem_size: usize) -> Result<XdpSocket, io::Error> {
+        if umem_size < 2 * FRAME_SIZE || !umem_size.is_multiple_of(FRAME_SIZE) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("UMEM size must be a multiple of {FRAME_SIZE} of at least two frames"),
+            ));
+        }
+        let name = CString::new(iface)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid interface name"))?;
+        // SAFETY: `name` is a valid C string.
+        let ifindex = unsafe { libc::if_nametoindex(name.as_ptr()) };
+        if ifindex == 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // SAFETY: plain system call; the descriptor is owned right away.
+        let fd = unsafe { libc::socket(libc::AF_XDP, libc::SOCK_RAW | libc::SOCK_CLOEXEC, 0) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `fd` is a new descriptor owned by nobody else.
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        let raw = fd.as_raw_fd();
+
+        let umem = Mmap::new(umem_size, libc::MAP_PRIVATE | libc::MAP_ANONYMOUS, -1, 0)?;
+        // SAFETY: all-zero is a valid value of this plain C struct.
+        let mut reg: libc::xdp_umem_reg = unsafe { mem::zeroed() };
+        reg.addr = umem.addr as u64;
+        reg.len = umem_size as u64;
+        reg.chunk_size = FRAME_SIZE as u32;
+        set_option(raw, libc::XDP_UMEM_REG, &reg)?;
+        for ring in [
+            libc::XDP_UMEM_FILL_RING,
+            libc::XDP_UMEM_COMPLETION_RING,
+            libc::XDP_RX_RING,
+            libc::XDP_TX_RING,
+        ] {
+            set_option(raw, ring, &RING_SIZE)?;
+        }
+
+        // SAFETY: all-zero is a valid value of this plain C struct.
+        let mut offsets: libc::xdp_mmap_offsets = unsafe { mem::zeroed() };
+        let mut len = mem::size_of_val(&offsets) as libc::socklen_t;
+        // SAFETY: `offsets` is writable for `len` bytes.
+        let ret = unsafe {
+            libc::getsockopt(
+                raw,
+                libc::SOL_XDP,
+                libc::XDP_MMAP_OFFSETS,
+                (&raw mut offsets).cast(),
+                &mut len,
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut socket = XdpSocket {
+            fill: Ring::map(raw, &offsets.fr, libc::XDP_UMEM_PGOFF_FILL_RING)?,
+            completion: Ring::map(raw, &offsets.cr, libc::XDP_UMEM_PGOFF_COMPLETION_RING)?,
+            rx: Ring::map(raw, &offsets.rx, libc::XDP_PGOFF_RX_RING as u64)?,
+            tx: Ring::map(raw, &offsets.tx, libc::XDP_PGOFF_TX_RING as u64)?,
+            umem,
+            fd,
+            zero_copy: true,
+            free_frames: Vec::new(),
+            received: None,
+        };
+
+        let bind = |mode: u16| {
+            let address = libc::sockaddr_xdp {
+                sxdp_family: libc::AF_XDP as u16,
+                sxdp_flags: mode | libc::XDP_USE_NEED_WAKEUP,
+                sxdp_ifindex: ifindex,
+                sxdp_queue_id: queue_id,
+                sxdp_shared_umem_fd: 0,
+            };
+            // SAFETY: `address` is a valid sockaddr_xdp of the given length.
+            let ret = unsafe {
+                libc::bind(
+                    raw,
+                    (&raw const address).cast(),
+                    mem::size_of_val(&address) as libc::socklen_t,
+                )
+            };
+            match ret {
+                0 => Ok(()),
+                _ => Err(io::Error::last_os_error()),
+            }
+        };
+        if bind(libc::XDP_ZEROCOPY).is_err() {
+            bind(libc::XDP_COPY)?;
+            socket.zero_copy = false;
+        }
+
+        let frames = (umem_size / FRAME_SIZE) as u64;
+        let rx_frames = (frames / 2).min(RING_SIZE as u64);
+        for frame in 0..rx_frames {
+            socket.fill.push(frame * FRAME_SIZE as u64);
+        }
+        socket.free_frames = (rx_frames..frames)
+            .rev()
+            .map(|frame| frame * FRAME_SIZE as u64)
+            .collect();
+        Ok(socket)
+    }
+
+    /// Returns true if the driver runs the socket in zero-copy mode.
+    pub fn is_zero_copy(&self) -> bool {
+        self.zero_copy
+    }
+
+    /// Queues `frame` for transmission and wakes up the kernel if needed.
+    /// Does not wait: fails with `XdpError::TxFull` while all TX frames are
+    /// in flight.
+    pub fn send(&mut self, frame: &[u8]) -> Result<(), XdpError> {
+        if frame.len() > FRAME_SIZE {
+            return Err(XdpError::FrameTooLarge {
+                len: frame.len(),
+                max: FRAME_SIZE,
+            });
+        }
+        while let Some(addr) = self.completion.pop() {
+            self.free_frames.push(addr);
+        }
+        let Some(addr) = self.free_frames.pop() else {
+            self.wake_up_tx()?;
+            return Err(XdpError::TxFull);
+        };
+        // SAFETY: `addr` is the start of a UMEM frame the kernel does not
+        // own, and the frame fits in it.
+        unsafe {
+            ptr::copy_nonoverlapping(
+                frame.as_ptr(),
+                self.umem.addr.add(addr as usize),
+                frame.len(),
+            );
+        }
+        let desc = libc::xdp_desc {
+            addr,
+            len: frame.len() as u32,
+            options: 0,
+        };
+        if !self.tx.push(desc) {
+            self.free_frames.push(addr);
+            return Err(XdpError::TxFull);
+        }
+        self.wake_up_tx()
+    }
+
+    /// Returns the next received frame, which stays valid until the next
+    /// call. Does not wait: fails with `XdpError::RxEmpty` if nothing was
+    /// received.
+    pub fn recv(&mut self) -> Result<&[u8], XdpError> {
+        if let Some(addr) = self.received.take() {
+            self.fill.push(addr);
+        }
+        let Some(desc) = self.rx.pop() else {
+            if self.fill.needs_wakeup() {
+                // SAFETY: an empty receive only wakes up the driver.
+                let ret = unsafe {
+                    libc::recvfrom(
+                        self.fd.as_raw_fd(),
+                        ptr::null_mut(),
+                        0,
+                        libc::MSG_DONTWAIT,
+                        ptr::null_mut(),
+                        ptr::null_mut(),
+                    )
+                };
+                check_wakeup(ret)?;
+            }
+            return Err(XdpError::RxEmpty);
+        };
+        // The address may point past the start of the frame (headroom).
+        self.received = Some(desc.addr - desc.addr % FRAME_SIZE as u64);
+        // SAFETY: the kernel wrote `len` bytes at `addr` within the UMEM and
+        // leaves the frame alone until it is put back in the FILL ring.
+        Ok(unsafe {
+            std::slice::from_raw_parts(self.umem.addr.add(desc.addr as usize), desc.len as usize)
+        })
+    }
+
+    fn wake_up_tx(&self) -> Result<(), XdpError> {
+        if !self.tx.needs_wakeup() {
+            return Ok(());
+        }
+        // SAFETY: an empty send only asks the kernel to process the TX ring.
+        let ret = unsafe {
+            libc::sendto(
+                self.fd.as_raw_fd(),
+                ptr::null(),
+                0,
+                libc::MSG_DONTWAIT,
+                ptr::null(),
+                0,
+            )
+        };
+        check_wakeup(ret)
+    }
+}
+
+impl AsRawFd for XdpSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+fn set_option<T>(fd: RawFd, name: libc::c_int, value: &T) -> io::Result<()> {
+    // SAFETY: `value` is readable for its size.
+    let ret = unsafe {
+        libc::setsockopt(
+            fd,
+            libc::SOL_XDP,
+            name,
+            (value as *const T).cast(),
+            mem::size_of::<T>() as libc::socklen_t,
+        )
+    };
+    match ret {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+/// Result of a wakeup system call; the errors meaning the kernel is busy
+/// are not failures.
+fn check_wakeup(ret: isize) -> Result<(), XdpError> {
+    if ret >= 0 {
+        return Ok(());
+    }
+    let err = io::Error::last_os_error();
+    match err.raw_os_error() {
+        Some(libc::EAGAIN | libc::EBUSY | libc::ENOBUFS | libc::ENETDOWN) => Ok(()),
+        _ => Err(err.into()),
+    }
+}
@@ -0,0 +1,279 @@
+use crate::util::{ParseError, ensure_len};
+
+// NWK frame (ZigBee specification 3.3.1), carried in an IEEE 802.15.4 MAC
+// data frame. Multi-byte fields are little-endian.
+//
+// +---------------+-------------+--------+--------+----------------+
+// | Frame Control | Destination | Source | Radius | Sequence Number|
+// |   2 bytes     |   2 bytes   |2 bytes | 1 byte |     1 byte     |
+// +---------------+-------------+--------+--------+----------------+
+// | Destination IEEE | Source IEEE | Multicast | Source Route      |
+// |  0/8 bytes       |  0/8 bytes  | 0/1 byte  | Subframe          |
+// +------------------+-------------+-----------+-------------------+
+// | Payload (auxiliary security header and MIC included when      |
+// | security is enabled)                                          |
+// +---------------------------------------------------------------+
+//
+// Frame control bits: 0-1 frame type, 2-5 protocol version, 6-7 discover
+// route, 8 multicast, 9 security, 10 source route, 11 destination IEEE
+// address, 12 source IEEE address, 13 end device initiator.
+
+// Frame types.
+pub const FRAME_TYPE_DATA: u8 = 0;
+pub const FRAME_TYPE_COMMAND: u8 = 1;
+pub const FRAME_TYPE_INTER_PAN: u8 = 3;
+
+/// Protocol version of ZigBee 2007 and ZigBee PRO.
+pub const PROTOCOL_VERSION_PRO: u8 = 2;
+
+// Discover route values.
+pub const DISCOVER_ROUTE_SUPPRESS: u8 = 0;
+pub const DISCOVER_ROUTE_ENABLE: u8 = 1;
+
+/// Broadcast address reaching all devices.
+pub const BROADCAST_ALL: u16 = 0xffff;
+/// Broadcast address reaching routers and the coordinator.
+pub const BROADCAST_ROUTERS: u16 = 0xfffc;
+
+/// Default radius (twice the default maximum depth).
+pub const DEFAULT_RADIUS: u8 = 30;
+
+/// Frame control field of an NWK frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ZigbeeNwkFrameControl {
+    pub frame_type: u8,
+    pub protocol_version: u8,
+    pub discover_route: u8,
+    pub multicast: bool,
+    pub security: bool,
+    pub source_route: bool,
+    pub dst_ieee_present: bool,
+    pub src_ieee_present: bool,
+    pub end_device_initiator: bool,
+}
+
+impl ZigbeeNwkFrameControl {
+    /// Frame control of a ZigBee PRO frame of `frame_type` with route
+    /// discovery enabled.
+    pub fn new(frame_type: u8) -> Self {
+        ZigbeeNwkFrameControl {
+            frame_type,
+            protocol_version: PROTOCOL_VERSION_PRO,
+            discover_route: DISCOVER_ROUTE_ENABLE,
+            ..Default::default()
+        }
+    }
+
+    pub fn to_u16(&self) -> u16 {
+        (self.frame_type as u16 & 0x3)
+            | (self.protocol_version as u16 & 0xf) << 2
+            | (self.discover_route as u16 & 0x3) << 6
+            | (self.multicast as u16) << 8
+            | (self.security as u16) << 9
+            | (self.source_route as u16) << 10
+            | (self.dst_ieee_present as u16) << 11
+            | (self.src_ieee_present as u16) << 12
+            | (self.end_device_initiator as u16) << 13
+    }
+
+    pub fn from_u16(value: u16) -> Self {
+        ZigbeeNwkFrameControl {
+            frame_type: (value & 0x3) as u8,
+            protocol_version: ((value >> 2) & 0xf) as u8,
+            discover_route: ((value >> 6) & 0x3) as u8,
+            multicast: value & (1 << 8) != 0,
+            security: value & (1 << 9) != 0,
+            source_route: value & (1 << 10) != 0,
+            dst_ieee_present: value & (1 << 11) != 0,
+            src_ieee_present: value & (1 << 12) != 0,
+            end_device_initiator: value & (1 << 13) != 0,
+        }
+    }
+}
+
+/// Source route subframe: the relays between the source and the
+/// destination, listed from the destination side.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ZigbeeSourceRoute {
+    /// Index in `relays` of the next relay.
+    pub relay_index: u8,
+    pub relays: Vec<u16>,
+}
+
+/// ZigBee NWK frame.
+///
+/// The presence bits of the frame control are derived from the optional
+/// fields when serializing. Security processing (AES-CCM*) is out of scope:
+/// with `security` set, the payload holds the auxiliary header, the
+/// encrypted data and the MIC as they are.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZigbeeNwk {
+    pub frame_control: ZigbeeNwkFrameControl,
+    pub destination: u16,
+    pub source: u16,
+    pub radius: u8,
+    pub sequence_number: u8,
+    pub dst_ieee: Option<u64>,
+    pub src_ieee: Option<u64>,
+    pub mcast_control: Option<u8>,
+    pub source_route: Option<ZigbeeSourceRoute>,
+    pub payload: Vec<u8>,
+}
+
+impl ZigbeeNwk {
+    /// Constructor to create a data frame between two short addresses.
+    pub fn new(destination: u16, source: u16, sequence_number: u8, payload: Vec<u8>) -> Self {
+        ZigbeeNwk {
+            frame_control: ZigbeeNwkFrameControl::new(FRAME_TYPE_DATA),
+            destination,
+            source,
+            radius: DEFAULT_RADIUS,
+            sequence_number,
+            dst_ieee: None,
+            src_ieee: None,
+            mcast_control: None,
+            source_route: None,
+            payload,
+        }
+    }
+
+    /// Returns true if the frame is secured at the NWK layer.
+    pub fn security_enabled(&self) -> bool {
+        self.frame_control.security
+    }
+
+    // --- SETTER METHODS ---
+
+    pub fn set_frame_control(mut self, frame_control: ZigbeeNwkFrameControl) -> Self {
+        self.frame_control = frame_control;
+        self
+    }
+
+    pub fn set_radius(mut self, radius: u8) -> Self {
+        self.radius = radius;
+        self
+    }
+
+    pub fn set_security_enabled(mut self, security: bool) -> Self {
+        self.frame_control.security = security;
+        self
+    }
+
+    pub fn set_dst_ieee(mut self, dst_ieee: Option<u64>) -> Self {
+        self.dst_ieee = dst_ieee;
+        self
+    }
+
+    pub fn set_src_ieee(mut self, src_ieee: Option<u64>) -> Self {
+        self.src_ieee = src_ieee;
+        self
+    }
+
+    pub fn set_mcast_control(mut self, mcast_control: Option<u8>) -> Self {
+        self.mcast_control = mcast_control;
+        self
+    }
+
+    pub fn set_source_route(mut self, source_route: Option<ZigbeeSourceRoute>) -> Self {
+        self.source_route = source_route;
+        self
+    }
+
+    pub fn set_payload(mut self, payload: Vec<u8>) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    // --- SERIALIZATION ---
+
+    pub fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        let frame_control = ZigbeeNwkFrameControl {
+            multicast: self.mcast_control.is_some(),
+            source_route: self.source_route.is_some(),
+            dst_ieee_present: self.dst_ieee.is_some(),
+            src_ieee_present: self.src_ieee.is_some(),
+            ..self.frame_control
+        };
+        bytes.extend_from_slice(&frame_control.to_u16().to_le_bytes());
+        bytes.extend_from_slice(&self.destination.to_le_bytes());
+        bytes.extend_from_slice(&self.source.to_le_bytes());
+        bytes.push(self.radius);
+        bytes.push(self.sequence_number);
+        for address in [self.dst_ieee, self.src_ieee].into_iter().flatten() {
+            bytes.extend_from_slice(&address.to_le_bytes());
+        }
+        if let Some(mcast_control) = self.mcast_control {
+            bytes.push(mcast_control);
+        }
+        if let Some(route) = &self.source_route {
+            bytes.push(route.relays.len() as u8);
+            bytes.push(route.relay_index);
+            for relay in &route.relays {
+                bytes.extend_from_slice(&relay.to_le_bytes());
+            }
+        }
+        bytes.extend_from_slice(&self.payload);
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(8 + self.payload.len());
+        self.serialize_into(&mut bytes);
+        bytes
+    }
+
+    /// Parses an NWK frame from the payload of an 802.15.4 data frame.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, ParseError> {
+        ensure_len(buf, 8)?;
+        let frame_control = ZigbeeNwkFrameControl::from_u16(u16::from_le_bytes([buf[0], buf[1]]));
+        let mut offset = 8;
+        let mut read_ieee = |present: bool| -> Result<Option<u64>, ParseError> {
+            if !present {
+                return Ok(None);
+            }
+            ensure_len(buf, offset + 8)?;
+            let mut octets = [0u8; 8];
+            octets.copy_from_slice(&buf[offset..offset + 8]);
+            offset += 8;
+            Ok(Some(u64::from_le_bytes(octets)))
+        };
+        let dst_ieee = read_ieee(frame_control.dst_ieee_present)?;
+        let src_ieee = read_ieee(frame_control.src_ieee_present)?;
+        let mcast_control = if frame_control.multicast {
+            ensure_len(buf, offset + 1)?;
+            offset += 1;
+            Some(buf[offset - 1])
+        } else {
+            None
+        };
+        let source_route = if frame_control.source_route {
+            ensure_len(buf, offset + 2)?;
+            let count = buf[offset] as usize;
+            let relay_index = buf[offset + 1];
+            offset += 2;
+            ensure_len(buf, offset + count * 2)?;
+            let relays = buf[offset..offset + count * 2]
+                .chunks_exact(2)
+                .map(|relay| u16::from_le_bytes([relay[0], relay[1]]))
+                .collect();
+            offset += count * 2;
+            Some(ZigbeeSourceRoute {
+                relay_index,
+                relays,
+            })
+        } else {
+            None
+        };
+        Ok(ZigbeeNwk {
+            frame_control,
+            destination: u16::from_le_bytes([buf[2], buf[3]]),
+            source: u16::from_le_bytes([buf[4], buf[5]]),
+            radius: buf[6],
+            sequence_number: buf[7],
+            dst_ieee,
+            src_ieee,
+            mcast_control,
+            source_route,
+            payload: buf[offset..].to_vec(),
+        })
+    }
+}